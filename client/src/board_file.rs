@@ -0,0 +1,237 @@
+//! Boards saved to a file so they can be carried over to another campaign or
+//! server. Images are kept as the URLs the pieces already use
+
+use std::collections::HashMap;
+
+use common::{
+    message::BoardMessage, Annotation, AnnotationShape, DndPlayerPiece, GridSettings, Wall,
+};
+use emath::Pos2;
+use uuid::Uuid;
+
+use crate::state::board::BoardState;
+
+/// Bumped whenever the file layout changes, with a step added to `migrate`
+/// to bring older files up to date
+pub const BOARD_FILE_VERSION: u64 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct BoardFile {
+    pub version: u64,
+    pub name: String,
+    /// The grid the board was laid out on. Everything is scaled to the
+    /// importing campaign's grid so tokens still fill a square
+    pub grid: GridSettings,
+    /// Keyed by their old ids so groups can be put back together
+    pub pieces: HashMap<Uuid, DndPlayerPiece>,
+    pub annotations: Vec<Annotation>,
+    pub walls: Vec<Wall>,
+    pub groups: Vec<Vec<Uuid>>,
+}
+
+impl BoardFile {
+    /// Portals are left out since the boards they lead to won't exist elsewhere
+    pub fn from_board(state: &BoardState, board: Uuid) -> Option<Self> {
+        let info = state.boards.get(&board)?;
+
+        let pieces = state
+            .players
+            .iter()
+            .filter(|(_, x)| x.board == board)
+            .map(|(id, x)| (*id, x.to_common()))
+            .collect::<HashMap<_, _>>();
+
+        let groups = state
+            .groups
+            .0
+            .values()
+            .filter(|x| x.iter().all(|id| pieces.contains_key(id)))
+            .cloned()
+            .collect();
+
+        Some(Self {
+            version: BOARD_FILE_VERSION,
+            name: info.name.clone(),
+            grid: state.grid,
+            pieces,
+            annotations: state
+                .annotations
+                .values()
+                .filter(|x| x.board == board)
+                .cloned()
+                .collect(),
+            walls: info.walls.values().cloned().collect(),
+            groups,
+        })
+    }
+
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let file: Self = serde_json::from_value(migrate(value)?).map_err(|e| e.to_string())?;
+
+        // Everything is divided by the spacing when it's fitted to a new grid
+        if !(file.grid.spacing.is_finite() && file.grid.spacing > 0.0) {
+            return Err(format!(
+                "The board's grid spacing has to be above 0, got {}",
+                file.grid.spacing
+            ));
+        }
+        Ok(file)
+    }
+
+    /// Everything needed to recreate the board under a new id, fitted to `grid`.
+    /// Kept at its own size if either grid has no usable spacing
+    pub fn into_messages(self, grid: &GridSettings) -> Vec<BoardMessage> {
+        let board = Uuid::new_v4();
+        let scale = Some(grid.spacing / self.grid.spacing)
+            .filter(|x| x.is_finite() && *x > 0.0)
+            .unwrap_or(1.0);
+        let at = |pos: Pos2| (pos.to_vec2() * scale).to_pos2();
+
+        let mut messages = vec![BoardMessage::CreateBoard(board, self.name)];
+
+        let ids = self
+            .pieces
+            .keys()
+            .map(|old| (*old, Uuid::new_v4()))
+            .collect::<HashMap<_, _>>();
+        for (old, mut piece) in self.pieces {
+            piece.board = board;
+            piece.position = at(piece.position);
+            piece.size *= scale;
+            messages.push(BoardMessage::AddPlayerPiece(ids[&old], piece));
+        }
+
+        for mut annotation in self.annotations {
+            annotation.board = board;
+            annotation.shape = scale_shape(annotation.shape, scale);
+            annotation.width *= scale;
+            messages.push(BoardMessage::AddAnnotation(Uuid::new_v4(), annotation));
+        }
+
+        for mut wall in self.walls {
+            wall.start = at(wall.start);
+            wall.end = at(wall.end);
+            messages.push(BoardMessage::AddWall(board, Uuid::new_v4(), wall));
+        }
+
+        for group in self.groups {
+            let pieces = group.iter().filter_map(|x| ids.get(x)).copied().collect();
+            messages.push(BoardMessage::GroupPieces(Uuid::new_v4(), pieces));
+        }
+
+        messages
+    }
+}
+
+fn scale_shape(shape: AnnotationShape, scale: f32) -> AnnotationShape {
+    let at = |pos: Pos2| (pos.to_vec2() * scale).to_pos2();
+    match shape {
+        AnnotationShape::Pen(points) => AnnotationShape::Pen(points.into_iter().map(at).collect()),
+        AnnotationShape::Line(a, b) => AnnotationShape::Line(at(a), at(b)),
+        AnnotationShape::Rect(a, b) => AnnotationShape::Rect(at(a), at(b)),
+        AnnotationShape::Ellipse(a, b) => AnnotationShape::Ellipse(at(a), at(b)),
+        AnnotationShape::Text(pos, text) => AnnotationShape::Text(at(pos), text),
+        AnnotationShape::Sphere(center, radius) => {
+            AnnotationShape::Sphere(at(center), radius * scale)
+        }
+        AnnotationShape::Cone(apex, edge) => AnnotationShape::Cone(at(apex), at(edge)),
+    }
+}
+
+/// Brings a file from any earlier version up to `BOARD_FILE_VERSION`. Files
+/// without a version are treated as the first
+fn migrate(mut value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let version = value.get("version").and_then(|x| x.as_u64()).unwrap_or(1);
+    if version > BOARD_FILE_VERSION {
+        return Err(format!(
+            "The board was exported by a newer version of the app (file version {version})"
+        ));
+    }
+
+    // Each layout change is applied on top of the ones before it, ie.
+    // `if version < 2 { rename_pieces(&mut value) }`. Fields added to pieces
+    // and the like don't need a step since they're read with their defaults
+    value["version"] = BOARD_FILE_VERSION.into();
+    Ok(value)
+}
+
+/// File name the board is exported to in the working directory
+pub fn file_name(board: &str) -> String {
+    let name = board
+        .chars()
+        .map(|x| if x.is_alphanumeric() { x } else { '_' })
+        .collect::<String>();
+    format!("{name}.board.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{message::BoardMessage, GridSettings, PieceVisibility};
+    use uuid::Uuid;
+
+    use super::{BoardFile, BOARD_FILE_VERSION};
+
+    /// Exported by the first release with board files
+    const BOARD_V1: &str = include_str!("../tests/fixtures/board_file_v1.json");
+
+    #[test]
+    fn version_1_files_still_load() {
+        let file = BoardFile::parse(BOARD_V1).unwrap();
+
+        assert_eq!(file.version, BOARD_FILE_VERSION);
+        assert_eq!(file.name, "Goblin cave");
+        assert_eq!(file.pieces.len(), 2);
+        assert!(file
+            .pieces
+            .values()
+            .all(|x| x.visibility == PieceVisibility::GmOnly));
+        assert_eq!(file.annotations.len(), 1);
+        assert_eq!(file.walls.len(), 2);
+        assert_eq!(
+            file.groups,
+            vec![vec![Uuid::from_u128(1), Uuid::from_u128(2)]]
+        );
+    }
+
+    #[test]
+    fn imports_are_fitted_to_the_grid() {
+        let file = BoardFile::parse(BOARD_V1).unwrap();
+        // The file was laid out on a grid half the size
+        let grid = GridSettings {
+            spacing: 0.1,
+            ..Default::default()
+        };
+
+        let messages = file.into_messages(&grid);
+        let mut pieces = Vec::new();
+        let mut groups = Vec::new();
+        for msg in messages {
+            match msg {
+                BoardMessage::AddPlayerPiece(id, piece) => pieces.push((id, piece)),
+                BoardMessage::GroupPieces(_, group) => groups.push(group),
+                _ => {}
+            }
+        }
+
+        let (id, goblin) = pieces.iter().find(|(_, x)| x.name == "Goblin 1").unwrap();
+        assert!((goblin.position.x - 0.6).abs() < 1e-6);
+        assert!((goblin.size.x - 0.1).abs() < 1e-6);
+        // Groups point at the pieces' new ids
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].contains(id));
+    }
+
+    #[test]
+    fn files_without_a_grid_spacing_are_refused() {
+        let mut value: serde_json::Value = serde_json::from_str(BOARD_V1).unwrap();
+        value["grid"]["spacing"] = 0.0.into();
+        assert!(BoardFile::parse(&value.to_string()).is_err());
+    }
+
+    #[test]
+    fn files_from_newer_versions_are_refused() {
+        let json = format!(r#"{{ "version": {} }}"#, BOARD_FILE_VERSION + 1);
+        assert!(BoardFile::parse(&json).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use common::{AbilityDefinition, Handout, HandoutVisibility, ItemDefinition};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::state::encounter::EncounterMonster;
+
+const SRD_ROOT: &str = "https://www.dnd5eapi.co";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SrdCategory {
+    Spells,
+    Monsters,
+    Equipment,
+}
+
+impl SrdCategory {
+    pub const ALL: [SrdCategory; 3] = [Self::Spells, Self::Monsters, Self::Equipment];
+
+    fn endpoint(&self) -> &'static str {
+        match self {
+            Self::Spells => "spells",
+            Self::Monsters => "monsters",
+            Self::Equipment => "equipment",
+        }
+    }
+}
+
+impl std::fmt::Display for SrdCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Spells => write!(f, "Spells"),
+            Self::Monsters => write!(f, "Monsters"),
+            Self::Equipment => write!(f, "Equipment"),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SrdEntry {
+    pub index: String,
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct SrdIndex {
+    results: Vec<SrdEntry>,
+}
+
+pub enum Fetch<T> {
+    Loading,
+    Done(Result<T, String>),
+}
+
+enum SrdResponse {
+    Index(SrdCategory, Result<Vec<SrdEntry>, String>),
+    Details(String, Result<Value, String>),
+}
+
+/// Fetches from the SRD API on background threads, caching everything it has already seen
+pub struct SrdClient {
+    indexes: HashMap<SrdCategory, Fetch<Vec<SrdEntry>>>,
+    details: HashMap<String, Fetch<Value>>,
+    tx: Sender<SrdResponse>,
+    rx: Receiver<SrdResponse>,
+}
+
+impl Default for SrdClient {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            indexes: HashMap::new(),
+            details: HashMap::new(),
+            tx,
+            rx,
+        }
+    }
+}
+
+fn get_json<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    ureq::get(&format!("{}{}", SRD_ROOT, path))
+        .call()
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| e.to_string())
+}
+
+impl SrdClient {
+    /// Drains any finished requests. Should be called once per frame
+    pub fn poll(&mut self) {
+        while let Ok(response) = self.rx.try_recv() {
+            match response {
+                SrdResponse::Index(category, result) => {
+                    self.indexes.insert(category, Fetch::Done(result));
+                }
+                SrdResponse::Details(url, result) => {
+                    self.details.insert(url, Fetch::Done(result));
+                }
+            }
+        }
+    }
+
+    pub fn index(&mut self, category: SrdCategory, ctx: &egui::Context) -> &Fetch<Vec<SrdEntry>> {
+        let tx = self.tx.clone();
+        self.indexes.entry(category).or_insert_with(|| {
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let path = format!("/api/2014/{}", category.endpoint());
+                let result = get_json::<SrdIndex>(&path).map(|x| x.results);
+                let _ = tx.send(SrdResponse::Index(category, result));
+                ctx.request_repaint();
+            });
+            Fetch::Loading
+        })
+    }
+
+    pub fn details(&mut self, entry: &SrdEntry, ctx: &egui::Context) -> &Fetch<Value> {
+        let tx = self.tx.clone();
+        self.details.entry(entry.url.clone()).or_insert_with(|| {
+            let ctx = ctx.clone();
+            let url = entry.url.clone();
+            thread::spawn(move || {
+                let result = get_json(&url);
+                let _ = tx.send(SrdResponse::Details(url, result));
+                ctx.request_repaint();
+            });
+            Fetch::Loading
+        })
+    }
+
+    /// Forgets a failed request so it will be tried again
+    pub fn retry(&mut self, category: SrdCategory) {
+        if matches!(self.indexes.get(&category), Some(Fetch::Done(Err(_)))) {
+            self.indexes.remove(&category);
+        }
+    }
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value[key].as_str().unwrap_or_default()
+}
+
+/// The API stores most descriptions as a list of paragraphs
+fn paragraphs(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => String::new(),
+    }
+}
+
+pub fn spell_to_ability(spell: &Value) -> AbilityDefinition {
+    let casting_time = str_field(spell, "casting_time");
+    let ability_type = match casting_time {
+        "1 action" => "Action",
+        "1 bonus action" => "Bonus Action",
+        "1 reaction" => "Reaction",
+        _ => "Other",
+    };
+
+    let level = spell["level"].as_i64().unwrap_or_default();
+    let school = str_field(&spell["school"], "name");
+    let flavor_text = if level == 0 {
+        format!("{} cantrip", school)
+    } else {
+        format!("Level {} {}", level, school.to_lowercase())
+    };
+
+    let mut description = paragraphs(&spell["desc"]);
+    let higher_level = paragraphs(&spell["higher_level"]);
+    if !higher_level.is_empty() {
+        description = format!("{}\n\n*At Higher Levels.* {}", description, higher_level);
+    }
+
+    let components = spell["components"]
+        .as_array()
+        .map(|c| {
+            c.iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+    let mut notes = format!(
+        "Casting time: {}\nRange: {}\nComponents: {}\nDuration: {}",
+        casting_time,
+        str_field(spell, "range"),
+        components,
+        str_field(spell, "duration"),
+    );
+    if spell["concentration"].as_bool().unwrap_or_default() {
+        notes.push_str("\nConcentration");
+    }
+
+    // Cantrips scale with character level, everything else with slot level
+    let damage = &spell["damage"];
+    let damage = [
+        &damage["damage_at_character_level"],
+        &damage["damage_at_slot_level"],
+    ]
+    .into_iter()
+    .filter_map(Value::as_object)
+    .flat_map(|x| x.values())
+    .find_map(|x| x.as_str()?.parse().ok());
+
+    AbilityDefinition {
+        name: str_field(spell, "name").to_owned(),
+        description,
+        notes: Some(notes),
+        ability_type: ability_type.to_owned(),
+        flavor_text: Some(flavor_text),
+        resource: if level == 0 { "None" } else { "PowerSlot" }.to_owned(),
+        max_count: 0,
+        // The spellcasting stat depends on the class so leave the attack roll to the player
+        to_hit: None,
+        damage,
+        recharge: None,
+    }
+}
+
+pub fn equipment_to_item(equipment: &Value) -> ItemDefinition {
+    let mut description = paragraphs(&equipment["desc"]);
+
+    let category = str_field(&equipment["equipment_category"], "name");
+    let cost = &equipment["cost"];
+    let summary = format!(
+        "{}, {} {}",
+        category,
+        cost["quantity"].as_i64().unwrap_or_default(),
+        str_field(cost, "unit")
+    );
+    description = if description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", summary, description)
+    };
+
+    let damage = &equipment["damage"];
+    if !damage.is_null() {
+        description.push_str(&format!(
+            "\n\nDamage: {} {}",
+            str_field(damage, "damage_dice"),
+            str_field(&damage["damage_type"], "name").to_lowercase()
+        ));
+    }
+
+    let armor_class = equipment["armor_class"]["base"]
+        .as_i64()
+        .map(|ac| ac as i16);
+
+    // Magic items only mention it in their description
+    let requires_attunement = description.to_lowercase().contains("requires attunement");
+
+    ItemDefinition {
+        name: str_field(equipment, "name").to_owned(),
+        description,
+        flavor_text: String::new(),
+        quest_item: false,
+        armor_class,
+        attack_bonus: None,
+        requires_attunement,
+        weight: equipment["weight"]
+            .as_f64()
+            .filter(|x| *x > 0.0)
+            .map(|x| x as f32),
+    }
+}
+
+fn modifier(score: i64) -> String {
+    format!("{} ({:+})", score, (score - 10).div_euclid(2))
+}
+
+pub fn monster_to_encounter(monster: &Value) -> EncounterMonster {
+    let size = match str_field(monster, "size") {
+        "Tiny" => 0.5,
+        "Large" => 2.0,
+        "Huge" => 3.0,
+        "Gargantuan" => 4.0,
+        _ => 1.0,
+    };
+
+    EncounterMonster {
+        name: str_field(monster, "name").to_owned(),
+        challenge: monster["challenge_rating"].as_f64().unwrap_or_default() as f32,
+        count: 1,
+        size,
+        dex: monster["dexterity"].as_i64().unwrap_or(10) as i16,
+        image_url: monster["image"]
+            .as_str()
+            .map(|path| format!("{}{}", SRD_ROOT, path)),
+    }
+}
+
+/// There are no NPC entities yet, so monsters become a hidden stat block handout for the GM
+pub fn monster_to_handout(monster: &Value) -> Handout {
+    let mut body = format!(
+        "/{} {}, {}/\n\n",
+        str_field(monster, "size"),
+        str_field(monster, "type"),
+        str_field(monster, "alignment")
+    );
+
+    let ac = monster["armor_class"][0]["value"]
+        .as_i64()
+        .unwrap_or_default();
+    body.push_str(&format!(
+        "*Armor Class* {}\n*Hit Points* {} ({})\n*Challenge* {}\n\n",
+        ac,
+        monster["hit_points"].as_i64().unwrap_or_default(),
+        str_field(monster, "hit_points_roll"),
+        monster["challenge_rating"].as_f64().unwrap_or_default()
+    ));
+
+    let stats = [
+        "strength",
+        "dexterity",
+        "constitution",
+        "intelligence",
+        "wisdom",
+        "charisma",
+    ]
+    .map(|stat| {
+        let score = monster[stat].as_i64().unwrap_or(10);
+        format!("*{}* {}", stat[..3].to_uppercase(), modifier(score))
+    });
+    body.push_str(&stats.join(" | "));
+    body.push_str("\n\n");
+
+    for (heading, key) in [
+        ("Traits", "special_abilities"),
+        ("Actions", "actions"),
+        ("Legendary Actions", "legendary_actions"),
+    ] {
+        let Some(entries) = monster[key].as_array().filter(|x| !x.is_empty()) else {
+            continue;
+        };
+        body.push_str(&format!("# {}\n\n", heading));
+        for entry in entries {
+            body.push_str(&format!(
+                "*{}.* {}\n\n",
+                str_field(entry, "name"),
+                str_field(entry, "desc")
+            ));
+        }
+    }
+
+    let image_url = monster["image"]
+        .as_str()
+        .map(|path| format!("{}{}", SRD_ROOT, path));
+
+    Handout {
+        title: str_field(monster, "name").to_owned(),
+        body,
+        image_url,
+        visibility: HandoutVisibility::Hidden,
+    }
+}
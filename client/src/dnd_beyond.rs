@@ -0,0 +1,708 @@
+use common::{ruleset::Ruleset, Ability, Character, Item};
+use serde_json::Value;
+
+use crate::state::character::CharacterState;
+
+/// Score names in the order D&D Beyond numbers them, starting at 1
+const STATS: [&str; 6] = [
+    "strength",
+    "dexterity",
+    "constitution",
+    "intelligence",
+    "wisdom",
+    "charisma",
+];
+
+/// ddb-importer keeps Foundry's skill abbreviations
+const FOUNDRY_SKILLS: [(&str, &str); 18] = [
+    ("acr", "Acrobatics"),
+    ("ani", "Animal Handling"),
+    ("arc", "Arcana"),
+    ("ath", "Athletics"),
+    ("dec", "Deception"),
+    ("his", "History"),
+    ("ins", "Insight"),
+    ("itm", "Intimidation"),
+    ("inv", "Investigation"),
+    ("med", "Medicine"),
+    ("nat", "Nature"),
+    ("prc", "Perception"),
+    ("prf", "Performance"),
+    ("per", "Persuasion"),
+    ("rel", "Religion"),
+    ("slt", "Sleight of Hand"),
+    ("ste", "Stealth"),
+    ("sur", "Survival"),
+];
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value[key].as_str().unwrap_or_default()
+}
+
+fn int_field(value: &Value, key: &str) -> i64 {
+    value[key].as_i64().unwrap_or_default()
+}
+
+/// Both formats store descriptions as HTML, the sheet only shows plain text
+fn strip_html(html: &str) -> String {
+    let mut text = String::new();
+    let mut tag = None;
+    for c in html.chars() {
+        match (&mut tag, c) {
+            (None, '<') => tag = Some(String::new()),
+            (None, c) => text.push(c),
+            (Some(name), '>') => {
+                let name = name.trim_start_matches('/').to_lowercase();
+                if name.starts_with("br") || name == "p" || name == "li" {
+                    text.push('\n');
+                }
+                tag = None;
+            }
+            (Some(name), c) => name.push(c),
+        }
+    }
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&rsquo;", "'")
+        .replace("&lsquo;", "'")
+        .replace("&ldquo;", "\"")
+        .replace("&rdquo;", "\"")
+        .replace("&mdash;", "-")
+        .replace("&ndash;", "-")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+
+    text.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The ruleset's name for a skill, skills it doesn't have are dropped
+fn ruleset_skill(ruleset: &Ruleset, name: &str) -> Option<String> {
+    ruleset
+        .skills
+        .iter()
+        .find(|x| x.name.eq_ignore_ascii_case(name))
+        .map(|x| x.name.clone())
+}
+
+fn add_item(items: &mut Vec<Item>, item: Item) {
+    // Stacks of the same item are listed separately, the inventory keeps one row per item
+    match items.iter_mut().find(|x| x.name == item.name) {
+        Some(existing) => existing.count += item.count,
+        None => items.push(item),
+    }
+}
+
+fn add_ability(abilities: &mut Vec<Ability>, ability: Ability) {
+    if !ability.name.is_empty() && !abilities.iter().any(|x| x.name == ability.name) {
+        abilities.push(ability);
+    }
+}
+
+/// Reads a character exported from D&D Beyond, either its own character JSON
+/// or the Foundry actor ddb-importer makes from it. Stats, skills, HP, items,
+/// spells and limited use features are kept
+pub fn from_json(text: &str, ruleset: &Ruleset) -> Result<CharacterState, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    // The character service wraps the character in a response
+    let value = match value.get("data") {
+        Some(data) if data.is_object() => data,
+        _ => &value,
+    };
+
+    let imported = if value.get("stats").is_some() {
+        from_dnd_beyond(value, ruleset)
+    } else if value.get("system").is_some() {
+        from_foundry(value, ruleset)
+    } else {
+        return Err("Not a D&D Beyond or ddb-importer character".to_owned());
+    };
+
+    if imported.character.name.trim().is_empty() {
+        return Err("The character doesn't have a name".to_owned());
+    }
+    Ok(imported)
+}
+
+/// Every modifier the character gets, from their race, class, background and
+/// feats. Magic item bonuses are left out, they only count while equipped
+fn ddb_modifiers(data: &Value) -> Vec<&Value> {
+    ["race", "class", "background", "feat"]
+        .into_iter()
+        .filter_map(|source| data["modifiers"][source].as_array())
+        .flatten()
+        .collect()
+}
+
+fn ddb_stat(data: &Value, modifiers: &[&Value], idx: usize) -> i16 {
+    let id = idx as i64 + 1;
+    let stat_value = |key: &str| {
+        data[key]
+            .as_array()?
+            .iter()
+            .find(|x| x["id"].as_i64() == Some(id))?["value"]
+            .as_i64()
+    };
+
+    if let Some(score) = stat_value("overrideStats") {
+        return score as i16;
+    }
+
+    let subtype = format!("{}-score", STATS[idx]);
+    let applies = |kind: &str| -> Vec<i64> {
+        modifiers
+            .iter()
+            .filter(|x| str_field(x, "type") == kind && str_field(x, "subType") == subtype)
+            .filter_map(|x| x["value"].as_i64())
+            .collect()
+    };
+
+    let mut score = stat_value("stats").unwrap_or(10)
+        + stat_value("bonusStats").unwrap_or_default()
+        + applies("bonus").iter().sum::<i64>();
+    if let Some(set) = applies("set").into_iter().max() {
+        score = score.max(set);
+    }
+    score as i16
+}
+
+/// D&D Beyond's activation types, 1 action, 3 bonus action and 4 reaction
+fn ddb_ability_type(activation: &Value) -> &'static str {
+    match activation["activationType"].as_i64() {
+        Some(1) => "Action",
+        Some(3) => "Bonus Action",
+        Some(4) => "Reaction",
+        Some(_) => "Other",
+        None => "Passive",
+    }
+}
+
+fn ddb_spell(spell: &Value) -> Ability {
+    let definition = &spell["definition"];
+    let level = int_field(definition, "level");
+    let school = str_field(definition, "school");
+    let flavor_text = if level == 0 {
+        format!("{} cantrip", school)
+    } else {
+        format!("Level {} {}", level, school.to_lowercase())
+    };
+
+    let range = &definition["range"];
+    let mut notes = match range["rangeValue"].as_i64() {
+        Some(feet) => format!("Range: {} ft", feet),
+        None => format!("Range: {}", str_field(range, "origin")),
+    };
+    if definition["concentration"].as_bool().unwrap_or_default() {
+        notes.push_str("\nConcentration");
+    }
+
+    Ability {
+        name: str_field(definition, "name").to_owned(),
+        description: strip_html(str_field(definition, "description")),
+        notes: Some(notes),
+        ability_type: ddb_ability_type(&definition["activation"]).to_owned(),
+        flavor_text: Some(flavor_text),
+        resource: if level == 0 { "None" } else { "PowerSlot" }.to_owned(),
+        max_count: 0,
+        uses: 0,
+        to_hit: None,
+        damage: None,
+        recharge: None,
+    }
+}
+
+fn ddb_action(action: &Value) -> Ability {
+    let description = match str_field(action, "description") {
+        "" => str_field(action, "snippet"),
+        description => description,
+    };
+
+    let limited = &action["limitedUse"];
+    let max_count = int_field(limited, "maxUses");
+    let resource = if max_count > 0 { "UseToken" } else { "None" };
+
+    Ability {
+        name: str_field(action, "name").to_owned(),
+        description: strip_html(description),
+        notes: None,
+        ability_type: ddb_ability_type(&action["activation"]).to_owned(),
+        flavor_text: None,
+        resource: resource.to_owned(),
+        max_count,
+        uses: (max_count - int_field(limited, "numberUsed")).max(0),
+        to_hit: None,
+        damage: None,
+        recharge: None,
+    }
+}
+
+fn from_dnd_beyond(data: &Value, ruleset: &Ruleset) -> CharacterState {
+    let modifiers = ddb_modifiers(data);
+    let [str, dex, con, int, wis, cha] = std::array::from_fn(|idx| ddb_stat(data, &modifiers, idx));
+
+    let skills = modifiers
+        .iter()
+        .filter(|x| matches!(str_field(x, "type"), "proficiency" | "expertise"))
+        .filter_map(|x| ruleset_skill(ruleset, &str_field(x, "subType").replace('-', " ")))
+        .fold(Vec::new(), |mut skills, skill| {
+            if !skills.contains(&skill) {
+                skills.push(skill);
+            }
+            skills
+        });
+
+    let classes = data["classes"].as_array().cloned().unwrap_or_default();
+    let level = classes
+        .iter()
+        .map(|x| int_field(x, "level"))
+        .sum::<i64>()
+        .max(1) as u32;
+    let class_names = classes
+        .iter()
+        .map(|x| {
+            format!(
+                "{} {}",
+                str_field(&x["definition"], "name"),
+                int_field(x, "level")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" / ");
+
+    let max_hp = match data["overrideHitPoints"].as_i64() {
+        Some(hp) => hp,
+        None => {
+            int_field(data, "baseHitPoints")
+                + int_field(data, "bonusHitPoints")
+                + ruleset.score_modifier(con) as i64 * level as i64
+        }
+    } as i32;
+
+    let character = Character {
+        name: str_field(data, "name").trim().to_owned(),
+        str,
+        dex,
+        con,
+        int,
+        wis,
+        cha,
+        tagline: format!("{} {}", str_field(&data["race"], "fullName"), class_names)
+            .trim()
+            .to_owned(),
+        backstory: str_field(&data["notes"], "backstory").to_owned(),
+        skills,
+        xp: int_field(data, "currentXp").max(0) as u32,
+        level,
+        curr_hp: (max_hp - int_field(data, "removedHitPoints") as i32).max(0),
+        max_hp,
+        portrait: data["decorations"]["avatarUrl"]
+            .as_str()
+            .filter(|x| !x.is_empty())
+            .map(str::to_owned),
+        ..Default::default()
+    };
+
+    let mut items = Vec::new();
+    for entry in data["inventory"].as_array().into_iter().flatten() {
+        let definition = &entry["definition"];
+        let armor_class = definition["armorClass"]
+            .as_i64()
+            .filter(|ac| *ac > 0)
+            .map(|ac| ac as i16);
+
+        add_item(
+            &mut items,
+            Item {
+                id: 0,
+                count: int_field(entry, "quantity").max(1) as u32,
+                name: str_field(definition, "name").to_owned(),
+                description: strip_html(str_field(definition, "description")),
+                flavor_text: String::new(),
+                quest_item: false,
+                slot: None,
+                armor_class,
+                attack_bonus: None,
+                requires_attunement: definition["canAttune"].as_bool().unwrap_or_default(),
+                attuned: entry["isAttuned"].as_bool().unwrap_or_default(),
+                weight: definition["weight"]
+                    .as_f64()
+                    .filter(|x| *x > 0.0)
+                    .map(|x| x as f32),
+            },
+        );
+    }
+
+    let mut abilities = Vec::new();
+    let class_spells = data["classSpells"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x["spells"].as_array())
+        .flatten();
+    let other_spells = ["race", "class", "item", "feat"]
+        .into_iter()
+        .filter_map(|source| data["spells"][source].as_array())
+        .flatten();
+    for spell in class_spells.chain(other_spells) {
+        add_ability(&mut abilities, ddb_spell(spell));
+    }
+
+    for source in ["race", "class", "feat"] {
+        for action in data["actions"][source].as_array().into_iter().flatten() {
+            add_ability(&mut abilities, ddb_action(action));
+        }
+    }
+
+    for feat in data["feats"].as_array().into_iter().flatten() {
+        let definition = &feat["definition"];
+        add_ability(
+            &mut abilities,
+            Ability {
+                name: str_field(definition, "name").to_owned(),
+                description: strip_html(str_field(definition, "description")),
+                notes: None,
+                ability_type: "Passive".to_owned(),
+                flavor_text: Some("Feat".to_owned()),
+                resource: "None".to_owned(),
+                max_count: 0,
+                uses: 0,
+                to_hit: None,
+                damage: None,
+                recharge: None,
+            },
+        );
+    }
+
+    CharacterState {
+        character,
+        items,
+        abilities,
+        ..Default::default()
+    }
+}
+
+/// Foundry's activation types
+fn foundry_ability_type(system: &Value) -> &'static str {
+    match str_field(&system["activation"], "type") {
+        "action" => "Action",
+        "bonus" => "Bonus Action",
+        "reaction" => "Reaction",
+        "" => "Passive",
+        _ => "Other",
+    }
+}
+
+/// Foundry stores some numbers as strings, ie. a formula for max uses
+fn foundry_number(value: &Value) -> i64 {
+    match value {
+        Value::String(s) => s.trim().parse().unwrap_or_default(),
+        value => value.as_i64().unwrap_or_default(),
+    }
+}
+
+fn from_foundry(actor: &Value, ruleset: &Ruleset) -> CharacterState {
+    let system = &actor["system"];
+    let score = |stat: &str| {
+        system["abilities"][&stat[..3]]["value"]
+            .as_i64()
+            .unwrap_or(10) as i16
+    };
+
+    let skills = FOUNDRY_SKILLS
+        .into_iter()
+        .filter(|(key, _)| system["skills"][*key]["value"].as_f64().unwrap_or_default() >= 1.0)
+        .filter_map(|(_, name)| ruleset_skill(ruleset, name))
+        .collect();
+
+    let entries = actor["items"].as_array().cloned().unwrap_or_default();
+    let classes: Vec<_> = entries
+        .iter()
+        .filter(|x| str_field(x, "type") == "class")
+        .collect();
+    let level = classes
+        .iter()
+        .map(|x| int_field(&x["system"], "levels"))
+        .sum::<i64>()
+        .max(1) as u32;
+    let class_names = classes
+        .iter()
+        .map(|x| {
+            format!(
+                "{} {}",
+                str_field(x, "name"),
+                int_field(&x["system"], "levels")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" / ");
+    let race = entries
+        .iter()
+        .find(|x| str_field(x, "type") == "race")
+        .map_or("", |x| str_field(x, "name"));
+
+    let hp = &system["attributes"]["hp"];
+    let character = Character {
+        name: str_field(actor, "name").trim().to_owned(),
+        str: score(STATS[0]),
+        dex: score(STATS[1]),
+        con: score(STATS[2]),
+        int: score(STATS[3]),
+        wis: score(STATS[4]),
+        cha: score(STATS[5]),
+        tagline: format!("{} {}", race, class_names).trim().to_owned(),
+        backstory: strip_html(str_field(&system["details"]["biography"], "value")),
+        skills,
+        xp: int_field(&system["details"]["xp"], "value").max(0) as u32,
+        level,
+        curr_hp: foundry_number(&hp["value"]) as i32,
+        max_hp: foundry_number(&hp["max"]) as i32,
+        portrait: actor["img"]
+            .as_str()
+            .filter(|x| x.starts_with("http"))
+            .map(str::to_owned),
+        ..Default::default()
+    };
+
+    let mut items = Vec::new();
+    let mut abilities = Vec::new();
+    for entry in entries.iter() {
+        let system = &entry["system"];
+        let name = str_field(entry, "name").to_owned();
+        let description = strip_html(str_field(&system["description"], "value"));
+
+        match str_field(entry, "type") {
+            "weapon" | "equipment" | "consumable" | "tool" | "loot" | "backpack" | "container" => {
+                let armor_class = system["armor"]["value"]
+                    .as_i64()
+                    .filter(|ac| *ac > 0)
+                    .map(|ac| ac as i16);
+                // Older versions use 1 for "required", newer ones spell it out
+                let requires_attunement = match &system["attunement"] {
+                    Value::String(s) => !s.is_empty(),
+                    value => value.as_i64().unwrap_or_default() > 0,
+                };
+                let attuned = system["attuned"].as_bool().unwrap_or_default()
+                    || system["attunement"].as_i64() == Some(2);
+                // Newer versions keep the units alongside it
+                let weight = system["weight"]
+                    .as_f64()
+                    .or_else(|| system["weight"]["value"].as_f64())
+                    .filter(|x| *x > 0.0)
+                    .map(|x| x as f32);
+
+                let item = Item {
+                    id: 0,
+                    count: int_field(system, "quantity").max(1) as u32,
+                    name,
+                    description,
+                    flavor_text: String::new(),
+                    quest_item: false,
+                    slot: None,
+                    armor_class,
+                    attack_bonus: None,
+                    requires_attunement,
+                    attuned,
+                    weight,
+                };
+                add_item(&mut items, item);
+            }
+            "spell" => {
+                let level = int_field(system, "level");
+                add_ability(
+                    &mut abilities,
+                    Ability {
+                        name,
+                        description,
+                        notes: None,
+                        ability_type: foundry_ability_type(system).to_owned(),
+                        flavor_text: Some(match level {
+                            0 => "Cantrip".to_owned(),
+                            level => format!("Level {} spell", level),
+                        }),
+                        resource: if level == 0 { "None" } else { "PowerSlot" }.to_owned(),
+                        max_count: 0,
+                        uses: 0,
+                        to_hit: None,
+                        damage: None,
+                        recharge: None,
+                    },
+                );
+            }
+            "feat" => {
+                let uses = &system["uses"];
+                let max_count = foundry_number(&uses["max"]);
+                let used = foundry_number(&uses["spent"]);
+                let remaining = match uses.get("value") {
+                    Some(value) if !value.is_null() => foundry_number(value),
+                    _ => max_count - used,
+                };
+                add_ability(
+                    &mut abilities,
+                    Ability {
+                        name,
+                        description,
+                        notes: None,
+                        ability_type: foundry_ability_type(system).to_owned(),
+                        flavor_text: None,
+                        resource: if max_count > 0 { "UseToken" } else { "None" }.to_owned(),
+                        max_count,
+                        uses: remaining.clamp(0, max_count),
+                        to_hit: None,
+                        damage: None,
+                        recharge: None,
+                    },
+                );
+            }
+            // Classes, races and backgrounds are already on the character
+            _ => {}
+        }
+    }
+
+    CharacterState {
+        character,
+        items,
+        abilities,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::ruleset::Ruleset;
+
+    use super::from_json;
+
+    /// A level 3 cleric as D&D Beyond's character service returns her
+    const DND_BEYOND: &str = include_str!("../tests/fixtures/dnd_beyond_character.json");
+    /// The same character after ddb-importer turned her into a Foundry actor
+    const DDB_IMPORTER: &str = include_str!("../tests/fixtures/ddb_importer_actor.json");
+
+    #[test]
+    fn dnd_beyond_characters_are_read() {
+        let imported = from_json(DND_BEYOND, &Ruleset::default()).unwrap();
+        let character = &imported.character;
+
+        assert_eq!(character.name, "Brinna Ashvale");
+        assert_eq!(character.tagline, "Hill Dwarf Cleric 3");
+        assert_eq!(character.level, 3);
+        assert_eq!(character.xp, 1200);
+        // Racial bonuses count, the item's doesn't since it only applies while worn
+        assert_eq!(
+            [
+                character.str,
+                character.dex,
+                character.con,
+                character.int,
+                character.wis,
+                character.cha
+            ],
+            [14, 10, 16, 10, 16, 8]
+        );
+        // 21 base plus +3 constitution for each of the 3 levels
+        assert_eq!(character.max_hp, 30);
+        assert_eq!(character.curr_hp, 23);
+        // Weapon and armor proficiencies aren't skills
+        assert_eq!(
+            character.skills,
+            ["Medicine", "Persuasion", "Insight", "Religion"]
+        );
+        assert!(character.portrait.is_some());
+        assert!(character
+            .backstory
+            .starts_with("Raised in the temple forge"));
+    }
+
+    #[test]
+    fn dnd_beyond_items_are_stacked_and_cleaned_up() {
+        let imported = from_json(DND_BEYOND, &Ruleset::default()).unwrap();
+        let item = |name: &str| imported.items.iter().find(|x| x.name == name).unwrap();
+
+        assert_eq!(imported.items.len(), 4);
+        assert_eq!(item("Rations (1 day)").count, 8);
+        assert_eq!(item("Chain Mail").armor_class, Some(16));
+        assert_eq!(item("Mace").weight, Some(4.0));
+
+        let periapt = item("Periapt of Wound Closure");
+        assert!(periapt.requires_attunement && periapt.attuned);
+        assert_eq!(periapt.weight, None);
+        assert_eq!(
+            periapt.description,
+            "While you wear this pendant, you stabilize whenever you are dying at the start of \
+             your turn.\n\nIn addition, whenever you roll a Hit Die to regain hit points, double \
+             the number of hit points it restores."
+        );
+    }
+
+    #[test]
+    fn dnd_beyond_spells_and_features_become_abilities() {
+        let imported = from_json(DND_BEYOND, &Ruleset::default()).unwrap();
+        let ability = |name: &str| imported.abilities.iter().find(|x| x.name == name).unwrap();
+
+        assert_eq!(imported.abilities.len(), 6);
+
+        let flame = ability("Sacred Flame");
+        assert_eq!(flame.flavor_text.as_deref(), Some("Evocation cantrip"));
+        assert_eq!(flame.resource, "None");
+        assert_eq!(flame.notes.as_deref(), Some("Range: 60 ft"));
+
+        let bless = ability("Bless");
+        assert_eq!(bless.resource, "PowerSlot");
+        assert_eq!(bless.notes.as_deref(), Some("Range: 30 ft\nConcentration"));
+        assert_eq!(ability("Healing Word").ability_type, "Bonus Action");
+        assert_eq!(ability("Identify").notes.as_deref(), Some("Range: Touch"));
+
+        // Falls back to the snippet, with the one use already spent
+        let channel = ability("Channel Divinity");
+        assert!(channel
+            .description
+            .starts_with("You can channel divine energy"));
+        assert_eq!(
+            (channel.resource.as_str(), channel.max_count, channel.uses),
+            ("UseToken", 1, 0)
+        );
+        assert_eq!(ability("Blessing of the Forge").ability_type, "Passive");
+    }
+
+    #[test]
+    fn ddb_importer_actors_are_read() {
+        let imported = from_json(DDB_IMPORTER, &Ruleset::default()).unwrap();
+        let character = &imported.character;
+
+        assert_eq!(character.name, "Brinna Ashvale");
+        assert_eq!(character.tagline, "Hill Dwarf Cleric 3");
+        assert_eq!((character.con, character.wis), (16, 16));
+        assert_eq!((character.curr_hp, character.max_hp), (23, 30));
+        // Half proficiency from Jack of all Trades isn't a proficiency
+        assert_eq!(
+            character.skills,
+            ["Insight", "Medicine", "Persuasion", "Religion"]
+        );
+
+        let item = |name: &str| imported.items.iter().find(|x| x.name == name).unwrap();
+        assert_eq!(imported.items.len(), 3);
+        assert_eq!(item("Mace").weight, Some(4.0));
+        assert_eq!(item("Chain Mail").armor_class, Some(16));
+        assert!(item("Periapt of Wound Closure").attuned);
+
+        let channel = imported
+            .abilities
+            .iter()
+            .find(|x| x.name == "Channel Divinity")
+            .unwrap();
+        assert_eq!((channel.max_count, channel.uses), (1, 0));
+    }
+
+    #[test]
+    fn other_json_is_refused() {
+        assert!(from_json(r#"{ "board": [] }"#, &Ruleset::default()).is_err());
+        assert!(from_json("not json", &Ruleset::default()).is_err());
+    }
+}
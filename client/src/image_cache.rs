@@ -0,0 +1,349 @@
+// The disk cache and background decoding need threads and a file system, the
+// web build leaves images to egui's own loaders and the browser's cache
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::SystemTime,
+};
+
+use egui::{
+    load::{
+        BytesLoadResult, BytesLoader, BytesPoll, ImageLoadResult, ImageLoader, ImagePoll,
+        LoadError, SizeHint, TexturePoll,
+    },
+    ColorImage, TextureOptions,
+};
+use log::{info, warn};
+
+/// Least recently used images are deleted once the cache grows past this
+const MAX_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Piece images are drawn with mipmaps so zoomed out maps don't shimmer
+pub fn texture_options() -> TextureOptions {
+    TextureOptions::LINEAR.with_mipmap_mode(Some(egui::TextureFilter::Linear))
+}
+
+fn is_remote(uri: &str) -> bool {
+    uri.starts_with("http://") || uri.starts_with("https://")
+}
+
+/// Stable across runs, unlike std's hasher, so cached files can be found again
+fn cache_key(uri: &str) -> String {
+    let hash = uri.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Writes next to the file first so a crash can't leave half an image behind
+fn write_cached(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, data)?;
+    fs::rename(temp, path)
+}
+
+/// Reading a cached image counts as using it, so it's the last to be pruned
+fn read_cached(path: &Path) -> io::Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    if let Err(e) = fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        warn!("Could not mark {} as used: {e}", path.display());
+    }
+    Ok(data)
+}
+
+/// Deletes the least recently used images until the cache fits in `max_bytes`
+fn prune(dir: &Path, max_bytes: u64) -> io::Result<()> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        info!("Pruning {} from the image cache", path.display());
+        fs::remove_file(path)?;
+        total -= len;
+    }
+    Ok(())
+}
+
+enum Entry<T> {
+    Pending,
+    Ready(T),
+    Failed(String),
+}
+
+/// Keeps downloaded images on disk so big maps only come over the network once.
+/// Anything not cached yet is fetched by the loaders installed before this one
+struct DiskBytesLoader {
+    dir: PathBuf,
+    fallback: Vec<Arc<dyn BytesLoader + Send + Sync>>,
+    entries: Arc<Mutex<HashMap<String, Entry<egui::load::Bytes>>>>,
+}
+
+impl DiskBytesLoader {
+    fn store(&self, uri: &str, bytes: &[u8]) {
+        let path = self.dir.join(cache_key(uri));
+        let data = bytes.to_vec();
+        let uri = uri.to_owned();
+        let dir = self.dir.clone();
+        thread::spawn(move || {
+            if let Err(e) = write_cached(&path, &data) {
+                warn!("Could not cache {uri} to {}: {e}", path.display());
+            }
+            if let Err(e) = prune(&dir, MAX_CACHE_BYTES) {
+                warn!("Could not prune the image cache: {e}");
+            }
+        });
+    }
+}
+
+impl BytesLoader for DiskBytesLoader {
+    fn id(&self) -> &str {
+        egui::generate_loader_id!(DiskBytesLoader)
+    }
+
+    fn load(&self, ctx: &egui::Context, uri: &str) -> BytesLoadResult {
+        if !is_remote(uri) {
+            return Err(LoadError::NotSupported);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(uri) {
+            Some(Entry::Pending) => return Ok(BytesPoll::Pending { size: None }),
+            Some(Entry::Ready(bytes)) => {
+                return Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: bytes.clone(),
+                    mime: None,
+                })
+            }
+            // Try the network again, the file may have been deleted
+            Some(Entry::Failed(_)) | None => {}
+        }
+
+        let path = self.dir.join(cache_key(uri));
+        if path.exists() {
+            entries.insert(uri.to_owned(), Entry::Pending);
+
+            let entries = self.entries.clone();
+            let uri = uri.to_owned();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let entry = match read_cached(&path) {
+                    Ok(data) => Entry::Ready(egui::load::Bytes::Shared(data.into())),
+                    Err(e) => Entry::Failed(e.to_string()),
+                };
+                entries.lock().unwrap().insert(uri, entry);
+                ctx.request_repaint();
+            });
+
+            return Ok(BytesPoll::Pending { size: None });
+        }
+        drop(entries);
+
+        for loader in self.fallback.iter().rev() {
+            match loader.load(ctx, uri) {
+                Err(LoadError::NotSupported) => continue,
+                Ok(BytesPoll::Ready { size, bytes, mime }) => {
+                    info!("Caching {uri}");
+                    self.store(uri, &bytes);
+                    self.entries
+                        .lock()
+                        .unwrap()
+                        .insert(uri.to_owned(), Entry::Ready(bytes.clone()));
+                    // We hold onto it from here on
+                    loader.forget(uri);
+
+                    return Ok(BytesPoll::Ready { size, bytes, mime });
+                }
+                result => return result,
+            }
+        }
+
+        Err(LoadError::NotSupported)
+    }
+
+    fn forget(&self, uri: &str) {
+        self.entries.lock().unwrap().remove(uri);
+        self.fallback.iter().for_each(|x| x.forget(uri));
+    }
+
+    fn forget_all(&self) {
+        self.entries.lock().unwrap().clear();
+        self.fallback.iter().for_each(|x| x.forget_all());
+    }
+
+    fn byte_size(&self) -> usize {
+        let cached: usize = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .map(|x| match x {
+                Entry::Ready(bytes) => bytes.len(),
+                _ => 0,
+            })
+            .sum();
+
+        cached + self.fallback.iter().map(|x| x.byte_size()).sum::<usize>()
+    }
+}
+
+/// Decodes remote images on a background thread. The default loader decodes
+/// on the UI thread, which freezes the board for a moment on big maps
+struct BackgroundImageLoader {
+    entries: Arc<Mutex<HashMap<String, Entry<Arc<ColorImage>>>>>,
+    /// Cached files that don't decode are deleted so they're downloaded
+    /// again next time, rather than failing forever
+    cache_dir: Option<PathBuf>,
+}
+
+fn decode(bytes: &[u8]) -> Result<ColorImage, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(ColorImage::from_rgba_unmultiplied(
+        size,
+        image.to_rgba8().as_flat_samples().as_slice(),
+    ))
+}
+
+impl ImageLoader for BackgroundImageLoader {
+    fn id(&self) -> &str {
+        egui::generate_loader_id!(BackgroundImageLoader)
+    }
+
+    fn load(&self, ctx: &egui::Context, uri: &str, _size_hint: SizeHint) -> ImageLoadResult {
+        // Leave svgs to the loader that knows how to rasterize them
+        if !is_remote(uri) || uri.ends_with(".svg") {
+            return Err(LoadError::NotSupported);
+        }
+
+        match self.entries.lock().unwrap().get(uri) {
+            Some(Entry::Pending) => return Ok(ImagePoll::Pending { size: None }),
+            Some(Entry::Ready(image)) => {
+                return Ok(ImagePoll::Ready {
+                    image: image.clone(),
+                })
+            }
+            Some(Entry::Failed(e)) => return Err(LoadError::Loading(e.clone())),
+            None => {}
+        }
+
+        match ctx.try_load_bytes(uri)? {
+            BytesPoll::Pending { size } => Ok(ImagePoll::Pending { size }),
+            BytesPoll::Ready { bytes, .. } => {
+                self.entries
+                    .lock()
+                    .unwrap()
+                    .insert(uri.to_owned(), Entry::Pending);
+
+                let entries = self.entries.clone();
+                let cached = self.cache_dir.as_ref().map(|x| x.join(cache_key(uri)));
+                let uri = uri.to_owned();
+                let ctx = ctx.clone();
+                thread::spawn(move || {
+                    let entry = match decode(&bytes) {
+                        Ok(image) => Entry::Ready(Arc::new(image)),
+                        Err(e) => {
+                            warn!("Could not decode {uri}: {e}");
+                            if let Some(path) = cached.filter(|x| x.exists()) {
+                                if let Err(e) = fs::remove_file(&path) {
+                                    warn!("Could not delete {}: {e}", path.display());
+                                }
+                            }
+                            Entry::Failed(e)
+                        }
+                    };
+                    entries.lock().unwrap().insert(uri, entry);
+                    ctx.request_repaint();
+                });
+
+                Ok(ImagePoll::Pending { size: None })
+            }
+        }
+    }
+
+    fn forget(&self, uri: &str) {
+        self.entries.lock().unwrap().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .map(|x| match x {
+                Entry::Ready(image) => image.pixels.len() * std::mem::size_of::<egui::Color32>(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// Adds the disk cache and background decoding in front of the loaders from
+/// `egui_extras::install_image_loaders`, which must be installed first
+pub fn install(ctx: &egui::Context, dir: PathBuf) {
+    let fallback = ctx.loaders().bytes.lock().clone();
+
+    ctx.add_bytes_loader(Arc::new(DiskBytesLoader {
+        dir: dir.clone(),
+        fallback,
+        entries: Default::default(),
+    }));
+    ctx.add_image_loader(Arc::new(BackgroundImageLoader {
+        entries: Default::default(),
+        cache_dir: Some(dir),
+    }));
+}
+
+/// Starts loading piece images as soon as we hear about them, rather than
+/// when they first scroll into view
+#[derive(Default)]
+pub struct Prefetcher {
+    seen: HashSet<String>,
+    loading: HashSet<String>,
+}
+
+impl Prefetcher {
+    pub fn poll<'a>(&mut self, ctx: &egui::Context, urls: impl Iterator<Item = &'a str>) {
+        for url in urls {
+            if !self.seen.contains(url) {
+                self.seen.insert(url.to_owned());
+                self.loading.insert(url.to_owned());
+            }
+        }
+
+        // Keep polling until the texture is uploaded, so it's ready the first time it's drawn
+        self.loading.retain(|url| {
+            matches!(
+                ctx.try_load_texture(url, texture_options(), SizeHint::default()),
+                Ok(TexturePoll::Pending { .. })
+            )
+        });
+    }
+}
@@ -1,21 +1,87 @@
+#[cfg(not(target_arch = "wasm32"))]
 use std::{
-    collections::HashMap,
     io,
-    sync::mpsc::{Receiver, Sender},
+    sync::{mpsc::Sender, Arc},
+};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
 
-use common::{message::DndMessage, User};
+use chrono::Utc;
+use common::message::DndMessage;
+#[cfg(not(target_arch = "wasm32"))]
+use common::User;
+#[cfg(not(target_arch = "wasm32"))]
 use message_io::{
-    events::EventSender,
     network::{Endpoint, NetEvent, Transport},
     node::{self, NodeHandler, NodeListener},
 };
 
 use crate::state::DndState;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use message_io::events::EventSender;
+
+#[cfg(target_arch = "wasm32")]
+pub use crate::web::{DndListener, EventSender};
+
+/// How often the server is pinged. It drops anyone it hasn't heard from in a
+/// few of these
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The clock pings are stamped with, see [`DndMessage::Ping`]
+pub fn now_millis() -> u64 {
+    Utc::now().timestamp_millis() as u64
+}
+
+/// Counted by the listener as messages go through it, for the diagnostics
+/// overlay. Shared with the UI thread, so everything is atomic
+#[derive(Default)]
+pub struct ListenerStats {
+    sent: AtomicU64,
+    received: AtomicU64,
+    /// When the server last sent a board change, see [`now_millis`]. Zero
+    /// until it has
+    last_board_sync: AtomicU64,
+}
+
+impl ListenerStats {
+    pub fn count_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_received(&self, message: &DndMessage) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        if matches!(message, DndMessage::BoardMessage(_)) {
+            self.last_board_sync.store(now_millis(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// How long ago the server last sent a board change, if it ever has
+    pub fn since_board_sync(&self) -> Option<Duration> {
+        match self.last_board_sync.load(Ordering::Relaxed) {
+            0 => None,
+            at => Some(Duration::from_millis(now_millis().saturating_sub(at))),
+        }
+    }
+}
+
 pub enum Signal {
     ClientMessage(DndMessage),
     RecieveMessage(DndMessage),
+    /// Time to ping the server
+    Heartbeat,
+    /// Time for a local session to write out its save
+    SaveLocal,
 }
 
 impl From<DndMessage> for Signal {
@@ -38,14 +104,17 @@ impl<'a> CommandQueue<'a> {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub struct DndListener {
     user: User,
     handler: NodeHandler<Signal>,
     node_listener: Option<NodeListener<Signal>>,
     server_endpoint: Endpoint,
     tx: Sender<DndMessage>,
+    stats: Arc<ListenerStats>,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl DndListener {
     pub fn new(tx: Sender<DndMessage>, user: User, server_addr: &str) -> io::Result<Self> {
         let (handler, node_listener) = node::split();
@@ -57,6 +126,7 @@ impl DndListener {
             node_listener: Some(node_listener),
             server_endpoint: endpoint,
             tx,
+            stats: Default::default(),
         })
     }
 
@@ -64,6 +134,18 @@ impl DndListener {
         self.handler.signals().clone()
     }
 
+    pub fn stats(&self) -> Arc<ListenerStats> {
+        self.stats.clone()
+    }
+
+    fn send(&self, message: &DndMessage) {
+        let output_data = bincode::serialize(message).unwrap();
+        self.handler
+            .network()
+            .send(self.server_endpoint, &output_data);
+        self.stats.count_sent();
+    }
+
     pub fn run(mut self) {
         let node_listener = self.node_listener.take().unwrap();
 
@@ -72,17 +154,12 @@ impl DndListener {
                 NetEvent::Connected(endpoint, established) => {
                     if endpoint == self.server_endpoint {
                         if established {
-                            let message = DndMessage::RegisterUser(self.user.name.clone());
-                            let output_data = bincode::serialize(&message).unwrap();
-                            self.handler
-                                .network()
-                                .send(self.server_endpoint, &output_data);
+                            self.send(&DndMessage::RegisterUser(self.user.name.clone()));
+                            self.send(&DndMessage::RetrieveCharacterData(self.user.clone()));
 
-                            let message = DndMessage::RetrieveCharacterData(self.user.clone());
-                            let output_data = bincode::serialize(&message).unwrap();
                             self.handler
-                                .network()
-                                .send(self.server_endpoint, &output_data);
+                                .signals()
+                                .send_with_timer(Signal::Heartbeat, HEARTBEAT_INTERVAL);
                         } else {
                             println!("Could not connect to the server");
                         }
@@ -91,6 +168,7 @@ impl DndListener {
                 NetEvent::Accepted(_, _) => (),
                 NetEvent::Message(_, input_data) => {
                     let message: DndMessage = bincode::deserialize(input_data).unwrap();
+                    self.stats.count_received(&message);
 
                     println!("Recieved message from server {message:?}");
 
@@ -103,10 +181,7 @@ impl DndListener {
             },
             node::NodeEvent::Signal(signal) => match signal {
                 Signal::ClientMessage(msg) => {
-                    let input_data = bincode::serialize(&msg).unwrap();
-                    self.handler
-                        .network()
-                        .send(self.server_endpoint, &input_data);
+                    self.send(&msg);
 
                     // Immediately send the message back to ourself
                     //if matches!(msg, DndMessage::BoardMessage(_)) {
@@ -116,6 +191,13 @@ impl DndListener {
                 Signal::RecieveMessage(msg) => {
                     self.tx.send(msg).unwrap();
                 }
+                Signal::Heartbeat => {
+                    self.send(&DndMessage::Ping(now_millis()));
+                    self.handler
+                        .signals()
+                        .send_with_timer(Signal::Heartbeat, HEARTBEAT_INTERVAL);
+                }
+                Signal::SaveLocal => {}
             },
         })
     }
@@ -0,0 +1,1417 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::PathBuf,
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use common::{
+    message::{
+        compact_journal, record_journal_entry, replay_journal, BoardMessage, DieKind, DieRoll,
+        DndMessage, EffectMessage, HandoutMessage, JournalChange, JournalEntry, JournalMessage,
+        LogMessage, PinMessage, PinnedMessage, RollVisibility, SessionClock, SessionClockMessage,
+        SnapshotMessage, SoundMessage, StashMessage, JOURNAL_PAGE, MANUAL_SAVE_TAG,
+    },
+    ruleset::Ruleset,
+    soundboard::{NowPlaying, Soundboard},
+    stats::{counted_rolls, RollStats},
+    Ability, Ambience, Annotation, BoardInfo, CampaignDate, Character, Cooldown, DndPlayerPiece,
+    EffectTarget, GridSettings, Handout, IssueReport, Item, LifeState, Loot, PieceGroups,
+    PieceTemplate, Recharge, RollTable, SnapshotInfo, SnapshotUsage, TimedEffect, User, XpTable,
+    MAIN_BOARD,
+};
+use itertools::Itertools;
+use message_io::{
+    events::EventSender,
+    node::{self, NodeHandler, NodeListener},
+};
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::{listener::Signal, prelude::*};
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+struct LocalCharacter {
+    character: Character,
+    items: Vec<Item>,
+    abilities: Vec<Ability>,
+}
+
+/// Local sessions only snapshot when the GM asks, there's no timer like on the server
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LocalSnapshot {
+    info: SnapshotInfo,
+    characters: HashMap<String, LocalCharacter>,
+}
+
+/// Who the journal credits with what was on the board when the save was opened
+const SAVE_FILE_USER: &str = "Save file";
+
+/// Bumped whenever the save layout changes in a way field defaults can't
+/// cover, with a step added to `migrate_save` to bring older saves up to date
+const LOCAL_SAVE_VERSION: u64 = 2;
+
+/// Everything a local session persists between runs
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+#[serde(default)]
+struct LocalSave {
+    version: u64,
+    characters: HashMap<String, LocalCharacter>,
+    players: HashMap<Uuid, DndPlayerPiece>,
+    annotations: HashMap<Uuid, Annotation>,
+    ambience: Ambience,
+    date: CampaignDate,
+    grid: GridSettings,
+    round: u32,
+    effects: HashMap<Uuid, TimedEffect>,
+    cooldowns: HashMap<Uuid, Cooldown>,
+    handouts: HashMap<Uuid, Handout>,
+    pins: HashMap<Uuid, PinnedMessage>,
+    stash: HashMap<Uuid, Loot>,
+    xp_table: XpTable,
+    ruleset: Ruleset,
+    roll_tables: Vec<RollTable>,
+    piece_templates: Vec<PieceTemplate>,
+    boards: HashMap<Uuid, BoardInfo>,
+    active_board: Uuid,
+    groups: PieceGroups,
+    snapshots: Vec<LocalSnapshot>,
+    /// Public rolls from every session before this one, by player
+    roll_stats: BTreeMap<String, RollStats>,
+    soundboard: Soundboard,
+}
+
+impl LocalSave {
+    fn parse(json: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        serde_json::from_value(migrate_save(value)?).map_err(|e| e.to_string())
+    }
+}
+
+/// Brings a save from any earlier version up to `LOCAL_SAVE_VERSION`. Saves
+/// from before they had a version are treated as the first
+fn migrate_save(mut value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let version = value.get("version").and_then(|x| x.as_u64()).unwrap_or(1);
+    if version > LOCAL_SAVE_VERSION {
+        return Err(format!(
+            "The save was written by a newer version of the app (save version {version})"
+        ));
+    }
+
+    if version < 2 {
+        piece_visibility_from_visible_by(&mut value);
+    }
+
+    value["version"] = LOCAL_SAVE_VERSION.into();
+    Ok(value)
+}
+
+/// Pieces used to list the players who could see them, nobody listed meaning
+/// everyone. Left alone they'd load as visible to everyone
+fn piece_visibility_from_visible_by(save: &mut serde_json::Value) {
+    let Some(pieces) = save.get_mut("players").and_then(|x| x.as_object_mut()) else {
+        return;
+    };
+
+    for piece in pieces.values_mut().filter_map(|x| x.as_object_mut()) {
+        let Some(serde_json::Value::Array(names)) = piece.remove("visible_by") else {
+            continue;
+        };
+        if !names.is_empty() && !piece.contains_key("visibility") {
+            piece.insert(
+                "visibility".to_owned(),
+                serde_json::json!({ "Players": names }),
+            );
+        }
+    }
+}
+
+/// Changes are written out together this long after the first one, rather
+/// than rewriting the whole save for every drag
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Stands in for the server when playing without one. Messages are applied to
+/// a save file on disk and answered in process, the local user is always the GM.
+/// Hosting for others runs the real server instead, see `MyApp::start_hosting`
+pub struct LocalSession {
+    user: User,
+    path: PathBuf,
+    save: LocalSave,
+    /// Changed since the save was last written, a write is on its way
+    save_pending: bool,
+    handler: NodeHandler<Signal>,
+    node_listener: Option<NodeListener<Signal>>,
+    tx: Sender<DndMessage>,
+    /// Time counted before the last pause. Local sessions don't remind about
+    /// breaks, there's no timer to check on the clock like on the server
+    clock: SessionClock,
+    clock_started: Option<Instant>,
+    /// Public rolls since the clock was last reset, added to the save's when it is
+    session_rolls: BTreeMap<String, RollStats>,
+    /// Music the GM started and when, for players joining part way through
+    now_playing: Option<(NowPlaying, Instant)>,
+    /// Board changes since the save was opened, starting with what was already on the board
+    journal: Vec<JournalEntry>,
+}
+
+impl LocalSession {
+    pub fn new(tx: Sender<DndMessage>, user: User, path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut save = match fs::read_to_string(&path) {
+            Ok(json) => LocalSave::parse(&json).map_err(io::Error::other)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => LocalSave {
+                version: LOCAL_SAVE_VERSION,
+                ..Default::default()
+            },
+            Err(e) => return Err(e),
+        };
+        save.boards
+            .entry(MAIN_BOARD)
+            .or_insert_with(BoardInfo::main);
+
+        let (handler, node_listener) = node::split();
+
+        let mut session = Self {
+            user,
+            path,
+            save,
+            save_pending: false,
+            handler,
+            node_listener: Some(node_listener),
+            tx,
+            clock: SessionClock::default(),
+            clock_started: None,
+            session_rolls: BTreeMap::new(),
+            now_playing: None,
+            journal: Vec::new(),
+        };
+        for msg in session.board_messages() {
+            record_journal_entry(&mut session.journal, SAVE_FILE_USER.to_owned(), msg);
+        }
+
+        Ok(session)
+    }
+
+    pub fn event_sender(&self) -> EventSender<Signal> {
+        self.handler.signals().clone()
+    }
+
+    /// Stopping it through here writes out any changes still waiting
+    pub fn handler(&self) -> NodeHandler<Signal> {
+        self.handler.clone()
+    }
+
+    pub fn run(mut self) {
+        let node_listener = self.node_listener.take().unwrap();
+
+        self.handle_message(DndMessage::RegisterUser(self.user.name.clone()));
+        self.handle_message(DndMessage::RetrieveCharacterData(self.user.clone()));
+
+        node_listener.for_each(move |event| match event {
+            node::NodeEvent::Network(_) => unreachable!(),
+            node::NodeEvent::Signal(signal) => match signal {
+                Signal::ClientMessage(msg) => {
+                    self.handle_message(msg.clone());
+                    self.handler.signals().send(Signal::RecieveMessage(msg))
+                }
+                Signal::RecieveMessage(msg) => {
+                    self.tx.send(msg).unwrap();
+                }
+                // Nothing to ping, the session is on this machine
+                Signal::Heartbeat => {}
+                Signal::SaveLocal => self.write_save(),
+            },
+        })
+    }
+
+    fn send(&self, message: DndMessage) {
+        self.handler.signals().send(Signal::RecieveMessage(message));
+    }
+
+    /// Sends to the user playing `name`, only the local user is ever here
+    fn send_to(&self, name: &str, message: DndMessage) {
+        if name == self.user.name {
+            self.send(message);
+        }
+    }
+
+    /// Writes the save soon, along with anything else that changes until then
+    fn queue_save(&mut self) {
+        if !self.save_pending {
+            self.save_pending = true;
+            self.handler
+                .signals()
+                .send_with_timer(Signal::SaveLocal, SAVE_DELAY);
+        }
+    }
+
+    fn write_save(&mut self) {
+        if !std::mem::take(&mut self.save_pending) {
+            return;
+        }
+
+        let result = serde_json::to_string_pretty(&self.save)
+            .map_err(io::Error::other)
+            .and_then(|json| fs::write(&self.path, json));
+
+        if let Err(e) = result {
+            error!("Failed to write local save to {}: {e}", self.path.display());
+            self.send(DndMessage::Error {
+                request_context: "Saving local session".to_owned(),
+                message: e.to_string(),
+            });
+        }
+    }
+
+    /// There's no server to collect reports, so they're appended to a file next to the save
+    fn save_issue_report(&self, report: &IssueReport) {
+        let path = self.path.with_extension("issues.txt");
+        let entry = format!(
+            "--- {} (version {})\n{}\n\n{}\n\n",
+            Utc::now().to_rfc3339(),
+            report.version,
+            report.description,
+            report.log
+        );
+
+        let result = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| io::Write::write_all(&mut file, entry.as_bytes()));
+
+        match result {
+            Ok(()) => info!("Saved issue report to {}", path.display()),
+            Err(e) => self.send(DndMessage::Error {
+                request_context: "Sending issue report".to_owned(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    fn character_mut(&mut self, user: &User) -> &mut LocalCharacter {
+        self.save
+            .characters
+            .entry(user.name.clone())
+            .or_insert_with(|| LocalCharacter {
+                character: Character {
+                    name: user.name.clone(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+    }
+
+    fn handle_message(&mut self, message: DndMessage) {
+        match message {
+            DndMessage::RegisterUser(_) => {
+                self.send(DndMessage::UserList(self.online()));
+                self.send(DndMessage::GameMaster(self.user.name.clone()));
+                self.send(DndMessage::SetXpTable(self.save.xp_table.clone()));
+                self.send(DndMessage::SetRuleset(self.save.ruleset.clone()));
+                self.send(DndMessage::RollTables(self.save.roll_tables.clone()));
+                self.send(DndMessage::PieceTemplates(
+                    self.save.piece_templates.clone(),
+                ));
+                self.send(DndMessage::RollStats(
+                    self.session_rolls.clone(),
+                    self.save.roll_stats.clone(),
+                ));
+                self.send_session_clock();
+                self.send(DndMessage::SoundMessage(SoundMessage::SetSoundboard(
+                    self.save.soundboard.clone(),
+                )));
+                if let Some((playing, started)) = &self.now_playing {
+                    let playing = NowPlaying {
+                        elapsed: playing.elapsed + started.elapsed().as_secs_f32(),
+                        ..playing.clone()
+                    };
+                    self.send(DndMessage::SoundMessage(SoundMessage::Play(playing)));
+                }
+                self.send_roster();
+                self.send_snapshot_list();
+                return;
+            }
+            DndMessage::RetrieveCharacterData(user) => {
+                let data = self.character_mut(&user);
+                let messages = [
+                    DndMessage::ItemList(data.items.clone()),
+                    DndMessage::AbilityList(data.abilities.clone()),
+                    DndMessage::CharacterData(data.character.clone()),
+                ];
+                messages.into_iter().for_each(|x| self.send(x));
+
+                self.send_initial_data();
+                return;
+            }
+            DndMessage::RetrievePartyMember(name) => {
+                if let Some(data) = self.save.characters.get(&name) {
+                    self.send(DndMessage::PartyMemberData(
+                        data.character.clone(),
+                        data.items.clone(),
+                        data.abilities.clone(),
+                    ));
+                }
+                return;
+            }
+            DndMessage::UpdateItemCount(user, item_id, count) => {
+                let items = &mut self.character_mut(&user).items;
+                if count > 0 {
+                    items
+                        .iter_mut()
+                        .filter(|x| x.id == item_id)
+                        .for_each(|x| x.count = count);
+                } else {
+                    items.retain(|x| x.id != item_id);
+                }
+            }
+            DndMessage::UpdateItemSlot(user, item_id, slot) => {
+                self.character_mut(&user)
+                    .items
+                    .iter_mut()
+                    .filter(|x| x.id == item_id)
+                    .for_each(|x| x.slot = slot);
+            }
+            DndMessage::UpdateItemAttunement(user, item_id, attuned) => {
+                let ruleset = self.save.ruleset.clone();
+                let items = &mut self.character_mut(&user).items;
+                let count = items.iter().filter(|x| x.attuned).count();
+                if attuned && !ruleset.can_attune(count) {
+                    let items = items.clone();
+                    self.send(DndMessage::Error {
+                        request_context: "Attuning item".to_owned(),
+                        message: format!(
+                            "{} is already attuned to {} items",
+                            user.name, ruleset.attunement_limit
+                        ),
+                    });
+                    self.send(DndMessage::ItemList(items));
+                    return;
+                }
+
+                items
+                    .iter_mut()
+                    .filter(|x| x.id == item_id)
+                    .for_each(|x| x.attuned = attuned);
+            }
+            DndMessage::UpdateAbilityCount(user, name, count) => {
+                self.character_mut(&user)
+                    .abilities
+                    .iter_mut()
+                    .filter(|x| x.name == name)
+                    .for_each(|x| x.uses = count);
+            }
+            DndMessage::UpdatePowerSlotCount(user, count) => {
+                self.character_mut(&user).character.power_slots = count;
+            }
+            DndMessage::UpdateCharacter(user, version, change) => {
+                let character = &mut self.character_mut(&user).character;
+                if character.version != version {
+                    let character = character.clone();
+                    self.send(DndMessage::CharacterConflict(character, change));
+                    return;
+                }
+                change.apply(character);
+                character.version += 1;
+            }
+            DndMessage::AwardXp(names, xp) => {
+                self.send_chat(format!("{} gained {} XP", names.join(", "), xp));
+
+                for name in names {
+                    let user = User { name };
+                    let character = &mut self.character_mut(&user).character;
+                    character.xp += xp;
+                    character.version += 1;
+
+                    let character = character.clone();
+                    if character.can_level_up(&self.save.xp_table) {
+                        self.send_chat(format!("{} can level up!", character.name));
+                    }
+                    self.send_to(&user.name, DndMessage::CharacterData(character));
+                }
+            }
+            DndMessage::GrantInspiration(names, points) => {
+                for name in names {
+                    let user = User { name };
+                    let character = &mut self.character_mut(&user).character;
+                    character.inspiration += points;
+                    character.version += 1;
+
+                    let character = character.clone();
+                    self.send(DndMessage::Log(
+                        User::server(),
+                        LogMessage::InspirationGranted(character.name.clone(), points),
+                        Some(Utc::now()),
+                    ));
+                    self.send_to(&user.name, DndMessage::CharacterData(character));
+                }
+            }
+            DndMessage::SpendInspiration(user) => {
+                let character = &mut self.character_mut(&user).character;
+                let Some(left) = character.inspiration.checked_sub(1) else {
+                    warn!("{} has no inspiration to spend", user.name);
+                    return;
+                };
+                character.inspiration = left;
+                character.version += 1;
+
+                let character = character.clone();
+                self.send(DndMessage::Log(
+                    user,
+                    LogMessage::InspirationSpent(character.name.clone()),
+                    Some(Utc::now()),
+                ));
+                self.send(DndMessage::CharacterData(character));
+            }
+            DndMessage::AdjustHp(name, amount) => {
+                let user = User { name };
+                let character = &mut self.character_mut(&user).character;
+                let before = character.life;
+                character.adjust_hp(amount);
+                character.version += 1;
+
+                let character = character.clone();
+                self.send(DndMessage::Log(
+                    self.user.clone(),
+                    LogMessage::HpChanged(character.name.clone(), amount, character.curr_hp),
+                    Some(Utc::now()),
+                ));
+                if before != character.life {
+                    self.send(DndMessage::Log(
+                        self.user.clone(),
+                        LogMessage::LifeChanged(character.name.clone(), character.life),
+                        Some(Utc::now()),
+                    ));
+                }
+                self.send_to(&user.name, DndMessage::CharacterData(character));
+            }
+            DndMessage::RollDeathSave(name) => {
+                let user = User { name };
+                let character = &mut self.character_mut(&user).character;
+                if !matches!(character.life, LifeState::Dying { .. }) {
+                    warn!("{} isn't making death saves", user.name);
+                    return;
+                }
+                let roll = rand::rng().random_range(1..=20);
+                character.death_save(roll);
+                character.version += 1;
+
+                let character = character.clone();
+                self.send(DndMessage::Log(
+                    self.user.clone(),
+                    LogMessage::DeathSave(character.name.clone(), roll, character.life),
+                    Some(Utc::now()),
+                ));
+                self.send_to(&user.name, DndMessage::CharacterData(character));
+            }
+            DndMessage::SetLifeState(name, life) => {
+                let user = User { name };
+                let character = &mut self.character_mut(&user).character;
+                character.set_life(life);
+                character.version += 1;
+
+                let character = character.clone();
+                self.send(DndMessage::Log(
+                    self.user.clone(),
+                    LogMessage::LifeChanged(character.name.clone(), character.life),
+                    Some(Utc::now()),
+                ));
+                self.send_to(&user.name, DndMessage::CharacterData(character));
+            }
+            DndMessage::ApplyAreaDamage(source, roll, mut hits) => {
+                let mut life_changes = Vec::new();
+                for hit in hits.iter_mut() {
+                    let Some(local) = self.save.characters.get_mut(&hit.name) else {
+                        continue;
+                    };
+                    let before = local.character.life;
+                    local.character.adjust_hp(-hit.damage);
+                    local.character.version += 1;
+                    hit.hp = Some(local.character.curr_hp);
+                    if before != local.character.life {
+                        life_changes.push(LogMessage::LifeChanged(
+                            hit.name.clone(),
+                            local.character.life,
+                        ));
+                    }
+                }
+
+                for hit in hits.iter() {
+                    if let Some(local) = self.save.characters.get(&hit.name) {
+                        let character = local.character.clone();
+                        self.send_to(&hit.name, DndMessage::CharacterData(character));
+                    }
+                }
+
+                self.send(DndMessage::Log(
+                    self.user.clone(),
+                    LogMessage::AreaDamage(source, roll, hits),
+                    Some(Utc::now()),
+                ));
+                for msg in life_changes {
+                    self.send(DndMessage::Log(self.user.clone(), msg, Some(Utc::now())));
+                }
+            }
+            DndMessage::SetXpTable(table) => self.save.xp_table = table,
+            DndMessage::SetRuleset(ruleset) => self.save.ruleset = ruleset,
+            DndMessage::SaveRollTable(table) => {
+                let tables = &mut self.save.roll_tables;
+                match tables.iter_mut().find(|x| x.name == table.name) {
+                    Some(existing) => *existing = table,
+                    None => tables.push(table),
+                }
+            }
+            DndMessage::DeleteRollTable(name) => self.save.roll_tables.retain(|x| x.name != name),
+            DndMessage::SoundMessage(msg) => match msg {
+                SoundMessage::SetSoundboard(soundboard) => self.save.soundboard = soundboard,
+                SoundMessage::Play(playing) => self.now_playing = Some((playing, Instant::now())),
+                SoundMessage::Stop(_) => self.now_playing = None,
+                SoundMessage::Effect(_) => {}
+            },
+            DndMessage::SavePieceTemplate(template) => {
+                let templates = &mut self.save.piece_templates;
+                match templates.iter_mut().find(|x| x.name == template.name) {
+                    Some(existing) => *existing = template,
+                    None => templates.push(template),
+                }
+            }
+            DndMessage::DeletePieceTemplate(name) => {
+                self.save.piece_templates.retain(|x| x.name != name)
+            }
+            DndMessage::CreateCharacter(character) => {
+                let name = character.name.clone();
+                if self.save.characters.contains_key(&name) {
+                    warn!("Character '{}' already exists", name);
+                    return;
+                }
+
+                self.save.characters.insert(
+                    name.clone(),
+                    LocalCharacter {
+                        character: character.clone(),
+                        ..Default::default()
+                    },
+                );
+                self.send_roster();
+                self.send_to(&name, DndMessage::CharacterData(character));
+            }
+            DndMessage::ImportCharacter(character, items, abilities) => {
+                let name = character.name.clone();
+                if self.save.characters.contains_key(&name) {
+                    self.send(DndMessage::Error {
+                        request_context: "Importing character".to_owned(),
+                        message: format!("Character '{}' already exists", name),
+                    });
+                    return;
+                }
+
+                self.save.characters.insert(
+                    name,
+                    LocalCharacter {
+                        character,
+                        items,
+                        abilities,
+                    },
+                );
+                self.send_roster();
+            }
+            DndMessage::DeleteCharacter(name) => {
+                self.save.characters.remove(&name);
+                self.send_roster();
+            }
+            DndMessage::ImportItems(definitions) => {
+                // There's no shared catalog offline, imports go straight into their inventory
+                let count = definitions.len();
+                let user = self.user.clone();
+                let items = &mut self.character_mut(&user).items;
+                let first_id = items.iter().map(|x| x.id).max().unwrap_or_default() + 1;
+                for (id, definition) in (first_id..).zip(definitions) {
+                    items.retain(|x| x.name != definition.name);
+                    items.push(Item {
+                        id,
+                        count: 1,
+                        name: definition.name,
+                        description: definition.description,
+                        flavor_text: definition.flavor_text,
+                        quest_item: definition.quest_item,
+                        slot: None,
+                        armor_class: definition.armor_class,
+                        attack_bonus: definition.attack_bonus,
+                        requires_attunement: definition.requires_attunement,
+                        attuned: false,
+                        weight: definition.weight,
+                    });
+                }
+
+                let items = items.clone();
+                self.send(DndMessage::ItemList(items));
+                self.send(DndMessage::ImportResult(Ok(count)));
+            }
+            DndMessage::ImportAbilities(definitions) => {
+                let count = definitions.len();
+                let user = self.user.clone();
+                let abilities = &mut self.character_mut(&user).abilities;
+                for definition in definitions {
+                    abilities.retain(|x| x.name != definition.name);
+                    abilities.push(Ability {
+                        name: definition.name,
+                        description: definition.description,
+                        notes: definition.notes,
+                        ability_type: definition.ability_type,
+                        flavor_text: definition.flavor_text,
+                        resource: definition.resource,
+                        max_count: definition.max_count,
+                        uses: definition.max_count,
+                        to_hit: definition.to_hit,
+                        damage: definition.damage,
+                        recharge: definition.recharge,
+                    });
+                }
+
+                let abilities = abilities.clone();
+                self.send(DndMessage::AbilityList(abilities));
+                self.send(DndMessage::ImportResult(Ok(count)));
+            }
+            // Nobody else is here to take a piece from
+            DndMessage::BoardMessage(
+                BoardMessage::HoldPiece(_)
+                | BoardMessage::ReleasePiece(_)
+                | BoardMessage::SetHolder(..),
+            ) => return,
+            DndMessage::BoardMessage(msg) => {
+                let user = self.user.name.clone();
+                self.handle_board_message(msg.clone());
+                self.record_board_change(user, msg);
+            }
+            DndMessage::EffectMessage(msg) => self.handle_effect_message(msg),
+            DndMessage::HandoutMessage(msg) => self.handle_handout_message(msg),
+            DndMessage::PinMessage(PinMessage::Pin(uuid, pin)) => {
+                self.save.pins.insert(uuid, pin);
+            }
+            DndMessage::PinMessage(PinMessage::Unpin(uuid)) => {
+                self.save.pins.remove(&uuid);
+            }
+            DndMessage::StashMessage(msg) => self.handle_stash_message(msg),
+            DndMessage::SnapshotMessage(msg) => self.handle_snapshot_message(msg),
+            DndMessage::SessionClockMessage(msg) => {
+                self.handle_session_clock_message(msg);
+                return;
+            }
+            DndMessage::Log(user, msg, _) => {
+                let rolls = counted_rolls(&msg);
+                if !rolls.is_empty() {
+                    let stats = self.session_rolls.entry(user.name).or_default();
+                    rolls.into_iter().for_each(|x| stats.record(x));
+                }
+                return;
+            }
+            DndMessage::JournalMessage(JournalMessage::Request(start)) => {
+                self.send_journal(start);
+                return;
+            }
+            DndMessage::JournalMessage(JournalMessage::Branch(point)) => self.branch_board(point),
+            DndMessage::ReportIssue(report) => {
+                self.save_issue_report(&report);
+                return;
+            }
+            // Only matter to other people, there's nobody to tell
+            DndMessage::Typing(..)
+            | DndMessage::FocusView(..)
+            | DndMessage::GmView(_)
+            | DndMessage::Ping(_)
+            | DndMessage::UnregisterUser(_) => return,
+            message => {
+                warn!("Local sessions can't handle {message:?}");
+                return;
+            }
+        }
+
+        self.queue_save();
+    }
+
+    fn handle_board_message(&mut self, msg: BoardMessage) {
+        let save = &mut self.save;
+        match msg {
+            BoardMessage::AddPlayerPiece(uuid, player)
+            | BoardMessage::UpdatePlayerPiece(uuid, player) => {
+                save.players.insert(uuid, player);
+            }
+            BoardMessage::UpdatePlayerLocation(uuid, position) => {
+                if let Some(player) = save.players.get_mut(&uuid) {
+                    player.position = position;
+                }
+            }
+            BoardMessage::DeletePlayerPiece(uuid) => {
+                save.players.remove(&uuid);
+                save.groups.retain_pieces(|x| *x != uuid);
+            }
+            BoardMessage::AddAnnotation(uuid, annotation) => {
+                save.annotations.insert(uuid, annotation);
+            }
+            BoardMessage::DeleteAnnotation(uuid) => {
+                save.annotations.remove(&uuid);
+            }
+            BoardMessage::ClearAnnotations(board, layer) => {
+                save.annotations
+                    .retain(|_, x| x.board != board || x.layer != layer);
+            }
+            BoardMessage::SetAmbience(ambience) => save.ambience = ambience,
+            BoardMessage::SetCampaignDate(date) => save.date = date,
+            BoardMessage::SetGrid(grid) => save.grid = grid,
+            BoardMessage::AdvanceTime(minutes) => save.date = save.date.advanced(minutes),
+            BoardMessage::CreateBoard(uuid, name) => {
+                save.boards.insert(
+                    uuid,
+                    BoardInfo {
+                        name,
+                        ..Default::default()
+                    },
+                );
+            }
+            BoardMessage::DeleteBoard(uuid) => {
+                if uuid == MAIN_BOARD {
+                    return;
+                }
+
+                save.boards.remove(&uuid);
+                save.players.retain(|_, x| x.board != uuid);
+                save.groups.retain_pieces(|x| save.players.contains_key(x));
+                save.annotations.retain(|_, x| x.board != uuid);
+                for board in save.boards.values_mut() {
+                    board.portals.retain(|_, x| x.target_board != uuid);
+                }
+                if save.active_board == uuid {
+                    save.active_board = MAIN_BOARD;
+                }
+            }
+            BoardMessage::SetActiveBoard(uuid) => save.active_board = uuid,
+            BoardMessage::AddPortal(board, uuid, portal) => {
+                if let Some(board) = save.boards.get_mut(&board) {
+                    board.portals.insert(uuid, portal);
+                }
+            }
+            BoardMessage::DeletePortal(board, uuid) => {
+                if let Some(board) = save.boards.get_mut(&board) {
+                    board.portals.remove(&uuid);
+                }
+            }
+            BoardMessage::AddWall(board, uuid, wall) => {
+                if let Some(board) = save.boards.get_mut(&board) {
+                    board.walls.insert(uuid, wall);
+                }
+            }
+            BoardMessage::DeleteWall(board, uuid) => {
+                if let Some(board) = save.boards.get_mut(&board) {
+                    board.walls.remove(&uuid);
+                }
+            }
+            BoardMessage::SetDoorOpen(board, uuid, open) => {
+                if let Some(wall) = save
+                    .boards
+                    .get_mut(&board)
+                    .and_then(|x| x.walls.get_mut(&uuid))
+                {
+                    wall.door = wall.door.map(|_| open);
+                }
+            }
+            BoardMessage::GroupPieces(uuid, pieces) => save.groups.set(uuid, pieces),
+            BoardMessage::Ungroup(uuid) => save.groups.remove(&uuid),
+            BoardMessage::HoldPiece(_)
+            | BoardMessage::ReleasePiece(_)
+            | BoardMessage::SetHolder(..) => {}
+        }
+    }
+
+    /// Empties the board, for replaying the journal onto
+    fn clear_board(&mut self) {
+        let save = &mut self.save;
+        save.players.clear();
+        save.annotations.clear();
+        save.ambience = Ambience::default();
+        save.date = CampaignDate::default();
+        save.grid = GridSettings::default();
+        save.boards = HashMap::from([(MAIN_BOARD, BoardInfo::main())]);
+        save.active_board = MAIN_BOARD;
+        save.groups = PieceGroups::default();
+    }
+
+    /// Puts the board back to how it was after `point` journal entries
+    fn replay_board(&mut self, point: usize) {
+        self.clear_board();
+        let messages = replay_journal(&self.journal, point)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        for msg in messages {
+            self.handle_board_message(msg);
+        }
+    }
+
+    /// The board is rebuilt from what's left whenever the journal gets squashed
+    fn record_board_change(&mut self, user: String, msg: BoardMessage) {
+        record_journal_entry(&mut self.journal, user, msg);
+
+        let mut journal = std::mem::take(&mut self.journal);
+        let squashed = compact_journal(&mut journal, |messages| {
+            self.clear_board();
+            for msg in messages {
+                self.handle_board_message(msg.clone());
+            }
+            self.board_messages()
+        });
+        self.journal = journal;
+
+        if squashed {
+            self.replay_board(self.journal.len());
+            self.send_journal(0);
+        }
+    }
+
+    /// Puts the board back to how it was after `point` journal entries. Later
+    /// entries stay in the journal and branching from before this undoes it
+    fn branch_board(&mut self, point: usize) {
+        let point = point.min(self.journal.len());
+
+        self.replay_board(point);
+        self.journal.push(JournalEntry {
+            at: Utc::now(),
+            user: self.user.name.clone(),
+            change: JournalChange::Branch(point),
+        });
+
+        self.send(DndMessage::JournalMessage(JournalMessage::Branched(point)));
+        self.board_messages()
+            .into_iter()
+            .for_each(|x| self.send(DndMessage::BoardMessage(x)));
+        self.send_chat(format!("The GM rewound the board to change {point}"));
+        self.send_journal(self.journal.len() - 1);
+    }
+
+    /// A page of the journal from `start` on
+    fn send_journal(&self, start: usize) {
+        let start = start.min(self.journal.len());
+        let page = self.journal[start..]
+            .iter()
+            .take(JOURNAL_PAGE)
+            .cloned()
+            .collect();
+        let total = self.journal.len();
+        self.send(DndMessage::JournalMessage(JournalMessage::Entries(
+            start, page, total,
+        )));
+    }
+
+    fn handle_effect_message(&mut self, msg: EffectMessage) {
+        match msg {
+            EffectMessage::ApplyEffect(uuid, effect) => {
+                self.save.effects.insert(uuid, effect);
+            }
+            EffectMessage::RemoveEffect(uuid) => {
+                self.save.effects.remove(&uuid);
+            }
+            EffectMessage::AdvanceRound => {
+                self.save.round += 1;
+
+                let mut expired = Vec::new();
+                self.save.effects.retain(|_, effect| {
+                    let done = effect.tick();
+                    if done {
+                        expired.push(effect.clone());
+                    }
+                    !done
+                });
+
+                for effect in expired {
+                    let target = match effect.target {
+                        EffectTarget::Character(name) => name,
+                        EffectTarget::Piece(_) => String::from("a token"),
+                    };
+                    self.send(DndMessage::Log(
+                        User::server(),
+                        LogMessage::EffectExpired(effect.name, target),
+                        Some(Utc::now()),
+                    ));
+                }
+
+                self.recharge_abilities(|cooldown| {
+                    matches!(cooldown.recharge, Recharge::Rounds(_))
+                });
+            }
+            EffectMessage::SetRound(round) => self.save.round = round,
+            EffectMessage::SetCooldown(uuid, cooldown) => {
+                self.save.cooldowns.insert(uuid, cooldown);
+            }
+            EffectMessage::RemoveCooldown(uuid) => {
+                self.save.cooldowns.remove(&uuid);
+            }
+            EffectMessage::StartTurn(piece) => {
+                let Some(player) = self.save.players.get(&piece) else {
+                    return;
+                };
+                let piece_target = EffectTarget::Piece(piece);
+                let character = EffectTarget::Character(
+                    player
+                        .link_stats_to
+                        .clone()
+                        .unwrap_or_else(|| player.name.clone()),
+                );
+                self.recharge_abilities(|cooldown| {
+                    matches!(cooldown.recharge, Recharge::Roll(_))
+                        && (cooldown.target == piece_target || cooldown.target == character)
+                });
+            }
+        }
+    }
+
+    fn recharge_abilities(&mut self, should_tick: impl Fn(&Cooldown) -> bool) {
+        let mut rng = rand::rng();
+        let uuids: Vec<_> = self
+            .save
+            .cooldowns
+            .iter()
+            .filter(|(_, x)| should_tick(x))
+            .map(|(uuid, _)| *uuid)
+            .collect();
+
+        for uuid in uuids {
+            let cooldown = self.save.cooldowns.get_mut(&uuid).unwrap();
+            let mut rolled = None;
+            let done = cooldown.tick(|| *rolled.insert(rng.random_range(1..=6)));
+            let cooldown = cooldown.clone();
+
+            if let Some(value) = rolled {
+                let roll = DieRoll {
+                    die: 6,
+                    count: 1,
+                    value: value as u32,
+                    modifier: 0,
+                    character: None,
+                    reason: Some(format!("{} recharge", cooldown.ability)),
+                    visibility: RollVisibility::GmOnly,
+                    kind: DieKind::Standard,
+                    rolls: vec![value as u32],
+                    outcome: None,
+                }
+                .resolve(&self.save.ruleset);
+                self.send(DndMessage::Log(
+                    User::server(),
+                    LogMessage::Roll(roll),
+                    Some(Utc::now()),
+                ));
+            }
+
+            if !done {
+                if matches!(cooldown.recharge, Recharge::Rounds(_)) {
+                    self.send(DndMessage::EffectMessage(EffectMessage::SetCooldown(
+                        uuid, cooldown,
+                    )));
+                }
+                continue;
+            }
+
+            self.save.cooldowns.remove(&uuid);
+            self.send(DndMessage::EffectMessage(EffectMessage::RemoveCooldown(
+                uuid,
+            )));
+
+            let target = match cooldown.target {
+                EffectTarget::Character(name) => {
+                    if let Some(uses) = cooldown.uses {
+                        self.restore_ability_uses(name.clone(), &cooldown.ability, uses);
+                    }
+                    name
+                }
+                EffectTarget::Piece(_) => String::from("a token"),
+            };
+            self.send(DndMessage::Log(
+                User::server(),
+                LogMessage::AbilityRecharged(cooldown.ability, target),
+                Some(Utc::now()),
+            ));
+        }
+    }
+
+    fn restore_ability_uses(&mut self, name: String, ability: &str, uses: i64) {
+        let user = User { name };
+        let abilities = &mut self.character_mut(&user).abilities;
+        if let Some(ability) = abilities.iter_mut().find(|x| x.name == ability) {
+            ability.uses = uses;
+        }
+
+        let abilities = abilities.clone();
+        self.send_to(&user.name, DndMessage::AbilityList(abilities));
+    }
+
+    fn handle_handout_message(&mut self, msg: HandoutMessage) {
+        match msg {
+            HandoutMessage::CreateHandout(uuid, handout) => {
+                self.save.handouts.insert(uuid, handout);
+            }
+            HandoutMessage::ShareHandout(uuid, visibility) => {
+                if let Some(handout) = self.save.handouts.get_mut(&uuid) {
+                    handout.visibility = visibility;
+                }
+            }
+            HandoutMessage::DeleteHandout(uuid) => {
+                self.save.handouts.remove(&uuid);
+            }
+        }
+    }
+
+    fn handle_stash_message(&mut self, msg: StashMessage) {
+        match msg {
+            StashMessage::AddLoot(name, count) => {
+                // No shared catalog offline, copy the item from whoever already has one
+                let item = self
+                    .save
+                    .characters
+                    .values()
+                    .flat_map(|x| x.items.iter())
+                    .find(|x| x.name == name)
+                    .cloned()
+                    .unwrap_or_else(|| Item {
+                        id: 0,
+                        count,
+                        name,
+                        description: String::new(),
+                        flavor_text: String::new(),
+                        quest_item: false,
+                        slot: None,
+                        armor_class: None,
+                        attack_bonus: None,
+                        requires_attunement: false,
+                        attuned: false,
+                        weight: None,
+                    });
+
+                let uuid = Uuid::new_v4();
+                let loot = Loot {
+                    item: Item {
+                        count,
+                        slot: None,
+                        attuned: false,
+                        ..item
+                    },
+                    pending_claim: None,
+                };
+                self.save.stash.insert(uuid, loot.clone());
+                self.send(DndMessage::StashMessage(StashMessage::SetLoot(uuid, loot)));
+            }
+            // The local user is the GM, claims never need approving
+            StashMessage::ClaimLoot(uuid, character) => {
+                let Some(loot) = self.save.stash.remove(&uuid) else {
+                    return;
+                };
+                self.send(DndMessage::StashMessage(StashMessage::RemoveLoot(uuid)));
+
+                let user = User { name: character };
+                let items = &mut self.character_mut(&user).items;
+                match items.iter_mut().find(|x| x.name == loot.item.name) {
+                    Some(item) => item.count += loot.item.count,
+                    None => {
+                        let id = items.iter().map(|x| x.id).max().unwrap_or_default() + 1;
+                        items.push(Item { id, ..loot.item });
+                    }
+                }
+
+                let items = self.character_mut(&user).items.clone();
+                self.send_to(&user.name, DndMessage::ItemList(items));
+            }
+            StashMessage::ResolveClaim(uuid, _) => {
+                if let Some(loot) = self.save.stash.get_mut(&uuid) {
+                    loot.pending_claim = None;
+                }
+            }
+            StashMessage::RemoveLoot(uuid) => {
+                self.save.stash.remove(&uuid);
+            }
+            StashMessage::SetLoot(..) | StashMessage::RequireApproval(_) => {}
+        }
+    }
+
+    fn send_chat(&self, msg: String) {
+        self.send(DndMessage::Log(
+            User::server(),
+            LogMessage::Chat(msg),
+            Some(Utc::now()),
+        ));
+    }
+
+    fn take_snapshot(&mut self, tag: String) -> SnapshotInfo {
+        let info = SnapshotInfo {
+            id: Uuid::new_v4(),
+            tag,
+            automatic: false,
+            created_at: Utc::now(),
+        };
+        self.save.snapshots.push(LocalSnapshot {
+            info: info.clone(),
+            characters: self.save.characters.clone(),
+        });
+        info
+    }
+
+    fn handle_snapshot_message(&mut self, msg: SnapshotMessage) {
+        match msg {
+            SnapshotMessage::Take(tag) => {
+                let info = self.take_snapshot(tag);
+                self.send(DndMessage::SnapshotMessage(SnapshotMessage::Saved(info)));
+            }
+            SnapshotMessage::SaveNow => {
+                self.save
+                    .snapshots
+                    .retain(|x| x.info.tag != MANUAL_SAVE_TAG);
+                let info = self.take_snapshot(MANUAL_SAVE_TAG.to_owned());
+                self.send(DndMessage::SnapshotMessage(SnapshotMessage::Saved(info)));
+            }
+            SnapshotMessage::Restore(uuid) => {
+                let Some(snapshot) = self.save.snapshots.iter().find(|x| x.info.id == uuid) else {
+                    return;
+                };
+                let characters = snapshot.characters.clone();
+                let tag = snapshot.info.tag.clone();
+
+                self.take_snapshot(format!("Before restoring '{}'", tag));
+                self.save.characters = characters;
+
+                self.send_chat(format!("The GM restored the snapshot '{}'", tag));
+                self.send_roster();
+                for name in self.online() {
+                    let data = self.character_mut(&User { name: name.clone() });
+                    let messages = [
+                        DndMessage::ItemList(data.items.clone()),
+                        DndMessage::AbilityList(data.abilities.clone()),
+                        DndMessage::CharacterData(data.character.clone()),
+                    ];
+                    messages.into_iter().for_each(|x| self.send_to(&name, x));
+                }
+            }
+            SnapshotMessage::Delete(uuid) => self.save.snapshots.retain(|x| x.info.id != uuid),
+            SnapshotMessage::RequestUsage => {
+                let bytes = self
+                    .save
+                    .snapshots
+                    .iter()
+                    .map(|x| serde_json::to_string(&x.characters).map_or(0, |x| x.len() as u64))
+                    .sum();
+                // Nothing is snapshotted automatically without a server
+                let usage = SnapshotUsage {
+                    automatic: 0,
+                    tagged: self.save.snapshots.len(),
+                    bytes,
+                    keep_automatic: None,
+                };
+                self.send(DndMessage::SnapshotMessage(SnapshotMessage::Usage(usage)));
+                return;
+            }
+            SnapshotMessage::List(_) | SnapshotMessage::Usage(_) | SnapshotMessage::Saved(_) => {}
+        }
+
+        self.send_snapshot_list();
+    }
+
+    fn handle_session_clock_message(&mut self, msg: SessionClockMessage) {
+        match msg {
+            SessionClockMessage::Start => {
+                self.clock_started.get_or_insert_with(Instant::now);
+            }
+            SessionClockMessage::Pause => {
+                if let Some(started) = self.clock_started.take() {
+                    self.clock.elapsed += started.elapsed().as_secs();
+                }
+            }
+            SessionClockMessage::Reset => {
+                self.clock_started = None;
+                self.clock.elapsed = 0;
+                self.end_roll_stats_session();
+            }
+            SessionClockMessage::SetBreakInterval(minutes) => self.clock.break_every = minutes,
+            SessionClockMessage::State(_) => return,
+        }
+
+        self.send_session_clock();
+    }
+
+    /// Posts the recap of the session's rolls and adds them to the save's
+    fn end_roll_stats_session(&mut self) {
+        if self.session_rolls.is_empty() {
+            return;
+        }
+
+        let session = std::mem::take(&mut self.session_rolls);
+        for (player, stats) in &session {
+            let total = self.save.roll_stats.entry(player.clone()).or_default();
+            total.merge(stats);
+        }
+        self.send(DndMessage::Log(
+            User::server(),
+            LogMessage::SessionSummary(session),
+            Some(Utc::now()),
+        ));
+        self.queue_save();
+    }
+
+    fn send_session_clock(&self) {
+        let running = self.clock_started.map(|x| x.elapsed().as_secs());
+        let clock = SessionClock {
+            running: running.is_some(),
+            elapsed: self.clock.elapsed + running.unwrap_or_default(),
+            ..self.clock
+        };
+        self.send(DndMessage::SessionClockMessage(SessionClockMessage::State(
+            clock,
+        )));
+    }
+
+    fn send_snapshot_list(&self) {
+        let list = self
+            .save
+            .snapshots
+            .iter()
+            .rev()
+            .map(|x| x.info.clone())
+            .collect();
+        self.send(DndMessage::SnapshotMessage(SnapshotMessage::List(list)));
+    }
+
+    /// Nobody but the local user plays in a local session
+    fn online(&self) -> Vec<String> {
+        vec![self.user.name.clone()]
+    }
+
+    fn send_roster(&self) {
+        let list = self.save.characters.keys().cloned().sorted().collect();
+        self.send(DndMessage::CharacterRoster(list));
+    }
+
+    fn board_messages(&self) -> Vec<BoardMessage> {
+        let save = &self.save;
+
+        let mut board = Vec::new();
+        for (uuid, info) in save.boards.iter() {
+            board.push(BoardMessage::CreateBoard(*uuid, info.name.clone()));
+            board.extend(
+                info.portals
+                    .iter()
+                    .map(|(id, portal)| BoardMessage::AddPortal(*uuid, *id, *portal)),
+            );
+            board.extend(
+                info.walls
+                    .iter()
+                    .map(|(id, wall)| BoardMessage::AddWall(*uuid, *id, *wall)),
+            );
+        }
+        board.push(BoardMessage::SetActiveBoard(save.active_board));
+        board.extend(
+            save.players
+                .iter()
+                .map(|(uuid, player)| BoardMessage::AddPlayerPiece(*uuid, player.clone())),
+        );
+        board.extend(
+            save.groups
+                .0
+                .iter()
+                .map(|(uuid, pieces)| BoardMessage::GroupPieces(*uuid, pieces.clone())),
+        );
+        board.extend(
+            save.annotations
+                .iter()
+                .map(|(uuid, annotation)| BoardMessage::AddAnnotation(*uuid, annotation.clone())),
+        );
+        board.push(BoardMessage::SetGrid(save.grid));
+        board.push(BoardMessage::SetCampaignDate(save.date));
+        board.push(BoardMessage::SetAmbience(save.ambience));
+        board
+    }
+
+    fn send_initial_data(&self) {
+        let save = &self.save;
+        let board = self.board_messages();
+
+        let mut effects = vec![EffectMessage::SetRound(save.round)];
+        effects.extend(
+            save.effects
+                .iter()
+                .map(|(uuid, effect)| EffectMessage::ApplyEffect(*uuid, effect.clone())),
+        );
+        effects.extend(
+            save.cooldowns
+                .iter()
+                .map(|(uuid, cooldown)| EffectMessage::SetCooldown(*uuid, cooldown.clone())),
+        );
+
+        let handouts = save
+            .handouts
+            .iter()
+            .map(|(uuid, handout)| HandoutMessage::CreateHandout(*uuid, handout.clone()));
+
+        board
+            .into_iter()
+            .for_each(|x| self.send(DndMessage::BoardMessage(x)));
+        effects
+            .into_iter()
+            .for_each(|x| self.send(DndMessage::EffectMessage(x)));
+        handouts.for_each(|x| self.send(DndMessage::HandoutMessage(x)));
+        save.pins.iter().for_each(|(uuid, pin)| {
+            self.send(DndMessage::PinMessage(PinMessage::Pin(*uuid, pin.clone())))
+        });
+        save.stash.iter().for_each(|(uuid, loot)| {
+            self.send(DndMessage::StashMessage(StashMessage::SetLoot(
+                *uuid,
+                loot.clone(),
+            )))
+        });
+    }
+}
+
+impl Drop for LocalSession {
+    fn drop(&mut self) {
+        self.write_save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::PieceVisibility;
+    use uuid::Uuid;
+
+    use super::{LocalSave, LOCAL_SAVE_VERSION};
+
+    /// Written by the first release with local sessions, before saves had a version
+    const SAVE_V1: &str = include_str!("../tests/fixtures/local_save_v1.json");
+
+    #[test]
+    fn unversioned_saves_still_load() {
+        let save = LocalSave::parse(SAVE_V1).unwrap();
+
+        assert_eq!(save.version, LOCAL_SAVE_VERSION);
+        let alice = &save.characters["Alice"];
+        assert_eq!(alice.items.len(), 2);
+        assert_eq!(alice.abilities[0].uses, 1);
+        assert_eq!(save.round, 3);
+        assert_eq!(save.effects.len(), 1);
+        assert_eq!(save.handouts.len(), 1);
+        assert_eq!(save.annotations.len(), 1);
+    }
+
+    #[test]
+    fn visible_by_becomes_piece_visibility() {
+        let save = LocalSave::parse(SAVE_V1).unwrap();
+
+        let everyone = &save.players[&Uuid::from_u128(1)];
+        assert_eq!(everyone.visibility, PieceVisibility::Everyone);
+        let hidden = &save.players[&Uuid::from_u128(2)];
+        assert_eq!(
+            hidden.visibility,
+            PieceVisibility::Players(vec!["Alice".to_owned()])
+        );
+    }
+
+    #[test]
+    fn saves_from_newer_versions_are_refused() {
+        let json = format!(r#"{{ "version": {} }}"#, LOCAL_SAVE_VERSION + 1);
+        assert!(LocalSave::parse(&json).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Entries kept around for the log viewer and issue reports
+const MAX_LINES: usize = 500;
+
+/// Once the log file grows past this it's moved aside and a new one started
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Rotated files are kept as `client.log.1` up to `client.log.{MAX_BACKUPS}`
+const MAX_BACKUPS: usize = 3;
+
+static RECENT: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}: {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        if fs::metadata(path).is_ok_and(|x| x.len() > MAX_FILE_BYTES) {
+            rotate(path)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            written,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.written > MAX_FILE_BYTES {
+            *self = Self::open(&self.path)?;
+        }
+
+        writeln!(self.file, "{line}")?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Shifts `client.log` to `client.log.1`, `client.log.1` to `client.log.2` and so on
+fn rotate(path: &Path) -> io::Result<()> {
+    for n in (1..MAX_BACKUPS).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(from, backup_path(path, n + 1))?;
+        }
+    }
+    fs::rename(path, backup_path(path, 1))
+}
+
+/// Passes everything on to env_logger, but also remembers recent info and
+/// above even when `RUST_LOG` isn't set. Release builds on windows have no
+/// console, so the log file and viewer are the only way to see what happened
+struct BufferedLogger {
+    inner: env_logger::Logger,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for BufferedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= Level::Info {
+            let entry = LogEntry {
+                time: chrono::Local::now().format("%H:%M:%S").to_string(),
+                level: record.level(),
+                target: record.target().to_owned(),
+                message: record.args().to_string(),
+            };
+
+            if let Some(Ok(mut file)) = self.file.as_ref().map(|x| x.lock()) {
+                let line = format!("{} {entry}", chrono::Local::now().format("%Y-%m-%d"));
+                // Nowhere left to report this
+                let _ = file.write_line(&line);
+            }
+
+            if let Ok(mut recent) = RECENT.lock() {
+                if recent.len() == MAX_LINES {
+                    recent.pop_front();
+                }
+                recent.push_back(entry);
+            }
+        }
+
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Some(Ok(mut file)) = self.file.as_ref().map(|x| x.lock()) {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Stands in for `env_logger::init`, also writing info and above to `log_path`
+/// when there is one. The web build only keeps the recent entries
+pub fn init(log_path: Option<&Path>) {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter().max(LevelFilter::Info);
+
+    let (file, file_error) = match log_path.map(|path| (path, LogFile::open(path))) {
+        Some((_, Ok(file))) => (Some(file), None),
+        Some((path, Err(e))) => (None, Some((path, e))),
+        None => (None, None),
+    };
+
+    let logger = BufferedLogger {
+        inner,
+        file: file.map(Mutex::new),
+    };
+    log::set_boxed_logger(Box::new(logger)).expect("logger already set");
+    log::set_max_level(max_level);
+
+    if let Some((path, e)) = file_error {
+        log::warn!("Could not open log file {}: {e}", path.display());
+    }
+}
+
+/// Recent entries, oldest first
+pub fn entries() -> Vec<LogEntry> {
+    RECENT
+        .lock()
+        .map(|x| x.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Recent log lines, oldest first
+pub fn recent() -> String {
+    entries()
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
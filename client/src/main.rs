@@ -1,39 +1,92 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
-use std::{
-    sync::mpsc::{channel, Receiver},
-    thread,
-};
+use std::sync::mpsc::{channel, Receiver};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
 
 use common::{message::DndMessage, User};
 use eframe::egui;
 use egui::{CentralPanel, Window};
-use egui_dock::{tab_viewer, DockArea, DockState, NodeIndex, SurfaceIndex};
-use listener::{CommandQueue, DndListener, Signal};
-use message_io::events::EventSender;
-use state::DndState;
-use view::DndTab;
+use egui_dock::{tab_viewer, DockArea, DockState, NodeIndex, SurfaceIndex, TabIndex};
+use listener::{CommandQueue, DndListener, EventSender, Signal};
+#[cfg(not(target_arch = "wasm32"))]
+use local::LocalSession;
+#[cfg(not(target_arch = "wasm32"))]
+use message_io::node::NodeHandler;
+use state::{
+    chat::commands::ShowChatHelp,
+    dice_tray::DiceTrayState,
+    inventory::InventoryState,
+    narration::NarrationState,
+    piece_templates::PieceTemplateState,
+    sheets::commands::{CloseAllSheets, OpenSheet},
+    snapshots::commands::SaveNow,
+    theme::{self, AppearanceState},
+    DndState,
+};
+use view::{DndTab, PaletteAction};
 
 use clap::Parser;
 
+mod board_file;
+#[cfg(feature = "compendium")]
+mod compendium;
+mod dnd_beyond;
+mod image_cache;
 mod listener;
+#[cfg(not(target_arch = "wasm32"))]
+mod local;
+mod log_buffer;
+mod npc_generator;
 mod prelude;
 mod state;
+mod storage;
 mod view;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     ip: Option<String>,
     name: Option<String>,
+    /// Save file used when playing without a server
+    #[arg(long, default_value = "local_session.json")]
+    save: String,
+    /// Port other players connect to when hosting
+    #[arg(long, default_value_t = 8080)]
+    host_port: u16,
+    /// Folder the campaign is kept in when hosting, a JSON file per table
+    #[arg(long, default_value = "campaign")]
+    campaign: String,
+    /// Info and above is written here, older logs are rotated to `<file>.1` and so on
+    #[arg(long, default_value = "client.log")]
+    log_file: std::path::PathBuf,
+    /// Downloaded piece images are kept here between sessions
+    #[arg(long, default_value = "image_cache")]
+    image_cache: std::path::PathBuf,
+    /// Rolls pinned in the character sheet's dice tray
+    #[arg(long, default_value = "favorite_rolls.json")]
+    favorite_rolls: std::path::PathBuf,
+    /// Board pieces saved in the piece templates palette
+    #[arg(long, default_value = "piece_templates.json")]
+    piece_templates: std::path::PathBuf,
+    /// Theme, UI scale, chat font size and narration picked in the settings
+    #[arg(long, default_value = "appearance.json")]
+    appearance: std::path::PathBuf,
+    /// How each character's items are sorted and which columns are shown
+    #[arg(long, default_value = "inventory_views.json")]
+    inventory_views: std::path::PathBuf,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
-
     let args = Args::parse();
 
+    // Log to stderr (if you run with `RUST_LOG=debug`) as well as the log file
+    log_buffer::init(Some(&args.log_file));
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([800.0, 600.0]),
         ..Default::default()
@@ -44,29 +97,105 @@ fn main() -> eframe::Result {
         Box::new(|cc| {
             // This gives us image support:
             egui_extras::install_image_loaders(&cc.egui_ctx);
+            image_cache::install(&cc.egui_ctx, args.image_cache.clone());
+
+            Ok(create_app(cc, args))
+        }),
+    )
+}
 
-            cc.egui_ctx.set_pixels_per_point(1.5);
+/// Entry point for the web build, served by `trunk serve` from `client/`
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use wasm_bindgen::JsCast;
 
-            let mut fonts = egui::FontDefinitions::default();
-            egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
+    // There's nowhere to keep a log file, the log viewer still has everything
+    log_buffer::init(None);
 
-            cc.egui_ctx.set_fonts(fonts);
+    // Nothing to parse, but the defaults double as the localStorage keys
+    let args = Args::parse_from(["client"]);
 
-            Ok(Box::new(MyApp::new(args)))
-        }),
-    )
+    wasm_bindgen_futures::spawn_local(async {
+        let canvas = web_sys::window()
+            .and_then(|x| x.document())
+            .and_then(|x| x.get_element_by_id("the_canvas_id"))
+            .and_then(|x| x.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+            .expect("index.html should have a canvas to draw on");
+
+        let result = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|cc| {
+                    // The browser caches and decodes images itself
+                    egui_extras::install_image_loaders(&cc.egui_ctx);
+
+                    Ok(create_app(cc, args))
+                }),
+            )
+            .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to start the client: {e:?}");
+        }
+    });
+}
+
+fn create_app(cc: &eframe::CreationContext, args: Args) -> Box<dyn eframe::App> {
+    let mut fonts = egui::FontDefinitions::default();
+    egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
+
+    cc.egui_ctx.set_fonts(fonts);
+
+    Box::new(MyApp::new(args))
+}
+
+/// Where the first tab with `title` is docked, if one is open
+fn find_tab(tree: &DockState<DndTab>, title: &str) -> Option<(SurfaceIndex, NodeIndex, TabIndex)> {
+    let (surface, node) = tree
+        .iter_all_tabs()
+        .find(|(_, tab)| tab.kind.title() == title)?
+        .0;
+    let tab = tree[surface][node]
+        .tabs()?
+        .iter()
+        .position(|x| x.kind.title() == title)?;
+    Some((surface, node, TabIndex(tab)))
 }
 
 struct MyApp {
     tree: DockState<DndTab>,
     counter: usize,
     state: DndState,
+    report: view::ReportIssue,
+    sheets: view::SheetWindows,
+    presentation: view::Presentation,
+    diagnostics: view::Diagnostics,
+    palette: view::CommandPalette,
+    search: view::CampaignSearch,
+    images: image_cache::Prefetcher,
 
     server_ip: String,
     user_string: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    save_path: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    host_port: u16,
+    #[cfg(not(target_arch = "wasm32"))]
+    campaign_dir: String,
+    login_error: Option<String>,
 
     tx: Option<EventSender<Signal>>,
     rx: Option<Receiver<DndMessage>>,
+    /// Polled every frame, there's no thread to run it on in the browser
+    #[cfg(target_arch = "wasm32")]
+    listener: Option<DndListener>,
+    /// Stopped when the app closes so it can write out its save
+    #[cfg(not(target_arch = "wasm32"))]
+    local_session: Option<(NodeHandler<Signal>, thread::JoinHandle<()>)>,
+    /// The server we're hosting for the others, stopped when the app closes
+    #[cfg(not(target_arch = "wasm32"))]
+    hosted: Option<server::EmbeddedServer>,
 }
 
 impl MyApp {
@@ -75,15 +204,43 @@ impl MyApp {
             DndTab::from_tab(view::Chat::default(), SurfaceIndex::main(), NodeIndex(1)),
             DndTab::from_tab(view::Board::default(), SurfaceIndex::main(), NodeIndex(2)),
         ]);
+        let appearance = AppearanceState::load(args.appearance);
 
         Self {
             tree,
             counter: 3,
             tx: None,
             rx: None,
-            state: Default::default(),
+            #[cfg(target_arch = "wasm32")]
+            listener: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            local_session: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            hosted: None,
+            state: DndState {
+                dice_tray: DiceTrayState::load(args.favorite_rolls),
+                piece_templates: PieceTemplateState::load(args.piece_templates),
+                narration: NarrationState::new(appearance.current.narration),
+                appearance,
+                inventory: InventoryState::load(args.inventory_views),
+                ..Default::default()
+            },
+            report: Default::default(),
+            sheets: Default::default(),
+            presentation: Default::default(),
+            diagnostics: Default::default(),
+            palette: Default::default(),
+            search: Default::default(),
+            images: Default::default(),
             server_ip: args.ip.unwrap_or_default(),
             user_string: args.name.unwrap_or_default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            save_path: args.save,
+            #[cfg(not(target_arch = "wasm32"))]
+            host_port: args.host_port,
+            #[cfg(not(target_arch = "wasm32"))]
+            campaign_dir: args.campaign,
+            login_error: None,
         }
     }
 
@@ -98,31 +255,161 @@ impl MyApp {
                     ui.label("Name: ");
                     let input = ui.text_edit_singleline(&mut self.user_string);
                     if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                        let user = User {
-                            name: self.user_string.clone(),
-                        };
+                        self.connect();
+                    }
+                });
 
-                        self.state.user = Some(user.clone());
+                // The web build is for joining someone else's game
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    ui.separator();
 
-                        // Create the server listener with the user that we've selected
-                        let (tx_listener, rx_main) = channel();
+                    ui.horizontal(|ui| {
+                        ui.label("Save file: ");
+                        ui.text_edit_singleline(&mut self.save_path);
+                    });
+                    if ui
+                        .button("Local Session")
+                        .on_hover_text("Play without a server, everything is kept in the save file")
+                        .clicked()
+                    {
+                        self.start_local_session();
+                    }
 
-                        let listener =
-                            DndListener::new(tx_listener, user, &self.server_ip).unwrap();
+                    ui.separator();
 
-                        self.tx = Some(listener.event_sender());
-                        self.rx = Some(rx_main);
+                    ui.horizontal(|ui| {
+                        ui.label("Campaign folder: ");
+                        ui.text_edit_singleline(&mut self.campaign_dir);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Port: ");
+                        ui.add(egui::DragValue::new(&mut self.host_port));
+                        if ui
+                            .button("Host")
+                            .on_hover_text(
+                                "Run the server here as the GM, others join at this computer's \
+                                 address and the port",
+                            )
+                            .clicked()
+                        {
+                            self.start_hosting();
+                        }
+                    });
+                }
 
-                        thread::spawn(move || listener.run());
-                    }
-                })
+                if let Some(e) = &self.login_error {
+                    ui.colored_label(theme::palette(ctx).negative, e);
+                }
             });
         });
     }
+
+    fn connect(&mut self) {
+        let user = User {
+            name: self.user_string.clone(),
+        };
+
+        // Create the server listener with the user that we've selected
+        let (tx_listener, rx_main) = channel();
+
+        let listener = match DndListener::new(tx_listener, user.clone(), &self.server_ip) {
+            Ok(listener) => listener,
+            Err(e) => {
+                self.login_error = Some(format!("Could not connect: {e}"));
+                return;
+            }
+        };
+
+        self.state.user = Some(user);
+        self.tx = Some(listener.event_sender());
+        self.rx = Some(rx_main);
+        self.diagnostics.stats = Some(listener.stats());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::spawn(move || listener.run());
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.listener = Some(listener);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_local_session(&mut self) {
+        if self.user_string.trim().is_empty() {
+            self.login_error = Some("Enter a name for your character first".to_owned());
+            return;
+        }
+
+        let user = User {
+            name: self.user_string.trim().to_owned(),
+        };
+
+        let (tx_session, rx_main) = channel();
+
+        let session = match LocalSession::new(tx_session, user.clone(), &self.save_path) {
+            Ok(session) => session,
+            Err(e) => {
+                self.login_error = Some(format!("Could not load {}: {e}", self.save_path));
+                return;
+            }
+        };
+
+        self.state.user = Some(user);
+        self.tx = Some(session.event_sender());
+        self.rx = Some(rx_main);
+
+        let handler = session.handler();
+        let thread = thread::spawn(move || session.run());
+        self.local_session = Some((handler, thread));
+    }
+
+    /// Starts the server on `host_port` with us as the GM, then joins it like
+    /// any other player would
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_hosting(&mut self) {
+        let name = self.user_string.trim().to_owned();
+        if name.is_empty() {
+            self.login_error = Some("Enter a name for your character first".to_owned());
+            return;
+        }
+
+        let dir = std::path::PathBuf::from(&self.campaign_dir);
+        let hosted = match server::EmbeddedServer::start(dir, self.host_port, &name) {
+            Ok(hosted) => hosted,
+            Err(e) => {
+                self.login_error = Some(format!("Could not host on port {}: {e}", self.host_port));
+                return;
+            }
+        };
+
+        self.user_string = name;
+        self.server_ip = format!("127.0.0.1:{}", hosted.addr().port());
+        self.connect();
+        if self.state.user.is_some() {
+            self.hosted = Some(hosted);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for MyApp {
+    fn drop(&mut self) {
+        // Changes to a local session are saved a moment after they're made
+        if let Some((handler, thread)) = self.local_session.take() {
+            handler.stop();
+            let _ = thread.join();
+        }
+    }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        theme::apply(ctx, &self.state.appearance.current);
+        self.state
+            .audio
+            .update(ctx, &self.state.soundboard.soundboard);
+
         if self.state.user.is_none() {
             self.show_login(ctx, _frame);
         } else {
@@ -130,6 +417,64 @@ impl eframe::App for MyApp {
 
             let mut command_queue = Vec::new();
 
+            egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+                egui::menu::bar(ui, |ui| {
+                    let mut commands = CommandQueue {
+                        command_queue: &mut command_queue,
+                    };
+
+                    ui.menu_button("Sheets", |ui| {
+                        for name in self.state.roster.iter() {
+                            let open = self.state.sheets.is_open(name);
+                            if ui.selectable_label(open, name).clicked() {
+                                commands.add(OpenSheet(name.clone()));
+                                ui.close_menu();
+                            }
+                        }
+
+                        ui.separator();
+                        let any_open = !self.state.sheets.windows.is_empty();
+                        if ui
+                            .add_enabled(any_open, egui::Button::new("Close All"))
+                            .clicked()
+                        {
+                            commands.add(CloseAllSheets);
+                            ui.close_menu();
+                        }
+                    });
+                    ui.menu_button("View", |ui| {
+                        if self.state.is_gm() {
+                            ui.checkbox(&mut self.presentation.open, "Presentation Window")
+                                .on_hover_text("Just the board as the players see it, for a TV");
+                        }
+                        ui.checkbox(&mut self.diagnostics.open, "Diagnostics")
+                            .on_hover_text("Latency and message counts, for lag reports");
+                    });
+                    if self.state.is_gm()
+                        && ui
+                            .button(format!("{} Save Now", egui_phosphor::regular::FLOPPY_DISK))
+                            .on_hover_text("Save the board and overwrite the manual save snapshot")
+                            .clicked()
+                    {
+                        commands.add(SaveNow);
+                    }
+                    ui.menu_button("Help", |ui| {
+                        if ui.button("Command Palette (Ctrl+P)").clicked() {
+                            self.palette.toggle();
+                            ui.close_menu();
+                        }
+                        if ui.button("Search Campaign (Ctrl+Shift+F)").clicked() {
+                            self.search.toggle();
+                            ui.close_menu();
+                        }
+                        if ui.button("Report Issue...").clicked() {
+                            self.report.open();
+                            ui.close_menu();
+                        }
+                    });
+                });
+            });
+
             {
                 let mut tab_viewer = view::TabViewer {
                     added_nodes: &mut added_nodes,
@@ -146,9 +491,103 @@ impl eframe::App for MyApp {
                     .show(ctx, &mut tab_viewer);
             }
 
+            view::toasts::show_toasts(
+                ctx,
+                &self.state,
+                &mut CommandQueue {
+                    command_queue: &mut command_queue,
+                },
+            );
+
+            self.sheets.show(
+                ctx,
+                &self.state,
+                &mut CommandQueue {
+                    command_queue: &mut command_queue,
+                },
+            );
+
+            view::show_conflicts(
+                ctx,
+                &self.state,
+                &mut CommandQueue {
+                    command_queue: &mut command_queue,
+                },
+            );
+
+            self.report.show(
+                ctx,
+                &mut CommandQueue {
+                    command_queue: &mut command_queue,
+                },
+            );
+
+            self.presentation.show(ctx, &self.state);
+            self.diagnostics.show(ctx, &self.state);
+
+            if let Some(action) = self.palette.show(ctx, &self.state) {
+                let mut commands = CommandQueue {
+                    command_queue: &mut command_queue,
+                };
+                match action {
+                    PaletteAction::OpenTab(kind) => {
+                        let (surface, node) = self
+                            .tree
+                            .focused_leaf()
+                            .unwrap_or((SurfaceIndex::main(), NodeIndex::root()));
+                        added_nodes.push(kind.open(surface, node));
+                    }
+                    PaletteAction::OpenSheet(name) => commands.add(OpenSheet(name)),
+                    PaletteAction::ChatHelp => commands.add(ShowChatHelp(true)),
+                    PaletteAction::ReportIssue => self.report.open(),
+                    PaletteAction::TogglePresentation => {
+                        self.presentation.open = !self.presentation.open
+                    }
+                    PaletteAction::ToggleDiagnostics => {
+                        self.diagnostics.open = !self.diagnostics.open
+                    }
+                }
+            }
+
+            let found = self.search.show(
+                ctx,
+                &self.state,
+                &mut CommandQueue {
+                    command_queue: &mut command_queue,
+                },
+            );
+            if let Some(kind) = found {
+                // Bring the tab forward if it's already open somewhere
+                match find_tab(&self.tree, kind.title) {
+                    Some((surface, node, tab)) => {
+                        self.tree.set_active_tab((surface, node, tab));
+                        self.tree.set_focused_node_and_surface((surface, node));
+                    }
+                    None => {
+                        let (surface, node) = self
+                            .tree
+                            .focused_leaf()
+                            .unwrap_or((SurfaceIndex::main(), NodeIndex::root()));
+                        added_nodes.push(kind.open(surface, node));
+                    }
+                }
+            }
+
+            #[cfg(target_arch = "wasm32")]
+            if let Some(listener) = &mut self.listener {
+                listener.poll();
+            }
+
+            let mut messages = 0;
             for msg in self.rx.as_ref().unwrap().try_iter() {
                 self.state.process(msg);
+                messages += 1;
             }
+            self.diagnostics.record_frame(command_queue.len(), messages);
+
+            let urls = self.state.board.players.values();
+            self.images
+                .poll(ctx, urls.filter_map(|x| x.image_url.as_deref()));
 
             for command in command_queue.drain(..) {
                 command.execute(&mut self.state, self.tx.as_ref().unwrap());
@@ -0,0 +1,312 @@
+use common::{Handout, HandoutVisibility};
+use rand::seq::IndexedRandom;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Culture {
+    Human,
+    Elf,
+    Dwarf,
+    Halfling,
+    Gnome,
+    Orc,
+}
+
+impl Culture {
+    pub const ALL: [Culture; 6] = [
+        Self::Human,
+        Self::Elf,
+        Self::Dwarf,
+        Self::Halfling,
+        Self::Gnome,
+        Self::Orc,
+    ];
+
+    /// (given names, family names)
+    fn names(&self) -> (&'static [&'static str], &'static [&'static str]) {
+        match self {
+            Self::Human => (
+                &[
+                    "Aldric", "Bryn", "Cedric", "Della", "Edwin", "Fiona", "Garret", "Hilda",
+                    "Isolde", "Jory", "Kara", "Leopold", "Marta", "Nell", "Osric", "Perrin",
+                    "Rowan", "Sabine", "Tomas", "Wren",
+                ],
+                &[
+                    "Ashdown",
+                    "Blackwood",
+                    "Carver",
+                    "Dunmore",
+                    "Fletcher",
+                    "Greaves",
+                    "Hollis",
+                    "Marsh",
+                    "Redfern",
+                    "Thatcher",
+                    "Underhill",
+                    "Whitlock",
+                ],
+            ),
+            Self::Elf => (
+                &[
+                    "Aelar",
+                    "Caelynn",
+                    "Erevan",
+                    "Galinndan",
+                    "Ielenia",
+                    "Lael",
+                    "Mialee",
+                    "Naivara",
+                    "Quarion",
+                    "Riardon",
+                    "Sariel",
+                    "Thamior",
+                    "Valanthe",
+                    "Varis",
+                ],
+                &[
+                    "Amakiir",
+                    "Galanodel",
+                    "Holimion",
+                    "Ilphelkiir",
+                    "Liadon",
+                    "Meliamne",
+                    "Nailo",
+                    "Siannodel",
+                    "Xiloscient",
+                ],
+            ),
+            Self::Dwarf => (
+                &[
+                    "Adrik", "Amber", "Baern", "Dagnal", "Eberk", "Gunnloda", "Harbek", "Kathra",
+                    "Morgran", "Riswynn", "Thorin", "Torbera", "Vistra", "Vondal",
+                ],
+                &[
+                    "Balderk",
+                    "Battlehammer",
+                    "Dankil",
+                    "Fireforge",
+                    "Gorunn",
+                    "Holderhek",
+                    "Ironfist",
+                    "Loderr",
+                    "Rumnaheim",
+                    "Torunn",
+                ],
+            ),
+            Self::Halfling => (
+                &[
+                    "Andry",
+                    "Bree",
+                    "Callie",
+                    "Cade",
+                    "Eldon",
+                    "Kithri",
+                    "Lavinia",
+                    "Merric",
+                    "Nedda",
+                    "Osborn",
+                    "Roscoe",
+                    "Seraphina",
+                    "Verna",
+                    "Wellby",
+                ],
+                &[
+                    "Brushgather",
+                    "Goodbarrel",
+                    "Greenbottle",
+                    "High-hill",
+                    "Hilltopple",
+                    "Leagallow",
+                    "Tealeaf",
+                    "Thorngage",
+                    "Tosscobble",
+                    "Underbough",
+                ],
+            ),
+            Self::Gnome => (
+                &[
+                    "Alston",
+                    "Bimpnottin",
+                    "Boddynock",
+                    "Caramip",
+                    "Dimble",
+                    "Ellyjobell",
+                    "Fonkin",
+                    "Nissa",
+                    "Orryn",
+                    "Roywyn",
+                    "Seebo",
+                    "Waywocket",
+                    "Zook",
+                ],
+                &[
+                    "Beren", "Daergel", "Folkor", "Garrick", "Nackle", "Murnig", "Ningel",
+                    "Raulnor", "Scheppen", "Timbers", "Turen",
+                ],
+            ),
+            Self::Orc => (
+                &[
+                    "Baggi", "Dench", "Emen", "Engong", "Feng", "Gell", "Holg", "Imsh", "Kansif",
+                    "Myev", "Ront", "Shump", "Sutha", "Thokk", "Vola", "Yevelda",
+                ],
+                &[
+                    "Bonebreaker",
+                    "Dirtfoot",
+                    "Gorefang",
+                    "Ironhide",
+                    "Skullsplitter",
+                    "Stoneback",
+                    "Tuskwhite",
+                ],
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Culture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Human => write!(f, "Human"),
+            Self::Elf => write!(f, "Elf"),
+            Self::Dwarf => write!(f, "Dwarf"),
+            Self::Halfling => write!(f, "Halfling"),
+            Self::Gnome => write!(f, "Gnome"),
+            Self::Orc => write!(f, "Orc"),
+        }
+    }
+}
+
+const OCCUPATIONS: [&str; 20] = [
+    "Innkeeper",
+    "Blacksmith",
+    "Merchant",
+    "Guard",
+    "Farmer",
+    "Priest",
+    "Sailor",
+    "Scholar",
+    "Beggar",
+    "Noble",
+    "Hunter",
+    "Bard",
+    "Fence",
+    "Herbalist",
+    "Stablehand",
+    "Miner",
+    "Tax collector",
+    "Ferryman",
+    "Gravedigger",
+    "Cartographer",
+];
+
+const QUIRKS: [&str; 20] = [
+    "Hums tunelessly while thinking",
+    "Never makes eye contact",
+    "Speaks about themselves in the third person",
+    "Constantly fidgets with a coin",
+    "Laughs at their own jokes before the punchline",
+    "Whispers everything, even good news",
+    "Sniffs food before every bite",
+    "Collects buttons and asks everyone for theirs",
+    "Quotes a proverb that doesn't quite fit",
+    "Taps the table twice before answering",
+    "Mispronounces the party's names, differently each time",
+    "Apologises far too often",
+    "Keeps a pet mouse in their breast pocket",
+    "Counts everything out loud",
+    "Bites their nails down to nothing",
+    "Ends every sentence like a question?",
+    "Always sits facing the door",
+    "Uses enormous words, slightly wrong",
+    "Is convinced it's about to rain",
+    "Gives everyone a nickname",
+];
+
+const TRAITS: [&str; 16] = [
+    "Friendly and far too trusting",
+    "Suspicious of anyone from out of town",
+    "Greedy, but honest about it",
+    "Cowardly until someone they love is threatened",
+    "Proud and easily offended",
+    "Cheerful no matter what",
+    "Blunt to the point of rudeness",
+    "Nosy and loves to gossip",
+    "Deeply religious",
+    "Bored and looking for excitement",
+    "Grieving a recent loss",
+    "Ambitious and always scheming",
+    "Lazy but surprisingly clever",
+    "Kind to animals, cold to people",
+    "Terrified of magic",
+    "Owes money to the wrong people",
+];
+
+/// A character made up on the spot, enough to play them and remember them later
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpcStub {
+    pub name: String,
+    pub culture: Culture,
+    pub occupation: String,
+    pub quirk: String,
+    pub personality: String,
+    /// Anything the GM adds before saving, ie. what they told the party
+    pub notes: String,
+}
+
+fn pick(list: &[&str]) -> String {
+    list.choose(&mut rand::rng())
+        .copied()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// A full name from the culture's lists
+pub fn random_name(culture: Culture) -> String {
+    let (given, family) = culture.names();
+    format!("{} {}", pick(given), pick(family))
+}
+
+pub fn random_occupation() -> String {
+    pick(&OCCUPATIONS)
+}
+
+pub fn random_quirk() -> String {
+    pick(&QUIRKS)
+}
+
+pub fn random_personality() -> String {
+    pick(&TRAITS)
+}
+
+impl NpcStub {
+    pub fn generate(culture: Culture) -> Self {
+        Self {
+            name: random_name(culture),
+            culture,
+            occupation: random_occupation(),
+            quirk: random_quirk(),
+            personality: random_personality(),
+            notes: String::new(),
+        }
+    }
+
+    /// There are no NPC entities yet, so they're kept as a hidden handout for the GM
+    pub fn to_handout(&self) -> Handout {
+        let mut body = format!(
+            "/{} {}/\n\n*Personality* {}\n*Quirk* {}\n",
+            self.culture,
+            self.occupation.to_lowercase(),
+            self.personality,
+            self.quirk
+        );
+        if !self.notes.trim().is_empty() {
+            body.push_str(&format!("\n{}\n", self.notes.trim()));
+        }
+
+        Handout {
+            title: self.name.trim().to_owned(),
+            body,
+            image_url: None,
+            visibility: HandoutVisibility::Hidden,
+        }
+    }
+}
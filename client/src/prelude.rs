@@ -1,5 +1,4 @@
 pub use log::{debug, error, info, warn};
-pub use message_io::events::EventSender;
 
 pub use common::message::*;
 pub use common::Item;
@@ -9,6 +8,6 @@ pub use egui::{text::LayoutJob, Color32, Layout, RichText, TextureId, Ui, Widget
 pub use emath::{Pos2, Rect, RectTransform, Vec2};
 
 pub use crate::{
-    listener::{Command, Signal},
+    listener::{Command, EventSender, Signal},
     state::DndState,
 };
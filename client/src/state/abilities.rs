@@ -1,4 +1,7 @@
 pub mod commands {
+    use common::{Cooldown, EffectTarget};
+    use uuid::Uuid;
+
     use crate::prelude::*;
 
     pub struct SetAbilityCount {
@@ -42,11 +45,42 @@ pub mod commands {
                     .into(),
                 );
 
+                // Recharging abilities go on cooldown once spent and come off it when reset
+                if let Some(recharge) = ability.recharge {
+                    let target = EffectTarget::Character(user.name.clone());
+                    let spent = ability.uses < ability.max_count;
+                    match state.effects.cooldown_for(&target, &ability.name) {
+                        None if spent => {
+                            let cooldown = Cooldown::new(
+                                ability.name.clone(),
+                                recharge,
+                                Some(ability.max_count),
+                                target,
+                            );
+                            tx.send(
+                                DndMessage::EffectMessage(EffectMessage::SetCooldown(
+                                    Uuid::new_v4(),
+                                    cooldown,
+                                ))
+                                .into(),
+                            );
+                        }
+                        Some((uuid, _)) if !spent => {
+                            tx.send(
+                                DndMessage::EffectMessage(EffectMessage::RemoveCooldown(*uuid))
+                                    .into(),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+
                 // Send Log Message
                 tx.send(
                     DndMessage::Log(
                         user,
                         LogMessage::SetAbilityCount(ability.name.clone(), self.count),
+                        None,
                     )
                     .into(),
                 );
@@ -87,6 +121,7 @@ pub mod commands {
                     DndMessage::Log(
                         user,
                         LogMessage::SetAbilityCount(ability.name.clone(), self.count),
+                        None,
                     )
                     .into(),
                 );
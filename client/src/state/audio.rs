@@ -0,0 +1,585 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{
+        mpsc::{channel, Receiver, TryRecvError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::soundboard::{NowPlaying, Soundboard};
+use egui::load::BytesPoll;
+use rodio::{source::SineWave, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use web_time::Instant;
+
+use crate::prelude::*;
+
+/// How often playlists are checked for the next track to queue up
+const MUSIC_POLL: Duration = Duration::from_millis(500);
+
+/// Tracks that haven't been played for this long are dropped, and fetched
+/// again if they're wanted
+const TRACK_CACHE_TIME: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct EventSound {
+    pub enabled: bool,
+    pub volume: f32,
+}
+
+impl Default for EventSound {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct AudioSettings {
+    pub muted: bool,
+    pub chat: EventSound,
+    pub roll: EventSound,
+    pub joined: EventSound,
+    /// The GM's soundboard
+    pub music: EventSound,
+    pub effects: EventSound,
+}
+
+#[derive(Clone, Copy)]
+enum AudioCue {
+    Chat,
+    Roll,
+    Joined,
+    Fanfare,
+}
+
+impl AudioCue {
+    /// (frequency, length in ms) of each note in the cue
+    fn notes(self) -> &'static [(f32, u64)] {
+        match self {
+            AudioCue::Chat => &[(880.0, 60)],
+            AudioCue::Roll => &[(523.0, 50), (659.0, 50), (784.0, 80)],
+            AudioCue::Joined => &[(440.0, 80), (660.0, 120)],
+            AudioCue::Fanfare => &[(523.0, 80), (659.0, 80), (784.0, 80), (1047.0, 200)],
+        }
+    }
+}
+
+/// A soundboard track's file. It's kept as it was downloaded and decoded
+/// while it plays, a decoded song would take hundreds of MB
+#[derive(Clone)]
+struct Track {
+    bytes: Arc<[u8]>,
+    duration: Duration,
+}
+
+impl Track {
+    /// Reads through the file once to check it can be played and, if its
+    /// header doesn't say, find out how long it is
+    fn decode(bytes: Arc<[u8]>) -> Result<Self, String> {
+        let decoder = Decoder::new(Cursor::new(bytes.clone())).map_err(|e| e.to_string())?;
+        let duration = match decoder.total_duration() {
+            Some(duration) => duration,
+            None => {
+                let rate = decoder.channels().max(1) as f64 * decoder.sample_rate().max(1) as f64;
+                Duration::from_secs_f64(decoder.count() as f64 / rate)
+            }
+        };
+        Ok(Self { bytes, duration })
+    }
+
+    /// Plays from `start` into the track
+    fn source(&self, start: Duration) -> Option<impl Source<Item = f32> + Send + 'static> {
+        let decoder = Decoder::new(Cursor::new(self.bytes.clone()))
+            .inspect_err(|e| warn!("Failed to play sound: {e}"))
+            .ok()?;
+        Some(decoder.skip_duration(start).convert_samples())
+    }
+}
+
+/// Which track is playing `offset` seconds into the playlist, and how far
+/// into it. Rounding can leave the offset a sliver past the end, that's the
+/// start again
+fn track_at(durations: &[Duration], mut offset: f64) -> (usize, f64) {
+    for (i, duration) in durations.iter().enumerate() {
+        let duration = duration.as_secs_f64();
+        if offset < duration {
+            return (i, offset);
+        }
+        offset -= duration;
+    }
+    (0, 0.0)
+}
+
+/// A soundboard track as it's fetched and decoded
+enum TrackAudio {
+    Loading,
+    Decoding(Receiver<Result<Track, String>>),
+    Ready(Track),
+    Failed,
+}
+
+struct CachedTrack {
+    audio: TrackAudio,
+    /// Last time it was wanted, old ones are dropped
+    used: Instant,
+}
+
+/// A change in volume over time, as a factor of the music volume setting
+struct Fade {
+    from: f32,
+    to: f32,
+    started: Instant,
+    secs: f32,
+}
+
+impl Fade {
+    fn new(from: f32, to: f32, secs: f32) -> Self {
+        Self {
+            from,
+            to,
+            started: Instant::now(),
+            secs,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.started.elapsed().as_secs_f32() >= self.secs
+    }
+
+    fn level(&self) -> f32 {
+        if self.done() {
+            return self.to;
+        }
+        let t = self.started.elapsed().as_secs_f32() / self.secs;
+        self.from + (self.to - self.from) * t
+    }
+}
+
+struct Music {
+    playing: NowPlaying,
+    /// When the music was started, time spent loading it is skipped like
+    /// when joining part way through
+    received: Instant,
+    /// `None` until every track has loaded
+    sink: Option<Sink>,
+    tracks: Vec<Track>,
+    /// The track to queue up next
+    next: usize,
+    fade: Fade,
+}
+
+pub struct AudioState {
+    pub settings: AudioSettings,
+    // The stream has to be kept alive for the handle to keep working
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    /// Soundboard tracks by their source, loaded the first time they're played
+    tracks: HashMap<String, CachedTrack>,
+    music: Option<Music>,
+    /// Music on its way out, dropped once it's silent
+    fading_out: Vec<(Sink, Fade)>,
+    /// Effects waiting on their track to load
+    effects: Vec<String>,
+}
+
+impl Default for AudioState {
+    fn default() -> Self {
+        let output = OutputStream::try_default()
+            .inspect_err(|e| warn!("No audio output available: {e}"))
+            .ok();
+
+        Self {
+            settings: AudioSettings::default(),
+            output,
+            tracks: HashMap::new(),
+            music: None,
+            fading_out: Vec::new(),
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl AudioState {
+    pub fn process(&mut self, message: &DndMessage, user: Option<&User>) {
+        if let DndMessage::SoundMessage(msg) = message {
+            self.process_sound(msg);
+            return;
+        }
+
+        let DndMessage::Log(from, msg, _) = message else {
+            return;
+        };
+
+        // Don't play sounds for our own messages
+        if user.is_some_and(|x| x.name == from.name) {
+            return;
+        }
+
+        let cue = match msg {
+            LogMessage::Chat(_)
+            | LogMessage::NpcChat(..)
+            | LogMessage::Emote(..)
+            | LogMessage::BreakReminder(_) => AudioCue::Chat,
+            LogMessage::Roll(_)
+            | LogMessage::Attack(_)
+            | LogMessage::AreaDamage(..)
+            | LogMessage::TableRoll(_) => AudioCue::Roll,
+            LogMessage::Joined(_) => AudioCue::Joined,
+            LogMessage::InspirationGranted(..) | LogMessage::SessionSummary(_) => AudioCue::Fanfare,
+            _ => return,
+        };
+
+        self.play(cue);
+    }
+
+    fn process_sound(&mut self, msg: &SoundMessage) {
+        match msg {
+            SoundMessage::Play(playing) => {
+                self.fade_out_music(playing.fade);
+                self.music = Some(Music {
+                    playing: playing.clone(),
+                    received: Instant::now(),
+                    sink: None,
+                    tracks: Vec::new(),
+                    next: 0,
+                    fade: Fade::new(0.0, 1.0, playing.fade),
+                });
+            }
+            SoundMessage::Stop(secs) => self.fade_out_music(*secs),
+            SoundMessage::Effect(name) => self.effects.push(name.clone()),
+            SoundMessage::SetSoundboard(_) => {}
+        }
+    }
+
+    fn fade_out_music(&mut self, secs: f32) {
+        let Some(music) = self.music.take() else {
+            return;
+        };
+        if let Some(sink) = music.sink {
+            let fade = Fade::new(music.fade.level(), 0.0, secs);
+            self.fading_out.push((sink, fade));
+        }
+    }
+
+    /// Whether the track couldn't be loaded or isn't a format that can be played
+    pub fn failed(&self, source: &str) -> bool {
+        matches!(
+            self.tracks.get(source),
+            Some(CachedTrack {
+                audio: TrackAudio::Failed,
+                ..
+            })
+        )
+    }
+
+    /// Tracks are loaded the first time they're played, `None` is returned
+    /// until they're ready or have failed to load
+    fn load(&mut self, ctx: &egui::Context, source: &str) -> Option<Option<Track>> {
+        let cached = self
+            .tracks
+            .entry(source.to_owned())
+            .or_insert_with(|| CachedTrack {
+                audio: TrackAudio::Loading,
+                used: Instant::now(),
+            });
+        cached.used = Instant::now();
+        let audio = &mut cached.audio;
+
+        if let TrackAudio::Loading = audio {
+            // Anything that isn't a URL is a file on this computer
+            let uri = match source.contains("://") {
+                true => source.to_owned(),
+                false => format!("file://{source}"),
+            };
+            match ctx.try_load_bytes(&uri) {
+                Ok(BytesPoll::Pending { .. }) => {}
+                Ok(BytesPoll::Ready { bytes, .. }) => {
+                    let bytes: Arc<[u8]> = bytes.to_vec().into();
+                    // Our copy is all that's kept
+                    ctx.forget_image(&uri);
+
+                    let (tx, rx) = channel();
+                    // Browsers have no threads, the page waits on the decoding instead
+                    #[cfg(target_arch = "wasm32")]
+                    let _ = tx.send(Track::decode(bytes));
+                    #[cfg(not(target_arch = "wasm32"))]
+                    std::thread::spawn(move || {
+                        let _ = tx.send(Track::decode(bytes));
+                    });
+                    *audio = TrackAudio::Decoding(rx);
+                }
+                Err(e) => {
+                    warn!("Failed to load sound '{source}': {e}");
+                    *audio = TrackAudio::Failed;
+                }
+            }
+        }
+
+        if let TrackAudio::Decoding(rx) = audio {
+            match rx.try_recv() {
+                Ok(Ok(track)) => *audio = TrackAudio::Ready(track),
+                Ok(Err(e)) => {
+                    warn!("Failed to decode sound '{source}': {e}");
+                    *audio = TrackAudio::Failed;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => *audio = TrackAudio::Failed,
+            }
+        }
+
+        match audio {
+            TrackAudio::Ready(track) => Some(Some(track.clone())),
+            TrackAudio::Failed => Some(None),
+            TrackAudio::Loading | TrackAudio::Decoding(_) => None,
+        }
+    }
+
+    /// How loud the soundboard plays, with mute and the sound's own toggle taken into account
+    fn volume(&self, sound: EventSound) -> f32 {
+        match self.settings.muted || !sound.enabled {
+            true => 0.0,
+            false => sound.volume,
+        }
+    }
+
+    /// Keeps the soundboard playing, called every frame
+    pub fn update(&mut self, ctx: &egui::Context, soundboard: &Soundboard) {
+        if self.output.is_none() {
+            return;
+        }
+
+        let loading = self.start_music(ctx, soundboard) | self.play_effects(ctx, soundboard);
+        if loading {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        // The music keeps its own copy of the tracks it's playing
+        self.tracks
+            .retain(|_, x| x.used.elapsed() < TRACK_CACHE_TIME);
+
+        let volume = self.volume(self.settings.music);
+        self.fading_out.retain(|(sink, fade)| {
+            sink.set_volume(volume * fade.level());
+            !fade.done()
+        });
+
+        let Some(music) = &mut self.music else {
+            if !self.fading_out.is_empty() {
+                ctx.request_repaint();
+            }
+            return;
+        };
+        let Some(sink) = &music.sink else {
+            return;
+        };
+
+        sink.set_volume(volume * music.fade.level());
+        // Two tracks are kept queued so there's no gap between them
+        while sink.len() < 2 {
+            let track = &music.tracks[music.next % music.tracks.len()];
+            music.next += 1;
+            match track.source(Duration::ZERO) {
+                Some(source) => sink.append(source),
+                None => break,
+            }
+        }
+
+        if music.fade.done() && self.fading_out.is_empty() {
+            ctx.request_repaint_after(MUSIC_POLL);
+        } else {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Starts the music once its tracks have loaded. Returns whether it's still loading
+    fn start_music(&mut self, ctx: &egui::Context, soundboard: &Soundboard) -> bool {
+        let Some(music) = &self.music else {
+            return false;
+        };
+        if music.sink.is_some() {
+            return false;
+        }
+
+        let sources: Vec<String> = music
+            .playing
+            .tracks
+            .iter()
+            .filter_map(|x| soundboard.track(x))
+            .map(|x| x.source.clone())
+            .collect();
+        let loaded: Option<Vec<_>> = sources.iter().map(|x| self.load(ctx, x)).collect();
+        let Some(loaded) = loaded else {
+            return true;
+        };
+
+        let tracks: Vec<Track> = loaded.into_iter().flatten().collect();
+        let durations: Vec<Duration> = tracks.iter().map(|x| x.duration).collect();
+        let total: f64 = durations.iter().map(Duration::as_secs_f64).sum();
+        let Some((_, handle)) = &self.output else {
+            return false;
+        };
+        let sink = match Sink::try_new(handle) {
+            Ok(sink) if total > 0.0 => sink,
+            Ok(_) => {
+                self.music = None;
+                return false;
+            }
+            Err(e) => {
+                warn!("Failed to play music: {e}");
+                self.music = None;
+                return false;
+            }
+        };
+
+        let Some(music) = &mut self.music else {
+            return false;
+        };
+        // Everyone hears the same part of the playlist, however late they joined
+        let elapsed = music.playing.elapsed as f64 + music.received.elapsed().as_secs_f64();
+        let (next, offset) = track_at(&durations, elapsed % total);
+
+        sink.set_volume(0.0);
+        if let Some(source) = tracks[next].source(Duration::from_secs_f64(offset)) {
+            sink.append(source);
+        }
+        music.sink = Some(sink);
+        music.tracks = tracks;
+        music.next = next + 1;
+        music.fade.started = Instant::now();
+        false
+    }
+
+    /// Plays the effects whose tracks have loaded. Returns whether any are still loading
+    fn play_effects(&mut self, ctx: &egui::Context, soundboard: &Soundboard) -> bool {
+        let volume = self.volume(self.settings.effects);
+        let mut waiting = Vec::new();
+
+        for name in std::mem::take(&mut self.effects) {
+            let Some(track) = soundboard.track(&name) else {
+                warn!("There's no sound called '{name}' on the soundboard");
+                continue;
+            };
+            let track = match self.load(ctx, &track.source) {
+                Some(Some(track)) => track,
+                Some(None) => continue,
+                None => {
+                    waiting.push(name);
+                    continue;
+                }
+            };
+
+            let Some(source) = track.source(Duration::ZERO) else {
+                continue;
+            };
+            if let Some((_, handle)) = &self.output {
+                if let Err(e) = handle.play_raw(source.amplify(volume)) {
+                    warn!("Failed to play sound effect: {e}");
+                }
+            }
+        }
+
+        self.effects = waiting;
+        !self.effects.is_empty()
+    }
+
+    fn play(&self, cue: AudioCue) {
+        let sound = match cue {
+            AudioCue::Chat => self.settings.chat,
+            AudioCue::Roll => self.settings.roll,
+            AudioCue::Joined => self.settings.joined,
+            // Rare enough that it isn't worth its own setting
+            AudioCue::Fanfare => self.settings.chat,
+        };
+
+        if self.settings.muted || !sound.enabled {
+            return;
+        }
+
+        let Some((_, handle)) = &self.output else {
+            return;
+        };
+
+        let mut delay = Duration::ZERO;
+        for &(freq, length) in cue.notes() {
+            let length = Duration::from_millis(length);
+            let note = SineWave::new(freq)
+                .take_duration(length)
+                .amplify(sound.volume * 0.2)
+                .delay(delay);
+
+            if let Err(e) = handle.play_raw(note) {
+                warn!("Failed to play audio cue: {e}");
+                return;
+            }
+
+            delay += length;
+        }
+    }
+}
+
+pub mod commands {
+    use super::AudioSettings;
+    use crate::prelude::*;
+
+    pub struct SetAudioSettings(pub AudioSettings);
+
+    impl Command for SetAudioSettings {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.audio.settings = self.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{track_at, Track};
+
+    /// `frames` of 16 bit mono silence
+    fn wav(sample_rate: u32, frames: u32) -> Vec<u8> {
+        let data = frames * 2;
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((36 + data).to_le_bytes());
+        bytes.extend(b"WAVEfmt \x10\0\0\0");
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(sample_rate.to_le_bytes());
+        bytes.extend((sample_rate * 2).to_le_bytes());
+        bytes.extend(2u16.to_le_bytes());
+        bytes.extend(16u16.to_le_bytes());
+        bytes.extend(b"data");
+        bytes.extend(data.to_le_bytes());
+        bytes.extend(vec![0; data as usize]);
+        bytes
+    }
+
+    #[test]
+    fn tracks_know_how_long_they_are() {
+        let track = Track::decode(wav(8000, 4000).into()).unwrap();
+        assert_eq!(track.duration, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn files_that_arent_audio_are_refused() {
+        assert!(Track::decode(b"not a sound at all".to_vec().into()).is_err());
+    }
+
+    #[test]
+    fn tracks_start_part_way_in() {
+        let track = Track::decode(wav(4, 4).into()).unwrap();
+        let source = track.source(Duration::from_millis(500)).unwrap();
+        assert_eq!(source.count(), 2);
+    }
+
+    #[test]
+    fn the_playlist_offset_picks_the_track_it_lands_in() {
+        let durations = [Duration::from_secs(1), Duration::from_secs(2)];
+        assert_eq!(track_at(&durations, 0.0), (0, 0.0));
+        assert_eq!(track_at(&durations, 1.5), (1, 0.5));
+        // Past the end from rounding, rather than past the last track
+        assert_eq!(track_at(&durations, 3.0), (0, 0.0));
+    }
+}
@@ -1,36 +1,187 @@
 use std::cmp;
 
-use common::SortingLayer;
-use egui::{ahash::HashMap, Image, Painter, Rounding, Stroke, TextureHandle, TextureOptions};
+use common::{
+    ruleset::Diagonals, Ambience, Annotation, AnnotationShape, BoardInfo, CampaignDate, GridKind,
+    GridSettings, LabelStyle, PieceGroups, PieceStatus, PieceVisibility, Portal, SortingLayer,
+    Wall, MAIN_BOARD,
+};
+use egui::{ahash::HashMap, load::TexturePoll, Image, Painter, Rounding, Stroke, TextureHandle};
 use itertools::Itertools;
 use uuid::Uuid;
+use web_time::{Duration, Instant};
 
-use crate::{prelude::*, view::Board};
+use crate::{image_cache, prelude::*, state::theme};
+
+const MOVE_ANIMATION_SECS: f32 = 0.2;
+const TRAIL_SECS: f32 = 1.0;
+pub const FLOATING_NUMBER_SECS: f32 = 1.5;
+/// Drags are shown locally every frame but only sent this often
+const DRAG_SEND_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Remote moves slide from where the piece was drawn to its new position
+pub struct PieceAnimation {
+    from: Pos2,
+    started: Instant,
+}
+
+/// HP change that drifts up from a token for a moment after it happens
+pub struct FloatingNumber {
+    pub piece: Uuid,
+    /// Negative for damage
+    pub amount: i32,
+    pub created: Instant,
+}
+
+impl FloatingNumber {
+    /// How far through its life the number is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        (self.created.elapsed().as_secs_f32() / FLOATING_NUMBER_SECS).min(1.0)
+    }
+}
 
 pub struct PlayerPiece {
+    pub name: String,
     pub rect: Rect,
+    pub animation: Option<PieceAnimation>,
+    /// Recent piece centers, used to draw a fading trail behind moving pieces
+    pub trail: Vec<(Pos2, Instant)>,
     pub image_url: Option<String>,
     pub color: Option<Color32>,
     pub dragged: bool,
+    /// Where our own drag has the piece, running ahead of `rect` which only
+    /// follows what's been sent over the network
+    pub predicted: Option<Pos2>,
     pub selected: bool,
     pub sorting_layer: SortingLayer,
-    pub visible_by: Vec<String>,
+    pub visibility: PieceVisibility,
     pub locked: bool,
+    pub board: Uuid,
+    pub owners: Vec<String>,
+    /// Vision radius in grid squares
+    pub vision: Option<f32>,
+    pub link_stats_to: Option<String>,
+    pub statuses: Vec<PieceStatus>,
+    pub label: Option<LabelStyle>,
+    /// Someone dragging or editing the piece, nobody else can change it until they let go
+    pub holder: Option<String>,
 }
 
 impl PlayerPiece {
+    /// Where the piece should be drawn. Runs ahead of `rect` while we're
+    /// dragging it and lags behind while animating someone else's move
+    pub fn display_rect(&self) -> Rect {
+        if self.predicted.is_some() {
+            return self.predicted_rect();
+        }
+
+        let Some(animation) = &self.animation else {
+            return self.rect;
+        };
+
+        let t = (animation.started.elapsed().as_secs_f32() / MOVE_ANIMATION_SECS).min(1.0);
+        let eased = 1.0 - (1.0 - t) * (1.0 - t);
+        let pos = animation.from.lerp(self.rect.left_top(), eased);
+
+        Rect::from_min_size(pos, self.rect.size())
+    }
+
+    /// Where the piece is as far as this client knows, including our own drag
+    pub fn predicted_rect(&self) -> Rect {
+        let pos = self.predicted.unwrap_or(self.rect.left_top());
+        Rect::from_min_size(pos, self.rect.size())
+    }
+
+    /// Character the piece stands in for, the one it's linked to or else the
+    /// one it's named after
+    pub fn stats_name(&self) -> &str {
+        self.link_stats_to.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn is_animating(&self) -> bool {
+        let moving = self
+            .animation
+            .as_ref()
+            .is_some_and(|x| x.started.elapsed().as_secs_f32() < MOVE_ANIMATION_SECS);
+        let trailing = self
+            .trail
+            .last()
+            .is_some_and(|(_, time)| time.elapsed().as_secs_f32() < TRAIL_SECS);
+
+        moving || trailing
+    }
+
+    fn move_to(&mut self, new_pos: Pos2) {
+        let new_rect = Rect::from_min_size(new_pos, self.rect.size());
+
+        // Our own drags are drawn from the prediction, which is reconciled on drop
+        if self.dragged || new_rect == self.rect {
+            self.rect = new_rect;
+            return;
+        }
+
+        let from = self.display_rect();
+        let now = Instant::now();
+
+        self.trail
+            .retain(|(_, time)| time.elapsed().as_secs_f32() < TRAIL_SECS);
+        if self.trail.is_empty() {
+            self.trail.push((from.center(), now));
+        }
+        self.trail.push((new_rect.center(), now));
+
+        self.animation = Some(PieceAnimation {
+            from: from.left_top(),
+            started: now,
+        });
+        self.rect = new_rect;
+    }
+
+    pub fn draw_trail(&self, painter: &Painter, to_screen: RectTransform) {
+        for ((start, _), (end, time)) in self.trail.iter().tuple_windows() {
+            let age = time.elapsed().as_secs_f32() / TRAIL_SECS;
+            if age >= 1.0 {
+                continue;
+            }
+
+            let alpha = ((1.0 - age) * 160.0) as u8;
+            painter.line_segment(
+                [to_screen * *start, to_screen * *end],
+                Stroke::new(3.0, Color32::from_rgba_unmultiplied(255, 255, 255, alpha)),
+            );
+        }
+    }
+
     pub fn draw_shape(&self, ui: &mut egui::Ui, painter: &Painter, to_screen: RectTransform) {
-        let transformed = to_screen.transform_rect(self.rect);
+        let transformed = to_screen.transform_rect(self.display_rect());
 
         let alpha = if self.dragged { u8::MAX / 10 } else { u8::MAX };
 
-        if let Some(url) = &self.image_url {
-            Image::new(url)
-                .texture_options(
-                    TextureOptions::LINEAR.with_mipmap_mode(Some(egui::TextureFilter::Linear)),
-                )
-                .tint(Color32::from_white_alpha(alpha))
-                .paint_at(ui, transformed);
+        if let Some(label) = self.label {
+            let [r, g, b, a] = label.color;
+            let color = Color32::from_rgba_unmultiplied(r, g, b, a.min(alpha));
+            painter.text(
+                transformed.center(),
+                egui::Align2::CENTER_CENTER,
+                &self.name,
+                egui::FontId::proportional(label.size * to_screen.scale().x),
+                color,
+            );
+        } else if let Some(url) = &self.image_url {
+            let image = Image::new(url)
+                .texture_options(image_cache::texture_options())
+                .tint(Color32::from_white_alpha(alpha));
+
+            // Stand in for the piece while the image loads, the spinner goes on top
+            let loading = image.load_for_size(ui.ctx(), transformed.size());
+            if matches!(loading, Ok(TexturePoll::Pending { .. })) {
+                painter.rect_filled(
+                    transformed,
+                    Rounding::ZERO,
+                    Color32::from_black_alpha(alpha / 2),
+                );
+            }
+
+            image.paint_at(ui, transformed);
         } else {
             painter.rect_filled(
                 transformed,
@@ -43,19 +194,46 @@ impl PlayerPiece {
             painter.rect_stroke(
                 transformed,
                 Rounding::ZERO,
-                Stroke::new(3.0, Color32::LIGHT_RED),
+                Stroke::new(3.0, theme::palette(ui.ctx()).selection),
             );
         }
     }
 
-    fn drop(&mut self) {
-        let pos = commands::snap_to_grid(self.rect.left_top());
-        self.rect = Rect::from_two_pos(pos, pos + self.rect.size());
+    pub fn to_common(&self) -> common::DndPlayerPiece {
+        common::DndPlayerPiece {
+            name: self.name.clone(),
+            position: self.rect.left_top(),
+            size: self.rect.size(),
+            image_url: self.image_url.clone(),
+            color: None,
+            sorting_layer: self.sorting_layer,
+            visibility: self.visibility.clone(),
+            locked: self.locked,
+            board: self.board,
+            owners: self.owners.clone(),
+            vision: self.vision,
+            link_stats_to: self.link_stats_to.clone(),
+            statuses: self.statuses.clone(),
+            label: self.label,
+        }
+    }
+
+    fn drop(&mut self, grid: &GridSettings) {
+        let rect = self.predicted_rect();
+        let pos = commands::snap_to_grid_for_size(grid, rect.left_top(), rect.size());
+        self.drop_at(pos);
+    }
+
+    /// Ends the drag with the piece at `pos`, ahead of the server echoing it back
+    fn drop_at(&mut self, pos: Pos2) {
+        self.rect = Rect::from_min_size(pos, self.rect.size());
+        self.predicted = None;
         self.dragged = false;
     }
 
     fn drag(&mut self) {
         self.dragged = true;
+        self.predicted = Some(self.rect.left_top());
     }
 }
 
@@ -64,12 +242,59 @@ pub struct BoardState {
     pub players: HashMap<uuid::Uuid, PlayerPiece>,
     pub dragged_id: Option<uuid::Uuid>,
     pub selected_id: Option<uuid::Uuid>,
+    /// Token our attacks are aimed at. Only kept on this client
+    pub target: Option<uuid::Uuid>,
+    pub annotations: HashMap<uuid::Uuid, Annotation>,
+    pub ambience: Ambience,
+    pub date: CampaignDate,
+    pub grid: GridSettings,
+    pub boards: HashMap<Uuid, BoardInfo>,
+    pub active_board: Uuid,
+    pub groups: PieceGroups,
+    /// Group whose pieces are being edited one at a time instead of as a unit
+    pub entered_group: Option<Uuid>,
+    /// Offsets from the dragged piece to the rest of its group
+    pub drag_offsets: Vec<(Uuid, Vec2)>,
+    /// Center of the dragged piece when it was picked up
+    pub drag_start: Option<Pos2>,
+    /// When the dragged pieces' positions were last sent
+    pub last_drag_send: Option<Instant>,
+    pub floating: Vec<FloatingNumber>,
+    /// Latest place the GM asked everyone to look at, and when
+    pub focus_hint: Option<(Uuid, Pos2, Instant)>,
+    /// (board, view center, zoom) while the GM is leading everyone's view,
+    /// and when it last came in
+    pub gm_view: Option<(Uuid, Pos2, f32, Instant)>,
 }
 
 impl BoardState {
-    const GRID_SIZE: f32 = 0.1;
-
     pub fn process(&mut self, message: &DndMessage) {
+        // The board is sent again right after
+        if let DndMessage::JournalMessage(JournalMessage::Branched(_)) = message {
+            *self = Self::default();
+            return;
+        }
+
+        if let DndMessage::Log(_, msg, _) = message {
+            match msg {
+                LogMessage::HpChanged(name, amount, _) => self.float_number(name, *amount),
+                LogMessage::AreaDamage(_, _, hits) => {
+                    for hit in hits {
+                        self.float_number(&hit.name, -hit.damage);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let DndMessage::FocusView(board, pos) = message {
+            self.focus_hint = Some((*board, *pos, Instant::now()));
+        }
+
+        if let DndMessage::GmView(view) = message {
+            self.gm_view = view.map(|(board, pos, zoom)| (board, pos, zoom, Instant::now()));
+        }
+
         let DndMessage::BoardMessage(msg) = message else {
             return;
         };
@@ -79,40 +304,260 @@ impl BoardState {
                 self.players.insert(
                     *uuid,
                     PlayerPiece {
+                        name: player.name.clone(),
                         rect: Rect::from_two_pos(player.position, player.position + player.size),
+                        animation: None,
+                        trail: Vec::new(),
                         image_url: player.image_url.clone(),
                         color: None,
                         dragged: false,
+                        predicted: None,
                         selected: false,
                         sorting_layer: player.sorting_layer,
-                        visible_by: player.visible_by.clone(),
+                        visibility: player.visibility.clone(),
                         locked: player.locked,
+                        board: player.board,
+                        owners: player.owners.clone(),
+                        vision: player.vision,
+                        link_stats_to: player.link_stats_to.clone(),
+                        statuses: player.statuses.clone(),
+                        label: player.label,
+                        holder: None,
                     },
                 );
             }
             BoardMessage::UpdatePlayerPiece(uuid, new_player) => {
                 if let Some(player) = self.players.get_mut(uuid) {
-                    player.rect = Rect::from_two_pos(
-                        new_player.position,
-                        new_player.position + new_player.size,
-                    );
+                    if player.board != new_player.board {
+                        // Sliding across from another map would look odd, just appear
+                        player.board = new_player.board;
+                        player.animation = None;
+                        player.trail.clear();
+                        player.rect = Rect::from_min_size(new_player.position, new_player.size);
+                    } else {
+                        player.rect = Rect::from_min_size(player.rect.left_top(), new_player.size);
+                        player.move_to(new_player.position);
+                    }
+                    player.name = new_player.name.clone();
                     player.image_url = new_player.image_url.clone();
                     player.sorting_layer = new_player.sorting_layer;
-                    player.visible_by = new_player.visible_by.clone();
+                    player.visibility = new_player.visibility.clone();
                     player.locked = new_player.locked;
+                    player.owners = new_player.owners.clone();
+                    player.vision = new_player.vision;
+                    player.link_stats_to = new_player.link_stats_to.clone();
+                    player.statuses = new_player.statuses.clone();
+                    player.label = new_player.label;
                 }
             }
             BoardMessage::UpdatePlayerLocation(uuid, new_pos) => {
                 if let Some(player) = self.players.get_mut(uuid) {
-                    player.rect = Rect::from_two_pos(*new_pos, *new_pos + player.rect.size());
+                    player.move_to(*new_pos);
                 }
             }
             BoardMessage::DeletePlayerPiece(uuid) => {
                 self.players.remove(uuid);
+                self.groups.retain_pieces(|x| x != uuid);
+                if self.target == Some(*uuid) {
+                    self.target = None;
+                }
+            }
+            BoardMessage::AddAnnotation(uuid, annotation) => {
+                self.annotations.insert(*uuid, annotation.clone());
+            }
+            BoardMessage::DeleteAnnotation(uuid) => {
+                self.annotations.remove(uuid);
+            }
+            BoardMessage::ClearAnnotations(board, layer) => {
+                self.annotations.retain(|_, annotation| {
+                    annotation.board != *board || annotation.layer != *layer
+                });
+            }
+            BoardMessage::SetAmbience(ambience) => {
+                self.ambience = *ambience;
+            }
+            BoardMessage::SetCampaignDate(date) => {
+                self.date = *date;
+            }
+            BoardMessage::SetGrid(grid) => {
+                self.grid = *grid;
+            }
+            BoardMessage::AdvanceTime(minutes) => {
+                self.date = self.date.advanced(*minutes);
+            }
+            BoardMessage::CreateBoard(uuid, name) => {
+                self.boards.insert(
+                    *uuid,
+                    BoardInfo {
+                        name: name.clone(),
+                        ..Default::default()
+                    },
+                );
+            }
+            BoardMessage::DeleteBoard(uuid) => {
+                self.boards.remove(uuid);
+                self.players.retain(|_, x| x.board != *uuid);
+                self.groups.retain_pieces(|x| self.players.contains_key(x));
+                self.annotations.retain(|_, x| x.board != *uuid);
+                for board in self.boards.values_mut() {
+                    board.portals.retain(|_, x| x.target_board != *uuid);
+                }
+                if self.active_board == *uuid {
+                    self.active_board = MAIN_BOARD;
+                }
+                self.selected_id = self.selected_id.filter(|x| self.players.contains_key(x));
+                self.target = self.target.filter(|x| self.players.contains_key(x));
+                self.dragged_id = self.dragged_id.filter(|x| self.players.contains_key(x));
+            }
+            BoardMessage::SetActiveBoard(uuid) => {
+                self.active_board = *uuid;
+                self.unselect_other_player();
+            }
+            BoardMessage::AddPortal(board, uuid, portal) => {
+                if let Some(board) = self.boards.get_mut(board) {
+                    board.portals.insert(*uuid, *portal);
+                }
+            }
+            BoardMessage::DeletePortal(board, uuid) => {
+                if let Some(board) = self.boards.get_mut(board) {
+                    board.portals.remove(uuid);
+                }
+            }
+            BoardMessage::AddWall(board, uuid, wall) => {
+                if let Some(board) = self.boards.get_mut(board) {
+                    board.walls.insert(*uuid, *wall);
+                }
+            }
+            BoardMessage::DeleteWall(board, uuid) => {
+                if let Some(board) = self.boards.get_mut(board) {
+                    board.walls.remove(uuid);
+                }
+            }
+            BoardMessage::SetDoorOpen(board, uuid, open) => {
+                if let Some(wall) = self
+                    .boards
+                    .get_mut(board)
+                    .and_then(|x| x.walls.get_mut(uuid))
+                {
+                    wall.door = wall.door.map(|_| *open);
+                }
+            }
+            BoardMessage::GroupPieces(uuid, pieces) => {
+                self.groups.set(*uuid, pieces.clone());
             }
+            BoardMessage::Ungroup(uuid) => {
+                self.groups.remove(uuid);
+                if self.entered_group == Some(*uuid) {
+                    self.entered_group = None;
+                }
+            }
+            BoardMessage::SetHolder(uuid, holder) => {
+                if let Some(player) = self.players.get_mut(uuid) {
+                    player.holder = holder.clone();
+                }
+            }
+            // Only the server's answer matters
+            BoardMessage::HoldPiece(_) | BoardMessage::ReleasePiece(_) => {}
         }
     }
 
+    /// The pieces that act along with this one. Grouped pieces act as a unit
+    /// unless the group has been entered
+    pub fn group_members(&self, uuid: &Uuid) -> Vec<Uuid> {
+        match self.groups.group_of(uuid) {
+            Some(group) if self.entered_group != Some(group) => self
+                .groups
+                .pieces(&group)
+                .iter()
+                .filter(|x| self.players.contains_key(x))
+                .copied()
+                .collect(),
+            _ => vec![*uuid],
+        }
+    }
+
+    /// Outline around the group of the selected piece
+    pub fn selected_group_rect(&self) -> Option<Rect> {
+        let group = self.groups.group_of(&self.selected_id?)?;
+        self.groups
+            .pieces(&group)
+            .iter()
+            .filter_map(|x| self.players.get(x))
+            .map(|x| x.display_rect())
+            .reduce(|a, b| a.union(b))
+    }
+
+    pub fn board_name(&self, uuid: &Uuid) -> &str {
+        self.boards
+            .get(uuid)
+            .map(|x| x.name.as_str())
+            .unwrap_or("Unknown")
+    }
+
+    /// Pops the change up over every token on this board standing in for the character
+    fn float_number(&mut self, name: &str, amount: i32) {
+        if amount == 0 {
+            return;
+        }
+
+        self.floating.retain(|x| x.progress() < 1.0);
+
+        let pieces = self
+            .active_players()
+            .filter(|(_, x)| x.stats_name() == name)
+            .map(|(id, _)| *id)
+            .collect_vec();
+        for piece in pieces {
+            self.floating.push(FloatingNumber {
+                piece,
+                amount,
+                created: Instant::now(),
+            });
+        }
+    }
+
+    /// Pieces on the board everyone is currently looking at
+    pub fn active_players(&self) -> impl Iterator<Item = (&Uuid, &PlayerPiece)> {
+        self.players
+            .iter()
+            .filter(|(_, x)| x.board == self.active_board)
+    }
+
+    pub fn active_portals(&self) -> impl Iterator<Item = (&Uuid, &Portal)> {
+        self.boards
+            .get(&self.active_board)
+            .into_iter()
+            .flat_map(|x| x.portals.iter())
+    }
+
+    pub fn active_walls(&self) -> impl Iterator<Item = (&Uuid, &Wall)> {
+        self.boards
+            .get(&self.active_board)
+            .into_iter()
+            .flat_map(|x| x.walls.iter())
+    }
+
+    /// Closest wall or door on the active board within `tolerance` of the canvas position
+    pub fn find_wall(&self, pos: Pos2, tolerance: f32) -> Option<(&Uuid, &Wall)> {
+        self.active_walls()
+            .map(|(id, wall)| (id, wall, wall.distance_to(pos)))
+            .filter(|(_, _, distance)| *distance <= tolerance)
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(id, wall, _)| (id, wall))
+    }
+
+    /// Whether a piece moving to `new_pos` would pass through a wall that blocks movement
+    pub fn blocks_movement(&self, piece: &Uuid, new_pos: Pos2) -> bool {
+        let Some(piece) = self.players.get(piece) else {
+            return false;
+        };
+
+        let from = piece.predicted_rect();
+        self.boards
+            .get(&piece.board)
+            .is_some_and(|board| board.blocks_movement(from.center(), new_pos + from.size() / 2.0))
+    }
+
     pub fn get_player_mut(&mut self, uuid: &Uuid) -> Option<&mut PlayerPiece> {
         self.players.get_mut(uuid)
     }
@@ -121,6 +566,15 @@ impl BoardState {
         self.dragged_id.and_then(|x| self.get_player_mut(&x))
     }
 
+    /// How far the dragged piece would have moved if it were dropped now
+    pub fn drag_distance(&self, diagonals: Diagonals) -> Option<u32> {
+        let start = self.drag_start?;
+        let rect = self.players.get(&self.dragged_id?)?.predicted_rect();
+        let pos = commands::snap_to_grid_for_size(&self.grid, rect.left_top(), rect.size());
+        let end = Rect::from_min_size(pos, rect.size()).center();
+        Some(self.grid.distance(start, end, diagonals))
+    }
+
     pub fn unselect_other_player(&mut self) {
         for player in self.players.values_mut() {
             if player.selected {
@@ -132,8 +586,7 @@ impl BoardState {
 
     pub fn find_selected_player_id(&self, pointer_pos: Pos2) -> Option<&Uuid> {
         for (id, player) in self
-            .players
-            .iter()
+            .active_players()
             .sorted_by_key(|x| cmp::Reverse(x.1.sorting_layer))
         {
             if player.rect.contains(pointer_pos) {
@@ -153,13 +606,52 @@ impl BoardState {
     pub fn get_position(&self, uuid: &Uuid) -> Option<Pos2> {
         self.players.get(uuid).map(|x| x.rect.left_top())
     }
+
+    /// Topmost annotation `user` can see and erase within `tolerance` of the
+    /// given canvas position. Text is drawn at a fixed screen size, so `text_size` measures
+    /// it in canvas units
+    pub fn find_annotation(
+        &self,
+        pos: Pos2,
+        tolerance: f32,
+        user: &str,
+        is_gm: bool,
+        text_size: impl Fn(&str) -> Vec2,
+    ) -> Option<&Uuid> {
+        self.annotations
+            .iter()
+            .filter(|(_, x)| x.board == self.active_board && x.visible_to(user))
+            .filter(|(_, x)| is_gm || x.author == user)
+            .sorted_by_key(|x| cmp::Reverse(x.1.layer))
+            .find(|(_, annotation)| {
+                let bounds = match &annotation.shape {
+                    AnnotationShape::Text(at, text) => Rect::from_min_size(*at, text_size(text)),
+                    shape => shape.bounding_rect(),
+                };
+                bounds.expand(tolerance).contains(pos)
+            })
+            .map(|(id, _)| id)
+    }
+
+    /// Topmost area of effect template covering the given canvas position
+    pub fn find_template(&self, pos: Pos2) -> Option<&Uuid> {
+        self.annotations
+            .iter()
+            .filter(|(_, x)| x.board == self.active_board && x.shape.is_template())
+            .sorted_by_key(|x| cmp::Reverse(x.1.layer))
+            .find(|(_, x)| x.shape.overlaps(Rect::from_min_max(pos, pos)))
+            .map(|(id, _)| id)
+    }
 }
 
 pub mod commands {
     use common::SortingLayer;
 
     use super::*;
-    use crate::{prelude::*, view::Board};
+    use crate::{
+        board_file::{self, BoardFile},
+        storage,
+    };
 
     pub struct SetPlayerPosition {
         id: Uuid,
@@ -173,31 +665,125 @@ pub mod commands {
     }
 
     impl Command for SetPlayerPosition {
-        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
-            tx.send(
-                DndMessage::BoardMessage(BoardMessage::UpdatePlayerLocation(self.id, self.new_pos))
-                    .into(),
-            );
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            // The piece stops at the wall until it's dragged back to this side
+            let blocked = std::iter::once((self.id, self.new_pos))
+                .chain(
+                    state
+                        .board
+                        .drag_offsets
+                        .iter()
+                        .map(|(id, offset)| (*id, self.new_pos + *offset)),
+                )
+                .any(|(id, pos)| state.board.blocks_movement(&id, pos));
+            if blocked && !state.is_gm() {
+                return;
+            }
+
+            // The rest of the group follows along
+            let moves = std::iter::once((self.id, self.new_pos))
+                .chain(
+                    state
+                        .board
+                        .drag_offsets
+                        .iter()
+                        .map(|(id, offset)| (*id, self.new_pos + *offset)),
+                )
+                .collect_vec();
+            for (id, pos) in moves.iter() {
+                if let Some(piece) = state.board.get_player_mut(id) {
+                    piece.predicted = Some(*pos);
+                }
+            }
+
+            // Everyone else only needs to see the piece every so often, they
+            // animate between updates anyway
+            let due = state
+                .board
+                .last_drag_send
+                .is_none_or(|x| x.elapsed() >= DRAG_SEND_INTERVAL);
+            let moved = state
+                .board
+                .get_position(&self.id)
+                .is_some_and(|x| x != self.new_pos);
+            if !due || !moved {
+                return;
+            }
+
+            state.board.last_drag_send = Some(Instant::now());
+            for (id, pos) in moves {
+                let msg = BoardMessage::UpdatePlayerLocation(id, pos);
+                tx.send(DndMessage::BoardMessage(msg).into());
+            }
         }
     }
 
     pub struct Drop;
     impl Command for Drop {
         fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let grid = state.board.grid;
+            let portal = state
+                .board
+                .get_dragged_player_mut()
+                .map(|piece| {
+                    piece.drop(&grid);
+                    piece.rect.center()
+                })
+                .and_then(|center| {
+                    state
+                        .board
+                        .active_portals()
+                        .find(|(_, portal)| portal.area.contains(center))
+                        .map(|(_, portal)| *portal)
+                });
+
             if let (Some(id), Some(piece)) =
                 (state.board.dragged_id, state.board.get_dragged_player_mut())
             {
-                piece.drop();
+                let (msg, leader_pos) = match portal {
+                    Some(portal) => {
+                        let size = piece.rect.size();
+                        let pos = snap_to_grid_for_size(&grid, portal.target - size / 2.0, size);
+                        let mut moved = piece.to_common();
+                        moved.board = portal.target_board;
+                        moved.position = pos;
+                        (BoardMessage::UpdatePlayerPiece(id, moved), pos)
+                    }
+                    None => {
+                        let pos = piece.rect.left_top();
+                        (BoardMessage::UpdatePlayerLocation(id, pos), pos)
+                    }
+                };
+                tx.send(DndMessage::BoardMessage(msg).into());
+                tx.send(DndMessage::BoardMessage(BoardMessage::ReleasePiece(id)).into());
 
-                tx.send(
-                    DndMessage::BoardMessage(BoardMessage::UpdatePlayerLocation(
-                        id,
-                        piece.rect.left_top(),
-                    ))
-                    .into(),
-                );
+                // Keep the group laid out the same way around the snapped piece
+                for (member, offset) in std::mem::take(&mut state.board.drag_offsets) {
+                    let Some(piece) = state.board.get_player_mut(&member) else {
+                        continue;
+                    };
+
+                    let pos = leader_pos + offset;
+                    piece.drop_at(pos);
+                    let msg = match portal {
+                        Some(portal) => {
+                            let mut moved = piece.to_common();
+                            moved.board = portal.target_board;
+                            moved.position = pos;
+                            BoardMessage::UpdatePlayerPiece(member, moved)
+                        }
+                        None => BoardMessage::UpdatePlayerLocation(member, pos),
+                    };
+                    tx.send(DndMessage::BoardMessage(msg).into());
+                    tx.send(DndMessage::BoardMessage(BoardMessage::ReleasePiece(member)).into());
+                }
 
                 state.board.dragged_id = None;
+                state.board.drag_start = None;
+                state.board.last_drag_send = None;
+                if portal.is_some() {
+                    state.board.unselect_other_player();
+                }
             }
         }
     }
@@ -205,10 +791,36 @@ pub mod commands {
     pub struct Drag(pub Uuid);
     impl Command for Drag {
         fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
-            if let Some(player) = state.board.get_player_mut(&self.0) {
-                player.drag();
-                state.board.dragged_id = Some(self.0);
+            let Some(leader) = state.board.get_position(&self.0) else {
+                return;
+            };
+
+            let offsets = state
+                .board
+                .group_members(&self.0)
+                .into_iter()
+                .filter(|x| *x != self.0 && state.can_control_piece(x))
+                .filter_map(|x| Some((x, state.board.get_position(&x)? - leader)))
+                .collect_vec();
+
+            // Keep everyone else's hands off the pieces until they're dropped
+            for id in offsets.iter().map(|(id, _)| id).chain([&self.0]) {
+                if let Some(player) = state.board.get_player_mut(id) {
+                    player.drag();
+                    tx.send(DndMessage::BoardMessage(BoardMessage::HoldPiece(*id)).into());
+                }
             }
+            state.board.drag_offsets = offsets;
+            state.board.drag_start = state.board.players.get(&self.0).map(|x| x.rect.center());
+            state.board.dragged_id = Some(self.0);
+        }
+    }
+
+    /// Picks the token attacks are rolled against, `None` clears it
+    pub struct SetTarget(pub Option<Uuid>);
+    impl Command for SetTarget {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.board.target = self.0;
         }
     }
 
@@ -216,6 +828,13 @@ pub mod commands {
     impl Command for Select {
         fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
             state.board.unselect_other_player();
+
+            // Picking something outside the entered group leaves it
+            let group = self.0.and_then(|x| state.board.groups.group_of(&x));
+            if state.board.entered_group.is_some() && state.board.entered_group != group {
+                state.board.entered_group = None;
+            }
+
             if let Some((idx, player)) = self
                 .0
                 .and_then(|idx| state.board.get_player_mut(&idx).map(|p| (idx, p)))
@@ -227,12 +846,17 @@ pub mod commands {
     }
 
     pub struct PieceParams {
+        pub name: String,
         pub pos: Pos2,
         pub size: Vec2,
         pub url: Option<String>,
-        pub visible_by: Vec<String>,
+        pub visibility: PieceVisibility,
+        pub owners: Vec<String>,
         pub sorting_layer: SortingLayer,
         pub locked: bool,
+        pub vision: Option<f32>,
+        pub link_stats_to: Option<String>,
+        pub label: Option<LabelStyle>,
     }
 
     pub struct AddPiece {
@@ -240,34 +864,60 @@ pub mod commands {
     }
 
     impl Command for AddPiece {
-        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
             let AddPiece {
                 params:
                     PieceParams {
+                        name,
                         pos,
                         size,
                         url,
-                        visible_by,
+                        visibility,
+                        mut owners,
                         sorting_layer,
                         locked,
+                        vision,
+                        link_stats_to,
+                        label,
                     },
             } = *self;
 
             let uuid = Uuid::new_v4();
-            let size = size * Board::GRID_SIZE;
-            let pos = snap_to_grid(pos);
+            let grid = &state.board.grid;
+            let size = size * grid.spacing;
+            let pos = snap_to_grid_for_size(grid, pos, size);
+            let board = state.board.active_board;
+
+            // Linked pieces without an image of their own use the character's portrait
+            let url = url.or_else(|| {
+                let sheet = state.character_sheet(link_stats_to.as_ref()?)?;
+                sheet.character.portrait.clone()
+            });
+
+            // Players would otherwise lose control of pieces as soon as they place them
+            let user = state.owned_user().name;
+            if !state.is_gm() && !owners.contains(&user) {
+                owners.push(user);
+            }
 
             tx.send(
                 DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
                     uuid,
                     common::DndPlayerPiece {
+                        name,
                         position: pos,
                         size,
                         image_url: url,
                         color: None,
                         sorting_layer,
-                        visible_by,
+                        visibility,
                         locked,
+                        board,
+                        owners,
+                        vision,
+                        link_stats_to,
+                        statuses: Vec::new(),
+                        label,
                     },
                 ))
                 .into(),
@@ -275,6 +925,34 @@ pub mod commands {
         }
     }
 
+    /// A one square token for our own character, centered on the square at `pos`
+    pub struct AddMyToken(pub Pos2);
+
+    impl Command for AddMyToken {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let user = state.owned_user().name;
+            let offset = Vec2::splat(state.board.grid.spacing / 2.0);
+
+            let add = AddPiece {
+                params: PieceParams {
+                    name: user.clone(),
+                    pos: self.0 - offset,
+                    size: Vec2::splat(1.0),
+                    url: None,
+                    visibility: PieceVisibility::Everyone,
+                    owners: vec![user.clone()],
+                    // Same layer as encounter monsters, above the map
+                    sorting_layer: SortingLayer(5),
+                    locked: false,
+                    vision: None,
+                    link_stats_to: Some(user),
+                    label: None,
+                },
+            };
+            Box::new(add).execute(state, tx);
+        }
+    }
+
     pub struct UpdatePiece {
         pub piece_id: Uuid,
         pub params: PieceParams,
@@ -286,29 +964,45 @@ pub mod commands {
                 piece_id,
                 params:
                     PieceParams {
+                        name,
                         pos: _pos,
                         size,
                         url,
-                        visible_by,
+                        visibility,
+                        owners,
                         sorting_layer,
                         locked,
+                        vision,
+                        link_stats_to,
+                        label,
                     },
             } = *self;
 
-            let size = size * Board::GRID_SIZE;
-            let piece_pos = snap_to_grid(state.board.get_position(&piece_id).unwrap());
+            let grid = &state.board.grid;
+            let size = size * grid.spacing;
+            let piece_pos =
+                snap_to_grid_for_size(grid, state.board.get_position(&piece_id).unwrap(), size);
+            let board = state.board.players[&piece_id].board;
+            let statuses = state.board.players[&piece_id].statuses.clone();
 
             tx.send(
                 DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(
                     piece_id,
                     common::DndPlayerPiece {
+                        name,
                         position: piece_pos,
                         size,
                         image_url: url,
                         color: None,
                         sorting_layer,
-                        visible_by,
+                        visibility,
                         locked,
+                        board,
+                        owners,
+                        vision,
+                        link_stats_to,
+                        statuses,
+                        label,
                     },
                 ))
                 .into(),
@@ -316,15 +1010,313 @@ pub mod commands {
         }
     }
 
-    pub fn snap_to_grid(pos: Pos2) -> Pos2 {
+    /// Replaces the status icons on a piece, leaving the rest of it as it is
+    pub struct SetPieceStatuses(pub Uuid, pub Vec<PieceStatus>);
+
+    impl Command for SetPieceStatuses {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let Some(piece) = state.board.players.get(&self.0) else {
+                return;
+            };
+
+            let mut piece = piece.to_common();
+            piece.statuses = self.1;
+            tx.send(
+                DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(self.0, piece)).into(),
+            );
+        }
+    }
+
+    pub fn snap_to_grid(grid: &GridSettings, pos: Pos2) -> Pos2 {
+        snap_to_step(pos, grid.spacing)
+    }
+
+    /// Pieces which aren't a whole number of squares snap to half squares so
+    /// that tiny tokens and auras can still be lined up. On hex grids the
+    /// piece is centered on the nearest hex instead.
+    pub fn snap_to_grid_for_size(grid: &GridSettings, pos: Pos2, size: Vec2) -> Pos2 {
+        if grid.kind != GridKind::Square {
+            let half = size / 2.0;
+            return grid.hex_center(pos + half) - half;
+        }
+
+        let squares = size / grid.spacing;
+        let whole = (squares - squares.round()).length() < 0.01;
+
+        if whole {
+            snap_to_grid(grid, pos)
+        } else {
+            snap_to_step(pos, grid.spacing / 2.0)
+        }
+    }
+
+    fn snap_to_step(pos: Pos2, step: f32) -> Pos2 {
         // Get back to a grid cell count
-        (pos / BoardState::GRID_SIZE).round() * BoardState::GRID_SIZE
+        (pos / step).round() * step
     }
 
-    pub struct DeletePiece(pub Uuid);
-    impl Command for DeletePiece {
+    /// Adds the annotation to the active board
+    pub struct AddAnnotation(pub Annotation);
+    impl Command for AddAnnotation {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let mut annotation = self.0;
+            annotation.board = state.board.active_board;
+            annotation.author = state.owned_user().name;
+            tx.send(
+                DndMessage::BoardMessage(BoardMessage::AddAnnotation(Uuid::new_v4(), annotation))
+                    .into(),
+            )
+        }
+    }
+
+    pub struct DeleteAnnotation(pub Uuid);
+    impl Command for DeleteAnnotation {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::DeleteAnnotation(self.0)).into())
+        }
+    }
+
+    pub struct ClearAnnotations(pub SortingLayer);
+    impl Command for ClearAnnotations {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let board = state.board.active_board;
+            tx.send(DndMessage::BoardMessage(BoardMessage::ClearAnnotations(board, self.0)).into())
+        }
+    }
+
+    pub struct SetAmbience(pub Ambience);
+    impl Command for SetAmbience {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::SetAmbience(self.0)).into())
+        }
+    }
+
+    pub struct SetGrid(pub GridSettings);
+    impl Command for SetGrid {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::SetGrid(self.0)).into())
+        }
+    }
+
+    pub struct SetCampaignDate(pub CampaignDate);
+    impl Command for SetCampaignDate {
         fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
-            tx.send(DndMessage::BoardMessage(BoardMessage::DeletePlayerPiece(self.0)).into())
+            tx.send(DndMessage::BoardMessage(BoardMessage::SetCampaignDate(self.0)).into())
+        }
+    }
+
+    /// Moves the campaign clock forward by the given number of minutes
+    pub struct AdvanceTime(pub u32);
+    impl Command for AdvanceTime {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::AdvanceTime(self.0)).into())
+        }
+    }
+
+    pub struct CreateBoard(pub String);
+    impl Command for CreateBoard {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::CreateBoard(Uuid::new_v4(), self.0);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    /// Writes the board to `<name>.board.json` in the working directory
+    pub struct ExportBoard(pub Uuid);
+    impl Command for ExportBoard {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            let Some(file) = BoardFile::from_board(&state.board, self.0) else {
+                return;
+            };
+
+            let file_name = board_file::file_name(&file.name);
+            let result = serde_json::to_string_pretty(&file)
+                .map_err(std::io::Error::other)
+                .and_then(|json| storage::write(&file_name, json));
+            match result {
+                Ok(_) => info!("Exported board to {file_name}"),
+                Err(e) => state.toasts.push("Exporting board", e),
+            }
+        }
+    }
+
+    /// Adds the board in the file as a new board, leaving the current ones alone
+    pub struct ImportBoard(pub String);
+    impl Command for ImportBoard {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let file = storage::read_to_string(&self.0)
+                .map_err(|e| e.to_string())
+                .and_then(|json| BoardFile::parse(&json));
+
+            match file {
+                Ok(file) => {
+                    info!("Importing board '{}' from {}", file.name, self.0);
+                    for msg in file.into_messages(&state.board.grid) {
+                        tx.send(DndMessage::BoardMessage(msg).into());
+                    }
+                }
+                Err(e) => state.toasts.push("Importing board", e),
+            }
+        }
+    }
+
+    pub struct DeleteBoard(pub Uuid);
+    impl Command for DeleteBoard {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::DeleteBoard(self.0)).into())
+        }
+    }
+
+    /// Asks everyone to look at this spot on the active board
+    pub struct FocusEveryone(pub Pos2);
+    impl Command for FocusEveryone {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::FocusView(state.board.active_board, self.0).into())
+        }
+    }
+
+    /// Sends where the GM is looking while they lead everyone's view, or
+    /// `None` to stop
+    pub struct ShareView(pub Option<(Pos2, f32)>);
+    impl Command for ShareView {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let board = state.board.active_board;
+            let view = self.0.map(|(pos, zoom)| (board, pos, zoom));
+            tx.send(DndMessage::GmView(view).into())
+        }
+    }
+
+    pub struct SetActiveBoard(pub Uuid);
+    impl Command for SetActiveBoard {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::SetActiveBoard(self.0)).into())
+        }
+    }
+
+    /// Adds a portal to the active board
+    pub struct AddPortal(pub Portal);
+    impl Command for AddPortal {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::AddPortal(state.board.active_board, Uuid::new_v4(), self.0);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    pub struct DeletePortal(pub Uuid);
+    impl Command for DeletePortal {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::DeletePortal(state.board.active_board, self.0);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    /// Adds a wall or door to the active board
+    pub struct AddWall(pub Wall);
+    impl Command for AddWall {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::AddWall(state.board.active_board, Uuid::new_v4(), self.0);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    pub struct DeleteWall(pub Uuid);
+    impl Command for DeleteWall {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::DeleteWall(state.board.active_board, self.0);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    /// Opens or closes a door on the active board
+    pub struct SetDoorOpen(pub Uuid, pub bool);
+    impl Command for SetDoorOpen {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::SetDoorOpen(state.board.active_board, self.0, self.1);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    /// Edits the pieces of a group one at a time, `None` goes back to moving groups as a unit
+    pub struct EnterGroup(pub Option<Uuid>);
+    impl Command for EnterGroup {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.board.entered_group = self.0;
+        }
+    }
+
+    pub struct GroupPieces(pub Vec<Uuid>);
+    impl Command for GroupPieces {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = BoardMessage::GroupPieces(Uuid::new_v4(), self.0);
+            tx.send(DndMessage::BoardMessage(msg).into())
+        }
+    }
+
+    pub struct Ungroup(pub Uuid);
+    impl Command for Ungroup {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::Ungroup(self.0)).into())
+        }
+    }
+
+    /// Claims the piece while we edit it, sending it again keeps the claim alive
+    pub struct HoldPiece(pub Uuid);
+    impl Command for HoldPiece {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::HoldPiece(self.0)).into())
+        }
+    }
+
+    pub struct ReleasePiece(pub Uuid);
+    impl Command for ReleasePiece {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::BoardMessage(BoardMessage::ReleasePiece(self.0)).into())
+        }
+    }
+
+    /// Deletes every piece we're allowed to control
+    pub struct DeletePieces(pub Vec<Uuid>);
+    impl Command for DeletePieces {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            for uuid in self.0.into_iter().filter(|x| state.can_control_piece(x)) {
+                tx.send(DndMessage::BoardMessage(BoardMessage::DeletePlayerPiece(uuid)).into())
+            }
+        }
+    }
+
+    pub enum PieceChange {
+        Locked(bool),
+        Layer(SortingLayer),
+        Visibility(PieceVisibility),
+    }
+
+    /// Applies the same change to every piece we're allowed to control
+    pub struct UpdatePieces {
+        pub ids: Vec<Uuid>,
+        pub change: PieceChange,
+    }
+
+    impl Command for UpdatePieces {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            for uuid in self.ids {
+                if !state.can_control_piece(&uuid) {
+                    continue;
+                }
+                let Some(player) = state.board.players.get(&uuid) else {
+                    continue;
+                };
+
+                let mut piece = player.to_common();
+                match &self.change {
+                    PieceChange::Locked(locked) => piece.locked = *locked,
+                    PieceChange::Layer(layer) => piece.sorting_layer = *layer,
+                    PieceChange::Visibility(visibility) => piece.visibility = visibility.clone(),
+                }
+
+                tx.send(
+                    DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(uuid, piece)).into(),
+                )
+            }
         }
     }
 }
@@ -1,10 +1,27 @@
-use common::{message::DndMessage, Ability, Item};
+use std::fmt::Write;
+
+use common::{
+    formula::Stat, message::DndMessage, ruleset::Ruleset, Ability, CharacterChange, EquipSlot, Item,
+};
+
+/// Marks the block of raw data at the end of an exported sheet
+const EXPORT_DATA_FENCE: &str = "```json character-data";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CharacterExport {
+    character: common::Character,
+    items: Vec<Item>,
+    abilities: Vec<Ability>,
+}
 
 #[derive(Default)]
 pub struct CharacterState {
     pub character: common::Character,
     pub items: Vec<Item>,
     pub abilities: Vec<Ability>,
+    /// Our changes the server turned down because the character changed
+    /// underneath them, waiting on the player to keep or drop them
+    pub conflicts: Vec<CharacterChange>,
 }
 
 impl CharacterState {
@@ -20,13 +37,198 @@ impl CharacterState {
             DndMessage::AbilityList(abilities) => {
                 self.abilities = abilities.clone();
             }
+            DndMessage::CharacterConflict(character, change)
+                if character.name == self.character.name =>
+            {
+                self.character = character.clone();
+
+                // Only the latest attempt at each field matters
+                self.conflicts
+                    .retain(|x| std::mem::discriminant(x) != std::mem::discriminant(change));
+                self.conflicts.push(change.clone());
+            }
             _ => {}
         }
     }
+
+    pub fn equipped(&self) -> impl Iterator<Item = (EquipSlot, &Item)> {
+        self.items
+            .iter()
+            .filter_map(|item| item.slot.map(|slot| (slot, item)))
+    }
+
+    pub fn attuned_count(&self) -> usize {
+        self.items.iter().filter(|x| x.attuned).count()
+    }
+
+    /// Armor sets the base AC (unarmored is 10), everything else equipped adds
+    /// its AC as a bonus on top. The character's override wins if set.
+    pub fn armor_class(&self, ruleset: &Ruleset) -> i16 {
+        if let Some(ac) = self.character.ac_override {
+            return ac;
+        }
+
+        let dex_mod = Stat::Dex.modifier(&self.character, ruleset) as i16;
+
+        let mut base = 10;
+        let mut bonus = 0;
+        for (slot, item) in self.equipped() {
+            match (slot, item.armor_class) {
+                (EquipSlot::Armor, Some(ac)) => base = ac,
+                (_, Some(ac)) => bonus += ac,
+                _ => {}
+            }
+        }
+
+        base + dex_mod + bonus
+    }
+
+    /// 10 plus the perception skill bonus, the ruleset decides which stat it uses
+    pub fn passive_perception(&self, ruleset: &Ruleset) -> i32 {
+        let c = &self.character;
+        let Some(skill) = ruleset.skills.iter().find(|x| x.name == "Perception") else {
+            return 10 + Stat::Wis.modifier(c, ruleset);
+        };
+
+        let mut bonus = skill.stat.modifier(c, ruleset);
+        if c.skills.contains(&skill.name) {
+            bonus += ruleset.proficiency_bonus(c.level);
+        }
+        10 + bonus
+    }
+
+    pub fn attack_bonus(&self) -> i16 {
+        self.equipped()
+            .filter_map(|(_, item)| item.attack_bonus)
+            .sum()
+    }
+
+    /// Printable markdown sheet. The raw data is embedded at the end so the
+    /// same file can be imported again with [`CharacterState::from_markdown`]
+    pub fn to_markdown(&self, ruleset: &Ruleset) -> String {
+        let c = &self.character;
+        let mut out = format!("# {}\n\n", c.name);
+        if !c.tagline.is_empty() {
+            let _ = writeln!(out, "*{}*\n", c.tagline);
+        }
+        let _ = writeln!(out, "**Level** {} ({} XP)\n", c.level, c.xp);
+
+        out.push_str(
+            "## Stats\n\n| STR | DEX | CON | INT | WIS | CHA |\n|---|---|---|---|---|---|\n|",
+        );
+        for stat in Stat::ALL {
+            let _ = write!(
+                out,
+                " {} ({:+}) |",
+                stat.score(c),
+                stat.modifier(c, ruleset)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "\n\n**Armor Class** {} | **Attack Bonus** {:+} | **Power Slots** {}\n",
+            self.armor_class(ruleset),
+            self.attack_bonus(),
+            c.power_slots
+        );
+
+        if !c.skills.is_empty() {
+            out.push_str("## Skills\n\n");
+            for skill in c.skills.iter() {
+                let _ = writeln!(out, "- {}", skill);
+            }
+            out.push('\n');
+        }
+
+        if !self.items.is_empty() {
+            out.push_str("## Inventory\n\n");
+            for item in self.items.iter() {
+                let _ = write!(out, "- **{}** x{}", item.name, item.count);
+                if let Some(slot) = item.slot {
+                    let _ = write!(out, " ({})", slot);
+                }
+                let _ = writeln!(out, ": {}", item.description.replace('\n', " "));
+            }
+            out.push('\n');
+        }
+
+        if !self.abilities.is_empty() {
+            out.push_str("## Abilities\n\n");
+            for ability in self.abilities.iter() {
+                let _ = writeln!(
+                    out,
+                    "### {} ({})\n\n{}\n",
+                    ability.name, ability.ability_type, ability.description
+                );
+                if let Some(to_hit) = &ability.to_hit {
+                    let _ = writeln!(out, "- To hit: {}", to_hit);
+                }
+                if let Some(damage) = &ability.damage {
+                    let _ = writeln!(out, "- Damage: {}", damage);
+                }
+            }
+        }
+
+        if !c.backstory.is_empty() {
+            let _ = writeln!(out, "## Backstory\n\n{}\n", c.backstory);
+        }
+
+        let export = CharacterExport {
+            character: self.character.clone(),
+            items: self.items.clone(),
+            abilities: self.abilities.clone(),
+        };
+        let data = serde_json::to_string_pretty(&export).unwrap_or_default();
+        let _ = writeln!(out, "## Data\n\n{}\n{}\n```", EXPORT_DATA_FENCE, data);
+
+        out
+    }
+
+    /// Reads a sheet written by [`CharacterState::to_markdown`]
+    pub fn from_markdown(text: &str) -> Result<Self, String> {
+        let (_, data) = text
+            .split_once(EXPORT_DATA_FENCE)
+            .ok_or("No character data found, was this file exported from a character sheet?")?;
+        let (json, _) = data
+            .split_once("```")
+            .ok_or("Character data is incomplete")?;
+
+        let export: CharacterExport = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        Ok(Self {
+            character: export.character,
+            items: export.items,
+            abilities: export.abilities,
+            ..Default::default()
+        })
+    }
 }
 
 pub mod commands {
-    use crate::prelude::*;
+    use common::{ruleset::Ruleset, Character, CharacterChange, EquipSlot, LifeState, XpTable};
+
+    use super::CharacterState;
+    use crate::{prelude::*, storage};
+
+    /// Applies `change` to the named sheet and sends it along with the version
+    /// it was made against. Only the GM edits sheets other than their own
+    fn send_change(
+        state: &mut DndState,
+        tx: &EventSender<Signal>,
+        name: &str,
+        change: CharacterChange,
+    ) {
+        let Some(sheet) = state.character_sheet_mut(name) else {
+            return;
+        };
+        let character = &mut sheet.character;
+        let user = User {
+            name: name.to_owned(),
+        };
+
+        change.apply(character);
+        tx.send(DndMessage::UpdateCharacter(user, character.version, change).into());
+        character.version += 1;
+    }
 
     pub struct UseItem {
         pub item_idx: usize,
@@ -58,7 +260,12 @@ pub mod commands {
 
             // Send Log Message
             tx.send(
-                DndMessage::Log(user, LogMessage::UseItem(item.name.clone(), self.count)).into(),
+                DndMessage::Log(
+                    user,
+                    LogMessage::UseItem(item.name.clone(), self.count),
+                    None,
+                )
+                .into(),
             );
 
             // Remove immediately from display if no more count.
@@ -69,6 +276,137 @@ pub mod commands {
         }
     }
 
+    /// Corrects how many of an item we have without posting anything to chat,
+    /// the item goes away at zero
+    pub struct SetItemCount {
+        pub item_idx: usize,
+        pub count: u32,
+    }
+
+    impl Command for SetItemCount {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let user = state.owned_user();
+
+            let Some(item) = state.character.items.get_mut(self.item_idx) else {
+                error!(
+                    "Trying to change the count of an item which no longer exists. Idx: {}",
+                    self.item_idx
+                );
+                return;
+            };
+
+            item.count = self.count;
+            tx.send(DndMessage::UpdateItemCount(user, item.id, item.count).into());
+
+            if item.count == 0 {
+                state.character.items.remove(self.item_idx);
+            }
+        }
+    }
+
+    /// Refused up front once the ruleset's attunement limit is reached and enforced
+    pub struct SetAttunement {
+        pub item_idx: usize,
+        pub attuned: bool,
+    }
+
+    impl Command for SetAttunement {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let user = state.owned_user();
+
+            if self.attuned && !state.ruleset.can_attune(state.character.attuned_count()) {
+                let limit = state.ruleset.attunement_limit;
+                state.toasts.push(
+                    "Attuning item",
+                    format!("You can only be attuned to {limit} items at once"),
+                );
+                return;
+            }
+
+            let Some(item) = state.character.items.get_mut(self.item_idx) else {
+                error!(
+                    "Trying to attune to an item which no longer exists. Idx: {}",
+                    self.item_idx
+                );
+                return;
+            };
+
+            item.attuned = self.attuned;
+            tx.send(DndMessage::UpdateItemAttunement(user, item.id, item.attuned).into());
+        }
+    }
+
+    pub struct EquipItem {
+        pub item_idx: usize,
+        pub slot: Option<EquipSlot>,
+    }
+
+    impl EquipItem {
+        pub fn new(item_idx: usize, slot: Option<EquipSlot>) -> Self {
+            Self { item_idx, slot }
+        }
+    }
+
+    impl Command for EquipItem {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let user = state.owned_user();
+
+            if self.item_idx >= state.character.items.len() {
+                error!(
+                    "Trying to equip item which no longer exists. Idx: {}",
+                    self.item_idx
+                );
+                return;
+            }
+
+            // Only one item per slot, bump whatever was there before
+            if self.slot.is_some() {
+                for (idx, item) in state.character.items.iter_mut().enumerate() {
+                    if idx != self.item_idx && item.slot == self.slot {
+                        item.slot = None;
+                        tx.send(DndMessage::UpdateItemSlot(user.clone(), item.id, None).into());
+                    }
+                }
+            }
+
+            let item = &mut state.character.items[self.item_idx];
+            item.slot = self.slot;
+
+            tx.send(DndMessage::UpdateItemSlot(user, item.id, item.slot).into());
+        }
+    }
+
+    /// Character to change and the override
+    pub struct SetArmorClassOverride(pub String, pub Option<i16>);
+
+    impl Command for SetArmorClassOverride {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            send_change(
+                state,
+                tx,
+                &self.0,
+                CharacterChange::ArmorClassOverride(self.1),
+            );
+        }
+    }
+
+    pub struct SetMaxHp(pub String, pub i32);
+
+    impl Command for SetMaxHp {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            send_change(state, tx, &self.0, CharacterChange::MaxHp(self.1));
+        }
+    }
+
+    /// Image for the character's token, `None` clears it
+    pub struct SetPortrait(pub String, pub Option<String>);
+
+    impl Command for SetPortrait {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            send_change(state, tx, &self.0, CharacterChange::Portrait(self.1));
+        }
+    }
+
     pub struct RefreshCharacter;
 
     impl Command for RefreshCharacter {
@@ -77,31 +415,238 @@ pub mod commands {
         }
     }
 
+    pub struct RefreshPartyMember(pub String);
+
+    impl Command for RefreshPartyMember {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::RetrievePartyMember(self.0).into())
+        }
+    }
+
     pub struct ToggleSkill {
+        pub character: String,
         pub skill_name: String,
     }
 
     impl ToggleSkill {
-        pub fn new(skill_name: String) -> Self {
-            ToggleSkill { skill_name }
+        pub fn new(character: String, skill_name: String) -> Self {
+            ToggleSkill {
+                character,
+                skill_name,
+            }
         }
     }
 
     impl Command for ToggleSkill {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let Some(sheet) = state.character_sheet(&self.character) else {
+                return;
+            };
+            let mut skills = sheet.character.skills.clone();
+
+            if skills.contains(&self.skill_name) {
+                skills.retain(|x| x != &self.skill_name);
+            } else {
+                skills.push(self.skill_name);
+            }
+
+            send_change(state, tx, &self.character, CharacterChange::Skills(skills));
+        }
+    }
+
+    /// Takes the next level, optionally adding a power slot on the way
+    pub struct LevelUp {
+        pub extra_power_slot: bool,
+    }
+
+    impl Command for LevelUp {
         fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
             let user = state.owned_user();
 
-            let skills = &mut state.character.character.skills;
+            let level = state.character.character.level + 1;
+            send_change(state, tx, &user.name, CharacterChange::Level(level));
 
+            if self.extra_power_slot {
+                let max_power_slots = state.character.character.max_power_slots + 1;
+                let change = CharacterChange::MaxPowerSlots(max_power_slots);
+                send_change(state, tx, &user.name, change);
 
-            if skills.contains(&self.skill_name) {
-                skills.retain(|x| x != &self.skill_name);
+                let character = &mut state.character.character;
+                character.power_slots = character.max_power_slots;
+                tx.send(
+                    DndMessage::UpdatePowerSlotCount(user.clone(), character.power_slots).into(),
+                );
             }
-            else {
-                skills.push(self.skill_name);
+
+            let character = &state.character.character;
+            let msg = format!("{} reached level {}!", character.name, character.level);
+            tx.send(DndMessage::Log(user, LogMessage::Chat(msg), None).into());
+        }
+    }
+
+    /// Settles the oldest conflict, either sending our change again on top of
+    /// the server's copy or dropping it
+    pub struct ResolveConflict {
+        pub keep_mine: bool,
+    }
+
+    impl Command for ResolveConflict {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            if state.character.conflicts.is_empty() {
+                return;
             }
 
-            tx.send(DndMessage::UpdateSkills(user.clone(), skills.clone()).into());
+            let change = state.character.conflicts.remove(0);
+            if self.keep_mine {
+                let name = state.owned_user().name;
+                send_change(state, tx, &name, change);
+            }
+        }
+    }
+
+    /// Only the GM can award XP
+    pub struct AwardXp {
+        pub characters: Vec<String>,
+        pub xp: u32,
+    }
+
+    impl Command for AwardXp {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::AwardXp(self.characters, self.xp).into());
+        }
+    }
+
+    /// Only the GM can grant inspiration
+    pub struct GrantInspiration {
+        pub characters: Vec<String>,
+        pub points: u32,
+    }
+
+    impl Command for GrantInspiration {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::GrantInspiration(self.characters, self.points).into());
+        }
+    }
+
+    /// Damage (negative) or healing, applied by the server on top of the current HP
+    pub struct AdjustHp {
+        pub character: String,
+        pub amount: i32,
+    }
+
+    impl Command for AdjustHp {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::AdjustHp(self.character, self.amount).into());
+        }
+    }
+
+    /// Rolled by the server, only dying characters roll
+    pub struct RollDeathSave(pub String);
+
+    impl Command for RollDeathSave {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::RollDeathSave(self.0).into());
+        }
+    }
+
+    /// Only the GM can mark characters dead or stable, or get them back up
+    pub struct SetLifeState {
+        pub character: String,
+        pub life: LifeState,
+    }
+
+    impl Command for SetLifeState {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SetLifeState(self.character, self.life).into());
+        }
+    }
+
+    /// Damage from an area of effect, every hit lands in one chat message
+    pub struct ApplyAreaDamage {
+        pub source: String,
+        pub roll: DieRoll,
+        pub hits: Vec<AreaHit>,
+    }
+
+    impl Command for ApplyAreaDamage {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::ApplyAreaDamage(self.source, self.roll, self.hits).into());
+        }
+    }
+
+    /// The server checks there's a point to spend and sends the sheet back
+    pub struct SpendInspiration;
+
+    impl Command for SpendInspiration {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SpendInspiration(state.owned_user()).into());
+        }
+    }
+
+    pub struct SetXpTable(pub XpTable);
+    impl Command for SetXpTable {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SetXpTable(self.0).into());
+        }
+    }
+
+    /// Only the GM can change the ruleset
+    pub struct SetRuleset(pub Ruleset);
+    impl Command for SetRuleset {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SetRuleset(self.0).into());
+        }
+    }
+
+    pub struct CreateCharacter(pub Character);
+    impl Command for CreateCharacter {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::CreateCharacter(self.0).into());
+        }
+    }
+
+    pub struct DeleteCharacter(pub String);
+    impl Command for DeleteCharacter {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::DeleteCharacter(self.0).into());
+        }
+    }
+
+    /// Writes our sheet to `<name>.md` in the working directory
+    pub struct ExportCharacter;
+    impl Command for ExportCharacter {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            let file_name = format!("{}.md", state.character.character.name);
+            match storage::write(&file_name, state.character.to_markdown(&state.ruleset)) {
+                Ok(_) => info!("Exported character to {file_name}"),
+                Err(e) => state.toasts.push("Exporting character", e),
+            }
+        }
+    }
+
+    /// Creates a new character, with their items and abilities, from an exported
+    /// sheet or a D&D Beyond JSON export
+    pub struct ImportCharacter(pub String);
+    impl Command for ImportCharacter {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let imported = storage::read_to_string(&self.0)
+                .map_err(|e| e.to_string())
+                .and_then(|text| match text.trim_start().starts_with('{') {
+                    true => crate::dnd_beyond::from_json(&text, &state.ruleset),
+                    false => CharacterState::from_markdown(&text),
+                });
+
+            match imported {
+                Ok(imported) => tx.send(
+                    DndMessage::ImportCharacter(
+                        imported.character,
+                        imported.items,
+                        imported.abilities,
+                    )
+                    .into(),
+                ),
+                Err(e) => state.toasts.push("Importing character", e),
+            }
         }
     }
 }
@@ -1,31 +1,201 @@
-use crate::prelude::*;
-use egui::{text::LayoutJob, Align, Color32, FontSelection, RichText, Style};
+use crate::{prelude::*, state::theme};
+use chrono::{DateTime, Local};
+use common::{stats::session_highlights, LifeState};
+use egui::{
+    accesskit::Role, text::LayoutJob, Align, Color32, FontSelection, Frame, Margin, RichText, Style,
+};
+use itertools::Itertools;
+use uuid::Uuid;
+use web_time::Instant;
 
 pub struct ClientLogMessage {
     pub user: User,
     pub message: LogMessage,
+    pub received: Instant,
+    pub time: DateTime<Local>,
 }
 
 impl ClientLogMessage {
-    pub fn new(user: User, message: LogMessage) -> Self {
-        Self { user, message }
+    pub fn new(user: User, message: LogMessage, time: DateTime<Local>) -> Self {
+        Self {
+            user,
+            message,
+            received: Instant::now(),
+            time,
+        }
     }
 
-    pub fn ui(&self, ui: &mut egui::Ui, display_name: bool) {
-        let hide_name = matches!(self.message, LogMessage::Joined(_))
-            || matches!(self.message, LogMessage::Disconnected(_));
+    /// Status messages like joins are shown without the sender's name
+    pub fn hides_name(&self) -> bool {
+        matches!(
+            self.message,
+            LogMessage::Joined(_)
+                | LogMessage::Disconnected(_)
+                | LogMessage::EffectExpired(..)
+                | LogMessage::AbilityRecharged(..)
+                | LogMessage::InspirationGranted(..)
+                | LogMessage::BreakReminder(_)
+                | LogMessage::SessionSummary(_)
+                | LogMessage::Emote(..)
+        )
+    }
 
-        if display_name {
-            ui.separator();
-            if !hide_name {
-                ui.colored_label(Color32::LIGHT_BLUE, format!("{}: ", self.user.name));
+    /// Time of day for today's messages, otherwise the date as well
+    pub fn time_label(&self) -> String {
+        if self.time.date_naive() == Local::now().date_naive() {
+            self.time.format("%H:%M").to_string()
+        } else {
+            self.time.format("%b %e, %H:%M").to_string()
+        }
+    }
+
+    /// Plain text version of the message used for exported transcripts
+    pub fn summary(&self, is_gm: bool) -> String {
+        match &self.message {
+            LogMessage::Chat(c) | LogMessage::NpcChat(_, c) => c.clone(),
+            LogMessage::Emote(..) => self.emote_text(),
+            LogMessage::UseItem(item, count) => format!("Used {} {}", count, item),
+            LogMessage::SetAbilityCount(ability, count) => {
+                format!("Used {}, they have {} uses left", ability, count)
+            }
+            LogMessage::Joined(name) => format!("{} joined", name),
+            LogMessage::Disconnected(name) => format!("{} disconnected", name),
+            LogMessage::Roll(roll) if roll.visibility == RollVisibility::Blind && !is_gm => {
+                "rolled secretly".to_owned()
             }
+            LogMessage::Roll(roll) => roll_summary(roll),
+            LogMessage::TableRoll(roll)
+                if roll.roll.visibility == RollVisibility::Blind && !is_gm =>
+            {
+                "rolled on a table secretly".to_owned()
+            }
+            LogMessage::TableRoll(roll) => {
+                let mut summary = format!(
+                    "rolled on {}, {}: {}",
+                    roll.table,
+                    roll_summary(&roll.roll),
+                    roll.result
+                );
+                for (item, count) in &roll.loot {
+                    summary.push_str(&format!(" ({} {} added to the stash)", count, item));
+                }
+                summary
+            }
+            LogMessage::Attack(attack) => attack_summary(attack),
+            LogMessage::EffectExpired(effect, target) => {
+                format!("{} has worn off {}", effect, target)
+            }
+            LogMessage::AbilityRecharged(ability, target) => {
+                format!("{} has recharged for {}", ability, target)
+            }
+            LogMessage::InspirationGranted(name, points) => inspiration_granted(name, *points),
+            LogMessage::HpChanged(name, amount, hp) => hp_changed(name, *amount, *hp),
+            LogMessage::AreaDamage(source, roll, hits) => area_summary(source, roll, hits),
+            LogMessage::InspirationSpent(name) => format!("{} spent inspiration", name),
+            LogMessage::BreakReminder(minutes) => break_reminder(*minutes),
+            LogMessage::LifeChanged(name, life) => life_changed(name, *life),
+            LogMessage::DeathSave(name, roll, life) => death_save(name, *roll, *life),
+            LogMessage::SessionSummary(stats) => session_highlights(stats).join(". "),
+        }
+    }
+
+    /// What screen readers and the narrator say for the message, with who sent it
+    pub fn narration(&self, is_gm: bool) -> String {
+        let summary = self.summary(is_gm);
+        if self.hides_name() {
+            summary
+        } else {
+            format!("{}: {}", self.speaker(), summary)
+        }
+    }
+
+    /// Name shown above the message, NPC chat is shown under the NPC's name
+    pub fn speaker(&self) -> &str {
+        match &self.message {
+            LogMessage::NpcChat(speaker, _) | LogMessage::Emote(Some(speaker), _) => speaker,
+            _ => &self.user.name,
+        }
+    }
+
+    /// "Terrin draws his blade", the actor's name is part of the action
+    fn emote_text(&self) -> String {
+        match &self.message {
+            LogMessage::Emote(_, action) => format!("{} {}", self.speaker(), action),
+            _ => String::new(),
+        }
+    }
+
+    /// Messages from the same speaker within the same minute are grouped under one header
+    pub fn same_group(&self, other: &ClientLogMessage) -> bool {
+        self.speaker() == other.speaker()
+            && self.time.timestamp() / 60 == other.time.timestamp() / 60
+    }
+
+    /// Every message is one node for screen readers, named with its narration,
+    /// so the chat is read a message at a time in order. The widgets drawn for
+    /// it sit under that node. `plain` draws the narration instead of the cards
+    pub fn ui(
+        &self,
+        ui: &mut egui::Ui,
+        display_header: bool,
+        is_gm: bool,
+        plain: bool,
+    ) -> egui::Response {
+        ui.scope(|ui| {
+            let id = ui.id();
+            let ctx = ui.ctx().clone();
+            ctx.accesskit_node_builder(id, |builder| {
+                builder.set_role(Role::ListItem);
+                builder.set_name(self.narration(is_gm));
+            });
+
+            ctx.with_accessibility_parent(id, || {
+                if plain {
+                    if display_header {
+                        ui.separator();
+                    }
+                    ui.label(format!("{} {}", self.time_label(), self.narration(is_gm)));
+                } else {
+                    self.message_ui(ui, display_header, is_gm);
+                }
+            });
+        })
+        .response
+    }
+
+    fn message_ui(&self, ui: &mut egui::Ui, display_header: bool, is_gm: bool) {
+        let hide_name = self.hides_name();
+        let palette = theme::palette(ui.ctx());
+
+        if display_header {
+            ui.separator();
+            ui.horizontal(|ui| {
+                if let LogMessage::NpcChat(speaker, _) = &self.message {
+                    let name = format!("{} {speaker}: ", egui_phosphor::regular::MASK_HAPPY);
+                    ui.label(RichText::new(name).italics().color(palette.critical));
+                } else if !hide_name {
+                    ui.colored_label(palette.accent, format!("{}: ", self.user.name));
+                }
+
+                ui.label(RichText::new(self.time_label()).small().weak())
+                    .on_hover_text(self.time.format("%A, %e %B %Y %H:%M:%S").to_string());
+            });
         }
 
         match &self.message {
             LogMessage::Chat(c) => {
                 ui.label(c);
             }
+            LogMessage::NpcChat(_, c) => {
+                ui.label(RichText::new(c).italics());
+            }
+            LogMessage::Emote(..) => {
+                ui.label(
+                    RichText::new(self.emote_text())
+                        .italics()
+                        .color(Color32::KHAKI),
+                );
+            }
             LogMessage::UseItem(item, count) => {
                 let style = Style::default();
                 let mut layout_job = LayoutJob::default();
@@ -33,7 +203,7 @@ impl ClientLogMessage {
                     .italics()
                     .append_to(&mut layout_job, &style, FontSelection::Default, Align::LEFT);
 
-                RichText::new(item).color(Color32::LIGHT_GREEN).append_to(
+                RichText::new(item).color(palette.positive).append_to(
                     &mut layout_job,
                     &style,
                     FontSelection::Default,
@@ -58,7 +228,7 @@ impl ClientLogMessage {
                     Align::LEFT,
                 );
 
-                RichText::new(ability).color(Color32::LIGHT_RED).append_to(
+                RichText::new(ability).color(palette.negative).append_to(
                     &mut layout_job,
                     &style,
                     FontSelection::Default,
@@ -71,48 +241,645 @@ impl ClientLogMessage {
 
                 ui.label(layout_job);
             }
-            LogMessage::Roll(die, value) => {
-                ui.colored_label(Color32::DARK_GRAY, format!("d{} = {}", die, value));
+            LogMessage::Roll(roll) if roll.visibility == RollVisibility::Blind && !is_gm => {
+                ui.colored_label(Color32::DARK_GRAY, "rolled secretly");
+            }
+            LogMessage::Roll(roll) => {
+                roll_card(ui, roll, self.received);
+            }
+            LogMessage::TableRoll(roll)
+                if roll.roll.visibility == RollVisibility::Blind && !is_gm =>
+            {
+                ui.colored_label(Color32::DARK_GRAY, "rolled on a table secretly");
+            }
+            LogMessage::TableRoll(roll) => {
+                roll_card(ui, &roll.roll, self.received);
+                ui.label(
+                    RichText::new(format!("{} {}", egui_phosphor::regular::LIST, roll.result))
+                        .strong(),
+                );
+                for (item, count) in &roll.loot {
+                    ui.weak(format!(
+                        "{} {} {} added to the stash",
+                        egui_phosphor::regular::PACKAGE,
+                        count,
+                        item
+                    ));
+                }
+            }
+            LogMessage::Attack(attack) => {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} {}",
+                        egui_phosphor::regular::CROSSHAIR,
+                        attack.target
+                    ));
+                    match attack.hits() {
+                        Some(true) if attack.to_hit.is_crit() => {
+                            ui.colored_label(palette.critical, "Critical hit!");
+                        }
+                        Some(true) => {
+                            ui.colored_label(palette.positive, "Hit");
+                        }
+                        Some(false) => {
+                            ui.colored_label(palette.negative, "Miss");
+                        }
+                        None => {
+                            ui.label(RichText::new("AC unknown, GM decides").weak());
+                        }
+                    }
+                    if let Some(ac) = attack.target_ac {
+                        ui.label(RichText::new(format!("vs AC {}", ac)).weak());
+                    }
+                });
+                roll_card(ui, &attack.to_hit, self.received);
+                if let Some(damage) = &attack.damage {
+                    roll_card(ui, damage, self.received);
+                }
+            }
+            LogMessage::EffectExpired(effect, target) => {
+                ui.colored_label(
+                    Color32::DARK_GRAY,
+                    format!("{} has worn off {}", effect, target),
+                );
+            }
+            LogMessage::AbilityRecharged(ability, target) => {
+                ui.colored_label(
+                    Color32::from_rgb(220, 140, 60),
+                    format!("{} has recharged for {}", ability, target),
+                );
+            }
+            LogMessage::InspirationGranted(name, points) => {
+                Frame::group(ui.style())
+                    .stroke(egui::Stroke::new(1.0, palette.critical))
+                    .show(ui, |ui| {
+                        let text = format!(
+                            "{} {}",
+                            egui_phosphor::regular::SPARKLE,
+                            inspiration_granted(name, *points)
+                        );
+                        ui.label(RichText::new(text).strong().color(palette.critical));
+                    });
+            }
+            LogMessage::InspirationSpent(name) => {
+                let text = format!("{} spent inspiration", name);
+                ui.label(RichText::new(text).italics().color(palette.critical));
+            }
+            LogMessage::BreakReminder(minutes) => {
+                let text = format!(
+                    "{} {}",
+                    egui_phosphor::regular::COFFEE,
+                    break_reminder(*minutes)
+                );
+                ui.colored_label(palette.accent, text);
+            }
+            LogMessage::HpChanged(name, amount, hp) => {
+                let color = if *amount < 0 {
+                    palette.negative
+                } else {
+                    palette.positive
+                };
+                ui.colored_label(color, hp_changed(name, *amount, *hp));
+            }
+            LogMessage::LifeChanged(name, life) => {
+                let (icon, color) = match life {
+                    LifeState::Conscious => (egui_phosphor::regular::HEART, palette.positive),
+                    LifeState::Dying { .. } => {
+                        (egui_phosphor::regular::HEARTBEAT, palette.negative)
+                    }
+                    LifeState::Stable => (egui_phosphor::regular::FIRST_AID, palette.accent),
+                    LifeState::Dead => (egui_phosphor::regular::SKULL, palette.critical),
+                };
+                let text = format!("{} {}", icon, life_changed(name, *life));
+                ui.label(RichText::new(text).strong().color(color));
+            }
+            LogMessage::DeathSave(name, roll, life) => {
+                ui.colored_label(palette.negative, death_save(name, *roll, *life));
+            }
+            LogMessage::SessionSummary(stats) => {
+                Frame::group(ui.style())
+                    .stroke(egui::Stroke::new(1.0, palette.accent))
+                    .show(ui, |ui| {
+                        let title = format!("{} That's a wrap!", egui_phosphor::regular::DICE_SIX);
+                        ui.label(RichText::new(title).strong().color(palette.accent));
+                        for line in session_highlights(stats) {
+                            ui.label(line);
+                        }
+                    });
+            }
+            LogMessage::AreaDamage(source, roll, hits) => {
+                ui.label(format!(
+                    "{} {}",
+                    egui_phosphor::regular::CIRCLE_DASHED,
+                    source
+                ));
+                roll_card(ui, roll, self.received);
+                for hit in hits {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(
+                            palette.negative,
+                            format!("{} took {} damage", hit.name, hit.damage),
+                        );
+                        if hit.saved {
+                            ui.label(RichText::new("saved").weak());
+                        }
+                        if let Some(hp) = hit.hp {
+                            ui.label(RichText::new(format!("({} HP)", hp)).weak());
+                        }
+                    });
+                }
             }
         };
     }
 }
 
+fn break_reminder(minutes: u64) -> String {
+    format!(
+        "{}h {:02}m into the session, time for a break",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+fn hp_changed(name: &str, amount: i32, hp: i32) -> String {
+    if amount < 0 {
+        format!("{} took {} damage ({} HP)", name, -amount, hp)
+    } else {
+        format!("{} healed {} ({} HP)", name, amount, hp)
+    }
+}
+
+fn life_changed(name: &str, life: LifeState) -> String {
+    match life {
+        LifeState::Conscious => format!("{} is back on their feet", name),
+        LifeState::Dying { .. } => format!("{} falls unconscious!", name),
+        LifeState::Stable => format!("{} is stable", name),
+        LifeState::Dead => format!("{} has died", name),
+    }
+}
+
+fn death_save(name: &str, roll: u32, life: LifeState) -> String {
+    let outcome = match life {
+        LifeState::Dying {
+            successes,
+            failures,
+        } => format!("{} passed, {} failed", successes, failures),
+        LifeState::Conscious => "back up with 1 HP!".to_owned(),
+        LifeState::Stable => "stable".to_owned(),
+        LifeState::Dead => "dead".to_owned(),
+    };
+    format!("{} rolled {} on a death save ({})", name, roll, outcome)
+}
+
+fn area_summary(source: &str, roll: &DieRoll, hits: &[AreaHit]) -> String {
+    let hits = hits
+        .iter()
+        .map(|hit| {
+            let mut summary = format!("{} {}", hit.name, hit.damage);
+            if hit.saved {
+                summary.push_str(" (saved)");
+            }
+            if let Some(hp) = hit.hp {
+                summary.push_str(&format!(" ({} HP)", hp));
+            }
+            summary
+        })
+        .join(", ");
+
+    format!("{}, {}, hit {}", source, roll_summary(roll), hits)
+}
+
+fn inspiration_granted(name: &str, points: u32) -> String {
+    if points == 1 {
+        format!("{} gained inspiration!", name)
+    } else {
+        format!("{} gained {} inspiration!", name, points)
+    }
+}
+
+fn roll_summary(roll: &DieRoll) -> String {
+    let mut summary = format!("rolled {}: {}", roll.dice(), roll.total());
+
+    if roll.is_crit() {
+        summary.push_str(" (Natural 20!)");
+    } else if roll.is_fumble() {
+        summary.push_str(" (Natural 1)");
+    }
+    if let Some(reason) = &roll.reason {
+        summary.push_str(&format!(" for {}", reason));
+    }
+    if let Some(character) = &roll.character {
+        summary.push_str(&format!(" as {}", character));
+    }
+    match roll.visibility {
+        RollVisibility::Public => {}
+        RollVisibility::GmOnly => summary.push_str(" [GM only]"),
+        RollVisibility::Blind => summary.push_str(" [Blind]"),
+    }
+
+    summary
+}
+
+fn attack_summary(attack: &AttackRoll) -> String {
+    let mut summary = format!(
+        "attacked {}, {}",
+        attack.target,
+        roll_summary(&attack.to_hit)
+    );
+
+    if let Some(ac) = attack.target_ac {
+        summary.push_str(&format!(" vs AC {}", ac));
+    }
+    match attack.hits() {
+        Some(true) => summary.push_str(", hit"),
+        Some(false) => summary.push_str(", miss"),
+        None => {}
+    }
+    if let Some(damage) = &attack.damage {
+        summary.push_str(&format!(" for {} damage", damage.total()));
+    }
+
+    summary
+}
+
+/// Each die on its own, only shown when there's more to it than the total
+fn dice_breakdown(roll: &DieRoll) -> Option<String> {
+    if roll.rolls.len() < 2 && roll.kind == DieKind::Standard {
+        return None;
+    }
+
+    let mut dice = roll.rolls.iter().map(|x| match (roll.kind, x) {
+        (DieKind::Fate, 0) => "-".to_owned(),
+        (DieKind::Fate, 1) => "0".to_owned(),
+        (DieKind::Fate, _) => "+".to_owned(),
+        (_, x) => x.to_string(),
+    });
+    Some(format!("[{}]", dice.join(", ")))
+}
+
+fn roll_card(ui: &mut egui::Ui, roll: &DieRoll, received: Instant) {
+    const ANIMATION_SECS: f32 = 0.4;
+
+    // Pops in large then settles down to the normal size
+    let t = (received.elapsed().as_secs_f32() / ANIMATION_SECS).min(1.0);
+    // Sized off the body text so the card follows the chat font size
+    let body = egui::TextStyle::Body.resolve(ui.style()).size;
+    let value_size = body * 1.6 * (1.0 + 0.5 * (1.0 - t));
+    let palette = theme::palette(ui.ctx());
+
+    let value_color = if roll.is_crit() {
+        palette.critical
+    } else if roll.is_fumble() {
+        palette.negative
+    } else {
+        ui.visuals().strong_text_color()
+    };
+
+    Frame::group(ui.style())
+        .inner_margin(Margin::same(6.0))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.label(RichText::new(egui_phosphor::regular::DICE_SIX).size(body * 1.4));
+                    ui.small(roll.dice());
+                });
+
+                ui.label(
+                    RichText::new(roll.total().to_string())
+                        .size(value_size)
+                        .strong()
+                        .color(value_color),
+                );
+
+                ui.vertical(|ui| {
+                    if let Some(character) = &roll.character {
+                        ui.label(RichText::new(character).strong());
+                    }
+                    if let Some(reason) = &roll.reason {
+                        ui.label(RichText::new(reason).italics());
+                    }
+                    if let Some(breakdown) = dice_breakdown(roll) {
+                        ui.small(breakdown);
+                    }
+                    if roll.is_crit() {
+                        ui.colored_label(palette.critical, "Natural 20!");
+                    } else if roll.is_fumble() {
+                        ui.colored_label(palette.negative, "Natural 1");
+                    }
+                    match roll.visibility {
+                        RollVisibility::Public => {}
+                        RollVisibility::GmOnly => {
+                            ui.small(RichText::new("GM only").weak());
+                        }
+                        RollVisibility::Blind => {
+                            ui.small(RichText::new("Blind").weak());
+                        }
+                    }
+                });
+            });
+        });
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Markdown,
+    Html,
+}
+
+impl TranscriptFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Markdown => "md",
+            TranscriptFormat::Html => "html",
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct ChatState {
     pub log_messages: Vec<ClientLogMessage>,
+    /// Set by `/help`, lists the chat commands above the chat box
+    pub show_help: bool,
+    /// Message the chat should scroll to next time it's shown, from the campaign search
+    pub scroll_to: Option<usize>,
+    /// Oldest first
+    pub pins: Vec<Pin>,
+}
+
+/// A message pinned above the chat, it may be from before we joined
+pub struct Pin {
+    pub id: Uuid,
+    pub pinned_by: String,
+    pub message: ClientLogMessage,
+}
+
+impl Pin {
+    /// Whoever pinned it and the GM can take it down
+    pub fn can_unpin(&self, user: &str, is_gm: bool) -> bool {
+        is_gm || self.pinned_by == user
+    }
 }
 
 impl ChatState {
+    /// Total of the latest initiative roll made for `character`
+    pub fn initiative(&self, character: &str) -> Option<i64> {
+        self.log_messages
+            .iter()
+            .rev()
+            .find_map(|msg| match &msg.message {
+                LogMessage::Roll(roll)
+                    if roll.character.as_deref() == Some(character)
+                        && roll.reason.as_deref() == Some("Initiative") =>
+                {
+                    Some(roll.total())
+                }
+                _ => None,
+            })
+    }
+
+    /// Where the pinned message is in our log, if it came in while we were here
+    pub fn find_pinned(&self, pin: &Pin) -> Option<usize> {
+        self.log_messages
+            .iter()
+            .position(|x| x.time == pin.message.time && x.user.name == pin.message.user.name)
+    }
+
+    /// The pin for the message at `index` in the log
+    pub fn pin_for(&self, index: usize) -> Option<&Pin> {
+        let msg = self.log_messages.get(index)?;
+        self.pins
+            .iter()
+            .find(|x| x.message.time == msg.time && x.message.user.name == msg.user.name)
+    }
+
+    /// Our latest rolls, newest first
+    pub fn recent_rolls<'a>(
+        &'a self,
+        user: &'a str,
+    ) -> impl Iterator<Item = (&'a ClientLogMessage, &'a DieRoll)> {
+        self.log_messages
+            .iter()
+            .rev()
+            .filter(move |msg| msg.user.name == user)
+            .filter_map(|msg| match &msg.message {
+                LogMessage::Roll(roll) => Some((msg, roll)),
+                _ => None,
+            })
+    }
+
+    /// Full session transcript. Messages the user couldn't see in chat stay hidden here too
+    pub fn transcript(&self, format: TranscriptFormat, is_gm: bool) -> String {
+        let title = match self.log_messages.first() {
+            Some(first) => format!("Session log {}", first.time.format("%Y-%m-%d")),
+            None => "Session log".to_owned(),
+        };
+
+        let mut lines = self.log_messages.iter().map(|msg| {
+            let time = msg.time.format("%H:%M:%S");
+            let name = Some(msg.speaker()).filter(|_| !msg.hides_name());
+            let text = msg.summary(is_gm);
+
+            match (format, name) {
+                (TranscriptFormat::Markdown, Some(name)) => {
+                    format!("- `{}` **{}**: {}", time, name, text)
+                }
+                (TranscriptFormat::Markdown, None) => format!("- `{}` _{}_", time, text),
+                (TranscriptFormat::Html, Some(name)) => format!(
+                    "<li><time>{}</time> <b>{}</b>: {}</li>",
+                    time,
+                    escape_html(name),
+                    escape_html(&text)
+                ),
+                (TranscriptFormat::Html, None) => format!(
+                    "<li><time>{}</time> <i>{}</i></li>",
+                    time,
+                    escape_html(&text)
+                ),
+            }
+        });
+
+        match format {
+            TranscriptFormat::Markdown => format!("# {}\n\n{}\n", title, lines.join("\n")),
+            TranscriptFormat::Html => {
+                let title = escape_html(&title);
+                format!(
+                    "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+                     <body>\n<h1>{title}</h1>\n<ul>\n{}\n</ul>\n</body>\n</html>\n",
+                    lines.join("\n")
+                )
+            }
+        }
+    }
+
     pub fn process(&mut self, message: &DndMessage) {
         #[allow(clippy::single_match)]
         match message {
-            DndMessage::Log(user, msg) => self
-                .log_messages
-                .push(ClientLogMessage::new(user.clone(), msg.clone())),
+            DndMessage::Log(user, msg, timestamp) => {
+                // Our own messages are echoed back locally before the server stamps them
+                let time = timestamp.map_or_else(Local::now, |x| x.with_timezone(&Local));
+                self.log_messages
+                    .push(ClientLogMessage::new(user.clone(), msg.clone(), time));
+            }
             DndMessage::ItemList(list) => {
                 println!("Recieved item list {list:?}");
             }
+            DndMessage::PinMessage(PinMessage::Pin(id, pin)) => {
+                let message = ClientLogMessage::new(
+                    pin.user.clone(),
+                    pin.message.clone(),
+                    pin.time.with_timezone(&Local),
+                );
+                self.pins.retain(|x| x.id != *id);
+                self.pins.push(Pin {
+                    id: *id,
+                    pinned_by: pin.pinned_by.clone(),
+                    message,
+                });
+                self.pins.sort_by_key(|x| x.message.time);
+            }
+            DndMessage::PinMessage(PinMessage::Unpin(id)) => self.pins.retain(|x| x.id != *id),
+            // Only the GM asks for this, so it's shown to them like a reply from the server
+            DndMessage::SnapshotMessage(SnapshotMessage::Usage(usage)) => {
+                self.log_messages.push(ClientLogMessage::new(
+                    User::server(),
+                    LogMessage::Chat(format!("Snapshot storage: {usage}")),
+                    Local::now(),
+                ));
+            }
             _ => {}
         }
     }
 }
 
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 pub mod commands {
 
+    use chrono::Utc;
+    use common::{
+        formula::{RollFormula, MAX_DICE, MAX_SIDES},
+        rules::Rest,
+        ruleset::Ruleset,
+        Character, RollTable,
+    };
     use itertools::Itertools;
     use rand::Rng;
     use thiserror::Error;
+    use uuid::Uuid;
+
+    use crate::{prelude::*, storage};
+
+    use super::TranscriptFormat;
 
-    use crate::prelude::*;
+    /// A slash command as it's listed by `/help`
+    pub struct ChatCommandInfo {
+        /// The first name is the one the command goes by, the rest are shorthands
+        pub names: &'static [&'static str],
+        pub usage: &'static str,
+        pub description: &'static str,
+        pub gm_only: bool,
+    }
+
+    /// Every command the chat box understands. Commands are looked up here
+    /// before they're parsed, so anything missing from this list can't be used
+    pub const CHAT_COMMANDS: [ChatCommandInfo; 9] = [
+        ChatCommandInfo {
+            names: &["roll", "r", "d"],
+            usage: "/roll 2d6+3 [reason]",
+            description: "Rolls dice for everyone to see, or with the chat box's visibility",
+            gm_only: false,
+        },
+        ChatCommandInfo {
+            names: &["gmroll", "gr"],
+            usage: "/gmroll d20 [reason]",
+            description: "Rolls dice only you and the GM can see",
+            gm_only: false,
+        },
+        ChatCommandInfo {
+            names: &["broll", "br"],
+            usage: "/broll d20 [reason]",
+            description: "Rolls dice only the GM can see the result of",
+            gm_only: false,
+        },
+        ChatCommandInfo {
+            names: &["table", "rolltable", "t"],
+            usage: "/table <name>",
+            description: "Rolls on one of the campaign's roll tables",
+            gm_only: false,
+        },
+        ChatCommandInfo {
+            names: &["me", "em"],
+            usage: "/me <action>",
+            description: "Describes what you do, ie. /me draws his blade",
+            gm_only: false,
+        },
+        ChatCommandInfo {
+            names: &["rest"],
+            usage: "/rest",
+            description: "Moves the campaign clock forward 8 hours for a long rest",
+            gm_only: true,
+        },
+        ChatCommandInfo {
+            names: &["advance"],
+            usage: "/advance <30m|2h|1d>",
+            description: "Moves the campaign clock forward",
+            gm_only: true,
+        },
+        ChatCommandInfo {
+            names: &["storage"],
+            usage: "/storage",
+            description: "Shows how much room the snapshots take up",
+            gm_only: true,
+        },
+        ChatCommandInfo {
+            names: &["help", "h", "?"],
+            usage: "/help",
+            description: "Lists these commands",
+            gm_only: false,
+        },
+    ];
+
+    /// Name the command typed as `name` goes by, shorthands included
+    pub fn command_name(name: &str) -> Option<&'static str> {
+        CHAT_COMMANDS
+            .iter()
+            .find(|x| x.names.contains(&name))
+            .map(|x| x.names[0])
+    }
 
     pub struct ChatCommand {
         text: String,
+        roll_visibility: RollVisibility,
+        speaker: Option<String>,
     }
 
     impl ChatCommand {
-        pub fn new(text: String) -> Self {
-            Self { text }
+        pub fn new(text: String, roll_visibility: RollVisibility) -> Self {
+            Self {
+                text,
+                roll_visibility,
+                speaker: None,
+            }
+        }
+
+        /// Post the message under an NPC's name instead of the user's. Only the GM may do this
+        pub fn speak_as(mut self, speaker: Option<String>) -> Self {
+            self.speaker = speaker.filter(|x| !x.is_empty());
+            self
+        }
+
+        fn chat_message(self, state: &DndState) -> DndMessage {
+            let msg = match self.speaker {
+                Some(speaker) => LogMessage::NpcChat(speaker, self.text),
+                None => LogMessage::Chat(self.text),
+            };
+
+            DndMessage::Log(state.owned_user(), msg, None)
         }
 
         fn parse_cmd(
@@ -121,19 +888,93 @@ pub mod commands {
             state: &mut DndState,
         ) -> Result<DndMessage, ChatCommandError> {
             let cmd_parts = cmd.split(" ").collect_vec();
-            match cmd_parts.first() {
+            let name = cmd_parts.first().and_then(|x| command_name(x));
+            let visibility = match name {
+                Some("gmroll") => RollVisibility::GmOnly,
+                Some("broll") => RollVisibility::Blind,
+                _ => self.roll_visibility,
+            };
+
+            match name {
                 // roll
-                Some(&"roll") | Some(&"r") | Some(&"d") => {
+                Some("roll") | Some("gmroll") | Some("broll") => {
                     let roll = *cmd_parts
                         .get(1)
                         .ok_or(ChatCommandError::ExpectedMoreArgs(1))?;
 
-                    roll_die(roll)
-                        .map(|(die, val)| {
-                            DndMessage::Log(state.owned_user(), LogMessage::Roll(die, val))
-                        })
-                        .map_err(|e| e.into())
+                    // Anything after the die is the reason for the roll
+                    let reason = Some(cmd_parts[2..].join(" ")).filter(|x| !x.is_empty());
+                    let character = self.speaker.clone().or_else(|| {
+                        Some(state.character.character.name.clone()).filter(|x| !x.is_empty())
+                    });
+
+                    let spec: DiceSpec = roll.parse()?;
+                    let rolls = spec.roll();
+                    let roll = DieRoll {
+                        die: spec.die,
+                        count: spec.count,
+                        value: rolls.iter().fold(0, |sum, x| sum.saturating_add(*x)),
+                        modifier: spec.modifier,
+                        character,
+                        reason,
+                        visibility,
+                        kind: spec.kind,
+                        rolls,
+                        outcome: None,
+                    }
+                    .resolve(&state.ruleset);
+
+                    Ok(DndMessage::Log(
+                        state.owned_user(),
+                        LogMessage::Roll(roll),
+                        None,
+                    ))
                 }
+                // roll tables
+                Some("table") => {
+                    let name = cmd_parts[1..].join(" ");
+                    if name.is_empty() {
+                        return Err(ChatCommandError::ExpectedMoreArgs(1));
+                    }
+
+                    let table = find_table(&state.roll_tables, &name)?;
+                    let roll = table_roll(state, table, visibility)?;
+                    Ok(DndMessage::Log(
+                        state.owned_user(),
+                        LogMessage::TableRoll(roll),
+                        None,
+                    ))
+                }
+                // actions, posted under the NPC's name when speaking as one
+                Some("me") => {
+                    let action = cmd_parts[1..].join(" ");
+                    if action.trim().is_empty() {
+                        return Err(ChatCommandError::ExpectedMoreArgs(1));
+                    }
+
+                    Ok(DndMessage::Log(
+                        state.owned_user(),
+                        LogMessage::Emote(self.speaker.clone(), action),
+                        None,
+                    ))
+                }
+                // campaign clock, rests take as long as the rules say
+                Some("rest") | Some("advance") | Some("storage") if !state.is_gm() => {
+                    Err(ChatCommandError::NotGm)
+                }
+                Some("rest") => Ok(DndMessage::BoardMessage(BoardMessage::AdvanceTime(
+                    state.ruleset.rules().rest_minutes(Rest::Long),
+                ))),
+                Some("advance") => {
+                    let duration = *cmd_parts
+                        .get(1)
+                        .ok_or(ChatCommandError::ExpectedMoreArgs(1))?;
+
+                    Ok(DndMessage::BoardMessage(BoardMessage::AdvanceTime(
+                        parse_duration(duration)?,
+                    )))
+                }
+                Some("storage") => Ok(DndMessage::SnapshotMessage(SnapshotMessage::RequestUsage)),
                 // add more cmds if you want cale
                 _ => Err(ChatCommandError::BadCommand),
             }
@@ -146,8 +987,13 @@ pub mod commands {
             match text_it.next() {
                 Some('/') => {
                     let cmd = text_it.as_str();
+                    if command_name(cmd.trim()) == Some("help") {
+                        state.chat.show_help = true;
+                        return;
+                    }
+
                     match self.parse_cmd(cmd, state) {
-                        Ok(msg) => tx.send(msg.into()),
+                        Ok(msg) => send_with_loot(msg, tx),
                         e => {
                             error!("Error parsing command: {e:?}")
                         }
@@ -158,16 +1004,174 @@ pub mod commands {
                     let die = ["d ", text_it.as_str()].concat();
                     match self.parse_cmd(&die, state) {
                         Ok(msg) => tx.send(msg.into()),
-                        _ => tx.send(
-                            DndMessage::Log(state.owned_user(), LogMessage::Chat(self.text)).into(),
-                        ),
+                        _ => tx.send(self.chat_message(state).into()),
                     };
                 }
                 None => {}
-                _ => {
-                    tx.send(DndMessage::Log(state.owned_user(), LogMessage::Chat(self.text)).into())
-                }
+                _ => tx.send(self.chat_message(state).into()),
+            }
+        }
+    }
+
+    /// Rolls one of an ability's formulas using the owned character's stats
+    pub struct RollAbility {
+        pub formula: RollFormula,
+        pub reason: String,
+    }
+
+    impl Command for RollAbility {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let roll = roll_formula(state, &self.formula, 1, self.reason);
+            tx.send(DndMessage::Log(state.owned_user(), LogMessage::Roll(roll), None).into());
+        }
+    }
+
+    /// `dice_multiplier` multiplies the dice on a critical hit
+    pub fn roll_formula(
+        state: &DndState,
+        formula: &RollFormula,
+        dice_multiplier: u32,
+        reason: String,
+    ) -> DieRoll {
+        roll_formula_for(
+            Some(&state.character.character),
+            &state.ruleset,
+            formula,
+            dice_multiplier,
+            reason,
+        )
+    }
+
+    /// Rolls with someone else's modifiers, or just the flat ones without a
+    /// character
+    pub fn roll_formula_for(
+        character: Option<&Character>,
+        ruleset: &Ruleset,
+        formula: &RollFormula,
+        dice_multiplier: u32,
+        reason: String,
+    ) -> DieRoll {
+        let (count, die) = formula.dice();
+        let count = count * dice_multiplier;
+
+        let mut rng = rand::rng();
+        let rolls: Vec<u32> = (0..count).map(|_| rng.random_range(1..=die)).collect();
+
+        DieRoll {
+            die,
+            count,
+            value: rolls.iter().fold(0, |sum, x| sum.saturating_add(*x)),
+            modifier: formula.modifier_for(character, ruleset),
+            character: character.map(|x| x.name.clone()).filter(|x| !x.is_empty()),
+            reason: Some(reason),
+            visibility: RollVisibility::Public,
+            kind: DieKind::Standard,
+            rolls,
+            outcome: None,
+        }
+        .resolve(ruleset)
+    }
+
+    /// Rolls an ability against the targeted token. Damage is only rolled if
+    /// the attack could have hit, and crits roll extra dice if the rules say so
+    pub struct RollAttack {
+        pub ability: String,
+        pub to_hit: RollFormula,
+        pub damage: Option<RollFormula>,
+    }
+
+    impl Command for RollAttack {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let Some(piece) = state.board.target.and_then(|x| state.board.players.get(&x)) else {
+                warn!("Attacking with {} but nothing is targeted", self.ability);
+                return;
+            };
+
+            let target = match piece.name.as_str() {
+                "" => "a token".to_owned(),
+                name => name.to_owned(),
+            };
+            let target_ac = state
+                .character_sheet(piece.stats_name())
+                .map(|x| x.armor_class(&state.ruleset));
+
+            let to_hit = roll_formula(state, &self.to_hit, 1, self.ability.clone());
+            let mut attack = AttackRoll {
+                to_hit,
+                target,
+                target_ac,
+                damage: None,
+            };
+
+            if attack.hits() != Some(false) {
+                let multiplier = match attack.to_hit.is_crit() {
+                    true => state.ruleset.rules().crit_dice_multiplier(),
+                    false => 1,
+                };
+                attack.damage = self.damage.map(|formula| {
+                    let reason = format!("{} damage", self.ability);
+                    roll_formula(state, &formula, multiplier, reason)
+                });
             }
+
+            tx.send(DndMessage::Log(state.owned_user(), LogMessage::Attack(attack), None).into());
+        }
+    }
+
+    /// Saves the session transcript next to the executable's working directory
+    pub struct ExportLog(pub TranscriptFormat);
+    impl Command for ExportLog {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            let file_name = format!(
+                "session-{}.{}",
+                chrono::Local::now().format("%Y-%m-%d-%H%M%S"),
+                self.0.extension()
+            );
+
+            let transcript = state.chat.transcript(self.0, state.is_gm());
+            match storage::write(&file_name, transcript) {
+                Ok(_) => info!("Exported chat log to {file_name}"),
+                Err(e) => error!("Failed to export chat log to {file_name}: {e}"),
+            }
+        }
+    }
+
+    /// Pins the message at this index in the log for everyone
+    pub struct PinChatMessage(pub usize);
+    impl Command for PinChatMessage {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let Some(msg) = state.chat.log_messages.get(self.0) else {
+                return;
+            };
+
+            let pin = PinnedMessage {
+                user: msg.user.clone(),
+                message: msg.message.clone(),
+                time: msg.time.with_timezone(&Utc),
+                pinned_by: state.owned_user().name,
+            };
+            tx.send(DndMessage::PinMessage(PinMessage::Pin(Uuid::new_v4(), pin)).into());
+        }
+    }
+
+    pub struct UnpinChatMessage(pub Uuid);
+    impl Command for UnpinChatMessage {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::PinMessage(PinMessage::Unpin(self.0)).into());
+        }
+    }
+
+    pub struct ShowChatHelp(pub bool);
+    impl Command for ShowChatHelp {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.chat.show_help = self.0;
+        }
+    }
+
+    pub struct ScrollChatTo(pub Option<usize>);
+    impl Command for ScrollChatTo {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.chat.scroll_to = self.0;
         }
     }
 
@@ -179,20 +1183,451 @@ pub mod commands {
         ExpectedMoreArgs(u32),
         #[error("error parsing dice roll {0}")]
         DiceRollError(#[from] DiceRollError),
+        #[error("only the GM can do that")]
+        NotGm,
+        #[error("durations look like 30m, 2h or 1d")]
+        BadDuration,
+        #[error("there's no roll table called '{0}'")]
+        UnknownTable(String),
+        #[error("the table has nothing on it to roll")]
+        EmptyTable,
+        #[error("tables can only roll on each other {MAX_TABLE_DEPTH} deep")]
+        TablesTooDeep,
     }
 
     #[derive(Error, Debug)]
     enum DiceRollError {
         #[error("Failed to parse the dice number")]
         ParseError(#[from] std::num::ParseIntError),
+        #[error("A die needs at least one side")]
+        NoSides,
+        #[error("Exploding dice need at least two sides")]
+        EndlessExplosion,
+        #[error("Rolls are limited to {MAX_DICE} dice")]
+        TooManyDice,
+        #[error("Dice are limited to {MAX_SIDES} sides")]
+        TooManySides,
     }
 
-    fn roll_die(roll: &str) -> Result<(u32, u32), DiceRollError> {
-        let die = roll.parse()?;
-        let mut rng = rand::rng();
-        let die_val: u32 = rng.random_range(0..die);
-        let die_tuple = (die, die_val);
+    /// Dice notation for the roll command: `20`, `d20`, `2d6+3`, `d%`, `4dF`
+    /// and exploding dice like `3d6!`
+    struct DiceSpec {
+        count: u32,
+        die: u32,
+        kind: DieKind,
+        modifier: i32,
+    }
+
+    impl std::str::FromStr for DiceSpec {
+        type Err = DiceRollError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let s = s.to_ascii_lowercase();
+            let (dice, modifier) = match s.rfind(['+', '-']) {
+                Some(i) => (&s[..i], s[i..].parse()?),
+                None => (s.as_str(), 0),
+            };
 
-        Ok(die_tuple)
+            // A bare number is the sides of a single die
+            let (count, die) = match dice.split_once('d') {
+                Some(("", die)) => (1, die),
+                Some((count, die)) => (count.parse()?, die),
+                None => (1, dice),
+            };
+
+            let (die, kind) = match die.strip_suffix('!') {
+                Some(die) => (die, DieKind::Exploding),
+                None => (die, DieKind::Standard),
+            };
+
+            let (die, kind) = match (die, kind) {
+                ("f", DieKind::Standard) => (3, DieKind::Fate),
+                ("%", kind) => (100, kind),
+                (die, kind) => (die.parse()?, kind),
+            };
+
+            if die == 0 || count == 0 {
+                return Err(DiceRollError::NoSides);
+            }
+            if die == 1 && kind == DieKind::Exploding {
+                return Err(DiceRollError::EndlessExplosion);
+            }
+            if count > MAX_DICE {
+                return Err(DiceRollError::TooManyDice);
+            }
+            if die > MAX_SIDES {
+                return Err(DiceRollError::TooManySides);
+            }
+
+            Ok(Self {
+                count,
+                die,
+                kind,
+                modifier,
+            })
+        }
+    }
+
+    impl DiceSpec {
+        /// Every die rolled, explosions are added after the die that exploded
+        fn roll(&self) -> Vec<u32> {
+            let mut rng = rand::rng();
+            let mut rolls = Vec::new();
+
+            for _ in 0..self.count {
+                loop {
+                    let value = rng.random_range(1..=self.die);
+                    match self.kind {
+                        DieKind::Fate => rolls.push(value - 1),
+                        _ => rolls.push(value),
+                    }
+
+                    let explodes = self.kind == DieKind::Exploding && value == self.die;
+                    if !explodes || rolls.len() as u32 >= MAX_DICE {
+                        break;
+                    }
+                }
+            }
+
+            rolls
+        }
+
+        /// Sum of the dice and the modifier, fate dice count from -1 to +1
+        fn total(&self, rolls: &[u32]) -> i64 {
+            let dice: i64 = rolls.iter().map(|x| *x as i64).sum();
+            let dice = match self.kind {
+                DieKind::Fate => dice - rolls.len() as i64,
+                _ => dice,
+            };
+            dice + self.modifier as i64
+        }
+    }
+
+    /// Tables can roll on each other, this stops them going around forever
+    const MAX_TABLE_DEPTH: u32 = 5;
+
+    fn find_table<'a>(
+        tables: &'a [RollTable],
+        name: &str,
+    ) -> Result<&'a RollTable, ChatCommandError> {
+        tables
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| ChatCommandError::UnknownTable(name.to_owned()))
+    }
+
+    /// An entry picked from a table, with everything in it rolled
+    struct TableResult {
+        /// What the table was rolled against
+        value: u32,
+        text: String,
+        /// (catalog item, count) from the entry and any tables it rolled on
+        items: Vec<(String, u32)>,
+    }
+
+    fn roll_entry(
+        table: &RollTable,
+        tables: &[RollTable],
+        depth: u32,
+    ) -> Result<TableResult, ChatCommandError> {
+        let total = table.total_weight();
+        if total == 0 {
+            return Err(ChatCommandError::EmptyTable);
+        }
+
+        let value = rand::rng().random_range(1..=total);
+        let entry = table.pick(value).ok_or(ChatCommandError::EmptyTable)?;
+
+        let (text, first_roll) = roll_dice_in(&entry.text);
+        let mut items = Vec::new();
+        if let Some(item) = entry.item.as_ref().filter(|x| !x.trim().is_empty()) {
+            items.push((item.trim().to_owned(), first_roll.unwrap_or(1)));
+        }
+        let text = roll_nested_tables(&text, tables, depth, &mut items)?;
+
+        Ok(TableResult { value, text, items })
+    }
+
+    /// Replaces the dice in the text with what they rolled, so `1d4 potions`
+    /// comes out as `3 potions`. The first roll is handed back as well, it's
+    /// how many of the entry's item to give
+    fn roll_dice_in(text: &str) -> (String, Option<u32>) {
+        let mut first_roll = None;
+        let words = text
+            .split(' ')
+            .map(|word| {
+                let dice = word.trim_matches(|c| matches!(c, ',' | '.' | ';' | ':' | '(' | ')'));
+                // Bare numbers are dice to the roll command, not in a sentence
+                let spec = Some(dice)
+                    .filter(|x| x.contains(['d', 'D']))
+                    .and_then(|x| x.parse::<DiceSpec>().ok());
+                let Some(spec) = spec else {
+                    return word.to_owned();
+                };
+
+                let total = spec.total(&spec.roll());
+                first_roll.get_or_insert(total.max(0) as u32);
+                word.replacen(dice, &total.to_string(), 1)
+            })
+            .collect_vec();
+
+        (words.join(" "), first_roll)
+    }
+
+    /// Replaces `[[Table]]` with a roll on that table
+    fn roll_nested_tables(
+        text: &str,
+        tables: &[RollTable],
+        depth: u32,
+        items: &mut Vec<(String, u32)>,
+    ) -> Result<String, ChatCommandError> {
+        let mut rolled = String::new();
+        let mut rest = text;
+        while let Some((before, after)) = rest.split_once("[[") {
+            let Some((name, after)) = after.split_once("]]") else {
+                break;
+            };
+            if depth >= MAX_TABLE_DEPTH {
+                return Err(ChatCommandError::TablesTooDeep);
+            }
+
+            let table = find_table(tables, name.trim())?;
+            let result = roll_entry(table, tables, depth + 1)?;
+            rolled.push_str(before);
+            rolled.push_str(&result.text);
+            items.extend(result.items);
+            rest = after;
+        }
+        rolled.push_str(rest);
+
+        Ok(rolled)
+    }
+
+    /// Sends the message, along with the stash additions when it's a roll on a loot table
+    fn send_with_loot(msg: DndMessage, tx: &EventSender<Signal>) {
+        let loot = stash_additions(&msg);
+
+        tx.send(msg.into());
+        for (item, count) in loot {
+            tx.send(DndMessage::StashMessage(StashMessage::AddLoot(item, count)).into());
+        }
+    }
+
+    /// The loot a table roll adds to the stash. Everyone can see the stash, so
+    /// hidden rolls would give their result away and add nothing
+    fn stash_additions(msg: &DndMessage) -> Vec<(String, u32)> {
+        match msg {
+            DndMessage::Log(_, LogMessage::TableRoll(roll), _)
+                if roll.roll.visibility == RollVisibility::Public =>
+            {
+                roll.loot
+                    .iter()
+                    .filter(|(_, count)| *count > 0)
+                    .cloned()
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Rolls against the table's total weight and looks up the entry it lands
+    /// on. Only the GM's rolls fill the stash
+    fn table_roll(
+        state: &DndState,
+        table: &RollTable,
+        visibility: RollVisibility,
+    ) -> Result<TableRoll, ChatCommandError> {
+        let total = table.total_weight();
+        let TableResult { value, text, items } = roll_entry(table, &state.roll_tables, 0)?;
+        let loot = match state.is_gm() && table.add_to_stash {
+            true => items,
+            false => Vec::new(),
+        };
+
+        Ok(TableRoll {
+            table: table.name.clone(),
+            roll: DieRoll {
+                die: total,
+                count: 1,
+                value,
+                modifier: 0,
+                character: None,
+                reason: Some(table.name.clone()),
+                visibility,
+                kind: DieKind::Standard,
+                rolls: vec![value],
+                // Landing on the last entry isn't a crit
+                outcome: Some(RollOutcome::Normal),
+            },
+            result: text,
+            loot,
+        })
+    }
+
+    /// Rolls on a table from the roll table tab
+    pub struct RollOnTable {
+        pub table: RollTable,
+        pub visibility: RollVisibility,
+    }
+
+    impl Command for RollOnTable {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            match table_roll(state, &self.table, self.visibility) {
+                Ok(roll) => send_with_loot(
+                    DndMessage::Log(state.owned_user(), LogMessage::TableRoll(roll), None),
+                    tx,
+                ),
+                Err(e) => error!("Failed to roll on {}: {e}", self.table.name),
+            }
+        }
+    }
+
+    /// Creates the table, or replaces the one with the same name. GM only
+    pub struct SaveRollTable(pub RollTable);
+
+    impl Command for SaveRollTable {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SaveRollTable(self.0).into());
+        }
+    }
+
+    pub struct DeleteRollTable(pub String);
+
+    impl Command for DeleteRollTable {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::DeleteRollTable(self.0).into());
+        }
+    }
+
+    /// Parses durations like `30m`, `2h` or `1d` into minutes
+    fn parse_duration(duration: &str) -> Result<u32, ChatCommandError> {
+        let unit = duration
+            .chars()
+            .next_back()
+            .ok_or(ChatCommandError::BadDuration)?;
+        let amount: u32 = duration[..duration.len() - unit.len_utf8()]
+            .parse()
+            .map_err(|_| ChatCommandError::BadDuration)?;
+
+        let minutes = match unit {
+            'm' => 1,
+            'h' => 60,
+            'd' => 24 * 60,
+            _ => return Err(ChatCommandError::BadDuration),
+        };
+
+        amount
+            .checked_mul(minutes)
+            .ok_or(ChatCommandError::BadDuration)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use common::TableEntry;
+
+        use super::*;
+
+        fn table(name: &str, text: &str, item: Option<&str>) -> RollTable {
+            RollTable {
+                name: name.to_owned(),
+                entries: vec![TableEntry {
+                    weight: 1,
+                    text: text.to_owned(),
+                    item: item.map(str::to_owned),
+                }],
+                add_to_stash: true,
+            }
+        }
+
+        fn gm() -> DndState {
+            DndState {
+                user: Some(User {
+                    name: "GM".to_owned(),
+                }),
+                gm: Some("GM".to_owned()),
+                ..Default::default()
+            }
+        }
+
+        fn loot_from(
+            state: &DndState,
+            table: &RollTable,
+            visibility: RollVisibility,
+        ) -> Vec<(String, u32)> {
+            let roll = table_roll(state, table, visibility).unwrap();
+            stash_additions(&DndMessage::Log(
+                state.owned_user(),
+                LogMessage::TableRoll(roll),
+                None,
+            ))
+        }
+
+        #[test]
+        fn dice_in_the_text_are_rolled() {
+            let (text, first) = roll_dice_in("Found 2d1 gems and (3d1) coins.");
+            assert_eq!(text, "Found 2 gems and (3) coins.");
+            assert_eq!(first, Some(2));
+        }
+
+        #[test]
+        fn words_that_arent_dice_are_left_alone() {
+            let (text, first) = roll_dice_in("A dead dragon guards 12 doors");
+            assert_eq!(text, "A dead dragon guards 12 doors");
+            assert_eq!(first, None);
+        }
+
+        #[test]
+        fn nested_tables_are_rolled_and_their_items_kept() {
+            let tables = [
+                table("Hoard", "A chest with [[Gems]] inside", Some("Chest")),
+                table("Gems", "3d1 rubies", Some("Ruby")),
+            ];
+
+            let result = roll_entry(&tables[0], &tables, 0).unwrap();
+            assert_eq!(result.text, "A chest with 3 rubies inside");
+            assert_eq!(
+                result.items,
+                [("Chest".to_owned(), 1), ("Ruby".to_owned(), 3)]
+            );
+        }
+
+        #[test]
+        fn tables_that_roll_on_themselves_stop() {
+            let tables = [table("Loop", "Again [[Loop]]", None)];
+            let result = roll_entry(&tables[0], &tables, 0);
+            assert!(matches!(result, Err(ChatCommandError::TablesTooDeep)));
+        }
+
+        #[test]
+        fn unknown_nested_tables_are_an_error() {
+            let tables = [table("Hoard", "[[Missing]]", None)];
+            let result = roll_entry(&tables[0], &tables, 0);
+            assert!(
+                matches!(result, Err(ChatCommandError::UnknownTable(name)) if name == "Missing")
+            );
+        }
+
+        #[test]
+        fn only_public_gm_rolls_fill_the_stash() {
+            let state = gm();
+            let hoard = table("Hoard", "2d1 rubies", Some("Ruby"));
+
+            let loot = loot_from(&state, &hoard, RollVisibility::Public);
+            assert_eq!(loot, [("Ruby".to_owned(), 2)]);
+            assert!(loot_from(&state, &hoard, RollVisibility::GmOnly).is_empty());
+            assert!(loot_from(&state, &hoard, RollVisibility::Blind).is_empty());
+
+            let player = DndState {
+                gm: Some("Someone else".to_owned()),
+                ..gm()
+            };
+            assert!(loot_from(&player, &hoard, RollVisibility::Public).is_empty());
+        }
+
+        #[test]
+        fn nothing_is_added_when_none_were_rolled() {
+            let hoard = table("Hoard", "1d1-5 rubies", Some("Ruby"));
+            assert!(loot_from(&gm(), &hoard, RollVisibility::Public).is_empty());
+        }
     }
 }
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::{
+    listener::{now_millis, HEARTBEAT_INTERVAL},
+    prelude::*,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionQuality {
+    /// Nothing to go on yet, or there's no server to ping in a local session
+    Unknown,
+    Good,
+    Slow,
+    /// Pings have stopped coming back
+    Lost,
+}
+
+/// How the connection to the server is holding up, going by the pings the
+/// listener sends
+#[derive(Default)]
+pub struct ConnectionState {
+    /// Round trip of the latest ping
+    pub latency: Option<Duration>,
+    last_pong: Option<Instant>,
+}
+
+impl ConnectionState {
+    /// Round trips slower than this make moving pieces feel laggy
+    const SLOW: Duration = Duration::from_millis(250);
+
+    /// Missing a ping or two is fine, the server gives up a bit after this
+    const MISSED_PINGS: u32 = 3;
+
+    pub fn process(&mut self, message: &DndMessage) {
+        if let DndMessage::Pong(sent) = message {
+            let round_trip = now_millis().saturating_sub(*sent);
+            self.latency = Some(Duration::from_millis(round_trip));
+            self.last_pong = Some(Instant::now());
+        }
+    }
+
+    pub fn quality(&self) -> ConnectionQuality {
+        let (Some(latency), Some(last_pong)) = (self.latency, self.last_pong) else {
+            return ConnectionQuality::Unknown;
+        };
+
+        if last_pong.elapsed() > HEARTBEAT_INTERVAL * Self::MISSED_PINGS {
+            ConnectionQuality::Lost
+        } else if latency > Self::SLOW {
+            ConnectionQuality::Slow
+        } else {
+            ConnectionQuality::Good
+        }
+    }
+
+    /// Seconds since the server last answered, if it ever has
+    pub fn silent_for(&self) -> Option<u64> {
+        self.last_pong.map(|x| x.elapsed().as_secs())
+    }
+}
@@ -0,0 +1,109 @@
+use std::{io, path::PathBuf};
+
+use crate::{prelude::*, storage};
+
+/// A roll kept at the top of the dice tray, ie. `d20+5` for Stealth
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct FavoriteRoll {
+    pub dice: String,
+    pub reason: Option<String>,
+}
+
+impl FavoriteRoll {
+    /// Splits `d20+5 Stealth` into the dice and the reason
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split_whitespace();
+        let dice = parts.next()?.to_owned();
+        let reason = Some(parts.collect::<Vec<_>>().join(" ")).filter(|x| !x.is_empty());
+        Some(Self { dice, reason })
+    }
+
+    pub fn from_roll(roll: &DieRoll) -> Self {
+        Self {
+            dice: roll.dice(),
+            reason: roll.reason.clone(),
+        }
+    }
+
+    /// The chat command that makes this roll
+    pub fn command(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("/roll {} {}", self.dice, reason),
+            None => format!("/roll {}", self.dice),
+        }
+    }
+}
+
+impl std::fmt::Display for FavoriteRoll {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "{} {}", self.dice, reason),
+            None => write!(f, "{}", self.dice),
+        }
+    }
+}
+
+/// Favorite rolls, saved to a file so they're still there next session. The
+/// recent rolls come straight from chat
+#[derive(Default)]
+pub struct DiceTrayState {
+    pub favorites: Vec<FavoriteRoll>,
+    path: Option<PathBuf>,
+}
+
+impl DiceTrayState {
+    pub fn load(path: PathBuf) -> Self {
+        let favorites = match storage::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Could not read favorite rolls from {}: {e}", path.display());
+                Vec::new()
+            }),
+            // Nothing's been pinned yet
+            Err(_) => Vec::new(),
+        };
+
+        Self {
+            favorites,
+            path: Some(path),
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.favorites)
+            .map_err(io::Error::other)
+            .and_then(|json| storage::write(path, json));
+        if let Err(e) = result {
+            error!("Could not save favorite rolls to {}: {e}", path.display());
+        }
+    }
+}
+
+pub mod commands {
+    use crate::prelude::*;
+
+    use super::FavoriteRoll;
+
+    pub struct PinRoll(pub FavoriteRoll);
+    impl Command for PinRoll {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if !state.dice_tray.favorites.contains(&self.0) {
+                state.dice_tray.favorites.push(self.0);
+                state.dice_tray.save();
+            }
+        }
+    }
+
+    pub struct UnpinRoll(pub usize);
+    impl Command for UnpinRoll {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if self.0 < state.dice_tray.favorites.len() {
+                state.dice_tray.favorites.remove(self.0);
+                state.dice_tray.save();
+            }
+        }
+    }
+}
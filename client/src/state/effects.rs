@@ -0,0 +1,154 @@
+use common::{Cooldown, EffectTarget, TimedEffect};
+use egui::ahash::HashMap;
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+#[derive(Default)]
+pub struct EffectState {
+    pub round: u32,
+    pub effects: HashMap<Uuid, TimedEffect>,
+    /// Spent abilities waiting to recharge. The server decides when they're back
+    pub cooldowns: HashMap<Uuid, Cooldown>,
+}
+
+impl EffectState {
+    pub fn process(&mut self, message: &DndMessage) {
+        let DndMessage::EffectMessage(msg) = message else {
+            return;
+        };
+
+        match msg {
+            EffectMessage::ApplyEffect(uuid, effect) => {
+                self.effects.insert(*uuid, effect.clone());
+            }
+            EffectMessage::RemoveEffect(uuid) => {
+                self.effects.remove(uuid);
+            }
+            EffectMessage::AdvanceRound => {
+                self.round += 1;
+                self.effects.retain(|_, effect| !effect.tick());
+            }
+            EffectMessage::SetRound(round) => {
+                self.round = *round;
+            }
+            EffectMessage::SetCooldown(uuid, cooldown) => {
+                self.cooldowns.insert(*uuid, cooldown.clone());
+            }
+            EffectMessage::RemoveCooldown(uuid) => {
+                self.cooldowns.remove(uuid);
+            }
+            EffectMessage::StartTurn(_) => {}
+        }
+    }
+
+    pub fn effects_on<'a>(
+        &'a self,
+        target: &'a EffectTarget,
+    ) -> impl Iterator<Item = (&'a Uuid, &'a TimedEffect)> {
+        self.effects
+            .iter()
+            .filter(move |(_, effect)| &effect.target == target)
+            .sorted_by_key(|(_, effect)| effect.name.clone())
+    }
+
+    pub fn cooldowns_on<'a>(
+        &'a self,
+        target: &'a EffectTarget,
+    ) -> impl Iterator<Item = (&'a Uuid, &'a Cooldown)> {
+        self.cooldowns
+            .iter()
+            .filter(move |(_, cooldown)| &cooldown.target == target)
+            .sorted_by_key(|(_, cooldown)| cooldown.ability.clone())
+    }
+
+    pub fn cooldown_for(&self, target: &EffectTarget, ability: &str) -> Option<(&Uuid, &Cooldown)> {
+        self.cooldowns
+            .iter()
+            .find(|(_, x)| &x.target == target && x.ability == ability)
+    }
+}
+
+pub mod commands {
+    use common::{Cooldown, EffectTarget, Recharge, TimedEffect};
+    use uuid::Uuid;
+
+    use crate::prelude::*;
+
+    pub struct ApplyEffect {
+        pub name: String,
+        pub description: String,
+        pub rounds: u32,
+        pub target: EffectTarget,
+    }
+
+    impl Command for ApplyEffect {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            let effect = TimedEffect {
+                name: self.name,
+                description: self.description,
+                rounds_left: self.rounds,
+                target: self.target,
+            };
+
+            tx.send(
+                DndMessage::EffectMessage(EffectMessage::ApplyEffect(Uuid::new_v4(), effect))
+                    .into(),
+            );
+        }
+    }
+
+    pub struct RemoveEffect(pub Uuid);
+
+    impl Command for RemoveEffect {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::EffectMessage(EffectMessage::RemoveEffect(self.0)).into());
+        }
+    }
+
+    /// Puts an ability on cooldown, replacing any cooldown it already had
+    pub struct StartCooldown {
+        pub ability: String,
+        pub recharge: Recharge,
+        pub uses: Option<i64>,
+        pub target: EffectTarget,
+    }
+
+    impl Command for StartCooldown {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let uuid = state
+                .effects
+                .cooldown_for(&self.target, &self.ability)
+                .map_or_else(Uuid::new_v4, |(uuid, _)| *uuid);
+            let cooldown = Cooldown::new(self.ability, self.recharge, self.uses, self.target);
+
+            tx.send(DndMessage::EffectMessage(EffectMessage::SetCooldown(uuid, cooldown)).into());
+        }
+    }
+
+    pub struct RemoveCooldown(pub Uuid);
+
+    impl Command for RemoveCooldown {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::EffectMessage(EffectMessage::RemoveCooldown(self.0)).into());
+        }
+    }
+
+    pub struct AdvanceRound;
+
+    impl Command for AdvanceRound {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::EffectMessage(EffectMessage::AdvanceRound).into());
+        }
+    }
+
+    /// Rolls the recharges for the token whose turn it is
+    pub struct StartTurn(pub Uuid);
+
+    impl Command for StartTurn {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::EffectMessage(EffectMessage::StartTurn(self.0)).into());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use crate::prelude::*;
+
+/// One kind of monster in the encounter being built
+#[derive(Clone, Debug)]
+pub struct EncounterMonster {
+    pub name: String,
+    pub challenge: f32,
+    pub count: u32,
+    /// Token size in grid squares
+    pub size: f32,
+    pub dex: i16,
+    pub image_url: Option<String>,
+}
+
+impl Default for EncounterMonster {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            challenge: 1.0,
+            count: 1,
+            size: 1.0,
+            dex: 10,
+            image_url: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Difficulty {
+    Trivial,
+    Easy,
+    Medium,
+    Hard,
+    Deadly,
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Trivial => write!(f, "Trivial"),
+            Difficulty::Easy => write!(f, "Easy"),
+            Difficulty::Medium => write!(f, "Medium"),
+            Difficulty::Hard => write!(f, "Hard"),
+            Difficulty::Deadly => write!(f, "Deadly"),
+        }
+    }
+}
+
+/// XP for each whole challenge rating from 1 to 30
+const CR_XP: [u32; 30] = [
+    200, 450, 700, 1100, 1800, 2300, 2900, 3900, 5000, 5900, 7200, 8400, 10000, 11500, 13000,
+    15000, 18000, 20000, 22000, 25000, 33000, 41000, 50000, 62000, 75000, 90000, 105000, 120000,
+    135000, 155000,
+];
+
+/// Easy, medium, hard and deadly XP thresholds per character for levels 1 to 20
+const LEVEL_THRESHOLDS: [[u32; 4]; 20] = [
+    [25, 50, 75, 100],
+    [50, 100, 150, 200],
+    [75, 150, 225, 400],
+    [125, 250, 375, 500],
+    [250, 500, 750, 1100],
+    [300, 600, 900, 1400],
+    [350, 750, 1100, 1700],
+    [450, 900, 1400, 2100],
+    [550, 1100, 1600, 2400],
+    [600, 1200, 1900, 2800],
+    [800, 1600, 2400, 3600],
+    [1000, 2000, 3000, 4500],
+    [1100, 2200, 3400, 5100],
+    [1250, 2500, 3800, 5700],
+    [1400, 2800, 4300, 6400],
+    [1600, 3200, 4800, 7200],
+    [2000, 3900, 5900, 8800],
+    [2100, 4200, 6300, 9500],
+    [2400, 4900, 7300, 10900],
+    [2800, 5700, 8500, 12700],
+];
+
+pub fn challenge_xp(challenge: f32) -> u32 {
+    match challenge {
+        x if x <= 0.0 => 10,
+        x if x <= 0.125 => 25,
+        x if x <= 0.25 => 50,
+        x if x <= 0.5 => 100,
+        x => CR_XP[(x.floor() as usize).clamp(1, CR_XP.len()) - 1],
+    }
+}
+
+/// Fights against more monsters are harder than their XP alone suggests
+fn count_multiplier(count: u32) -> f32 {
+    match count {
+        0 | 1 => 1.0,
+        2 => 1.5,
+        3..=6 => 2.0,
+        7..=10 => 2.5,
+        11..=14 => 3.0,
+        _ => 4.0,
+    }
+}
+
+#[derive(Default)]
+pub struct EncounterState {
+    pub monsters: Vec<EncounterMonster>,
+}
+
+impl EncounterState {
+    pub fn monster_count(&self) -> u32 {
+        self.monsters.iter().map(|x| x.count).sum()
+    }
+
+    pub fn base_xp(&self) -> u32 {
+        self.monsters
+            .iter()
+            .map(|x| challenge_xp(x.challenge) * x.count)
+            .sum()
+    }
+
+    pub fn adjusted_xp(&self) -> u32 {
+        (self.base_xp() as f32 * count_multiplier(self.monster_count())) as u32
+    }
+
+    pub fn difficulty(&self, party_level: usize, party_size: u32) -> Difficulty {
+        let thresholds = LEVEL_THRESHOLDS[party_level.clamp(1, 20) - 1].map(|x| x * party_size);
+        let xp = self.adjusted_xp();
+
+        match thresholds.iter().filter(|x| xp >= **x).count() {
+            0 => Difficulty::Trivial,
+            1 => Difficulty::Easy,
+            2 => Difficulty::Medium,
+            3 => Difficulty::Hard,
+            _ => Difficulty::Deadly,
+        }
+    }
+
+    /// Top left corners, in grid squares from `origin`, for every token in the
+    /// encounter. Tokens are laid out in a square block with a gap between them
+    pub fn formation(&self, origin: Pos2) -> Vec<(&EncounterMonster, Pos2)> {
+        let tokens: Vec<_> = self
+            .monsters
+            .iter()
+            .flat_map(|x| std::iter::repeat_n(x, x.count as usize))
+            .collect();
+
+        let columns = (tokens.len() as f32).sqrt().ceil().max(1.0) as usize;
+        let cell = tokens.iter().map(|x| x.size).fold(1.0, f32::max) + 1.0;
+
+        tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, monster)| {
+                let offset = Vec2::new((i % columns) as f32, (i / columns) as f32) * cell;
+                (monster, origin + offset)
+            })
+            .collect()
+    }
+}
+
+pub mod commands {
+    use rand::Rng;
+
+    use super::EncounterMonster;
+    use crate::prelude::*;
+
+    pub struct AddMonster(pub EncounterMonster);
+    impl Command for AddMonster {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            let monsters = &mut state.encounter.monsters;
+
+            // Adding the same monster again just adds another copy of it
+            match monsters.iter_mut().find(|x| x.name == self.0.name) {
+                Some(monster) => monster.count += self.0.count,
+                None => monsters.push(self.0),
+            }
+        }
+    }
+
+    pub struct UpdateMonster(pub usize, pub EncounterMonster);
+    impl Command for UpdateMonster {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if let Some(monster) = state.encounter.monsters.get_mut(self.0) {
+                *monster = self.1;
+            }
+        }
+    }
+
+    pub struct RemoveMonster(pub usize);
+    impl Command for RemoveMonster {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if self.0 < state.encounter.monsters.len() {
+                state.encounter.monsters.remove(self.0);
+            }
+        }
+    }
+
+    pub struct ClearEncounter;
+    impl Command for ClearEncounter {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.encounter.monsters.clear();
+        }
+    }
+
+    /// Rolls initiative for a monster where only the GM can see it
+    pub struct RollInitiative {
+        pub name: String,
+        pub dex: i16,
+    }
+
+    impl Command for RollInitiative {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let value = rand::rng().random_range(1..=20);
+            let roll = DieRoll {
+                die: 20,
+                count: 1,
+                value,
+                modifier: state.ruleset.score_modifier(self.dex),
+                character: Some(self.name),
+                reason: Some("Initiative".to_owned()),
+                visibility: RollVisibility::GmOnly,
+                kind: DieKind::Standard,
+                rolls: vec![value],
+                outcome: None,
+            }
+            .resolve(&state.ruleset);
+
+            tx.send(DndMessage::Log(state.owned_user(), LogMessage::Roll(roll), None).into());
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use common::Handout;
+use egui::ahash::{HashMap, HashSet};
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+#[derive(Default)]
+pub struct HandoutState {
+    pub handouts: HashMap<Uuid, Handout>,
+    pub unread: HashSet<Uuid>,
+}
+
+impl HandoutState {
+    pub fn process(&mut self, message: &DndMessage, is_gm: bool) {
+        let DndMessage::HandoutMessage(msg) = message else {
+            return;
+        };
+
+        match msg {
+            HandoutMessage::CreateHandout(uuid, handout) => {
+                // The GM wrote it, so there is nothing new for them to read
+                if !is_gm {
+                    self.unread.insert(*uuid);
+                }
+                self.handouts.insert(*uuid, handout.clone());
+            }
+            HandoutMessage::ShareHandout(uuid, visibility) => {
+                if let Some(handout) = self.handouts.get_mut(uuid) {
+                    handout.visibility = visibility.clone();
+                }
+            }
+            HandoutMessage::DeleteHandout(uuid) => {
+                self.handouts.remove(uuid);
+                self.unread.remove(uuid);
+            }
+        }
+    }
+
+    pub fn sorted(&self) -> impl Iterator<Item = (&Uuid, &Handout)> {
+        self.handouts
+            .iter()
+            .sorted_by_key(|(_, handout)| handout.title.to_lowercase())
+    }
+}
+
+pub mod commands {
+    use common::{Handout, HandoutVisibility};
+    use uuid::Uuid;
+
+    use crate::prelude::*;
+
+    /// Creates a new handout or replaces an existing one with the same id
+    pub struct CreateHandout(pub Uuid, pub Handout);
+    impl Command for CreateHandout {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(
+                DndMessage::HandoutMessage(HandoutMessage::CreateHandout(self.0, self.1)).into(),
+            );
+        }
+    }
+
+    pub struct ShareHandout(pub Uuid, pub HandoutVisibility);
+    impl Command for ShareHandout {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(
+                DndMessage::HandoutMessage(HandoutMessage::ShareHandout(self.0, self.1)).into(),
+            );
+        }
+    }
+
+    pub struct DeleteHandout(pub Uuid);
+    impl Command for DeleteHandout {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::HandoutMessage(HandoutMessage::DeleteHandout(self.0)).into());
+        }
+    }
+
+    pub struct MarkHandoutRead(pub Uuid);
+    impl Command for MarkHandoutRead {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.handouts.unread.remove(&self.0);
+        }
+    }
+}
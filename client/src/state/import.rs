@@ -0,0 +1,121 @@
+use common::{AbilityDefinition, ItemDefinition, Recharge};
+use serde::de::DeserializeOwned;
+
+use crate::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    Items,
+    Abilities,
+}
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), String>;
+    fn name(&self) -> &str;
+}
+
+impl Validate for ItemDefinition {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Missing a name".to_owned());
+        }
+        if self.description.trim().is_empty() {
+            return Err("Missing a description".to_owned());
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Validate for AbilityDefinition {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Missing a name".to_owned());
+        }
+        if self.max_count < 0 {
+            return Err("max_count can't be negative".to_owned());
+        }
+        match self.recharge {
+            Some(Recharge::Roll(min)) if !(1..=6).contains(&min) => {
+                return Err("A recharge roll needs to be between 1 and 6".to_owned());
+            }
+            Some(Recharge::Rounds(0)) => {
+                return Err("A recharge needs at least one round".to_owned());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Parses a JSON array (or single object) or a CSV file with a header row.
+/// Each entry is parsed and validated on its own so one bad row doesn't hide the rest
+pub fn parse_entries<T: DeserializeOwned + Validate>(text: &str) -> Vec<Result<T, String>> {
+    let trimmed = text.trim_start();
+
+    let entries: Vec<Result<T, String>> = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        let values = match serde_json::from_str::<serde_json::Value>(trimmed) {
+            Ok(serde_json::Value::Array(values)) => values,
+            Ok(value) => vec![value],
+            Err(e) => return vec![Err(format!("Invalid JSON: {e}"))],
+        };
+
+        values
+            .into_iter()
+            .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+            .collect()
+    } else {
+        csv::Reader::from_reader(trimmed.as_bytes())
+            .deserialize()
+            .map(|record| record.map_err(|e| e.to_string()))
+            .collect()
+    };
+
+    entries
+        .into_iter()
+        .map(|entry| entry.and_then(|x| x.validate().map(|_| x)))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct ImportState {
+    /// Number of rows saved by the last import, or why it failed
+    pub last_result: Option<Result<usize, String>>,
+}
+
+impl ImportState {
+    pub fn process(&mut self, message: &DndMessage) {
+        if let DndMessage::ImportResult(result) = message {
+            self.last_result = Some(result.clone());
+        }
+    }
+}
+
+pub mod commands {
+    use common::{AbilityDefinition, ItemDefinition};
+
+    use crate::prelude::*;
+
+    pub struct ImportItems(pub Vec<ItemDefinition>);
+    impl Command for ImportItems {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            state.import.last_result = None;
+            tx.send(DndMessage::ImportItems(self.0).into());
+        }
+    }
+
+    pub struct ImportAbilities(pub Vec<AbilityDefinition>);
+    impl Command for ImportAbilities {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            state.import.last_result = None;
+            tx.send(DndMessage::ImportAbilities(self.0).into());
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::{cmp::Ordering, collections::HashMap, io, path::PathBuf};
+
+use common::EquipSlot;
+
+use crate::{prelude::*, storage};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemSort {
+    /// The order the inventory keeps them in
+    #[default]
+    Custom,
+    Name,
+    Quantity,
+    /// Of the whole stack
+    Weight,
+    /// Equipped items grouped by slot, unequipped ones last
+    Slot,
+}
+
+impl ItemSort {
+    pub const ALL: [ItemSort; 5] = [
+        Self::Custom,
+        Self::Name,
+        Self::Quantity,
+        Self::Weight,
+        Self::Slot,
+    ];
+}
+
+impl std::fmt::Display for ItemSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Custom => write!(f, "Custom"),
+            Self::Name => write!(f, "Name"),
+            Self::Quantity => write!(f, "Quantity"),
+            Self::Weight => write!(f, "Weight"),
+            Self::Slot => write!(f, "Slot"),
+        }
+    }
+}
+
+/// How one character's items are listed
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct InventoryView {
+    pub sort: ItemSort,
+    pub descending: bool,
+    pub show_quantity: bool,
+    pub show_weight: bool,
+    pub show_slot: bool,
+}
+
+impl Default for InventoryView {
+    fn default() -> Self {
+        Self {
+            sort: ItemSort::Custom,
+            descending: false,
+            show_quantity: true,
+            show_weight: true,
+            show_slot: true,
+        }
+    }
+}
+
+/// Weight of the whole stack, `None` if the item doesn't have one
+pub fn stack_weight(item: &Item) -> Option<f32> {
+    item.weight.map(|x| x * item.count as f32)
+}
+
+/// Weight of every item that has one
+pub fn total_weight(items: &[Item]) -> f32 {
+    items.iter().filter_map(stack_weight).sum()
+}
+
+fn slot_rank(slot: Option<EquipSlot>) -> usize {
+    slot.and_then(|x| EquipSlot::ALL.iter().position(|y| *y == x))
+        .unwrap_or(EquipSlot::ALL.len())
+}
+
+impl InventoryView {
+    /// Indices of the items in the order they're listed. Ties keep the custom
+    /// order whichever way it's sorted
+    pub fn order(&self, items: &[Item]) -> Vec<usize> {
+        let names: Vec<String> = items.iter().map(|x| x.name.to_lowercase()).collect();
+        let weight = |x: usize| stack_weight(&items[x]).unwrap_or_default();
+        let compare = |a: usize, b: usize| -> Ordering {
+            match self.sort {
+                ItemSort::Custom => a.cmp(&b),
+                ItemSort::Name => names[a].cmp(&names[b]),
+                ItemSort::Quantity => items[a].count.cmp(&items[b].count),
+                ItemSort::Weight => weight(a).total_cmp(&weight(b)),
+                ItemSort::Slot => slot_rank(items[a].slot).cmp(&slot_rank(items[b].slot)),
+            }
+        };
+
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        match self.descending {
+            true => order.sort_by(|a, b| compare(*b, *a)),
+            false => order.sort_by(|a, b| compare(*a, *b)),
+        }
+        order
+    }
+}
+
+/// Each character's inventory view, saved to a file so it sticks between sessions
+#[derive(Default)]
+pub struct InventoryState {
+    views: HashMap<String, InventoryView>,
+    path: Option<PathBuf>,
+}
+
+impl InventoryState {
+    pub fn load(path: PathBuf) -> Self {
+        let views = match storage::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(
+                    "Could not read inventory views from {}: {e}",
+                    path.display()
+                );
+                HashMap::new()
+            }),
+            // Nobody has changed theirs yet
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            views,
+            path: Some(path),
+        }
+    }
+
+    pub fn view(&self, character: &str) -> InventoryView {
+        self.views.get(character).copied().unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.views)
+            .map_err(io::Error::other)
+            .and_then(|json| storage::write(path, json));
+        if let Err(e) = result {
+            error!("Could not save inventory views to {}: {e}", path.display());
+        }
+    }
+}
+
+pub mod commands {
+    use super::InventoryView;
+    use crate::prelude::*;
+
+    /// (character, view)
+    pub struct SetInventoryView(pub String, pub InventoryView);
+    impl Command for SetInventoryView {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.inventory.views.insert(self.0, self.1);
+            state.inventory.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::Item;
+
+    use super::{InventoryView, ItemSort};
+
+    fn item(name: &str, count: u32) -> Item {
+        Item {
+            id: 0,
+            count,
+            name: name.to_owned(),
+            description: String::new(),
+            flavor_text: String::new(),
+            quest_item: false,
+            slot: None,
+            armor_class: None,
+            attack_bonus: None,
+            requires_attunement: false,
+            attuned: false,
+            weight: None,
+        }
+    }
+
+    #[test]
+    fn descending_ties_keep_the_custom_order() {
+        let items = [item("Rope", 1), item("Torch", 3), item("Apple", 1)];
+        let view = InventoryView {
+            sort: ItemSort::Quantity,
+            descending: true,
+            ..Default::default()
+        };
+        assert_eq!(view.order(&items), [1, 0, 2]);
+
+        let view = InventoryView {
+            descending: false,
+            ..view
+        };
+        assert_eq!(view.order(&items), [0, 2, 1]);
+    }
+
+    #[test]
+    fn the_custom_order_can_be_reversed() {
+        let items = [item("Rope", 1), item("Torch", 3)];
+        let view = InventoryView {
+            sort: ItemSort::Custom,
+            descending: true,
+            ..Default::default()
+        };
+        assert_eq!(view.order(&items), [1, 0]);
+    }
+}
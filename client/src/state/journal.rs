@@ -0,0 +1,52 @@
+use common::message::JournalEntry;
+
+use crate::prelude::*;
+
+/// Only filled in for the GM, a page at a time as they ask for it
+#[derive(Default)]
+pub struct JournalState {
+    pub entries: Vec<JournalEntry>,
+    /// How many entries the server has, some might not have been sent yet
+    pub total: usize,
+}
+
+impl JournalState {
+    pub fn process(&mut self, message: &DndMessage) {
+        if let DndMessage::JournalMessage(JournalMessage::Entries(start, entries, total)) = message
+        {
+            // Pages carry on from what we have, or start over after a squash
+            if *start > self.entries.len() {
+                return;
+            }
+
+            self.entries.truncate(*start);
+            self.entries.extend(entries.iter().cloned());
+            self.total = *total;
+        }
+    }
+
+    /// Whether there's more to ask the server for
+    pub fn is_partial(&self) -> bool {
+        self.entries.len() < self.total
+    }
+}
+
+pub mod commands {
+    use crate::prelude::*;
+
+    /// Asks for the entries from this one on
+    pub struct RequestJournal(pub usize);
+    impl Command for RequestJournal {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::JournalMessage(JournalMessage::Request(self.0)).into());
+        }
+    }
+
+    /// Puts the board back to how it was after this many journal entries
+    pub struct BranchBoard(pub usize);
+    impl Command for BranchBoard {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::JournalMessage(JournalMessage::Branch(self.0)).into());
+        }
+    }
+}
@@ -1,17 +1,74 @@
-use common::{message::DndMessage, User};
+use common::{
+    message::{DndMessage, LogMessage, TradeMessage},
+    ruleset::Ruleset,
+    RollTable, User, XpTable,
+};
+use egui::ahash::HashMap;
+use uuid::Uuid;
 
 pub mod abilities;
+pub mod audio;
 pub mod board;
 pub mod character;
 pub mod chat;
+pub mod connection;
+pub mod dice_tray;
+pub mod effects;
+pub mod encounter;
+pub mod handouts;
+pub mod import;
+pub mod inventory;
+pub mod journal;
+pub mod narration;
+pub mod piece_templates;
+pub mod players;
+pub mod roll_stats;
+pub mod search;
+pub mod session_clock;
+pub mod sheets;
+pub mod snapshots;
+pub mod soundboard;
+pub mod stash;
+pub mod theme;
+pub mod toasts;
+pub mod trade;
 
 #[derive(Default)]
 pub struct DndState {
+    pub appearance: theme::AppearanceState,
     pub board: board::BoardState,
     pub chat: chat::ChatState,
     pub character: character::CharacterState,
+    pub connection: connection::ConnectionState,
+    pub dice_tray: dice_tray::DiceTrayState,
+    /// Sheets for other characters we've looked at
+    pub party: HashMap<String, character::CharacterState>,
+    pub effects: effects::EffectState,
+    pub encounter: encounter::EncounterState,
+    pub handouts: handouts::HandoutState,
+    pub import: import::ImportState,
+    pub inventory: inventory::InventoryState,
+    pub journal: journal::JournalState,
+    pub narration: narration::NarrationState,
+    pub piece_templates: piece_templates::PieceTemplateState,
+    pub players: players::PlayerState,
+    pub roll_stats: roll_stats::RollStatsState,
+    pub search: search::SearchIndex,
+    pub session_clock: session_clock::SessionClockState,
+    pub sheets: sheets::SheetState,
+    pub snapshots: snapshots::SnapshotState,
+    pub soundboard: soundboard::SoundboardState,
+    pub stash: stash::StashState,
+    pub toasts: toasts::ToastState,
+    pub trade: trade::TradeState,
+    pub audio: audio::AudioState,
     pub user: Option<User>,
-    pub character_list: Vec<String>,
+    pub gm: Option<String>,
+    /// Every character in the campaign, as sent by the server
+    pub roster: Vec<String>,
+    pub xp_table: XpTable,
+    pub ruleset: Ruleset,
+    pub roll_tables: Vec<RollTable>,
 }
 
 impl DndState {
@@ -19,14 +76,156 @@ impl DndState {
         self.chat.process(&message);
         self.character.process(&message);
         self.board.process(&message);
+        self.connection.process(&message);
+        self.effects.process(&message);
+        self.players.process(&message);
+        self.roll_stats.process(&message);
+        self.session_clock.process(&message);
+        self.sheets.process(&message);
+        self.snapshots.process(&message);
+        self.soundboard.process(&message);
+        self.stash.process(&message);
+        self.handouts.process(&message, self.is_gm());
+        self.import.process(&message);
+        self.journal.process(&message);
+        self.piece_templates.process(&message);
+        self.toasts.process(&message);
+        self.trade.process(&message);
+        self.audio.process(&message, self.user.as_ref());
+        self.narration
+            .process(&message, self.user.as_ref(), self.is_gm());
 
+        let reindex = search::SearchIndex::is_outdated_by(&message);
         match message {
-            DndMessage::CharacterList(list) => self.character_list = list,
+            DndMessage::CharacterRoster(list) => self.roster = list,
+            DndMessage::GameMaster(name) => self.gm = Some(name),
+            DndMessage::SetXpTable(table) => self.xp_table = table,
+            DndMessage::SetRuleset(ruleset) => self.ruleset = ruleset,
+            DndMessage::RollTables(tables) => self.roll_tables = tables,
+            DndMessage::SaveRollTable(table) => {
+                match self.roll_tables.iter_mut().find(|x| x.name == table.name) {
+                    Some(existing) => *existing = table,
+                    None => self.roll_tables.push(table),
+                }
+            }
+            DndMessage::DeleteRollTable(name) => self.roll_tables.retain(|x| x.name != name),
+            DndMessage::TradeMessage(TradeMessage::Closed(_, reason)) => {
+                self.toasts.push("Trade", reason)
+            }
+            // Keeps the sheets we've already loaded current without asking for them again
+            DndMessage::Log(_, LogMessage::HpChanged(name, _, hp), _) => {
+                if let Some(sheet) = self.party.get_mut(&name) {
+                    sheet.character.curr_hp = hp;
+                }
+            }
+            DndMessage::Log(_, LogMessage::LifeChanged(name, life), _)
+            | DndMessage::Log(_, LogMessage::DeathSave(name, _, life), _) => {
+                if let Some(sheet) = self.party.get_mut(&name) {
+                    sheet.character.set_life(life);
+                }
+            }
+            // The GM's edit to someone else's sheet lost to a newer change,
+            // show them what it is now
+            DndMessage::CharacterConflict(character, change) => {
+                if let Some(sheet) = self.party.get_mut(&character.name) {
+                    let text = format!(
+                        "{}'s {} changed before your edit was saved",
+                        character.name,
+                        change.field_name().to_lowercase()
+                    );
+                    self.toasts.push("Character", text);
+                    sheet.character = character;
+                }
+            }
+            DndMessage::PartyMemberData(character, items, abilities) => {
+                self.party.insert(
+                    character.name.clone(),
+                    character::CharacterState {
+                        character,
+                        items,
+                        abilities,
+                        ..Default::default()
+                    },
+                );
+            }
             _ => {}
         };
+
+        if reindex {
+            self.search = search::SearchIndex::build(self);
+        } else {
+            let is_gm = self.is_gm();
+            self.search.add_chat(&self.chat, is_gm);
+        }
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+    }
+
+    /// Logs anything about the sheets that a message left in a state it
+    /// should never be in
+    #[cfg(debug_assertions)]
+    fn check_invariants(&self) {
+        let sheets = std::iter::once(&self.character).chain(self.party.values());
+        for sheet in sheets {
+            let broken =
+                common::invariants::check_sheet(&sheet.character, &sheet.items, &sheet.abilities);
+            for problem in broken {
+                log::warn!("Invariant broken: {problem}");
+            }
+        }
     }
 
     pub fn owned_user(&self) -> User {
         self.user.clone().unwrap()
     }
+
+    /// Players can only change their own character, the GM can fix anyone's
+    pub fn can_edit_character(&self, name: &str) -> bool {
+        self.is_gm() || self.user.as_ref().is_some_and(|user| user.name == name)
+    }
+
+    /// Our own sheet, or another character's if we've loaded it
+    pub fn character_sheet(&self, name: &str) -> Option<&character::CharacterState> {
+        if self.user.as_ref().is_some_and(|user| user.name == name) {
+            Some(&self.character)
+        } else {
+            self.party.get(name)
+        }
+    }
+
+    pub fn character_sheet_mut(&mut self, name: &str) -> Option<&mut character::CharacterState> {
+        if self.user.as_ref().is_some_and(|user| user.name == name) {
+            Some(&mut self.character)
+        } else {
+            self.party.get_mut(name)
+        }
+    }
+
+    /// Players can only move and edit the pieces they own, the server rejects anything else.
+    /// Only the GM can touch a piece someone else is holding
+    pub fn can_control_piece(&self, uuid: &Uuid) -> bool {
+        let Some(user) = self.user.as_ref() else {
+            return false;
+        };
+        let Some(piece) = self.board.players.get(uuid) else {
+            return self.is_gm();
+        };
+        if !self.is_gm() && piece.holder.as_ref().is_some_and(|x| *x != user.name) {
+            return false;
+        }
+
+        self.is_gm() || piece.owners.contains(&user.name)
+    }
+
+    pub fn is_gm(&self) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|user| self.gm.as_ref() == Some(&user.name))
+    }
+
+    /// The ruleset can stop our character using abilities and items while they're down
+    pub fn can_act(&self) -> bool {
+        !(self.ruleset.disable_actions_when_down && self.character.character.life.is_down())
+    }
 }
@@ -0,0 +1,96 @@
+use chrono::Local;
+
+use crate::{prelude::*, state::chat::ClientLogMessage};
+
+/// Accessibility options for players who can't easily read the chat
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct NarrationSettings {
+    /// Chat messages are drawn as a line of text each instead of cards, in
+    /// the same words a screen reader reads them
+    pub plain_chat: bool,
+    /// Chat and roll results are read aloud as they come in
+    pub read_aloud: bool,
+}
+
+#[derive(Default)]
+pub struct NarrationState {
+    pub settings: NarrationSettings,
+    /// Started the first time something is read, so players who never turn
+    /// it on don't need a speech service
+    #[cfg(feature = "narration")]
+    tts: Option<tts::Tts>,
+    #[cfg(feature = "narration")]
+    tts_failed: bool,
+}
+
+impl NarrationState {
+    pub fn new(settings: NarrationSettings) -> Self {
+        Self {
+            settings,
+            ..Default::default()
+        }
+    }
+
+    /// Whether this build can read aloud, it needs the `narration` feature
+    pub fn can_read_aloud(&self) -> bool {
+        cfg!(feature = "narration")
+    }
+
+    pub fn process(&mut self, message: &DndMessage, user: Option<&User>, is_gm: bool) {
+        if !self.settings.read_aloud {
+            return;
+        }
+
+        let DndMessage::Log(from, msg, _) = message else {
+            return;
+        };
+
+        // We already know what we said, but still want to hear how our rolls went
+        let ours = user.is_some_and(|x| x.name == from.name);
+        if ours && matches!(msg, LogMessage::Chat(_) | LogMessage::Emote(..)) {
+            return;
+        }
+
+        let message = ClientLogMessage::new(from.clone(), msg.clone(), Local::now());
+        self.speak(&message.narration(is_gm));
+    }
+
+    #[cfg(feature = "narration")]
+    fn speak(&mut self, text: &str) {
+        if self.tts.is_none() && !self.tts_failed {
+            self.tts = tts::Tts::default()
+                .inspect_err(|e| warn!("Text to speech isn't available: {e}"))
+                .ok();
+            self.tts_failed = self.tts.is_none();
+        }
+
+        if let Some(tts) = &mut self.tts {
+            if let Err(e) = tts.speak(text, false) {
+                warn!("Could not read the message aloud: {e}");
+            }
+        }
+    }
+
+    #[cfg(not(feature = "narration"))]
+    fn speak(&mut self, _text: &str) {}
+}
+
+pub mod commands {
+    use crate::prelude::*;
+
+    use super::NarrationSettings;
+
+    pub struct SetNarrationSettings(pub NarrationSettings);
+
+    impl Command for SetNarrationSettings {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.narration.settings = self.0;
+            // Saved with the rest of the appearance so it sticks between sessions
+            if state.appearance.current.narration != self.0 {
+                state.appearance.current.narration = self.0;
+                state.appearance.save();
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::{io, path::PathBuf};
+
+use common::PieceTemplate;
+
+use crate::{prelude::*, storage};
+
+/// Pieces saved to stamp copies of. Our own are kept in a file, the shared
+/// ones come from the server so the whole table has the same set
+#[derive(Default)]
+pub struct PieceTemplateState {
+    pub saved: Vec<PieceTemplate>,
+    pub shared: Vec<PieceTemplate>,
+    path: Option<PathBuf>,
+}
+
+impl PieceTemplateState {
+    pub fn load(path: PathBuf) -> Self {
+        let saved = match storage::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(
+                    "Could not read piece templates from {}: {e}",
+                    path.display()
+                );
+                Vec::new()
+            }),
+            // Nothing's been saved yet
+            Err(_) => Vec::new(),
+        };
+
+        Self {
+            saved,
+            shared: Vec::new(),
+            path: Some(path),
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.saved)
+            .map_err(io::Error::other)
+            .and_then(|json| storage::write(path, json));
+        if let Err(e) = result {
+            error!("Could not save piece templates to {}: {e}", path.display());
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&PieceTemplate> {
+        self.saved
+            .iter()
+            .chain(self.shared.iter())
+            .find(|x| x.name == name)
+    }
+
+    pub fn is_shared(&self, name: &str) -> bool {
+        self.shared.iter().any(|x| x.name == name)
+    }
+
+    pub fn process(&mut self, message: &DndMessage) {
+        match message {
+            DndMessage::PieceTemplates(templates) => self.shared = templates.clone(),
+            DndMessage::SavePieceTemplate(template) => {
+                match self.shared.iter_mut().find(|x| x.name == template.name) {
+                    Some(existing) => *existing = template.clone(),
+                    None => self.shared.push(template.clone()),
+                }
+            }
+            DndMessage::DeletePieceTemplate(name) => self.shared.retain(|x| x.name != *name),
+            _ => {}
+        }
+    }
+}
+
+pub mod commands {
+    use common::PieceTemplate;
+    use uuid::Uuid;
+
+    use crate::{prelude::*, state::board::commands::snap_to_grid_for_size};
+
+    /// Keeps the piece as a template, replacing any of ours with the same name
+    pub struct SaveAsTemplate {
+        pub piece: Uuid,
+        pub name: String,
+    }
+
+    impl Command for SaveAsTemplate {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            let Some(piece) = state.board.players.get(&self.piece) else {
+                return;
+            };
+
+            let spacing = state.board.grid.spacing;
+            let mut piece = piece.to_common();
+            piece.position = Pos2::ZERO;
+            piece.size /= spacing;
+            piece.owners.clear();
+            piece.statuses.clear();
+            if let Some(label) = &mut piece.label {
+                label.size /= spacing;
+            }
+
+            let template = PieceTemplate {
+                name: self.name,
+                piece,
+            };
+            let templates = &mut state.piece_templates;
+            match templates.saved.iter_mut().find(|x| x.name == template.name) {
+                Some(existing) => *existing = template,
+                None => templates.saved.push(template),
+            }
+            templates.save();
+        }
+    }
+
+    pub struct DeleteTemplate(pub String);
+
+    impl Command for DeleteTemplate {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            let templates = &mut state.piece_templates;
+            templates.saved.retain(|x| x.name != self.0);
+            templates.save();
+        }
+    }
+
+    /// Sends one of our templates to the server for everyone to use
+    pub struct ShareTemplate(pub String);
+
+    impl Command for ShareTemplate {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let templates = &state.piece_templates;
+            if let Some(template) = templates.saved.iter().find(|x| x.name == self.0) {
+                tx.send(DndMessage::SavePieceTemplate(template.clone()).into());
+            }
+        }
+    }
+
+    pub struct UnshareTemplate(pub String);
+
+    impl Command for UnshareTemplate {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::DeletePieceTemplate(self.0).into());
+        }
+    }
+
+    /// Places a copy of the template centered on `pos` on the active board
+    pub struct StampTemplate {
+        pub template: String,
+        pub pos: Pos2,
+    }
+
+    impl Command for StampTemplate {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let Some(template) = state.piece_templates.find(&self.template) else {
+                return;
+            };
+
+            let grid = &state.board.grid;
+            let mut piece = template.piece.clone();
+            piece.size *= grid.spacing;
+            if let Some(label) = &mut piece.label {
+                label.size *= grid.spacing;
+            }
+            piece.position = snap_to_grid_for_size(grid, self.pos - piece.size / 2.0, piece.size);
+            piece.board = state.board.active_board;
+
+            // Same as adding a piece, players keep control of what they place
+            let user = state.owned_user().name;
+            if !state.is_gm() && !piece.owners.contains(&user) {
+                piece.owners.push(user);
+            }
+
+            tx.send(
+                DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(Uuid::new_v4(), piece))
+                    .into(),
+            );
+        }
+    }
+}
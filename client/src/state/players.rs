@@ -0,0 +1,59 @@
+use std::collections::BTreeSet;
+
+use egui::ahash::HashMap;
+use web_time::Instant;
+
+use crate::prelude::*;
+
+#[derive(Default)]
+pub struct PlayerState {
+    /// Other users connected to the server
+    pub online: BTreeSet<String>,
+    typing: HashMap<String, Instant>,
+}
+
+impl PlayerState {
+    /// Typing notifications can get lost if someone disconnects mid message, so
+    /// they time out on their own as well.
+    const TYPING_TIMEOUT_SECS: f32 = 6.0;
+
+    pub fn process(&mut self, message: &DndMessage) {
+        match message {
+            DndMessage::UserList(list) => {
+                self.online = list.iter().cloned().collect();
+            }
+            DndMessage::UserNotificationAdded(name) => {
+                self.online.insert(name.clone());
+            }
+            DndMessage::UserNotificationRemoved(name) => {
+                self.online.remove(name);
+                self.typing.remove(name);
+            }
+            DndMessage::Typing(name, true) => {
+                self.typing.insert(name.clone(), Instant::now());
+            }
+            DndMessage::Typing(name, false) => {
+                self.typing.remove(name);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_typing(&self, name: &str) -> bool {
+        self.typing
+            .get(name)
+            .is_some_and(|x| x.elapsed().as_secs_f32() < Self::TYPING_TIMEOUT_SECS)
+    }
+}
+
+pub mod commands {
+    use crate::prelude::*;
+
+    pub struct SetTyping(pub bool);
+
+    impl Command for SetTyping {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::Typing(state.owned_user().name, self.0).into());
+        }
+    }
+}
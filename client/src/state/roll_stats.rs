@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+use common::stats::{counted_rolls, RollStats};
+
+use crate::prelude::*;
+
+/// Everyone's public rolls, kept up to date from the log between the totals
+/// the server sends on join
+#[derive(Default)]
+pub struct RollStatsState {
+    /// Since the GM last reset the session clock
+    pub session: BTreeMap<String, RollStats>,
+    /// Every session before this one
+    pub earlier: BTreeMap<String, RollStats>,
+}
+
+impl RollStatsState {
+    pub fn process(&mut self, message: &DndMessage) {
+        match message {
+            DndMessage::RollStats(session, earlier) => {
+                self.session = session.clone();
+                self.earlier = earlier.clone();
+            }
+            DndMessage::Log(_, LogMessage::SessionSummary(summary), _) => {
+                for (player, stats) in summary {
+                    self.earlier.entry(player.clone()).or_default().merge(stats);
+                }
+                self.session.clear();
+            }
+            DndMessage::Log(user, msg, _) => {
+                for roll in counted_rolls(msg) {
+                    self.session
+                        .entry(user.name.clone())
+                        .or_default()
+                        .record(roll);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// This session's rolls added onto the earlier ones
+    pub fn campaign(&self) -> BTreeMap<String, RollStats> {
+        let mut campaign = self.earlier.clone();
+        for (player, stats) in &self.session {
+            campaign.entry(player.clone()).or_default().merge(stats);
+        }
+        campaign
+    }
+}
@@ -0,0 +1,184 @@
+use crate::prelude::*;
+
+use super::chat::ChatState;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SearchCategory {
+    Items,
+    Abilities,
+    Characters,
+    Chat,
+}
+
+impl SearchCategory {
+    pub const ALL: [SearchCategory; 4] = [
+        SearchCategory::Items,
+        SearchCategory::Abilities,
+        SearchCategory::Characters,
+        SearchCategory::Chat,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchCategory::Items => "Items",
+            SearchCategory::Abilities => "Abilities",
+            SearchCategory::Characters => "Characters",
+            SearchCategory::Chat => "Chat",
+        }
+    }
+}
+
+/// Where a result leads when it's clicked
+#[derive(Clone)]
+pub enum SearchTarget {
+    Item {
+        owner: String,
+        id: i64,
+    },
+    Ability {
+        owner: String,
+    },
+    Character(String),
+    /// Index into the chat log
+    Chat(usize),
+}
+
+impl SearchTarget {
+    pub fn category(&self) -> SearchCategory {
+        match self {
+            SearchTarget::Item { .. } => SearchCategory::Items,
+            SearchTarget::Ability { .. } => SearchCategory::Abilities,
+            SearchTarget::Character(_) => SearchCategory::Characters,
+            SearchTarget::Chat(_) => SearchCategory::Chat,
+        }
+    }
+}
+
+pub struct SearchEntry {
+    pub target: SearchTarget,
+    pub title: String,
+    pub detail: String,
+    /// Lowercase copies so searching doesn't redo them on every keystroke
+    title_key: Vec<char>,
+    detail_key: String,
+}
+
+impl SearchEntry {
+    fn new(target: SearchTarget, title: String, detail: String) -> Self {
+        Self {
+            title_key: title.to_lowercase().chars().collect(),
+            detail_key: detail.to_lowercase(),
+            target,
+            title,
+            detail,
+        }
+    }
+
+    /// Names are matched loosely, descriptions need every word of the query
+    pub fn score(&self, query: &str) -> Option<u32> {
+        let query = query.to_lowercase();
+        let title = fuzzy_score(&query, &self.title_key).map(|x| x * 2 + 1);
+        let detail = query
+            .split_whitespace()
+            .all(|word| self.detail_key.contains(word))
+            .then_some(1);
+        title.max(detail)
+    }
+}
+
+/// Characters of `query` in order somewhere in `text`. Runs of them and ones
+/// at the start of a word count for more, so "lsw" finds "Long Sword" first
+fn fuzzy_score(query: &str, text: &[char]) -> Option<u32> {
+    let mut score = 0;
+    let mut from = 0;
+    let mut last = None;
+
+    for c in query.chars().filter(|x| !x.is_whitespace()) {
+        let found = from + text[from..].iter().position(|x| *x == c)?;
+        score += 1;
+        if last.is_some_and(|x: usize| x + 1 == found) {
+            score += 3;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        last = Some(found);
+        from = found + 1;
+    }
+
+    (score > 0).then_some(score)
+}
+
+/// Everything the campaign search looks through. Rebuilt when the data it's
+/// made from arrives, chat is added to as messages come in
+#[derive(Default)]
+pub struct SearchIndex {
+    pub entries: Vec<SearchEntry>,
+    /// Chat messages already in `entries`
+    indexed_chat: usize,
+}
+
+impl SearchIndex {
+    /// Messages that change anything the index is built from
+    pub fn is_outdated_by(message: &DndMessage) -> bool {
+        matches!(
+            message,
+            DndMessage::ItemList(_)
+                | DndMessage::AbilityList(_)
+                | DndMessage::CharacterData(_)
+                | DndMessage::PartyMemberData(..)
+                | DndMessage::CharacterRoster(_)
+                | DndMessage::GameMaster(_)
+        )
+    }
+
+    pub fn build(state: &DndState) -> Self {
+        let mut entries = Vec::new();
+
+        let mut sheets = state.party.iter().collect::<Vec<_>>();
+        if let Some(user) = &state.user {
+            sheets.push((&user.name, &state.character));
+        }
+
+        for (owner, sheet) in sheets {
+            entries.extend(sheet.items.iter().map(|x| {
+                let target = SearchTarget::Item {
+                    owner: owner.clone(),
+                    id: x.id,
+                };
+                SearchEntry::new(target, x.name.clone(), x.description.clone())
+            }));
+            entries.extend(sheet.abilities.iter().map(|x| {
+                let target = SearchTarget::Ability {
+                    owner: owner.clone(),
+                };
+                SearchEntry::new(target, x.name.clone(), x.description.clone())
+            }));
+        }
+
+        entries.extend(state.roster.iter().map(|name| {
+            let detail = state
+                .character_sheet(name)
+                .map(|x| format!("{} {}", x.character.tagline, x.character.backstory))
+                .unwrap_or_default();
+            SearchEntry::new(SearchTarget::Character(name.clone()), name.clone(), detail)
+        }));
+
+        let mut index = Self {
+            entries,
+            indexed_chat: 0,
+        };
+        index.add_chat(&state.chat, state.is_gm());
+        index
+    }
+
+    /// Picks up any chat messages that arrived since the last call
+    pub fn add_chat(&mut self, chat: &ChatState, is_gm: bool) {
+        let new = chat.log_messages.iter().enumerate().skip(self.indexed_chat);
+        self.entries.extend(new.map(|(i, msg)| {
+            let detail = format!("{}, {}", msg.speaker(), msg.time_label());
+            SearchEntry::new(SearchTarget::Chat(i), msg.summary(is_gm), detail)
+        }));
+        self.indexed_chat = chat.log_messages.len();
+    }
+}
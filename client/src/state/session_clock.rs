@@ -0,0 +1,62 @@
+use common::message::SessionClock;
+use web_time::{Duration, Instant};
+
+use crate::prelude::*;
+
+/// The GM's session clock, counted on locally between updates from the server
+#[derive(Default)]
+pub struct SessionClockState {
+    pub clock: SessionClock,
+    received: Option<Instant>,
+}
+
+impl SessionClockState {
+    pub fn process(&mut self, message: &DndMessage) {
+        if let DndMessage::SessionClockMessage(SessionClockMessage::State(clock)) = message {
+            self.clock = *clock;
+            self.received = Some(Instant::now());
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        let elapsed = Duration::from_secs(self.clock.elapsed);
+        match self.received {
+            Some(received) if self.clock.running => elapsed + received.elapsed(),
+            _ => elapsed,
+        }
+    }
+}
+
+pub mod commands {
+    use crate::prelude::*;
+
+    pub struct StartSessionClock;
+    impl Command for StartSessionClock {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SessionClockMessage(SessionClockMessage::Start).into());
+        }
+    }
+
+    pub struct PauseSessionClock;
+    impl Command for PauseSessionClock {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SessionClockMessage(SessionClockMessage::Pause).into());
+        }
+    }
+
+    pub struct ResetSessionClock;
+    impl Command for ResetSessionClock {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SessionClockMessage(SessionClockMessage::Reset).into());
+        }
+    }
+
+    /// Minutes between break reminders, `None` turns them off
+    pub struct SetBreakInterval(pub Option<u32>);
+    impl Command for SetBreakInterval {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            let msg = SessionClockMessage::SetBreakInterval(self.0);
+            tx.send(DndMessage::SessionClockMessage(msg).into());
+        }
+    }
+}
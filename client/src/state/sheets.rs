@@ -0,0 +1,79 @@
+use common::message::DndMessage;
+
+pub struct SheetWindow {
+    pub name: String,
+    pub minimized: bool,
+}
+
+/// Character sheets popped out into their own windows, in the order they were opened
+#[derive(Default)]
+pub struct SheetState {
+    pub windows: Vec<SheetWindow>,
+}
+
+impl SheetState {
+    pub fn process(&mut self, message: &DndMessage) {
+        // Deleted characters take their sheet with them
+        if let DndMessage::CharacterRoster(list) = message {
+            self.windows.retain(|x| list.contains(&x.name));
+        }
+    }
+
+    pub fn is_open(&self, name: &str) -> bool {
+        self.windows.iter().any(|x| x.name == name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut SheetWindow> {
+        self.windows.iter_mut().find(|x| x.name == name)
+    }
+}
+
+pub mod commands {
+    use super::SheetWindow;
+    use crate::prelude::*;
+
+    /// Opens the sheet, or brings it back if it was minimized
+    pub struct OpenSheet(pub String);
+    impl Command for OpenSheet {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            if let Some(window) = state.sheets.get_mut(&self.0) {
+                window.minimized = false;
+                return;
+            }
+
+            if self.0 != state.owned_user().name {
+                tx.send(DndMessage::RetrievePartyMember(self.0.clone()).into());
+            }
+            state.sheets.windows.push(SheetWindow {
+                name: self.0,
+                minimized: false,
+            });
+        }
+    }
+
+    pub struct CloseSheet(pub String);
+    impl Command for CloseSheet {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.sheets.windows.retain(|x| x.name != self.0);
+        }
+    }
+
+    pub struct CloseAllSheets;
+    impl Command for CloseAllSheets {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            state.sheets.windows.clear();
+        }
+    }
+
+    pub struct MinimizeSheet {
+        pub name: String,
+        pub minimized: bool,
+    }
+    impl Command for MinimizeSheet {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if let Some(window) = state.sheets.get_mut(&self.name) {
+                window.minimized = self.minimized;
+            }
+        }
+    }
+}
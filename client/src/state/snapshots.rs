@@ -0,0 +1,66 @@
+use common::{SnapshotInfo, SnapshotUsage};
+
+use crate::prelude::*;
+
+/// Only filled in for the GM
+#[derive(Default)]
+pub struct SnapshotState {
+    pub snapshots: Vec<SnapshotInfo>,
+    /// Last storage check, only asked for by the GM
+    pub usage: Option<SnapshotUsage>,
+}
+
+impl SnapshotState {
+    pub fn process(&mut self, message: &DndMessage) {
+        match message {
+            DndMessage::SnapshotMessage(SnapshotMessage::List(list)) => {
+                self.snapshots = list.clone();
+            }
+            DndMessage::SnapshotMessage(SnapshotMessage::Usage(usage)) => {
+                self.usage = Some(*usage);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub mod commands {
+    use uuid::Uuid;
+
+    use crate::prelude::*;
+
+    pub struct TakeSnapshot(pub String);
+    impl Command for TakeSnapshot {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SnapshotMessage(SnapshotMessage::Take(self.0)).into());
+        }
+    }
+
+    pub struct SaveNow;
+    impl Command for SaveNow {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SnapshotMessage(SnapshotMessage::SaveNow).into());
+        }
+    }
+
+    pub struct RestoreSnapshot(pub Uuid);
+    impl Command for RestoreSnapshot {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SnapshotMessage(SnapshotMessage::Restore(self.0)).into());
+        }
+    }
+
+    pub struct CheckSnapshotUsage;
+    impl Command for CheckSnapshotUsage {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SnapshotMessage(SnapshotMessage::RequestUsage).into());
+        }
+    }
+
+    pub struct DeleteSnapshot(pub Uuid);
+    impl Command for DeleteSnapshot {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SnapshotMessage(SnapshotMessage::Delete(self.0)).into());
+        }
+    }
+}
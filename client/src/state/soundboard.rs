@@ -0,0 +1,72 @@
+use common::soundboard::{NowPlaying, Soundboard};
+
+use crate::prelude::*;
+
+/// The GM's tracks and what's playing, for the soundboard tab. The audio
+/// state does the playing
+#[derive(Default)]
+pub struct SoundboardState {
+    pub soundboard: Soundboard,
+    pub now_playing: Option<NowPlaying>,
+}
+
+impl SoundboardState {
+    pub fn process(&mut self, message: &DndMessage) {
+        let DndMessage::SoundMessage(msg) = message else {
+            return;
+        };
+
+        match msg {
+            SoundMessage::SetSoundboard(soundboard) => self.soundboard = soundboard.clone(),
+            SoundMessage::Play(playing) => self.now_playing = Some(playing.clone()),
+            SoundMessage::Stop(_) => self.now_playing = None,
+            SoundMessage::Effect(_) => {}
+        }
+    }
+}
+
+pub mod commands {
+    use common::soundboard::{NowPlaying, Soundboard};
+
+    use crate::prelude::*;
+
+    pub struct SaveSoundboard(pub Soundboard);
+    impl Command for SaveSoundboard {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SoundMessage(SoundMessage::SetSoundboard(self.0)).into());
+        }
+    }
+
+    /// Starts a track or playlist for everyone, fading in over `fade` seconds
+    pub struct PlayMusic {
+        pub name: String,
+        pub tracks: Vec<String>,
+        pub fade: f32,
+    }
+    impl Command for PlayMusic {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            let playing = NowPlaying {
+                name: self.name,
+                tracks: self.tracks,
+                fade: self.fade,
+                elapsed: 0.0,
+            };
+            tx.send(DndMessage::SoundMessage(SoundMessage::Play(playing)).into());
+        }
+    }
+
+    /// Fades the music out for everyone over this many seconds
+    pub struct StopMusic(pub f32);
+    impl Command for StopMusic {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SoundMessage(SoundMessage::Stop(self.0)).into());
+        }
+    }
+
+    pub struct PlayEffect(pub String);
+    impl Command for PlayEffect {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::SoundMessage(SoundMessage::Effect(self.0)).into());
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use common::Loot;
+use egui::ahash::HashMap;
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+pub struct StashState {
+    pub loot: HashMap<Uuid, Loot>,
+    /// Players have to wait for the GM before claimed loot is theirs
+    pub require_approval: bool,
+}
+
+impl Default for StashState {
+    fn default() -> Self {
+        Self {
+            loot: HashMap::default(),
+            require_approval: true,
+        }
+    }
+}
+
+impl StashState {
+    pub fn process(&mut self, message: &DndMessage) {
+        let DndMessage::StashMessage(msg) = message else {
+            return;
+        };
+
+        match msg {
+            StashMessage::SetLoot(uuid, loot) => {
+                self.loot.insert(*uuid, loot.clone());
+            }
+            StashMessage::RemoveLoot(uuid) => {
+                self.loot.remove(uuid);
+            }
+            StashMessage::RequireApproval(required) => self.require_approval = *required,
+            // The server answers these with the updated loot
+            StashMessage::AddLoot(..)
+            | StashMessage::ClaimLoot(..)
+            | StashMessage::ResolveClaim(..) => {}
+        }
+    }
+
+    pub fn sorted(&self) -> impl Iterator<Item = (&Uuid, &Loot)> {
+        self.loot
+            .iter()
+            .sorted_by_key(|(_, loot)| loot.item.name.to_lowercase())
+    }
+}
+
+pub mod commands {
+    use uuid::Uuid;
+
+    use crate::prelude::*;
+
+    pub struct AddLoot {
+        pub name: String,
+        pub count: u32,
+    }
+
+    impl Command for AddLoot {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::StashMessage(StashMessage::AddLoot(self.name, self.count)).into());
+        }
+    }
+
+    /// Claims the loot for the owned character
+    pub struct ClaimLoot(pub Uuid);
+    impl Command for ClaimLoot {
+        fn execute(self: Box<Self>, state: &mut DndState, tx: &EventSender<Signal>) {
+            let character = state.owned_user().name;
+            tx.send(DndMessage::StashMessage(StashMessage::ClaimLoot(self.0, character)).into());
+        }
+    }
+
+    pub struct ResolveClaim {
+        pub uuid: Uuid,
+        pub approved: bool,
+    }
+
+    impl Command for ResolveClaim {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(
+                DndMessage::StashMessage(StashMessage::ResolveClaim(self.uuid, self.approved))
+                    .into(),
+            );
+        }
+    }
+
+    pub struct RemoveLoot(pub Uuid);
+    impl Command for RemoveLoot {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::StashMessage(StashMessage::RemoveLoot(self.0)).into());
+        }
+    }
+
+    pub struct SetRequireApproval(pub bool);
+    impl Command for SetRequireApproval {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::StashMessage(StashMessage::RequireApproval(self.0)).into());
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use egui::{Id, Visuals};
+
+use super::narration::NarrationSettings;
+use crate::{prelude::*, storage};
+
+/// Colors with a meaning, so they can be swapped out for ones that are easier
+/// to tell apart. Everything else follows the egui light or dark visuals
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    /// Hits, healing and anything else that went well
+    pub positive: Color32,
+    /// Misses, damage, errors and buttons that can't be undone
+    pub negative: Color32,
+    /// Quest items and things worth a second look
+    pub caution: Color32,
+    /// Natural 20s, the GM and NPCs
+    pub critical: Color32,
+    /// Speaker names and attunement
+    pub accent: Color32,
+    /// Outline of the selected piece on the board
+    pub selection: Color32,
+}
+
+impl Palette {
+    pub const DARK: Palette = Palette {
+        positive: Color32::LIGHT_GREEN,
+        negative: Color32::LIGHT_RED,
+        caution: Color32::YELLOW,
+        critical: Color32::GOLD,
+        accent: Color32::LIGHT_BLUE,
+        selection: Color32::LIGHT_RED,
+    };
+
+    pub const LIGHT: Palette = Palette {
+        positive: Color32::from_rgb(0, 130, 40),
+        negative: Color32::from_rgb(200, 30, 30),
+        caution: Color32::from_rgb(170, 120, 0),
+        critical: Color32::from_rgb(180, 120, 0),
+        accent: Color32::from_rgb(30, 90, 200),
+        selection: Color32::from_rgb(220, 40, 40),
+    };
+
+    /// Okabe-Ito colors, which stay distinct with the common kinds of color
+    /// blindness. Good and bad are blue and orange rather than green and red
+    pub const COLORBLIND_DARK: Palette = Palette {
+        positive: Color32::from_rgb(86, 180, 233),
+        negative: Color32::from_rgb(230, 159, 0),
+        caution: Color32::from_rgb(240, 228, 66),
+        critical: Color32::from_rgb(204, 121, 167),
+        accent: Color32::from_rgb(0, 158, 115),
+        selection: Color32::from_rgb(240, 228, 66),
+    };
+
+    pub const COLORBLIND_LIGHT: Palette = Palette {
+        positive: Color32::from_rgb(0, 114, 178),
+        negative: Color32::from_rgb(213, 94, 0),
+        caution: Color32::from_rgb(150, 130, 0),
+        critical: Color32::from_rgb(170, 70, 130),
+        accent: Color32::from_rgb(0, 120, 90),
+        selection: Color32::from_rgb(213, 94, 0),
+    };
+
+    /// Every color with its name, for editing
+    pub fn roles_mut(&mut self) -> [(&'static str, &mut Color32); 6] {
+        [
+            ("Positive", &mut self.positive),
+            ("Negative", &mut self.negative),
+            ("Caution", &mut self.caution),
+            ("Critical", &mut self.critical),
+            ("Accent", &mut self.accent),
+            ("Selection", &mut self.selection),
+        ]
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub dark: bool,
+    pub palette: Palette,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemePreset::Dark.theme()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemePreset {
+    Dark,
+    Light,
+    ColorblindDark,
+    ColorblindLight,
+}
+
+impl ThemePreset {
+    pub const ALL: [ThemePreset; 4] = [
+        ThemePreset::Dark,
+        ThemePreset::Light,
+        ThemePreset::ColorblindDark,
+        ThemePreset::ColorblindLight,
+    ];
+
+    pub fn theme(&self) -> Theme {
+        let (dark, palette) = match self {
+            ThemePreset::Dark => (true, Palette::DARK),
+            ThemePreset::Light => (false, Palette::LIGHT),
+            ThemePreset::ColorblindDark => (true, Palette::COLORBLIND_DARK),
+            ThemePreset::ColorblindLight => (false, Palette::COLORBLIND_LIGHT),
+        };
+        Theme { dark, palette }
+    }
+
+    /// The preset `theme` is, unless it's been customized
+    pub fn of(theme: &Theme) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.theme() == *theme)
+    }
+}
+
+impl std::fmt::Display for ThemePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThemePreset::Dark => write!(f, "Dark"),
+            ThemePreset::Light => write!(f, "Light"),
+            ThemePreset::ColorblindDark => write!(f, "Colorblind (dark)"),
+            ThemePreset::ColorblindLight => write!(f, "Colorblind (light)"),
+        }
+    }
+}
+
+/// Everything in the settings that changes how the UI looks or reads out
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Appearance {
+    pub theme: Theme,
+    /// Scales the whole UI, egui's pixels per point
+    pub ui_scale: f32,
+    /// Size of the body text in the chat log, the rest of its text scales with it
+    pub chat_font_size: f32,
+    pub narration: NarrationSettings,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            ui_scale: 1.5,
+            chat_font_size: DEFAULT_BODY_SIZE,
+            narration: NarrationSettings::default(),
+        }
+    }
+}
+
+/// egui's own body text size
+const DEFAULT_BODY_SIZE: f32 = 12.5;
+
+/// What the settings sliders allow, anything outside them is pulled back in on load
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.5..=3.0;
+pub const CHAT_FONT_SIZE_RANGE: RangeInclusive<f32> = 8.0..=32.0;
+
+/// Where the theme was saved before the scale and font size joined it
+const OLD_THEME_FILE: &str = "theme.json";
+
+impl Appearance {
+    /// Keeps a hand edited or corrupt file from leaving the UI unusable
+    fn clamped(self) -> Self {
+        let clamp = |value: f32, range: RangeInclusive<f32>, default: f32| {
+            if value.is_finite() {
+                value.clamp(*range.start(), *range.end())
+            } else {
+                default
+            }
+        };
+
+        let default = Self::default();
+        Self {
+            ui_scale: clamp(self.ui_scale, UI_SCALE_RANGE, default.ui_scale),
+            chat_font_size: clamp(
+                self.chat_font_size,
+                CHAT_FONT_SIZE_RANGE,
+                default.chat_font_size,
+            ),
+            ..self
+        }
+    }
+}
+
+fn appearance_id() -> Id {
+    Id::new("appearance")
+}
+
+/// Switches egui over to the theme and scale, if it isn't using them already
+pub fn apply(ctx: &egui::Context, appearance: &Appearance) {
+    let applied = ctx.data(|d| d.get_temp::<Appearance>(appearance_id()));
+    if applied == Some(*appearance) {
+        return;
+    }
+
+    let theme = &appearance.theme;
+    if applied.map(|x| x.theme) != Some(*theme) {
+        let mut visuals = if theme.dark {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        };
+        visuals.error_fg_color = theme.palette.negative;
+        visuals.warn_fg_color = theme.palette.caution;
+        ctx.set_visuals(visuals);
+    }
+    if applied.map(|x| x.ui_scale) != Some(appearance.ui_scale) {
+        ctx.set_pixels_per_point(appearance.ui_scale);
+    }
+    ctx.data_mut(|d| d.insert_temp(appearance_id(), *appearance));
+}
+
+/// Colors of the theme in use
+pub fn palette(ctx: &egui::Context) -> Palette {
+    ctx.data(|d| d.get_temp::<Appearance>(appearance_id()))
+        .unwrap_or_default()
+        .theme
+        .palette
+}
+
+/// The picked appearance, saved to a file so it sticks between sessions
+#[derive(Default)]
+pub struct AppearanceState {
+    pub current: Appearance,
+    path: Option<PathBuf>,
+}
+
+impl AppearanceState {
+    pub fn load(path: PathBuf) -> Self {
+        let current = match storage::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Could not read the appearance from {}: {e}", path.display());
+                Appearance::default()
+            }),
+            Err(_) => Self::load_old_theme(&path),
+        };
+
+        Self {
+            current: current.clamped(),
+            path: Some(path),
+        }
+    }
+
+    /// Carries over a theme saved before the appearance file existed, which
+    /// is then written out under the new name
+    fn load_old_theme(path: &Path) -> Appearance {
+        let old = path.with_file_name(OLD_THEME_FILE);
+        // Nothing's been picked yet
+        let Ok(json) = storage::read_to_string(&old) else {
+            return Appearance::default();
+        };
+
+        match serde_json::from_str(&json) {
+            Ok(theme) => {
+                info!(
+                    "Moving the theme from {} to {}",
+                    old.display(),
+                    path.display()
+                );
+                let state = Self {
+                    current: Appearance {
+                        theme,
+                        ..Default::default()
+                    },
+                    path: Some(path.to_owned()),
+                };
+                state.save();
+                state.current
+            }
+            Err(e) => {
+                warn!("Could not read the theme from {}: {e}", old.display());
+                Appearance::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.current)
+            .map_err(io::Error::other)
+            .and_then(|json| storage::write(path, json));
+        if let Err(e) = result {
+            error!("Could not save the appearance to {}: {e}", path.display());
+        }
+    }
+}
+
+pub mod commands {
+    use crate::prelude::*;
+
+    use super::Appearance;
+
+    pub struct SetAppearance(pub Appearance);
+    impl Command for SetAppearance {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if state.appearance.current != self.0 {
+                state.appearance.current = self.0;
+                state.appearance.save();
+            }
+        }
+    }
+}
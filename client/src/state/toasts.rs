@@ -0,0 +1,82 @@
+use common::message::{DndMessage, SnapshotMessage};
+use web_time::{Duration, Instant};
+
+/// How long a toast stays on screen before fading away
+pub const TOAST_DURATION: Duration = Duration::from_secs(8);
+
+pub struct Toast {
+    pub request_context: String,
+    pub message: String,
+    pub created: Instant,
+    /// Confirms something went through rather than reporting an error
+    pub success: bool,
+}
+
+impl Toast {
+    pub fn is_expired(&self) -> bool {
+        self.created.elapsed() > TOAST_DURATION
+    }
+}
+
+#[derive(Default)]
+pub struct ToastState {
+    pub toasts: Vec<Toast>,
+}
+
+impl ToastState {
+    pub fn process(&mut self, message: &DndMessage) {
+        if let DndMessage::Error {
+            request_context,
+            message,
+        } = message
+        {
+            self.push(request_context, message);
+        }
+
+        if let DndMessage::SnapshotMessage(SnapshotMessage::Saved(info)) = message {
+            self.push_success("Saved", format!("Snapshot '{}' taken", info.tag));
+        }
+    }
+
+    /// Shows an error that happened on our end rather than on the server
+    pub fn push(&mut self, request_context: &str, message: impl ToString) {
+        self.add(request_context, message, false);
+    }
+
+    pub fn push_success(&mut self, request_context: &str, message: impl ToString) {
+        self.add(request_context, message, true);
+    }
+
+    fn add(&mut self, request_context: &str, message: impl ToString, success: bool) {
+        self.toasts.retain(|x| !x.is_expired());
+        self.toasts.push(Toast {
+            request_context: request_context.to_owned(),
+            message: message.to_string(),
+            created: Instant::now(),
+            success,
+        });
+    }
+}
+
+pub mod commands {
+    use common::IssueReport;
+
+    use crate::prelude::*;
+
+    pub struct DismissToast(pub usize);
+    impl Command for DismissToast {
+        fn execute(self: Box<Self>, state: &mut DndState, _tx: &EventSender<Signal>) {
+            if self.0 < state.toasts.toasts.len() {
+                state.toasts.toasts.remove(self.0);
+            }
+        }
+    }
+
+    /// Failures come back as an error toast like anything else
+    pub struct SendIssueReport(pub IssueReport);
+    impl Command for SendIssueReport {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::ReportIssue(self.0).into());
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use common::Trade;
+use egui::ahash::HashMap;
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+#[derive(Default)]
+pub struct TradeState {
+    pub trades: HashMap<Uuid, Trade>,
+}
+
+impl TradeState {
+    pub fn process(&mut self, message: &DndMessage) {
+        let DndMessage::TradeMessage(msg) = message else {
+            return;
+        };
+
+        match msg {
+            TradeMessage::Update(uuid, trade) => {
+                self.trades.insert(*uuid, trade.clone());
+            }
+            TradeMessage::Closed(uuid, _) => {
+                self.trades.remove(uuid);
+            }
+            // The server answers these with an update
+            TradeMessage::Request(_)
+            | TradeMessage::SetOffer(..)
+            | TradeMessage::Confirm(_)
+            | TradeMessage::Cancel(_) => {}
+        }
+    }
+}
+
+pub mod commands {
+    use uuid::Uuid;
+
+    use crate::prelude::*;
+
+    pub struct RequestTrade(pub String);
+    impl Command for RequestTrade {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::TradeMessage(TradeMessage::Request(self.0)).into());
+        }
+    }
+
+    /// Replaces our side of the trade, the items carry the count being given away
+    pub struct SetOffer(pub Uuid, pub Vec<Item>);
+    impl Command for SetOffer {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::TradeMessage(TradeMessage::SetOffer(self.0, self.1)).into());
+        }
+    }
+
+    pub struct ConfirmTrade(pub Uuid);
+    impl Command for ConfirmTrade {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::TradeMessage(TradeMessage::Confirm(self.0)).into());
+        }
+    }
+
+    pub struct CancelTrade(pub Uuid);
+    impl Command for CancelTrade {
+        fn execute(self: Box<Self>, _state: &mut DndState, tx: &EventSender<Signal>) {
+            tx.send(DndMessage::TradeMessage(TradeMessage::Cancel(self.0)).into());
+        }
+    }
+}
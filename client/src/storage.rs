@@ -0,0 +1,40 @@
+//! Where the client keeps its own files: favorite rolls, templates, exported
+//! sheets and so on. On the web there's no file system, so each path is a
+//! key in the browser's localStorage instead
+
+use std::{io, path::Path};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    std::fs::read_to_string(path)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<str>) -> io::Result<()> {
+    std::fs::write(path, contents.as_ref())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> io::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|x| x.local_storage().ok().flatten())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "localStorage is not available"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let key = path.as_ref().to_string_lossy();
+    local_storage()?
+        .get_item(&key)
+        .map_err(|_| io::Error::other("localStorage refused the read"))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{key} is not saved")))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<str>) -> io::Result<()> {
+    let key = path.as_ref().to_string_lossy();
+    local_storage()?
+        .set_item(&key, contents.as_ref())
+        // Usually the quota, browsers give each site a few megabytes
+        .map_err(|_| io::Error::other("localStorage is full"))
+}
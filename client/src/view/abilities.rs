@@ -1,7 +1,7 @@
 use core::f32;
 use std::{collections::HashMap, hash::Hash};
 
-use common::Ability;
+use common::{Ability, EffectTarget};
 use egui::{
     collapsing_header, epaint, vec2, Color32, DragValue, NumExt, RadioButton, Resize, RichText,
     ScrollArea, Sense, TextBuffer, Vec2, Widget,
@@ -13,11 +13,12 @@ use crate::{
     listener::CommandQueue,
     state::{
         abilities::commands::{SetAbilityCount, SetPowerSlotCount},
+        chat::commands::{RollAbility, RollAttack},
         DndState,
     },
 };
 
-use super::DndTabImpl;
+use super::{effects::cooldown_label, DndTabImpl};
 
 #[derive(Default)]
 pub struct Abilities;
@@ -101,6 +102,12 @@ impl Widget for Indicator {
     }
 }
 
+/// Greyed out while the ruleset stops characters who are down from acting
+fn use_button(ui: &mut egui::Ui, state: &DndState) -> egui::Response {
+    ui.add_enabled(state.can_act(), egui::Button::new("Use"))
+        .on_disabled_hover_text("Can't act while down")
+}
+
 struct AbilityWidget<'a, 'c> {
     ability_idx: usize,
     state: &'a DndState,
@@ -125,7 +132,7 @@ impl<'a, 'c> Widget for AbilityWidget<'a, 'c> {
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         match &*self.ability.resource {
                             "UseToken" => {
-                                if ui.button("Use").clicked() {
+                                if use_button(ui, self.state).clicked() {
                                     self.commands.add(SetAbilityCount {
                                         ability_idx: self.ability_idx,
                                         count: ability.uses.saturating_sub(1),
@@ -171,7 +178,7 @@ impl<'a, 'c> Widget for AbilityWidget<'a, 'c> {
                                 }
                             }
                             "PowerSlot" => {
-                                if ui.button("Use").clicked() {
+                                if use_button(ui, self.state).clicked() {
                                     self.commands.add(SetPowerSlotCount {
                                         count: self
                                             .state
@@ -184,11 +191,81 @@ impl<'a, 'c> Widget for AbilityWidget<'a, 'c> {
                             }
                             _ => {}
                         }
+
+                        if let Some(recharge) = ability.recharge {
+                            let target = EffectTarget::Character(
+                                self.state.character.character.name.clone(),
+                            );
+                            match self.state.effects.cooldown_for(&target, &ability.name) {
+                                Some((_, cooldown)) => {
+                                    ui.label(
+                                        RichText::new(cooldown_label(cooldown))
+                                            .color(Color32::from_rgb(220, 140, 60)),
+                                    )
+                                    .on_hover_text(recharge.to_string());
+                                }
+                                None => {
+                                    ui.label(RichText::new(recharge.to_string()).small().weak());
+                                }
+                            }
+                        }
+
+                        let target = self
+                            .state
+                            .board
+                            .target
+                            .and_then(|x| self.state.board.players.get(&x));
+                        if let (Some(target), Some(to_hit)) = (target, &ability.to_hit) {
+                            let text = format!("{} Attack", egui_phosphor::regular::CROSSHAIR);
+                            let name = match target.name.as_str() {
+                                "" => "the target",
+                                name => name,
+                            };
+                            if ui
+                                .button(text)
+                                .on_hover_text(format!("Roll {} against {}", to_hit, name))
+                                .clicked()
+                            {
+                                self.commands.add(RollAttack {
+                                    ability: ability.name.clone(),
+                                    to_hit: to_hit.clone(),
+                                    damage: ability.damage.clone(),
+                                });
+                            }
+                        }
+
+                        let rolls = [("Damage", &ability.damage), ("To Hit", &ability.to_hit)];
+                        for (label, formula) in rolls {
+                            let Some(formula) = formula else {
+                                continue;
+                            };
+
+                            let text = format!("{} {}", egui_phosphor::regular::DICE_SIX, label);
+                            if ui.button(text).on_hover_text(formula.to_string()).clicked() {
+                                self.commands.add(RollAbility {
+                                    formula: formula.clone(),
+                                    reason: format!("{} ({})", ability.name, label.to_lowercase()),
+                                });
+                            }
+                        }
                     });
                 })
                 .body_unindented(|ui| {
                     egui_demo_lib::easy_mark::easy_mark(ui, &ability.description);
 
+                    let character = &self.state.character.character;
+                    let rolls = [("To hit", &ability.to_hit), ("Damage", &ability.damage)];
+                    for (label, formula) in rolls {
+                        if let Some(formula) = formula {
+                            let (count, die) = formula.dice();
+                            let modifier = formula.modifier(character, &self.state.ruleset);
+                            ui.label(format!(
+                                "{}: {} ({}d{}{:+})",
+                                label, formula, count, die, modifier
+                            ));
+                        }
+                    }
+
                     if let Some(notes) = ability.notes.as_ref().filter(|x| !x.is_empty()) {
                         ui.scope(|ui| {
                             ui.visuals_mut().override_text_color = Some(Color32::DARK_GRAY);
@@ -241,14 +318,16 @@ impl DndTabImpl for Abilities {
                     ui.label("Power Slots:");
 
                     if ui.button("Reset").clicked() {
-                        commands.add(SetPowerSlotCount { count: 3 });
+                        commands.add(SetPowerSlotCount {
+                            count: state.character.character.max_power_slots,
+                        });
                     }
 
                     ui.style_mut().spacing.item_spacing = egui::vec2(2.0, 0.0);
 
                     let shape = IndicatorShape::Circle;
 
-                    for ind in 0..3 {
+                    for ind in 0..state.character.character.max_power_slots {
                         Indicator {
                             shape,
                             filled: ind < state.character.character.power_slots,
@@ -0,0 +1,83 @@
+use common::Ambience;
+use egui::{epaint::PathStroke, Painter, Rounding, Shape, Slider};
+
+use crate::{listener::CommandQueue, prelude::*, state::board::commands::SetAmbience};
+
+const MAX_RAIN_DROPS: f32 = 300.0;
+
+/// Tints and particles drawn over the whole board. `time` drives the rain animation
+pub fn paint_ambience(painter: &Painter, rect: Rect, ambience: &Ambience, time: f64) {
+    if ambience.night {
+        painter.rect_filled(
+            rect,
+            Rounding::ZERO,
+            Color32::from_rgba_unmultiplied(10, 20, 60, 110),
+        );
+    }
+
+    if ambience.darkness > 0.0 {
+        let alpha = (ambience.darkness * 230.0) as u8;
+        painter.rect_filled(rect, Rounding::ZERO, Color32::from_black_alpha(alpha));
+    }
+
+    if ambience.fog > 0.0 {
+        let alpha = (ambience.fog * 170.0) as u8;
+        painter.rect_filled(
+            rect,
+            Rounding::ZERO,
+            Color32::from_rgba_unmultiplied(190, 195, 205, alpha),
+        );
+    }
+
+    if ambience.rain > 0.0 {
+        paint_rain(painter, rect, ambience.rain, time);
+        painter.ctx().request_repaint();
+    }
+}
+
+fn paint_rain(painter: &Painter, rect: Rect, intensity: f32, time: f64) {
+    let drops = (intensity * MAX_RAIN_DROPS) as usize;
+    let length = rect.height() * 0.04;
+    let slant = Vec2::new(-0.25, 1.0) * length;
+    let stroke = PathStroke::new(1.0, Color32::from_rgba_unmultiplied(170, 190, 230, 120));
+
+    let shapes = (0..drops).map(|i| {
+        // Cheap stable per-drop randomness so drops don't jump around between frames
+        let x = hash(i as f32 * 12.9898);
+        let offset = hash(i as f32 * 78.233);
+        let speed = 0.6 + hash(i as f32 * 39.346) * 0.6;
+
+        let y = (offset as f64 + time * speed as f64).fract() as f32;
+        let start = rect.lerp_inside(Vec2::new(x, y));
+
+        Shape::line_segment([start, start + slant], stroke.clone())
+    });
+
+    painter.extend(shapes);
+}
+
+fn hash(x: f32) -> f32 {
+    (x.sin() * 43758.547).fract().abs()
+}
+
+/// GM controls for the shared board ambience
+pub fn ambience_controls(ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+    let mut ambience = state.board.ambience;
+
+    Slider::new(&mut ambience.rain, 0.0..=1.0)
+        .text("Rain")
+        .ui(ui);
+    Slider::new(&mut ambience.fog, 0.0..=1.0).text("Fog").ui(ui);
+    Slider::new(&mut ambience.darkness, 0.0..=1.0)
+        .text("Darkness")
+        .ui(ui);
+    ui.checkbox(&mut ambience.night, "Night");
+
+    if ui.button("Clear").clicked() {
+        ambience = Ambience::default();
+    }
+
+    if ambience != state.board.ambience {
+        commands.add(SetAmbience(ambience));
+    }
+}
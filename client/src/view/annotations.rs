@@ -0,0 +1,326 @@
+use common::{cone_points, Annotation, AnnotationShape, SortingLayer, MAIN_BOARD};
+use egui::{epaint::PathStroke, Align2, DragValue, FontId, Painter, Rounding, Shape, Stroke};
+use itertools::Itertools;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::board::{
+        commands::{AddAnnotation, ClearAnnotations, DeleteAnnotation},
+        BoardState,
+    },
+};
+
+use super::board::character_selection;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationTool {
+    Select,
+    Pen,
+    Line,
+    Rect,
+    Ellipse,
+    Text,
+    Sphere,
+    Cone,
+    Eraser,
+}
+
+impl AnnotationTool {
+    const ALL: [AnnotationTool; 9] = [
+        AnnotationTool::Select,
+        AnnotationTool::Pen,
+        AnnotationTool::Line,
+        AnnotationTool::Rect,
+        AnnotationTool::Ellipse,
+        AnnotationTool::Text,
+        AnnotationTool::Sphere,
+        AnnotationTool::Cone,
+        AnnotationTool::Eraser,
+    ];
+
+    fn icon(self) -> &'static str {
+        match self {
+            AnnotationTool::Select => egui_phosphor::regular::CURSOR,
+            AnnotationTool::Pen => egui_phosphor::regular::PENCIL_SIMPLE,
+            AnnotationTool::Line => egui_phosphor::regular::LINE_SEGMENT,
+            AnnotationTool::Rect => egui_phosphor::regular::SQUARE,
+            AnnotationTool::Ellipse => egui_phosphor::regular::CIRCLE,
+            AnnotationTool::Text => egui_phosphor::regular::TEXT_T,
+            AnnotationTool::Sphere => egui_phosphor::regular::CIRCLE_DASHED,
+            AnnotationTool::Cone => egui_phosphor::regular::TRIANGLE,
+            AnnotationTool::Eraser => egui_phosphor::regular::ERASER,
+        }
+    }
+}
+
+/// Drawing tools for the board's annotation layer. Owns the settings for new
+/// annotations and the shape currently being drawn.
+pub struct AnnotationEditor {
+    pub tool: AnnotationTool,
+    color: Color32,
+    width: f32,
+    layer: SortingLayer,
+    text: String,
+    visible_by: Vec<String>,
+    points: Vec<Pos2>,
+}
+
+impl Default for AnnotationEditor {
+    fn default() -> Self {
+        Self {
+            tool: AnnotationTool::Select,
+            color: Color32::LIGHT_RED,
+            width: 2.0,
+            layer: SortingLayer(10),
+            text: String::new(),
+            visible_by: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+}
+
+impl AnnotationEditor {
+    const TEXT_SIZE: f32 = 14.0;
+    const ERASER_TOLERANCE: f32 = 0.02;
+
+    pub fn is_drawing(&self) -> bool {
+        self.tool != AnnotationTool::Select
+    }
+
+    pub fn toolbar(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.horizontal(|ui| {
+            for tool in AnnotationTool::ALL {
+                ui.selectable_value(&mut self.tool, tool, tool.icon());
+            }
+
+            if !self.is_drawing() {
+                return;
+            }
+
+            ui.separator();
+            ui.color_edit_button_srgba(&mut self.color);
+            DragValue::new(&mut self.width)
+                .prefix("width: ")
+                .range(1.0..=10.0)
+                .ui(ui);
+            DragValue::new(&mut self.layer.0)
+                .prefix("layer: ")
+                .range(1..=10)
+                .ui(ui);
+
+            if self.tool == AnnotationTool::Text {
+                ui.text_edit_singleline(&mut self.text);
+            }
+
+            ui.menu_button("Visible By", |ui| {
+                character_selection(ui, state, &mut self.visible_by);
+            });
+
+            if ui.button("Clear layer").clicked() {
+                commands.add(ClearAnnotations(self.layer));
+            }
+        });
+    }
+
+    pub fn handle_input(
+        &mut self,
+        response: &egui::Response,
+        from_screen: RectTransform,
+        state: &DndState,
+        commands: &mut CommandQueue,
+    ) {
+        let pointer = response.interact_pointer_pos().map(|x| from_screen * x);
+
+        match self.tool {
+            AnnotationTool::Select => {}
+            AnnotationTool::Text => {
+                if let Some(pos) = pointer.filter(|_| response.clicked()) {
+                    if !self.text.is_empty() {
+                        let text = std::mem::take(&mut self.text);
+                        self.submit(AnnotationShape::Text(pos, text), commands);
+                    }
+                }
+            }
+            AnnotationTool::Eraser => {
+                if let Some(pos) = pointer.filter(|_| response.clicked()) {
+                    let user = state.owned_user();
+                    let text_size = |text: &str| {
+                        let galley = response.ctx.fonts(|f| {
+                            f.layout_no_wrap(
+                                text.to_owned(),
+                                FontId::proportional(Self::TEXT_SIZE),
+                                Color32::WHITE,
+                            )
+                        });
+                        galley.size() * from_screen.scale()
+                    };
+
+                    let board = &state.board;
+                    let found = board.find_annotation(
+                        pos,
+                        Self::ERASER_TOLERANCE,
+                        &user.name,
+                        state.is_gm(),
+                        text_size,
+                    );
+                    if let Some(id) = found {
+                        commands.add(DeleteAnnotation(*id));
+                    }
+                }
+            }
+            AnnotationTool::Pen
+            | AnnotationTool::Line
+            | AnnotationTool::Rect
+            | AnnotationTool::Ellipse
+            | AnnotationTool::Sphere
+            | AnnotationTool::Cone => {
+                if response.drag_started_by(egui::PointerButton::Primary) {
+                    self.points.clear();
+                }
+
+                if response.dragged_by(egui::PointerButton::Primary) {
+                    if let Some(pos) = pointer {
+                        if self.tool == AnnotationTool::Pen || self.points.len() < 2 {
+                            self.points.push(pos);
+                        } else {
+                            self.points[1] = pos;
+                        }
+                    }
+                }
+
+                if response.drag_stopped_by(egui::PointerButton::Primary) {
+                    if let Some(shape) = self.pending_shape() {
+                        self.submit(shape, commands);
+                    }
+                    self.points.clear();
+                }
+            }
+        }
+    }
+
+    fn pending_shape(&self) -> Option<AnnotationShape> {
+        let (first, last) = (*self.points.first()?, *self.points.last()?);
+
+        match self.tool {
+            AnnotationTool::Pen if self.points.len() > 1 => {
+                Some(AnnotationShape::Pen(self.points.clone()))
+            }
+            AnnotationTool::Line => Some(AnnotationShape::Line(first, last)),
+            AnnotationTool::Rect => Some(AnnotationShape::Rect(first, last)),
+            AnnotationTool::Ellipse => Some(AnnotationShape::Ellipse(first, last)),
+            AnnotationTool::Sphere => Some(AnnotationShape::Sphere(first, (last - first).length())),
+            AnnotationTool::Cone => Some(AnnotationShape::Cone(first, last)),
+            _ => None,
+        }
+    }
+
+    fn submit(&self, shape: AnnotationShape, commands: &mut CommandQueue) {
+        commands.add(AddAnnotation(Annotation {
+            shape,
+            color: self.color.to_array(),
+            width: self.width,
+            layer: self.layer,
+            visible_by: self.visible_by.clone(),
+            // Filled in with the active board and user by the command
+            board: MAIN_BOARD,
+            author: String::new(),
+        }));
+    }
+
+    pub fn paint(&self, painter: &Painter, to_screen: RectTransform, state: &DndState) {
+        let user = state.owned_user();
+        paint_annotations(painter, to_screen, &state.board, |x| {
+            x.visible_to(&user.name)
+        });
+
+        if let Some(shape) = self.pending_shape() {
+            paint_shape(painter, to_screen, &shape, self.color, self.width);
+        }
+    }
+}
+
+/// Annotations on the active board that pass `visible`, bottom layer first
+pub fn paint_annotations(
+    painter: &Painter,
+    to_screen: RectTransform,
+    board: &BoardState,
+    visible: impl Fn(&Annotation) -> bool,
+) {
+    for annotation in board
+        .annotations
+        .values()
+        .filter(|x| x.board == board.active_board)
+        .filter(|x| visible(x))
+        .sorted_by_key(|x| x.layer)
+    {
+        let [r, g, b, a] = annotation.color;
+        let color = Color32::from_rgba_premultiplied(r, g, b, a);
+        paint_shape(
+            painter,
+            to_screen,
+            &annotation.shape,
+            color,
+            annotation.width,
+        );
+    }
+}
+
+fn paint_shape(
+    painter: &Painter,
+    to_screen: RectTransform,
+    shape: &AnnotationShape,
+    color: Color32,
+    width: f32,
+) {
+    let stroke = Stroke::new(width, color);
+    // Templates are filled so it's clear what's inside them
+    let fill = color.gamma_multiply(0.2);
+
+    match shape {
+        AnnotationShape::Pen(points) => {
+            let points = points.iter().map(|x| to_screen * *x).collect();
+            painter.add(Shape::line(points, PathStroke::from(stroke)));
+        }
+        AnnotationShape::Line(a, b) => {
+            painter.line_segment([to_screen * *a, to_screen * *b], stroke);
+        }
+        AnnotationShape::Rect(a, b) => {
+            let rect = to_screen.transform_rect(Rect::from_two_pos(*a, *b));
+            painter.rect_stroke(rect, Rounding::ZERO, stroke);
+        }
+        AnnotationShape::Ellipse(a, b) => {
+            const SEGMENTS: usize = 48;
+
+            let rect = to_screen.transform_rect(Rect::from_two_pos(*a, *b));
+            let radius = rect.size() / 2.0;
+            let points = (0..SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+                    rect.center() + Vec2::new(angle.cos() * radius.x, angle.sin() * radius.y)
+                })
+                .collect();
+            painter.add(Shape::closed_line(points, PathStroke::from(stroke)));
+        }
+        AnnotationShape::Sphere(center, radius) => {
+            let radius = radius * to_screen.scale().x;
+            painter.circle(to_screen * *center, radius, fill, stroke);
+        }
+        AnnotationShape::Cone(apex, end) => {
+            let points = cone_points(*apex, *end)
+                .into_iter()
+                .map(|x| to_screen * x)
+                .collect();
+            painter.add(Shape::convex_polygon(points, fill, stroke));
+        }
+        AnnotationShape::Text(pos, text) => {
+            painter.text(
+                to_screen * *pos,
+                Align2::LEFT_TOP,
+                text,
+                FontId::proportional(AnnotationEditor::TEXT_SIZE),
+                color,
+            );
+        }
+    }
+}
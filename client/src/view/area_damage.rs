@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+
+use common::formula::RollFormula;
+use egui::{Painter, Rounding, Stroke};
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        board::PlayerPiece, character::commands::ApplyAreaDamage, chat::commands::roll_formula_for,
+        theme,
+    },
+};
+
+/// Rolls damage for an area of effect template and applies it to every token
+/// inside, halved for the ones that made their save
+pub struct AreaDamageForm {
+    /// Template the damage comes from, the window is closed while this is unset
+    template: Option<Uuid>,
+    source: String,
+    formula: String,
+    /// The damage dice and flat bonus, along with the formula they came from
+    /// so each target can add their own modifiers
+    roll: Option<(RollFormula, DieRoll)>,
+    saved: HashSet<Uuid>,
+    /// Tokens inside the area that are left out, ie. the caster's allies
+    skipped: HashSet<Uuid>,
+}
+
+impl Default for AreaDamageForm {
+    fn default() -> Self {
+        Self {
+            template: None,
+            source: String::new(),
+            formula: "8d6".to_owned(),
+            roll: None,
+            saved: HashSet::new(),
+            skipped: HashSet::new(),
+        }
+    }
+}
+
+impl AreaDamageForm {
+    const HIGHLIGHT_COLOR: Color32 = Color32::from_rgb(255, 140, 40);
+
+    /// Opens the window for a template. The roll and saves are reset, the
+    /// effect name and formula are kept for the next cast
+    pub fn open(&mut self, template: Uuid) {
+        self.template = Some(template);
+        self.roll = None;
+        self.saved.clear();
+        self.skipped.clear();
+    }
+
+    fn source_name(&self) -> String {
+        match self.source.trim() {
+            "" => "Area of effect".to_owned(),
+            source => source.to_owned(),
+        }
+    }
+
+    /// The roll plus the target's own modifiers, halved and rounded down for
+    /// tokens that saved
+    fn damage_for(&self, state: &DndState, id: &Uuid, piece: &PlayerPiece) -> Option<i32> {
+        let (formula, roll) = self.roll.as_ref()?;
+        let sheet = state.character_sheet(&target_name(piece));
+        let modifier = formula.modifier_for(sheet.map(|x| &x.character), &state.ruleset)
+            - formula.modifier_for(None, &state.ruleset);
+
+        let damage = (roll.total() as i32 + modifier).max(0);
+        if self.saved.contains(id) {
+            Some(damage / 2)
+        } else {
+            Some(damage)
+        }
+    }
+
+    fn hits(&self, state: &DndState, tokens: &[(Uuid, &PlayerPiece)]) -> Vec<AreaHit> {
+        tokens
+            .iter()
+            .filter(|(id, _)| !self.skipped.contains(id))
+            .filter_map(|(id, piece)| {
+                Some(AreaHit {
+                    piece: *id,
+                    name: target_name(piece),
+                    damage: self.damage_for(state, id, piece)?,
+                    saved: self.saved.contains(id),
+                    hp: None,
+                })
+            })
+            .collect()
+    }
+
+    pub fn window(&mut self, ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+        let Some(template) = self.template else {
+            return;
+        };
+
+        // The template was erased, or the GM moved to another board
+        if state
+            .board
+            .annotations
+            .get(&template)
+            .is_none_or(|x| x.board != state.board.active_board)
+        {
+            self.template = None;
+            return;
+        }
+
+        let tokens = tokens_inside(state, template);
+        let mut open = true;
+
+        egui::Window::new("Area Damage")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("area_damage_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Effect");
+                        ui.add(egui::TextEdit::singleline(&mut self.source).hint_text("Fireball"));
+                        ui.end_row();
+
+                        ui.label("Damage");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.formula).desired_width(80.0),
+                            );
+
+                            let formula = self.formula.parse::<RollFormula>();
+                            let response = ui.add_enabled(
+                                formula.is_ok(),
+                                egui::Button::new(egui_phosphor::regular::DICE_SIX),
+                            );
+                            match formula {
+                                Ok(formula) if response.clicked() => {
+                                    let roll = roll_formula_for(
+                                        None,
+                                        &state.ruleset,
+                                        &formula,
+                                        1,
+                                        self.source_name(),
+                                    );
+                                    self.roll = Some((formula, roll));
+                                }
+                                Err(e) if !self.formula.trim().is_empty() => {
+                                    ui.colored_label(theme::palette(ui.ctx()).negative, e);
+                                }
+                                _ => {}
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                if let Some((_, roll)) = &self.roll {
+                    ui.label(format!("Rolled {}: {}", roll.dice(), roll.total()));
+                }
+
+                ui.separator();
+
+                if tokens.is_empty() {
+                    ui.label(RichText::new("No tokens inside the template").weak());
+                }
+
+                egui::Grid::new("area_damage_tokens")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (id, piece) in &tokens {
+                            let mut hit = !self.skipped.contains(id);
+                            if ui.checkbox(&mut hit, token_name(piece)).changed() {
+                                toggle(&mut self.skipped, *id, !hit);
+                            }
+
+                            let mut saved = self.saved.contains(id);
+                            if ui
+                                .add_enabled(hit, egui::Checkbox::new(&mut saved, "Saved"))
+                                .changed()
+                            {
+                                toggle(&mut self.saved, *id, saved);
+                            }
+
+                            match self.damage_for(state, id, piece).filter(|_| hit) {
+                                Some(damage) => ui.label(format!("{} damage", damage)),
+                                None => ui.label(""),
+                            };
+                            ui.end_row();
+                        }
+                    });
+
+                let hits = self.hits(state, &tokens);
+                if ui
+                    .add_enabled(!hits.is_empty(), egui::Button::new("Apply"))
+                    .on_disabled_hover_text("Roll the damage and include at least one token")
+                    .clicked()
+                {
+                    if let Some((_, roll)) = self.roll.clone() {
+                        commands.add(ApplyAreaDamage {
+                            source: self.source_name(),
+                            roll,
+                            hits,
+                        });
+                    }
+                    self.template = None;
+                }
+            });
+
+        if !open {
+            self.template = None;
+        }
+    }
+
+    /// Outlines the tokens that will be hit while the window is open
+    pub fn paint(&self, painter: &Painter, to_screen: RectTransform, state: &DndState) {
+        let Some(template) = self.template else {
+            return;
+        };
+
+        for (id, piece) in tokens_inside(state, template) {
+            if !self.skipped.contains(&id) {
+                painter.rect_stroke(
+                    to_screen.transform_rect(piece.display_rect()).expand(2.0),
+                    Rounding::same(2.0),
+                    Stroke::new(2.0, Self::HIGHLIGHT_COLOR),
+                );
+            }
+        }
+    }
+}
+
+/// Tokens on the active board that are at least partly inside the template
+fn tokens_inside(state: &DndState, template: Uuid) -> Vec<(Uuid, &PlayerPiece)> {
+    let Some(annotation) = state.board.annotations.get(&template) else {
+        return Vec::new();
+    };
+
+    state
+        .board
+        .active_players()
+        .filter(|(_, x)| annotation.shape.overlaps(x.rect))
+        .sorted_by(|a, b| a.1.name.cmp(&b.1.name))
+        .map(|(id, x)| (*id, x))
+        .collect()
+}
+
+/// The character a token's damage goes to, or just the token
+fn target_name(piece: &PlayerPiece) -> String {
+    piece
+        .link_stats_to
+        .clone()
+        .unwrap_or_else(|| token_name(piece).to_owned())
+}
+
+fn token_name(piece: &PlayerPiece) -> &str {
+    match piece.name.as_str() {
+        "" => "Unnamed token",
+        name => name,
+    }
+}
+
+fn toggle(set: &mut HashSet<Uuid>, id: Uuid, insert: bool) {
+    if insert {
+        set.insert(id);
+    } else {
+        set.remove(&id);
+    }
+}
@@ -1,8 +1,15 @@
 use crate::{
     prelude::*,
-    state::board::commands::{Drag, PieceParams},
+    state::board::commands::{
+        AddMyToken, AddPortal, CreateBoard, DeleteBoard, DeletePieces, DeletePortal, Drag,
+        EnterGroup, ExportBoard, FocusEveryone, HoldPiece, ImportBoard, PieceParams, ReleasePiece,
+        Select, SetActiveBoard, SetGrid, ShareView, Ungroup,
+    },
+};
+use common::{
+    GridKind, GridSettings, LabelStyle, PieceVisibility, Portal, SortingLayer, FEET_PER_SQUARE,
+    MAIN_BOARD,
 };
-use common::SortingLayer;
 use egui::{
     epaint::PathStroke, Color32, DragValue, Frame, Image, Painter, Rect, Rounding, Shape, Stroke,
     Widget,
@@ -10,17 +17,49 @@ use egui::{
 use emath::RectTransform;
 use itertools::Itertools;
 use log::info;
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
 use uuid::Uuid;
+use web_time::{Duration, Instant};
 
 use crate::{
     listener::CommandQueue,
     state::{
         board::{self},
-        DndState,
+        character::commands::{AdjustHp, RefreshPartyMember},
+        piece_templates::commands::StampTemplate,
+        theme, DndState,
     },
 };
 
-use super::{multi_select::MultiSelect, DndTabImpl};
+use super::{
+    ambience,
+    annotations::AnnotationEditor,
+    area_damage::AreaDamageForm,
+    calendar::CampaignClock,
+    effects::{self, EffectForm},
+    map_import::MapImport,
+    multi_select::MultiSelect,
+    piece_search::{my_token, CameraMove, PieceSearch},
+    piece_templates::PiecePalette,
+    session_clock::SessionTimer,
+    statuses::{self, StatusForm},
+    vision::FogCache,
+    walls::WallEditor,
+    DndTabImpl,
+};
+
+/// 60 feet, the usual darkvision range
+const DEFAULT_VISION: f32 = 12.0;
+/// Text labels start half a square tall
+const DEFAULT_LABEL_SQUARES: f32 = 0.5;
+/// How often an open edit menu renews its hold, well inside the server's timeout
+const HOLD_RENEW: Duration = Duration::from_secs(10);
+/// An edit menu left open without being touched for this long stops renewing
+/// its hold, so the piece frees up for everyone else
+const HOLD_IDLE: Duration = Duration::from_secs(60);
 
 pub struct Board {
     mouse_pos: Pos2,
@@ -29,15 +68,68 @@ pub struct Board {
     highlight_start_pos: Option<Pos2>,
     highlight_end_pos: Pos2,
     zoom: f32,
-    width: u32,
-    height: u32,
+    width: f32,
+    height: f32,
     new_url: String,
+    new_name: String,
 
+    grid_settings_open: bool,
+    grid_draft: GridSettings,
+    boards_open: bool,
+    new_board_name: String,
+    /// Exported board file to import
+    board_file_path: String,
+    /// Board the next portal drawn on the canvas leads to
+    portal_target: Option<Uuid>,
+    /// Canvas space corners of the portal being drawn
+    portal_drag: Option<(Pos2, Pos2)>,
+    /// Hides the grid on this client even while the GM has it shown
     show_grid: bool,
-    player_list: Vec<String>,
+    show_ambience: bool,
+    show_trails: bool,
+    visibility: PieceVisibility,
+    owner_list: Vec<String>,
     sorting_layer: SortingLayer,
 
     locked: bool,
+    /// Vision radius in grid squares for new or updated pieces
+    vision: Option<f32>,
+    /// Character new or updated pieces take their stats from
+    link_stats_to: Option<String>,
+    /// Set when new or updated pieces are text labels
+    label: Option<LabelStyle>,
+
+    effect_form: EffectForm,
+    status_form: StatusForm,
+    /// Amount for the damage and heal menus, kept between hits
+    hp_amount: i32,
+    area_damage: AreaDamageForm,
+    fog: FogCache,
+    annotations: AnnotationEditor,
+    walls: WallEditor,
+    clock: CampaignClock,
+    session_timer: SessionTimer,
+    map_import: MapImport,
+    templates: PiecePalette,
+    /// Piece the open "Update Piece" menu is holding and when the hold was last renewed
+    menu_hold: Option<(Uuid, Instant)>,
+    /// Last time there was any input while the menu was open
+    menu_active: Instant,
+    piece_search: PieceSearch,
+    camera: Option<CameraMove>,
+    /// Players move their view when the GM asks everyone to look somewhere
+    follow_gm: bool,
+    /// The GM's last focus request we've acted on
+    followed_hint: Option<Instant>,
+    /// GM: sending out where we're looking so players' views follow along
+    leading_view: bool,
+    /// When our view was last sent while leading, and what it was
+    last_view_sent: Option<(Instant, Uuid, Pos2, f32)>,
+    /// The GM's view we last moved to
+    followed_view: Option<(Pos2, f32)>,
+    /// Characters with tokens here whose sheets we've asked for, so their
+    /// tokens can show when they're down
+    requested_sheets: HashSet<String>,
 }
 
 impl Default for Board {
@@ -49,46 +141,335 @@ impl Default for Board {
             highlight_start_pos: None,
             highlight_end_pos: Pos2::ZERO,
             zoom: 1.0,
-            width: 0,
-            height: 0,
+            width: 1.0,
+            height: 1.0,
             new_url: String::new(),
+            new_name: String::new(),
 
-            show_grid: false,
-            player_list: Vec::default(),
+            grid_settings_open: false,
+            grid_draft: GridSettings::default(),
+            boards_open: false,
+            new_board_name: String::new(),
+            board_file_path: String::new(),
+            portal_target: None,
+            portal_drag: None,
+            show_grid: true,
+            show_ambience: true,
+            show_trails: true,
+            visibility: PieceVisibility::default(),
+            owner_list: Vec::default(),
             sorting_layer: SortingLayer::default(),
 
             locked: false,
+            vision: None,
+            link_stats_to: None,
+            label: None,
+
+            effect_form: EffectForm::default(),
+            status_form: StatusForm::default(),
+            hp_amount: 1,
+            area_damage: AreaDamageForm::default(),
+            fog: FogCache::default(),
+            annotations: AnnotationEditor::default(),
+            walls: WallEditor::default(),
+            clock: CampaignClock::default(),
+            session_timer: SessionTimer::default(),
+            map_import: MapImport::default(),
+            templates: PiecePalette::default(),
+            menu_hold: None,
+            menu_active: Instant::now(),
+            piece_search: PieceSearch::default(),
+            camera: None,
+            follow_gm: true,
+            followed_hint: None,
+            leading_view: false,
+            last_view_sent: None,
+            followed_view: None,
+            requested_sheets: HashSet::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SizePreset {
+    Tiny,
+    Medium,
+    Large,
+    Huge,
+    Gargantuan,
+}
+
+impl SizePreset {
+    const ALL: [SizePreset; 5] = [
+        SizePreset::Tiny,
+        SizePreset::Medium,
+        SizePreset::Large,
+        SizePreset::Huge,
+        SizePreset::Gargantuan,
+    ];
+
+    /// Size of the token in grid squares
+    fn squares(self) -> f32 {
+        match self {
+            SizePreset::Tiny => 0.5,
+            SizePreset::Medium => 1.0,
+            SizePreset::Large => 2.0,
+            SizePreset::Huge => 3.0,
+            SizePreset::Gargantuan => 4.0,
+        }
+    }
+}
+
+impl std::fmt::Display for SizePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizePreset::Tiny => write!(f, "Tiny"),
+            SizePreset::Medium => write!(f, "Small/Med"),
+            SizePreset::Large => write!(f, "Large"),
+            SizePreset::Huge => write!(f, "Huge"),
+            SizePreset::Gargantuan => write!(f, "Garg"),
+        }
+    }
+}
+
+pub(super) fn character_selection(ui: &mut egui::Ui, state: &DndState, list: &mut Vec<String>) {
+    let mut new_list = Vec::new();
+    for c in state.roster.iter() {
+        let mut checked = list.contains(c);
+        ui.checkbox(&mut checked, c);
+
+        if checked {
+            new_list.push(c.clone());
         }
     }
+    *list = new_list;
+}
+
+/// Crosshair over the token our attacks are aimed at
+fn paint_target_marker(painter: &Painter, rect: Rect) {
+    let stroke = Stroke::new(2.0, Color32::from_rgb(255, 80, 40));
+    let center = rect.center();
+    let radius = rect.size().min_elem() / 2.0 + 4.0;
+
+    painter.circle_stroke(center, radius, stroke);
+    for dir in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+        painter.line_segment(
+            [center + dir * (radius - 6.0), center + dir * (radius + 6.0)],
+            stroke,
+        );
+    }
+}
+
+/// Outlines a piece someone else is holding in a color picked from their name
+fn paint_holder(painter: &Painter, rect: Rect, holder: &str) {
+    let mut hasher = DefaultHasher::new();
+    holder.hash(&mut hasher);
+    let hue = (hasher.finish() % 360) as f32 / 360.0;
+    let color: Color32 = egui::ecolor::Hsva::new(hue, 0.7, 0.9, 1.0).into();
+
+    painter.rect_stroke(
+        rect.expand(2.0),
+        Rounding::same(2.0),
+        Stroke::new(2.0, color),
+    );
+    painter.text(
+        rect.center_top() - Vec2::Y * 4.0,
+        egui::Align2::CENTER_BOTTOM,
+        holder,
+        egui::FontId::proportional(12.0),
+        color,
+    );
+}
+
+/// How far the dragged piece has come, next to the cursor
+fn paint_drag_distance(painter: &Painter, pointer: Pos2, squares: u32, kind: GridKind) {
+    let unit = match kind {
+        GridKind::Square => "sq",
+        GridKind::HexPointy | GridKind::HexFlat => "hex",
+    };
+    let text = format!("{} ft ({squares} {unit})", squares * FEET_PER_SQUARE);
+
+    let galley = painter.layout_no_wrap(text, egui::FontId::proportional(14.0), Color32::WHITE);
+    let pos = pointer + Vec2::new(16.0, -8.0 - galley.size().y);
+    let rect = Rect::from_min_size(pos, galley.size()).expand(4.0);
+    painter.rect_filled(rect, Rounding::same(4.0), Color32::from_black_alpha(180));
+    painter.galley(pos, galley, Color32::WHITE);
+}
+
+/// Pieces linked to or named after a character stand in for them on the board
+fn linked_character<'a>(state: &'a DndState, piece: &Uuid) -> Option<&'a String> {
+    let name = state.board.players.get(piece)?.stats_name();
+    state.roster.iter().find(|x| *x == name)
+}
+
+/// Players with a character and no token for it on this board yet
+fn can_add_my_token(state: &DndState) -> bool {
+    let user = state.owned_user().name;
+    state.roster.contains(&user)
+        && !state
+            .board
+            .active_players()
+            .any(|(_, x)| x.link_stats_to.as_ref() == Some(&user))
+}
+
+fn visibility_selection(ui: &mut egui::Ui, state: &DndState, visibility: &mut PieceVisibility) {
+    let mut players = match visibility {
+        PieceVisibility::Players(players) => players.clone(),
+        _ => Vec::new(),
+    };
+
+    ui.radio_value(visibility, PieceVisibility::Everyone, "Everyone");
+    ui.radio_value(visibility, PieceVisibility::GmOnly, "GM only");
+    if ui
+        .radio(matches!(visibility, PieceVisibility::Players(_)), "Players")
+        .clicked()
+    {
+        *visibility = PieceVisibility::Players(players.clone());
+    }
+
+    if let PieceVisibility::Players(selected) = visibility {
+        ui.separator();
+        character_selection(ui, state, &mut players);
+        *selected = players;
+    }
 }
 
 impl Board {
-    pub const GRID_SIZE: f32 = 0.1;
+    const MAX_ZOOM: f32 = 10.0;
+    const MIN_ZOOM: f32 = 0.5;
+
+    /// Hex grids are drawn cell by cell, so stop drawing once zoomed too far out
+    const MAX_HEX_CELLS: i32 = 20_000;
+
+    /// Holds the piece the "Update Piece" menu is open for, releasing it once the
+    /// menu closes or moves on to another piece. The hold is only renewed while
+    /// the menu is being used, an idle one lets it run out on the server
+    fn update_menu_hold(&mut self, ui: &Ui, editing: Option<Uuid>, commands: &mut CommandQueue) {
+        if ui.input(|i| !i.events.is_empty() || i.pointer.is_moving()) {
+            self.menu_active = Instant::now();
+        }
+
+        match (self.menu_hold, editing) {
+            (Some((held, renewed)), Some(id)) if held == id => {
+                if renewed.elapsed() >= HOLD_RENEW && self.menu_active.elapsed() < HOLD_IDLE {
+                    commands.add(HoldPiece(id));
+                    self.menu_hold = Some((id, Instant::now()));
+                }
+            }
+            (held, editing) => {
+                if let Some((id, _)) = held {
+                    commands.add(ReleasePiece(id));
+                }
+                if let Some(id) = editing {
+                    commands.add(HoldPiece(id));
+                }
+                self.menu_hold = editing.map(|id| (id, Instant::now()));
+            }
+        }
+    }
 
     fn copy_selected_stats(&mut self, state: &DndState, selected: &Uuid) {
         let selected = &state.board.players[selected];
         self.new_url = selected.image_url.clone().unwrap_or_default();
+        self.new_name = selected.name.clone();
 
-        let dims = (selected.rect.size() / Board::GRID_SIZE).round();
-        self.width = dims.x as u32;
-        self.height = dims.y as u32;
+        // Sizes are kept in half square increments
+        let dims = (selected.rect.size() / state.board.grid.spacing * 2.0).round() / 2.0;
+        self.width = dims.x;
+        self.height = dims.y;
 
         self.sorting_layer = selected.sorting_layer;
         self.locked = selected.locked;
-        self.player_list = selected.visible_by.clone();
+        self.vision = selected.vision;
+        self.link_stats_to = selected.link_stats_to.clone();
+        self.label = selected.label;
+        self.visibility = selected.visibility.clone();
+        self.owner_list = selected.owners.clone();
+    }
+
+    /// Links the piece to a character, taking their portrait if the piece
+    /// doesn't have an image yet
+    fn link(&mut self, character: &str, state: &DndState, commands: &mut CommandQueue) {
+        self.link_stats_to = Some(character.to_owned());
+        match state.character_sheet(character) {
+            Some(sheet) if self.new_url.is_empty() => {
+                self.new_url = sheet.character.portrait.clone().unwrap_or_default();
+            }
+            // The portrait is filled in when the piece is added once the sheet arrives
+            None => commands.add(RefreshPartyMember(character.to_owned())),
+            _ => {}
+        }
     }
 
-    fn character_selection(&mut self, ui: &mut egui::Ui, state: &DndState) {
-        let mut new_list = Vec::new();
-        for c in state.character_list.iter() {
-            let mut checked = self.player_list.contains(c);
-            ui.checkbox(&mut checked, c);
+    /// Offers to link the piece to the character it's named after, or any
+    /// other character in the roster
+    fn link_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let name = self.new_name.trim();
+        let suggestion = state
+            .roster
+            .iter()
+            .find(|x| *x == name)
+            .filter(|x| self.link_stats_to.as_ref() != Some(*x));
 
-            if checked {
-                new_list.push(c.clone());
+        if let Some(character) = suggestion {
+            let text = format!("{} Link to {character}", egui_phosphor::regular::LINK);
+            if ui
+                .button(text)
+                .on_hover_text("Use their sheet for HP and AC, and their portrait")
+                .clicked()
+            {
+                self.link(character, state, commands);
             }
         }
-        self.player_list = new_list;
+
+        ui.horizontal(|ui| {
+            ui.label("Linked to");
+            let selected = self.link_stats_to.as_deref().unwrap_or("Nobody");
+            egui::ComboBox::from_id_salt("piece_link")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for character in state.roster.iter() {
+                        let linked = self.link_stats_to.as_ref() == Some(character);
+                        if ui.selectable_label(linked, character).clicked() && !linked {
+                            self.link(character, state, commands);
+                        }
+                    }
+                });
+
+            if self.link_stats_to.is_some()
+                && ui
+                    .small_button(egui_phosphor::regular::LINK_BREAK)
+                    .on_hover_text("Unlink")
+                    .clicked()
+            {
+                self.link_stats_to = None;
+            }
+        });
+    }
+
+    /// `sign` is -1 for damage and 1 for healing
+    fn hp_menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        character: &str,
+        sign: i32,
+        commands: &mut CommandQueue,
+    ) {
+        let label = if sign < 0 { "Damage" } else { "Heal" };
+
+        let response = DragValue::new(&mut self.hp_amount)
+            .range(1..=999)
+            .prefix(format!("{}: ", label.to_lowercase()))
+            .ui(ui);
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+        if ui.button(label).clicked() || submitted {
+            commands.add(AdjustHp {
+                character: character.to_owned(),
+                amount: sign * self.hp_amount,
+            });
+            ui.close_menu();
+        }
     }
 
     fn ui_content(
@@ -106,6 +487,21 @@ impl Board {
             self.mouse_pos = pos;
         }
 
+        self.follow_focus_hint(state);
+        self.follow_gm_view(state);
+        if let Some(camera) = &self.camera {
+            let ((origin, zoom), arrived) = camera.step();
+            self.grid_origin = origin;
+            self.zoom = zoom;
+            if arrived {
+                self.camera = None;
+            } else {
+                ui.ctx().request_repaint();
+            }
+        }
+        self.lead_view(state, commands);
+        self.load_linked_sheets(state, commands);
+
         let dims = response.rect.square_proportions() * self.zoom;
         let to_screen = emath::RectTransform::from_to(
             Rect::from_center_size(self.grid_origin, dims),
@@ -125,6 +521,42 @@ impl Board {
             } else {
                 commands.add(board::commands::Drop)
             }
+        } else if let Some(target_board) = self.portal_target {
+            let pointer = response.interact_pointer_pos().map(|x| from_screen * x);
+            if response.drag_started_by(egui::PointerButton::Primary) {
+                self.portal_drag = pointer.map(|x| (x, x));
+            }
+            if let (Some((start, _)), Some(end)) = (self.portal_drag, pointer) {
+                self.portal_drag = Some((start, end));
+                if response.drag_stopped_by(egui::PointerButton::Primary) {
+                    let area = Rect::from_two_pos(start, end);
+                    commands.add(AddPortal(Portal {
+                        area,
+                        target_board,
+                        target: area.center(),
+                    }));
+                    self.portal_target = None;
+                    self.portal_drag = None;
+                }
+            }
+        } else if let Some(template) = self
+            .templates
+            .armed()
+            .filter(|_| response.clicked_by(egui::PointerButton::Primary))
+        {
+            if let Some(pos) = response.interact_pointer_pos() {
+                commands.add(StampTemplate {
+                    template: template.clone(),
+                    pos: from_screen * pos,
+                });
+            }
+        } else if self.walls.is_drawing() && !response.dragged_by(egui::PointerButton::Middle) {
+            self.walls
+                .handle_input(&response, from_screen, state, commands);
+        } else if self.annotations.is_drawing() && !response.dragged_by(egui::PointerButton::Middle)
+        {
+            self.annotations
+                .handle_input(&response, from_screen, state, commands);
         } else if response.dragged_by(egui::PointerButton::Primary)
             && ui.input(|input| !input.modifiers.any())
         {
@@ -133,7 +565,12 @@ impl Board {
                 .interact_pointer_pos()
                 .and_then(|x| state.board.find_selected_player_id(from_screen * x))
             {
-                if !state.board.is_locked(uuid) {
+                let locked = state
+                    .board
+                    .group_members(uuid)
+                    .iter()
+                    .any(|x| state.board.is_locked(x));
+                if !locked && state.can_control_piece(uuid) {
                     // Get dragging offset
                     let pointer_canvas_pos = from_screen * response.interact_pointer_pos().unwrap();
                     let piece_canvas_pos = state.board.get_position(uuid).unwrap();
@@ -152,34 +589,47 @@ impl Board {
             if let Some(pointer_pos) = response.interact_pointer_pos() {
                 self.highlight_end_pos = pointer_pos;
             } else {
+                let spacing = state.board.grid.spacing;
                 let size_rect = Rect::from_two_pos(
-                    (from_screen * self.highlight_end_pos * 10.0).round(),
-                    (from_screen * self.highlight_start_pos.unwrap() * 10.0).round(),
+                    (from_screen * self.highlight_end_pos / spacing).round(),
+                    (from_screen * self.highlight_start_pos.unwrap() / spacing).round(),
                 );
 
                 let center_rect = Rect::from_two_pos(
-                    (from_screen * self.highlight_end_pos / Board::GRID_SIZE).round() * Board::GRID_SIZE,
-                    (from_screen * self.highlight_start_pos.unwrap() / Board::GRID_SIZE).round() * Board::GRID_SIZE,
+                    (from_screen * self.highlight_end_pos / spacing).round() * spacing,
+                    (from_screen * self.highlight_start_pos.unwrap() / spacing).round() * spacing,
                 );
 
                 commands.add(board::commands::AddPiece {
                     params: PieceParams {
+                        name: String::new(),
                         pos: center_rect.left_top(),
                         size: size_rect.size(),
                         url: None,
-                        visible_by: vec![],
+                        visibility: PieceVisibility::default(),
+                        owners: vec![],
                         sorting_layer: common::SortingLayer(10),
                         locked: false,
+                        vision: None,
+                        link_stats_to: None,
+                        label: None,
                     },
                 });
 
                 self.highlight_start_pos = None;
             }
-        } else if ui.input(|input| input.modifiers.ctrl)
-            && response.is_pointer_button_down_on()
-        {
+        } else if ui.input(|input| input.modifiers.ctrl) && response.is_pointer_button_down_on() {
             self.highlight_start_pos = response.interact_pointer_pos();
             self.highlight_end_pos = response.interact_pointer_pos().unwrap();
+        } else if response.clicked_by(egui::PointerButton::Primary)
+            && ui.input(|input| input.modifiers.alt)
+        {
+            // Alt clicking picks the attack target, or clears it when clicking nothing
+            let target = response
+                .interact_pointer_pos()
+                .and_then(|x| state.board.find_selected_player_id(from_screen * x))
+                .copied();
+            commands.add(board::commands::SetTarget(target));
         } else if response.clicked_by(egui::PointerButton::Primary) {
             // Handle selection of a piece
             let selected_idx = response
@@ -193,14 +643,20 @@ impl Board {
 
             commands.add(board::commands::Select(selected_idx));
         } else if response.dragged_by(egui::PointerButton::Middle) {
+            self.camera = None;
             let screen_origin = to_screen * self.grid_origin;
             self.grid_origin = from_screen * (screen_origin - response.drag_delta());
         } else if ui.input(|input| input.key_pressed(egui::Key::Delete)) {
-            if let Some(selected) = state.board.selected_id {
-                commands.add(board::commands::DeletePiece(selected));
+            if let Some(selected) = state
+                .board
+                .selected_id
+                .filter(|x| state.can_control_piece(x))
+            {
+                commands.add(DeletePieces(state.board.group_members(&selected)));
             }
         }
 
+        let mut editing = None;
         response.context_menu(|ui| {
             let menu_text = if state.board.selected_id.is_some() {
                 "Update Piece"
@@ -209,25 +665,63 @@ impl Board {
             };
 
             ui.menu_button(menu_text, |ui| {
+                editing = state
+                    .board
+                    .selected_id
+                    .filter(|x| state.can_control_piece(x));
+
                 ui.menu_button("Visible By", |ui| {
-                    self.character_selection(ui, state);
+                    visibility_selection(ui, state, &mut self.visibility);
+                });
+
+                if state.is_gm() {
+                    ui.menu_button("Owned By", |ui| {
+                        character_selection(ui, state, &mut self.owner_list);
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    for preset in SizePreset::ALL {
+                        if ui
+                            .small_button(preset.to_string())
+                            .on_hover_text(format!("{0}x{0}", preset.squares()))
+                            .clicked()
+                        {
+                            self.width = preset.squares();
+                            self.height = preset.squares();
+                        }
+                    }
                 });
 
                 DragValue::new(&mut self.width)
                     .prefix("w: ")
-                    .range(1..=100)
+                    .range(0.5..=100.0)
+                    .speed(0.1)
                     .ui(ui);
 
                 DragValue::new(&mut self.height)
                     .prefix("h: ")
-                    .range(1..=100)
+                    .range(0.5..=100.0)
+                    .speed(0.1)
                     .ui(ui);
 
+                self.width = (self.width * 2.0).round() / 2.0;
+                self.height = (self.height * 2.0).round() / 2.0;
+
                 DragValue::new(&mut self.sorting_layer.0)
                     .prefix("layer: ")
                     .range(1..=10)
                     .ui(ui);
 
+                ui.horizontal(|ui| {
+                    ui.label("name: ");
+                    ui.text_edit_singleline(&mut self.new_name);
+                });
+
+                if state.is_gm() {
+                    self.link_ui(ui, state, commands);
+                }
+
                 ui.horizontal(|ui| {
                     ui.label("url: ");
                     ui.text_edit_singleline(&mut self.new_url);
@@ -235,8 +729,59 @@ impl Board {
 
                 ui.checkbox(&mut self.locked, "Locked: ");
 
+                // The server only takes vision changes from the GM
+                ui.add_enabled_ui(state.is_gm(), |ui| {
+                    ui.horizontal(|ui| {
+                        let mut has_vision = self.vision.is_some();
+                        ui.checkbox(&mut has_vision, "Vision: ").on_hover_text(
+                            "Players who own a token with vision only see what their tokens can see",
+                        );
+                        self.vision = has_vision.then(|| self.vision.unwrap_or(DEFAULT_VISION));
+
+                        if let Some(vision) = &mut self.vision {
+                            DragValue::new(vision)
+                                .suffix(" squares")
+                                .range(1.0..=100.0)
+                                .ui(ui);
+                        }
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    let spacing = state.board.grid.spacing;
+                    let mut is_label = self.label.is_some();
+                    ui.checkbox(&mut is_label, "Text label: ")
+                        .on_hover_text("Draws the name on the board instead of an image");
+                    self.label = is_label.then(|| {
+                        self.label.unwrap_or(LabelStyle {
+                            size: DEFAULT_LABEL_SQUARES * spacing,
+                            color: [255, 255, 255, 255],
+                        })
+                    });
+
+                    if let Some(label) = &mut self.label {
+                        // Edited in squares like the piece size, stored in canvas units
+                        let mut squares = label.size / spacing;
+                        DragValue::new(&mut squares)
+                            .prefix("size: ")
+                            .range(0.1..=10.0)
+                            .speed(0.05)
+                            .ui(ui);
+                        label.size = squares * spacing;
+
+                        ui.color_edit_button_srgba_unmultiplied(&mut label.color);
+                    }
+                });
+
                 if let Some(selected) = state.board.selected_id {
-                    if ui.button("Update").clicked() {
+                    let response = ui.add_enabled(
+                        state.can_control_piece(&selected),
+                        egui::Button::new("Update"),
+                    );
+                    if response
+                        .on_disabled_hover_text("You can only edit your own pieces")
+                        .clicked()
+                    {
                         info!(
                             "Updating {} {}",
                             from_screen * self.mouse_pos,
@@ -252,12 +797,17 @@ impl Board {
                         commands.add(board::commands::UpdatePiece {
                             piece_id: selected,
                             params: PieceParams {
+                                name: self.new_name.clone(),
                                 pos: Pos2::ZERO,
-                                size: Vec2::new(self.width as f32, self.height as f32),
+                                size: Vec2::new(self.width, self.height),
                                 url: image_url,
-                                visible_by: self.player_list.clone(),
+                                visibility: self.visibility.clone(),
+                                owners: self.owner_list.clone(),
                                 sorting_layer: self.sorting_layer,
                                 locked: self.locked,
+                                vision: self.vision,
+                                link_stats_to: self.link_stats_to.clone(),
+                                label: self.label,
                             },
                         });
                     }
@@ -272,88 +822,804 @@ impl Board {
 
                     commands.add(board::commands::AddPiece {
                         params: PieceParams {
+                            name: self.new_name.clone(),
                             pos: from_screen * self.mouse_pos,
-                            size: Vec2::new(self.width as f32, self.height as f32),
+                            size: Vec2::new(self.width, self.height),
                             url: image_url,
-                            visible_by: self.player_list.clone(),
+                            visibility: self.visibility.clone(),
+                            owners: self.owner_list.clone(),
                             sorting_layer: self.sorting_layer,
                             locked: self.locked,
+                            vision: self.vision,
+                            link_stats_to: self.link_stats_to.clone(),
+                            label: self.label,
                         },
                     });
                 }
             });
 
-            ui.checkbox(&mut self.show_grid, "Grid");
+            if can_add_my_token(state) && ui.button("Add my token").clicked() {
+                commands.add(AddMyToken(from_screen * self.mouse_pos));
+                ui.close_menu();
+            }
+
+            if let Some(group) = state
+                .board
+                .selected_id
+                .and_then(|x| state.board.groups.group_of(&x))
+            {
+                if state.board.entered_group == Some(group) {
+                    if ui.button("Exit Group").clicked() {
+                        commands.add(EnterGroup(None));
+                        ui.close_menu();
+                    }
+                } else if ui
+                    .button("Enter Group")
+                    .on_hover_text("Move and edit the grouped pieces one at a time")
+                    .clicked()
+                {
+                    commands.add(EnterGroup(Some(group)));
+                    ui.close_menu();
+                }
+
+                if state.is_gm() && ui.button("Ungroup").clicked() {
+                    commands.add(Ungroup(group));
+                    ui.close_menu();
+                }
+            }
+
+            if let Some(selected) = state.board.selected_id {
+                self.templates.save_menu(ui, selected, state, commands);
+
+                ui.menu_button("Effects", |ui| {
+                    self.effect_form
+                        .ui(ui, common::EffectTarget::Piece(selected), commands);
+                });
+            }
+
+            if let Some((id, piece)) = state
+                .board
+                .selected_id
+                .filter(|x| state.can_control_piece(x))
+                .and_then(|x| Some((x, state.board.players.get(&x)?)))
+            {
+                ui.menu_button("Status", |ui| {
+                    self.status_form.ui(ui, id, piece, commands);
+                });
+            }
+
+            if state.board.target.is_some()
+                && ui
+                    .button("Clear Target")
+                    .on_hover_text("Alt click a token to target it")
+                    .clicked()
+            {
+                commands.add(board::commands::SetTarget(None));
+                ui.close_menu();
+            }
+
+            if let Some(character) = state
+                .board
+                .selected_id
+                .and_then(|x| linked_character(state, &x))
+                .filter(|x| state.is_gm() || state.can_edit_character(x))
+            {
+                ui.menu_button("Damage...", |ui| {
+                    self.hp_menu(ui, character, -1, commands);
+                });
+                ui.menu_button("Heal...", |ui| {
+                    self.hp_menu(ui, character, 1, commands);
+                });
+            }
+
+            if state.is_gm() {
+                self.walls
+                    .menu(ui, from_screen * self.mouse_pos, state, commands);
+            }
+
+            if let Some(template) = state
+                .board
+                .find_template(from_screen * self.mouse_pos)
+                .filter(|_| state.is_gm())
+            {
+                if ui.button("Area Damage...").clicked() {
+                    self.area_damage.open(*template);
+                    ui.close_menu();
+                }
+            }
+
+            if state.is_gm()
+                && ui
+                    .button(format!("Next Round ({})", state.effects.round))
+                    .clicked()
+            {
+                commands.add(crate::state::effects::commands::AdvanceRound);
+            }
+
+            // Someone else is holding the selected piece
+            let held = state.board.selected_id.filter(|id| {
+                let holder = state.board.players.get(id).and_then(|x| x.holder.as_ref());
+                holder.is_some() && holder != state.user.as_ref().map(|x| &x.name)
+            });
+            if let Some(selected) = held.filter(|_| state.is_gm()) {
+                if ui
+                    .button("Break Hold")
+                    .on_hover_text("Frees the piece up for everyone else")
+                    .clicked()
+                {
+                    commands.add(ReleasePiece(selected));
+                    ui.close_menu();
+                }
+            }
+
+            if let Some(selected) = state.board.selected_id.filter(|_| state.is_gm()) {
+                if ui
+                    .button("Start Turn")
+                    .on_hover_text("Rolls to recharge the selected token's abilities")
+                    .clicked()
+                {
+                    commands.add(crate::state::effects::commands::StartTurn(selected));
+                    ui.close_menu();
+                }
+            }
+
+            if state.is_gm() {
+                ui.menu_button("Ambience", |ui| {
+                    ambience::ambience_controls(ui, state, commands);
+                });
+            }
+
+            if ui.button("Piece Templates...").clicked() {
+                self.templates.open();
+                ui.close_menu();
+            }
+
+            if state.is_gm() && ui.button("Boards...").clicked() {
+                self.boards_open = true;
+                ui.close_menu();
+            }
+
+            if state.is_gm() && ui.button("Import Map...").clicked() {
+                self.map_import.open();
+                ui.close_menu();
+            }
+
+            if state.is_gm() && ui.button("Board Settings...").clicked() {
+                self.grid_draft = state.board.grid;
+                self.grid_settings_open = true;
+                ui.close_menu();
+            }
+
+            ui.add_enabled(
+                state.board.grid.visible,
+                egui::Checkbox::new(&mut self.show_grid, "Grid"),
+            )
+            .on_hover_text("Turn off locally to hide the grid the GM has shown");
+            ui.checkbox(&mut self.show_trails, "Movement Trails");
+            if !state.is_gm() {
+                ui.checkbox(&mut self.follow_gm, "Follow GM's Camera")
+                    .on_hover_text("Move the view when the GM leads or points everyone somewhere");
+            }
+            ui.checkbox(&mut self.show_ambience, "Weather Effects")
+                .on_hover_text("Turn off locally to improve performance");
         });
 
+        self.update_menu_hold(ui, editing, commands);
         self.handle_zoom(ui);
 
         if self.show_grid {
-            self.draw_grid(dims, &painter, &to_screen);
+            let grid = &state.board.grid;
+            Self::paint_grid(self.grid_origin, dims, &painter, &to_screen, grid);
         }
 
-        for player in state
+        self.walls.paint(&painter, to_screen, state);
+
+        for (id, player) in state
             .board
-            .players
-            .values()
-            .sorted_by_key(|x| x.sorting_layer)
-            .filter(|x| x.visible_by.contains(&state.owned_user().name) || x.visible_by.is_empty())
+            .active_players()
+            .sorted_by_key(|(_, x)| x.sorting_layer)
+            .filter(|(_, x)| state.is_gm() || x.visibility.includes(&state.owned_user().name))
         {
+            if self.show_trails {
+                player.draw_trail(&painter, to_screen);
+            }
             player.draw_shape(ui, &painter, to_screen);
+            let sheet = linked_character(state, id).and_then(|x| state.character_sheet(x));
+            if let Some(sheet) = sheet {
+                let rect = to_screen.transform_rect(player.display_rect());
+                statuses::paint_life(&painter, rect, sheet.character.life);
+            }
+            if state.board.target == Some(*id) {
+                paint_target_marker(&painter, to_screen.transform_rect(player.display_rect()));
+            }
+            effects::paint_piece_effects(
+                &painter,
+                state,
+                *id,
+                to_screen.transform_rect(player.display_rect()),
+            );
+            statuses::paint_statuses(
+                &painter,
+                to_screen.transform_rect(player.display_rect()),
+                &player.statuses,
+            );
+            if let Some(holder) = player
+                .holder
+                .as_ref()
+                .filter(|x| **x != state.owned_user().name)
+            {
+                paint_holder(
+                    &painter,
+                    to_screen.transform_rect(player.display_rect()),
+                    holder,
+                );
+            }
+            let floating = super::floating_numbers::paint_floating_numbers(
+                &painter,
+                to_screen.transform_rect(player.display_rect()),
+                *id,
+                &state.board.floating,
+            );
+
+            if player.is_animating() || floating {
+                ui.ctx().request_repaint();
+            }
+        }
+
+        if let Some(rect) = state.board.selected_group_rect() {
+            let color = match state.board.entered_group {
+                Some(_) => theme::palette(ui.ctx()).accent.gamma_multiply(0.6),
+                None => Color32::from_white_alpha(90),
+            };
+            painter.rect_stroke(
+                to_screen.transform_rect(rect).expand(4.0),
+                Rounding::same(4.0),
+                Stroke::new(1.0, color),
+            );
+        }
+
+        if self.show_ambience {
+            let time = ui.input(|i| i.time);
+            ambience::paint_ambience(&painter, response.rect, &state.board.ambience, time);
+        }
+
+        self.piece_search.paint(&painter, to_screen, state);
+        self.annotations.paint(&painter, to_screen, state);
+        self.area_damage.paint(&painter, to_screen, state);
+        self.fog.paint(&painter, response.rect, to_screen, state);
+
+        if state.is_gm() {
+            self.draw_portals(&painter, to_screen, state);
         }
 
         if let Some(pointer_pos) = self.highlight_start_pos {
             //Draw highlight rect
             let rect = Rect::from_two_pos(pointer_pos, self.highlight_end_pos);
-            painter.rect_stroke(rect, Rounding::ZERO, Stroke::new(1.0, Color32::LIGHT_BLUE));
+            let stroke = Stroke::new(1.0, theme::palette(ui.ctx()).accent);
+            painter.rect_stroke(rect, Rounding::ZERO, stroke);
+        }
+
+        if let (Some(squares), Some(pointer)) = (
+            state.board.drag_distance(state.ruleset.diagonals),
+            response.interact_pointer_pos(),
+        ) {
+            paint_drag_distance(&painter, pointer, squares, grid.kind);
         }
 
+        self.following_banner(ui.ctx(), response.rect, state);
+
         response
     }
 
-    fn draw_grid(&self, dims: egui::Vec2, painter: &Painter, to_screen: &RectTransform) {
-        let num_x = (dims.x / Board::GRID_SIZE) as i32 + 1;
-        let num_y = (dims.y / Board::GRID_SIZE) as i32 + 1;
+    /// Grid lines covering `dims` of canvas space around `origin`
+    pub(super) fn paint_grid(
+        origin: Pos2,
+        dims: egui::Vec2,
+        painter: &Painter,
+        to_screen: &RectTransform,
+        grid: &GridSettings,
+    ) {
+        if !grid.visible {
+            return;
+        }
 
-        let topleft_boundary = self.grid_origin - dims / 2.0;
+        match grid.kind {
+            GridKind::Square => Self::draw_grid(origin, dims, painter, to_screen, grid),
+            GridKind::HexPointy | GridKind::HexFlat => {
+                Self::draw_hex_grid(origin, dims, painter, to_screen, grid)
+            }
+        }
+    }
+
+    fn draw_grid(
+        origin: Pos2,
+        dims: egui::Vec2,
+        painter: &Painter,
+        to_screen: &RectTransform,
+        grid: &GridSettings,
+    ) {
+        let spacing = grid.spacing;
+        let [r, g, b, a] = grid.color;
+        let color = Color32::from_rgba_unmultiplied(r, g, b, a);
+
+        let num_x = (dims.x / spacing) as i32 + 1;
+        let num_y = (dims.y / spacing) as i32 + 1;
 
-        let round = topleft_boundary.y.rem_euclid(Board::GRID_SIZE);
+        let topleft_boundary = origin - dims / 2.0;
+
+        let round = topleft_boundary.y.rem_euclid(spacing);
         let y_start = topleft_boundary.y - round;
-        for y in (0..num_y).map(|x| x as f32 * Board::GRID_SIZE + y_start) {
+        for y in (0..num_y).map(|x| x as f32 * spacing + y_start) {
             painter.add(Shape::line_segment(
                 [
-                    to_screen * Pos2::new(-dims.x + self.grid_origin.x, y),
-                    to_screen * Pos2::new(dims.x + self.grid_origin.x, y),
+                    to_screen * Pos2::new(-dims.x + origin.x, y),
+                    to_screen * Pos2::new(dims.x + origin.x, y),
                 ],
-                PathStroke::new(1.0, Color32::DARK_GRAY),
+                PathStroke::new(1.0, color),
             ));
         }
 
-        let round = topleft_boundary.x.rem_euclid(Board::GRID_SIZE);
+        let round = topleft_boundary.x.rem_euclid(spacing);
         let x_start = topleft_boundary.x - round;
-        for x in (0..num_x).map(|x| x as f32 * Board::GRID_SIZE + x_start) {
+        for x in (0..num_x).map(|x| x as f32 * spacing + x_start) {
             painter.add(Shape::line_segment(
                 [
-                    to_screen * Pos2::new(x, -dims.y + self.grid_origin.y),
-                    to_screen * Pos2::new(x, dims.y + self.grid_origin.y),
+                    to_screen * Pos2::new(x, -dims.y + origin.y),
+                    to_screen * Pos2::new(x, dims.y + origin.y),
                 ],
-                PathStroke::new(1.0, Color32::DARK_GRAY),
+                PathStroke::new(1.0, color),
             ));
         }
     }
 
+    fn draw_hex_grid(
+        origin: Pos2,
+        dims: egui::Vec2,
+        painter: &Painter,
+        to_screen: &RectTransform,
+        grid: &GridSettings,
+    ) {
+        let [r, g, b, a] = grid.color;
+        let color = Color32::from_rgba_unmultiplied(r, g, b, a);
+
+        // Rows of hexes for pointy grids, columns for flat ones
+        let pointy = grid.kind == GridKind::HexPointy;
+        let (step_x, step_y) = if pointy {
+            (grid.spacing, grid.hex_radius() * 1.5)
+        } else {
+            (grid.hex_radius() * 1.5, grid.spacing)
+        };
+
+        let num_x = (dims.x / step_x) as i32 + 3;
+        let num_y = (dims.y / step_y) as i32 + 3;
+        if num_x * num_y > Board::MAX_HEX_CELLS {
+            return;
+        }
+
+        let topleft_boundary = origin - dims / 2.0;
+        let col_start = (topleft_boundary.x / step_x).floor() as i32 - 1;
+        let row_start = (topleft_boundary.y / step_y).floor() as i32 - 1;
+
+        for row in row_start..row_start + num_y {
+            for col in col_start..col_start + num_x {
+                let mut center = Pos2::new(col as f32 * step_x, row as f32 * step_y);
+                if pointy && row.rem_euclid(2) == 1 {
+                    center.x += grid.spacing / 2.0;
+                } else if !pointy && col.rem_euclid(2) == 1 {
+                    center.y += grid.spacing / 2.0;
+                }
+
+                let points = grid
+                    .hex_corners(center)
+                    .into_iter()
+                    .map(|x| to_screen * x)
+                    .collect();
+                painter.add(Shape::closed_line(points, PathStroke::new(1.0, color)));
+            }
+        }
+    }
+
+    /// Portals are only shown to the GM, players just find themselves somewhere new
+    fn draw_portals(&self, painter: &Painter, to_screen: RectTransform, state: &DndState) {
+        let stroke = Stroke::new(2.0, Color32::from_rgb(170, 90, 230));
+
+        for (_, portal) in state.board.active_portals() {
+            let rect = to_screen.transform_rect(portal.area);
+            painter.rect_stroke(rect, Rounding::same(4.0), stroke);
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                state.board.board_name(&portal.target_board),
+                egui::FontId::proportional(12.0),
+                stroke.color,
+            );
+        }
+
+        if let Some((start, end)) = self.portal_drag {
+            let rect = Rect::from_two_pos(to_screen * start, to_screen * end);
+            painter.rect_stroke(rect, Rounding::same(4.0), stroke);
+        }
+    }
+
+    fn boards_window(
+        &mut self,
+        ctx: &egui::Context,
+        state: &DndState,
+        commands: &mut CommandQueue,
+    ) {
+        let mut open = self.boards_open;
+
+        egui::Window::new("Boards")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let boards = state
+                    .board
+                    .boards
+                    .iter()
+                    .sorted_by_key(|(id, x)| (**id != MAIN_BOARD, x.name.clone()));
+
+                for (id, board) in boards {
+                    ui.horizontal(|ui| {
+                        let active = *id == state.board.active_board;
+                        if ui.selectable_label(active, &board.name).clicked() && !active {
+                            commands.add(SetActiveBoard(*id));
+                        }
+                        if ui
+                            .small_button(egui_phosphor::regular::EXPORT)
+                            .on_hover_text("Export to a file to use in another campaign")
+                            .clicked()
+                        {
+                            commands.add(ExportBoard(*id));
+                        }
+                        if *id != MAIN_BOARD
+                            && ui
+                                .small_button(egui_phosphor::regular::TRASH)
+                                .on_hover_text("Delete this board and everything on it")
+                                .clicked()
+                        {
+                            commands.add(DeleteBoard(*id));
+                        }
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.new_board_name);
+                    let name = self.new_board_name.trim();
+                    if ui
+                        .add_enabled(!name.is_empty(), egui::Button::new("Create"))
+                        .clicked()
+                    {
+                        commands.add(CreateBoard(name.to_owned()));
+                        self.new_board_name.clear();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.board_file_path)
+                            .hint_text("Main.board.json"),
+                    );
+                    let path = self.board_file_path.trim();
+                    if ui
+                        .add_enabled(!path.is_empty(), egui::Button::new("Import"))
+                        .on_hover_text("Adds the exported board as a new board")
+                        .clicked()
+                    {
+                        commands.add(ImportBoard(path.to_owned()));
+                        self.board_file_path.clear();
+                    }
+                });
+
+                ui.separator();
+                ui.label("Portals on this board");
+
+                for (id, portal) in state.board.active_portals() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "To {}",
+                            state.board.board_name(&portal.target_board)
+                        ));
+                        if ui.small_button(egui_phosphor::regular::TRASH).clicked() {
+                            commands.add(DeletePortal(*id));
+                        }
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    let selected = self
+                        .portal_target
+                        .map(|x| state.board.board_name(&x).to_owned())
+                        .unwrap_or_else(|| "Add portal to...".to_owned());
+
+                    egui::ComboBox::new("portal_target", "")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (id, board) in state
+                                .board
+                                .boards
+                                .iter()
+                                .filter(|(id, _)| **id != state.board.active_board)
+                            {
+                                ui.selectable_value(
+                                    &mut self.portal_target,
+                                    Some(*id),
+                                    &board.name,
+                                );
+                            }
+                        });
+
+                    if self.portal_target.is_some() {
+                        ui.label("Drag out the portal on the board");
+                        if ui.button("Cancel").clicked() {
+                            self.portal_target = None;
+                            self.portal_drag = None;
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Tokens dropped in the portal arrive at the same spot on the other board",
+                );
+            });
+
+        self.boards_open = open;
+    }
+
+    fn grid_settings_window(&mut self, ctx: &egui::Context, commands: &mut CommandQueue) {
+        let mut open = self.grid_settings_open;
+
+        egui::Window::new("Board Settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("grid_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let draft = &mut self.grid_draft;
+
+                        ui.label("Grid");
+                        egui::ComboBox::new("grid_kind", "")
+                            .selected_text(draft.kind.to_string())
+                            .show_ui(ui, |ui| {
+                                for kind in GridKind::ALL {
+                                    ui.selectable_value(&mut draft.kind, kind, kind.to_string());
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Spacing");
+                        DragValue::new(&mut draft.spacing)
+                            .range(0.02..=1.0)
+                            .speed(0.005)
+                            .ui(ui);
+                        ui.end_row();
+
+                        ui.label("Color");
+                        ui.color_edit_button_srgba_unmultiplied(&mut draft.color);
+                        ui.end_row();
+
+                        ui.label("Visible");
+                        ui.checkbox(&mut draft.visible, "");
+                        ui.end_row();
+                    });
+
+                if ui.button("Apply").clicked() {
+                    commands.add(SetGrid(self.grid_draft));
+                }
+            });
+
+        self.grid_settings_open = open;
+    }
+
     fn handle_zoom(&mut self, ui: &mut egui::Ui) {
         const ZOOM_FACTOR: f32 = 0.01;
-        const MAX_ZOOM: f32 = 10.0;
-        const MIN_ZOOM: f32 = 0.5;
         self.zoom /= (ui.input(|i| i.smooth_scroll_delta.y) * ZOOM_FACTOR) + 1.0;
-        self.zoom = self.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom = self.zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+    }
+
+    /// Moves the view to center on `pos`, zooming in if the view is so far out
+    /// that a piece `size` across would be hard to spot
+    fn focus_on(&mut self, pos: Pos2, size: f32) {
+        // Pieces take up at least a tenth of the view once there
+        let zoom = self
+            .zoom
+            .min(size * 10.0)
+            .clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        self.camera = Some(CameraMove::new((self.grid_origin, self.zoom), (pos, zoom)));
+    }
+
+    fn focus_on_piece(&mut self, state: &DndState, id: Uuid) {
+        if let Some(piece) = state.board.players.get(&id) {
+            let rect = piece.display_rect();
+            self.focus_on(rect.center(), rect.size().max_elem());
+        }
+    }
+
+    fn follow_focus_hint(&mut self, state: &DndState) {
+        let Some((board, pos, at)) = state.board.focus_hint else {
+            return;
+        };
+        if self.followed_hint == Some(at) {
+            return;
+        }
+        self.followed_hint = Some(at);
+
+        // The GM gets their own request back
+        if self.follow_gm && !state.is_gm() && board == state.board.active_board {
+            self.camera = Some(CameraMove::new(
+                (self.grid_origin, self.zoom),
+                (pos, self.zoom),
+            ));
+        }
+    }
+
+    fn load_linked_sheets(&mut self, state: &DndState, commands: &mut CommandQueue) {
+        for (id, _) in state.board.active_players() {
+            let Some(name) = linked_character(state, id) else {
+                continue;
+            };
+            if state.character_sheet(name).is_none() && self.requested_sheets.insert(name.clone()) {
+                commands.add(RefreshPartyMember(name.clone()));
+            }
+        }
+    }
+
+    /// Whether the GM is leading everyone's view around the board we're on
+    fn gm_view(state: &DndState) -> Option<(Pos2, f32)> {
+        // The GM sends their view every couple of seconds while leading, so
+        // anything older means they've dropped off
+        const STALE: Duration = Duration::from_secs(6);
+
+        state
+            .board
+            .gm_view
+            .filter(|x| !state.is_gm() && x.0 == state.board.active_board)
+            .filter(|x| x.3.elapsed() < STALE)
+            .map(|(_, pos, zoom, _)| (pos, zoom))
+    }
+
+    fn follow_gm_view(&mut self, state: &DndState) {
+        let Some(view) = Self::gm_view(state).filter(|_| self.follow_gm) else {
+            self.followed_view = None;
+            return;
+        };
+        // Only move when the GM does, so we can still look around between
+        if self.followed_view == Some(view) {
+            return;
+        }
+        self.followed_view = Some(view);
+        self.camera = Some(CameraMove::new((self.grid_origin, self.zoom), view));
+    }
+
+    /// Sends our view out while leading, often enough to look smooth without
+    /// flooding everyone as we pan
+    fn lead_view(&mut self, state: &DndState, commands: &mut CommandQueue) {
+        const SEND_INTERVAL: Duration = Duration::from_millis(250);
+        // Lets players who join late catch up even when we're standing still
+        const RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
+        if !self.leading_view || !state.is_gm() {
+            return;
+        }
+
+        let view = (state.board.active_board, self.grid_origin, self.zoom);
+        let due = match self.last_view_sent {
+            Some((at, board, pos, zoom)) if (board, pos, zoom) == view => {
+                at.elapsed() >= RESEND_INTERVAL
+            }
+            Some((at, ..)) => at.elapsed() >= SEND_INTERVAL,
+            None => true,
+        };
+        if due {
+            self.last_view_sent = Some((Instant::now(), view.0, view.1, view.2));
+            commands.add(ShareView(Some((view.1, view.2))));
+        }
+    }
+
+    fn set_leading_view(&mut self, leading: bool, commands: &mut CommandQueue) {
+        self.leading_view = leading;
+        self.last_view_sent = None;
+        if !leading {
+            commands.add(ShareView(None));
+        }
+    }
+
+    /// Sits over the top of the board while the GM leads the view, so there's
+    /// always an obvious way out of following
+    fn following_banner(&mut self, ctx: &egui::Context, canvas: Rect, state: &DndState) {
+        if Self::gm_view(state).is_none() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("following_gm_view"))
+            .fixed_pos(canvas.center_top() + Vec2::new(0.0, 8.0))
+            .pivot(egui::Align2::CENTER_TOP)
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if self.follow_gm {
+                            ui.label(egui_phosphor::regular::PROJECTOR_SCREEN);
+                            ui.strong("Following the GM's view");
+                            if ui.button("Stop following").clicked() {
+                                self.follow_gm = false;
+                            }
+                        } else if ui
+                            .button(format!(
+                                "{} Follow the GM's view",
+                                egui_phosphor::regular::PROJECTOR_SCREEN
+                            ))
+                            .clicked()
+                        {
+                            self.follow_gm = true;
+                        }
+                    });
+                });
+            });
+    }
+
+    fn find_toolbar(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if let Some(id) = self.piece_search.ui(ui, state) {
+            commands.add(Select(Some(id)));
+            self.focus_on_piece(state, id);
+        }
+
+        let mine = my_token(state);
+        if ui
+            .add_enabled(
+                mine.is_some(),
+                egui::Button::new(egui_phosphor::regular::CROSSHAIR),
+            )
+            .on_hover_text("Focus on my token")
+            .on_disabled_hover_text("You don't have a token on this board")
+            .clicked()
+        {
+            if let Some(id) = mine {
+                self.focus_on_piece(state, id);
+            }
+        }
+
+        if state.is_gm()
+            && ui
+                .button(egui_phosphor::regular::BROADCAST)
+                .on_hover_text("Focus all players' views here")
+                .clicked()
+        {
+            commands.add(FocusEveryone(self.grid_origin));
+        }
+
+        if state.is_gm()
+            && ui
+                .selectable_label(self.leading_view, egui_phosphor::regular::PROJECTOR_SCREEN)
+                .on_hover_text("Lead everyone's view, players' cameras follow yours")
+                .clicked()
+        {
+            self.set_leading_view(!self.leading_view, commands);
+        }
     }
 }
 
 impl DndTabImpl for Board {
     fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.horizontal(|ui| {
+            ui.strong(state.board.board_name(&state.board.active_board));
+            ui.separator();
+            self.annotations.toolbar(ui, state, commands);
+            ui.separator();
+            self.find_toolbar(ui, state, commands);
+            ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                self.clock.ui(ui, state, commands);
+                ui.separator();
+                self.session_timer.ui(ui, state, commands);
+            });
+        });
         Frame::canvas(ui.style()).show(ui, |ui| self.ui_content(ui, state, commands));
+        self.grid_settings_window(ui.ctx(), commands);
+        self.boards_window(ui.ctx(), state, commands);
+        self.area_damage.window(ui.ctx(), state, commands);
+        self.map_import.show(ui.ctx(), state, commands);
+        self.templates.window(ui.ctx(), state, commands);
     }
 
     fn title(&self) -> String {
@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+
+use common::{PieceVisibility, SortingLayer};
+use egui::{DragValue, Grid, ScrollArea};
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::board::{
+        commands::{DeletePieces, GroupPieces, PieceChange, Select, Ungroup, UpdatePieces},
+        PlayerPiece,
+    },
+};
+
+use super::DndTabImpl;
+
+#[derive(PartialEq, Clone, Copy)]
+enum SortBy {
+    Name,
+    Layer,
+}
+
+/// Lists every piece on the active board so they can be changed in bulk
+pub struct BoardObjects {
+    search: String,
+    sort_by: SortBy,
+    descending: bool,
+    selected: HashSet<Uuid>,
+    layer: SortingLayer,
+}
+
+impl Default for BoardObjects {
+    fn default() -> Self {
+        Self {
+            search: String::new(),
+            sort_by: SortBy::Name,
+            descending: false,
+            selected: HashSet::new(),
+            layer: SortingLayer(5),
+        }
+    }
+}
+
+/// Unnamed pieces fall back to their image or a short id
+fn display_name(uuid: &Uuid, piece: &PlayerPiece) -> String {
+    if !piece.name.is_empty() {
+        return piece.name.clone();
+    }
+
+    piece
+        .image_url
+        .as_ref()
+        .and_then(|url| url.rsplit('/').next())
+        .filter(|x| !x.is_empty())
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid.to_string()[..8].to_owned())
+}
+
+fn visibility_label(visibility: &PieceVisibility) -> String {
+    match visibility {
+        PieceVisibility::Everyone => "Everyone".to_owned(),
+        PieceVisibility::GmOnly => "GM only".to_owned(),
+        PieceVisibility::Players(players) => players.join(", "),
+    }
+}
+
+impl BoardObjects {
+    fn pieces(&self, state: &DndState) -> Vec<(Uuid, String, SortingLayer)> {
+        let user = state.owned_user().name;
+        let search = self.search.to_lowercase();
+
+        let pieces = state
+            .board
+            .active_players()
+            .filter(|(_, x)| state.is_gm() || x.visibility.includes(&user))
+            .map(|(uuid, x)| (*uuid, display_name(uuid, x), x.sorting_layer))
+            .filter(|(_, name, _)| name.to_lowercase().contains(&search));
+
+        let mut pieces: Vec<_> = match self.sort_by {
+            SortBy::Name => pieces
+                .sorted_by_key(|(_, name, _)| name.to_lowercase())
+                .collect(),
+            SortBy::Layer => pieces
+                .sorted_by_key(|(_, name, layer)| (*layer, name.to_lowercase()))
+                .collect(),
+        };
+        if self.descending {
+            pieces.reverse();
+        }
+        pieces
+    }
+
+    fn bulk_actions(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let ids = || self.selected.iter().copied().collect_vec();
+
+        ui.horizontal_wrapped(|ui| {
+            ui.add_enabled_ui(!self.selected.is_empty(), |ui| {
+                if ui.button("Delete").clicked() {
+                    commands.add(DeletePieces(ids()));
+                }
+                if ui.button("Lock").clicked() {
+                    commands.add(UpdatePieces {
+                        ids: ids(),
+                        change: PieceChange::Locked(true),
+                    });
+                }
+                if ui.button("Unlock").clicked() {
+                    commands.add(UpdatePieces {
+                        ids: ids(),
+                        change: PieceChange::Locked(false),
+                    });
+                }
+                if state.is_gm() {
+                    if ui
+                        .add_enabled(self.selected.len() > 1, egui::Button::new("Group"))
+                        .on_hover_text("Grouped pieces move and get deleted together")
+                        .clicked()
+                    {
+                        commands.add(GroupPieces(ids()));
+                    }
+                    if ui.button("Ungroup").clicked() {
+                        self.selected
+                            .iter()
+                            .filter_map(|x| state.board.groups.group_of(x))
+                            .unique()
+                            .for_each(|x| commands.add(Ungroup(x)));
+                    }
+                    if ui.button("Hide").clicked() {
+                        commands.add(UpdatePieces {
+                            ids: ids(),
+                            change: PieceChange::Visibility(PieceVisibility::GmOnly),
+                        });
+                    }
+                    if ui.button("Show").clicked() {
+                        commands.add(UpdatePieces {
+                            ids: ids(),
+                            change: PieceChange::Visibility(PieceVisibility::Everyone),
+                        });
+                    }
+                }
+
+                ui.separator();
+                DragValue::new(&mut self.layer.0).range(0..=10).ui(ui);
+                if ui.button("Set Layer").clicked() {
+                    commands.add(UpdatePieces {
+                        ids: ids(),
+                        change: PieceChange::Layer(self.layer),
+                    });
+                }
+            });
+        });
+    }
+}
+
+impl DndTabImpl for BoardObjects {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let pieces = self.pieces(state);
+
+        // Pieces can disappear underneath us
+        self.selected
+            .retain(|x| state.board.players.contains_key(x));
+
+        ui.horizontal(|ui| {
+            ui.label(egui_phosphor::regular::MAGNIFYING_GLASS);
+            ui.text_edit_singleline(&mut self.search);
+
+            egui::ComboBox::from_id_salt("board_objects_sort")
+                .selected_text(match self.sort_by {
+                    SortBy::Name => "Name",
+                    SortBy::Layer => "Layer",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.sort_by, SortBy::Name, "Name");
+                    ui.selectable_value(&mut self.sort_by, SortBy::Layer, "Layer");
+                });
+
+            let arrow = if self.descending {
+                egui_phosphor::regular::SORT_DESCENDING
+            } else {
+                egui_phosphor::regular::SORT_ASCENDING
+            };
+            if ui.small_button(arrow).clicked() {
+                self.descending = !self.descending;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let all_selected =
+                !pieces.is_empty() && pieces.iter().all(|(x, _, _)| self.selected.contains(x));
+            let mut select_all = all_selected;
+            if ui.checkbox(&mut select_all, "All").changed() {
+                if select_all {
+                    self.selected.extend(pieces.iter().map(|(x, _, _)| *x));
+                } else {
+                    self.selected.clear();
+                }
+            }
+            ui.weak(format!("{} selected", self.selected.len()));
+        });
+
+        self.bulk_actions(ui, state, commands);
+        ui.separator();
+
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("board_objects").striped(true).show(ui, |ui| {
+                ui.label("");
+                ui.strong("Name");
+                ui.strong("Layer");
+                ui.strong("Locked");
+                ui.strong("Visible to");
+                ui.end_row();
+
+                for (uuid, name, layer) in pieces.iter() {
+                    let piece = &state.board.players[uuid];
+
+                    let mut checked = self.selected.contains(uuid);
+                    if ui.checkbox(&mut checked, "").changed() {
+                        if checked {
+                            self.selected.insert(*uuid);
+                        } else {
+                            self.selected.remove(uuid);
+                        }
+                    }
+
+                    if ui.link(name).on_hover_text("Select on the board").clicked() {
+                        commands.add(Select(Some(*uuid)));
+                    }
+                    ui.label(layer.0.to_string());
+                    if piece.locked {
+                        ui.label(egui_phosphor::regular::LOCK);
+                    } else {
+                        ui.weak(egui_phosphor::regular::LOCK_OPEN);
+                    }
+                    ui.label(visibility_label(&piece.visibility));
+                    ui.end_row();
+                }
+            });
+
+            if pieces.is_empty() {
+                ui.weak("No pieces on this board");
+            }
+        });
+    }
+
+    fn title(&self) -> String {
+        "Board Objects".to_owned()
+    }
+}
@@ -0,0 +1,89 @@
+use common::{rules::Rest, CampaignDate};
+use egui::{DragValue, Grid};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::board::commands::{AdvanceTime, SetCampaignDate},
+};
+
+/// Shared in-game date. Everyone can see it, only the GM can change it
+#[derive(Default)]
+pub struct CampaignClock {
+    draft: CampaignDate,
+}
+
+impl CampaignClock {
+    const ADVANCE_PRESETS: [(&'static str, u32); 3] =
+        [("+10 min", 10), ("+1 hour", 60), ("+1 day", 24 * 60)];
+
+    pub fn ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        let text = format!(
+            "{} {}",
+            egui_phosphor::regular::CALENDAR_BLANK,
+            state.board.date
+        );
+
+        if !state.is_gm() {
+            ui.label(text).on_hover_text("In-game date");
+            return;
+        }
+
+        let response = ui.menu_button(text, |ui| {
+            ui.label("Advance");
+            ui.horizontal(|ui| {
+                for (label, minutes) in Self::ADVANCE_PRESETS {
+                    if ui.button(label).clicked() {
+                        commands.add(AdvanceTime(minutes));
+                    }
+                }
+                for rest in [Rest::Short, Rest::Long] {
+                    if ui.button(rest.to_string()).clicked() {
+                        commands.add(AdvanceTime(state.ruleset.rules().rest_minutes(rest)));
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.label("Set date");
+            Grid::new("campaign_date").num_columns(2).show(ui, |ui| {
+                ui.label("Year");
+                DragValue::new(&mut self.draft.year).suffix(" DR").ui(ui);
+                ui.end_row();
+
+                ui.label("Month");
+                DragValue::new(&mut self.draft.month)
+                    .range(1..=12)
+                    .custom_formatter(|month, _| {
+                        CampaignDate::MONTHS[(month as usize).clamp(1, 12) - 1].to_owned()
+                    })
+                    .ui(ui);
+                ui.end_row();
+
+                ui.label("Day");
+                DragValue::new(&mut self.draft.day)
+                    .range(1..=CampaignDate::DAYS_PER_MONTH)
+                    .ui(ui);
+                ui.end_row();
+
+                ui.label("Time");
+                ui.horizontal(|ui| {
+                    DragValue::new(&mut self.draft.hour).range(0..=23).ui(ui);
+                    ui.label(":");
+                    DragValue::new(&mut self.draft.minute).range(0..=59).ui(ui);
+                });
+                ui.end_row();
+            });
+
+            if ui.button("Set").clicked() {
+                commands.add(SetCampaignDate(self.draft));
+                ui.close_menu();
+            }
+        });
+
+        // Start editing from the current date each time the menu is opened
+        if response.response.clicked() {
+            self.draft = state.board.date;
+        }
+    }
+}
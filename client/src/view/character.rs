@@ -1,168 +1,94 @@
-use std::fmt::Display;
-
 use crate::{
     prelude::*,
-    state::character::commands::{RefreshCharacter, ToggleSkill},
+    state::character::{
+        commands::{
+            ExportCharacter, GrantInspiration, LevelUp, RefreshCharacter, RefreshPartyMember,
+            ResolveConflict, RollDeathSave, SetArmorClassOverride, SetLifeState, SetMaxHp,
+            SetPortrait, SpendInspiration, ToggleSkill,
+        },
+        CharacterState,
+    },
 };
+use common::{formula::Stat, ruleset::Ruleset, LifeState};
 use egui::{
     collapsing_header, popup_below_widget, text::LayoutJob, tooltip_id, Align, Button,
-    CentralPanel, CollapsingHeader, Color32, DragValue, Frame, Label, Margin, Resize, RichText,
-    TopBottomPanel, Vec2, Widget,
+    CentralPanel, CollapsingHeader, Color32, DragValue, Frame, Label, Margin, RadioButton, Resize,
+    RichText, TopBottomPanel, Vec2, Widget,
 };
 use egui_extras::{Column, TableBuilder};
 use serde::de::IntoDeserializer;
 
 use crate::{
     listener::CommandQueue,
-    state::{character::commands::UseItem, DndState},
+    state::{character::commands::UseItem, theme, DndState},
 };
 
-use super::DndTabImpl;
-
-#[derive(Clone, Copy)]
-enum CharStat {
-    Cha,
-    Str,
-    Wis,
-    Int,
-    Dex,
-    Con,
-}
-
-struct Skill<'a> {
-    stat: CharStat,
-    name: &'a str,
-}
-
-impl Display for CharStat {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CharStat::Cha => write!(f, "CHA"),
-            CharStat::Str => write!(f, "STR"),
-            CharStat::Wis => write!(f, "WIS"),
-            CharStat::Int => write!(f, "INT"),
-            CharStat::Dex => write!(f, "DEX"),
-            CharStat::Con => write!(f, "CON"),
-        }
-    }
-}
-
-trait ModScore {
-    fn mod_score(&self) -> i16;
-}
+use super::{
+    dice_tray::DiceTray,
+    effects::{EffectChips, EffectForm},
+    DndTabImpl,
+};
 
-impl ModScore for i16 {
-    fn mod_score(&self) -> i16 {
-        (self / 2) - 5
-    }
-}
+/// Asks what to do about a change the server rejected because the character
+/// was edited somewhere else first
+pub fn show_conflicts(ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+    let Some(change) = state.character.conflicts.first() else {
+        return;
+    };
+
+    egui::Window::new("Character changed")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} was changed somewhere else before your edit to {} was saved.",
+                state.character.character.name,
+                change.field_name().to_lowercase()
+            ));
+
+            egui::Grid::new("character_conflict").show(ui, |ui| {
+                ui.strong("Theirs");
+                ui.label(change.current(&state.character.character).to_string());
+                ui.end_row();
+
+                ui.strong("Mine");
+                ui.label(change.to_string());
+                ui.end_row();
+            });
 
-impl CharStat {
-    fn get_mod_score(self, char: &common::Character) -> i64 {
-        let stat = match self {
-            CharStat::Cha => char.cha,
-            CharStat::Str => char.str,
-            CharStat::Wis => char.wis,
-            CharStat::Int => char.int,
-            CharStat::Dex => char.dex,
-            CharStat::Con => char.con,
-        };
+            ui.horizontal(|ui| {
+                if ui.button("Keep Mine").clicked() {
+                    commands.add(ResolveConflict { keep_mine: true });
+                }
+                if ui.button("Keep Theirs").clicked() {
+                    commands.add(ResolveConflict { keep_mine: false });
+                }
+            });
 
-        stat.mod_score().into()
-    }
+            if state.character.conflicts.len() > 1 {
+                ui.weak(format!(
+                    "{} more after this",
+                    state.character.conflicts.len() - 1
+                ));
+            }
+        });
 }
 
-const SKILL_LIST: [Skill<'static>; 18] = [
-    Skill {
-        stat: CharStat::Dex,
-        name: "Acrobatics",
-    },
-    Skill {
-        stat: CharStat::Wis,
-        name: "Animal Handling",
-    },
-    Skill {
-        stat: CharStat::Int,
-        name: "Arcana",
-    },
-    Skill {
-        stat: CharStat::Str,
-        name: "Athletics",
-    },
-    Skill {
-        stat: CharStat::Cha,
-        name: "Deception",
-    },
-    Skill {
-        stat: CharStat::Int,
-        name: "History",
-    },
-    Skill {
-        stat: CharStat::Wis,
-        name: "Insight",
-    },
-    Skill {
-        stat: CharStat::Cha,
-        name: "Intimidation",
-    },
-    Skill {
-        stat: CharStat::Int,
-        name: "Investigation",
-    },
-    Skill {
-        stat: CharStat::Wis,
-        name: "Medicine",
-    },
-    Skill {
-        stat: CharStat::Int,
-        name: "Nature",
-    },
-    Skill {
-        stat: CharStat::Wis,
-        name: "Perception",
-    },
-    Skill {
-        stat: CharStat::Cha,
-        name: "Performance",
-    },
-    Skill {
-        stat: CharStat::Cha,
-        name: "Persuasion",
-    },
-    Skill {
-        stat: CharStat::Int,
-        name: "Religion",
-    },
-    Skill {
-        stat: CharStat::Dex,
-        name: "Sleight of Hand",
-    },
-    Skill {
-        stat: CharStat::Dex,
-        name: "Stealth",
-    },
-    Skill {
-        stat: CharStat::Wis,
-        name: "Survival",
-    },
-];
-
 pub struct StatWidget {
     name: String,
     value: i16,
+    modifier: i32,
 }
 
 impl StatWidget {
-    pub fn new(name: impl ToString, value: i16) -> Self {
+    pub fn new(name: impl ToString, value: i16, modifier: i32) -> Self {
         Self {
             name: name.to_string(),
             value,
+            modifier,
         }
     }
-
-    fn mod_score(&self) -> i16 {
-        (self.value / 2) - 5
-    }
 }
 
 impl egui::Widget for StatWidget {
@@ -180,8 +106,8 @@ impl egui::Widget for StatWidget {
                     .show(ui, |ui| {
                         ui.vertical_centered_justified(|ui| {
                             ui.label(RichText::new(&self.name).monospace());
-                            let prefix = if self.mod_score() > 0 { "+" } else { "" };
-                            ui.heading(format!("{}{}", prefix, self.mod_score()));
+                            let prefix = if self.modifier > 0 { "+" } else { "" };
+                            ui.heading(format!("{}{}", prefix, self.modifier));
                             ui.small(self.value.to_string());
                         });
                     });
@@ -190,93 +116,442 @@ impl egui::Widget for StatWidget {
     }
 }
 
+/// Levels that come with an ability score improvement
+const ASI_LEVELS: [u32; 5] = [4, 8, 12, 16, 19];
+
+/// Checklist for taking a new level. Only the power slot is applied for the
+/// player, the rest are reminders for things the sheet doesn't track
 #[derive(Default)]
-pub struct Character;
+pub struct LevelUpForm {
+    open: bool,
+    extra_power_slot: bool,
+    hit_points: bool,
+    abilities: bool,
+    ability_scores: bool,
+}
 
-impl DndTabImpl for Character {
-    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
-        let char = &state.character.character;
+impl LevelUpForm {
+    fn open(&mut self) {
+        *self = Self {
+            open: true,
+            extra_power_slot: true,
+            ..Default::default()
+        };
+    }
 
-        egui::CentralPanel::default().show_inside(ui, |ui| {
-            ui.horizontal(|ui| {
-                ui.heading(&char.name);
-                ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
-                    if ui.button("Refresh").clicked() {
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        char: &common::Character,
+        ruleset: &Ruleset,
+        commands: &mut CommandQueue,
+    ) {
+        let level = char.level + 1;
+        let mut open = self.open;
+
+        egui::Window::new(format!("Level {}", level))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.checkbox(
+                    &mut self.extra_power_slot,
+                    format!(
+                        "Gain a power slot ({} to {})",
+                        char.max_power_slots,
+                        char.max_power_slots + 1
+                    ),
+                );
+                ui.checkbox(
+                    &mut self.hit_points,
+                    format!(
+                        "Roll your hit die and add {:+} (CON) to your max HP",
+                        Stat::Con.modifier(char, ruleset)
+                    ),
+                );
+                ui.checkbox(&mut self.abilities, "Add any abilities you learned");
+                if ASI_LEVELS.contains(&level) {
+                    ui.checkbox(&mut self.ability_scores, "Improve your ability scores");
+                }
+
+                ui.separator();
+                if ui.button("Finish").clicked() {
+                    commands.add(LevelUp {
+                        extra_power_slot: self.extra_power_slot,
+                    });
+                    self.open = false;
+                }
+            });
+
+        self.open &= open;
+    }
+}
+
+#[derive(Default)]
+pub struct Character {
+    effect_form: EffectForm,
+    level_up: LevelUpForm,
+    portrait: String,
+    dice_tray: DiceTray,
+}
+
+/// Full character sheet for either the user's own character or a party member
+pub struct CharacterSheet<'a, 'c> {
+    pub state: &'a DndState,
+    pub sheet: &'a CharacterState,
+    pub commands: &'a mut CommandQueue<'c>,
+    pub effect_form: &'a mut EffectForm,
+    pub level_up: &'a mut LevelUpForm,
+    /// Portrait URL being edited
+    pub portrait: &'a mut String,
+}
+
+impl CharacterSheet<'_, '_> {
+    pub fn show(self, ui: &mut egui::Ui) {
+        let CharacterSheet {
+            state,
+            sheet,
+            commands,
+            effect_form,
+            level_up,
+            portrait,
+        } = self;
+
+        let char = &sheet.character;
+        let is_own = state.owned_user().name == char.name;
+        let read_only = !state.can_edit_character(&char.name);
+
+        ui.horizontal(|ui| {
+            ui.heading(&char.name);
+            if read_only {
+                ui.label(RichText::new("(read only)").small().weak());
+            }
+            ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
+                if state.is_gm()
+                    && ui
+                        .button(egui_phosphor::regular::SPARKLE)
+                        .on_hover_text("Grant inspiration")
+                        .clicked()
+                {
+                    commands.add(GrantInspiration {
+                        characters: vec![char.name.clone()],
+                        points: 1,
+                    });
+                }
+                if state.is_gm() {
+                    ui.menu_button(egui_phosphor::regular::SKULL, |ui| {
+                        mark_life_menu(ui, char, commands)
+                    })
+                    .response
+                    .on_hover_text("Mark dead, stable or back up");
+                }
+                if is_own
+                    && !read_only
+                    && char.inspiration > 0
+                    && ui
+                        .button("Spend")
+                        .on_hover_text("Spend a point of inspiration")
+                        .clicked()
+                {
+                    commands.add(SpendInspiration);
+                }
+                if char.inspiration > 0 {
+                    let text = format!("{} {}", egui_phosphor::regular::SPARKLE, char.inspiration);
+                    ui.label(RichText::new(text).color(theme::palette(ui.ctx()).critical))
+                        .on_hover_text("Inspiration");
+                }
+                if ui.button("Refresh").clicked() {
+                    if is_own {
                         commands.add(RefreshCharacter);
+                    } else {
+                        commands.add(RefreshPartyMember(char.name.clone()));
                     }
-                })
-            });
+                }
+                if !read_only {
+                    let response = ui.menu_button(egui_phosphor::regular::IMAGE, |ui| {
+                        ui.label("Portrait URL");
+                        ui.text_edit_singleline(portrait);
+                        if ui.button("Set").clicked() {
+                            let url = portrait.trim();
+                            let url = (!url.is_empty()).then(|| url.to_owned());
+                            commands.add(SetPortrait(char.name.clone(), url));
+                            ui.close_menu();
+                        }
+                    });
 
-            ui.add_space(4.0);
+                    // Start from the current portrait each time the menu is opened
+                    if response.response.clicked() {
+                        *portrait = char.portrait.clone().unwrap_or_default();
+                    }
+                    response
+                        .response
+                        .on_hover_text("Portrait, used for your token on the board");
+                }
+                if is_own
+                    && ui
+                        .button(egui_phosphor::regular::EXPORT)
+                        .on_hover_text("Export character sheet")
+                        .clicked()
+                {
+                    commands.add(ExportCharacter);
+                }
+            })
+        });
 
-            ui.label(RichText::new(format!("\"{}\"", char.tagline)).italics());
-            ui.separator();
-            ui.add_space(6.0);
-            ui.horizontal(|ui| {
-                StatWidget::new("CHA", char.cha).ui(ui);
-                StatWidget::new("STR", char.str).ui(ui);
-                StatWidget::new("WIS", char.wis).ui(ui);
-                StatWidget::new("INT", char.int).ui(ui);
-                StatWidget::new("DEX", char.dex).ui(ui);
-                StatWidget::new("CON", char.con).ui(ui);
-            });
-            ui.add_space(6.0);
-            ui.separator();
+        ui.add_space(4.0);
+
+        ui.label(RichText::new(format!("\"{}\"", char.tagline)).italics());
+
+        ui.horizontal(|ui| {
+            ui.label("Level");
+            ui.heading(char.level.to_string());
+
+            let xp = match state.xp_table.next_level_xp(char.level) {
+                Some(next) => format!("{} / {} XP", char.xp, next),
+                None => format!("{} XP", char.xp),
+            };
+            ui.label(RichText::new(xp).weak());
+
+            // Levelling up is the player's call, so only on their own sheet
+            if is_own
+                && char.can_level_up(&state.xp_table)
+                && ui
+                    .button(RichText::new("Level Up").color(theme::palette(ui.ctx()).positive))
+                    .clicked()
+            {
+                level_up.open();
+            }
+        });
+        if level_up.open {
+            level_up.show(ui.ctx(), char, &state.ruleset, commands);
+        }
 
-            ui.label("Skills");
+        ui.horizontal(|ui| {
+            ui.label("HP");
+            ui.heading(char.curr_hp.to_string());
+            ui.label("/");
+
+            if read_only {
+                ui.heading(char.max_hp.to_string());
+            } else {
+                let mut max_hp = char.max_hp;
+                let resp = DragValue::new(&mut max_hp)
+                    .range(0..=999)
+                    .update_while_editing(false)
+                    .ui(ui)
+                    .on_hover_text("Max HP");
+
+                if resp.changed() {
+                    commands.add(SetMaxHp(char.name.clone(), max_hp));
+                }
+            }
+        });
+        if char.life.is_down() {
+            let can_roll = is_own || state.is_gm();
+            life_row(ui, char, can_roll, commands);
+        }
 
-            let table = TableBuilder::new(ui)
-                .striped(false)
-                .resizable(false)
-                .column(Column::auto())
-                .column(Column::auto())
-                .column(Column::exact(120.0))
-                .column(Column::exact(16.0))
-                .column(Column::exact(6.0))
-                .cell_layout(egui::Layout::left_to_right(Align::Center));
+        let target = common::EffectTarget::Character(char.name.clone());
+        ui.horizontal(|ui| {
+            if !read_only {
+                ui.menu_button("+ Effect", |ui| {
+                    effect_form.ui(ui, target.clone(), commands);
+                });
+            }
+            EffectChips::new(state, &target, commands).ui(ui);
+        });
 
-            table.body(|body| {
-                let row_height = 18.0;
-                let num_rows = SKILL_LIST.len();
+        ui.separator();
+        ui.add_space(6.0);
+        ui.horizontal(|ui| {
+            for stat in state.ruleset.stats.iter() {
+                StatWidget::new(
+                    &stat.name,
+                    stat.stat.score(char),
+                    stat.stat.modifier(char, &state.ruleset),
+                )
+                .ui(ui);
+            }
+        });
+        ui.add_space(6.0);
+        ui.horizontal(|ui| {
+            ui.label("AC");
+            ui.heading(sheet.armor_class(&state.ruleset).to_string());
+
+            let mut overridden = char.ac_override.is_some();
+            if !read_only && ui.checkbox(&mut overridden, "Override").changed() {
+                let ac = overridden.then(|| sheet.armor_class(&state.ruleset));
+                commands.add(SetArmorClassOverride(char.name.clone(), ac));
+            }
+
+            if let Some(mut ac) = char.ac_override.filter(|_| !read_only) {
+                let resp = DragValue::new(&mut ac)
+                    .range(0..=50)
+                    .update_while_editing(false)
+                    .ui(ui);
+
+                if resp.changed() {
+                    commands.add(SetArmorClassOverride(char.name.clone(), Some(ac)));
+                }
+            }
 
-                body.rows(row_height, num_rows, |mut row| {
-                    let index = row.index();
+            ui.separator();
 
-                    let skill = &SKILL_LIST[index];
+            let attack_bonus = sheet.attack_bonus();
+            let prefix = if attack_bonus >= 0 { "+" } else { "" };
+            ui.label("Attack");
+            ui.heading(format!("{}{}", prefix, attack_bonus));
+        });
+        ui.add_space(6.0);
+        ui.separator();
+
+        ui.label("Skills");
+
+        let table = TableBuilder::new(ui)
+            .striped(false)
+            .resizable(false)
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::exact(120.0))
+            .column(Column::exact(16.0))
+            .column(Column::exact(6.0))
+            .cell_layout(egui::Layout::left_to_right(Align::Center));
+
+        table.body(|body| {
+            let row_height = 18.0;
+            let skills = &state.ruleset.skills;
+            let num_rows = skills.len();
+
+            body.rows(row_height, num_rows, |mut row| {
+                let index = row.index();
+
+                let skill = &skills[index];
+
+                let selected = char.skills.contains(&skill.name);
+
+                row.col(|ui| {
+                    if ui
+                        .add_enabled(!read_only, RadioButton::new(selected, ""))
+                        .clicked()
+                    {
+                        commands.add(ToggleSkill::new(char.name.clone(), skill.name.clone()));
+                    }
+                });
 
-                    let selected = char.skills.contains(&(skill.name).to_string());
+                row.col(|ui| {
+                    ui.label(RichText::new(state.ruleset.stat_name(skill.stat)).monospace());
+                });
 
-                    row.col(|ui| {
-                        if ui.radio(selected, "").clicked() {
-                            commands.add(ToggleSkill::new(skill.name.to_string()));
+                row.col(|ui| {
+                    ui.label(&skill.name);
+                });
+
+                row.col(|ui| {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let mut bonus = skill.stat.modifier(char, &state.ruleset);
+
+                        if selected {
+                            bonus += state.ruleset.proficiency_bonus(char.level);
                         }
-                    });
 
-                    row.col(|ui| {
-                        ui.label(RichText::new(format!("{}", skill.stat)).monospace());
-                    });
+                        let prefix = if bonus > 0 { "+" } else { "" };
 
-                    row.col(|ui| {
-                        ui.label(skill.name);
+                        ui.label(format!("{}{}", prefix, bonus));
                     });
+                });
 
-                    row.col(|ui| {
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            let mut bonus = skill.stat.get_mod_score(char);
+                row.col(|_| {});
+            });
+        });
+    }
+}
 
-                            if selected {
-                                bonus += 2;
-                            }
+/// Death saves while they're dying, or how things turned out
+fn life_row(ui: &mut Ui, char: &common::Character, can_roll: bool, commands: &mut CommandQueue) {
+    let palette = theme::palette(ui.ctx());
+    ui.horizontal(|ui| match char.life {
+        LifeState::Dying {
+            successes,
+            failures,
+        } => {
+            let text = format!("{} Dying", egui_phosphor::regular::HEARTBEAT);
+            ui.label(RichText::new(text).strong().color(palette.negative));
+
+            let pips = |passed: u8, filled: &str| {
+                (0..LifeState::SAVES_NEEDED)
+                    .map(|x| {
+                        if x < passed {
+                            filled
+                        } else {
+                            egui_phosphor::regular::CIRCLE
+                        }
+                    })
+                    .collect::<String>()
+            };
+            ui.colored_label(
+                palette.positive,
+                pips(successes, egui_phosphor::regular::CHECK_CIRCLE),
+            )
+            .on_hover_text("Successes");
+            ui.colored_label(
+                palette.negative,
+                pips(failures, egui_phosphor::regular::X_CIRCLE),
+            )
+            .on_hover_text("Failures");
+
+            if can_roll && ui.button("Roll Death Save").clicked() {
+                commands.add(RollDeathSave(char.name.clone()));
+            }
+        }
+        LifeState::Stable => {
+            let text = format!("{} Stable, unconscious", egui_phosphor::regular::FIRST_AID);
+            ui.label(RichText::new(text).strong().color(palette.accent));
+        }
+        LifeState::Dead => {
+            let text = format!("{} Dead", egui_phosphor::regular::SKULL);
+            ui.label(RichText::new(text).strong().color(palette.critical));
+        }
+        LifeState::Conscious => {}
+    });
+}
 
-                            let prefix = if bonus > 0 { "+" } else { "" };
+/// The GM overruling the dice
+fn mark_life_menu(ui: &mut Ui, char: &common::Character, commands: &mut CommandQueue) {
+    let options = [
+        (LifeState::Conscious, "Back Up"),
+        (LifeState::Stable, "Stable"),
+        (LifeState::Dead, "Dead"),
+    ];
+    for (life, label) in options {
+        if ui
+            .add_enabled(char.life != life, Button::new(label))
+            .clicked()
+        {
+            commands.add(SetLifeState {
+                character: char.name.clone(),
+                life,
+            });
+            ui.close_menu();
+        }
+    }
+}
 
-                            ui.label(format!("{}{}", prefix, bonus));
-                        });
-                    });
+impl DndTabImpl for Character {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        TopBottomPanel::bottom("dice_tray").show_inside(ui, |ui| {
+            self.dice_tray.ui(ui, state, commands);
+        });
 
-                    row.col(|_| {});
-                });
-            });
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            CharacterSheet {
+                state,
+                sheet: &state.character,
+                commands,
+                effect_form: &mut self.effect_form,
+                level_up: &mut self.level_up,
+                portrait: &mut self.portrait,
+            }
+            .show(ui);
         });
     }
 
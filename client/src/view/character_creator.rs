@@ -0,0 +1,271 @@
+use common::{formula::Stat, Character};
+use egui::{DragValue, Grid, TextEdit};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        character::commands::{CreateCharacter, DeleteCharacter, ImportCharacter},
+        theme,
+    },
+};
+
+use super::DndTabImpl;
+
+const POINT_BUY_BUDGET: i16 = 27;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StatMethod {
+    PointBuy,
+    Manual,
+}
+
+pub struct CharacterCreator {
+    draft: Character,
+    method: StatMethod,
+    confirm_delete: Option<String>,
+    import_path: String,
+}
+
+impl Default for CharacterCreator {
+    fn default() -> Self {
+        Self {
+            draft: new_draft(),
+            method: StatMethod::PointBuy,
+            confirm_delete: None,
+            import_path: String::new(),
+        }
+    }
+}
+
+fn new_draft() -> Character {
+    Character {
+        int: 8,
+        wis: 8,
+        str: 8,
+        cha: 8,
+        dex: 8,
+        con: 8,
+        ..Default::default()
+    }
+}
+
+/// Standard 5e point buy costs, scores outside 8-15 can't be bought
+fn point_cost(score: i16) -> Option<i16> {
+    match score {
+        8..=13 => Some(score - 8),
+        14 => Some(7),
+        15 => Some(9),
+        _ => None,
+    }
+}
+
+impl CharacterCreator {
+    fn stat_mut(&mut self, stat: Stat) -> &mut i16 {
+        let c = &mut self.draft;
+        match stat {
+            Stat::Str => &mut c.str,
+            Stat::Dex => &mut c.dex,
+            Stat::Con => &mut c.con,
+            Stat::Int => &mut c.int,
+            Stat::Wis => &mut c.wis,
+            Stat::Cha => &mut c.cha,
+        }
+    }
+
+    fn points_spent(&self) -> Option<i16> {
+        let c = &self.draft;
+        [c.str, c.dex, c.con, c.int, c.wis, c.cha]
+            .into_iter()
+            .map(point_cost)
+            .sum()
+    }
+
+    fn stats_ui(&mut self, ui: &mut egui::Ui, state: &DndState) {
+        ui.horizontal(|ui| {
+            let previous = self.method;
+            ui.radio_value(&mut self.method, StatMethod::PointBuy, "Point Buy");
+            ui.radio_value(&mut self.method, StatMethod::Manual, "Manual");
+
+            if previous == StatMethod::Manual && self.method == StatMethod::PointBuy {
+                for stat in Stat::ALL {
+                    let score = self.stat_mut(stat);
+                    *score = (*score).clamp(8, 15);
+                }
+            }
+        });
+
+        let range = match self.method {
+            StatMethod::PointBuy => 8..=15,
+            StatMethod::Manual => 1..=20,
+        };
+
+        Grid::new("creator_stats").show(ui, |ui| {
+            // Stats the ruleset hides stay at 8, which costs nothing
+            for stat in state.ruleset.stats.iter() {
+                ui.label(&stat.name);
+                DragValue::new(self.stat_mut(stat.stat))
+                    .range(range.clone())
+                    .ui(ui);
+                ui.end_row();
+            }
+        });
+
+        if self.method == StatMethod::PointBuy {
+            let remaining = self
+                .points_spent()
+                .map(|spent| POINT_BUY_BUDGET - spent)
+                .unwrap_or_default();
+            let color = if remaining < 0 {
+                theme::palette(ui.ctx()).negative
+            } else {
+                ui.visuals().text_color()
+            };
+            ui.colored_label(color, format!("Points remaining: {}", remaining));
+        }
+    }
+
+    fn skills_ui(&mut self, ui: &mut egui::Ui, state: &DndState) {
+        let ruleset = &state.ruleset;
+        Grid::new("creator_skills").num_columns(2).show(ui, |ui| {
+            for (i, skill) in ruleset.skills.iter().enumerate() {
+                let mut checked = self.draft.skills.contains(&skill.name);
+                let label = format!("{} ({})", skill.name, ruleset.stat_name(skill.stat));
+                if ui.checkbox(&mut checked, label).changed() {
+                    if checked {
+                        self.draft.skills.push(skill.name.clone());
+                    } else {
+                        self.draft.skills.retain(|x| *x != skill.name);
+                    }
+                }
+
+                if i % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+
+    fn can_create(&self, state: &DndState) -> Result<(), &'static str> {
+        let name = self.draft.name.trim();
+        if name.is_empty() {
+            return Err("Needs a name");
+        }
+        if state.roster.iter().any(|x| x == name) {
+            return Err("A character with this name already exists");
+        }
+        if self.method == StatMethod::PointBuy
+            && !matches!(self.points_spent(), Some(spent) if spent <= POINT_BUY_BUDGET)
+        {
+            return Err("Too many points spent");
+        }
+
+        Ok(())
+    }
+
+    fn delete_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.heading("Characters");
+
+        for name in state.roster.iter() {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                if ui
+                    .small_button(egui_phosphor::regular::TRASH)
+                    .on_hover_text("Delete character")
+                    .clicked()
+                {
+                    self.confirm_delete = Some(name.clone());
+                }
+            });
+        }
+
+        let Some(name) = self.confirm_delete.clone() else {
+            return;
+        };
+
+        egui::Window::new("Delete character?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "'{}' and all of their data will be removed. This can't be undone.",
+                    name
+                ));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(RichText::new("Delete").color(theme::palette(ui.ctx()).negative))
+                        .clicked()
+                    {
+                        commands.add(DeleteCharacter(name.clone()));
+                        self.confirm_delete = None;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_delete = None;
+                    }
+                });
+            });
+    }
+}
+
+impl DndTabImpl for CharacterCreator {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.heading("Create Character");
+
+            Grid::new("creator_info").num_columns(2).show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut self.draft.name);
+                ui.end_row();
+
+                ui.label("Tagline");
+                ui.text_edit_singleline(&mut self.draft.tagline);
+                ui.end_row();
+            });
+
+            ui.label("Backstory");
+            TextEdit::multiline(&mut self.draft.backstory)
+                .desired_rows(3)
+                .ui(ui);
+
+            ui.separator();
+            self.stats_ui(ui, state);
+
+            ui.separator();
+            ui.label("Starting skills");
+            self.skills_ui(ui, state);
+
+            ui.separator();
+            let valid = self.can_create(state);
+            let response = ui.add_enabled(valid.is_ok(), egui::Button::new("Create"));
+            if let Err(reason) = valid {
+                response.on_disabled_hover_text(reason);
+            } else if response.clicked() {
+                let mut character = std::mem::replace(&mut self.draft, new_draft());
+                character.name = character.name.trim().to_owned();
+                commands.add(CreateCharacter(character));
+            }
+
+            ui.separator();
+            ui.label("Import an exported character sheet or a D&D Beyond JSON export");
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.import_path);
+                if ui
+                    .add_enabled(!self.import_path.is_empty(), egui::Button::new("Import"))
+                    .clicked()
+                {
+                    commands.add(ImportCharacter(self.import_path.clone()));
+                }
+            });
+
+            if state.is_gm() {
+                ui.separator();
+                self.delete_ui(ui, state, commands);
+            }
+        });
+    }
+
+    fn title(&self) -> String {
+        "Create Character".to_owned()
+    }
+}
@@ -1,19 +1,50 @@
 use std::sync::mpsc::Receiver;
 
-use common::{message::DndMessage, User};
-use egui::{Color32, ScrollArea, TextEdit, Widget};
-use message_io::events::EventSender;
+use common::{
+    message::{DndMessage, RollVisibility},
+    User,
+};
+use egui::{
+    accesskit::{Live, Role},
+    Color32, RichText, ScrollArea, TextEdit, TextStyle, Widget,
+};
+use itertools::Itertools;
 
 use crate::{
     listener::{CommandQueue, Signal},
-    state::{chat::commands::ChatCommand, DndState},
+    state::{
+        chat::{
+            commands::{
+                ChatCommand, ExportLog, PinChatMessage, ScrollChatTo, ShowChatHelp,
+                UnpinChatMessage, CHAT_COMMANDS,
+            },
+            TranscriptFormat,
+        },
+        players::commands::SetTyping,
+        DndState,
+    },
 };
 
-use super::DndTabImpl;
+use super::{chat_input::ChatInput, DndTabImpl};
+
+/// One click actions for the emote menu, posted as `/me <emote>`
+const QUICK_EMOTES: [&str; 8] = [
+    "waves",
+    "nods",
+    "laughs",
+    "shrugs",
+    "cheers",
+    "sighs",
+    "grins",
+    "facepalms",
+];
 
 #[derive(Default)]
 pub struct Chat {
-    text: String,
+    input: ChatInput,
+    roll_visibility: RollVisibility,
+    speak_as: String,
+    typing: bool,
 }
 
 impl DndTabImpl for Chat {
@@ -23,31 +54,160 @@ impl DndTabImpl for Chat {
             .min_height(30.0)
             .show_inside(ui, |ui| {
                 ui.horizontal_centered(|ui| {
-                    let submitted = TextEdit::singleline(&mut self.text)
-                        .desired_width(f32::INFINITY)
-                        .ui(ui);
+                    ui.menu_button(egui_phosphor::regular::EXPORT, |ui| {
+                        if ui.button("Markdown").clicked() {
+                            network.add(ExportLog(TranscriptFormat::Markdown));
+                            ui.close_menu();
+                        }
+                        if ui.button("HTML").clicked() {
+                            network.add(ExportLog(TranscriptFormat::Html));
+                            ui.close_menu();
+                        }
+                    })
+                    .response
+                    .on_hover_text("Export chat log");
+
+                    let speaker = Some(self.speak_as.clone()).filter(|_| state.is_gm());
+                    ui.menu_button(egui_phosphor::regular::SMILEY, |ui| {
+                        for emote in QUICK_EMOTES {
+                            if ui.button(emote).clicked() {
+                                let text = format!("/me {emote}");
+                                network.add(
+                                    ChatCommand::new(text, self.roll_visibility)
+                                        .speak_as(speaker.clone()),
+                                );
+                                ui.close_menu();
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Emotes");
+
+                    if ui
+                        .button(egui_phosphor::regular::QUESTION)
+                        .on_hover_text("Chat commands")
+                        .clicked()
+                    {
+                        network.add(ShowChatHelp(!state.chat.show_help));
+                    }
+
+                    egui::ComboBox::new("roll_visibility", "")
+                        .width(60.0)
+                        .selected_text(roll_visibility_label(self.roll_visibility))
+                        .show_ui(ui, |ui| {
+                            for visibility in [
+                                RollVisibility::Public,
+                                RollVisibility::GmOnly,
+                                RollVisibility::Blind,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.roll_visibility,
+                                    visibility,
+                                    roll_visibility_label(visibility),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("Who sees your rolls");
+
+                    if state.is_gm() {
+                        TextEdit::singleline(&mut self.speak_as)
+                            .hint_text("Speak as...")
+                            .desired_width(80.0)
+                            .ui(ui)
+                            .on_hover_text("Post messages under an NPC's name");
+                    }
+
+                    let submitted = self.input.ui(ui, state);
 
                     if submitted.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
                         submitted.request_focus();
 
-                        network.add(ChatCommand::new(self.text.clone()));
+                        let speaker = Some(self.speak_as.clone()).filter(|_| state.is_gm());
+                        network.add(
+                            ChatCommand::new(self.input.text.clone(), self.roll_visibility)
+                                .speak_as(speaker),
+                        );
 
-                        self.text.clear();
+                        self.input.text.clear();
+                    }
+
+                    let typing = !self.input.text.is_empty();
+                    if typing != self.typing {
+                        self.typing = typing;
+                        network.add(SetTyping(typing));
                     }
                 })
             });
 
+        if state.chat.show_help {
+            egui::TopBottomPanel::bottom("chat_help").show_inside(ui, |ui| {
+                command_help(ui, state.is_gm(), network);
+            });
+        }
+
+        let typing = state
+            .players
+            .online
+            .iter()
+            .filter(|name| state.players.is_typing(name))
+            .collect_vec();
+
+        if !typing.is_empty() {
+            egui::TopBottomPanel::bottom("chat_typing")
+                .show_separator_line(false)
+                .show_inside(ui, |ui| {
+                    let verb = if typing.len() == 1 { "is" } else { "are" };
+                    ui.label(
+                        RichText::new(format!("{} {} typing...", typing.iter().join(", "), verb))
+                            .small()
+                            .italics()
+                            .color(Color32::GRAY),
+                    );
+                });
+        }
+
+        let font_size = state.appearance.current.chat_font_size;
+        if !state.chat.pins.is_empty() {
+            egui::TopBottomPanel::top("chat_pins").show_inside(ui, |ui| {
+                set_font_size(ui, font_size);
+                pinned_ui(ui, state, network);
+            });
+        }
+
         egui::CentralPanel::default().show_inside(ui, |ui| {
+            set_font_size(ui, font_size);
             ScrollArea::new([false, true])
                 .stick_to_bottom(true)
                 .show(ui, |ui| {
-                    let mut last_user = "";
-                    for msg in state.chat.log_messages.iter() {
-                        let display_name = msg.user.name != last_user;
-                        msg.ui(ui, display_name);
+                    // A live log, so screen readers read new messages as they come in
+                    let log_id = ui.id().with("chat_log");
+                    let ctx = ui.ctx().clone();
+                    ctx.accesskit_node_builder(log_id, |builder| {
+                        builder.set_role(Role::Log);
+                        builder.set_live(Live::Polite);
+                        builder.set_name("Chat");
+                    });
 
-                        last_user = &msg.user.name;
-                    }
+                    let plain = state.narration.settings.plain_chat;
+                    let mut last_msg = None;
+                    ctx.with_accessibility_parent(log_id, || {
+                        for (i, msg) in state.chat.log_messages.iter().enumerate() {
+                            let display_header = !last_msg.is_some_and(|x| msg.same_group(x));
+                            let response = msg.ui(ui, display_header, state.is_gm(), plain);
+
+                            response
+                                .interact(egui::Sense::click())
+                                .context_menu(|ui| pin_menu(ui, i, state, network));
+
+                            if state.chat.scroll_to == Some(i) {
+                                response.scroll_to_me(Some(egui::Align::Center));
+                                network.add(ScrollChatTo(None));
+                            }
+
+                            last_msg = Some(msg);
+                        }
+                    });
                 });
         });
     }
@@ -56,3 +216,106 @@ impl DndTabImpl for Chat {
         "Chat".to_owned()
     }
 }
+
+/// Scales every text style in `ui` so body text ends up `size` points tall
+fn set_font_size(ui: &mut egui::Ui, size: f32) {
+    let factor = size / TextStyle::Body.resolve(ui.style()).size;
+    for font in ui.style_mut().text_styles.values_mut() {
+        font.size *= factor;
+    }
+}
+
+fn pinned_ui(ui: &mut egui::Ui, state: &DndState, network: &mut CommandQueue) {
+    let title = format!(
+        "{} Pinned ({})",
+        egui_phosphor::regular::PUSH_PIN,
+        state.chat.pins.len()
+    );
+    egui::CollapsingHeader::new(title)
+        .id_salt("chat_pins")
+        .default_open(true)
+        .show(ui, |ui| {
+            ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                let user = state.owned_user().name;
+                for pin in state.chat.pins.iter() {
+                    ui.horizontal(|ui| {
+                        ui.weak(format!("Pinned by {}", pin.pinned_by));
+                        if let Some(i) = state.chat.find_pinned(pin) {
+                            if ui.small_button("Jump").clicked() {
+                                network.add(ScrollChatTo(Some(i)));
+                            }
+                        }
+                        if pin.can_unpin(&user, state.is_gm())
+                            && ui
+                                .small_button(egui_phosphor::regular::X)
+                                .on_hover_text("Unpin")
+                                .clicked()
+                        {
+                            network.add(UnpinChatMessage(pin.id));
+                        }
+                    });
+                    let plain = state.narration.settings.plain_chat;
+                    pin.message.ui(ui, true, state.is_gm(), plain);
+                    ui.add_space(4.0);
+                }
+            });
+        });
+}
+
+fn pin_menu(ui: &mut egui::Ui, index: usize, state: &DndState, network: &mut CommandQueue) {
+    let user = state.owned_user().name;
+    match state.chat.pin_for(index) {
+        Some(pin) if pin.can_unpin(&user, state.is_gm()) => {
+            if ui.button("Unpin").clicked() {
+                network.add(UnpinChatMessage(pin.id));
+                ui.close_menu();
+            }
+        }
+        Some(pin) => {
+            ui.weak(format!("Pinned by {}", pin.pinned_by));
+        }
+        None if state.chat.log_messages[index].message.is_private() => {
+            ui.weak("Private rolls can't be pinned");
+        }
+        None => {
+            if ui.button("Pin message").clicked() {
+                network.add(PinChatMessage(index));
+                ui.close_menu();
+            }
+        }
+    }
+}
+
+fn command_help(ui: &mut egui::Ui, is_gm: bool, network: &mut CommandQueue) {
+    ui.horizontal(|ui| {
+        ui.strong("Chat commands");
+        if ui.small_button(egui_phosphor::regular::X).clicked() {
+            network.add(ShowChatHelp(false));
+        }
+    });
+
+    egui::Grid::new("chat_commands")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for command in CHAT_COMMANDS.iter().filter(|x| is_gm || !x.gm_only) {
+                ui.label(RichText::new(command.usage).monospace());
+                let mut description = command.description.to_owned();
+                if command.names.len() > 1 {
+                    let shorthands = command.names[1..].iter().map(|x| format!("/{x}"));
+                    description += &format!(" ({})", shorthands.format(", "));
+                }
+                ui.label(description);
+                ui.end_row();
+            }
+        });
+    ui.weak("Messages starting with a die, like d20, are rolled too");
+}
+
+fn roll_visibility_label(visibility: RollVisibility) -> &'static str {
+    match visibility {
+        RollVisibility::Public => "Public",
+        RollVisibility::GmOnly => "GM",
+        RollVisibility::Blind => "Blind",
+    }
+}
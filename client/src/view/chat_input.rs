@@ -0,0 +1,170 @@
+use egui::{
+    text::CCursor, text_selection::CCursorRange, Align2, Area, Frame, Key, Modifiers, TextEdit,
+};
+use itertools::Itertools;
+
+use crate::{
+    prelude::*,
+    state::chat::commands::{command_name, CHAT_COMMANDS},
+};
+
+/// Ways to finish the word being typed, each replacing everything from `start` on
+struct Completions {
+    start: usize,
+    /// Replacement and a hint shown next to it
+    options: Vec<(String, String)>,
+}
+
+fn completions(text: &str, state: &DndState) -> Option<Completions> {
+    if let Some(cmd) = text.strip_prefix('/') {
+        match cmd.split_once(' ') {
+            None => {
+                let options = CHAT_COMMANDS
+                    .iter()
+                    .filter(|x| state.is_gm() || !x.gm_only)
+                    .filter(|x| x.names.iter().any(|name| name.starts_with(cmd)))
+                    .filter(|x| !x.names.contains(&cmd))
+                    .map(|x| (format!("/{}", x.names[0]), x.description.to_owned()))
+                    .collect();
+                return Some(Completions { start: 0, options });
+            }
+            Some((name, rest)) if command_name(name) == Some("table") => {
+                let rest = rest.to_lowercase();
+                let options = state
+                    .roll_tables
+                    .iter()
+                    .filter(|x| {
+                        let table = x.name.to_lowercase();
+                        table.starts_with(&rest) && table != rest
+                    })
+                    .map(|x| (x.name.clone(), String::new()))
+                    .collect();
+                return Some(Completions {
+                    start: name.len() + 2,
+                    options,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Names anywhere in the message, ie. "@Ga" for Gandalf
+    let start = text.rfind(' ').map_or(0, |x| x + 1);
+    let prefix = text[start..].strip_prefix('@')?.to_lowercase();
+    let user = state.owned_user().name;
+    let options = state
+        .players
+        .online
+        .iter()
+        .chain([&user])
+        .filter(|x| {
+            let name = x.to_lowercase();
+            name.starts_with(&prefix) && name != prefix
+        })
+        .sorted()
+        .map(|x| (format!("@{x}"), String::new()))
+        .collect();
+
+    Some(Completions { start, options })
+}
+
+/// The chat box's text field. Suggests commands, roll tables and user names
+/// as they're typed, Tab fills in the highlighted one
+#[derive(Default)]
+pub struct ChatInput {
+    pub text: String,
+    selected: usize,
+    /// Escape hides the suggestions until the text changes
+    dismissed: Option<String>,
+}
+
+impl ChatInput {
+    pub fn ui(&mut self, ui: &mut Ui, state: &DndState) -> egui::Response {
+        let id = ui.id().with("chat_input");
+        let focused = ui.memory(|m| m.has_focus(id));
+
+        let options = completions(&self.text, state)
+            .filter(|_| focused && self.dismissed.as_ref() != Some(&self.text))
+            .filter(|x| !x.options.is_empty());
+
+        if let Some(completions) = &options {
+            let count = completions.options.len();
+            let (tab, up, down, escape) = ui.input_mut(|i| {
+                (
+                    i.consume_key(Modifiers::NONE, Key::Tab),
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                    i.consume_key(Modifiers::NONE, Key::Escape),
+                )
+            });
+
+            if up {
+                self.selected = (self.selected + count - 1) % count;
+            }
+            if down {
+                self.selected = (self.selected + 1) % count;
+            }
+            if escape {
+                self.dismissed = Some(self.text.clone());
+            }
+            if tab {
+                let (replacement, _) = &completions.options[self.selected.min(count - 1)];
+                self.accept(ui, id, completions.start, replacement);
+            }
+        }
+
+        let response = TextEdit::singleline(&mut self.text)
+            .id(id)
+            .desired_width(f32::INFINITY)
+            .ui(ui);
+
+        if response.changed() {
+            self.selected = 0;
+        }
+
+        // Options are worked out before the text changes, so skip a frame after a completion
+        if let Some(completions) = options.filter(|_| !response.changed()) {
+            self.popup(ui, &response, &completions);
+        }
+
+        response
+    }
+
+    fn accept(&mut self, ui: &Ui, id: egui::Id, start: usize, replacement: &str) {
+        self.text.truncate(start);
+        self.text.push_str(replacement);
+        self.text.push(' ');
+        self.selected = 0;
+
+        // Keep typing after the completion rather than wherever the cursor was
+        if let Some(mut edit) = TextEdit::load_state(ui.ctx(), id) {
+            let end = CCursor::new(self.text.chars().count());
+            edit.cursor.set_char_range(Some(CCursorRange::one(end)));
+            edit.store(ui.ctx(), id);
+        }
+    }
+
+    fn popup(&mut self, ui: &Ui, response: &egui::Response, completions: &Completions) {
+        Area::new(response.id.with("completions"))
+            .order(egui::Order::Foreground)
+            .pivot(Align2::LEFT_BOTTOM)
+            .fixed_pos(response.rect.left_top())
+            .show(ui.ctx(), |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    for (i, (option, hint)) in completions.options.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let selected = i == self.selected.min(completions.options.len() - 1);
+                            if ui.selectable_label(selected, option).clicked() {
+                                self.accept(ui, response.id, completions.start, option);
+                                response.request_focus();
+                            }
+                            if !hint.is_empty() {
+                                ui.weak(hint);
+                            }
+                        });
+                    }
+                    ui.weak("Tab to complete");
+                });
+            });
+    }
+}
@@ -0,0 +1,155 @@
+use egui::{Key, Modifiers, ScrollArea, TextEdit};
+
+use crate::prelude::*;
+
+use super::{tab_kinds, TabKind};
+
+/// Something picked from the palette, carried out by the app since most of
+/// these live outside of any one tab
+pub enum PaletteAction {
+    OpenTab(TabKind),
+    OpenSheet(String),
+    ChatHelp,
+    ReportIssue,
+    TogglePresentation,
+    ToggleDiagnostics,
+}
+
+struct Entry {
+    label: String,
+    action: PaletteAction,
+}
+
+/// Ctrl+P lists everything the UI can do, narrowed down by typing
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl CommandPalette {
+    fn entries(state: &DndState) -> Vec<Entry> {
+        let mut entries = tab_kinds()
+            .into_iter()
+            .map(|kind| Entry {
+                label: format!("Open tab: {}", kind.title),
+                action: PaletteAction::OpenTab(kind),
+            })
+            .collect::<Vec<_>>();
+
+        entries.extend(state.roster.iter().map(|name| Entry {
+            label: format!("Open sheet: {name}"),
+            action: PaletteAction::OpenSheet(name.clone()),
+        }));
+
+        entries.push(Entry {
+            label: "Chat commands".to_owned(),
+            action: PaletteAction::ChatHelp,
+        });
+        entries.push(Entry {
+            label: "Report issue".to_owned(),
+            action: PaletteAction::ReportIssue,
+        });
+        entries.push(Entry {
+            label: "Toggle diagnostics overlay".to_owned(),
+            action: PaletteAction::ToggleDiagnostics,
+        });
+        if state.is_gm() {
+            entries.push(Entry {
+                label: "Toggle presentation window".to_owned(),
+                action: PaletteAction::TogglePresentation,
+            });
+        }
+
+        entries
+    }
+
+    /// Every word typed has to show up somewhere in the label
+    fn matches(&self, label: &str) -> bool {
+        let label = label.to_lowercase();
+        self.query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| label.contains(word))
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.query.clear();
+        self.selected = 0;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, state: &DndState) -> Option<PaletteAction> {
+        if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::P)) {
+            self.toggle();
+        }
+
+        if !self.open {
+            return None;
+        }
+
+        let mut entries = Self::entries(state);
+        entries.retain(|x| self.matches(&x.label));
+
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                i.key_pressed(Key::Enter),
+                i.key_pressed(Key::Escape),
+            )
+        });
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if down {
+            self.selected += 1;
+        }
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+
+        let mut picked = enter.then_some(self.selected);
+        egui::Window::new("Command Palette")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, Vec2::new(0.0, 40.0))
+            .show(ctx, |ui| {
+                ui.set_width(300.0);
+                let response = TextEdit::singleline(&mut self.query)
+                    .hint_text("Type to search...")
+                    .desired_width(f32::INFINITY)
+                    .ui(ui);
+                if response.changed() {
+                    self.selected = 0;
+                }
+                response.request_focus();
+
+                ui.separator();
+                ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (i, entry) in entries.iter().enumerate() {
+                        let response = ui.selectable_label(i == self.selected, &entry.label);
+                        if i == self.selected && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            picked = Some(i);
+                        }
+                    }
+
+                    if entries.is_empty() {
+                        ui.weak("Nothing matches");
+                    }
+                });
+            });
+
+        if escape {
+            self.open = false;
+            return None;
+        }
+
+        let picked = picked.filter(|x| *x < entries.len())?;
+        self.open = false;
+        Some(entries.swap_remove(picked).action)
+    }
+}
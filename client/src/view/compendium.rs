@@ -0,0 +1,178 @@
+use egui::ScrollArea;
+use uuid::Uuid;
+
+use crate::{
+    compendium::{
+        equipment_to_item, monster_to_encounter, monster_to_handout, spell_to_ability, Fetch,
+        SrdCategory, SrdClient, SrdEntry,
+    },
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        encounter::commands::AddMonster,
+        handouts::commands::CreateHandout,
+        import::commands::{ImportAbilities, ImportItems},
+        theme,
+    },
+};
+
+use super::DndTabImpl;
+
+pub struct Compendium {
+    client: SrdClient,
+    category: SrdCategory,
+    search: String,
+    selected: Option<SrdEntry>,
+}
+
+impl Default for Compendium {
+    fn default() -> Self {
+        Self {
+            client: SrdClient::default(),
+            category: SrdCategory::Spells,
+            search: String::new(),
+            selected: None,
+        }
+    }
+}
+
+impl Compendium {
+    fn entry_list(&mut self, ui: &mut egui::Ui) {
+        let search = self.search.to_lowercase();
+        let mut retry = false;
+
+        match self.client.index(self.category, ui.ctx()) {
+            Fetch::Loading => {
+                ui.spinner();
+            }
+            Fetch::Done(Err(e)) => {
+                ui.colored_label(theme::palette(ui.ctx()).negative, e);
+                retry = ui.button("Retry").clicked();
+            }
+            Fetch::Done(Ok(entries)) => {
+                ScrollArea::vertical()
+                    .id_salt("compendium_list")
+                    .show(ui, |ui| {
+                        for entry in entries
+                            .iter()
+                            .filter(|x| x.name.to_lowercase().contains(&search))
+                        {
+                            let selected =
+                                matches!(&self.selected, Some(x) if x.index == entry.index);
+                            if ui.selectable_label(selected, &entry.name).clicked() {
+                                self.selected = Some(entry.clone());
+                            }
+                        }
+                    });
+            }
+        }
+
+        if retry {
+            self.client.retry(self.category);
+        }
+    }
+
+    fn details(&mut self, ui: &mut egui::Ui, commands: &mut CommandQueue) {
+        let Some(entry) = &self.selected else {
+            ui.label("Select an entry to see its details");
+            return;
+        };
+
+        let category = self.category;
+        match self.client.details(entry, ui.ctx()) {
+            Fetch::Loading => {
+                ui.spinner();
+            }
+            Fetch::Done(Err(e)) => {
+                ui.colored_label(theme::palette(ui.ctx()).negative, e);
+            }
+            Fetch::Done(Ok(details)) => {
+                ui.heading(&entry.name);
+
+                let (label, hover) = match category {
+                    SrdCategory::Spells => {
+                        ("Add as Ability", "Adds this spell to the ability list")
+                    }
+                    SrdCategory::Equipment => ("Add as Item", "Adds this to the item list"),
+                    SrdCategory::Monsters => {
+                        ("Add as Handout", "Creates a hidden stat block handout")
+                    }
+                };
+                if ui.button(label).on_hover_text(hover).clicked() {
+                    match category {
+                        SrdCategory::Spells => {
+                            commands.add(ImportAbilities(vec![spell_to_ability(details)]))
+                        }
+                        SrdCategory::Equipment => {
+                            commands.add(ImportItems(vec![equipment_to_item(details)]))
+                        }
+                        SrdCategory::Monsters => {
+                            let handout = monster_to_handout(details);
+                            commands.add(CreateHandout(Uuid::new_v4(), handout))
+                        }
+                    }
+                }
+
+                if category == SrdCategory::Monsters
+                    && ui
+                        .button("Add to Encounter")
+                        .on_hover_text("Adds this monster to the encounter builder")
+                        .clicked()
+                {
+                    commands.add(AddMonster(monster_to_encounter(details)));
+                }
+
+                ui.separator();
+
+                let preview = match category {
+                    SrdCategory::Spells => spell_to_ability(details).description,
+                    SrdCategory::Equipment => equipment_to_item(details).description,
+                    SrdCategory::Monsters => monster_to_handout(details).body,
+                };
+                ScrollArea::vertical()
+                    .id_salt("compendium_details")
+                    .show(ui, |ui| {
+                        egui_demo_lib::easy_mark::easy_mark(ui, &preview);
+                    });
+            }
+        }
+    }
+}
+
+impl DndTabImpl for Compendium {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can add entries from the compendium");
+            return;
+        }
+
+        self.client.poll();
+
+        ui.horizontal(|ui| {
+            for category in SrdCategory::ALL {
+                if ui
+                    .selectable_value(&mut self.category, category, category.to_string())
+                    .changed()
+                {
+                    self.selected = None;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(egui_phosphor::regular::MAGNIFYING_GLASS);
+            ui.text_edit_singleline(&mut self.search);
+        });
+
+        ui.separator();
+
+        ui.columns(2, |columns| {
+            self.entry_list(&mut columns[0]);
+            self.details(&mut columns[1], commands);
+        });
+    }
+
+    fn title(&self) -> String {
+        "Compendium".to_owned()
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use egui::{Align2, Area, Frame, Grid, Order};
+use web_time::{Duration, Instant};
+
+use crate::{listener::ListenerStats, prelude::*};
+
+/// Numbers for working out why the board feels laggy, drawn over everything
+/// in the corner of the window
+#[derive(Default)]
+pub struct Diagnostics {
+    pub open: bool,
+    /// Only set when connected to a server, a local session has nothing to count
+    pub stats: Option<Arc<ListenerStats>>,
+    /// Commands queued and messages processed in the last frame
+    frame: (usize, usize),
+    rates: Rates,
+}
+
+/// Messages a second, worked out from how the listener's counts moved
+#[derive(Default)]
+struct Rates {
+    sampled: Option<(Instant, u64, u64)>,
+    sent: f32,
+    received: f32,
+}
+
+impl Diagnostics {
+    const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+    /// Called every frame, whether or not the overlay is showing
+    pub fn record_frame(&mut self, commands: usize, messages: usize) {
+        self.frame = (commands, messages);
+
+        let Some(stats) = &self.stats else {
+            return;
+        };
+        let (sent, received) = (stats.sent(), stats.received());
+        match self.rates.sampled {
+            Some((at, last_sent, last_received)) => {
+                let elapsed = at.elapsed();
+                if elapsed >= Self::RATE_WINDOW {
+                    let secs = elapsed.as_secs_f32();
+                    self.rates.sent = (sent - last_sent) as f32 / secs;
+                    self.rates.received = (received - last_received) as f32 / secs;
+                    self.rates.sampled = Some((Instant::now(), sent, received));
+                }
+            }
+            None => self.rates.sampled = Some((Instant::now(), sent, received)),
+        }
+    }
+
+    pub fn show(&self, ctx: &egui::Context, state: &DndState) {
+        if !self.open {
+            return;
+        }
+
+        Area::new("diagnostics".into())
+            .order(Order::Foreground)
+            .anchor(Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+            .interactable(false)
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    Grid::new("diagnostics_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| self.rows(ui, ctx, state));
+                });
+            });
+    }
+
+    fn rows(&self, ui: &mut Ui, ctx: &egui::Context, state: &DndState) {
+        match &self.stats {
+            Some(stats) => Self::connection_rows(ui, state, stats, &self.rates),
+            None => {
+                ui.label("Connection");
+                ui.monospace("local session");
+                ui.end_row();
+            }
+        }
+
+        let (commands, messages) = self.frame;
+        ui.label("Last frame");
+        ui.monospace(format!(
+            "{commands} commands, {messages} messages, {:.1} ms",
+            ctx.input(|i| i.unstable_dt) * 1000.0
+        ));
+        ui.end_row();
+    }
+
+    fn connection_rows(ui: &mut Ui, state: &DndState, stats: &ListenerStats, rates: &Rates) {
+        ui.label("Round trip");
+        match state.connection.latency {
+            Some(latency) => ui.monospace(format!("{} ms", latency.as_millis())),
+            None => ui.monospace("-"),
+        };
+        ui.end_row();
+
+        ui.label("Last board sync");
+        match stats.since_board_sync() {
+            Some(since) => ui.monospace(format!("{:.1} s ago", since.as_secs_f32())),
+            None => ui.monospace("never"),
+        };
+        ui.end_row();
+
+        ui.label("Sent");
+        ui.monospace(format!("{} ({:.1}/s)", stats.sent(), rates.sent));
+        ui.end_row();
+
+        ui.label("Received");
+        ui.monospace(format!("{} ({:.1}/s)", stats.received(), rates.received));
+        ui.end_row();
+    }
+}
@@ -0,0 +1,118 @@
+use egui::{CollapsingHeader, TextEdit};
+use egui_phosphor::regular as icons;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        chat::commands::ChatCommand,
+        dice_tray::{
+            commands::{PinRoll, UnpinRoll},
+            FavoriteRoll,
+        },
+    },
+};
+
+/// How many of our latest rolls are listed
+const RECENT_ROLLS: usize = 20;
+
+/// Our recent rolls and pinned favorites, each one click away from being rolled again
+#[derive(Default)]
+pub struct DiceTray {
+    /// Formula being typed in to pin, ie. `d20+5 Stealth`
+    formula: String,
+}
+
+impl DiceTray {
+    pub fn ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        CollapsingHeader::new(format!("{} Dice Tray", icons::DICE_FIVE))
+            .id_salt("dice_tray")
+            .show(ui, |ui| {
+                self.favorites_ui(ui, state, commands);
+                ui.separator();
+                recent_ui(ui, state, commands);
+            });
+    }
+
+    fn favorites_ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.strong("Favorites");
+        for (idx, favorite) in state.dice_tray.favorites.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button(favorite.to_string()).clicked() {
+                    commands.add(ChatCommand::new(favorite.command(), RollVisibility::Public));
+                }
+                if ui.small_button(icons::X).on_hover_text("Unpin").clicked() {
+                    commands.add(UnpinRoll(idx));
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            let response = TextEdit::singleline(&mut self.formula)
+                .hint_text("d20+5 Stealth")
+                .desired_width(120.0)
+                .ui(ui);
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            if ui.button("Pin").clicked() || entered {
+                if let Some(favorite) = FavoriteRoll::parse(&self.formula) {
+                    commands.add(PinRoll(favorite));
+                    self.formula.clear();
+                }
+            }
+        });
+    }
+}
+
+fn recent_ui(ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+    ui.strong("Recent");
+
+    let Some(user) = &state.user else {
+        return;
+    };
+
+    let mut rolls = state
+        .chat
+        .recent_rolls(&user.name)
+        .take(RECENT_ROLLS)
+        .peekable();
+    if rolls.peek().is_none() {
+        ui.weak("Nothing rolled yet");
+        return;
+    }
+
+    egui::ScrollArea::vertical()
+        .id_salt("recent_rolls")
+        .max_height(200.0)
+        .show(ui, |ui| {
+            for (idx, (msg, roll)) in rolls.enumerate() {
+                ui.push_id(idx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.weak(msg.time_label());
+                        ui.strong(roll.total().to_string());
+                        ui.label(roll.dice());
+                        if let Some(reason) = &roll.reason {
+                            ui.label(RichText::new(reason).italics());
+                        }
+
+                        let favorite = FavoriteRoll::from_roll(roll);
+                        if ui
+                            .small_button(icons::ARROW_CLOCKWISE)
+                            .on_hover_text("Roll again")
+                            .clicked()
+                        {
+                            commands.add(ChatCommand::new(favorite.command(), roll.visibility));
+                        }
+                        if !state.dice_tray.favorites.contains(&favorite)
+                            && ui
+                                .small_button(icons::PUSH_PIN)
+                                .on_hover_text("Pin to favorites")
+                                .clicked()
+                        {
+                            commands.add(PinRoll(favorite));
+                        }
+                    });
+                });
+            }
+        });
+}
@@ -0,0 +1,240 @@
+use common::{Cooldown, EffectTarget, Recharge, TimedEffect};
+use egui::{DragValue, Frame, Margin, Rounding};
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::effects::commands::{ApplyEffect, RemoveCooldown, RemoveEffect, StartCooldown},
+};
+
+const EFFECT_COLOR: Color32 = Color32::from_rgb(60, 50, 90);
+const COOLDOWN_COLOR: Color32 = Color32::from_rgb(110, 60, 30);
+
+/// How long until the ability is back, ie. "5-6" for a recharge roll
+fn cooldown_status(cooldown: &Cooldown) -> String {
+    match cooldown.recharge {
+        Recharge::Roll(6) => "6".to_owned(),
+        Recharge::Roll(min) => format!("{}-6", min),
+        Recharge::Rounds(_) => cooldown.rounds_left.to_string(),
+    }
+}
+
+pub fn cooldown_label(cooldown: &Cooldown) -> String {
+    format!(
+        "{} {} ({})",
+        egui_phosphor::regular::HOURGLASS,
+        cooldown.ability,
+        cooldown_status(cooldown)
+    )
+}
+
+/// Row of small labels for the effects currently on a target, each removable
+/// with a click by whoever can edit the target.
+pub struct EffectChips<'a, 'c> {
+    state: &'a DndState,
+    target: &'a EffectTarget,
+    commands: &'a mut CommandQueue<'c>,
+}
+
+impl<'a, 'c> EffectChips<'a, 'c> {
+    pub fn new(
+        state: &'a DndState,
+        target: &'a EffectTarget,
+        commands: &'a mut CommandQueue<'c>,
+    ) -> Self {
+        Self {
+            state,
+            target,
+            commands,
+        }
+    }
+}
+
+impl Widget for EffectChips<'_, '_> {
+    fn ui(self, ui: &mut Ui) -> egui::Response {
+        let editable = match self.target {
+            EffectTarget::Character(name) => self.state.can_edit_character(name),
+            EffectTarget::Piece(id) => self.state.can_control_piece(id),
+        };
+
+        ui.horizontal_wrapped(|ui| {
+            for (uuid, effect) in self.state.effects.effects_on(self.target) {
+                let response = effect_chip(ui, effect);
+                if !editable {
+                    response.on_hover_text(&effect.description);
+                } else if response
+                    .on_hover_text(format!("{}\n\nClick to remove", effect.description))
+                    .clicked()
+                {
+                    self.commands.add(RemoveEffect(*uuid));
+                }
+            }
+            for (uuid, cooldown) in self.state.effects.cooldowns_on(self.target) {
+                let response = chip(ui, cooldown_label(cooldown), COOLDOWN_COLOR);
+                if !editable {
+                    response.on_hover_text(cooldown.recharge.to_string());
+                } else if response
+                    .on_hover_text(format!("{}\n\nClick to recharge now", cooldown.recharge))
+                    .clicked()
+                {
+                    self.commands.add(RemoveCooldown(*uuid));
+                }
+            }
+        })
+        .response
+    }
+}
+
+fn effect_chip(ui: &mut Ui, effect: &TimedEffect) -> egui::Response {
+    chip(
+        ui,
+        format!("{} ({})", effect.name, effect.rounds_left),
+        EFFECT_COLOR,
+    )
+}
+
+fn chip(ui: &mut Ui, text: String, fill: Color32) -> egui::Response {
+    Frame::none()
+        .fill(fill)
+        .rounding(Rounding::same(4.0))
+        .inner_margin(Margin::symmetric(4.0, 1.0))
+        .show(ui, |ui| {
+            ui.label(RichText::new(text).small().color(Color32::WHITE));
+        })
+        .response
+        .interact(egui::Sense::click())
+}
+
+/// Draws the chips for a board piece directly with the painter, just under the
+/// token.
+pub fn paint_piece_effects(painter: &egui::Painter, state: &DndState, id: Uuid, rect: Rect) {
+    let target = EffectTarget::Piece(id);
+    let font = egui::FontId::proportional(9.0);
+
+    let effects = state.effects.effects_on(&target).map(|(_, effect)| {
+        let text = format!("{} ({})", effect.name, effect.rounds_left);
+        (text, EFFECT_COLOR)
+    });
+    let cooldowns = state
+        .effects
+        .cooldowns_on(&target)
+        .map(|(_, cooldown)| (cooldown_label(cooldown), COOLDOWN_COLOR));
+
+    let mut pos = rect.left_bottom() + Vec2::new(0.0, 2.0);
+    for (text, fill) in effects.chain(cooldowns) {
+        let galley = painter.layout_no_wrap(text, font.clone(), Color32::WHITE);
+
+        let bg = Rect::from_min_size(pos, galley.size()).expand2(Vec2::new(2.0, 0.0));
+        painter.rect_filled(bg, Rounding::same(3.0), fill);
+        painter.galley(pos, galley, Color32::WHITE);
+
+        pos.y += bg.height() + 1.0;
+    }
+}
+
+/// Applies a timed effect, or puts one of the target's abilities on cooldown
+pub struct EffectForm {
+    name: String,
+    description: String,
+    rounds: u32,
+    /// Set when the form is starting a cooldown instead of an effect
+    recharge: Option<Recharge>,
+}
+
+impl Default for EffectForm {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            description: String::new(),
+            rounds: 10,
+            recharge: None,
+        }
+    }
+}
+
+impl EffectForm {
+    pub fn ui(&mut self, ui: &mut Ui, target: EffectTarget, commands: &mut CommandQueue) {
+        ui.horizontal(|ui| {
+            if ui
+                .selectable_label(self.recharge.is_none(), "Effect")
+                .clicked()
+            {
+                self.recharge = None;
+            }
+            if ui
+                .selectable_label(matches!(self.recharge, Some(Recharge::Roll(_))), "Recharge")
+                .on_hover_text("Ability recharges on a d6 roll at the start of its turn")
+                .clicked()
+            {
+                self.recharge = Some(Recharge::Roll(5));
+            }
+            if ui
+                .selectable_label(
+                    matches!(self.recharge, Some(Recharge::Rounds(_))),
+                    "Cooldown",
+                )
+                .on_hover_text("Ability recharges after a number of rounds")
+                .clicked()
+            {
+                self.recharge = Some(Recharge::Rounds(3));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let label = match self.recharge {
+                Some(_) => "Ability: ",
+                None => "Name: ",
+            };
+            ui.label(label);
+            ui.text_edit_singleline(&mut self.name);
+        });
+
+        match &mut self.recharge {
+            None => {
+                ui.horizontal(|ui| {
+                    ui.label("Effect: ");
+                    ui.text_edit_singleline(&mut self.description);
+                });
+                DragValue::new(&mut self.rounds)
+                    .prefix("rounds: ")
+                    .range(1..=100)
+                    .ui(ui);
+            }
+            Some(Recharge::Roll(min)) => {
+                DragValue::new(min)
+                    .prefix("recharge ")
+                    .suffix("-6")
+                    .range(2..=6)
+                    .ui(ui);
+            }
+            Some(Recharge::Rounds(rounds)) => {
+                DragValue::new(rounds)
+                    .prefix("rounds: ")
+                    .range(1..=100)
+                    .ui(ui);
+            }
+        }
+
+        if ui
+            .add_enabled(!self.name.is_empty(), egui::Button::new("Apply"))
+            .clicked()
+        {
+            let name = std::mem::take(&mut self.name);
+            match self.recharge {
+                Some(recharge) => commands.add(StartCooldown {
+                    ability: name,
+                    recharge,
+                    uses: None,
+                    target,
+                }),
+                None => commands.add(ApplyEffect {
+                    name,
+                    description: std::mem::take(&mut self.description),
+                    rounds: self.rounds,
+                    target,
+                }),
+            }
+        }
+    }
+}
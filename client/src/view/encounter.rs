@@ -0,0 +1,206 @@
+use common::{PieceVisibility, SortingLayer};
+use egui::{DragValue, Grid, ScrollArea};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        board::commands::{AddPiece, PieceParams},
+        encounter::{
+            commands::{AddMonster, ClearEncounter, RemoveMonster, RollInitiative, UpdateMonster},
+            Difficulty, EncounterMonster,
+        },
+        theme::{self, Palette},
+    },
+};
+
+use super::DndTabImpl;
+
+pub struct Encounter {
+    party_level: usize,
+    party_size: u32,
+    new_monster: String,
+    /// Grid square the formation starts from
+    spawn_at: Pos2,
+    hidden: bool,
+}
+
+impl Default for Encounter {
+    fn default() -> Self {
+        Self {
+            party_level: 1,
+            party_size: 4,
+            new_monster: String::new(),
+            spawn_at: Pos2::ZERO,
+            hidden: false,
+        }
+    }
+}
+
+fn difficulty_color(difficulty: Difficulty, palette: &Palette) -> Color32 {
+    match difficulty {
+        Difficulty::Trivial => Color32::GRAY,
+        Difficulty::Easy => palette.positive,
+        Difficulty::Medium => palette.caution,
+        Difficulty::Hard => palette.caution.lerp_to_gamma(palette.negative, 0.5),
+        Difficulty::Deadly => palette.negative,
+    }
+}
+
+impl Encounter {
+    fn monster_list(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        Grid::new("encounter_monsters")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Monster");
+                ui.strong("CR");
+                ui.strong("Count");
+                ui.strong("Size");
+                ui.strong("DEX");
+                ui.end_row();
+
+                for (idx, monster) in state.encounter.monsters.iter().enumerate() {
+                    let mut edited = monster.clone();
+                    ui.label(&monster.name);
+                    DragValue::new(&mut edited.challenge)
+                        .range(0.0..=30.0)
+                        .speed(0.125)
+                        .ui(ui);
+                    DragValue::new(&mut edited.count).range(1..=50).ui(ui);
+                    DragValue::new(&mut edited.size)
+                        .range(0.5..=4.0)
+                        .speed(0.5)
+                        .ui(ui);
+                    DragValue::new(&mut edited.dex).range(1..=30).ui(ui);
+
+                    if ui
+                        .small_button(egui_phosphor::regular::TRASH)
+                        .on_hover_text("Remove from the encounter")
+                        .clicked()
+                    {
+                        commands.add(RemoveMonster(idx));
+                    } else if edited.challenge != monster.challenge
+                        || edited.count != monster.count
+                        || edited.size != monster.size
+                        || edited.dex != monster.dex
+                    {
+                        commands.add(UpdateMonster(idx, edited));
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_monster);
+            let name = self.new_monster.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Add"))
+                .clicked()
+            {
+                commands.add(AddMonster(EncounterMonster {
+                    name: name.to_owned(),
+                    ..Default::default()
+                }));
+                self.new_monster.clear();
+            }
+        });
+    }
+
+    fn spawn(&self, state: &DndState, commands: &mut CommandQueue) {
+        let spacing = state.board.grid.spacing;
+        let visibility = if self.hidden {
+            PieceVisibility::GmOnly
+        } else {
+            PieceVisibility::Everyone
+        };
+
+        let mut numbers = std::collections::HashMap::new();
+        for (monster, pos) in state.encounter.formation(self.spawn_at) {
+            let number = numbers.entry(&monster.name).or_insert(0);
+            *number += 1;
+            let name = format!("{} {}", monster.name, number);
+
+            commands.add(AddPiece {
+                params: PieceParams {
+                    name: name.clone(),
+                    pos: pos * spacing,
+                    size: Vec2::splat(monster.size),
+                    url: monster.image_url.clone(),
+                    visibility: visibility.clone(),
+                    owners: vec![],
+                    sorting_layer: SortingLayer(5),
+                    locked: false,
+                    vision: None,
+                    link_stats_to: None,
+                    label: None,
+                },
+            });
+
+            commands.add(RollInitiative {
+                name,
+                dex: monster.dex,
+            });
+        }
+    }
+}
+
+impl DndTabImpl for Encounter {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can build encounters");
+            return;
+        }
+
+        ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Party level");
+                DragValue::new(&mut self.party_level).range(1..=20).ui(ui);
+                ui.label("Party size");
+                DragValue::new(&mut self.party_size).range(1..=10).ui(ui);
+            });
+
+            ui.separator();
+            self.monster_list(ui, state, commands);
+
+            ui.separator();
+            let encounter = &state.encounter;
+            let difficulty = encounter.difficulty(self.party_level, self.party_size);
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} monsters, {} XP ({} adjusted)",
+                    encounter.monster_count(),
+                    encounter.base_xp(),
+                    encounter.adjusted_xp()
+                ));
+                let color = difficulty_color(difficulty, &theme::palette(ui.ctx()));
+                ui.colored_label(color, difficulty.to_string());
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Spawn at square");
+                DragValue::new(&mut self.spawn_at.x).prefix("x: ").ui(ui);
+                DragValue::new(&mut self.spawn_at.y).prefix("y: ").ui(ui);
+                ui.checkbox(&mut self.hidden, "Hidden from players");
+            });
+
+            ui.horizontal(|ui| {
+                let has_monsters = !encounter.monsters.is_empty();
+                if ui
+                    .add_enabled(has_monsters, egui::Button::new("Spawn"))
+                    .on_hover_text("Adds every token to the board and rolls their initiative")
+                    .clicked()
+                {
+                    self.spawn(state, commands);
+                }
+                if ui.button("Clear").clicked() {
+                    commands.add(ClearEncounter);
+                }
+            });
+        });
+    }
+
+    fn title(&self) -> String {
+        "Encounter".to_owned()
+    }
+}
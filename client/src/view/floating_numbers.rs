@@ -0,0 +1,56 @@
+use egui::{Align2, FontId, Painter};
+use uuid::Uuid;
+
+use crate::{
+    prelude::*,
+    state::{board::FloatingNumber, theme},
+};
+
+const FONT_SIZE: f32 = 22.0;
+
+/// Recent HP changes for a piece, rising off the top of the token and fading
+/// out. Returns whether any are still moving so the caller keeps repainting
+pub fn paint_floating_numbers(
+    painter: &Painter,
+    rect: Rect,
+    piece: Uuid,
+    floating: &[FloatingNumber],
+) -> bool {
+    let mut alive = false;
+    let palette = theme::palette(painter.ctx());
+
+    for (i, number) in floating.iter().filter(|x| x.piece == piece).enumerate() {
+        let t = number.progress();
+        if t >= 1.0 {
+            continue;
+        }
+        alive = true;
+
+        let (text, color) = if number.amount < 0 {
+            (number.amount.to_string(), palette.negative)
+        } else {
+            (format!("+{}", number.amount), palette.positive)
+        };
+        let alpha = 1.0 - t * t;
+        // Hits landing together stack instead of drawing over each other
+        let offset = Vec2::new(0.0, -(t * rect.height() * 0.5 + i as f32 * FONT_SIZE));
+        let pos = rect.center_top() + offset;
+
+        painter.text(
+            pos + Vec2::splat(1.5),
+            Align2::CENTER_BOTTOM,
+            &text,
+            FontId::proportional(FONT_SIZE),
+            Color32::BLACK.gamma_multiply(alpha),
+        );
+        painter.text(
+            pos,
+            Align2::CENTER_BOTTOM,
+            &text,
+            FontId::proportional(FONT_SIZE),
+            color.gamma_multiply(alpha),
+        );
+    }
+
+    alive
+}
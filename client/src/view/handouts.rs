@@ -0,0 +1,204 @@
+use common::{Handout, HandoutVisibility};
+use egui::{Image, RichText, ScrollArea, TextEdit};
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::handouts::commands::{CreateHandout, DeleteHandout, MarkHandoutRead, ShareHandout},
+};
+
+use super::{board::character_selection, DndTabImpl};
+
+#[derive(Default)]
+pub struct Handouts {
+    selected: Option<Uuid>,
+    editing: Option<(Uuid, Handout)>,
+    unread: usize,
+}
+
+fn visibility_ui(ui: &mut egui::Ui, state: &DndState, visibility: &mut HandoutVisibility) {
+    ui.horizontal(|ui| {
+        if ui
+            .radio(*visibility == HandoutVisibility::Hidden, "GM only")
+            .clicked()
+        {
+            *visibility = HandoutVisibility::Hidden;
+        }
+        if ui
+            .radio(*visibility == HandoutVisibility::Everyone, "Everyone")
+            .clicked()
+        {
+            *visibility = HandoutVisibility::Everyone;
+        }
+        if ui
+            .radio(
+                matches!(visibility, HandoutVisibility::Players(_)),
+                "Specific players",
+            )
+            .clicked()
+            && !matches!(visibility, HandoutVisibility::Players(_))
+        {
+            *visibility = HandoutVisibility::Players(Vec::new());
+        }
+    });
+
+    if let HandoutVisibility::Players(players) = visibility {
+        character_selection(ui, state, players);
+    }
+}
+
+fn visibility_label(visibility: &HandoutVisibility) -> String {
+    match visibility {
+        HandoutVisibility::Hidden => "GM only".to_owned(),
+        HandoutVisibility::Everyone => "Shared with everyone".to_owned(),
+        HandoutVisibility::Players(players) => format!("Shared with {}", players.join(", ")),
+    }
+}
+
+impl Handouts {
+    fn list_ui(&mut self, ui: &mut egui::Ui, state: &DndState) {
+        if state.is_gm() && ui.button("New Handout").clicked() {
+            self.editing = Some((Uuid::new_v4(), Handout::default()));
+        }
+
+        ScrollArea::vertical().show(ui, |ui| {
+            for (id, handout) in state.handouts.sorted() {
+                let mut text = RichText::new(&handout.title);
+                if state.handouts.unread.contains(id) {
+                    text =
+                        RichText::new(format!("{} {}", egui_phosphor::fill::CIRCLE, handout.title))
+                            .strong();
+                }
+
+                if ui
+                    .selectable_label(self.selected == Some(*id), text)
+                    .clicked()
+                {
+                    self.selected = Some(*id);
+                    self.editing = None;
+                }
+            }
+        });
+    }
+
+    fn editor_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let Some((id, handout)) = &mut self.editing else {
+            return;
+        };
+
+        egui::Grid::new("handout_editor")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Title");
+                ui.text_edit_singleline(&mut handout.title);
+                ui.end_row();
+
+                ui.label("Image url");
+                let mut url = handout.image_url.clone().unwrap_or_default();
+                ui.text_edit_singleline(&mut url);
+                handout.image_url = Some(url).filter(|x| !x.is_empty());
+                ui.end_row();
+            });
+
+        TextEdit::multiline(&mut handout.body)
+            .hint_text("Supports easy mark: *bold*, /italics/, # headings")
+            .desired_width(f32::INFINITY)
+            .desired_rows(10)
+            .ui(ui);
+
+        visibility_ui(ui, state, &mut handout.visibility);
+
+        let (save, cancel) = ui
+            .horizontal(|ui| (ui.button("Save").clicked(), ui.button("Cancel").clicked()))
+            .inner;
+
+        if save {
+            let id = *id;
+            commands.add(CreateHandout(id, handout.clone()));
+            self.selected = Some(id);
+            self.editing = None;
+        } else if cancel {
+            self.editing = None;
+        }
+    }
+
+    fn handout_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        state: &DndState,
+        commands: &mut CommandQueue,
+        id: Uuid,
+        handout: &Handout,
+    ) {
+        if state.handouts.unread.contains(&id) {
+            commands.add(MarkHandoutRead(id));
+        }
+
+        ui.heading(&handout.title);
+
+        if state.is_gm() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(visibility_label(&handout.visibility)).weak());
+
+                ui.menu_button("Share", |ui| {
+                    let mut visibility = handout.visibility.clone();
+                    visibility_ui(ui, state, &mut visibility);
+                    if visibility != handout.visibility {
+                        commands.add(ShareHandout(id, visibility));
+                    }
+                });
+
+                if ui.button("Edit").clicked() {
+                    self.editing = Some((id, handout.clone()));
+                }
+                if ui.button("Delete").clicked() {
+                    commands.add(DeleteHandout(id));
+                    self.selected = None;
+                }
+            });
+        }
+
+        ui.separator();
+
+        ScrollArea::vertical().show(ui, |ui| {
+            if let Some(url) = &handout.image_url {
+                ui.add(Image::new(url).max_width(ui.available_width()));
+            }
+
+            egui_demo_lib::easy_mark::easy_mark(ui, &handout.body);
+        });
+    }
+}
+
+impl DndTabImpl for Handouts {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        self.unread = state.handouts.unread.len();
+
+        egui::SidePanel::left("handout_list")
+            .resizable(true)
+            .default_width(150.0)
+            .show_inside(ui, |ui| self.list_ui(ui, state));
+
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            if self.editing.is_some() {
+                self.editor_ui(ui, state, commands);
+            } else if let Some((id, handout)) = self
+                .selected
+                .and_then(|id| state.handouts.handouts.get(&id).map(|x| (id, x)))
+            {
+                self.handout_ui(ui, state, commands, id, handout);
+            } else {
+                ui.label("Select a handout");
+            }
+        });
+    }
+
+    fn title(&self) -> String {
+        if self.unread > 0 {
+            format!("Handouts ({})", self.unread)
+        } else {
+            "Handouts".to_owned()
+        }
+    }
+}
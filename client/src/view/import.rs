@@ -0,0 +1,181 @@
+use common::{AbilityDefinition, ItemDefinition};
+use egui::{Grid, RichText, ScrollArea, TextEdit};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        import::{
+            commands::{ImportAbilities, ImportItems},
+            parse_entries, ImportKind, Validate,
+        },
+        theme,
+    },
+};
+
+use super::DndTabImpl;
+
+pub struct Import {
+    kind: ImportKind,
+    path: String,
+    text: String,
+    items: Vec<Result<ItemDefinition, String>>,
+    abilities: Vec<Result<AbilityDefinition, String>>,
+    load_error: Option<String>,
+}
+
+impl Default for Import {
+    fn default() -> Self {
+        Self {
+            kind: ImportKind::Items,
+            path: String::new(),
+            text: String::new(),
+            items: Vec::new(),
+            abilities: Vec::new(),
+            load_error: None,
+        }
+    }
+}
+
+enum ImportEntries {
+    Items(Vec<ItemDefinition>),
+    Abilities(Vec<AbilityDefinition>),
+}
+
+fn preview<T: Validate>(ui: &mut egui::Ui, entries: &[Result<T, String>]) {
+    ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+        Grid::new("import_preview").striped(true).show(ui, |ui| {
+            for (i, entry) in entries.iter().enumerate() {
+                ui.label(format!("{}", i + 1));
+                match entry {
+                    Ok(entry) => {
+                        ui.label(entry.name());
+                        let color = theme::palette(ui.ctx()).positive;
+                        ui.colored_label(color, egui_phosphor::regular::CHECK);
+                    }
+                    Err(e) => {
+                        ui.label("");
+                        ui.colored_label(theme::palette(ui.ctx()).negative, e);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
+
+/// Only returns the entries when every one of them is valid
+fn valid_entries<T: Clone>(entries: &[Result<T, String>]) -> Option<Vec<T>> {
+    if entries.is_empty() {
+        return None;
+    }
+    entries.iter().cloned().collect::<Result<_, _>>().ok()
+}
+
+impl Import {
+    fn reparse(&mut self) {
+        match self.kind {
+            ImportKind::Items => self.items = parse_entries(&self.text),
+            ImportKind::Abilities => self.abilities = parse_entries(&self.text),
+        }
+    }
+}
+
+impl DndTabImpl for Import {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can import items and abilities");
+            return;
+        }
+
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            changed |= ui
+                .radio_value(&mut self.kind, ImportKind::Items, "Items")
+                .changed();
+            changed |= ui
+                .radio_value(&mut self.kind, ImportKind::Abilities, "Abilities")
+                .changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("File: ");
+            ui.text_edit_singleline(&mut self.path);
+            if ui.button("Load").clicked() {
+                match crate::storage::read_to_string(&self.path) {
+                    Ok(text) => {
+                        self.text = text;
+                        self.load_error = None;
+                        changed = true;
+                    }
+                    Err(e) => self.load_error = Some(e.to_string()),
+                }
+            }
+        });
+
+        if let Some(e) = &self.load_error {
+            ui.colored_label(theme::palette(ui.ctx()).negative, e);
+        }
+
+        changed |= TextEdit::multiline(&mut self.text)
+            .hint_text("Paste a JSON array or CSV with a header row")
+            .code_editor()
+            .desired_width(f32::INFINITY)
+            .desired_rows(8)
+            .ui(ui)
+            .changed();
+
+        if changed {
+            self.reparse();
+        }
+
+        ui.separator();
+
+        let import = match self.kind {
+            ImportKind::Items => {
+                preview(ui, &self.items);
+                valid_entries(&self.items).map(|x| (x.len(), ImportEntries::Items(x)))
+            }
+            ImportKind::Abilities => {
+                preview(ui, &self.abilities);
+                valid_entries(&self.abilities).map(|x| (x.len(), ImportEntries::Abilities(x)))
+            }
+        };
+
+        ui.horizontal(|ui| {
+            let count = import.as_ref().map(|(count, _)| *count).unwrap_or_default();
+            let button = ui
+                .add_enabled(
+                    import.is_some(),
+                    egui::Button::new(format!("Import {count}")),
+                )
+                .on_disabled_hover_text("Fix every error before importing");
+
+            if button.clicked() {
+                match import {
+                    Some((_, ImportEntries::Items(items))) => commands.add(ImportItems(items)),
+                    Some((_, ImportEntries::Abilities(abilities))) => {
+                        commands.add(ImportAbilities(abilities))
+                    }
+                    None => {}
+                }
+            }
+
+            match &state.import.last_result {
+                Some(Ok(count)) => {
+                    ui.label(RichText::new(format!("Saved {count} entries")).weak());
+                }
+                Some(Err(e)) => {
+                    let color = theme::palette(ui.ctx()).negative;
+                    ui.colored_label(color, format!("Import failed: {e}"));
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn title(&self) -> String {
+        "Import".to_owned()
+    }
+}
@@ -1,13 +1,39 @@
 use egui::{collapsing_header, popup_below_widget, DragValue};
 
-use crate::{listener::CommandQueue, prelude::*, state::character::commands::UseItem};
+use common::EquipSlot;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        character::commands::{EquipItem, SetAttunement, SetItemCount, UseItem},
+        inventory::{commands::SetInventoryView, total_weight, InventoryView, ItemSort},
+        theme,
+    },
+};
 
 use super::DndTabImpl;
 
+/// A change that would use up the last of an item, held until it's confirmed
+#[derive(Clone, Copy)]
+enum Removal {
+    /// Using them posts to chat
+    Use(u32),
+    /// Taking one off with the minus button
+    Decrement,
+}
+
 pub struct ItemWidget<'a, 'b, 'c> {
     idx: usize,
     item: Item,
     use_num: &'a mut u32,
+    info_item: &'a mut Option<i64>,
+    confirm_removal: &'a mut Option<(i64, Removal)>,
+    /// Whether there's room to attune to one more item
+    can_attune: bool,
+    can_use: bool,
+    /// Which of the item's details are shown on its row
+    columns: InventoryView,
     commands: &'b mut CommandQueue<'c>,
 }
 
@@ -16,26 +42,105 @@ impl<'a, 'b, 'c> ItemWidget<'a, 'b, 'c> {
         idx: usize,
         item: Item,
         use_num: &'a mut u32,
+        info_item: &'a mut Option<i64>,
+        confirm_removal: &'a mut Option<(i64, Removal)>,
+        can_attune: bool,
         commands: &'b mut CommandQueue<'c>,
     ) -> Self {
         Self {
             idx,
             item,
             use_num,
+            info_item,
+            confirm_removal,
+            can_attune,
+            can_use: true,
+            columns: InventoryView::default(),
             commands,
         }
     }
+
+    /// Greys out the use button, ie. while the character is down
+    fn can_use(mut self, can_use: bool) -> Self {
+        self.can_use = can_use;
+        self
+    }
+
+    fn columns(mut self, columns: InventoryView) -> Self {
+        self.columns = columns;
+        self
+    }
+}
+
+/// Rounded off so stacks don't show float noise, ie. 0.1 lb x3
+fn pounds(weight: f32) -> String {
+    format!("{} lb", (weight * 100.0).round() / 100.0)
+}
+
+/// Detail window for a single item. Kept separate from [`ItemWidget`] so any
+/// view holding an [`Item`] can pop it open.
+pub struct ItemInfo<'a> {
+    item: &'a Item,
+}
+
+impl<'a> ItemInfo<'a> {
+    pub fn new(item: &'a Item) -> Self {
+        Self { item }
+    }
+
+    pub fn show(self, ctx: &egui::Context, open: &mut bool) {
+        let item = self.item;
+
+        egui::Window::new(item.name.clone())
+            .id(egui::Id::new(("item_info", item.id)))
+            .open(open)
+            .collapsible(false)
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                let palette = theme::palette(ui.ctx());
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format!("x{}", item.count))
+                            .color(palette.positive)
+                            .italics(),
+                    );
+
+                    if item.quest_item {
+                        ui.label(RichText::new("Quest Item").color(palette.caution));
+                    }
+
+                    if item.requires_attunement {
+                        ui.label(RichText::new("Requires Attunement").color(palette.accent));
+                    }
+                });
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui_demo_lib::easy_mark::easy_mark(ui, &item.description);
+
+                    if !item.flavor_text.is_empty() {
+                        ui.add_space(4.0);
+                        egui_demo_lib::easy_mark::easy_mark(
+                            ui,
+                            &format!("/\"{}\"/", &item.flavor_text),
+                        );
+                    }
+                });
+            });
+    }
 }
 
 impl<'a, 'b, 'c> Widget for ItemWidget<'a, 'b, 'c> {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let palette = theme::palette(ui.ctx());
         let mut item_text =
             LayoutJob::single_section(self.item.name.clone(), egui::TextFormat::default());
         item_text.append(
             &format!("x{}", self.item.count),
             10.0,
             egui::TextFormat {
-                color: Color32::LIGHT_GREEN,
+                color: palette.positive,
                 italics: true,
                 ..Default::default()
             },
@@ -50,13 +155,23 @@ impl<'a, 'b, 'c> Widget for ItemWidget<'a, 'b, 'c> {
                     let mut title = RichText::new(&self.item.name);
 
                     if self.item.quest_item {
-                        title = title.color(Color32::YELLOW);
+                        title = title.color(palette.caution);
                     }
 
                     ui.label(title);
 
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        let button = ui.button("Use");
+                        if ui
+                            .button(egui_phosphor::regular::INFO)
+                            .on_hover_text("Item info")
+                            .clicked()
+                        {
+                            *self.info_item = Some(self.item.id);
+                        }
+
+                        let button = ui
+                            .add_enabled(self.can_use, egui::Button::new("Use"))
+                            .on_disabled_hover_text("Can't act while down");
                         if button.clicked() {
                             ui.memory_mut(|mem| mem.toggle_popup(popup_id));
                         }
@@ -73,7 +188,13 @@ impl<'a, 'b, 'c> Widget for ItemWidget<'a, 'b, 'c> {
                                         .ui(ui);
 
                                     if ui.button("Done").clicked() {
-                                        self.commands.add(UseItem::new(self.idx, *self.use_num));
+                                        let count = (*self.use_num).clamp(1, self.item.count);
+                                        if count >= self.item.count {
+                                            *self.confirm_removal =
+                                                Some((self.item.id, Removal::Use(count)));
+                                        } else {
+                                            self.commands.add(UseItem::new(self.idx, count));
+                                        }
 
                                         ui.memory_mut(|mem| mem.toggle_popup(popup_id));
                                     }
@@ -81,15 +202,95 @@ impl<'a, 'b, 'c> Widget for ItemWidget<'a, 'b, 'c> {
                             },
                         );
 
-                        ui.label(
-                            RichText::new(format!("x{}", self.item.count))
-                                .color(Color32::LIGHT_GREEN)
-                                .italics(),
-                        );
+                        if self.columns.show_quantity {
+                            if ui
+                                .small_button(egui_phosphor::regular::PLUS)
+                                .on_hover_text("Add one")
+                                .clicked()
+                            {
+                                self.commands.add(SetItemCount {
+                                    item_idx: self.idx,
+                                    count: self.item.count + 1,
+                                });
+                            }
+
+                            ui.label(
+                                RichText::new(format!("x{}", self.item.count))
+                                    .color(palette.positive)
+                                    .italics(),
+                            );
+
+                            if ui
+                                .small_button(egui_phosphor::regular::MINUS)
+                                .on_hover_text("Take one away without using it")
+                                .clicked()
+                            {
+                                if self.item.count <= 1 {
+                                    *self.confirm_removal =
+                                        Some((self.item.id, Removal::Decrement));
+                                } else {
+                                    self.commands.add(SetItemCount {
+                                        item_idx: self.idx,
+                                        count: self.item.count - 1,
+                                    });
+                                }
+                            }
+                        }
+
+                        if let Some(slot) = self.item.slot.filter(|_| self.columns.show_slot) {
+                            ui.label(RichText::new(slot.to_string()).small().weak());
+                        }
+
+                        if let Some(each) = self.item.weight.filter(|_| self.columns.show_weight) {
+                            let weight = each * self.item.count as f32;
+                            let label = ui.label(RichText::new(pounds(weight)).small().weak());
+                            if self.item.count > 1 {
+                                label.on_hover_text(format!("{} each", pounds(each)));
+                            }
+                        }
+
+                        if self.item.requires_attunement {
+                            let attuned = self.item.attuned;
+                            let response = ui
+                                .add_enabled(
+                                    attuned || self.can_attune,
+                                    egui::SelectableLabel::new(
+                                        attuned,
+                                        egui_phosphor::regular::SPARKLE,
+                                    ),
+                                )
+                                .on_hover_text(if attuned { "Attuned" } else { "Attune" })
+                                .on_disabled_hover_text(
+                                    "You're attuned to as many items as you can be",
+                                );
+                            if response.clicked() {
+                                self.commands.add(SetAttunement {
+                                    item_idx: self.idx,
+                                    attuned: !attuned,
+                                });
+                            }
+                        }
                     })
                 })
             })
             .body(|ui| {
+                let mut slot = self.item.slot;
+                egui::ComboBox::new(("equip_slot", self.item.id), "Slot")
+                    .selected_text(
+                        slot.map(|x| x.to_string())
+                            .unwrap_or_else(|| "-".to_owned()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut slot, None, "-");
+                        for option in EquipSlot::ALL {
+                            ui.selectable_value(&mut slot, Some(option), option.to_string());
+                        }
+                    });
+
+                if slot != self.item.slot {
+                    self.commands.add(EquipItem::new(self.idx, slot));
+                }
+
                 egui_demo_lib::easy_mark::easy_mark(ui, &self.item.description);
 
                 egui_demo_lib::easy_mark::easy_mark(
@@ -101,20 +302,168 @@ impl<'a, 'b, 'c> Widget for ItemWidget<'a, 'b, 'c> {
     }
 }
 
+/// Attuned item count, warning when it's past the limit
+fn attunement_ui(ui: &mut Ui, state: &DndState) {
+    if !state.character.items.iter().any(|x| x.requires_attunement) {
+        return;
+    }
+
+    let attuned = state.character.attuned_count();
+    let limit = state.ruleset.attunement_limit as usize;
+    let text = format!("Attuned {attuned}/{limit}");
+    if attuned > limit {
+        let color = theme::palette(ui.ctx()).negative;
+        ui.colored_label(color, format!("{text}, that's over the limit"));
+    } else {
+        ui.label(RichText::new(text).weak());
+    }
+}
+
+/// Sort order and which columns are shown, saved for the character
+fn view_options_ui(ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+    let character = &state.character.character.name;
+    let mut view = state.inventory.view(character);
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("inventory_sort")
+            .selected_text(format!("Sort: {}", view.sort))
+            .show_ui(ui, |ui| {
+                for sort in ItemSort::ALL {
+                    ui.selectable_value(&mut view.sort, sort, sort.to_string());
+                }
+            });
+
+        let arrow = if view.descending {
+            egui_phosphor::regular::SORT_DESCENDING
+        } else {
+            egui_phosphor::regular::SORT_ASCENDING
+        };
+        if ui.small_button(arrow).clicked() {
+            view.descending = !view.descending;
+        }
+
+        ui.menu_button("Columns", |ui| {
+            ui.checkbox(&mut view.show_quantity, "Quantity");
+            ui.checkbox(&mut view.show_weight, "Weight");
+            ui.checkbox(&mut view.show_slot, "Slot");
+        });
+    });
+
+    if view != state.inventory.view(character) {
+        commands.add(SetInventoryView(character.clone(), view));
+    }
+}
+
+/// Carried weight of everything that has one
+fn total_weight_ui(ui: &mut Ui, items: &[Item]) {
+    let unweighed = items.iter().filter(|x| x.weight.is_none()).count();
+    ui.horizontal(|ui| {
+        ui.strong(format!("Total weight {}", pounds(total_weight(items))));
+        if unweighed > 0 {
+            ui.weak(format!("({unweighed} without a weight)"));
+        }
+    });
+}
+
 #[derive(Default)]
 pub struct Items {
     use_num: u32,
+    info_item: Option<i64>,
+    confirm_removal: Option<(i64, Removal)>,
+}
+
+impl Items {
+    fn confirm_removal_ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        let Some((id, removal)) = self.confirm_removal else {
+            return;
+        };
+        let Some((idx, item)) = state
+            .character
+            .items
+            .iter()
+            .enumerate()
+            .find(|(_, x)| x.id == id)
+        else {
+            self.confirm_removal = None;
+            return;
+        };
+
+        egui::Window::new("Remove item?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "That's the last of your {}, it will be removed from your inventory.",
+                    item.name
+                ));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(RichText::new("Remove").color(theme::palette(ui.ctx()).negative))
+                        .clicked()
+                    {
+                        match removal {
+                            Removal::Use(count) => commands.add(UseItem::new(idx, count)),
+                            Removal::Decrement => commands.add(SetItemCount {
+                                item_idx: idx,
+                                count: 0,
+                            }),
+                        }
+                        self.confirm_removal = None;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_removal = None;
+                    }
+                });
+            });
+    }
 }
 
 impl DndTabImpl for Items {
     fn ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
         egui::CentralPanel::default().show_inside(ui, |ui| {
             ui.heading("Items");
-            for (idx, item) in state.character.items.iter().enumerate() {
-                ItemWidget::new(idx, item.clone(), &mut self.use_num, commands).ui(ui);
+            attunement_ui(ui, state);
+            view_options_ui(ui, state, commands);
+            ui.separator();
+
+            let items = &state.character.items;
+            let view = state.inventory.view(&state.character.character.name);
+            let can_attune = state.ruleset.can_attune(state.character.attuned_count());
+            for idx in view.order(items) {
+                let item = &items[idx];
+                ItemWidget::new(
+                    idx,
+                    item.clone(),
+                    &mut self.use_num,
+                    &mut self.info_item,
+                    &mut self.confirm_removal,
+                    can_attune,
+                    commands,
+                )
+                .can_use(state.can_act())
+                .columns(view)
+                .ui(ui);
                 ui.separator();
             }
+
+            total_weight_ui(ui, items);
         });
+
+        let info_item = self
+            .info_item
+            .and_then(|id| state.character.items.iter().find(|x| x.id == id));
+
+        if let Some(item) = info_item {
+            let mut open = true;
+            ItemInfo::new(item).show(ui.ctx(), &mut open);
+
+            if !open {
+                self.info_item = None;
+            }
+        }
+
+        self.confirm_removal_ui(ui, state, commands);
     }
 
     fn title(&self) -> String {
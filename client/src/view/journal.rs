@@ -0,0 +1,327 @@
+use common::{
+    message::{replay_journal, JournalChange, JournalEntry},
+    BoardInfo, MAIN_BOARD,
+};
+use egui::{Rounding, ScrollArea, Sense, Slider, Stroke};
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        board::BoardState,
+        journal::commands::{BranchBoard, RequestJournal},
+        theme,
+    },
+};
+
+use super::{annotations::paint_annotations, walls::paint_walls, Board, DndTabImpl};
+
+/// Scrubs through every board change made this session, to find out who did
+/// what or to branch the board from an earlier point
+pub struct BoardHistory {
+    /// Number of changes applied to the preview
+    position: usize,
+    filter: String,
+    /// What each change did, worked out against the board as it was at the time
+    descriptions: Vec<String>,
+    /// Board after `position` changes
+    preview: Option<(usize, BoardState)>,
+    confirm_branch: Option<usize>,
+    /// Where the last page we asked for starts
+    requested: Option<usize>,
+    origin: Pos2,
+    zoom: f32,
+}
+
+impl Default for BoardHistory {
+    fn default() -> Self {
+        Self {
+            position: 0,
+            filter: String::new(),
+            descriptions: Vec::new(),
+            preview: None,
+            confirm_branch: None,
+            requested: None,
+            origin: Pos2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+/// The board after the first `count` entries, the same way the server rebuilds it
+fn board_at(entries: &[JournalEntry], count: usize) -> BoardState {
+    let mut board = BoardState::default();
+    board.boards.insert(MAIN_BOARD, BoardInfo::main());
+    for msg in replay_journal(entries, count) {
+        board.process(&DndMessage::BoardMessage(msg.clone()));
+    }
+
+    // Pieces would slide into place from wherever the replay last had them
+    for piece in board.players.values_mut() {
+        piece.animation = None;
+    }
+    board
+}
+
+fn describe_all(entries: &[JournalEntry]) -> Vec<String> {
+    let mut board = board_at(entries, 0);
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| match &entry.change {
+            JournalChange::Board(msg) => {
+                let text = describe(msg, &board);
+                board.process(&DndMessage::BoardMessage(msg.clone()));
+                text
+            }
+            JournalChange::Branch(point) => {
+                board = board_at(entries, (*point).min(i));
+                format!("rewound the board to change {point}, branch from change {i} to undo it")
+            }
+        })
+        .collect()
+}
+
+fn quoted(name: &str) -> String {
+    match name {
+        "" => "an unnamed piece".to_owned(),
+        name => format!("'{name}'"),
+    }
+}
+
+fn piece_name(board: &BoardState, uuid: &Uuid) -> String {
+    board
+        .players
+        .get(uuid)
+        .map_or_else(|| "a missing piece".to_owned(), |x| quoted(&x.name))
+}
+
+fn board_name(board: &BoardState, uuid: &Uuid) -> String {
+    board
+        .boards
+        .get(uuid)
+        .map_or_else(|| "a missing board".to_owned(), |x| format!("'{}'", x.name))
+}
+
+fn describe(msg: &BoardMessage, board: &BoardState) -> String {
+    match msg {
+        BoardMessage::AddPlayerPiece(_, piece) => format!("added {}", quoted(&piece.name)),
+        BoardMessage::UpdatePlayerPiece(uuid, piece) => match board.players.get(uuid) {
+            Some(old) if old.board != piece.board => format!(
+                "moved {} to {}",
+                quoted(&old.name),
+                board_name(board, &piece.board)
+            ),
+            _ => format!("edited {}", piece_name(board, uuid)),
+        },
+        BoardMessage::UpdatePlayerLocation(uuid, _) => format!("moved {}", piece_name(board, uuid)),
+        BoardMessage::DeletePlayerPiece(uuid) => format!("deleted {}", piece_name(board, uuid)),
+        BoardMessage::AddAnnotation(..) => "drew an annotation".to_owned(),
+        BoardMessage::DeleteAnnotation(_) => "erased an annotation".to_owned(),
+        BoardMessage::ClearAnnotations(..) => "cleared the annotations".to_owned(),
+        BoardMessage::SetAmbience(_) => "changed the ambience".to_owned(),
+        BoardMessage::SetCampaignDate(date) => format!("set the date to {date}"),
+        BoardMessage::SetGrid(_) => "changed the grid".to_owned(),
+        BoardMessage::AdvanceTime(minutes) => format!("advanced time by {minutes} minutes"),
+        BoardMessage::CreateBoard(_, name) => format!("created the board '{name}'"),
+        BoardMessage::DeleteBoard(uuid) => format!("deleted the board {}", board_name(board, uuid)),
+        BoardMessage::SetActiveBoard(uuid) => {
+            format!("switched everyone to {}", board_name(board, uuid))
+        }
+        BoardMessage::AddPortal(..) => "added a portal".to_owned(),
+        BoardMessage::DeletePortal(..) => "removed a portal".to_owned(),
+        BoardMessage::AddWall(..) => "added a wall".to_owned(),
+        BoardMessage::DeleteWall(..) => "removed a wall".to_owned(),
+        BoardMessage::SetDoorOpen(_, _, true) => "opened a door".to_owned(),
+        BoardMessage::SetDoorOpen(_, _, false) => "closed a door".to_owned(),
+        BoardMessage::GroupPieces(..) => "grouped pieces".to_owned(),
+        BoardMessage::Ungroup(_) => "ungrouped pieces".to_owned(),
+        BoardMessage::HoldPiece(uuid) => format!("started editing {}", piece_name(board, uuid)),
+        BoardMessage::ReleasePiece(uuid) => format!("stopped editing {}", piece_name(board, uuid)),
+        BoardMessage::SetHolder(..) => "changed who is editing a piece".to_owned(),
+    }
+}
+
+impl BoardHistory {
+    const ZOOM_FACTOR: f32 = 0.01;
+    const MAX_ZOOM: f32 = 10.0;
+    const MIN_ZOOM: f32 = 0.5;
+
+    fn entry_list(&mut self, ui: &mut egui::Ui, entries: &[JournalEntry]) {
+        let filter = self.filter.to_lowercase();
+        for (i, (entry, description)) in entries.iter().zip(&self.descriptions).enumerate() {
+            if !filter.is_empty()
+                && !entry.user.to_lowercase().contains(&filter)
+                && !description.to_lowercase().contains(&filter)
+            {
+                continue;
+            }
+
+            let time = entry.at.with_timezone(&chrono::Local).format("%H:%M:%S");
+            let text = format!("{}. {time} {} {description}", i + 1, entry.user);
+            if ui.selectable_label(self.position == i + 1, text).clicked() {
+                self.position = i + 1;
+            }
+        }
+
+        if entries.is_empty() {
+            ui.weak("Nothing has changed on the board yet");
+        }
+    }
+
+    fn preview_ui(&mut self, ui: &mut egui::Ui, entries: &[JournalEntry]) {
+        if self.preview.as_ref().map(|(at, _)| *at) != Some(self.position) {
+            self.preview = Some((self.position, board_at(entries, self.position)));
+        }
+
+        let (response, painter) =
+            ui.allocate_painter(ui.available_size_before_wrap(), Sense::drag());
+
+        if response.hovered() {
+            self.zoom /= (ui.input(|i| i.smooth_scroll_delta.y) * Self::ZOOM_FACTOR) + 1.0;
+            self.zoom = self.zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        }
+
+        let dims = response.rect.square_proportions() * self.zoom;
+        let to_screen =
+            RectTransform::from_to(Rect::from_center_size(self.origin, dims), response.rect);
+
+        if response.dragged() {
+            let from_screen = to_screen.inverse();
+            self.origin = from_screen * (to_screen * self.origin - response.drag_delta());
+        }
+
+        let Some((_, board)) = &self.preview else {
+            return;
+        };
+
+        Board::paint_grid(self.origin, dims, &painter, &to_screen, &board.grid);
+        paint_walls(&painter, to_screen, board);
+
+        for (_, player) in board
+            .active_players()
+            .sorted_by_key(|(_, x)| x.sorting_layer)
+        {
+            player.draw_shape(ui, &painter, to_screen);
+        }
+
+        paint_annotations(&painter, to_screen, board, |_| true);
+
+        // Outline the piece the last applied change was about
+        let changed = self
+            .position
+            .checked_sub(1)
+            .and_then(|i| entries.get(i))
+            .and_then(|x| match &x.change {
+                JournalChange::Board(msg) => msg.piece_id(),
+                JournalChange::Branch(_) => None,
+            })
+            .and_then(|id| board.players.get(&id));
+        if let Some(piece) = changed {
+            painter.rect_stroke(
+                to_screen.transform_rect(piece.display_rect()),
+                Rounding::ZERO,
+                Stroke::new(3.0, theme::palette(ui.ctx()).caution),
+            );
+        }
+    }
+
+    fn confirm_ui(&mut self, ui: &mut egui::Ui, commands: &mut CommandQueue) {
+        let Some(point) = self.confirm_branch else {
+            return;
+        };
+
+        egui::Window::new("Branch the board?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "The board goes back to how it was after change {point} for everyone."
+                ));
+                ui.label("Later changes stay in the history in case you change your mind.");
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(RichText::new("Branch").color(theme::palette(ui.ctx()).negative))
+                        .clicked()
+                    {
+                        commands.add(BranchBoard(point));
+                        self.confirm_branch = None;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_branch = None;
+                    }
+                });
+            });
+    }
+}
+
+impl DndTabImpl for BoardHistory {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can look through the board history");
+            return;
+        }
+
+        // Keep asking for pages until we have everything
+        let have = state.journal.entries.len();
+        if self.requested.is_none() || (state.journal.is_partial() && self.requested != Some(have))
+        {
+            commands.add(RequestJournal(have));
+            self.requested = Some(have);
+        }
+
+        let entries = &state.journal.entries;
+        if self.descriptions.len() != entries.len() {
+            // Keep following the latest change if that's where we were
+            if self.position == self.descriptions.len() {
+                self.position = entries.len();
+            }
+            self.position = self.position.min(entries.len());
+            self.descriptions = describe_all(entries);
+            self.preview = None;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                commands.add(RequestJournal(entries.len()));
+                self.requested = Some(entries.len());
+            }
+            if state.journal.is_partial() {
+                ui.spinner();
+            }
+            ui.add(Slider::new(&mut self.position, 0..=entries.len()).text("changes"));
+            if ui
+                .add_enabled(
+                    self.position < entries.len(),
+                    egui::Button::new("Branch from here"),
+                )
+                .on_hover_text("Put the board back to how it was at this point")
+                .clicked()
+            {
+                self.confirm_branch = Some(self.position);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Filter: ");
+            ui.text_edit_singleline(&mut self.filter)
+                .on_hover_text("Only list changes by this user or mentioning this piece");
+        });
+        ui.separator();
+
+        egui::SidePanel::left("board_history_list")
+            .resizable(true)
+            .show_inside(ui, |ui| {
+                ScrollArea::vertical().show(ui, |ui| self.entry_list(ui, entries));
+            });
+
+        self.preview_ui(ui, entries);
+        self.confirm_ui(ui, commands);
+    }
+
+    fn title(&self) -> String {
+        "Board History".to_owned()
+    }
+}
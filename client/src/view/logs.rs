@@ -0,0 +1,90 @@
+use egui::{ComboBox, ScrollArea};
+use log::Level;
+
+use crate::{
+    listener::CommandQueue,
+    log_buffer,
+    prelude::*,
+    state::theme::{self, Palette},
+};
+
+use super::DndTabImpl;
+
+/// Recent client log entries, for tracking down sync problems mid-session
+pub struct Logs {
+    level: Level,
+    search: String,
+}
+
+impl Default for Logs {
+    fn default() -> Self {
+        Self {
+            level: Level::Warn,
+            search: String::new(),
+        }
+    }
+}
+
+fn level_color(level: Level, palette: &Palette) -> Color32 {
+    match level {
+        Level::Error => palette.negative,
+        Level::Warn => palette.caution,
+        _ => Color32::GRAY,
+    }
+}
+
+impl DndTabImpl for Logs {
+    fn ui(&mut self, ui: &mut egui::Ui, _state: &DndState, _commands: &mut CommandQueue) {
+        ui.horizontal(|ui| {
+            ComboBox::from_id_salt("log_level")
+                .selected_text(self.level.to_string())
+                .show_ui(ui, |ui| {
+                    for level in [Level::Error, Level::Warn, Level::Info] {
+                        ui.selectable_value(&mut self.level, level, level.to_string());
+                    }
+                });
+
+            ui.label(egui_phosphor::regular::MAGNIFYING_GLASS);
+            ui.text_edit_singleline(&mut self.search)
+                .on_hover_text("Matches the message or where it came from");
+
+            if ui.button("Copy").clicked() {
+                ui.ctx().copy_text(log_buffer::recent());
+            }
+        });
+        ui.separator();
+
+        let search = self.search.to_lowercase();
+        let entries: Vec<_> = log_buffer::entries()
+            .into_iter()
+            .filter(|x| x.level <= self.level)
+            .filter(|x| {
+                x.message.to_lowercase().contains(&search)
+                    || x.target.to_lowercase().contains(&search)
+            })
+            .collect();
+
+        ScrollArea::vertical()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for entry in entries.iter() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.weak(&entry.time);
+                        let color = level_color(entry.level, &theme::palette(ui.ctx()));
+                        ui.colored_label(color, entry.level.to_string());
+                        ui.weak(&entry.target);
+                        ui.label(&entry.message);
+                    });
+                }
+
+                if entries.is_empty() {
+                    ui.weak("Nothing logged yet");
+                }
+            });
+    }
+
+    fn title(&self) -> String {
+        "Logs".to_owned()
+    }
+}
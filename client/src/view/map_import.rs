@@ -0,0 +1,366 @@
+use std::sync::mpsc::{channel, Receiver};
+
+use common::{GridKind, GridSettings, PieceVisibility, SortingLayer};
+use egui::{load::BytesPoll, DragValue, Image, Sense, Stroke};
+use image::GrayImage;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        board::commands::{AddPiece, PieceParams, SetGrid},
+        theme,
+    },
+};
+
+/// Squares smaller than this are more likely the texture of the map than its grid
+const MIN_SQUARE_PX: usize = 10;
+
+/// How much a grid has to stand out before we trust it, 0 to 1
+const MIN_GRID_STRENGTH: f32 = 0.1;
+
+/// Width of the preview the calibration clicks are made on
+const PREVIEW_WIDTH: f32 = 360.0;
+
+struct MapImage {
+    width: u32,
+    height: u32,
+    /// Pixels per square, if the image has a grid we could find
+    detected: Option<f32>,
+}
+
+/// Period the signal repeats at along with how strongly it does, from its
+/// autocorrelation. Peaks that are a multiple of the grid score lower since
+/// less of the signal overlaps, so the square itself wins
+fn strongest_period(signal: &[f32]) -> Option<(f32, f32)> {
+    let mean = signal.iter().sum::<f32>() / signal.len() as f32;
+    let centered = signal.iter().map(|x| x - mean).collect::<Vec<_>>();
+    let energy = centered.iter().map(|x| x * x).sum::<f32>();
+    if energy <= f32::EPSILON {
+        return None;
+    }
+
+    let correlation = |lag: usize| {
+        centered
+            .iter()
+            .zip(&centered[lag..])
+            .map(|(a, b)| a * b)
+            .sum::<f32>()
+            / energy
+    };
+
+    // A handful of squares have to fit for the repeat to mean anything
+    let max_lag = signal.len() / 4;
+    if max_lag <= MIN_SQUARE_PX {
+        return None;
+    }
+    let scores = (0..=max_lag + 1).map(correlation).collect::<Vec<_>>();
+
+    let (lag, strength) = (MIN_SQUARE_PX..=max_lag)
+        .filter(|&lag| scores[lag] >= scores[lag - 1] && scores[lag] >= scores[lag + 1])
+        .map(|lag| (lag, scores[lag]))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    // Fit a parabola through the peak for a spacing between whole pixels
+    let (before, after) = (scores[lag - 1], scores[lag + 1]);
+    let curve = before - 2.0 * strength + after;
+    let shift = if curve.abs() > f32::EPSILON {
+        (0.5 * (before - after) / curve).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+
+    Some((lag as f32 + shift, strength))
+}
+
+/// Grid lines are columns and rows with a lot of contrast that repeat every
+/// square, so the contrast summed along each axis repeats at the grid spacing
+fn detect_grid_spacing(image: &GrayImage) -> Option<f32> {
+    let (width, height) = image.dimensions();
+    let mut columns = vec![0.0; width as usize];
+    let mut rows = vec![0.0; height as usize];
+
+    for y in 1..height {
+        for x in 1..width {
+            let pixel = image.get_pixel(x, y)[0] as f32;
+            columns[x as usize] += (pixel - image.get_pixel(x - 1, y)[0] as f32).abs();
+            rows[y as usize] += (pixel - image.get_pixel(x, y - 1)[0] as f32).abs();
+        }
+    }
+
+    let found = [strongest_period(&columns), strongest_period(&rows)];
+    let (spacing, strength) = match found {
+        // Squares should match both ways, otherwise go with whichever is clearer
+        [Some(x), Some(y)] if (x.0 - y.0).abs() <= x.0.max(y.0) * 0.05 => {
+            ((x.0 + y.0) / 2.0, x.1.max(y.1))
+        }
+        [Some(x), Some(y)] => {
+            if x.1 >= y.1 {
+                x
+            } else {
+                y
+            }
+        }
+        [x, y] => x.or(y)?,
+    };
+
+    (strength >= MIN_GRID_STRENGTH).then_some(spacing)
+}
+
+fn analyze(bytes: &[u8]) -> Result<MapImage, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    Ok(MapImage {
+        width: image.width(),
+        height: image.height(),
+        detected: detect_grid_spacing(&image.to_luma8()),
+    })
+}
+
+/// Walks the GM through adding a map: load the image, work out how many
+/// pixels make up a square, then add it as a locked piece on the bottom layer
+/// sized so its squares match the board's
+#[derive(Default)]
+pub struct MapImport {
+    open: bool,
+    url: String,
+    name: String,
+    /// Image being downloaded or analyzed
+    loading: Option<String>,
+    analysis: Option<Receiver<Result<MapImage, String>>>,
+    image: Option<MapImage>,
+    error: Option<String>,
+    /// Pixels per square on the map
+    square_px: f32,
+    /// Clicks on the preview in image pixels while calibrating
+    calibration: Option<Vec<Pos2>>,
+    /// Squares between the two calibration clicks
+    calibration_squares: u32,
+    show_grid: bool,
+}
+
+impl MapImport {
+    pub fn open(&mut self) {
+        *self = Self {
+            open: true,
+            calibration_squares: 1,
+            show_grid: true,
+            ..Default::default()
+        };
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.analysis {
+            match rx.try_recv() {
+                Ok(Ok(image)) => {
+                    self.square_px = image
+                        .detected
+                        .unwrap_or(image.width.max(image.height) as f32 / 20.0);
+                    self.image = Some(image);
+                    self.analysis = None;
+                }
+                Ok(Err(e)) => {
+                    self.error = Some(e);
+                    self.analysis = None;
+                }
+                Err(_) => ctx.request_repaint(),
+            }
+            return;
+        }
+
+        let Some(url) = self.loading.clone() else {
+            return;
+        };
+
+        match ctx.try_load_bytes(&url) {
+            Ok(BytesPoll::Pending { .. }) => ctx.request_repaint(),
+            Ok(BytesPoll::Ready { bytes, .. }) => {
+                let bytes = bytes.to_vec();
+                let (tx, rx) = channel();
+                // Browsers have no threads, the page waits on the analysis instead
+                #[cfg(target_arch = "wasm32")]
+                let _ = tx.send(analyze(&bytes));
+                #[cfg(not(target_arch = "wasm32"))]
+                std::thread::spawn(move || {
+                    let _ = tx.send(analyze(&bytes));
+                });
+
+                self.analysis = Some(rx);
+                self.loading = None;
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                self.loading = None;
+            }
+        }
+    }
+
+    fn preview_ui(&mut self, ui: &mut Ui, image: &MapImage) {
+        let response = ui.add(
+            Image::new(self.url.as_str())
+                .max_width(PREVIEW_WIDTH)
+                .sense(Sense::click()),
+        );
+
+        let Some(clicks) = &mut self.calibration else {
+            return;
+        };
+        // Still loading
+        if response.rect.width() < 1.0 {
+            return;
+        }
+        let scale = image.width as f32 / response.rect.width();
+
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        {
+            clicks.push(((pos - response.rect.min) * scale).to_pos2());
+        }
+
+        let painter = ui.painter_at(response.rect);
+        let stroke = Stroke::new(2.0, theme::palette(ui.ctx()).selection);
+        for click in clicks.iter() {
+            let pos = response.rect.min + click.to_vec2() / scale;
+            painter.circle_stroke(pos, 4.0, stroke);
+        }
+
+        if let [first, second] = clicks[..] {
+            let squares = self.calibration_squares.max(1) as f32;
+            self.square_px = ((second - first).abs().max_elem() / squares).max(1.0);
+            self.calibration = None;
+        }
+    }
+
+    fn grid_ui(&mut self, ui: &mut Ui, image: &MapImage) {
+        ui.horizontal(|ui| {
+            ui.label("Pixels per square");
+            DragValue::new(&mut self.square_px)
+                .range(MIN_SQUARE_PX as f32..=1000.0)
+                .speed(0.1)
+                .ui(ui);
+
+            match image.detected {
+                Some(px) => ui.weak(format!("found a {px:.1} px grid")),
+                None => ui.weak("no grid found"),
+            };
+        });
+
+        ui.horizontal(|ui| {
+            if self.calibration.is_some() {
+                ui.label("Click two points on the map");
+                if ui.button("Cancel").clicked() {
+                    self.calibration = None;
+                }
+            } else if ui.button("Calibrate").clicked() {
+                self.calibration = Some(Vec::new());
+            }
+
+            DragValue::new(&mut self.calibration_squares)
+                .range(1..=50)
+                .suffix(" squares apart")
+                .ui(ui);
+        });
+
+        let squares = Vec2::new(image.width as f32, image.height as f32) / self.square_px;
+        ui.weak(format!(
+            "{}x{} px, about {:.1} by {:.1} squares",
+            image.width, image.height, squares.x, squares.y
+        ));
+    }
+
+    fn create(&mut self, state: &DndState, image: &MapImage, commands: &mut CommandQueue) {
+        let name = match self.name.trim() {
+            "" => "Map".to_owned(),
+            name => name.to_owned(),
+        };
+
+        commands.add(AddPiece {
+            params: PieceParams {
+                name,
+                pos: Pos2::ZERO,
+                size: Vec2::new(image.width as f32, image.height as f32) / self.square_px,
+                url: Some(self.url.trim().to_owned()),
+                visibility: PieceVisibility::default(),
+                owners: vec![],
+                sorting_layer: SortingLayer(0),
+                locked: true,
+                vision: None,
+                link_stats_to: None,
+                label: None,
+            },
+        });
+
+        if self.show_grid && !state.board.grid.visible {
+            commands.add(SetGrid(GridSettings {
+                kind: GridKind::Square,
+                visible: true,
+                ..state.board.grid
+            }));
+        }
+
+        self.open = false;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+        if !self.open {
+            return;
+        }
+        self.poll(ctx);
+
+        let mut open = self.open;
+        egui::Window::new("Import Map")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Image URL");
+                    ui.text_edit_singleline(&mut self.url);
+
+                    let busy = self.loading.is_some() || self.analysis.is_some();
+                    let url = self.url.trim();
+                    if ui
+                        .add_enabled(!url.is_empty() && !busy, egui::Button::new("Load"))
+                        .clicked()
+                    {
+                        self.loading = Some(url.to_owned());
+                        self.image = None;
+                        self.error = None;
+                        self.calibration = None;
+                    }
+                    if busy {
+                        ui.spinner();
+                    }
+                });
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(theme::palette(ui.ctx()).negative, error);
+                }
+
+                let Some(image) = self.image.take() else {
+                    ui.weak(
+                        "Tokens line up best when the map's grid starts at its top left corner",
+                    );
+                    return;
+                };
+
+                self.preview_ui(ui, &image);
+                ui.separator();
+                self.grid_ui(ui, &image);
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut self.name);
+                });
+                ui.checkbox(&mut self.show_grid, "Show the board's grid over it");
+
+                if ui.button("Add to Board").clicked() {
+                    self.create(state, &image, commands);
+                }
+
+                self.image = Some(image);
+            });
+
+        self.open &= open;
+    }
+}
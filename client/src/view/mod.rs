@@ -1,22 +1,84 @@
 mod abilities;
+mod ambience;
+mod annotations;
+mod area_damage;
 mod board;
+mod board_objects;
+mod calendar;
 mod character;
+mod character_creator;
 mod chat;
+mod chat_input;
+mod command_palette;
+#[cfg(feature = "compendium")]
+mod compendium;
+mod diagnostics;
+mod dice_tray;
+mod effects;
+mod encounter;
+mod floating_numbers;
+mod handouts;
+mod import;
 mod items;
+mod journal;
+mod logs;
+mod map_import;
 pub mod multi_select;
+mod npc_generator;
+mod party_overview;
+mod piece_search;
+mod piece_templates;
+mod players;
+mod presentation;
+mod report;
+mod roll_tables;
+mod search;
+mod session_clock;
 mod settings;
+mod sheets;
+mod snapshots;
+mod soundboard;
+mod stash;
+mod stats;
+mod statuses;
+pub mod toasts;
+mod trade;
+mod vision;
+mod walls;
 
 use std::sync::mpsc::Receiver;
 
 pub use abilities::*;
 pub use board::*;
+pub use board_objects::*;
 pub use character::*;
+pub use character_creator::*;
 pub use chat::*;
+pub use command_palette::*;
 use common::message::DndMessage;
+#[cfg(feature = "compendium")]
+pub use compendium::*;
+pub use diagnostics::*;
 use egui::Color32;
 use egui_dock::{NodeIndex, SurfaceIndex};
+pub use encounter::*;
+pub use handouts::*;
+pub use import::*;
 pub use items::*;
-use message_io::events::EventSender;
+pub use journal::*;
+pub use logs::*;
+pub use npc_generator::*;
+pub use party_overview::*;
+pub use players::*;
+pub use presentation::*;
+pub use report::*;
+pub use roll_tables::*;
+pub use search::*;
+pub use sheets::*;
+pub use snapshots::*;
+pub use soundboard::*;
+pub use stash::*;
+pub use stats::*;
 
 use crate::{
     listener::{CommandQueue, Signal},
@@ -54,6 +116,61 @@ impl DndTab {
     }
 }
 
+/// A tab that can be opened from the add menu or the command palette
+pub struct TabKind {
+    pub title: &'static str,
+    new: fn() -> Box<dyn DndTabImpl>,
+}
+
+impl TabKind {
+    fn of<T: DndTabImpl + Default + 'static>(title: &'static str) -> Self {
+        Self {
+            title,
+            new: || Box::new(T::default()),
+        }
+    }
+
+    pub fn open(&self, surface: SurfaceIndex, node: NodeIndex) -> DndTab {
+        DndTab {
+            kind: (self.new)(),
+            surface,
+            node,
+        }
+    }
+}
+
+/// Every tab, in the order the add menu lists them
+pub fn tab_kinds() -> Vec<TabKind> {
+    let mut kinds = vec![
+        TabKind::of::<Chat>("Chat"),
+        TabKind::of::<Board>("Game Board"),
+        TabKind::of::<BoardObjects>("Board Objects"),
+        TabKind::of::<Character>("Character"),
+        TabKind::of::<CharacterCreator>("Create Character"),
+        TabKind::of::<Abilities>("Abilities"),
+        TabKind::of::<Items>("Items"),
+        TabKind::of::<Handouts>("Handouts"),
+        TabKind::of::<Stash>("Party Stash"),
+        TabKind::of::<Encounter>("Encounter"),
+        TabKind::of::<RollTables>("Roll Tables"),
+        TabKind::of::<NpcGenerator>("NPC Generator"),
+        TabKind::of::<Soundboard>("Soundboard"),
+        TabKind::of::<Import>("Import"),
+    ];
+    #[cfg(feature = "compendium")]
+    kinds.push(TabKind::of::<Compendium>("Compendium"));
+    kinds.extend([
+        TabKind::of::<Players>("Players"),
+        TabKind::of::<PartyOverview>("Party Overview"),
+        TabKind::of::<Stats>("Stats"),
+        TabKind::of::<Snapshots>("Snapshots"),
+        TabKind::of::<BoardHistory>("Board History"),
+        TabKind::of::<Logs>("Logs"),
+        TabKind::of::<Settings>("Settings"),
+    ]);
+    kinds
+}
+
 pub struct TabViewer<'a> {
     pub added_nodes: &'a mut Vec<DndTab>,
     pub state: &'a DndState,
@@ -76,30 +193,10 @@ impl egui_dock::TabViewer for TabViewer<'_> {
         ui.set_min_width(120.0);
         ui.style_mut().visuals.button_frame = false;
 
-        if ui.button("Chat").clicked() {
-            self.added_nodes
-                .push(DndTab::from_tab(Chat::default(), surface, node))
-        }
-        if ui.button("Game Board").clicked() {
-            self.added_nodes
-                .push(DndTab::from_tab(Board::default(), surface, node))
-        }
-        if ui.button("Character").clicked() {
-            self.added_nodes
-                .push(DndTab::from_tab(Character::default(), surface, node))
-        }
-        if ui.button("Abilities").clicked() {
-            self.added_nodes
-                .push(DndTab::from_tab(Abilities::default(), surface, node))
-        }
-        if ui.button("Items").clicked() {
-            self.added_nodes
-                .push(DndTab::from_tab(Items::default(), surface, node))
-        }
-
-        if ui.button("Settings").clicked() {
-            self.added_nodes
-                .push(DndTab::from_tab(Settings::default(), surface, node))
+        for kind in tab_kinds() {
+            if ui.button(kind.title).clicked() {
+                self.added_nodes.push(kind.open(surface, node))
+            }
         }
     }
 }
@@ -0,0 +1,142 @@
+use egui::{ComboBox, Grid};
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    npc_generator::{
+        random_name, random_occupation, random_personality, random_quirk, Culture, NpcStub,
+    },
+    prelude::*,
+    state::handouts::commands::CreateHandout,
+};
+
+use super::DndTabImpl;
+
+/// Names picked from to swap the stub's name for
+const NAME_CHOICES: usize = 5;
+
+/// Makes up NPCs on the spot for the GM, who can keep the ones worth keeping
+pub struct NpcGenerator {
+    culture: Culture,
+    stub: NpcStub,
+    other_names: Vec<String>,
+    /// Name of the last NPC saved, until the next one is rolled
+    saved: Option<String>,
+}
+
+fn other_names(culture: Culture) -> Vec<String> {
+    (0..NAME_CHOICES).map(|_| random_name(culture)).collect()
+}
+
+impl Default for NpcGenerator {
+    fn default() -> Self {
+        Self {
+            culture: Culture::Human,
+            stub: NpcStub::generate(Culture::Human),
+            other_names: other_names(Culture::Human),
+            saved: None,
+        }
+    }
+}
+
+impl NpcGenerator {
+    fn reroll(&mut self) {
+        self.stub = NpcStub::generate(self.culture);
+        self.other_names = other_names(self.culture);
+        self.saved = None;
+    }
+
+    /// A row of the stub with a button to roll just that part again
+    fn field(ui: &mut Ui, label: &str, value: &mut String, reroll: impl FnOnce() -> String) {
+        ui.strong(label);
+        ui.text_edit_singleline(value);
+        if ui
+            .small_button(egui_phosphor::regular::DICE_FIVE)
+            .on_hover_text(format!("New {}", label.to_lowercase()))
+            .clicked()
+        {
+            *value = reroll();
+        }
+        ui.end_row();
+    }
+
+    fn stub_ui(&mut self, ui: &mut Ui) {
+        let culture = self.stub.culture;
+        Grid::new("npc_stub").num_columns(3).show(ui, |ui| {
+            Self::field(ui, "Name", &mut self.stub.name, || random_name(culture));
+            Self::field(
+                ui,
+                "Occupation",
+                &mut self.stub.occupation,
+                random_occupation,
+            );
+            Self::field(
+                ui,
+                "Personality",
+                &mut self.stub.personality,
+                random_personality,
+            );
+            Self::field(ui, "Quirk", &mut self.stub.quirk, random_quirk);
+        });
+
+        ui.horizontal_wrapped(|ui| {
+            ui.weak("Or call them");
+            for name in self.other_names.iter() {
+                if ui.link(name).clicked() {
+                    self.stub.name = name.clone();
+                }
+            }
+        });
+
+        ui.add(
+            egui::TextEdit::multiline(&mut self.stub.notes)
+                .hint_text("Notes, ie. what they told the party")
+                .desired_rows(3),
+        );
+    }
+}
+
+impl DndTabImpl for NpcGenerator {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can make up NPCs");
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            let before = self.culture;
+            ComboBox::from_id_salt("npc_culture")
+                .selected_text(self.culture.to_string())
+                .show_ui(ui, |ui| {
+                    for culture in Culture::ALL {
+                        ui.selectable_value(&mut self.culture, culture, culture.to_string());
+                    }
+                });
+            if ui.button("Generate").clicked() || self.culture != before {
+                self.reroll();
+            }
+        });
+        ui.separator();
+
+        self.stub_ui(ui);
+
+        ui.horizontal(|ui| {
+            let name = self.stub.name.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save NPC"))
+                .on_hover_text("Keeps them as a hidden handout")
+                .clicked()
+            {
+                commands.add(CreateHandout(Uuid::new_v4(), self.stub.to_handout()));
+                self.saved = Some(name.to_owned());
+            }
+            if let Some(saved) = &self.saved {
+                ui.weak(format!("Saved {} to the handouts", saved));
+            }
+        });
+    }
+
+    fn title(&self) -> String {
+        "NPC Generator".to_owned()
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet};
+
+use egui::{Align, DragValue, Layout};
+use egui_extras::{Column, TableBuilder};
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        board::PlayerPiece,
+        character::{
+            commands::{AdjustHp, RefreshPartyMember},
+            CharacterState,
+        },
+        sheets::commands::OpenSheet,
+    },
+};
+
+use super::{statuses::StatusForm, DndTabImpl};
+
+/// A character, or a token on the board standing in for an NPC
+struct Row<'a> {
+    name: &'a str,
+    sheet: Option<&'a CharacterState>,
+    piece: Option<(Uuid, &'a PlayerPiece)>,
+    initiative: Option<i64>,
+}
+
+/// Every character and NPC on the board at a glance, so the GM can keep track
+/// of a fight without opening each sheet
+#[derive(Default)]
+pub struct PartyOverview {
+    by_initiative: bool,
+    /// Sheets we've asked the server for
+    requested: HashSet<String>,
+    /// HP being typed or dragged, only sent once the edit is finished
+    hp_drafts: HashMap<String, i32>,
+    status_form: StatusForm,
+}
+
+impl PartyOverview {
+    fn rows<'a>(&self, state: &'a DndState) -> Vec<Row<'a>> {
+        let characters = state.roster.iter().map(|name| Row {
+            name,
+            sheet: state.character_sheet(name),
+            piece: state
+                .board
+                .active_players()
+                .find(|(_, x)| x.stats_name() == name)
+                .map(|(id, x)| (*id, x)),
+            initiative: state.chat.initiative(name),
+        });
+
+        // Named tokens that don't stand in for a character, ie. spawned monsters
+        let npcs = state
+            .board
+            .active_players()
+            .filter(|(_, x)| x.label.is_none() && !x.name.is_empty())
+            .filter(|(_, x)| !state.roster.iter().any(|c| c == x.stats_name()))
+            .map(|(id, x)| Row {
+                name: &x.name,
+                sheet: None,
+                piece: Some((*id, x)),
+                initiative: state.chat.initiative(&x.name),
+            });
+
+        let rows = characters.chain(npcs);
+        if self.by_initiative {
+            // Highest first, anyone who hasn't rolled goes last
+            rows.sorted_by_key(|x| (std::cmp::Reverse(x.initiative), x.name))
+                .collect()
+        } else {
+            rows.sorted_by_key(|x| x.name).collect()
+        }
+    }
+
+    fn request_sheets(&mut self, state: &DndState, commands: &mut CommandQueue) {
+        for name in state.roster.iter() {
+            if state.character_sheet(name).is_none() && self.requested.insert(name.clone()) {
+                commands.add(RefreshPartyMember(name.clone()));
+            }
+        }
+    }
+
+    fn hp_ui(
+        &mut self,
+        ui: &mut Ui,
+        name: &str,
+        sheet: &CharacterState,
+        commands: &mut CommandQueue,
+    ) {
+        let current = sheet.character.curr_hp;
+        let mut hp = self.hp_drafts.get(name).copied().unwrap_or(current);
+
+        let response = DragValue::new(&mut hp)
+            .range(0..=sheet.character.max_hp.max(current))
+            .ui(ui);
+        ui.label(format!("/ {}", sheet.character.max_hp));
+
+        if response.changed() {
+            self.hp_drafts.insert(name.to_owned(), hp);
+        }
+
+        let editing = response.dragged() || response.has_focus();
+        if !editing {
+            if let Some(hp) = self.hp_drafts.remove(name).filter(|x| *x != current) {
+                commands.add(AdjustHp {
+                    character: name.to_owned(),
+                    amount: hp - current,
+                });
+            }
+        }
+    }
+
+    fn conditions_ui(
+        &mut self,
+        ui: &mut Ui,
+        id: Uuid,
+        piece: &PlayerPiece,
+        commands: &mut CommandQueue,
+    ) {
+        let text = if piece.statuses.is_empty() {
+            "None".to_owned()
+        } else {
+            piece.statuses.iter().map(|x| &x.icon).join(" ")
+        };
+
+        let response = ui
+            .menu_button(text, |ui| {
+                self.status_form.ui(ui, id, piece, commands);
+            })
+            .response;
+        if !piece.statuses.is_empty() {
+            response.on_hover_text(piece.statuses.iter().map(|x| &x.name).join(", "));
+        }
+    }
+}
+
+impl DndTabImpl for PartyOverview {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can see everyone's sheets at once");
+            return;
+        }
+
+        self.request_sheets(state, commands);
+
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                for name in state.roster.iter() {
+                    commands.add(RefreshPartyMember(name.clone()));
+                }
+            }
+            ui.checkbox(&mut self.by_initiative, "Sort by initiative");
+        });
+        ui.separator();
+
+        let rows = self.rows(state);
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .column(Column::auto())
+            .column(Column::auto().at_least(100.0))
+            .column(Column::auto().at_least(90.0))
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::remainder())
+            .cell_layout(Layout::left_to_right(Align::Center))
+            .header(20.0, |mut header| {
+                for title in ["Init", "Name", "HP", "AC", "Passive", "Conditions"] {
+                    header.col(|ui| {
+                        ui.strong(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for row in rows {
+                    body.row(20.0, |mut table_row| {
+                        table_row.col(|ui| {
+                            match row.initiative {
+                                Some(init) => ui.label(init.to_string()),
+                                None => ui.weak("-"),
+                            };
+                        });
+                        table_row.col(|ui| {
+                            if row.sheet.is_some() {
+                                if ui
+                                    .link(row.name)
+                                    .on_hover_text("Open their sheet")
+                                    .clicked()
+                                {
+                                    commands.add(OpenSheet(row.name.to_owned()));
+                                }
+                            } else {
+                                ui.label(row.name);
+                            }
+                        });
+                        table_row.col(|ui| match row.sheet {
+                            Some(sheet) => self.hp_ui(ui, row.name, sheet, commands),
+                            None => {
+                                ui.weak("-");
+                            }
+                        });
+                        table_row.col(|ui| match row.sheet {
+                            Some(sheet) => {
+                                ui.label(sheet.armor_class(&state.ruleset).to_string());
+                            }
+                            None => {
+                                ui.weak("-");
+                            }
+                        });
+                        table_row.col(|ui| match row.sheet {
+                            Some(sheet) => {
+                                ui.label(sheet.passive_perception(&state.ruleset).to_string());
+                            }
+                            None => {
+                                ui.weak("-");
+                            }
+                        });
+                        table_row.col(|ui| match row.piece {
+                            Some((id, piece)) => self.conditions_ui(ui, id, piece, commands),
+                            None => {
+                                ui.weak("No token").on_hover_text(
+                                    "Conditions go on the character's token on this board",
+                                );
+                            }
+                        });
+                    });
+                }
+            });
+    }
+
+    fn title(&self) -> String {
+        "Party Overview".to_owned()
+    }
+}
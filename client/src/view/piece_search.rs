@@ -0,0 +1,118 @@
+use egui::{Rounding, Stroke, TextEdit};
+use emath::RectTransform;
+use itertools::Itertools;
+use uuid::Uuid;
+use web_time::Instant;
+
+use crate::{
+    prelude::*,
+    state::{board::PlayerPiece, theme},
+};
+
+/// Finds pieces on the active board by name. Everything matching is outlined
+/// on the canvas until the box is cleared
+#[derive(Default)]
+pub struct PieceSearch {
+    query: String,
+}
+
+impl PieceSearch {
+    /// Pieces we can see with every word typed somewhere in their name
+    fn matches<'a>(&self, state: &'a DndState) -> Vec<(&'a Uuid, &'a PlayerPiece)> {
+        if self.query.trim().is_empty() {
+            return Vec::new();
+        }
+
+        let user = state.owned_user().name;
+        let query = self.query.to_lowercase();
+        state
+            .board
+            .active_players()
+            .filter(|(_, x)| state.is_gm() || x.visibility.includes(&user))
+            .filter(|(_, x)| {
+                let name = x.name.to_lowercase();
+                query.split_whitespace().all(|word| name.contains(word))
+            })
+            .sorted_by(|a, b| a.1.name.cmp(&b.1.name))
+            .collect()
+    }
+
+    /// The search box and its matches. Hands back the piece picked, Enter
+    /// picks the first
+    pub fn ui(&mut self, ui: &mut Ui, state: &DndState) -> Option<Uuid> {
+        let response = TextEdit::singleline(&mut self.query)
+            .hint_text("Find piece")
+            .desired_width(120.0)
+            .ui(ui);
+
+        let matches = self.matches(state);
+        let mut picked = None;
+        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            picked = matches.first().map(|(id, _)| **id);
+        }
+
+        if !self.query.trim().is_empty() {
+            ui.menu_button(format!("{} found", matches.len()), |ui| {
+                for (id, piece) in matches.iter() {
+                    if ui.button(&piece.name).clicked() {
+                        picked = Some(**id);
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+
+        picked
+    }
+
+    pub fn paint(&self, painter: &egui::Painter, to_screen: RectTransform, state: &DndState) {
+        let stroke = Stroke::new(2.0, theme::palette(painter.ctx()).accent);
+        for (_, piece) in self.matches(state) {
+            let rect = to_screen.transform_rect(piece.display_rect()).expand(6.0);
+            painter.rect_stroke(rect, Rounding::same(4.0), stroke);
+        }
+    }
+}
+
+/// The piece standing in for our character on the active board, or failing
+/// that the first one we own
+pub fn my_token(state: &DndState) -> Option<Uuid> {
+    let user = state.owned_user().name;
+    let pieces = || state.board.active_players();
+
+    pieces()
+        .find(|(_, x)| x.link_stats_to.as_ref() == Some(&user))
+        .or_else(|| pieces().find(|(_, x)| x.owners.contains(&user)))
+        .map(|(id, _)| *id)
+}
+
+/// Eases the board's view from where it was to somewhere else
+pub struct CameraMove {
+    from: (Pos2, f32),
+    to: (Pos2, f32),
+    started: Instant,
+}
+
+impl CameraMove {
+    const SECS: f32 = 0.4;
+
+    /// Between two (view center, zoom)s
+    pub fn new(from: (Pos2, f32), to: (Pos2, f32)) -> Self {
+        Self {
+            from,
+            to,
+            started: Instant::now(),
+        }
+    }
+
+    /// Where the view is this frame, and whether it's arrived
+    pub fn step(&self) -> ((Pos2, f32), bool) {
+        let t = (self.started.elapsed().as_secs_f32() / Self::SECS).min(1.0);
+        // Starts quick and slows into place
+        let eased = 1.0 - (1.0 - t).powi(3);
+
+        let origin = self.from.0.lerp(self.to.0, eased);
+        let zoom = emath::lerp(self.from.1..=self.to.1, eased);
+        ((origin, zoom), t >= 1.0)
+    }
+}
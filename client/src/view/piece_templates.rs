@@ -0,0 +1,182 @@
+use common::PieceTemplate;
+use egui::{Image, Key, ScrollArea, Vec2};
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::piece_templates::commands::{
+        DeleteTemplate, SaveAsTemplate, ShareTemplate, UnshareTemplate,
+    },
+};
+
+const THUMBNAIL_SIZE: f32 = 24.0;
+
+/// Saved pieces to stamp onto the board. Picking one arms it, then every
+/// click on the board places a copy until it's picked again or Esc is pressed
+#[derive(Default)]
+pub struct PiecePalette {
+    open: bool,
+    armed: Option<String>,
+    /// Name for the piece being saved from the context menu
+    new_name: String,
+}
+
+impl PiecePalette {
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// Template the next click on the board places
+    pub fn armed(&self) -> Option<&String> {
+        self.armed.as_ref()
+    }
+
+    /// Context menu entry saving the selected piece as one of our templates
+    pub fn save_menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        piece: Uuid,
+        state: &DndState,
+        commands: &mut CommandQueue,
+    ) {
+        ui.menu_button("Save as Template", |ui| {
+            if self.new_name.is_empty() {
+                if let Some(piece) = state.board.players.get(&piece) {
+                    self.new_name = piece.name.clone();
+                }
+            }
+
+            let name = self.new_name.trim().to_owned();
+            let replaces = state.piece_templates.saved.iter().any(|x| x.name == name);
+            let submitted = ui
+                .horizontal(|ui| {
+                    ui.label("name: ");
+                    ui.text_edit_singleline(&mut self.new_name).lost_focus()
+                        && ui.input(|i| i.key_pressed(Key::Enter))
+                })
+                .inner;
+
+            let label = if replaces { "Replace" } else { "Save" };
+            let button = ui.add_enabled(!name.is_empty(), egui::Button::new(label));
+            if (button.clicked() || submitted) && !name.is_empty() {
+                commands.add(SaveAsTemplate { piece, name });
+                self.new_name.clear();
+                self.open = true;
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn template_row(
+        &mut self,
+        ui: &mut egui::Ui,
+        template: &PieceTemplate,
+        state: &DndState,
+        commands: &mut CommandQueue,
+        ours: bool,
+    ) {
+        ui.horizontal(|ui| {
+            let piece = &template.piece;
+            let thumbnail = Vec2::splat(THUMBNAIL_SIZE);
+            match &piece.image_url {
+                Some(url) if piece.label.is_none() => {
+                    Image::new(url).fit_to_exact_size(thumbnail).ui(ui);
+                }
+                _ => {
+                    ui.allocate_exact_size(thumbnail, egui::Sense::hover());
+                }
+            }
+
+            let armed = self.armed.as_ref() == Some(&template.name);
+            if ui
+                .selectable_label(armed, &template.name)
+                .on_hover_text("Click the board to place copies")
+                .clicked()
+            {
+                self.armed = (!armed).then(|| template.name.clone());
+            }
+            ui.weak(format!("{}x{}", piece.size.x, piece.size.y));
+
+            ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                let shared = state.piece_templates.is_shared(&template.name);
+                if ours {
+                    if ui
+                        .small_button(egui_phosphor::regular::TRASH)
+                        .on_hover_text("Delete template")
+                        .clicked()
+                    {
+                        commands.add(DeleteTemplate(template.name.clone()));
+                    }
+                    if state.is_gm()
+                        && ui
+                            .small_button(egui_phosphor::regular::SHARE_NETWORK)
+                            .on_hover_text(if shared {
+                                "Update the shared copy"
+                            } else {
+                                "Share with the table"
+                            })
+                            .clicked()
+                    {
+                        commands.add(ShareTemplate(template.name.clone()));
+                    }
+                } else if state.is_gm()
+                    && ui
+                        .small_button(egui_phosphor::regular::X)
+                        .on_hover_text("Stop sharing")
+                        .clicked()
+                {
+                    commands.add(UnshareTemplate(template.name.clone()));
+                }
+            });
+        });
+    }
+
+    pub fn window(&mut self, ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+        if ctx.input(|i| i.key_pressed(Key::Escape)) {
+            self.armed = None;
+        }
+
+        // Armed templates can disappear when someone else unshares them
+        if let Some(name) = &self.armed {
+            if state.piece_templates.find(name).is_none() {
+                self.armed = None;
+            }
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Piece Templates")
+            .open(&mut open)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.strong("Mine");
+                    for template in state.piece_templates.saved.iter() {
+                        self.template_row(ui, template, state, commands, true);
+                    }
+                    if state.piece_templates.saved.is_empty() {
+                        ui.weak("Right click a piece and pick \"Save as Template\"");
+                    }
+
+                    ui.separator();
+                    ui.strong("Shared");
+                    for template in state.piece_templates.shared.iter() {
+                        self.template_row(ui, template, state, commands, false);
+                    }
+                    if state.piece_templates.shared.is_empty() {
+                        ui.weak("The GM hasn't shared any templates");
+                    }
+                });
+
+                if self.armed.is_some() {
+                    ui.separator();
+                    ui.weak("Click the board to place, Esc to stop");
+                }
+            });
+
+        self.open = open;
+        if !self.open {
+            self.armed = None;
+        }
+    }
+}
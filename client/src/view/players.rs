@@ -0,0 +1,141 @@
+use egui::{Color32, DragValue, RichText};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        character::commands::AwardXp, connection::ConnectionQuality, sheets::commands::OpenSheet,
+        theme, trade::commands::RequestTrade,
+    },
+};
+
+use super::{board::character_selection, trade::TradeWindows, DndTabImpl};
+
+#[derive(Default)]
+pub struct Players {
+    trades: TradeWindows,
+    xp_award: u32,
+    xp_recipients: Vec<String>,
+}
+
+impl Players {
+    fn player_row(
+        &mut self,
+        ui: &mut Ui,
+        state: &DndState,
+        commands: &mut CommandQueue,
+        name: &str,
+        is_self: bool,
+    ) {
+        let palette = theme::palette(ui.ctx());
+        ui.horizontal(|ui| {
+            if is_self {
+                connection_indicator(ui, state);
+            } else {
+                ui.label(RichText::new(egui_phosphor::fill::CIRCLE).color(palette.positive))
+                    .on_hover_text("Online");
+            }
+
+            let mut label = RichText::new(name);
+            if is_self {
+                label = label.strong();
+            }
+
+            if ui
+                .link(label)
+                .on_hover_text("View character sheet")
+                .clicked()
+            {
+                commands.add(OpenSheet(name.to_owned()));
+            }
+
+            if state.gm.as_deref() == Some(name) {
+                ui.label(RichText::new("GM").small().color(palette.critical));
+            }
+
+            if !is_self
+                && ui
+                    .small_button(egui_phosphor::regular::HANDSHAKE)
+                    .on_hover_text("Trade items")
+                    .clicked()
+            {
+                commands.add(RequestTrade(name.to_owned()));
+            }
+
+            if !is_self && state.players.is_typing(name) {
+                ui.label(RichText::new(egui_phosphor::regular::CHAT_DOTS).color(Color32::GRAY))
+                    .on_hover_text("Typing...");
+            }
+        });
+    }
+
+    fn award_xp_ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.menu_button("Award XP", |ui| {
+            DragValue::new(&mut self.xp_award)
+                .range(0..=100000)
+                .suffix(" XP")
+                .ui(ui);
+            character_selection(ui, state, &mut self.xp_recipients);
+
+            ui.separator();
+            let valid = self.xp_award > 0 && !self.xp_recipients.is_empty();
+            if ui.add_enabled(valid, egui::Button::new("Award")).clicked() {
+                commands.add(AwardXp {
+                    characters: self.xp_recipients.clone(),
+                    xp: self.xp_award,
+                });
+                self.xp_award = 0;
+                ui.close_menu();
+            }
+        });
+    }
+}
+
+/// Our own dot shows how the connection to the server is holding up
+fn connection_indicator(ui: &mut Ui, state: &DndState) {
+    let palette = theme::palette(ui.ctx());
+    let connection = &state.connection;
+    let latency = connection.latency.unwrap_or_default().as_millis();
+
+    let (color, hover) = match connection.quality() {
+        ConnectionQuality::Unknown => (palette.positive, "Online".to_owned()),
+        ConnectionQuality::Good => (palette.positive, format!("Connected, {latency} ms")),
+        ConnectionQuality::Slow => (palette.caution, format!("Slow connection, {latency} ms")),
+        ConnectionQuality::Lost => (
+            palette.negative,
+            format!(
+                "No answer from the server for {}s",
+                connection.silent_for().unwrap_or_default()
+            ),
+        ),
+    };
+    ui.label(RichText::new(egui_phosphor::fill::CIRCLE).color(color))
+        .on_hover_text(hover);
+}
+
+impl DndTabImpl for Players {
+    fn ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            ui.heading(format!("Players ({})", state.players.online.len() + 1));
+            ui.separator();
+
+            let user = state.owned_user();
+            self.player_row(ui, state, commands, &user.name, true);
+
+            for name in state.players.online.iter() {
+                self.player_row(ui, state, commands, name, false);
+            }
+
+            if state.is_gm() {
+                ui.separator();
+                self.award_xp_ui(ui, state, commands);
+            }
+        });
+
+        self.trades.show(ui.ctx(), state, commands);
+    }
+
+    fn title(&self) -> String {
+        "Players".to_owned()
+    }
+}
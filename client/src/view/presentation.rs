@@ -0,0 +1,132 @@
+use common::PieceVisibility;
+use egui::{CentralPanel, Frame, ViewportBuilder, ViewportClass, ViewportId, Window};
+use itertools::Itertools;
+
+use crate::prelude::*;
+
+use super::{
+    ambience, annotations::paint_annotations, effects, floating_numbers, statuses,
+    vision::FogCache, walls::paint_walls, Board,
+};
+
+/// The board on its own in a second window, for the GM to drag onto a TV at
+/// the table. Only what every player can see is drawn, under the party's fog
+pub struct Presentation {
+    pub open: bool,
+    /// Panned and zoomed separately so the GM's own view stays put
+    origin: Pos2,
+    zoom: f32,
+    fog: FogCache,
+}
+
+impl Default for Presentation {
+    fn default() -> Self {
+        Self {
+            open: false,
+            origin: Pos2::ZERO,
+            zoom: 1.0,
+            fog: FogCache::default(),
+        }
+    }
+}
+
+impl Presentation {
+    const ZOOM_FACTOR: f32 = 0.01;
+    const MAX_ZOOM: f32 = 10.0;
+    const MIN_ZOOM: f32 = 0.5;
+
+    pub fn show(&mut self, ctx: &egui::Context, state: &DndState) {
+        if !self.open {
+            return;
+        }
+
+        let viewport = ViewportBuilder::default()
+            .with_title("Board")
+            .with_inner_size([800.0, 600.0]);
+
+        ctx.show_viewport_immediate(
+            ViewportId::from_hash_of("presentation"),
+            viewport,
+            |ctx, class| {
+                // Backends without multiple viewports get a window inside the main one
+                if class == ViewportClass::Embedded {
+                    let mut open = true;
+                    Window::new("Presentation")
+                        .open(&mut open)
+                        .show(ctx, |ui| self.board_ui(ui, state));
+                    self.open &= open;
+                    return;
+                }
+
+                CentralPanel::default()
+                    .frame(Frame::canvas(&ctx.style()))
+                    .show(ctx, |ui| self.board_ui(ui, state));
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+            },
+        );
+    }
+
+    fn board_ui(&mut self, ui: &mut Ui, state: &DndState) {
+        let (response, painter) =
+            ui.allocate_painter(ui.available_size_before_wrap(), egui::Sense::drag());
+
+        if response.hovered() {
+            self.zoom /= (ui.input(|i| i.smooth_scroll_delta.y) * Self::ZOOM_FACTOR) + 1.0;
+            self.zoom = self.zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        }
+
+        let dims = response.rect.square_proportions() * self.zoom;
+        let to_screen =
+            RectTransform::from_to(Rect::from_center_size(self.origin, dims), response.rect);
+
+        if response.dragged() {
+            let from_screen = to_screen.inverse();
+            self.origin = from_screen * (to_screen * self.origin - response.drag_delta());
+        }
+
+        Board::paint_grid(self.origin, dims, &painter, &to_screen, &state.board.grid);
+        paint_walls(&painter, to_screen, &state.board);
+
+        for (id, player) in state
+            .board
+            .active_players()
+            .sorted_by_key(|(_, x)| x.sorting_layer)
+            .filter(|(_, x)| x.visibility == PieceVisibility::Everyone)
+        {
+            player.draw_shape(ui, &painter, to_screen);
+            effects::paint_piece_effects(
+                &painter,
+                state,
+                *id,
+                to_screen.transform_rect(player.display_rect()),
+            );
+            statuses::paint_statuses(
+                &painter,
+                to_screen.transform_rect(player.display_rect()),
+                &player.statuses,
+            );
+            let floating = floating_numbers::paint_floating_numbers(
+                &painter,
+                to_screen.transform_rect(player.display_rect()),
+                *id,
+                &state.board.floating,
+            );
+
+            if player.is_animating() || floating {
+                ui.ctx().request_repaint();
+            }
+        }
+
+        let time = ui.input(|i| i.time);
+        ambience::paint_ambience(&painter, response.rect, &state.board.ambience, time);
+
+        paint_annotations(&painter, to_screen, &state.board, |x| {
+            x.visible_by.is_empty()
+        });
+        self.fog
+            .paint_party(&painter, response.rect, to_screen, state);
+    }
+}
@@ -0,0 +1,81 @@
+use common::IssueReport;
+use egui::{CollapsingHeader, ScrollArea, TextEdit};
+
+use crate::{listener::CommandQueue, log_buffer, state::toasts::commands::SendIssueReport};
+
+/// Help > Report Issue, sends a description of the problem along with the recent log
+pub struct ReportIssue {
+    open: bool,
+    description: String,
+    include_log: bool,
+    sent: bool,
+}
+
+impl Default for ReportIssue {
+    fn default() -> Self {
+        Self {
+            open: false,
+            description: String::new(),
+            include_log: true,
+            sent: false,
+        }
+    }
+}
+
+impl ReportIssue {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.sent = false;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, commands: &mut CommandQueue) {
+        let mut open = self.open;
+
+        egui::Window::new("Report Issue")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if self.sent {
+                    ui.label("Thanks, the report was sent to the server");
+                    if ui.button("Close").clicked() {
+                        self.open = false;
+                    }
+                    return;
+                }
+
+                ui.label("What happened?");
+                ui.add(
+                    TextEdit::multiline(&mut self.description)
+                        .hint_text("Optional")
+                        .desired_rows(4),
+                );
+
+                ui.checkbox(&mut self.include_log, "Include recent log");
+                if self.include_log {
+                    CollapsingHeader::new("Log").show(ui, |ui| {
+                        ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                            ui.monospace(log_buffer::recent());
+                        });
+                    });
+                }
+
+                ui.weak(format!("Version {}", env!("CARGO_PKG_VERSION")));
+
+                if ui.button("Send").clicked() {
+                    commands.add(SendIssueReport(IssueReport {
+                        version: env!("CARGO_PKG_VERSION").to_owned(),
+                        description: self.description.trim().to_owned(),
+                        log: if self.include_log {
+                            log_buffer::recent()
+                        } else {
+                            String::new()
+                        },
+                    }));
+                    self.description.clear();
+                    self.sent = true;
+                }
+            });
+
+        self.open &= open;
+    }
+}
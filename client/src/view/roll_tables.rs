@@ -0,0 +1,244 @@
+use common::{message::RollVisibility, RollTable, TableEntry};
+use egui::{DragValue, Grid, ScrollArea};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        chat::commands::{DeleteRollTable, RollOnTable, SaveRollTable},
+        theme,
+    },
+};
+
+use super::DndTabImpl;
+
+/// Lists the GM's roll tables. Everyone can roll on them, only the GM can edit them
+#[derive(Default)]
+pub struct RollTables {
+    selected: Option<String>,
+    /// Copy of the selected table being edited by the GM
+    draft: Option<RollTable>,
+    new_name: String,
+    /// GM rolls only they get to see
+    blind: bool,
+}
+
+impl RollTables {
+    fn table_list(&mut self, ui: &mut egui::Ui, state: &DndState) {
+        for table in state.roll_tables.iter() {
+            let selected = self.selected.as_ref() == Some(&table.name);
+            if ui.selectable_label(selected, &table.name).clicked() {
+                self.selected = Some(table.name.clone());
+                self.draft = None;
+            }
+        }
+
+        if state.roll_tables.is_empty() {
+            ui.weak("No roll tables yet");
+        }
+
+        if !state.is_gm() {
+            return;
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_name)
+                .on_hover_text("Name of the new table, ie. \"Wild Magic Surge\"");
+
+            let name = self.new_name.trim();
+            let taken = state.roll_tables.iter().any(|x| x.name == name);
+            if ui
+                .add_enabled(!name.is_empty() && !taken, egui::Button::new("New Table"))
+                .clicked()
+            {
+                self.selected = Some(name.to_owned());
+                self.draft = Some(RollTable {
+                    name: name.to_owned(),
+                    entries: Vec::new(),
+                    add_to_stash: false,
+                });
+                self.new_name.clear();
+            }
+        });
+    }
+
+    fn table_ui(&mut self, ui: &mut egui::Ui, table: &RollTable, commands: &mut CommandQueue) {
+        ui.heading(&table.name);
+
+        Grid::new("roll_table_entries")
+            .striped(true)
+            .num_columns(3)
+            .show(ui, |ui| {
+                let mut low = 1;
+                for entry in table.entries.iter().filter(|x| x.weight > 0) {
+                    let high = low + entry.weight - 1;
+                    if low == high {
+                        ui.weak(low.to_string());
+                    } else {
+                        ui.weak(format!("{low}-{high}"));
+                    }
+                    ui.label(&entry.text);
+                    match &entry.item {
+                        Some(item) if table.add_to_stash => {
+                            ui.weak(format!("{} {}", egui_phosphor::regular::PACKAGE, item));
+                        }
+                        _ => {
+                            ui.label("");
+                        }
+                    }
+                    ui.end_row();
+                    low = high + 1;
+                }
+            });
+
+        ui.horizontal(|ui| {
+            let total = table.total_weight();
+            if ui
+                .add_enabled(total > 0, egui::Button::new(format!("Roll d{total}")))
+                .clicked()
+            {
+                let visibility = if self.blind {
+                    RollVisibility::Blind
+                } else {
+                    RollVisibility::Public
+                };
+                commands.add(RollOnTable {
+                    table: table.clone(),
+                    visibility,
+                });
+            }
+        });
+    }
+
+    fn editor_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let saved = state
+            .roll_tables
+            .iter()
+            .any(|x| Some(&x.name) == self.selected.as_ref());
+        let Some(draft) = self.draft.as_mut() else {
+            return;
+        };
+
+        ui.checkbox(
+            &mut draft.add_to_stash,
+            "Add rolled items to the party stash",
+        )
+        .on_hover_text("As many as the first dice in the result rolled, only when you roll");
+
+        Grid::new("roll_table_editor")
+            .num_columns(4)
+            .show(ui, |ui| {
+                ui.weak("Weight");
+                ui.weak("Result").on_hover_text(
+                    "Results can roll dice, ie. \"1d4 healing potions\", \
+                    and other tables, ie. \"[[Gems]]\"",
+                );
+                if draft.add_to_stash {
+                    ui.weak("Item");
+                }
+                ui.end_row();
+
+                let mut remove = None;
+                for (i, entry) in draft.entries.iter_mut().enumerate() {
+                    DragValue::new(&mut entry.weight).range(0..=1000).ui(ui);
+                    ui.text_edit_singleline(&mut entry.text);
+                    if draft.add_to_stash {
+                        let mut item = entry.item.clone().unwrap_or_default();
+                        if ui
+                            .text_edit_singleline(&mut item)
+                            .on_hover_text("Name of an item in the catalog, blank for none")
+                            .changed()
+                        {
+                            entry.item = Some(item).filter(|x| !x.trim().is_empty());
+                        }
+                    }
+                    if ui
+                        .small_button(egui_phosphor::regular::TRASH)
+                        .on_hover_text("Remove entry")
+                        .clicked()
+                    {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+
+                if let Some(i) = remove {
+                    draft.entries.remove(i);
+                }
+            });
+
+        if ui.button(egui_phosphor::regular::PLUS).clicked() {
+            draft.entries.push(TableEntry {
+                weight: 1,
+                text: String::new(),
+                item: None,
+            });
+        }
+
+        let mut close = false;
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                commands.add(SaveRollTable(draft.clone()));
+                close = true;
+            }
+            if ui.button("Cancel").clicked() {
+                close = true;
+            }
+            if saved
+                && ui
+                    .button(RichText::new("Delete").color(theme::palette(ui.ctx()).negative))
+                    .clicked()
+            {
+                commands.add(DeleteRollTable(draft.name.clone()));
+                close = true;
+            }
+        });
+
+        if close {
+            self.draft = None;
+        }
+    }
+}
+
+impl DndTabImpl for RollTables {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        egui::SidePanel::left("roll_table_list")
+            .resizable(true)
+            .show_inside(ui, |ui| {
+                ScrollArea::vertical().show(ui, |ui| self.table_list(ui, state));
+            });
+
+        ScrollArea::vertical().show(ui, |ui| {
+            if self.draft.is_some() {
+                self.editor_ui(ui, state, commands);
+                return;
+            }
+
+            let table = state
+                .roll_tables
+                .iter()
+                .find(|x| Some(&x.name) == self.selected.as_ref());
+            let Some(table) = table else {
+                ui.weak("Pick a table to roll on");
+                return;
+            };
+
+            self.table_ui(ui, table, commands);
+
+            if state.is_gm() {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.blind, "Blind")
+                        .on_hover_text("Only you see the result");
+                    if ui.button("Edit").clicked() {
+                        self.draft = Some(table.clone());
+                    }
+                });
+            }
+        });
+    }
+
+    fn title(&self) -> String {
+        "Roll Tables".to_owned()
+    }
+}
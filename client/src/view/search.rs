@@ -0,0 +1,177 @@
+use egui::{Key, Label, Modifiers, ScrollArea, TextEdit};
+use itertools::Itertools;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        chat::commands::ScrollChatTo,
+        search::{SearchCategory, SearchEntry, SearchTarget},
+        sheets::commands::OpenSheet,
+    },
+};
+
+use super::{items::ItemInfo, tab_kinds, TabKind};
+
+/// Results shown per category, the rest are left for a narrower search
+const MAX_RESULTS: usize = 8;
+
+/// Ctrl+Shift+F looks through the items, abilities and characters we know
+/// about along with the chat log, and jumps to whatever is picked
+#[derive(Default)]
+pub struct CampaignSearch {
+    open: bool,
+    query: String,
+    /// Item picked from the results, by its owner and id
+    item_info: Option<(String, i64)>,
+}
+
+impl CampaignSearch {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.query.clear();
+    }
+
+    fn results<'a>(&self, state: &'a DndState) -> Vec<(SearchCategory, Vec<&'a SearchEntry>)> {
+        let query = self.query.trim();
+        if query.is_empty() {
+            return vec![];
+        }
+
+        let scored = state
+            .search
+            .entries
+            .iter()
+            .filter_map(|x| Some((x.score(query)?, x)))
+            .collect_vec();
+
+        SearchCategory::ALL
+            .into_iter()
+            .map(|category| {
+                // Best matches first, the latest chat among equally good ones
+                let entries = scored
+                    .iter()
+                    .filter(|(_, x)| x.target.category() == category)
+                    .sorted_by_key(|(score, x)| {
+                        let recent = match x.target {
+                            SearchTarget::Chat(i) => i,
+                            _ => 0,
+                        };
+                        std::cmp::Reverse((*score, recent))
+                    })
+                    .map(|(_, x)| *x)
+                    .collect_vec();
+                (category, entries)
+            })
+            .filter(|(_, entries)| !entries.is_empty())
+            .collect()
+    }
+
+    /// Carries out a picked result, returning the tab it should be shown in
+    fn pick(
+        &mut self,
+        target: &SearchTarget,
+        state: &DndState,
+        commands: &mut CommandQueue,
+    ) -> Option<&'static str> {
+        match target {
+            SearchTarget::Item { owner, id } => {
+                self.item_info = Some((owner.clone(), *id));
+                None
+            }
+            // The abilities tab only has our own, anyone else's are on their sheet
+            SearchTarget::Ability { owner } if state.can_edit_character(owner) => Some("Abilities"),
+            SearchTarget::Ability { owner } | SearchTarget::Character(owner) => {
+                commands.add(OpenSheet(owner.clone()));
+                None
+            }
+            SearchTarget::Chat(i) => {
+                commands.add(ScrollChatTo(Some(*i)));
+                Some("Chat")
+            }
+        }
+    }
+
+    fn show_item_info(&mut self, ctx: &egui::Context, state: &DndState) {
+        let Some((owner, id)) = &self.item_info else {
+            return;
+        };
+        let item = state
+            .character_sheet(owner)
+            .and_then(|x| x.items.iter().find(|item| item.id == *id));
+
+        let mut open = item.is_some();
+        if let Some(item) = item {
+            ItemInfo::new(item).show(ctx, &mut open);
+        }
+        if !open {
+            self.item_info = None;
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        state: &DndState,
+        commands: &mut CommandQueue,
+    ) -> Option<TabKind> {
+        if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::F)) {
+            self.toggle();
+        }
+
+        self.show_item_info(ctx, state);
+
+        if !self.open {
+            return None;
+        }
+
+        let mut picked = None;
+        let mut open = self.open;
+        egui::Window::new("Search Campaign")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(350.0)
+            .show(ctx, |ui| {
+                let response = TextEdit::singleline(&mut self.query)
+                    .hint_text("Items, abilities, characters and chat...")
+                    .desired_width(f32::INFINITY)
+                    .ui(ui);
+                response.request_focus();
+
+                let results = self.results(state);
+                if self.query.trim().is_empty() {
+                    return;
+                }
+
+                ui.separator();
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for (category, entries) in results.iter() {
+                        ui.strong(format!("{} ({})", category.label(), entries.len()));
+                        for entry in entries.iter().take(MAX_RESULTS) {
+                            if ui.selectable_label(false, &entry.title).clicked() {
+                                picked = Some(entry.target.clone());
+                            }
+                            if !entry.detail.is_empty() {
+                                ui.add(Label::new(RichText::new(&entry.detail).weak()).truncate());
+                            }
+                        }
+                        if entries.len() > MAX_RESULTS {
+                            ui.weak(format!("and {} more", entries.len() - MAX_RESULTS));
+                        }
+                        ui.add_space(4.0);
+                    }
+
+                    if results.is_empty() {
+                        ui.weak("Nothing matches");
+                    }
+                });
+            });
+
+        self.open = open && !ctx.input(|i| i.key_pressed(Key::Escape));
+
+        let target = picked?;
+        self.open = false;
+        let tab = self.pick(&target, state, commands)?;
+        tab_kinds().into_iter().find(|x| x.title == tab)
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use egui::DragValue;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::session_clock::commands::{
+        PauseSessionClock, ResetSessionClock, SetBreakInterval, StartSessionClock,
+    },
+};
+
+/// Real time since the GM started the session. Everyone can see it, only the
+/// GM can start, pause or reset it
+#[derive(Default)]
+pub struct SessionTimer {
+    /// Minutes between breaks, kept while reminders are turned off
+    break_every: u32,
+}
+
+impl SessionTimer {
+    const DEFAULT_BREAK: u32 = 90;
+
+    pub fn ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        let clock = state.session_clock.clock;
+        let elapsed = state.session_clock.elapsed().as_secs();
+        let icon = if clock.running {
+            egui_phosphor::regular::TIMER
+        } else {
+            egui_phosphor::regular::PAUSE
+        };
+        let text = format!(
+            "{icon} {}:{:02}:{:02}",
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60
+        );
+
+        if clock.running {
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+        }
+
+        if !state.is_gm() {
+            ui.label(text).on_hover_text("Session time");
+            return;
+        }
+
+        let response = ui.menu_button(text, |ui| {
+            ui.horizontal(|ui| {
+                if clock.running {
+                    if ui.button("Pause").clicked() {
+                        commands.add(PauseSessionClock);
+                    }
+                } else if ui.button("Start").clicked() {
+                    commands.add(StartSessionClock);
+                }
+                if ui.button("Reset").clicked() {
+                    commands.add(ResetSessionClock);
+                }
+            });
+
+            ui.separator();
+            let mut reminders = clock.break_every.is_some();
+            if ui.checkbox(&mut reminders, "Break reminders").changed() {
+                commands.add(SetBreakInterval(reminders.then_some(self.break_every)));
+            }
+
+            ui.add_enabled_ui(reminders, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Every");
+                    let response = DragValue::new(&mut self.break_every)
+                        .range(5..=240)
+                        .suffix(" min")
+                        .ui(ui);
+                    if response.drag_stopped() || response.lost_focus() {
+                        commands.add(SetBreakInterval(Some(self.break_every)));
+                    }
+                });
+            });
+        });
+
+        // Start editing from the current interval each time the menu is opened
+        if response.response.clicked() {
+            self.break_every = clock.break_every.unwrap_or(Self::DEFAULT_BREAK);
+        }
+    }
+}
@@ -1,44 +1,366 @@
-use egui::{DragValue, Slider};
+use common::{
+    formula::Stat,
+    rules::RulesSystem,
+    ruleset::{Diagonals, ModifierFormula, Ruleset, SkillConfig, StatConfig},
+};
+use egui::{CollapsingHeader, ComboBox, DragValue, Slider};
 
-use crate::prelude::*;
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        audio::{commands::SetAudioSettings, EventSound},
+        character::commands::{SetRuleset, SetXpTable},
+        narration::commands::SetNarrationSettings,
+        theme::{self, commands::SetAppearance, Theme, ThemePreset},
+    },
+};
 
 use super::DndTabImpl;
 
+#[derive(Default)]
 pub struct Settings {
-    pixels_per_point: f32,
+    /// Held while the scale slider is dragged, rescaling mid drag would move
+    /// the slider out from under the pointer
+    ui_scale: Option<f32>,
+    /// Ruleset changes aren't sent until they're saved
+    ruleset: Option<Ruleset>,
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            pixels_per_point: 1.5,
+impl Settings {
+    fn ruleset_ui(&mut self, ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        let draft = self.ruleset.get_or_insert_with(|| state.ruleset.clone());
+
+        ui.horizontal(|ui| {
+            ui.label("Rules")
+                .on_hover_text("Decides crits and fumbles, stat modifiers and how long rests take");
+            ComboBox::from_id_salt("ruleset_system")
+                .selected_text(draft.system.to_string())
+                .show_ui(ui, |ui| {
+                    for option in RulesSystem::ALL {
+                        ui.selectable_value(&mut draft.system, option, option.to_string());
+                    }
+                });
+        });
+
+        ui.separator();
+        ui.label("Stats");
+        let mut remove = None;
+        egui::Grid::new("ruleset_stats").show(ui, |ui| {
+            for (idx, stat) in draft.stats.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut stat.name).desired_width(80.0));
+                stat_combo(ui, ("ruleset_stat", idx), &mut stat.stat);
+                if ui.small_button(egui_phosphor::regular::TRASH).clicked() {
+                    remove = Some(idx);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(idx) = remove {
+            draft.stats.remove(idx);
+        }
+
+        let unused = Stat::ALL
+            .into_iter()
+            .find(|x| !draft.stats.iter().any(|stat| stat.stat == *x));
+        if let Some(stat) = unused {
+            if ui.button("Add Stat").clicked() {
+                draft.stats.push(StatConfig {
+                    name: stat.to_string(),
+                    stat,
+                });
+            }
+        }
+
+        ui.separator();
+        ui.label("Skills");
+        let mut remove = None;
+        egui::Grid::new("ruleset_skills").show(ui, |ui| {
+            for (idx, skill) in draft.skills.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut skill.name).desired_width(120.0));
+                stat_combo(ui, ("ruleset_skill", idx), &mut skill.stat);
+                if ui.small_button(egui_phosphor::regular::TRASH).clicked() {
+                    remove = Some(idx);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(idx) = remove {
+            draft.skills.remove(idx);
         }
+        if ui.button("Add Skill").clicked() {
+            draft.skills.push(SkillConfig {
+                name: String::new(),
+                stat: Stat::Str,
+            });
+        }
+
+        ui.separator();
+        modifier_ui(ui, &mut draft.modifier);
+
+        ui.separator();
+        ui.label("Proficiency bonus");
+        egui::Grid::new("ruleset_proficiency").show(ui, |ui| {
+            for (idx, bonus) in draft.proficiency.iter_mut().enumerate() {
+                ui.label(format!("Level {}", idx + 1));
+                DragValue::new(bonus).range(-10..=20).ui(ui);
+                ui.end_row();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Add Level").clicked() {
+                let last = draft.proficiency.last().copied().unwrap_or_default();
+                draft.proficiency.push(last);
+            }
+            if ui
+                .add_enabled(
+                    draft.proficiency.len() > 1,
+                    egui::Button::new("Remove Level"),
+                )
+                .clicked()
+            {
+                draft.proficiency.pop();
+            }
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Attunement limit");
+            DragValue::new(&mut draft.attunement_limit)
+                .range(0..=20)
+                .ui(ui);
+            ui.checkbox(&mut draft.enforce_attunement_limit, "Enforce")
+                .on_hover_text("Refuse attuning past the limit instead of only warning");
+        });
+
+        ui.checkbox(
+            &mut draft.disable_actions_when_down,
+            "Grey out abilities and items while down",
+        )
+        .on_hover_text("Characters at 0 HP can't use them until they're back up");
+
+        ui.horizontal(|ui| {
+            ui.label("Diagonal moves")
+                .on_hover_text("How drag distances count diagonal steps on square grids");
+            ComboBox::from_id_salt("ruleset_diagonals")
+                .selected_text(draft.diagonals.to_string())
+                .show_ui(ui, |ui| {
+                    for option in Diagonals::ALL {
+                        ui.selectable_value(&mut draft.diagonals, option, option.to_string());
+                    }
+                });
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let changed = *draft != state.ruleset;
+            if ui.add_enabled(changed, egui::Button::new("Save")).clicked() {
+                commands.add(SetRuleset(draft.clone()));
+            }
+            if ui
+                .add_enabled(changed, egui::Button::new("Revert"))
+                .clicked()
+            {
+                *draft = state.ruleset.clone();
+            }
+            if ui
+                .button("Reset to D&D 5e")
+                .on_hover_text("Still has to be saved")
+                .clicked()
+            {
+                *draft = Ruleset::default();
+            }
+        });
     }
 }
 
+fn modifier_ui(ui: &mut Ui, formula: &mut ModifierFormula) {
+    ui.label("Stat modifier")
+        .on_hover_text("(score - base) / divisor, rounded down");
+    ui.horizontal(|ui| {
+        ui.label("Base");
+        DragValue::new(&mut formula.base).range(-30..=30).ui(ui);
+        ui.label("Divisor");
+        DragValue::new(&mut formula.divisor).range(1..=10).ui(ui);
+    });
+    ui.horizontal(|ui| {
+        let mut capped = formula.cap.is_some();
+        if ui.checkbox(&mut capped, "Cap at ±").changed() {
+            formula.cap = capped.then_some(5);
+        }
+        if let Some(cap) = formula.cap.as_mut() {
+            DragValue::new(cap).range(0..=30).ui(ui);
+        }
+    });
+
+    let examples = [1, 8, 10, 14, 20, 30]
+        .map(|score| format!("{} → {:+}", score, formula.modifier(score)))
+        .join(", ");
+    ui.weak(examples);
+}
+
+fn stat_combo(ui: &mut Ui, id: impl std::hash::Hash, stat: &mut Stat) {
+    ComboBox::from_id_salt(id)
+        .selected_text(stat.to_string())
+        .show_ui(ui, |ui| {
+            for option in Stat::ALL {
+                ui.selectable_value(stat, option, option.to_string());
+            }
+        });
+}
+
 impl DndTabImpl for Settings {
-    fn ui(
-        &mut self,
-        ui: &mut egui::Ui,
-        _state: &DndState,
-        _commands: &mut crate::listener::CommandQueue,
-    ) {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        egui::ScrollArea::vertical().show(ui, |ui| self.settings_ui(ui, state, commands));
+    }
+
+    fn title(&self) -> String {
+        "Settings".to_owned()
+    }
+}
+
+impl Settings {
+    fn appearance_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let mut appearance = state.appearance.current;
+
         egui::Grid::new("settings").show(ui, |ui| {
             ui.label("UI Scale: ");
-            if DragValue::new(&mut self.pixels_per_point)
-                .range(0.5..=3.0)
-                .update_while_editing(false)
-                .ui(ui)
-                .changed()
-            {
-                ui.ctx().set_pixels_per_point(self.pixels_per_point);
+            let scale = self.ui_scale.get_or_insert(appearance.ui_scale);
+            let response = Slider::new(scale, theme::UI_SCALE_RANGE)
+                .step_by(0.05)
+                .ui(ui);
+            if !response.dragged() {
+                appearance.ui_scale = *scale;
+                self.ui_scale = None;
             }
+            ui.end_row();
 
+            ui.label("Chat Text: ");
+            Slider::new(&mut appearance.chat_font_size, theme::CHAT_FONT_SIZE_RANGE)
+                .step_by(0.5)
+                .ui(ui);
             ui.end_row();
         });
+
+        ui.separator();
+        ui.heading("Theme");
+        theme_ui(ui, &mut appearance.theme);
+
+        if appearance != state.appearance.current {
+            commands.add(SetAppearance(appearance));
+        }
     }
 
-    fn title(&self) -> String {
-        "Settings".to_owned()
+    fn settings_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        self.appearance_ui(ui, state, commands);
+
+        ui.separator();
+        ui.heading("Sounds");
+
+        let mut audio = state.audio.settings;
+        ui.checkbox(&mut audio.muted, "Mute all");
+
+        egui::Grid::new("audio_settings").show(ui, |ui| {
+            event_sound_row(ui, "Chat messages", &mut audio.chat);
+            event_sound_row(ui, "Dice rolls", &mut audio.roll);
+            event_sound_row(ui, "Players joining", &mut audio.joined);
+            event_sound_row(ui, "Music", &mut audio.music);
+            event_sound_row(ui, "Sound effects", &mut audio.effects);
+        });
+
+        if audio != state.audio.settings {
+            commands.add(SetAudioSettings(audio));
+        }
+
+        ui.separator();
+        ui.heading("Accessibility");
+
+        let mut narration = state.narration.settings;
+        ui.checkbox(&mut narration.plain_chat, "Plain text chat")
+            .on_hover_text("Show each chat message as one line, the way a screen reader reads it");
+        ui.add_enabled(
+            state.narration.can_read_aloud(),
+            egui::Checkbox::new(&mut narration.read_aloud, "Read chat aloud"),
+        )
+        .on_hover_text("Chat and roll results are read out as they come in")
+        .on_disabled_hover_text("Built without the narration feature");
+
+        if narration != state.narration.settings {
+            commands.add(SetNarrationSettings(narration));
+        }
+
+        if state.is_gm() {
+            ui.separator();
+            CollapsingHeader::new("XP Table").show(ui, |ui| xp_table_ui(ui, state, commands));
+            CollapsingHeader::new("Ruleset").show(ui, |ui| self.ruleset_ui(ui, state, commands));
+        }
+    }
+}
+
+fn theme_ui(ui: &mut Ui, theme: &mut Theme) {
+    let preset = ThemePreset::of(theme);
+
+    ComboBox::from_label("Preset")
+        .selected_text(preset.map_or_else(|| "Custom".to_owned(), |x| x.to_string()))
+        .show_ui(ui, |ui| {
+            for option in ThemePreset::ALL {
+                if ui
+                    .selectable_label(preset == Some(option), option.to_string())
+                    .clicked()
+                {
+                    *theme = option.theme();
+                }
+            }
+        });
+    ui.checkbox(&mut theme.dark, "Dark mode");
+
+    egui::Grid::new("theme_palette").show(ui, |ui| {
+        for (name, color) in theme.palette.roles_mut() {
+            ui.colored_label(*color, name);
+            ui.color_edit_button_srgba(color);
+            ui.end_row();
+        }
+    });
+}
+
+fn xp_table_ui(ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+    let mut table = state.xp_table.clone();
+
+    egui::Grid::new("xp_table").show(ui, |ui| {
+        // Everyone starts at level 1, so its entry is always 0
+        for (idx, xp) in table.0.iter_mut().enumerate().skip(1) {
+            ui.label(format!("Level {}", idx + 1));
+            DragValue::new(xp)
+                .suffix(" XP")
+                .update_while_editing(false)
+                .ui(ui);
+            ui.end_row();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("Add Level").clicked() {
+            let last = table.0.last().copied().unwrap_or_default();
+            table.0.push(last);
+        }
+        if ui
+            .add_enabled(table.0.len() > 1, egui::Button::new("Remove Level"))
+            .clicked()
+        {
+            table.0.pop();
+        }
+    });
+
+    if table != state.xp_table {
+        commands.add(SetXpTable(table));
     }
 }
+
+fn event_sound_row(ui: &mut Ui, label: &str, sound: &mut EventSound) {
+    ui.checkbox(&mut sound.enabled, label);
+    ui.add_enabled(
+        sound.enabled,
+        Slider::new(&mut sound.volume, 0.0..=1.0).show_value(false),
+    );
+    ui.end_row();
+}
@@ -0,0 +1,109 @@
+use egui::{ahash::HashMap, Align2, Area, Frame};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::sheets::commands::{CloseSheet, MinimizeSheet, OpenSheet},
+};
+
+use super::{
+    character::{CharacterSheet, LevelUpForm},
+    effects::EffectForm,
+};
+
+#[derive(Default)]
+struct SheetForms {
+    effect_form: EffectForm,
+    level_up: LevelUpForm,
+    portrait: String,
+}
+
+/// Draws every open character sheet window once a frame, no matter which tabs are open
+#[derive(Default)]
+pub struct SheetWindows {
+    forms: HashMap<String, SheetForms>,
+}
+
+impl SheetWindows {
+    pub fn show(&mut self, ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+        self.forms.retain(|name, _| state.sheets.is_open(name));
+
+        for window in state.sheets.windows.iter().filter(|x| !x.minimized) {
+            let name = &window.name;
+            let forms = self.forms.entry(name.clone()).or_default();
+            let mut open = true;
+
+            egui::Window::new(format!("{}'s sheet", name))
+                .id(egui::Id::new(("character_sheet", name)))
+                .open(&mut open)
+                .collapsible(false)
+                .default_width(320.0)
+                .show(ctx, |ui| {
+                    ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+                        if ui
+                            .small_button(egui_phosphor::regular::MINUS)
+                            .on_hover_text("Minimize")
+                            .clicked()
+                        {
+                            commands.add(MinimizeSheet {
+                                name: name.clone(),
+                                minimized: true,
+                            });
+                        }
+                    });
+
+                    match state.character_sheet(name) {
+                        Some(sheet) => CharacterSheet {
+                            state,
+                            sheet,
+                            commands,
+                            effect_form: &mut forms.effect_form,
+                            level_up: &mut forms.level_up,
+                            portrait: &mut forms.portrait,
+                        }
+                        .show(ui),
+                        None => {
+                            ui.spinner();
+                        }
+                    }
+                });
+
+            if !open {
+                commands.add(CloseSheet(name.clone()));
+            }
+        }
+
+        self.minimized_bar(ctx, state, commands);
+    }
+
+    /// Minimized sheets wait in the bottom left corner until they're brought back
+    fn minimized_bar(&self, ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+        if !state.sheets.windows.iter().any(|x| x.minimized) {
+            return;
+        }
+
+        Area::new(egui::Id::new("minimized_sheets"))
+            .anchor(Align2::LEFT_BOTTOM, Vec2::new(8.0, -8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for window in state.sheets.windows.iter().filter(|x| x.minimized) {
+                        Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .link(&window.name)
+                                    .on_hover_text("Restore sheet")
+                                    .clicked()
+                                {
+                                    commands.add(OpenSheet(window.name.clone()));
+                                }
+                                if ui.small_button(egui_phosphor::regular::X).clicked() {
+                                    commands.add(CloseSheet(window.name.clone()));
+                                }
+                            });
+                        });
+                    }
+                });
+            });
+    }
+}
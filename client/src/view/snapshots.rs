@@ -0,0 +1,135 @@
+use common::SnapshotInfo;
+use egui::{Grid, ScrollArea};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        snapshots::commands::{CheckSnapshotUsage, DeleteSnapshot, RestoreSnapshot, TakeSnapshot},
+        theme,
+    },
+};
+
+use super::DndTabImpl;
+
+#[derive(Default)]
+pub struct Snapshots {
+    new_tag: String,
+    confirm_restore: Option<SnapshotInfo>,
+}
+
+fn created_label(snapshot: &SnapshotInfo) -> String {
+    snapshot
+        .created_at
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+impl Snapshots {
+    fn snapshot_list(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        Grid::new("snapshots").striped(true).show(ui, |ui| {
+            for snapshot in state.snapshots.snapshots.iter() {
+                ui.label(&snapshot.tag);
+                ui.weak(created_label(snapshot));
+                if snapshot.automatic {
+                    ui.weak(egui_phosphor::regular::CLOCK)
+                        .on_hover_text("Taken automatically");
+                } else {
+                    ui.label("");
+                }
+
+                if ui.button("Restore").clicked() {
+                    self.confirm_restore = Some(snapshot.clone());
+                }
+                if ui
+                    .small_button(egui_phosphor::regular::TRASH)
+                    .on_hover_text("Delete snapshot")
+                    .clicked()
+                {
+                    commands.add(DeleteSnapshot(snapshot.id));
+                }
+                ui.end_row();
+            }
+        });
+
+        if state.snapshots.snapshots.is_empty() {
+            ui.weak("No snapshots yet");
+        }
+    }
+
+    fn confirm_ui(&mut self, ui: &mut egui::Ui, commands: &mut CommandQueue) {
+        let Some(snapshot) = self.confirm_restore.clone() else {
+            return;
+        };
+
+        egui::Window::new("Restore snapshot?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Every character, along with their items and abilities, goes back to how \
+                     they were in '{}' from {}.",
+                    snapshot.tag,
+                    created_label(&snapshot)
+                ));
+                ui.label("The current data is snapshotted first in case you change your mind.");
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(RichText::new("Restore").color(theme::palette(ui.ctx()).negative))
+                        .clicked()
+                    {
+                        commands.add(RestoreSnapshot(snapshot.id));
+                        self.confirm_restore = None;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_restore = None;
+                    }
+                });
+            });
+    }
+}
+
+impl DndTabImpl for Snapshots {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if !state.is_gm() {
+            ui.label("Only the GM can manage snapshots");
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_tag)
+                .on_hover_text("Tag to remember the snapshot by, ie. \"Before the heist\"");
+
+            let tag = self.new_tag.trim();
+            if ui
+                .add_enabled(!tag.is_empty(), egui::Button::new("Take Snapshot"))
+                .clicked()
+            {
+                commands.add(TakeSnapshot(tag.to_owned()));
+                self.new_tag.clear();
+            }
+        });
+        ui.weak("The server also takes a snapshot every half hour");
+        ui.horizontal(|ui| {
+            if ui.button("Check Storage").clicked() {
+                commands.add(CheckSnapshotUsage);
+            }
+            if let Some(usage) = &state.snapshots.usage {
+                ui.weak(usage.to_string());
+            }
+        });
+        ui.separator();
+
+        ScrollArea::vertical().show(ui, |ui| {
+            self.snapshot_list(ui, state, commands);
+        });
+
+        self.confirm_ui(ui, commands);
+    }
+
+    fn title(&self) -> String {
+        "Snapshots".to_owned()
+    }
+}
@@ -0,0 +1,270 @@
+use common::soundboard::{self, Playlist, SoundTrack};
+use egui::{DragValue, Grid, Slider};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        audio::commands::SetAudioSettings,
+        soundboard::commands::{PlayEffect, PlayMusic, SaveSoundboard, StopMusic},
+    },
+};
+
+use super::DndTabImpl;
+
+/// Music for the scene and sound effects, played for everyone by the GM
+pub struct Soundboard {
+    /// Seconds music fades in and out over
+    fade: f32,
+    new_track: SoundTrack,
+    new_playlist: Playlist,
+}
+
+fn empty_track() -> SoundTrack {
+    SoundTrack {
+        name: String::new(),
+        source: String::new(),
+    }
+}
+
+fn empty_playlist() -> Playlist {
+    Playlist {
+        name: String::new(),
+        tracks: Vec::new(),
+    }
+}
+
+impl Default for Soundboard {
+    fn default() -> Self {
+        Self {
+            fade: 3.0,
+            new_track: empty_track(),
+            new_playlist: empty_playlist(),
+        }
+    }
+}
+
+impl Soundboard {
+    /// Everyone sets how loud the GM's sounds are for themselves
+    fn volume_ui(ui: &mut Ui, state: &DndState, commands: &mut CommandQueue) {
+        let mut audio = state.audio.settings;
+        ui.horizontal(|ui| {
+            ui.label("Music");
+            ui.add(Slider::new(&mut audio.music.volume, 0.0..=1.0).show_value(false));
+            ui.label("Effects");
+            ui.add(Slider::new(&mut audio.effects.volume, 0.0..=1.0).show_value(false));
+        });
+        if audio != state.audio.settings {
+            commands.add(SetAudioSettings(audio));
+        }
+    }
+
+    fn tracks_ui(
+        &mut self,
+        ui: &mut Ui,
+        state: &DndState,
+        board: &mut soundboard::Soundboard,
+        commands: &mut CommandQueue,
+    ) {
+        Grid::new("soundboard_tracks").striped(true).show(ui, |ui| {
+            let mut removed = None;
+            for (index, track) in board.tracks.iter().enumerate() {
+                ui.label(&track.name);
+                ui.weak(&track.source);
+                if ui
+                    .small_button(egui_phosphor::regular::PLAY)
+                    .on_hover_text("Play as music, looped")
+                    .clicked()
+                {
+                    commands.add(PlayMusic {
+                        name: track.name.clone(),
+                        tracks: vec![track.name.clone()],
+                        fade: self.fade,
+                    });
+                }
+                if ui
+                    .small_button(egui_phosphor::regular::SPEAKER_HIGH)
+                    .on_hover_text("Play once as a sound effect")
+                    .clicked()
+                {
+                    commands.add(PlayEffect(track.name.clone()));
+                }
+                if ui.small_button(egui_phosphor::regular::TRASH).clicked() {
+                    removed = Some(index);
+                }
+                if state.audio.failed(&track.source) {
+                    ui.label(egui_phosphor::regular::WARNING).on_hover_text(
+                        "Couldn't be played, only WAV, OGG and MP3 files are supported",
+                    );
+                }
+                ui.end_row();
+            }
+
+            if let Some(index) = removed {
+                let name = board.tracks.remove(index).name;
+                for playlist in board.playlists.iter_mut() {
+                    playlist.tracks.retain(|x| *x != name);
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let track = &mut self.new_track;
+            ui.add(
+                egui::TextEdit::singleline(&mut track.name)
+                    .hint_text("Name")
+                    .desired_width(100.0),
+            );
+            ui.add(egui::TextEdit::singleline(&mut track.source).hint_text("URL or file path"))
+                .on_hover_text(
+                    "Files are read from each player's own computer, \
+                    so they need it at the same path",
+                );
+
+            let name = track.name.trim();
+            let valid =
+                !name.is_empty() && !track.source.trim().is_empty() && board.track(name).is_none();
+            if ui
+                .add_enabled(valid, egui::Button::new("Add Track"))
+                .clicked()
+            {
+                board.tracks.push(SoundTrack {
+                    name: name.to_owned(),
+                    source: track.source.trim().to_owned(),
+                });
+                *track = empty_track();
+            }
+        });
+    }
+
+    fn playlists_ui(
+        &mut self,
+        ui: &mut Ui,
+        board: &mut soundboard::Soundboard,
+        commands: &mut CommandQueue,
+    ) {
+        Grid::new("soundboard_playlists")
+            .striped(true)
+            .show(ui, |ui| {
+                let mut removed = None;
+                for (index, playlist) in board.playlists.iter().enumerate() {
+                    ui.label(&playlist.name);
+                    ui.weak(playlist.tracks.join(", "));
+                    if ui
+                        .add_enabled(
+                            !playlist.tracks.is_empty(),
+                            egui::Button::new(egui_phosphor::regular::PLAY).small(),
+                        )
+                        .on_hover_text("Play the tracks in order, looped")
+                        .clicked()
+                    {
+                        commands.add(PlayMusic {
+                            name: playlist.name.clone(),
+                            tracks: playlist.tracks.clone(),
+                            fade: self.fade,
+                        });
+                    }
+                    if ui.small_button(egui_phosphor::regular::TRASH).clicked() {
+                        removed = Some(index);
+                    }
+                    ui.end_row();
+                }
+
+                if let Some(index) = removed {
+                    board.playlists.remove(index);
+                }
+            });
+
+        let playlist = &mut self.new_playlist;
+        ui.horizontal_wrapped(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut playlist.name)
+                    .hint_text("Playlist, ie. Tavern")
+                    .desired_width(100.0),
+            );
+            // Tracks are played in the order they're ticked
+            for track in board.tracks.iter() {
+                let mut included = playlist.tracks.contains(&track.name);
+                if ui.checkbox(&mut included, &track.name).changed() {
+                    match included {
+                        true => playlist.tracks.push(track.name.clone()),
+                        false => playlist.tracks.retain(|x| *x != track.name),
+                    }
+                }
+            }
+
+            let name = playlist.name.trim();
+            let valid =
+                !name.is_empty() && !playlist.tracks.is_empty() && board.playlist(name).is_none();
+            if ui
+                .add_enabled(valid, egui::Button::new("Add Playlist"))
+                .clicked()
+            {
+                board.playlists.push(Playlist {
+                    name: name.to_owned(),
+                    tracks: std::mem::take(&mut playlist.tracks),
+                });
+                *playlist = empty_playlist();
+            }
+        });
+    }
+}
+
+impl DndTabImpl for Soundboard {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.horizontal(|ui| match &state.soundboard.now_playing {
+            Some(playing) => {
+                ui.label(format!(
+                    "{} {}",
+                    egui_phosphor::regular::MUSIC_NOTES,
+                    playing.name
+                ));
+            }
+            None => {
+                ui.weak("No music playing");
+            }
+        });
+        Self::volume_ui(ui, state, commands);
+
+        if !state.is_gm() {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Fade");
+            ui.add(
+                DragValue::new(&mut self.fade)
+                    .range(0.0..=30.0)
+                    .speed(0.1)
+                    .suffix("s"),
+            );
+            if ui
+                .add_enabled(
+                    state.soundboard.now_playing.is_some(),
+                    egui::Button::new(format!("{} Stop", egui_phosphor::regular::STOP)),
+                )
+                .clicked()
+            {
+                commands.add(StopMusic(self.fade));
+            }
+        });
+        ui.separator();
+
+        let mut board = state.soundboard.soundboard.clone();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.heading("Tracks");
+            self.tracks_ui(ui, state, &mut board, commands);
+            ui.separator();
+            ui.heading("Playlists");
+            self.playlists_ui(ui, &mut board, commands);
+        });
+
+        if board != state.soundboard.soundboard {
+            commands.add(SaveSoundboard(board));
+        }
+    }
+
+    fn title(&self) -> String {
+        "Soundboard".to_owned()
+    }
+}
@@ -0,0 +1,137 @@
+use egui::{DragValue, Grid, ScrollArea};
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::stash::commands::{AddLoot, ClaimLoot, RemoveLoot, ResolveClaim, SetRequireApproval},
+};
+
+use super::{DndTabImpl, ItemInfo};
+
+pub struct Stash {
+    new_loot: String,
+    new_count: u32,
+    info: Option<Uuid>,
+}
+
+impl Default for Stash {
+    fn default() -> Self {
+        Self {
+            new_loot: String::new(),
+            new_count: 1,
+            info: None,
+        }
+    }
+}
+
+impl Stash {
+    fn gm_ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_loot)
+                .on_hover_text("Name of an item in the catalog");
+            DragValue::new(&mut self.new_count).range(1..=999).ui(ui);
+
+            let name = self.new_loot.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Add Loot"))
+                .clicked()
+            {
+                commands.add(AddLoot {
+                    name: name.to_owned(),
+                    count: self.new_count,
+                });
+                self.new_loot.clear();
+                self.new_count = 1;
+            }
+        });
+
+        let mut require_approval = state.stash.require_approval;
+        if ui
+            .checkbox(&mut require_approval, "Claims need GM approval")
+            .changed()
+        {
+            commands.add(SetRequireApproval(require_approval));
+        }
+    }
+
+    fn loot_list(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        let is_gm = state.is_gm();
+
+        Grid::new("stash_loot").striped(true).show(ui, |ui| {
+            for (uuid, loot) in state.stash.sorted() {
+                if ui.link(&loot.item.name).clicked() {
+                    self.info = Some(*uuid);
+                }
+                ui.label(format!("x{}", loot.item.count));
+
+                match &loot.pending_claim {
+                    Some(character) if is_gm => {
+                        ui.label(format!("Claimed by {}", character));
+                        if ui.button("Approve").clicked() {
+                            commands.add(ResolveClaim {
+                                uuid: *uuid,
+                                approved: true,
+                            });
+                        }
+                        if ui.button("Deny").clicked() {
+                            commands.add(ResolveClaim {
+                                uuid: *uuid,
+                                approved: false,
+                            });
+                        }
+                    }
+                    Some(character) => {
+                        ui.weak(format!("Claimed by {}", character));
+                    }
+                    None => {
+                        if !is_gm && ui.button("Claim").clicked() {
+                            commands.add(ClaimLoot(*uuid));
+                        }
+                    }
+                }
+
+                if is_gm
+                    && ui
+                        .small_button(egui_phosphor::regular::TRASH)
+                        .on_hover_text("Remove from the stash")
+                        .clicked()
+                {
+                    commands.add(RemoveLoot(*uuid));
+                }
+                ui.end_row();
+            }
+        });
+
+        if state.stash.loot.is_empty() {
+            ui.weak("The party stash is empty");
+        }
+    }
+}
+
+impl DndTabImpl for Stash {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, commands: &mut CommandQueue) {
+        if state.is_gm() {
+            self.gm_ui(ui, state, commands);
+            ui.separator();
+        } else if state.stash.require_approval {
+            ui.weak("The GM has to approve claimed loot");
+        }
+
+        ScrollArea::vertical().show(ui, |ui| {
+            self.loot_list(ui, state, commands);
+        });
+
+        if let Some(loot) = self.info.and_then(|uuid| state.stash.loot.get(&uuid)) {
+            let mut open = true;
+            ItemInfo::new(&loot.item).show(ui.ctx(), &mut open);
+            if !open {
+                self.info = None;
+            }
+        }
+    }
+
+    fn title(&self) -> String {
+        "Party Stash".to_owned()
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use common::stats::RollStats;
+use egui::{Grid, ScrollArea, Sense};
+use itertools::Itertools;
+
+use crate::{listener::CommandQueue, prelude::*, state::theme};
+
+use super::DndTabImpl;
+
+const BAR_WIDTH: f32 = 5.0;
+const BAR_HEIGHT: f32 = 24.0;
+
+/// Everyone's public rolls, for this session or the whole campaign
+#[derive(Default)]
+pub struct Stats {
+    whole_campaign: bool,
+}
+
+/// "12 d20, 4 d6", largest dice first
+fn dice_label(stats: &RollStats) -> String {
+    stats
+        .dice
+        .iter()
+        .rev()
+        .map(|(die, count)| format!("{} d{}", count, die))
+        .join(", ")
+}
+
+/// One bar per d20 face, natural 1s on the left
+fn distribution(ui: &mut Ui, stats: &RollStats) {
+    let palette = theme::palette(ui.ctx());
+    let size = Vec2::new(BAR_WIDTH * 20.0, BAR_HEIGHT);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+
+    let most = stats.d20.iter().copied().max().unwrap_or_default().max(1);
+    for (face, count) in (1..).zip(stats.d20) {
+        let height = BAR_HEIGHT * count as f32 / most as f32;
+        let left = rect.left() + BAR_WIDTH * (face - 1) as f32;
+        let bar = Rect::from_min_max(
+            Pos2::new(left, rect.bottom() - height),
+            Pos2::new(left + BAR_WIDTH - 1.0, rect.bottom()),
+        );
+        let color = match face {
+            1 => palette.negative,
+            20 => palette.critical,
+            _ => palette.accent,
+        };
+        ui.painter().rect_filled(bar, 0.0, color);
+    }
+
+    response.on_hover_text(
+        (1..)
+            .zip(stats.d20)
+            .filter(|(_, count)| *count > 0)
+            .map(|(face, count)| format!("{}: {}", face, count))
+            .join("\n"),
+    );
+}
+
+impl Stats {
+    fn table(&self, ui: &mut Ui, stats: &BTreeMap<String, RollStats>) {
+        let palette = theme::palette(ui.ctx());
+        Grid::new("roll_stats").striped(true).show(ui, |ui| {
+            for title in [
+                "Player",
+                "Rolls",
+                "d20 average",
+                "Nat 20s",
+                "Nat 1s",
+                "d20s",
+            ] {
+                ui.strong(title);
+            }
+            ui.end_row();
+
+            for (player, stats) in stats {
+                ui.label(player);
+                ui.label(stats.rolls.to_string())
+                    .on_hover_text(dice_label(stats));
+                match stats.d20_average() {
+                    Some(average) => ui.label(format!("{:.1}", average)),
+                    None => ui.weak("-"),
+                };
+                ui.colored_label(palette.critical, stats.nat20s().to_string());
+                ui.colored_label(palette.negative, stats.nat1s().to_string());
+                distribution(ui, stats);
+                ui.end_row();
+            }
+        });
+    }
+}
+
+impl DndTabImpl for Stats {
+    fn ui(&mut self, ui: &mut egui::Ui, state: &DndState, _commands: &mut CommandQueue) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.whole_campaign, false, "This session");
+            ui.selectable_value(&mut self.whole_campaign, true, "Whole campaign");
+            ui.weak("Secret rolls aren't counted");
+        });
+        ui.separator();
+
+        let stats = if self.whole_campaign {
+            state.roll_stats.campaign()
+        } else {
+            state.roll_stats.session.clone()
+        };
+        if stats.is_empty() {
+            ui.weak("Nobody has rolled where everyone can see yet");
+            return;
+        }
+
+        ScrollArea::both().show(ui, |ui| self.table(ui, &stats));
+    }
+
+    fn title(&self) -> String {
+        "Stats".to_owned()
+    }
+}
@@ -0,0 +1,170 @@
+use common::{LifeState, PieceStatus};
+use egui::{Align2, FontId, Painter, Rounding};
+use egui_phosphor::regular as icons;
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::board::{commands::SetPieceStatuses, PlayerPiece},
+};
+
+/// Conditions every token can be marked with
+const PRESETS: [(&str, &str); 10] = [
+    ("Concentrating", icons::BRAIN),
+    ("Blessed", icons::STAR),
+    ("Restrained", icons::LOCK),
+    ("Poisoned", icons::DROP),
+    ("Prone", icons::ARROW_FAT_DOWN),
+    ("Stunned", icons::SPIRAL),
+    ("Invisible", icons::EYE_SLASH),
+    ("Frightened", icons::GHOST),
+    ("Charmed", icons::HEART),
+    ("Dead", icons::SKULL),
+];
+
+/// Glyphs to pick from for statuses that aren't presets
+const CUSTOM_ICONS: [&str; 12] = [
+    icons::FIRE,
+    icons::SNOWFLAKE,
+    icons::LIGHTNING,
+    icons::SHIELD,
+    icons::SWORD,
+    icons::MOON,
+    icons::FLAG,
+    icons::TARGET,
+    icons::HOURGLASS,
+    icons::FEATHER,
+    icons::SPARKLE,
+    icons::WARNING,
+];
+
+const ICON_SIZE: f32 = 11.0;
+
+/// Menu for toggling the status icons on a piece
+pub struct StatusForm {
+    name: String,
+    icon: &'static str,
+}
+
+impl Default for StatusForm {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            icon: CUSTOM_ICONS[0],
+        }
+    }
+}
+
+impl StatusForm {
+    pub fn ui(&mut self, ui: &mut Ui, id: Uuid, piece: &PlayerPiece, commands: &mut CommandQueue) {
+        let mut statuses = piece.statuses.clone();
+
+        for (name, icon) in PRESETS {
+            let mut checked = statuses.iter().any(|x| x.name == name);
+            if ui
+                .checkbox(&mut checked, format!("{icon} {name}"))
+                .changed()
+            {
+                toggle(&mut statuses, name, icon, checked);
+            }
+        }
+
+        // Custom statuses only show up while they're on the piece
+        let custom: Vec<_> = piece
+            .statuses
+            .iter()
+            .filter(|x| PRESETS.iter().all(|(name, _)| *name != x.name))
+            .collect();
+        if !custom.is_empty() {
+            ui.separator();
+        }
+        for status in custom {
+            let mut checked = true;
+            if ui
+                .checkbox(&mut checked, format!("{} {}", status.icon, status.name))
+                .changed()
+            {
+                toggle(&mut statuses, &status.name, &status.icon, false);
+            }
+        }
+
+        ui.separator();
+        ui.horizontal_wrapped(|ui| {
+            for icon in CUSTOM_ICONS {
+                ui.selectable_value(&mut self.icon, icon, icon);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.name)
+                .on_hover_text("Name of a custom status, ie. \"Hexed\"");
+
+            let name = self.name.trim();
+            let exists = statuses.iter().any(|x| x.name == name);
+            if ui
+                .add_enabled(!name.is_empty() && !exists, egui::Button::new("Add"))
+                .clicked()
+            {
+                toggle(&mut statuses, name, self.icon, true);
+                self.name.clear();
+            }
+        });
+
+        if statuses != piece.statuses {
+            commands.add(SetPieceStatuses(id, statuses));
+        }
+    }
+}
+
+fn toggle(statuses: &mut Vec<PieceStatus>, name: &str, icon: &str, on: bool) {
+    statuses.retain(|x| x.name != name);
+    if on {
+        statuses.push(PieceStatus {
+            name: name.to_owned(),
+            icon: icon.to_owned(),
+        });
+    }
+}
+
+/// Status icons in a row along the top right corner of the token, wrapping
+/// down the side when there are too many to fit
+pub fn paint_statuses(painter: &Painter, rect: Rect, statuses: &[PieceStatus]) {
+    let font = FontId::proportional(ICON_SIZE);
+    let per_row = ((rect.width() / ICON_SIZE) as usize).max(1);
+
+    for (i, status) in statuses.iter().enumerate() {
+        let (row, col) = (i / per_row, i % per_row);
+        let center =
+            rect.right_top() + Vec2::new(-(col as f32 + 0.5), row as f32 + 0.5) * ICON_SIZE;
+
+        painter.circle_filled(center, ICON_SIZE / 2.0, Color32::from_black_alpha(180));
+        painter.text(
+            center,
+            Align2::CENTER_CENTER,
+            &status.icon,
+            font.clone(),
+            Color32::WHITE,
+        );
+    }
+}
+
+/// Greys out the token of a character who's down, with how they're doing
+/// in the middle of it
+pub fn paint_life(painter: &Painter, rect: Rect, life: LifeState) {
+    let (icon, shade) = match life {
+        LifeState::Conscious => return,
+        LifeState::Dying { .. } => (icons::HEARTBEAT, 140),
+        LifeState::Stable => (icons::FIRST_AID, 140),
+        LifeState::Dead => (icons::SKULL, 200),
+    };
+
+    painter.rect_filled(rect, Rounding::ZERO, Color32::from_black_alpha(shade));
+    let size = (rect.width().min(rect.height()) * 0.5).max(ICON_SIZE);
+    painter.text(
+        rect.center(),
+        Align2::CENTER_CENTER,
+        icon,
+        FontId::proportional(size),
+        Color32::from_white_alpha(220),
+    );
+}
@@ -0,0 +1,48 @@
+use egui::{Align2, Area, Frame};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{theme, toasts::commands::DismissToast},
+};
+
+/// Server errors and confirmations stacked in the bottom right corner of the window
+pub fn show_toasts(ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+    if state.toasts.toasts.iter().all(|x| x.is_expired()) {
+        return;
+    }
+
+    Area::new(egui::Id::new("toasts"))
+        .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-8.0, -8.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            ui.set_max_width(250.0);
+
+            for (i, toast) in state.toasts.toasts.iter().enumerate() {
+                if toast.is_expired() {
+                    continue;
+                }
+
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let palette = theme::palette(ui.ctx());
+                        if toast.success {
+                            ui.colored_label(
+                                palette.positive,
+                                egui_phosphor::regular::CHECK_CIRCLE,
+                            );
+                        } else {
+                            ui.colored_label(palette.negative, egui_phosphor::regular::WARNING);
+                        }
+                        ui.strong(&toast.request_context);
+                        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button(egui_phosphor::regular::X).clicked() {
+                                commands.add(DismissToast(i));
+                            }
+                        });
+                    });
+                    ui.label(&toast.message);
+                });
+            }
+        });
+}
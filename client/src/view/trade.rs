@@ -0,0 +1,135 @@
+use common::Trade;
+use egui::{ahash::HashMap, DragValue, Grid};
+use uuid::Uuid;
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::{
+        theme,
+        trade::commands::{CancelTrade, ConfirmTrade, SetOffer},
+    },
+};
+
+/// Windows for every trade we're part of. Closing one cancels the trade
+#[derive(Default)]
+pub struct TradeWindows {
+    /// Counts being staged for our offer, before they're sent to the server
+    staged: HashMap<Uuid, HashMap<i64, u32>>,
+}
+
+fn confirmed_label(ui: &mut Ui, confirmed: bool) {
+    if confirmed {
+        ui.colored_label(
+            theme::palette(ui.ctx()).positive,
+            format!("{} Confirmed", egui_phosphor::regular::CHECK),
+        );
+    } else {
+        ui.weak("Not confirmed");
+    }
+}
+
+impl TradeWindows {
+    pub fn show(&mut self, ctx: &egui::Context, state: &DndState, commands: &mut CommandQueue) {
+        let name = state.owned_user().name;
+        self.staged
+            .retain(|uuid, _| state.trade.trades.contains_key(uuid));
+
+        for (uuid, trade) in state.trade.trades.iter() {
+            let Some(side) = trade.side(&name) else {
+                continue;
+            };
+
+            let mut open = true;
+            egui::Window::new(format!("Trade with {}", trade.players[1 - side]))
+                .id(egui::Id::new(("trade", uuid)))
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    self.trade_ui(ui, state, commands, *uuid, trade, side)
+                });
+
+            if !open {
+                commands.add(CancelTrade(*uuid));
+            }
+        }
+    }
+
+    fn trade_ui(
+        &mut self,
+        ui: &mut Ui,
+        state: &DndState,
+        commands: &mut CommandQueue,
+        uuid: Uuid,
+        trade: &Trade,
+        side: usize,
+    ) {
+        let staged = self
+            .staged
+            .entry(uuid)
+            .or_insert_with(|| trade.offers[side].iter().map(|x| (x.id, x.count)).collect());
+
+        ui.columns(2, |columns| {
+            let ui = &mut columns[0];
+            ui.strong("You give");
+            Grid::new(("trade_ours", uuid)).show(ui, |ui| {
+                for item in state.character.items.iter() {
+                    ui.label(&item.name);
+                    let count = staged.entry(item.id).or_default();
+                    DragValue::new(count).range(0..=item.count).ui(ui);
+                    ui.end_row();
+                }
+            });
+            confirmed_label(ui, trade.confirmed[side]);
+
+            let ui = &mut columns[1];
+            ui.strong(format!("{} gives", trade.players[1 - side]));
+            for item in trade.offers[1 - side].iter() {
+                ui.label(format!("{} x{}", item.name, item.count));
+            }
+            if trade.offers[1 - side].is_empty() {
+                ui.weak("Nothing yet");
+            }
+            confirmed_label(ui, trade.confirmed[1 - side]);
+        });
+
+        let offer: Vec<Item> = state
+            .character
+            .items
+            .iter()
+            .filter_map(|item| match staged.get(&item.id) {
+                Some(count) if *count > 0 => Some(Item {
+                    count: *count,
+                    slot: None,
+                    attuned: false,
+                    ..item.clone()
+                }),
+                _ => None,
+            })
+            .collect();
+        let changed = offer.len() != trade.offers[side].len()
+            || offer
+                .iter()
+                .zip(trade.offers[side].iter())
+                .any(|(a, b)| a.id != b.id || a.count != b.count);
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if changed {
+                if ui.button("Update Offer").clicked() {
+                    commands.add(SetOffer(uuid, offer));
+                }
+            } else if ui
+                .add_enabled(!trade.confirmed[side], egui::Button::new("Confirm"))
+                .on_hover_text("Nothing changes hands until both of you confirm")
+                .clicked()
+            {
+                commands.add(ConfirmTrade(uuid));
+            }
+
+            if ui.button("Cancel").clicked() {
+                commands.add(CancelTrade(uuid));
+            }
+        });
+    }
+}
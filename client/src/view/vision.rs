@@ -0,0 +1,170 @@
+use common::{polygon_contains, visibility_polygon, Wall};
+use egui::{Mesh, Painter, Shape};
+
+use crate::{prelude::*, state::board::PlayerPiece};
+
+const FOG_COLOR: Color32 = Color32::from_rgb(12, 12, 16);
+/// Screen space spacing of the fog mesh. The fog fades out across one cell,
+/// which softens the edges of the visible area
+const CELL_SIZE: f32 = 10.0;
+
+/// What each of the player's tokens with vision can see, in screen space
+struct Sight {
+    center: Pos2,
+    radius: f32,
+    area: Vec<Pos2>,
+}
+
+impl Sight {
+    fn contains(&self, pos: Pos2) -> bool {
+        self.center.distance(pos) <= self.radius && polygon_contains(&self.area, pos)
+    }
+}
+
+/// The fog from the last frame, only rebuilt once the view, a wall or one of
+/// the tokens with vision changes
+#[derive(Default)]
+pub struct FogCache {
+    /// Everything the fog was built from, flattened
+    key: Vec<f32>,
+    mesh: Option<Mesh>,
+}
+
+impl FogCache {
+    /// Covers everything the player's tokens can't see. The GM sees everything,
+    /// and so do players without a token that has vision on this board
+    pub fn paint(
+        &mut self,
+        painter: &Painter,
+        rect: Rect,
+        to_screen: RectTransform,
+        state: &DndState,
+    ) {
+        if state.is_gm() {
+            return;
+        }
+
+        let user = state.owned_user();
+        self.paint_for(painter, rect, to_screen, state, |x| {
+            x.owners.contains(&user.name)
+        });
+    }
+
+    /// Covers everything none of the players' tokens can see, for a screen the
+    /// whole table shares
+    pub fn paint_party(
+        &mut self,
+        painter: &Painter,
+        rect: Rect,
+        to_screen: RectTransform,
+        state: &DndState,
+    ) {
+        let gm = state.gm.as_ref();
+        self.paint_for(painter, rect, to_screen, state, |x| {
+            x.owners.iter().any(|owner| Some(owner) != gm)
+        });
+    }
+
+    /// Fog around the tokens `sees` picks out, nothing is covered if none of them have vision
+    fn paint_for(
+        &mut self,
+        painter: &Painter,
+        rect: Rect,
+        to_screen: RectTransform,
+        state: &DndState,
+        sees: impl Fn(&PlayerPiece) -> bool,
+    ) {
+        let spacing = state.board.grid.spacing;
+        let walls: Vec<Wall> = state.board.active_walls().map(|(_, x)| *x).collect();
+        let eyes: Vec<(Pos2, f32)> = state
+            .board
+            .active_players()
+            .filter(|(_, x)| sees(x))
+            .filter_map(|(_, piece)| Some((piece.display_rect().center(), piece.vision? * spacing)))
+            .collect();
+
+        let mut key = vec![
+            rect.min.x,
+            rect.min.y,
+            rect.max.x,
+            rect.max.y,
+            to_screen.from().min.x,
+            to_screen.from().min.y,
+            to_screen.from().max.x,
+            to_screen.from().max.y,
+        ];
+        key.extend(
+            eyes.iter()
+                .flat_map(|(center, radius)| [center.x, center.y, *radius]),
+        );
+        key.extend(
+            walls
+                .iter()
+                .filter(|x| !x.is_open())
+                .flat_map(|x| [x.start.x, x.start.y, x.end.x, x.end.y]),
+        );
+
+        if key != self.key {
+            self.mesh = fog_mesh(rect, to_screen, &eyes, &walls);
+            self.key = key;
+        }
+
+        if let Some(mesh) = &self.mesh {
+            painter.add(Shape::mesh(mesh.clone()));
+        }
+    }
+}
+
+/// None if no token has vision
+fn fog_mesh(
+    rect: Rect,
+    to_screen: RectTransform,
+    eyes: &[(Pos2, f32)],
+    walls: &[Wall],
+) -> Option<Mesh> {
+    let sights: Vec<_> = eyes
+        .iter()
+        .map(|&(center, radius)| {
+            let area = visibility_polygon(center, radius, walls)
+                .into_iter()
+                .map(|x| to_screen * x)
+                .collect();
+
+            Sight {
+                center: to_screen * center,
+                radius: radius * to_screen.scale().x,
+                area,
+            }
+        })
+        .collect();
+
+    if sights.is_empty() {
+        return None;
+    }
+
+    let cols = (rect.width() / CELL_SIZE).ceil().max(0.0) as u32 + 1;
+    let rows = (rect.height() / CELL_SIZE).ceil().max(0.0) as u32 + 1;
+
+    let mut mesh = Mesh::default();
+    for row in 0..rows {
+        for col in 0..cols {
+            let pos = rect.min + Vec2::new(col as f32, row as f32) * CELL_SIZE;
+            let color = if sights.iter().any(|x| x.contains(pos)) {
+                Color32::TRANSPARENT
+            } else {
+                FOG_COLOR
+            };
+            mesh.colored_vertex(pos, color);
+        }
+    }
+
+    for row in 0..rows.saturating_sub(1) {
+        for col in 0..cols.saturating_sub(1) {
+            let i = row * cols + col;
+            mesh.add_triangle(i, i + 1, i + cols);
+            mesh.add_triangle(i + 1, i + cols + 1, i + cols);
+        }
+    }
+
+    Some(mesh)
+}
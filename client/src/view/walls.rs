@@ -0,0 +1,181 @@
+use common::{GridKind, Wall};
+use egui::{Painter, Shape, Stroke};
+
+use crate::{
+    listener::CommandQueue,
+    prelude::*,
+    state::board::{
+        commands::{snap_to_grid, AddWall, DeleteWall, SetDoorOpen},
+        BoardState,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WallTool {
+    Wall,
+    Door,
+}
+
+/// GM tool for drawing walls and doors onto the active board. Stays active so
+/// a room can be drawn one wall after another
+pub struct WallEditor {
+    tool: Option<WallTool>,
+    blocks_movement: bool,
+    /// Canvas space ends of the wall being drawn
+    drag: Option<(Pos2, Pos2)>,
+}
+
+impl Default for WallEditor {
+    fn default() -> Self {
+        Self {
+            tool: None,
+            blocks_movement: true,
+            drag: None,
+        }
+    }
+}
+
+impl WallEditor {
+    const WALL_COLOR: Color32 = Color32::from_rgb(40, 40, 40);
+    const SIGHT_ONLY_COLOR: Color32 = Color32::from_rgb(110, 110, 140);
+    const DOOR_COLOR: Color32 = Color32::from_rgb(150, 100, 50);
+    const FIND_TOLERANCE: f32 = 0.02;
+
+    pub fn is_drawing(&self) -> bool {
+        self.tool.is_some()
+    }
+
+    /// Context menu entries for the wall or door under `pos`, and the drawing tools
+    pub fn menu(&mut self, ui: &mut Ui, pos: Pos2, state: &DndState, commands: &mut CommandQueue) {
+        let wall = state.board.find_wall(pos, Self::FIND_TOLERANCE);
+
+        if let Some((id, wall)) = wall.filter(|(_, x)| x.door.is_some()) {
+            let text = if wall.is_open() {
+                "Close Door"
+            } else {
+                "Open Door"
+            };
+            if ui.button(text).clicked() {
+                commands.add(SetDoorOpen(*id, !wall.is_open()));
+                ui.close_menu();
+            }
+        }
+
+        ui.menu_button("Walls", |ui| {
+            if self.is_drawing() {
+                if ui.button("Stop Drawing").clicked() {
+                    self.tool = None;
+                    self.drag = None;
+                    ui.close_menu();
+                }
+            } else {
+                if ui.button("Draw Walls").clicked() {
+                    self.tool = Some(WallTool::Wall);
+                    ui.close_menu();
+                }
+                if ui.button("Draw Doors").clicked() {
+                    self.tool = Some(WallTool::Door);
+                    ui.close_menu();
+                }
+            }
+
+            ui.checkbox(&mut self.blocks_movement, "Blocks movement")
+                .on_hover_text("Otherwise new walls only block line of sight");
+
+            if let Some((id, _)) = wall {
+                if ui.button("Delete Wall").clicked() {
+                    commands.add(DeleteWall(*id));
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    pub fn handle_input(
+        &mut self,
+        response: &egui::Response,
+        from_screen: RectTransform,
+        state: &DndState,
+        commands: &mut CommandQueue,
+    ) {
+        if response.ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.tool = None;
+            self.drag = None;
+            return;
+        }
+
+        // Walls join up at the grid corners on square grids
+        let grid = &state.board.grid;
+        let pointer = response.interact_pointer_pos().map(|x| match grid.kind {
+            GridKind::Square => snap_to_grid(grid, from_screen * x),
+            _ => from_screen * x,
+        });
+
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            self.drag = pointer.map(|x| (x, x));
+        }
+
+        if let (Some((start, _)), Some(end)) = (self.drag, pointer) {
+            self.drag = Some((start, end));
+        }
+
+        if response.drag_stopped_by(egui::PointerButton::Primary) {
+            if let Some((start, end)) = self.drag.take().filter(|(a, b)| a != b) {
+                commands.add(AddWall(Wall {
+                    start,
+                    end,
+                    door: (self.tool == Some(WallTool::Door)).then_some(false),
+                    blocks_movement: self.blocks_movement,
+                }));
+            }
+        }
+    }
+
+    pub fn paint(&self, painter: &Painter, to_screen: RectTransform, state: &DndState) {
+        paint_walls(painter, to_screen, &state.board);
+
+        if let (Some(tool), Some((start, end))) = (self.tool, self.drag) {
+            let wall = Wall {
+                start,
+                end,
+                door: (tool == WallTool::Door).then_some(false),
+                blocks_movement: self.blocks_movement,
+            };
+            paint_wall(painter, to_screen, &wall);
+        }
+    }
+}
+
+/// Every wall and door on the active board
+pub fn paint_walls(painter: &Painter, to_screen: RectTransform, board: &BoardState) {
+    for (_, wall) in board.active_walls() {
+        paint_wall(painter, to_screen, wall);
+    }
+}
+
+fn paint_wall(painter: &Painter, to_screen: RectTransform, wall: &Wall) {
+    let points = [to_screen * wall.start, to_screen * wall.end];
+
+    match wall.door {
+        // Open doors are dashed so they're still easy to find and close again
+        Some(true) => {
+            painter.extend(Shape::dashed_line(
+                &points,
+                Stroke::new(2.0, WallEditor::DOOR_COLOR),
+                6.0,
+                4.0,
+            ));
+        }
+        Some(false) => {
+            painter.line_segment(points, Stroke::new(6.0, WallEditor::DOOR_COLOR));
+        }
+        None => {
+            let color = if wall.blocks_movement {
+                WallEditor::WALL_COLOR
+            } else {
+                WallEditor::SIGHT_ONLY_COLOR
+            };
+            painter.line_segment(points, Stroke::new(4.0, color));
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Stand-ins for the parts of the client that need threads or sockets, which
+//! the browser doesn't have. Everything here runs on the UI thread and is
+//! polled once a frame instead, apart from the heartbeat
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+use common::{message::DndMessage, User};
+use ewebsock::{Options, WsEvent, WsMessage, WsReceiver, WsSender};
+use log::{info, warn};
+use wasm_bindgen::{closure::Closure, JsCast};
+
+use crate::listener::{now_millis, ListenerStats, Signal, HEARTBEAT_INTERVAL};
+
+/// Matches message-io's `EventSender`, so commands don't need to know which
+/// build they're running in
+pub struct EventSender<S>(Sender<S>);
+
+impl<S> EventSender<S> {
+    pub fn send(&self, event: S) {
+        // The listener is only gone once the app is closing
+        let _ = self.0.send(event);
+    }
+}
+
+impl<S> Clone for EventSender<S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+fn send(ws_sender: &RefCell<WsSender>, stats: &ListenerStats, message: &DndMessage) {
+    let data = bincode::serialize(message).unwrap();
+    ws_sender.borrow_mut().send(WsMessage::Binary(data));
+    stats.count_sent();
+}
+
+/// Pings the server from a browser timer. Frames stop in background tabs, so
+/// pinging from `poll` would get the player dropped for going quiet
+struct Heartbeat {
+    id: i32,
+    _callback: Closure<dyn FnMut()>,
+}
+
+impl Heartbeat {
+    fn start(
+        ws_sender: Rc<RefCell<WsSender>>,
+        connected: Rc<Cell<bool>>,
+        stats: Arc<ListenerStats>,
+    ) -> Option<Self> {
+        let callback = Closure::<dyn FnMut()>::new(move || {
+            if connected.get() {
+                send(&ws_sender, &stats, &DndMessage::Ping(now_millis()));
+            }
+        });
+        let id = web_sys::window()?
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                callback.as_ref().unchecked_ref(),
+                HEARTBEAT_INTERVAL.as_millis() as i32,
+            )
+            .inspect_err(|e| warn!("Could not start the heartbeat: {e:?}"))
+            .ok()?;
+
+        Some(Self {
+            id,
+            _callback: callback,
+        })
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            window.clear_interval_with_handle(self.id);
+        }
+    }
+}
+
+/// Talks to the server over the browser's WebSocket
+pub struct DndListener {
+    user: User,
+    /// Shared with the heartbeat
+    ws_sender: Rc<RefCell<WsSender>>,
+    ws_receiver: WsReceiver,
+    signals: Receiver<Signal>,
+    signal_sender: Sender<Signal>,
+    tx: Sender<DndMessage>,
+    connected: Rc<Cell<bool>>,
+    stats: Arc<ListenerStats>,
+    _heartbeat: Option<Heartbeat>,
+}
+
+impl DndListener {
+    pub fn new(tx: Sender<DndMessage>, user: User, server_addr: &str) -> Result<Self, String> {
+        // The native client takes a bare address, browsers need to be told the scheme
+        let url = if server_addr.contains("://") {
+            server_addr.to_owned()
+        } else {
+            format!("ws://{server_addr}")
+        };
+        let (ws_sender, ws_receiver) = ewebsock::connect(url, Options::default())?;
+        let (signal_sender, signals) = channel();
+
+        let ws_sender = Rc::new(RefCell::new(ws_sender));
+        let connected = Rc::new(Cell::new(false));
+        let stats = Arc::<ListenerStats>::default();
+        let heartbeat = Heartbeat::start(ws_sender.clone(), connected.clone(), stats.clone());
+
+        Ok(Self {
+            user,
+            ws_sender,
+            ws_receiver,
+            signals,
+            signal_sender,
+            tx,
+            connected,
+            stats,
+            _heartbeat: heartbeat,
+        })
+    }
+
+    pub fn event_sender(&self) -> EventSender<Signal> {
+        EventSender(self.signal_sender.clone())
+    }
+
+    pub fn stats(&self) -> Arc<ListenerStats> {
+        self.stats.clone()
+    }
+
+    fn send(&self, message: &DndMessage) {
+        send(&self.ws_sender, &self.stats, message);
+    }
+
+    /// Handles everything that came in since the last frame
+    pub fn poll(&mut self) {
+        while let Some(event) = self.ws_receiver.try_recv() {
+            match event {
+                WsEvent::Opened => {
+                    self.connected.set(true);
+                    self.send(&DndMessage::RegisterUser(self.user.name.clone()));
+                    self.send(&DndMessage::RetrieveCharacterData(self.user.clone()));
+                }
+                WsEvent::Message(WsMessage::Binary(data)) => match bincode::deserialize(&data) {
+                    Ok(message) => {
+                        self.stats.count_received(&message);
+                        self.tx.send(message).unwrap()
+                    }
+                    Err(e) => warn!("Could not read message from the server: {e}"),
+                },
+                WsEvent::Message(_) => {}
+                WsEvent::Error(e) => warn!("Connection error: {e}"),
+                WsEvent::Closed => {
+                    info!("Server is disconnected");
+                    self.connected.set(false);
+                }
+            }
+        }
+
+        // Anything sent before the socket opens waits for it
+        if !self.connected.get() {
+            return;
+        }
+
+        while let Ok(signal) = self.signals.try_recv() {
+            match signal {
+                Signal::ClientMessage(msg) => {
+                    self.send(&msg);
+                    // Immediately send the message back to ourself
+                    self.signal_sender
+                        .send(Signal::RecieveMessage(msg))
+                        .unwrap();
+                }
+                Signal::RecieveMessage(msg) => {
+                    self.tx.send(msg).unwrap();
+                }
+                Signal::Heartbeat | Signal::SaveLocal => {}
+            }
+        }
+    }
+}
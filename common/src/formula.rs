@@ -0,0 +1,262 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{ruleset::Ruleset, Character};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    Str,
+    Dex,
+    Con,
+    Int,
+    Wis,
+    Cha,
+}
+
+impl Stat {
+    pub const ALL: [Stat; 6] = [
+        Stat::Str,
+        Stat::Dex,
+        Stat::Con,
+        Stat::Int,
+        Stat::Wis,
+        Stat::Cha,
+    ];
+
+    pub fn score(&self, character: &Character) -> i16 {
+        match self {
+            Stat::Str => character.str,
+            Stat::Dex => character.dex,
+            Stat::Con => character.con,
+            Stat::Int => character.int,
+            Stat::Wis => character.wis,
+            Stat::Cha => character.cha,
+        }
+    }
+
+    pub fn modifier(&self, character: &Character, ruleset: &Ruleset) -> i32 {
+        ruleset.stat_modifier(*self, character)
+    }
+}
+
+impl Display for Stat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stat::Str => write!(f, "STR"),
+            Stat::Dex => write!(f, "DEX"),
+            Stat::Con => write!(f, "CON"),
+            Stat::Int => write!(f, "INT"),
+            Stat::Wis => write!(f, "WIS"),
+            Stat::Cha => write!(f, "CHA"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormulaTerm {
+    Dice { count: u32, sides: u32 },
+    Stat(Stat),
+    Proficiency,
+    Flat(i32),
+}
+
+/// A roll like `1d20 + DEX + PROF` or `2d6 + STR - 1`. Stored as text so it
+/// can be written by hand, but only valid formulas ever make it into an ability
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct RollFormula {
+    pub terms: Vec<FormulaTerm>,
+}
+
+impl RollFormula {
+    /// (count, sides) of the dice to roll
+    pub fn dice(&self) -> (u32, u32) {
+        self.terms
+            .iter()
+            .find_map(|term| match term {
+                FormulaTerm::Dice { count, sides } => Some((*count, *sides)),
+                _ => None,
+            })
+            .unwrap_or((1, 20))
+    }
+
+    /// Everything added on top of the dice for this character. Stat modifiers
+    /// and the proficiency bonus both depend on the campaign's ruleset
+    pub fn modifier(&self, character: &Character, ruleset: &Ruleset) -> i32 {
+        self.modifier_for(Some(character), ruleset)
+    }
+
+    /// Like [`Self::modifier`], but stats and proficiency count for nothing
+    /// without a character, ie. for a token with no sheet
+    pub fn modifier_for(&self, character: Option<&Character>, ruleset: &Ruleset) -> i32 {
+        self.terms
+            .iter()
+            .map(|term| match (term, character) {
+                (FormulaTerm::Flat(bonus), _) => *bonus,
+                (FormulaTerm::Stat(stat), Some(character)) => stat.modifier(character, ruleset),
+                (FormulaTerm::Proficiency, Some(character)) => {
+                    ruleset.proficiency_bonus(character.level)
+                }
+                _ => 0,
+            })
+            .fold(0, i32::saturating_add)
+    }
+}
+
+/// Anything more is a typo, and would take a while to roll
+pub const MAX_DICE: u32 = 100;
+pub const MAX_SIDES: u32 = 1000;
+/// Bigger bonuses are typos too, and could overflow once added up
+pub const MAX_BONUS: i32 = 1000;
+
+fn parse_term(term: &str) -> Result<FormulaTerm, String> {
+    let upper = term.to_uppercase();
+    let stat = match upper.as_str() {
+        "STR" => Some(Stat::Str),
+        "DEX" => Some(Stat::Dex),
+        "CON" => Some(Stat::Con),
+        "INT" => Some(Stat::Int),
+        "WIS" => Some(Stat::Wis),
+        "CHA" => Some(Stat::Cha),
+        _ => None,
+    };
+    if let Some(stat) = stat {
+        return Ok(FormulaTerm::Stat(stat));
+    }
+    if upper == "PROF" {
+        return Ok(FormulaTerm::Proficiency);
+    }
+
+    if let Some((count, sides)) = upper.split_once('D') {
+        let count = if count.is_empty() {
+            1
+        } else {
+            count
+                .parse()
+                .map_err(|_| format!("'{}' isn't a number of dice", count))?
+        };
+        let sides: u32 = sides
+            .parse()
+            .map_err(|_| format!("'{}' isn't a die size", sides))?;
+        if count == 0 || sides == 0 {
+            return Err(format!("'{}' doesn't roll anything", term));
+        }
+        if count > MAX_DICE {
+            return Err(format!("Can't roll more than {} dice at once", MAX_DICE));
+        }
+        if sides > MAX_SIDES {
+            return Err(format!("Dice can't have more than {} sides", MAX_SIDES));
+        }
+        return Ok(FormulaTerm::Dice { count, sides });
+    }
+
+    let bonus: i32 = upper.parse().map_err(|_| {
+        format!(
+            "Unknown term '{}', expected dice, a stat, PROF or a number",
+            term
+        )
+    })?;
+    if bonus > MAX_BONUS {
+        return Err(format!("Bonuses can't be more than {}", MAX_BONUS));
+    }
+    Ok(FormulaTerm::Flat(bonus))
+}
+
+impl FromStr for RollFormula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut terms = Vec::new();
+        let mut negative = false;
+
+        for token in s.replace('-', " - ").replace('+', " + ").split_whitespace() {
+            match token {
+                "+" => {}
+                "-" => negative = !negative,
+                _ => {
+                    let term = match (parse_term(token)?, negative) {
+                        (FormulaTerm::Flat(bonus), true) => FormulaTerm::Flat(-bonus),
+                        (_, true) => return Err("Only numbers can be subtracted".to_owned()),
+                        (term, false) => term,
+                    };
+                    terms.push(term);
+                    negative = false;
+                }
+            }
+        }
+
+        let dice = terms
+            .iter()
+            .filter(|x| matches!(x, FormulaTerm::Dice { .. }))
+            .count();
+        if dice != 1 {
+            return Err("A formula needs exactly one kind of dice, ie. 1d20".to_owned());
+        }
+
+        Ok(Self { terms })
+    }
+}
+
+/// For formulas loaded as part of a list. One saved before the current rules
+/// (or edited by hand) is dropped instead of failing the whole list
+pub fn deserialize_lenient<'de, D>(deserializer: D) -> Result<Option<RollFormula>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(text.and_then(|x| x.parse().ok()))
+}
+
+impl TryFrom<String> for RollFormula {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<RollFormula> for String {
+    fn from(value: RollFormula) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for RollFormula {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            let negative = matches!(term, FormulaTerm::Flat(x) if *x < 0);
+            match (i, negative) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            match term {
+                FormulaTerm::Dice { count, sides } => write!(f, "{}d{}", count, sides)?,
+                FormulaTerm::Stat(stat) => write!(f, "{}", stat)?,
+                FormulaTerm::Proficiency => write!(f, "PROF")?,
+                FormulaTerm::Flat(bonus) => write!(f, "{}", bonus.abs())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_bonuses_are_added_up() {
+        let formula: RollFormula = "1d20 + 5 - 2".parse().unwrap();
+        assert_eq!(formula.modifier_for(None, &Ruleset::default()), 3);
+    }
+
+    #[test]
+    fn huge_bonuses_are_rejected() {
+        assert!("1d20 + 2147483647 + 1".parse::<RollFormula>().is_err());
+        assert!("1d20 - 2147483647".parse::<RollFormula>().is_err());
+        assert!("1d20 + 99999999999".parse::<RollFormula>().is_err());
+    }
+}
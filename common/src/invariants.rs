@@ -0,0 +1,107 @@
+//! Things that should always hold for a character's sheet however the
+//! messages that built it arrived. Broken ones point at a sync bug
+
+use std::collections::HashSet;
+
+use crate::{Ability, Character, Item};
+
+/// What's wrong with the sheet, empty if nothing is
+pub fn check_sheet(character: &Character, items: &[Item], abilities: &[Ability]) -> Vec<String> {
+    let mut broken = Vec::new();
+
+    if character.power_slots < 0 {
+        broken.push(format!(
+            "{} has {} power slots",
+            character.name, character.power_slots
+        ));
+    }
+
+    for ability in abilities {
+        if !(0..=ability.max_count).contains(&ability.uses) {
+            broken.push(format!(
+                "{}'s {} has {} of {} uses",
+                character.name, ability.name, ability.uses, ability.max_count
+            ));
+        }
+    }
+
+    let mut ids = HashSet::new();
+    for item in items.iter().filter(|x| !ids.insert(x.id)) {
+        broken.push(format!(
+            "{} has item {} ({}) more than once",
+            character.name, item.id, item.name
+        ));
+    }
+
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(power_slots: i16) -> Character {
+        Character {
+            name: "Alice".to_owned(),
+            power_slots,
+            ..Default::default()
+        }
+    }
+
+    fn ability(uses: i64, max_count: i64) -> Ability {
+        Ability {
+            name: "Second Wind".to_owned(),
+            description: String::new(),
+            notes: None,
+            ability_type: "Action".to_owned(),
+            flavor_text: None,
+            resource: "UseToken".to_owned(),
+            max_count,
+            uses,
+            to_hit: None,
+            damage: None,
+            recharge: None,
+        }
+    }
+
+    fn item(id: i64) -> Item {
+        Item {
+            id,
+            count: 1,
+            name: format!("Item {id}"),
+            description: String::new(),
+            flavor_text: String::new(),
+            quest_item: false,
+            slot: None,
+            armor_class: None,
+            attack_bonus: None,
+            requires_attunement: false,
+            attuned: false,
+            weight: None,
+        }
+    }
+
+    #[test]
+    fn power_slots_cant_be_negative() {
+        assert!(check_sheet(&character(0), &[], &[]).is_empty());
+        assert_eq!(check_sheet(&character(-1), &[], &[]).len(), 1);
+    }
+
+    #[test]
+    fn uses_stay_within_the_max() {
+        let abilities = [ability(0, 2), ability(2, 2)];
+        assert!(check_sheet(&character(0), &[], &abilities).is_empty());
+
+        let abilities = [ability(-1, 2), ability(3, 2)];
+        assert_eq!(check_sheet(&character(0), &[], &abilities).len(), 2);
+    }
+
+    #[test]
+    fn items_are_only_listed_once() {
+        assert!(check_sheet(&character(0), &[item(1), item(2)], &[]).is_empty());
+        assert_eq!(
+            check_sheet(&character(0), &[item(1), item(1)], &[]).len(),
+            1
+        );
+    }
+}
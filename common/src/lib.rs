@@ -1,6 +1,17 @@
-use emath::{Pos2, Vec2};
+use std::{collections::HashMap, fmt::Display};
 
+use emath::{Pos2, Rect, Vec2};
+use formula::RollFormula;
+use ruleset::Diagonals;
+use uuid::Uuid;
+
+pub mod formula;
+pub mod invariants;
 pub mod message;
+pub mod rules;
+pub mod ruleset;
+pub mod soundboard;
+pub mod stats;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct User {
@@ -23,6 +34,145 @@ pub struct Item {
     pub description: String,
     pub flavor_text: String,
     pub quest_item: bool,
+    pub slot: Option<EquipSlot>,
+    /// Base AC when worn as armor, otherwise a flat bonus to AC
+    pub armor_class: Option<i16>,
+    pub attack_bonus: Option<i16>,
+    /// Magic items that only work for someone attuned to them
+    #[serde(default)]
+    pub requires_attunement: bool,
+    /// Per character like the slot, cleared when the item changes hands
+    #[serde(default)]
+    pub attuned: bool,
+    /// In pounds, for one of them
+    #[serde(default)]
+    pub weight: Option<f32>,
+}
+
+/// Two players swapping items. Coins are items like anything else, so they can
+/// be staged the same way. Nothing changes hands until both sides confirm
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Trade {
+    pub players: [String; 2],
+    /// Items each player is giving away, with the count being traded
+    pub offers: [Vec<Item>; 2],
+    pub confirmed: [bool; 2],
+}
+
+impl Trade {
+    pub fn new(from: String, to: String) -> Self {
+        Self {
+            players: [from, to],
+            offers: Default::default(),
+            confirmed: [false; 2],
+        }
+    }
+
+    /// Which side of the trade the player is on
+    pub fn side(&self, name: &str) -> Option<usize> {
+        self.players.iter().position(|x| x == name)
+    }
+}
+
+/// A problem a player ran into, sent from the client so it can be looked at after the session
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct IssueReport {
+    pub version: String,
+    pub description: String,
+    /// Recent client log lines, empty if the player chose not to include them
+    pub log: String,
+}
+
+/// A saved copy of every character along with their items and abilities
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SnapshotInfo {
+    pub id: Uuid,
+    pub tag: String,
+    /// Taken on a timer by the server rather than by the GM
+    pub automatic: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// How much room the snapshots are taking up
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default)]
+pub struct SnapshotUsage {
+    pub automatic: usize,
+    pub tagged: usize,
+    /// Size of the saved data
+    pub bytes: u64,
+    /// How many automatic snapshots are kept, `None` when none are taken
+    pub keep_automatic: Option<usize>,
+}
+
+impl Display for SnapshotUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} snapshots ({} automatic, {} tagged) using {:.1} KB",
+            self.automatic + self.tagged,
+            self.automatic,
+            self.tagged,
+            self.bytes as f64 / 1024.0
+        )?;
+        if let Some(keep) = self.keep_automatic {
+            write!(f, ", keeping the last {keep} automatic ones")?;
+        }
+        Ok(())
+    }
+}
+
+/// An item sitting in the party stash
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Loot {
+    pub item: Item,
+    /// Character waiting on the GM to approve their claim
+    pub pending_claim: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipSlot {
+    Armor,
+    Shield,
+    MainHand,
+    OffHand,
+    Head,
+    Neck,
+    Hands,
+    Feet,
+    RingLeft,
+    RingRight,
+}
+
+impl EquipSlot {
+    pub const ALL: [EquipSlot; 10] = [
+        EquipSlot::Armor,
+        EquipSlot::Shield,
+        EquipSlot::MainHand,
+        EquipSlot::OffHand,
+        EquipSlot::Head,
+        EquipSlot::Neck,
+        EquipSlot::Hands,
+        EquipSlot::Feet,
+        EquipSlot::RingLeft,
+        EquipSlot::RingRight,
+    ];
+}
+
+impl Display for EquipSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EquipSlot::Armor => write!(f, "Armor"),
+            EquipSlot::Shield => write!(f, "Shield"),
+            EquipSlot::MainHand => write!(f, "Main Hand"),
+            EquipSlot::OffHand => write!(f, "Off Hand"),
+            EquipSlot::Head => write!(f, "Head"),
+            EquipSlot::Neck => write!(f, "Neck"),
+            EquipSlot::Hands => write!(f, "Hands"),
+            EquipSlot::Feet => write!(f, "Feet"),
+            EquipSlot::RingLeft => write!(f, "Ring (L)"),
+            EquipSlot::RingRight => write!(f, "Ring (R)"),
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -35,9 +185,51 @@ pub struct Ability {
     pub resource: String,
     pub max_count: i64,
     pub uses: i64,
+    #[serde(default, deserialize_with = "formula::deserialize_lenient")]
+    pub to_hit: Option<RollFormula>,
+    #[serde(default, deserialize_with = "formula::deserialize_lenient")]
+    pub damage: Option<RollFormula>,
+    #[serde(default)]
+    pub recharge: Option<Recharge>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+/// Catalog entry for an item, without any per character inventory data
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub flavor_text: String,
+    #[serde(default)]
+    pub quest_item: bool,
+    pub armor_class: Option<i16>,
+    pub attack_bonus: Option<i16>,
+    #[serde(default)]
+    pub requires_attunement: bool,
+    /// In pounds
+    #[serde(default)]
+    pub weight: Option<f32>,
+}
+
+/// Catalog entry for an ability, without any per character usage data
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AbilityDefinition {
+    pub name: String,
+    pub description: String,
+    pub notes: Option<String>,
+    pub ability_type: String,
+    pub flavor_text: Option<String>,
+    pub resource: String,
+    pub max_count: i64,
+    #[serde(default)]
+    pub to_hit: Option<RollFormula>,
+    #[serde(default)]
+    pub damage: Option<RollFormula>,
+    #[serde(default)]
+    pub recharge: Option<Recharge>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Character {
     pub name: String,
     pub int: i16,
@@ -49,7 +241,319 @@ pub struct Character {
     pub tagline: String,
     pub backstory: String,
     pub skills: Vec<String>,
-    pub power_slots: i16
+    pub power_slots: i16,
+    pub ac_override: Option<i16>,
+    #[serde(default)]
+    pub xp: u32,
+    /// Only goes up once the player has worked through their level up
+    #[serde(default = "default_level")]
+    pub level: u32,
+    #[serde(default = "default_max_power_slots")]
+    pub max_power_slots: i16,
+    /// Inspiration, luck or hero points, granted by the GM and spent by the player
+    #[serde(default)]
+    pub inspiration: u32,
+    #[serde(default)]
+    pub curr_hp: i32,
+    #[serde(default)]
+    pub max_hp: i32,
+    /// Whether they're still standing, kept up to date as their HP changes
+    #[serde(default)]
+    pub life: LifeState,
+    /// Image used for the character's token on the board
+    #[serde(default)]
+    pub portrait: Option<String>,
+    /// Bumped by the server on every stat change. Changes made against an
+    /// older version are rejected instead of overwriting the newer edit
+    #[serde(default)]
+    pub version: u32,
+}
+
+fn default_level() -> u32 {
+    1
+}
+
+fn default_max_power_slots() -> i16 {
+    3
+}
+
+impl Default for Character {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            int: 0,
+            wis: 0,
+            str: 0,
+            cha: 0,
+            dex: 0,
+            con: 0,
+            tagline: String::new(),
+            backstory: String::new(),
+            skills: Vec::new(),
+            power_slots: 0,
+            ac_override: None,
+            xp: 0,
+            level: default_level(),
+            max_power_slots: default_max_power_slots(),
+            inspiration: 0,
+            curr_hp: 0,
+            max_hp: 0,
+            life: LifeState::default(),
+            portrait: None,
+            version: 0,
+        }
+    }
+}
+
+impl Character {
+    /// The XP table says they've earned a level they haven't taken yet
+    pub fn can_level_up(&self, table: &XpTable) -> bool {
+        table.level_for(self.xp) > self.level
+    }
+
+    /// Applies damage (negative) or healing, keeping HP between 0 and the max.
+    /// Characters without a max set can heal as high as they like
+    pub fn adjust_hp(&mut self, amount: i32) {
+        let hp = self.curr_hp.saturating_add(amount).max(0);
+        self.curr_hp = match self.max_hp {
+            max if max > 0 => hp.min(max),
+            _ => hp,
+        };
+
+        self.life = match self.life {
+            LifeState::Dead => LifeState::Dead,
+            _ if self.curr_hp > 0 => LifeState::Conscious,
+            life if amount >= 0 => life,
+            LifeState::Conscious => LifeState::Dying {
+                successes: 0,
+                failures: 0,
+            },
+            // Hits taken while down count as failed saves
+            life => life.after_save(false),
+        };
+    }
+
+    /// Rolls of 10 or more pass, a 1 counts as two failures and a 20 brings
+    /// them back with 1 HP. Only dying characters roll
+    pub fn death_save(&mut self, roll: u32) {
+        if !matches!(self.life, LifeState::Dying { .. }) {
+            return;
+        }
+
+        self.life = match roll {
+            20 => {
+                self.curr_hp = 1;
+                LifeState::Conscious
+            }
+            1 => self.life.after_save(false).after_save(false),
+            roll => self.life.after_save(roll >= 10),
+        };
+    }
+
+    /// For the GM to overrule the dice. Bringing someone back up gives them
+    /// at least 1 HP, anything else leaves them at 0
+    pub fn set_life(&mut self, life: LifeState) {
+        self.curr_hp = match life {
+            LifeState::Conscious => self.curr_hp.max(1),
+            _ => 0,
+        };
+        self.life = life;
+    }
+}
+
+/// How a character is doing once their HP runs out
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LifeState {
+    #[default]
+    Conscious,
+    /// At 0 HP and rolling death saves
+    Dying {
+        successes: u8,
+        failures: u8,
+    },
+    /// At 0 HP but out of danger
+    Stable,
+    Dead,
+}
+
+impl LifeState {
+    /// Three of either settles things for a dying character
+    pub const SAVES_NEEDED: u8 = 3;
+
+    pub fn is_down(self) -> bool {
+        self != Self::Conscious
+    }
+
+    /// Stable characters who fail one start dying again
+    fn after_save(self, success: bool) -> Self {
+        let (mut successes, mut failures) = match self {
+            Self::Dying {
+                successes,
+                failures,
+            } => (successes, failures),
+            Self::Stable if !success => (0, 0),
+            life => return life,
+        };
+
+        if success {
+            successes += 1;
+        } else {
+            failures += 1;
+        }
+
+        if failures >= Self::SAVES_NEEDED {
+            Self::Dead
+        } else if successes >= Self::SAVES_NEEDED {
+            Self::Stable
+        } else {
+            Self::Dying {
+                successes,
+                failures,
+            }
+        }
+    }
+}
+
+impl Display for LifeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Conscious => write!(f, "Conscious"),
+            Self::Dying {
+                successes,
+                failures,
+            } => write!(f, "Dying ({successes} passed, {failures} failed)"),
+            Self::Stable => write!(f, "Stable"),
+            Self::Dead => write!(f, "Dead"),
+        }
+    }
+}
+
+/// One edit to a character's stats
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CharacterChange {
+    Skills(Vec<String>),
+    ArmorClassOverride(Option<i16>),
+    Level(u32),
+    MaxPowerSlots(i16),
+    MaxHp(i32),
+    Portrait(Option<String>),
+}
+
+impl CharacterChange {
+    pub fn apply(&self, character: &mut Character) {
+        match self {
+            Self::Skills(skills) => character.skills = skills.clone(),
+            Self::ArmorClassOverride(ac) => character.ac_override = *ac,
+            Self::Level(level) => character.level = *level,
+            Self::MaxPowerSlots(count) => character.max_power_slots = *count,
+            Self::MaxHp(hp) => character.max_hp = *hp,
+            Self::Portrait(url) => character.portrait = url.clone(),
+        }
+    }
+
+    /// The same field as it is on the character, for showing both sides of a conflict
+    pub fn current(&self, character: &Character) -> Self {
+        match self {
+            Self::Skills(_) => Self::Skills(character.skills.clone()),
+            Self::ArmorClassOverride(_) => Self::ArmorClassOverride(character.ac_override),
+            Self::Level(_) => Self::Level(character.level),
+            Self::MaxPowerSlots(_) => Self::MaxPowerSlots(character.max_power_slots),
+            Self::MaxHp(_) => Self::MaxHp(character.max_hp),
+            Self::Portrait(_) => Self::Portrait(character.portrait.clone()),
+        }
+    }
+
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            Self::Skills(_) => "Skills",
+            Self::ArmorClassOverride(_) => "AC override",
+            Self::Level(_) => "Level",
+            Self::MaxPowerSlots(_) => "Max power slots",
+            Self::MaxHp(_) => "Max HP",
+            Self::Portrait(_) => "Portrait",
+        }
+    }
+}
+
+impl Display for CharacterChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Skills(skills) if skills.is_empty() => write!(f, "None"),
+            Self::Skills(skills) => write!(f, "{}", skills.join(", ")),
+            Self::ArmorClassOverride(Some(ac)) => write!(f, "{}", ac),
+            Self::ArmorClassOverride(None) => write!(f, "None"),
+            Self::Level(level) => write!(f, "{}", level),
+            Self::MaxPowerSlots(count) => write!(f, "{}", count),
+            Self::MaxHp(hp) => write!(f, "{}", hp),
+            Self::Portrait(Some(url)) => write!(f, "{}", url),
+            Self::Portrait(None) => write!(f, "None"),
+        }
+    }
+}
+
+/// Total XP needed to reach each level, starting with level 1
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct XpTable(pub Vec<u32>);
+
+impl Default for XpTable {
+    fn default() -> Self {
+        Self(vec![
+            0, 300, 900, 2700, 6500, 14000, 23000, 34000, 48000, 64000, 85000, 100000, 120000,
+            140000, 165000, 195000, 225000, 265000, 305000, 355000,
+        ])
+    }
+}
+
+impl XpTable {
+    pub fn level_for(&self, xp: u32) -> u32 {
+        self.0.iter().take_while(|x| **x <= xp).count().max(1) as u32
+    }
+
+    /// XP needed for the level after this one, `None` at the top of the table
+    pub fn next_level_xp(&self, level: u32) -> Option<u32> {
+        self.0.get(level as usize).copied()
+    }
+}
+
+/// GM defined table of results picked at random, ie. loot or wild magic surges
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct RollTable {
+    pub name: String,
+    pub entries: Vec<TableEntry>,
+    /// Items rolled by the GM go straight into the party stash
+    #[serde(default)]
+    pub add_to_stash: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TableEntry {
+    /// Entries with more weight come up more often. The text can roll dice,
+    /// ie. `1d4 healing potions`, and roll on other tables, ie. `[[Gems]]`
+    pub weight: u32,
+    pub text: String,
+    /// Catalog item the entry gives, as many as the first dice in the text rolled
+    #[serde(default)]
+    pub item: Option<String>,
+}
+
+impl RollTable {
+    pub fn total_weight(&self) -> u32 {
+        self.entries
+            .iter()
+            .fold(0, |sum, x| sum.saturating_add(x.weight))
+    }
+
+    /// Entry for a roll from 1 to [`Self::total_weight`]
+    pub fn pick(&self, roll: u32) -> Option<&TableEntry> {
+        let mut remaining = roll;
+        self.entries.iter().find(|x| {
+            if remaining <= x.weight {
+                return true;
+            }
+            remaining -= x.weight;
+            false
+        })
+    }
 }
 
 #[derive(
@@ -68,11 +572,828 @@ pub struct SortingLayer(pub u32);
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct DndPlayerPiece {
+    #[serde(default)]
+    pub name: String,
     pub position: Pos2,
     pub size: Vec2,
     pub image_url: Option<String>,
     pub color: Option<[u8; 4]>,
     pub sorting_layer: SortingLayer,
-    pub visible_by: Vec<String>,
+    #[serde(default)]
+    pub visibility: PieceVisibility,
     pub locked: bool,
+    #[serde(default)]
+    pub board: Uuid,
+    /// Users allowed to move this piece. The GM can always move everything
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// How far the token can see in grid squares. Once a player owns a token
+    /// with vision they only see what their tokens can see
+    #[serde(default)]
+    pub vision: Option<f32>,
+    /// Character whose sheet the piece uses for HP and AC. Pieces without a
+    /// link stand in for the character they're named after, if there is one
+    #[serde(default)]
+    pub link_stats_to: Option<String>,
+    /// Conditions shown as icons in the corner of the token
+    #[serde(default)]
+    pub statuses: Vec<PieceStatus>,
+    /// Set for text labels, which draw their name instead of an image
+    #[serde(default)]
+    pub label: Option<LabelStyle>,
+}
+
+/// How a text label piece draws its name
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LabelStyle {
+    /// In canvas units, so the label scales with the board
+    pub size: f32,
+    pub color: [u8; 4],
+}
+
+/// Condition marked on a token, ie. concentrating or restrained
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PieceStatus {
+    pub name: String,
+    /// Glyph from the icon font drawn on the token
+    pub icon: String,
+}
+
+impl DndPlayerPiece {
+    pub fn is_owned_by(&self, name: &str) -> bool {
+        self.owners.iter().any(|x| x == name)
+    }
+
+    /// Whether a player can see this piece. The GM can always see every piece
+    pub fn visible_to(&self, name: &str) -> bool {
+        self.visibility.includes(name)
+    }
+}
+
+/// A configured piece kept to place copies of, ie. a goblin token. Sizes are
+/// in grid squares so copies fit whatever grid the board uses
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PieceTemplate {
+    pub name: String,
+    pub piece: DndPlayerPiece,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum PieceVisibility {
+    #[default]
+    Everyone,
+    GmOnly,
+    Players(Vec<String>),
+}
+
+impl PieceVisibility {
+    pub fn includes(&self, name: &str) -> bool {
+        match self {
+            PieceVisibility::Everyone => true,
+            PieceVisibility::GmOnly => false,
+            PieceVisibility::Players(players) => players.iter().any(|x| x == name),
+        }
+    }
+}
+
+/// Group id to the pieces in it. Grouped pieces move and get deleted together,
+/// a piece is in at most one group
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct PieceGroups(pub HashMap<Uuid, Vec<Uuid>>);
+
+impl PieceGroups {
+    /// Pieces are pulled out of whatever group they were in before
+    pub fn set(&mut self, group: Uuid, pieces: Vec<Uuid>) {
+        self.retain_pieces(|x| !pieces.contains(x));
+        if pieces.len() > 1 {
+            self.0.insert(group, pieces);
+        }
+    }
+
+    pub fn remove(&mut self, group: &Uuid) {
+        self.0.remove(group);
+    }
+
+    /// Groups left with a single piece are dropped
+    pub fn retain_pieces(&mut self, f: impl Fn(&Uuid) -> bool) {
+        for pieces in self.0.values_mut() {
+            pieces.retain(&f);
+        }
+        self.0.retain(|_, pieces| pieces.len() > 1);
+    }
+
+    pub fn group_of(&self, piece: &Uuid) -> Option<Uuid> {
+        self.0
+            .iter()
+            .find(|(_, pieces)| pieces.contains(piece))
+            .map(|(group, _)| *group)
+    }
+
+    pub fn pieces(&self, group: &Uuid) -> &[Uuid] {
+        self.0.get(group).map_or(&[], |x| x.as_slice())
+    }
+}
+
+/// Id of the board every session starts with. It can't be deleted
+pub const MAIN_BOARD: Uuid = Uuid::nil();
+
+/// A named map within the session, ie. "Town" or "Dungeon Level 1"
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct BoardInfo {
+    pub name: String,
+    pub portals: HashMap<Uuid, Portal>,
+    #[serde(default)]
+    pub walls: HashMap<Uuid, Wall>,
+}
+
+impl BoardInfo {
+    pub fn main() -> Self {
+        Self {
+            name: String::from("Main"),
+            ..Default::default()
+        }
+    }
+
+    /// Whether a piece moving in a straight line between the two points
+    /// would pass through a wall or closed door that blocks movement
+    pub fn blocks_movement(&self, from: Pos2, to: Pos2) -> bool {
+        self.walls
+            .values()
+            .any(|x| x.blocks_movement && x.blocks(from, to))
+    }
+}
+
+/// Pieces dropped inside `area` are moved to `target` on `target_board`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct Portal {
+    pub area: Rect,
+    pub target_board: Uuid,
+    pub target: Pos2,
+}
+
+/// A wall segment on a board. Doors block the same as walls until they're opened
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct Wall {
+    pub start: Pos2,
+    pub end: Pos2,
+    /// `Some(open)` for doors
+    pub door: Option<bool>,
+    /// Otherwise the wall only blocks line of sight
+    pub blocks_movement: bool,
+}
+
+impl Wall {
+    pub fn is_open(&self) -> bool {
+        self.door == Some(true)
+    }
+
+    /// Whether the segment between the two points crosses the wall while it's closed
+    pub fn blocks(&self, from: Pos2, to: Pos2) -> bool {
+        if self.is_open() {
+            return false;
+        }
+
+        let cross = |o: Pos2, a: Pos2, b: Pos2| (a - o).x * (b - o).y - (a - o).y * (b - o).x;
+        let d1 = cross(self.start, self.end, from);
+        let d2 = cross(self.start, self.end, to);
+        let d3 = cross(from, to, self.start);
+        let d4 = cross(from, to, self.end);
+
+        // Touching the wall without going through it doesn't count
+        d1 * d2 < 0.0 && d3 * d4 < 0.0
+    }
+
+    pub fn distance_to(&self, pos: Pos2) -> f32 {
+        let along = self.end - self.start;
+        let t = if along.length_sq() > 0.0 {
+            ((pos - self.start).dot(along) / along.length_sq()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        (self.start + along * t).distance(pos)
+    }
+
+    /// Distance along the ray from `origin` to where it hits the wall
+    fn ray_hit(&self, origin: Pos2, dir: Vec2) -> Option<f32> {
+        let along = self.end - self.start;
+        let denom = cross(dir, along);
+        if denom.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let offset = self.start - origin;
+        let distance = cross(offset, along) / denom;
+        let on_wall = cross(offset, dir) / denom;
+        (distance >= 0.0 && (0.0..=1.0).contains(&on_wall)).then_some(distance)
+    }
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Area a token at `origin` can see out to `radius`, as a polygon around the
+/// origin. Closed walls and doors cast shadows
+pub fn visibility_polygon(origin: Pos2, radius: f32, walls: &[Wall]) -> Vec<Pos2> {
+    const CIRCLE_RAYS: usize = 64;
+    /// Rays just either side of a wall's end see past the corner
+    const NUDGE: f32 = 0.0001;
+
+    let walls: Vec<_> = walls.iter().filter(|x| !x.is_open()).collect();
+
+    let mut angles: Vec<f32> = (0..CIRCLE_RAYS)
+        .map(|i| i as f32 / CIRCLE_RAYS as f32 * std::f32::consts::TAU)
+        .collect();
+    for end in walls.iter().flat_map(|x| [x.start, x.end]) {
+        if end.distance(origin) <= radius {
+            let angle = (end - origin).angle();
+            angles.extend([angle - NUDGE, angle, angle + NUDGE]);
+        }
+    }
+    angles.sort_by(f32::total_cmp);
+
+    angles
+        .into_iter()
+        .map(|angle| {
+            let dir = Vec2::angled(angle);
+            let distance = walls
+                .iter()
+                .filter_map(|x| x.ray_hit(origin, dir))
+                .fold(radius, f32::min);
+            origin + dir * distance
+        })
+        .collect()
+}
+
+/// Even-odd test, works for the concave polygons vision produces
+pub fn polygon_contains(points: &[Pos2], pos: Pos2) -> bool {
+    let mut inside = false;
+    for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+        let straddles = (a.y > pos.y) != (b.y > pos.y);
+        if straddles && pos.x < a.x + (pos.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum AnnotationShape {
+    Pen(Vec<Pos2>),
+    Line(Pos2, Pos2),
+    Rect(Pos2, Pos2),
+    /// Ellipse inscribed in the rect between the two corners
+    Ellipse(Pos2, Pos2),
+    Text(Pos2, String),
+    /// Area of effect template (center, radius)
+    Sphere(Pos2, f32),
+    /// Area of effect template (apex, middle of the far edge)
+    Cone(Pos2, Pos2),
+}
+
+impl AnnotationShape {
+    pub fn bounding_rect(&self) -> Rect {
+        match self {
+            AnnotationShape::Pen(points) => Rect::from_points(points),
+            AnnotationShape::Line(a, b)
+            | AnnotationShape::Rect(a, b)
+            | AnnotationShape::Ellipse(a, b) => Rect::from_two_pos(*a, *b),
+            AnnotationShape::Text(pos, _) => Rect::from_min_max(*pos, *pos),
+            AnnotationShape::Sphere(center, radius) => {
+                Rect::from_center_size(*center, Vec2::splat(radius * 2.0))
+            }
+            AnnotationShape::Cone(apex, end) => Rect::from_points(&cone_points(*apex, *end)),
+        }
+    }
+
+    pub fn is_template(&self) -> bool {
+        matches!(
+            self,
+            AnnotationShape::Sphere(..) | AnnotationShape::Cone(..)
+        )
+    }
+
+    /// Whether any part of `rect` is inside the shape. Lines and text don't
+    /// cover an area so never overlap anything
+    pub fn overlaps(&self, rect: Rect) -> bool {
+        match self {
+            AnnotationShape::Rect(a, b) => Rect::from_two_pos(*a, *b).intersects(rect),
+            AnnotationShape::Ellipse(a, b) => ellipse_overlaps(Rect::from_two_pos(*a, *b), rect),
+            AnnotationShape::Sphere(..) => ellipse_overlaps(self.bounding_rect(), rect),
+            AnnotationShape::Cone(apex, end) => convex_overlaps(&cone_points(*apex, *end), rect),
+            AnnotationShape::Pen(_) | AnnotationShape::Line(..) | AnnotationShape::Text(..) => {
+                false
+            }
+        }
+    }
+}
+
+/// Corners of a cone template, which is as wide at the end as it is long
+pub fn cone_points(apex: Pos2, end: Pos2) -> [Pos2; 3] {
+    let half_width = (end - apex).rot90() / 2.0;
+    [apex, end + half_width, end - half_width]
+}
+
+/// Squashes the ellipse into a unit circle and checks the closest point of the rect
+fn ellipse_overlaps(bounds: Rect, rect: Rect) -> bool {
+    let radius = bounds.size() / 2.0;
+    if radius.x <= 0.0 || radius.y <= 0.0 {
+        return false;
+    }
+
+    let closest = bounds.center().clamp(rect.min, rect.max);
+    ((closest - bounds.center()) / radius).length_sq() <= 1.0
+}
+
+/// Separating axis test between a convex polygon and a rect
+fn convex_overlaps(points: &[Pos2], rect: Rect) -> bool {
+    let corners = [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ];
+    let project = |points: &[Pos2], axis: Vec2| {
+        points
+            .iter()
+            .map(|x| x.to_vec2().dot(axis))
+            .fold((f32::MAX, f32::MIN), |(min, max), x| {
+                (min.min(x), max.max(x))
+            })
+    };
+
+    let edge_normals = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| (*b - *a).rot90());
+
+    [Vec2::X, Vec2::Y]
+        .into_iter()
+        .chain(edge_normals)
+        .all(|axis| {
+            let (a_min, a_max) = project(points, axis);
+            let (b_min, b_max) = project(&corners, axis);
+            a_max >= b_min && b_max >= a_min
+        })
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Annotation {
+    pub shape: AnnotationShape,
+    pub color: [u8; 4],
+    pub width: f32,
+    pub layer: SortingLayer,
+    pub visible_by: Vec<String>,
+    #[serde(default)]
+    pub board: Uuid,
+    /// Who drew it, set by the server. Only they or the GM can erase it
+    #[serde(default)]
+    pub author: String,
+}
+
+impl Annotation {
+    /// Annotations without anyone picked are shown to everyone
+    pub fn visible_to(&self, name: &str) -> bool {
+        self.visible_by.is_empty() || self.visible_by.iter().any(|x| x == name)
+    }
+}
+
+/// Purely visual weather/lighting drawn over the board. Intensities are in `0.0..=1.0`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Ambience {
+    pub rain: f32,
+    pub fog: f32,
+    pub darkness: f32,
+    pub night: bool,
+}
+
+impl Ambience {
+    pub fn is_clear(&self) -> bool {
+        *self == Ambience::default()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum EffectTarget {
+    Character(String),
+    Piece(Uuid),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TimedEffect {
+    pub name: String,
+    pub description: String,
+    pub rounds_left: u32,
+    pub target: EffectTarget,
+}
+
+impl TimedEffect {
+    /// Counts the effect down by a round, returning true once it has expired
+    pub fn tick(&mut self) -> bool {
+        self.rounds_left = self.rounds_left.saturating_sub(1);
+        self.rounds_left == 0
+    }
+}
+
+/// How a spent ability comes back during combat
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recharge {
+    /// Comes back when a d6 rolled at the start of its turn is at least this
+    Roll(u8),
+    /// Comes back after this many rounds
+    Rounds(u32),
+}
+
+impl Display for Recharge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recharge::Roll(6) => write!(f, "Recharge 6"),
+            Recharge::Roll(min) => write!(f, "Recharge {}-6", min),
+            Recharge::Rounds(1) => write!(f, "Recharges after 1 round"),
+            Recharge::Rounds(rounds) => write!(f, "Recharges after {} rounds", rounds),
+        }
+    }
+}
+
+/// A spent ability waiting to recharge. Counted down with the timed effects
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Cooldown {
+    pub ability: String,
+    pub recharge: Recharge,
+    /// Only counts down for abilities that recharge after a number of rounds
+    pub rounds_left: u32,
+    /// Uses a character's ability gets back once it recharges. Tokens don't
+    /// track uses, so theirs is just the cooldown
+    pub uses: Option<i64>,
+    pub target: EffectTarget,
+}
+
+impl Cooldown {
+    pub fn new(
+        ability: String,
+        recharge: Recharge,
+        uses: Option<i64>,
+        target: EffectTarget,
+    ) -> Self {
+        let rounds_left = match recharge {
+            Recharge::Roll(_) => 0,
+            Recharge::Rounds(rounds) => rounds,
+        };
+
+        Self {
+            ability,
+            recharge,
+            rounds_left,
+            uses,
+            target,
+        }
+    }
+
+    /// Moves the cooldown on a turn for abilities recharged by a roll, or a
+    /// round for the rest. Returns true once the ability has recharged.
+    /// `roll_d6` is only called for abilities recharged by a roll
+    pub fn tick(&mut self, roll_d6: impl FnOnce() -> u8) -> bool {
+        match self.recharge {
+            Recharge::Roll(min) => roll_d6() >= min,
+            Recharge::Rounds(_) => {
+                self.rounds_left = self.rounds_left.saturating_sub(1);
+                self.rounds_left == 0
+            }
+        }
+    }
+}
+
+/// In-game date on the Calendar of Harptos, twelve months of thirty days
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CampaignDate {
+    pub year: i32,
+    /// 1 - 12
+    pub month: u8,
+    /// 1 - 30
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl Default for CampaignDate {
+    fn default() -> Self {
+        Self {
+            year: 1492,
+            month: 1,
+            day: 1,
+            hour: 8,
+            minute: 0,
+        }
+    }
+}
+
+impl CampaignDate {
+    pub const MONTHS: [&'static str; 12] = [
+        "Hammer",
+        "Alturiak",
+        "Ches",
+        "Tarsakh",
+        "Mirtul",
+        "Kythorn",
+        "Flamerule",
+        "Eleasis",
+        "Eleint",
+        "Marpenoth",
+        "Uktar",
+        "Nightal",
+    ];
+    pub const DAYS_PER_MONTH: u8 = 30;
+
+    const MINUTES_PER_DAY: i64 = 24 * 60;
+    const DAYS_PER_YEAR: i64 = Self::MONTHS.len() as i64 * Self::DAYS_PER_MONTH as i64;
+
+    pub fn month_name(&self) -> &'static str {
+        Self::MONTHS[(self.month.clamp(1, 12) - 1) as usize]
+    }
+
+    fn to_minutes(self) -> i64 {
+        let days = self.year as i64 * Self::DAYS_PER_YEAR
+            + (self.month as i64 - 1) * Self::DAYS_PER_MONTH as i64
+            + (self.day as i64 - 1);
+
+        days * Self::MINUTES_PER_DAY + self.hour as i64 * 60 + self.minute as i64
+    }
+
+    fn from_minutes(minutes: i64) -> Self {
+        let days = minutes.div_euclid(Self::MINUTES_PER_DAY);
+        let time = minutes.rem_euclid(Self::MINUTES_PER_DAY);
+        let day_of_year = days.rem_euclid(Self::DAYS_PER_YEAR);
+
+        Self {
+            year: days.div_euclid(Self::DAYS_PER_YEAR) as i32,
+            month: (day_of_year / Self::DAYS_PER_MONTH as i64) as u8 + 1,
+            day: (day_of_year % Self::DAYS_PER_MONTH as i64) as u8 + 1,
+            hour: (time / 60) as u8,
+            minute: (time % 60) as u8,
+        }
+    }
+
+    /// Moves time forward, rolling over into the next day, month and year as needed
+    pub fn advanced(self, minutes: u32) -> Self {
+        Self::from_minutes(self.to_minutes() + minutes as i64)
+    }
+}
+
+impl Display for CampaignDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} DR, {:02}:{:02}",
+            self.day,
+            self.month_name(),
+            self.year,
+            self.hour,
+            self.minute
+        )
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum HandoutVisibility {
+    /// Only the GM can see it
+    #[default]
+    Hidden,
+    Everyone,
+    Players(Vec<String>),
+}
+
+/// A document the GM can reveal to players. The body is easy mark text
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Handout {
+    pub title: String,
+    pub body: String,
+    pub image_url: Option<String>,
+    pub visibility: HandoutVisibility,
+}
+
+impl Handout {
+    /// Whether a player can see this handout. The GM can always see every handout
+    pub fn visible_to(&self, name: &str) -> bool {
+        match &self.visibility {
+            HandoutVisibility::Hidden => false,
+            HandoutVisibility::Everyone => true,
+            HandoutVisibility::Players(players) => players.iter().any(|x| x == name),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridKind {
+    #[default]
+    Square,
+    HexPointy,
+    HexFlat,
+}
+
+impl GridKind {
+    pub const ALL: [GridKind; 3] = [GridKind::Square, GridKind::HexPointy, GridKind::HexFlat];
+}
+
+impl Display for GridKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridKind::Square => write!(f, "Square"),
+            GridKind::HexPointy => write!(f, "Hex (pointy)"),
+            GridKind::HexFlat => write!(f, "Hex (flat)"),
+        }
+    }
+}
+
+/// Size of a grid square in feet
+pub const FEET_PER_SQUARE: u32 = 5;
+
+/// Board wide grid layout. `spacing` is the distance between neighbouring cell centers
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GridSettings {
+    pub kind: GridKind,
+    pub spacing: f32,
+    pub color: [u8; 4],
+    pub visible: bool,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            kind: GridKind::Square,
+            spacing: 0.1,
+            color: [96, 96, 96, 255],
+            visible: false,
+        }
+    }
+}
+
+impl GridSettings {
+    /// Distance from a hex center to its corners
+    pub fn hex_radius(&self) -> f32 {
+        self.spacing / 3f32.sqrt()
+    }
+
+    /// Center of the hex containing `pos`. Only meaningful for hex grids
+    pub fn hex_center(&self, pos: Pos2) -> Pos2 {
+        let r = self.hex_radius();
+        let sqrt3 = 3f32.sqrt();
+        let (q, s) = self.hex_axial(pos);
+
+        match self.kind {
+            GridKind::HexFlat => Pos2::new(r * 1.5 * q, r * (sqrt3 / 2.0 * q + sqrt3 * s)),
+            _ => Pos2::new(r * (sqrt3 * q + sqrt3 / 2.0 * s), r * 1.5 * s),
+        }
+    }
+
+    /// Axial coordinates of the hex containing `pos`
+    fn hex_axial(&self, pos: Pos2) -> (f32, f32) {
+        let r = self.hex_radius();
+        let sqrt3 = 3f32.sqrt();
+
+        let (q, s) = match self.kind {
+            GridKind::HexFlat => (
+                (2.0 / 3.0 * pos.x) / r,
+                (-1.0 / 3.0 * pos.x + sqrt3 / 3.0 * pos.y) / r,
+            ),
+            _ => (
+                (sqrt3 / 3.0 * pos.x - 1.0 / 3.0 * pos.y) / r,
+                (2.0 / 3.0 * pos.y) / r,
+            ),
+        };
+        round_axial(q, s)
+    }
+
+    /// Squares (or hexes) a piece moves going from `from` to `to`
+    pub fn distance(&self, from: Pos2, to: Pos2, diagonals: Diagonals) -> u32 {
+        if self.kind != GridKind::Square {
+            let (q1, s1) = self.hex_axial(from);
+            let (q2, s2) = self.hex_axial(to);
+            let (dq, ds) = (q2 - q1, s2 - s1);
+            return ((dq.abs() + ds.abs() + (dq + ds).abs()) / 2.0) as u32;
+        }
+
+        let steps = ((to - from) / self.spacing).round().abs();
+        let (long, short) = (steps.max_elem() as u32, steps.min_elem() as u32);
+        match diagonals {
+            Diagonals::Chebyshev => long,
+            Diagonals::Alternating => long + short / 2,
+        }
+    }
+
+    /// Corners of the hex centered at `center`, in order
+    pub fn hex_corners(&self, center: Pos2) -> [Pos2; 6] {
+        let r = self.hex_radius();
+        let offset = match self.kind {
+            GridKind::HexFlat => 0.0,
+            _ => -30.0,
+        };
+
+        std::array::from_fn(|i| {
+            let angle = (60.0 * i as f32 + offset).to_radians();
+            center + Vec2::new(angle.cos(), angle.sin()) * r
+        })
+    }
+}
+
+/// Rounds fractional axial hex coordinates to the nearest hex
+fn round_axial(q: f32, s: f32) -> (f32, f32) {
+    let t = -q - s;
+    let (mut rq, mut rs, rt) = (q.round(), s.round(), t.round());
+
+    let (dq, ds, dt) = ((rq - q).abs(), (rs - s).abs(), (rt - t).abs());
+    if dq > ds && dq > dt {
+        rq = -rs - rt;
+    } else if ds > dt {
+        rs = -rq - rt;
+    }
+
+    (rq, rs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dying(successes: u8, failures: u8) -> LifeState {
+        LifeState::Dying {
+            successes,
+            failures,
+        }
+    }
+
+    fn down() -> Character {
+        let mut character = Character {
+            max_hp: 10,
+            curr_hp: 1,
+            ..Default::default()
+        };
+        character.adjust_hp(-5);
+        character
+    }
+
+    #[test]
+    fn three_successes_make_them_stable() {
+        let life = dying(0, 0).after_save(true).after_save(true);
+        assert_eq!(life, dying(2, 0));
+        assert_eq!(life.after_save(true), LifeState::Stable);
+    }
+
+    #[test]
+    fn three_failures_kill_them() {
+        let life = dying(2, 0).after_save(false).after_save(false);
+        assert_eq!(life, dying(2, 2));
+        assert_eq!(life.after_save(false), LifeState::Dead);
+    }
+
+    #[test]
+    fn stable_characters_who_fail_start_dying_again() {
+        assert_eq!(LifeState::Stable.after_save(true), LifeState::Stable);
+        assert_eq!(LifeState::Stable.after_save(false), dying(0, 1));
+    }
+
+    #[test]
+    fn saves_dont_change_the_conscious_or_the_dead() {
+        for life in [LifeState::Conscious, LifeState::Dead] {
+            assert_eq!(life.after_save(true), life);
+            assert_eq!(life.after_save(false), life);
+        }
+    }
+
+    #[test]
+    fn a_natural_20_brings_them_back_with_1_hp() {
+        let mut character = down();
+        character.death_save(5);
+        character.death_save(20);
+        assert_eq!(character.life, LifeState::Conscious);
+        assert_eq!(character.curr_hp, 1);
+    }
+
+    #[test]
+    fn a_natural_1_counts_as_two_failures() {
+        let mut character = down();
+        character.death_save(1);
+        assert_eq!(character.life, dying(0, 2));
+        character.death_save(1);
+        assert_eq!(character.life, LifeState::Dead);
+    }
+
+    #[test]
+    fn only_dying_characters_roll() {
+        let mut character = down();
+        character.life = LifeState::Stable;
+        character.death_save(20);
+        assert_eq!(character.life, LifeState::Stable);
+        assert_eq!(character.curr_hp, 0);
+    }
+
+    #[test]
+    fn hits_while_down_count_as_failed_saves() {
+        let mut character = down();
+        assert_eq!(character.life, dying(0, 0));
+        character.adjust_hp(-3);
+        assert_eq!(character.life, dying(0, 1));
+        character.adjust_hp(4);
+        assert_eq!(character.life, LifeState::Conscious);
+    }
 }
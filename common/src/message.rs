@@ -1,18 +1,236 @@
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 
+use chrono::{DateTime, Utc};
 use emath::Pos2;
 use uuid::Uuid;
 
-use crate::{Ability, Character, DndPlayerPiece, Item, User};
+use crate::{
+    rules::{Dnd5e, Rules},
+    ruleset::Ruleset,
+    soundboard::{NowPlaying, Soundboard},
+    stats::RollStats,
+    Ability, AbilityDefinition, Ambience, Annotation, CampaignDate, Character, CharacterChange,
+    Cooldown, DndPlayerPiece, EquipSlot, GridSettings, Handout, HandoutVisibility, IssueReport,
+    Item, ItemDefinition, LifeState, Loot, PieceTemplate, Portal, RollTable, SnapshotInfo,
+    SnapshotUsage, SortingLayer, TimedEffect, Trade, User, Wall, XpTable,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DieRoll {
+    pub die: u32,
+    /// Number of dice rolled, `value` is their sum
+    pub count: u32,
+    pub value: u32,
+    /// Added to the dice, ie. stat modifiers and proficiency
+    pub modifier: i32,
+    /// Character the roll was made for
+    pub character: Option<String>,
+    /// What the roll was for, ie. "Stealth" or "Longsword"
+    pub reason: Option<String>,
+    pub visibility: RollVisibility,
+    #[serde(default)]
+    pub kind: DieKind,
+    /// Each die on its own, including the extra dice from explosions. Empty
+    /// when only the sum was kept
+    #[serde(default)]
+    pub rolls: Vec<u32>,
+    /// Decided by the campaign's rules when the roll is made. Missing on rolls
+    /// from before rules modules, those follow 5e
+    #[serde(default)]
+    pub outcome: Option<RollOutcome>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DieKind {
+    #[default]
+    Standard,
+    /// Dice that come up on their highest side are rolled again and added on
+    Exploding,
+    /// -1, 0 or +1 per die. Stored as 0 to 2 so `value` can stay unsigned
+    Fate,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RollOutcome {
+    #[default]
+    Normal,
+    Critical,
+    Fumble,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RollVisibility {
+    #[default]
+    Public,
+    /// Roller and GM see the result
+    GmOnly,
+    /// Only the GM sees the result
+    Blind,
+}
+
+impl DieRoll {
+    /// Lets the campaign's rules decide whether the roll crit or fumbled
+    pub fn resolve(mut self, ruleset: &Ruleset) -> Self {
+        self.outcome = Some(ruleset.rules().roll_outcome(&self));
+        self
+    }
+
+    pub fn outcome(&self) -> RollOutcome {
+        self.outcome.unwrap_or_else(|| Dnd5e.roll_outcome(self))
+    }
+
+    pub fn is_crit(&self) -> bool {
+        self.outcome() == RollOutcome::Critical
+    }
+
+    pub fn is_fumble(&self) -> bool {
+        self.outcome() == RollOutcome::Fumble
+    }
+
+    pub fn total(&self) -> i64 {
+        let dice = match self.kind {
+            DieKind::Fate => self.value as i64 - self.count as i64,
+            _ => self.value as i64,
+        };
+        dice + self.modifier as i64
+    }
+
+    /// What was rolled, ie. `d20`, `2d6+3`, `3d6!` or `4dF`
+    pub fn dice(&self) -> String {
+        let die = match self.kind {
+            DieKind::Fate => "F".to_owned(),
+            _ => self.die.to_string(),
+        };
+        let mut dice = match self.count {
+            1 => format!("d{}", die),
+            count => format!("{}d{}", count, die),
+        };
+        if self.kind == DieKind::Exploding {
+            dice.push('!');
+        }
+        if self.modifier != 0 {
+            dice.push_str(&format!("{:+}", self.modifier));
+        }
+        dice
+    }
+}
+
+/// An attack rolled against a targeted token
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AttackRoll {
+    /// Its reason is the name of the attack
+    pub to_hit: DieRoll,
+    pub target: String,
+    /// Only known when the target is linked to a character sheet we can see
+    pub target_ac: Option<i16>,
+    /// Left out when the attack is known to have missed
+    pub damage: Option<DieRoll>,
+}
+
+impl AttackRoll {
+    /// `None` when the target's AC isn't known and the GM has to make the call
+    pub fn hits(&self) -> Option<bool> {
+        if self.to_hit.is_crit() {
+            return Some(true);
+        }
+        if self.to_hit.is_fumble() {
+            return Some(false);
+        }
+        self.target_ac.map(|ac| self.to_hit.total() >= ac as i64)
+    }
+}
+
+/// A roll on one of the GM's roll tables
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TableRoll {
+    pub table: String,
+    /// Rolled against the table's total weight to pick the entry
+    pub roll: DieRoll,
+    /// With any dice and nested tables in the entry rolled
+    pub result: String,
+    /// (catalog item, count) added to the party stash
+    #[serde(default)]
+    pub loot: Vec<(String, u32)>,
+}
+
+/// One token caught in an area of effect
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AreaHit {
+    /// Token that was hit, so players aren't told about ones hidden from them
+    pub piece: Uuid,
+    pub name: String,
+    pub damage: i32,
+    /// Made their save and took half
+    pub saved: bool,
+    /// HP left afterwards, filled in by the server for tokens linked to a character
+    pub hp: Option<i32>,
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum LogMessage {
     Chat(String),
+    /// Chat posted by the GM under an NPC's name (speaker, text)
+    NpcChat(String, String),
+    /// `/me` actions (NPC acting, action). Only the GM can act as an NPC
+    Emote(Option<String>, String),
     UseItem(String, u32),
     SetAbilityCount(String, i64),
     Joined(String),
     Disconnected(String),
-    Roll(u32, u32),
+    Roll(DieRoll),
+    Attack(AttackRoll),
+    /// (effect name, target name)
+    EffectExpired(String, String),
+    /// (ability name, target name)
+    AbilityRecharged(String, String),
+    /// (character, points granted)
+    InspirationGranted(String, u32),
+    /// Character who spent a point
+    InspirationSpent(String),
+    /// (character, change, hp after). Negative changes are damage
+    HpChanged(String, i32, i32),
+    /// (effect name, damage roll, everything it hit)
+    AreaDamage(String, DieRoll, Vec<AreaHit>),
+    TableRoll(TableRoll),
+    /// Minutes into the session, sent by the server every break interval
+    BreakReminder(u64),
+    /// (character, state now) Going down, stabilising, dying or getting back up
+    LifeChanged(String, LifeState),
+    /// (character, d20 roll, state after)
+    DeathSave(String, u32, LifeState),
+    /// Everyone's rolls for the session, posted when the GM resets the clock
+    SessionSummary(BTreeMap<String, RollStats>),
+}
+
+impl LogMessage {
+    /// Rolls only the roller and the GM get to see
+    pub fn is_private(&self) -> bool {
+        match self {
+            LogMessage::Roll(roll) => roll.visibility != RollVisibility::Public,
+            LogMessage::TableRoll(roll) => roll.roll.visibility != RollVisibility::Public,
+            _ => false,
+        }
+    }
+}
+
+/// A chat message pinned above the chat for everyone. The server doesn't keep
+/// the chat, so the pin holds its own copy of the message
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PinnedMessage {
+    pub user: User,
+    pub message: LogMessage,
+    pub time: DateTime<Utc>,
+    /// They can take the pin down again, as can the GM
+    pub pinned_by: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum PinMessage {
+    /// Sent on by the server once the pin is saved. Private rolls can't be pinned
+    Pin(Uuid, PinnedMessage),
+    Unpin(Uuid),
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -21,33 +239,414 @@ pub enum BoardMessage {
     UpdatePlayerPiece(Uuid, DndPlayerPiece),
     UpdatePlayerLocation(Uuid, Pos2),
     DeletePlayerPiece(Uuid),
+    AddAnnotation(Uuid, Annotation),
+    DeleteAnnotation(Uuid),
+    /// Clears one layer of annotations on a board
+    ClearAnnotations(Uuid, SortingLayer),
+    SetAmbience(Ambience),
+    SetCampaignDate(CampaignDate),
+    SetGrid(GridSettings),
+    /// Minutes to move the campaign clock forward
+    AdvanceTime(u32),
+    CreateBoard(Uuid, String),
+    /// Removes the board along with everything on it
+    DeleteBoard(Uuid),
+    /// The board everyone is looking at
+    SetActiveBoard(Uuid),
+    /// (board, portal id, portal)
+    AddPortal(Uuid, Uuid, Portal),
+    /// (board, portal id)
+    DeletePortal(Uuid, Uuid),
+    /// (board, wall id, wall)
+    AddWall(Uuid, Uuid, Wall),
+    /// (board, wall id)
+    DeleteWall(Uuid, Uuid),
+    /// (board, wall id, open) for doors
+    SetDoorOpen(Uuid, Uuid, bool),
+    /// Replaces the pieces in a group, creating it if needed
+    GroupPieces(Uuid, Vec<Uuid>),
+    Ungroup(Uuid),
+    /// Asks for nobody else to change the piece while we drag or edit it
+    HoldPiece(Uuid),
+    ReleasePiece(Uuid),
+    /// Sent by the server when someone starts or stops holding a piece
+    SetHolder(Uuid, Option<String>),
+}
+
+impl BoardMessage {
+    /// The piece this message changes, if any
+    pub fn piece_id(&self) -> Option<Uuid> {
+        match self {
+            Self::AddPlayerPiece(uuid, _)
+            | Self::UpdatePlayerPiece(uuid, _)
+            | Self::UpdatePlayerLocation(uuid, _)
+            | Self::DeletePlayerPiece(uuid) => Some(*uuid),
+            _ => None,
+        }
+    }
+
+    /// The annotation this message changes, if any
+    pub fn annotation_id(&self) -> Option<Uuid> {
+        match self {
+            Self::AddAnnotation(uuid, _) | Self::DeleteAnnotation(uuid) => Some(*uuid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum EffectMessage {
+    ApplyEffect(Uuid, TimedEffect),
+    RemoveEffect(Uuid),
+    AdvanceRound,
+    SetRound(u32),
+    /// Starts or updates the cooldown on a spent ability
+    SetCooldown(Uuid, Cooldown),
+    /// The ability is usable again, or was reset by hand
+    RemoveCooldown(Uuid),
+    /// Rolls to recharge the abilities of the token whose turn it is, and its
+    /// character's if it has one. Only accepted from the GM
+    StartTurn(Uuid),
+}
+
+/// Handouts are created and shared by the GM, players only ever receive the
+/// handouts visible to them
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum HandoutMessage {
+    CreateHandout(Uuid, Handout),
+    ShareHandout(Uuid, HandoutVisibility),
+    DeleteHandout(Uuid),
+}
+
+/// The party stash holds loot nobody has claimed yet
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum StashMessage {
+    /// (catalog item name, count), only accepted from the GM
+    AddLoot(String, u32),
+    /// Sent by the server whenever loot is added or changes
+    SetLoot(Uuid, Loot),
+    RemoveLoot(Uuid),
+    /// (loot, character claiming it)
+    ClaimLoot(Uuid, String),
+    /// GM approves or denies a pending claim
+    ResolveClaim(Uuid, bool),
+    /// Whether claims wait for the GM before the item changes hands
+    RequireApproval(bool),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum TradeMessage {
+    /// Asks the server to open a trade with another player
+    Request(String),
+    /// Sent by the server whenever either side changes the trade
+    Update(Uuid, Trade),
+    /// Replaces everything we're giving away, unconfirms both sides
+    SetOffer(Uuid, Vec<Item>),
+    Confirm(Uuid),
+    Cancel(Uuid),
+    /// (trade, reason) sent by the server once the trade is done or called off
+    Closed(Uuid, String),
+}
+
+/// Tag of the snapshot `SaveNow` keeps overwriting
+pub const MANUAL_SAVE_TAG: &str = "Manual save";
+
+/// Only accepted from the GM
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum SnapshotMessage {
+    /// Snapshots the character data under the given tag
+    Take(String),
+    /// Saves the board and replaces the [`MANUAL_SAVE_TAG`] snapshot with the
+    /// campaign as it is now
+    SaveNow,
+    /// Replaces the character data with the snapshot, after taking a snapshot of the current data
+    Restore(Uuid),
+    Delete(Uuid),
+    /// Sent by the server, newest first
+    List(Vec<SnapshotInfo>),
+    RequestUsage,
+    /// Sent by the server in reply to `RequestUsage`
+    Usage(SnapshotUsage),
+    /// Sent by the server once a snapshot asked for with `Take` is saved
+    Saved(SnapshotInfo),
+}
+
+/// A board change the server applied, kept so the GM can replay the session
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    /// Who sent the change
+    pub user: String,
+    pub change: JournalChange,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum JournalChange {
+    Board(BoardMessage),
+    /// The GM put the board back to how it was after this many entries
+    Branch(usize),
+}
+
+/// Adds an applied change to the end of the journal. Entries are never
+/// changed once they're in, so the journal is a full record of who did what
+pub fn record_journal_entry(journal: &mut Vec<JournalEntry>, user: String, msg: BoardMessage) {
+    journal.push(JournalEntry {
+        at: Utc::now(),
+        user,
+        change: JournalChange::Board(msg),
+    });
+}
+
+/// Entries sent to the GM at a time, later ones are asked for as they're needed
+pub const JOURNAL_PAGE: usize = 500;
+
+/// Past this the oldest half of the journal is squashed into the board as it
+/// stood at that point
+pub const MAX_JOURNAL_ENTRIES: usize = 20_000;
+
+/// Who the journal credits with the board as it was before the entries that
+/// were squashed
+pub const EARLIER_CHANGES_USER: &str = "Earlier changes";
+
+/// Squashes the oldest entries once the journal is past [`MAX_JOURNAL_ENTRIES`].
+/// `rebuild` turns the changes up to that point into what's needed to set up
+/// the board as it was then. Branches back past the squashed part go to where
+/// it ends instead. Returns whether anything was squashed
+pub fn compact_journal(
+    journal: &mut Vec<JournalEntry>,
+    rebuild: impl FnOnce(Vec<&BoardMessage>) -> Vec<BoardMessage>,
+) -> bool {
+    if journal.len() <= MAX_JOURNAL_ENTRIES {
+        return false;
+    }
+
+    let cut = journal.len() - MAX_JOURNAL_ENTRIES / 2;
+    let at = journal[cut - 1].at;
+    let base = rebuild(replay_journal(journal, cut));
+    let shift = |point: usize| point.saturating_sub(cut) + base.len();
+
+    let mut compacted: Vec<_> = base
+        .iter()
+        .map(|msg| JournalEntry {
+            at,
+            user: EARLIER_CHANGES_USER.to_owned(),
+            change: JournalChange::Board(msg.clone()),
+        })
+        .collect();
+    compacted.extend(journal.drain(cut..).map(|mut entry| {
+        if let JournalChange::Branch(point) = &mut entry.change {
+            *point = shift(*point);
+        }
+        entry
+    }));
+
+    *journal = compacted;
+    true
+}
+
+/// Board changes that rebuild the board as it was after the first `count`
+/// journal entries, starting from an empty main board. Branches start over
+/// from the point they went back to
+pub fn replay_journal(entries: &[JournalEntry], count: usize) -> Vec<&BoardMessage> {
+    let mut messages = Vec::new();
+    for (i, entry) in entries.iter().enumerate().take(count) {
+        match &entry.change {
+            JournalChange::Board(msg) => messages.push(msg),
+            JournalChange::Branch(point) => messages = replay_journal(entries, (*point).min(i)),
+        }
+    }
+    messages
+}
+
+/// Only accepted from the GM
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum JournalMessage {
+    /// Asks for the entries from this one on, they're sent a page at a time
+    Request(usize),
+    /// (index of the first entry, up to [`JOURNAL_PAGE`] entries, how many
+    /// there are in all) sent by the server in answer to `Request`. Also sent
+    /// from the start whenever the journal gets squashed
+    Entries(usize, Vec<JournalEntry>, usize),
+    /// Puts the board back to how it was after this many entries
+    Branch(usize),
+    /// Sent by the server to everyone after a branch, the board is cleared and
+    /// sent again like when joining
+    Branched(usize),
+}
+
+/// Real time the GM has had the session running for
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionClock {
+    pub running: bool,
+    /// Seconds counted when the server sent this
+    pub elapsed: u64,
+    /// Minutes between break reminders
+    pub break_every: Option<u32>,
+}
+
+/// Everything but `State` is only accepted from the GM
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum SessionClockMessage {
+    Start,
+    Pause,
+    /// Back to zero, the clock is paused until started again
+    Reset,
+    SetBreakInterval(Option<u32>),
+    /// Sent by the server to everyone whenever the clock changes
+    State(SessionClock),
+}
+
+/// Only accepted from the GM, the server passes them on to everyone else
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum SoundMessage {
+    /// Saved by the server and sent to everyone on join
+    SetSoundboard(Soundboard),
+    /// Replaces whatever music was playing
+    Play(NowPlaying),
+    /// Fades the music out over this many seconds
+    Stop(f32),
+    /// Plays a track once over the music, ie. a door slamming
+    Effect(String),
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 pub enum DndMessage {
     // Bidirectional
-    Log(User, LogMessage),
+    /// The timestamp is stamped by the server, clients always send `None`
+    Log(User, LogMessage, Option<DateTime<Utc>>),
+    /// Only accepted from the GM, the server sends it out to everyone
+    SetXpTable(XpTable),
+    /// Only accepted from the GM, the server saves it and sends it out to everyone
+    SetRuleset(Ruleset),
+    /// Adds or replaces the table with the same name. Only accepted from the GM
+    SaveRollTable(RollTable),
+    /// Only accepted from the GM
+    DeleteRollTable(String),
+    /// Shared with everyone, replaces the template with the same name. Only accepted from the GM
+    SavePieceTemplate(PieceTemplate),
+    /// Only accepted from the GM
+    DeletePieceTemplate(String),
 
     // From Client
     RegisterUser(String),
     UnregisterUser(String),
     RetrieveCharacterData(User),
+    /// Request another character's sheet without replacing our own
+    RetrievePartyMember(String),
     /// (User, id, new_count)
     UpdateItemCount(User, i64, u32),
     UpdateAbilityCount(User, String, i64),
     UpdatePowerSlotCount(User, i16),
+    /// (User, item id, slot)
+    UpdateItemSlot(User, i64, Option<EquipSlot>),
+    /// (owner, item id, attuned). Attuning can be refused once the ruleset's limit is reached
+    UpdateItemAttunement(User, i64, bool),
+    /// (User, version the change was made against, change). Rejected with a
+    /// [`DndMessage::CharacterConflict`] if the character has changed since
+    UpdateCharacter(User, u32, CharacterChange),
+    /// (characters, xp) only accepted from the GM
+    AwardXp(Vec<String>, u32),
+    /// (characters, points) only accepted from the GM
+    GrantInspiration(Vec<String>, u32),
+    SpendInspiration(User),
+    /// (character, change) negative for damage. Only accepted from the GM or
+    /// the character's own player
+    AdjustHp(String, i32),
+    /// (effect name, damage roll, tokens hit) only accepted from the GM. Hits
+    /// on tokens linked to a character come off their HP
+    ApplyAreaDamage(String, DieRoll, Vec<AreaHit>),
+    /// (character) rolled by the server. Only accepted from the GM or the
+    /// character's own player
+    RollDeathSave(String),
+    /// (character, state) the GM marking someone dead or stable, or getting
+    /// them back up. Only accepted from the GM
+    SetLifeState(String, LifeState),
+
+    CreateCharacter(Character),
+    /// Creates a character along with their inventory and abilities, ie. from a backup
+    ImportCharacter(Character, Vec<Item>, Vec<Ability>),
+    /// Only accepted from the GM
+    DeleteCharacter(String),
 
-    UpdateSkills(User, Vec<String>),
+    // Bulk imports, only accepted from the GM
+    ImportItems(Vec<ItemDefinition>),
+    ImportAbilities(Vec<AbilityDefinition>),
+
+    /// Saved by the server for whoever maintains it
+    ReportIssue(IssueReport),
+
+    // Presence
+    /// (username, is typing)
+    Typing(String, bool),
+    /// Sent by clients every few seconds so the server can tell they're still
+    /// there. Carries the sender's clock in milliseconds, handed back unchanged
+    /// in the [`DndMessage::Pong`] so it can time the round trip
+    Ping(u64),
+    Pong(u64),
 
     // Board
     BoardMessage(BoardMessage),
+    /// (board, canvas position) Only accepted from the GM, passed on to
+    /// everyone else as a hint to move their view there
+    FocusView(Uuid, Pos2),
+    /// (board, view center, zoom) Where the GM is looking while they lead
+    /// everyone's view, `None` once they stop. Only accepted from the GM
+    GmView(Option<(Uuid, Pos2, f32)>),
+
+    // Effects
+    EffectMessage(EffectMessage),
+
+    // Handouts
+    HandoutMessage(HandoutMessage),
+
+    // Party stash
+    StashMessage(StashMessage),
+
+    // Trading between players
+    TradeMessage(TradeMessage),
+
+    // Snapshots of the character data
+    SnapshotMessage(SnapshotMessage),
+
+    // Real time session clock
+    SessionClockMessage(SessionClockMessage),
+
+    // History of board changes
+    JournalMessage(JournalMessage),
+
+    // Chat messages pinned for everyone
+    PinMessage(PinMessage),
+
+    // Music and sound effects from the GM's soundboard
+    SoundMessage(SoundMessage),
 
     // From DndServer
+    GameMaster(String),
     UserList(Vec<String>),
-    CharacterList(Vec<String>),
+    /// Every character's name, sorted. Sent on join and again whenever a
+    /// character is created, imported, deleted or restored
+    CharacterRoster(Vec<String>),
     UserNotificationAdded(String),
     UserNotificationRemoved(String),
     ItemList(Vec<Item>),
     CharacterData(Character),
     AbilityList(Vec<Ability>),
+    PartyMemberData(Character, Vec<Item>, Vec<Ability>),
+    RollTables(Vec<RollTable>),
+    /// Templates the GM has shared, sent on join
+    PieceTemplates(Vec<PieceTemplate>),
+    /// Everyone's public rolls (this session, earlier sessions), sent on join.
+    /// Rolls made since come in through the log
+    RollStats(BTreeMap<String, RollStats>, BTreeMap<String, RollStats>),
+    /// The character as it is now, along with the change that was rejected
+    CharacterConflict(Character, CharacterChange),
+    /// Number of rows saved, or why the import failed
+    ImportResult(Result<usize, String>),
+    /// Something the client asked for failed on the server
+    Error {
+        /// What was being attempted, ie. "Saving handout"
+        request_context: String,
+        message: String,
+    },
 }
@@ -0,0 +1,99 @@
+use std::fmt::Display;
+
+use crate::{
+    message::{DieRoll, RollOutcome},
+    ruleset::Ruleset,
+};
+
+/// The kinds of rest the calendar and `/rest` can take
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rest {
+    Short,
+    Long,
+}
+
+impl Display for Rest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rest::Short => write!(f, "Short Rest"),
+            Rest::Long => write!(f, "Long Rest"),
+        }
+    }
+}
+
+/// Hooks a game system uses to change how rolls, modifiers and rests work.
+/// Everything has a default, so a system only needs the rules it does differently
+pub trait Rules: Sync {
+    /// Whether a roll is a critical or a fumble, decided once when it's made
+    fn roll_outcome(&self, _roll: &DieRoll) -> RollOutcome {
+        RollOutcome::Normal
+    }
+
+    /// How many times over the damage dice are rolled on a critical hit
+    fn crit_dice_multiplier(&self) -> u32 {
+        1
+    }
+
+    /// Modifier for a stat with the given score
+    fn stat_modifier(&self, ruleset: &Ruleset, score: i16) -> i32 {
+        ruleset.modifier.modifier(score)
+    }
+
+    /// How long a rest takes on the campaign clock
+    fn rest_minutes(&self, rest: Rest) -> u32 {
+        match rest {
+            Rest::Short => 60,
+            Rest::Long => 8 * 60,
+        }
+    }
+}
+
+/// D&D 5e: natural 20s crit and double the damage dice, natural 1s fumble
+pub struct Dnd5e;
+
+impl Rules for Dnd5e {
+    fn roll_outcome(&self, roll: &DieRoll) -> RollOutcome {
+        match (roll.die, roll.count, roll.value) {
+            (20, 1, 20) => RollOutcome::Critical,
+            (20, 1, 1) => RollOutcome::Fumble,
+            _ => RollOutcome::Normal,
+        }
+    }
+
+    fn crit_dice_multiplier(&self) -> u32 {
+        2
+    }
+}
+
+/// No natural crits or fumbles, rolls are only ever compared to their target
+pub struct Generic;
+
+impl Rules for Generic {}
+
+/// Which rules module the campaign is played with
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RulesSystem {
+    #[default]
+    Dnd5e,
+    Generic,
+}
+
+impl RulesSystem {
+    pub const ALL: [RulesSystem; 2] = [RulesSystem::Dnd5e, RulesSystem::Generic];
+
+    pub fn rules(&self) -> &'static dyn Rules {
+        match self {
+            RulesSystem::Dnd5e => &Dnd5e,
+            RulesSystem::Generic => &Generic,
+        }
+    }
+}
+
+impl Display for RulesSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RulesSystem::Dnd5e => write!(f, "D&D 5e"),
+            RulesSystem::Generic => write!(f, "Generic"),
+        }
+    }
+}
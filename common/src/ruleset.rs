@@ -0,0 +1,193 @@
+use std::fmt::Display;
+
+use crate::{
+    formula::Stat,
+    rules::{Rules, RulesSystem},
+    Character,
+};
+
+/// One of the six stored scores, under the name the system uses for it
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StatConfig {
+    pub name: String,
+    pub stat: Stat,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SkillConfig {
+    pub name: String,
+    pub stat: Stat,
+}
+
+/// How a score turns into its modifier: `(score - base) / divisor` rounded
+/// down. Bounded accuracy systems can cap it so it never goes past +/- `cap`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModifierFormula {
+    pub base: i32,
+    pub divisor: i32,
+    pub cap: Option<i32>,
+}
+
+impl Default for ModifierFormula {
+    fn default() -> Self {
+        Self {
+            base: 10,
+            divisor: 2,
+            cap: None,
+        }
+    }
+}
+
+impl ModifierFormula {
+    pub fn modifier(&self, score: i16) -> i32 {
+        let modifier = (score as i32 - self.base).div_euclid(self.divisor.max(1));
+        // Rulesets can come from anywhere, a negative cap would make clamp panic
+        self.cap
+            .map(i32::saturating_abs)
+            .map_or(modifier, |cap| modifier.clamp(-cap, cap))
+    }
+}
+
+/// How moving diagonally across square grids is counted
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Diagonals {
+    /// Every diagonal step is one square
+    #[default]
+    Chebyshev,
+    /// Every second diagonal step counts as two squares, ie. 5-10-5 feet
+    Alternating,
+}
+
+impl Diagonals {
+    pub const ALL: [Diagonals; 2] = [Diagonals::Chebyshev, Diagonals::Alternating];
+}
+
+impl Display for Diagonals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagonals::Chebyshev => write!(f, "One square each"),
+            Diagonals::Alternating => write!(f, "Alternating 5-10-5"),
+        }
+    }
+}
+
+/// Stats, skills and proficiency for the system the campaign is played in.
+/// Defaults to D&D 5e
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ruleset {
+    /// Rules module for crits, modifiers and rests
+    #[serde(default)]
+    pub system: RulesSystem,
+    /// Shown on the sheet in this order, stats left out are hidden
+    pub stats: Vec<StatConfig>,
+    pub skills: Vec<SkillConfig>,
+    /// Proficiency bonus at each level, starting with level 1
+    pub proficiency: Vec<i32>,
+    #[serde(default)]
+    pub modifier: ModifierFormula,
+    /// Most items a character can be attuned to at once
+    #[serde(default = "default_attunement_limit")]
+    pub attunement_limit: u32,
+    /// Refuse attuning past the limit instead of only warning about it
+    #[serde(default)]
+    pub enforce_attunement_limit: bool,
+    #[serde(default)]
+    pub diagonals: Diagonals,
+    /// Grey out using abilities and items while the character is down
+    #[serde(default)]
+    pub disable_actions_when_down: bool,
+}
+
+fn default_attunement_limit() -> u32 {
+    3
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        let stats = [
+            ("CHA", Stat::Cha),
+            ("STR", Stat::Str),
+            ("WIS", Stat::Wis),
+            ("INT", Stat::Int),
+            ("DEX", Stat::Dex),
+            ("CON", Stat::Con),
+        ];
+        let skills = [
+            ("Acrobatics", Stat::Dex),
+            ("Animal Handling", Stat::Wis),
+            ("Arcana", Stat::Int),
+            ("Athletics", Stat::Str),
+            ("Deception", Stat::Cha),
+            ("History", Stat::Int),
+            ("Insight", Stat::Wis),
+            ("Intimidation", Stat::Cha),
+            ("Investigation", Stat::Int),
+            ("Medicine", Stat::Wis),
+            ("Nature", Stat::Int),
+            ("Perception", Stat::Wis),
+            ("Performance", Stat::Cha),
+            ("Persuasion", Stat::Cha),
+            ("Religion", Stat::Int),
+            ("Sleight of Hand", Stat::Dex),
+            ("Stealth", Stat::Dex),
+            ("Survival", Stat::Wis),
+        ];
+
+        Self {
+            system: RulesSystem::default(),
+            stats: stats
+                .into_iter()
+                .map(|(name, stat)| StatConfig {
+                    name: name.to_owned(),
+                    stat,
+                })
+                .collect(),
+            skills: skills
+                .into_iter()
+                .map(|(name, stat)| SkillConfig {
+                    name: name.to_owned(),
+                    stat,
+                })
+                .collect(),
+            proficiency: (1..=20).map(|level| (level - 1) / 4 + 2).collect(),
+            modifier: ModifierFormula::default(),
+            attunement_limit: default_attunement_limit(),
+            enforce_attunement_limit: false,
+            diagonals: Diagonals::default(),
+            disable_actions_when_down: false,
+        }
+    }
+}
+
+impl Ruleset {
+    /// Levels past the end of the table keep the last bonus
+    pub fn proficiency_bonus(&self, level: u32) -> i32 {
+        let idx = (level.max(1) as usize - 1).min(self.proficiency.len().saturating_sub(1));
+        self.proficiency.get(idx).copied().unwrap_or_default()
+    }
+
+    pub fn rules(&self) -> &'static dyn Rules {
+        self.system.rules()
+    }
+
+    pub fn stat_modifier(&self, stat: Stat, character: &Character) -> i32 {
+        self.score_modifier(stat.score(character))
+    }
+
+    /// Modifier for a bare score, ie. from a monster's stat block
+    pub fn score_modifier(&self, score: i16) -> i32 {
+        self.rules().stat_modifier(self, score)
+    }
+
+    /// Whether someone already attuned to `attuned` items may attune to another
+    pub fn can_attune(&self, attuned: usize) -> bool {
+        !self.enforce_attunement_limit || attuned < self.attunement_limit as usize
+    }
+
+    pub fn stat_name(&self, stat: Stat) -> String {
+        self.stats
+            .iter()
+            .find(|x| x.stat == stat)
+            .map_or_else(|| stat.to_string(), |x| x.name.clone())
+    }
+}
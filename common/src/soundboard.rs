@@ -0,0 +1,44 @@
+/// A sound the GM can play, from a URL or a file every player has at the same path
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SoundTrack {
+    pub name: String,
+    pub source: String,
+}
+
+/// Tracks played one after another and looped, ie. for a tavern or a battle
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Playlist {
+    pub name: String,
+    /// Names of the tracks, in the order they're played
+    pub tracks: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Soundboard {
+    pub tracks: Vec<SoundTrack>,
+    pub playlists: Vec<Playlist>,
+}
+
+impl Soundboard {
+    pub fn track(&self, name: &str) -> Option<&SoundTrack> {
+        self.tracks.iter().find(|x| x.name == name)
+    }
+
+    pub fn playlist(&self, name: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|x| x.name == name)
+    }
+}
+
+/// Music the GM started for everyone
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct NowPlaying {
+    /// The track or playlist that was started
+    pub name: String,
+    /// Names of the tracks, looped
+    pub tracks: Vec<String>,
+    /// Seconds to fade in over, the music playing before fades out as long
+    pub fade: f32,
+    /// Seconds into the music when the message was sent. Zero when the GM
+    /// starts it, the server fills it in for anyone joining part way through
+    pub elapsed: f32,
+}
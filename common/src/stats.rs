@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use crate::message::{DieKind, DieRoll, LogMessage, RollVisibility};
+
+/// Tally of the dice one player has rolled where everyone could see
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollStats {
+    pub rolls: u32,
+    /// Dice rolled of each size, keyed by their number of sides
+    pub dice: BTreeMap<u32, u32>,
+    /// How often each face came up on a d20, natural 1s first
+    pub d20: [u32; 20],
+}
+
+impl RollStats {
+    pub fn record(&mut self, roll: &DieRoll) {
+        if roll.kind == DieKind::Fate {
+            return;
+        }
+
+        self.rolls += 1;
+        let dice = roll.count.max(roll.rolls.len() as u32);
+        *self.dice.entry(roll.die).or_default() += dice;
+
+        if roll.die != 20 {
+            return;
+        }
+        // Rolls from before each die was kept only have their sum, that's
+        // still the face when a single die was rolled
+        let faces = match (roll.rolls.is_empty(), roll.count) {
+            (false, _) => roll.rolls.clone(),
+            (true, 1) => vec![roll.value],
+            (true, _) => Vec::new(),
+        };
+        for face in faces {
+            if let Some(count) = (face as usize)
+                .checked_sub(1)
+                .and_then(|x| self.d20.get_mut(x))
+            {
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &RollStats) {
+        self.rolls += other.rolls;
+        for (die, count) in &other.dice {
+            *self.dice.entry(*die).or_default() += count;
+        }
+        for (total, count) in self.d20.iter_mut().zip(other.d20) {
+            *total += count;
+        }
+    }
+
+    pub fn d20_rolls(&self) -> u32 {
+        self.d20.iter().sum()
+    }
+
+    /// `None` until they've rolled a d20
+    pub fn d20_average(&self) -> Option<f32> {
+        let rolls = self.d20_rolls();
+        if rolls == 0 {
+            return None;
+        }
+        let total: u32 = (1..).zip(self.d20).map(|(face, count)| face * count).sum();
+        Some(total as f32 / rolls as f32)
+    }
+
+    pub fn nat20s(&self) -> u32 {
+        self.d20[19]
+    }
+
+    pub fn nat1s(&self) -> u32 {
+        self.d20[0]
+    }
+}
+
+/// Rolls in the message that count towards the roller's stats. Only public
+/// rolls are counted, so the stats can't give away a secret roll
+pub fn counted_rolls(message: &LogMessage) -> Vec<&DieRoll> {
+    let rolls = match message {
+        LogMessage::Roll(roll) => vec![roll],
+        LogMessage::Attack(attack) => std::iter::once(&attack.to_hit)
+            .chain(attack.damage.as_ref())
+            .collect(),
+        _ => Vec::new(),
+    };
+    rolls
+        .into_iter()
+        .filter(|x| x.visibility == RollVisibility::Public)
+        .collect()
+}
+
+/// Lines for the end of session recap, ie. who rolled the most nat 20s
+pub fn session_highlights(stats: &BTreeMap<String, RollStats>) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let rolls: u32 = stats.values().map(|x| x.rolls).sum();
+    let nat20s: u32 = stats.values().map(|x| x.nat20s()).sum();
+    let nat1s: u32 = stats.values().map(|x| x.nat1s()).sum();
+    lines.push(format!(
+        "{} rolls this session, {} natural 20s and {} natural 1s",
+        rolls, nat20s, nat1s
+    ));
+
+    let averages: Vec<(&String, f32)> = stats
+        .iter()
+        .filter_map(|(name, x)| Some((name, x.d20_average()?)))
+        .collect();
+    let hottest = averages.iter().max_by(|a, b| a.1.total_cmp(&b.1));
+    let coldest = averages.iter().min_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((name, average)) = hottest {
+        lines.push(format!(
+            "Hottest dice: {} (d20 average {:.1})",
+            name, average
+        ));
+    }
+    if let Some((name, average)) = coldest.filter(|_| averages.len() > 1) {
+        lines.push(format!(
+            "Coldest dice: {} (d20 average {:.1})",
+            name, average
+        ));
+    }
+
+    let luckiest = stats.iter().max_by_key(|(_, x)| x.nat20s());
+    if let Some((name, x)) = luckiest.filter(|(_, x)| x.nat20s() > 0) {
+        lines.push(format!("Most natural 20s: {} with {}", name, x.nat20s()));
+    }
+    let unluckiest = stats.iter().max_by_key(|(_, x)| x.nat1s());
+    if let Some((name, x)) = unluckiest.filter(|(_, x)| x.nat1s() > 0) {
+        lines.push(format!("Most natural 1s: {} with {}", name, x.nat1s()));
+    }
+
+    lines
+}
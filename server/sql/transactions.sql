@@ -0,0 +1,94 @@
+-- Functions the server calls through PostgREST for writes that have to happen
+-- together. Run this in the Supabase SQL editor once per project.
+
+-- Makes every write in the list or, if any of them fails, none of them. Each
+-- write looks like
+--   {"op": "insert" | "upsert" | "update" | "delete" | "reset_identity",
+--    "table": "inventory",
+--    "rows": [{...}],                  -- insert and upsert
+--    "key": "name",                    -- upsert, the id column for reset_identity
+--    "filters": [["player", "Alice"]], -- update and delete, null for "is not null"
+--    "changes": {...}}                 -- update
+create or replace function apply_writes(writes jsonb) returns void
+language plpgsql
+as $$
+declare
+    w jsonb;
+    tbl text;
+    rows jsonb;
+    cols text;
+    sets text;
+    cond text;
+begin
+    for w in select * from jsonb_array_elements(writes) loop
+        tbl := w->>'table';
+
+        select coalesce(string_agg(
+            case
+                when f->>1 is null then format('%I.%I is not null', tbl, f->>0)
+                else format('%I.%I::text = %L', tbl, f->>0, f->>1)
+            end, ' and '), 'true')
+        into cond
+        from jsonb_array_elements(coalesce(w->'filters', '[]')) f;
+
+        case w->>'op'
+        when 'insert', 'upsert' then
+            rows := case jsonb_typeof(w->'rows')
+                when 'array' then w->'rows'
+                else jsonb_build_array(w->'rows')
+            end;
+
+            select string_agg(distinct format('%I', k), ', ')
+            into cols
+            from jsonb_array_elements(rows) r, jsonb_object_keys(r) k;
+
+            if cols is null then
+                continue;
+            end if;
+
+            if w->>'op' = 'insert' then
+                execute format(
+                    'insert into %I (%s) select %s from jsonb_populate_recordset(null::%I, $1)',
+                    tbl, cols, cols, tbl
+                ) using rows;
+            else
+                select string_agg(format('%I = excluded.%I', k, k), ', ')
+                into sets
+                from (
+                    select distinct k
+                    from jsonb_array_elements(rows) r, jsonb_object_keys(r) k
+                ) keys;
+
+                execute format(
+                    'insert into %I (%s) select %s from jsonb_populate_recordset(null::%I, $1)
+                     on conflict (%I) do update set %s',
+                    tbl, cols, cols, tbl, w->>'key', sets
+                ) using rows;
+            end if;
+        when 'update' then
+            select string_agg(format('%I = changes.%I', k, k), ', ')
+            into sets
+            from jsonb_object_keys(w->'changes') k;
+
+            if sets is null then
+                continue;
+            end if;
+
+            execute format(
+                'update %I set %s from jsonb_populate_record(null::%I, $1) as changes where %s',
+                tbl, sets, tbl, cond
+            ) using w->'changes';
+        when 'delete' then
+            execute format('delete from %I where %s', tbl, cond);
+        when 'reset_identity' then
+            -- The next id handed out comes after the biggest one in the table
+            execute format(
+                'select setval(pg_get_serial_sequence(%L, %L), coalesce(max(%I), 0) + 1, false) from %I',
+                tbl, w->>'key', w->>'key', tbl
+            );
+        else
+            raise exception 'Unknown write %', w->>'op';
+        end case;
+    end loop;
+end;
+$$;
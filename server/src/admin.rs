@@ -0,0 +1,154 @@
+//! Maintenance run from the command line against the campaign's storage,
+//! without starting the server
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::{
+    storage::{Query, Storage, Write},
+    DndServer,
+};
+
+/// Every table in a campaign, in the order they're imported, along with a
+/// column every row has a value for. The catalog comes before what points at it
+const CAMPAIGN_TABLES: [(&str, &str); 18] = [
+    ("items", "id"),
+    ("abilities", "name"),
+    ("character", "name"),
+    ("characters", "player"),
+    ("inventory", "player"),
+    ("player_abilities", "player"),
+    ("party_stash", "id"),
+    ("handouts", "id"),
+    ("pinned_messages", "id"),
+    ("ruleset", "id"),
+    ("xp_table", "id"),
+    ("soundboard", "id"),
+    ("roll_tables", "name"),
+    ("piece_templates", "name"),
+    ("roll_stats", "player"),
+    ("snapshots", "id"),
+    ("feedback", "username"),
+    ("board_journal", "id"),
+];
+
+/// Tables whose ids come from an identity sequence, which has to be moved
+/// past the imported ids
+const IDENTITY_TABLES: [&str; 3] = ["items", "feedback", "board_journal"];
+
+/// Bumped whenever an older export can't be imported as it is
+const CAMPAIGN_FILE_VERSION: u64 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CampaignFile {
+    version: u64,
+    exported_at: DateTime<Utc>,
+    tables: BTreeMap<String, Vec<Value>>,
+}
+
+pub fn export_campaign(db: &dyn Storage, file: &Path) -> Result<(), String> {
+    let mut tables = BTreeMap::new();
+    for (table, _) in CAMPAIGN_TABLES {
+        let rows = futures::executor::block_on(db.select(Query::table(table)))?;
+        println!("{table}: {} rows", rows.len());
+        tables.insert(table.to_owned(), rows);
+    }
+
+    let campaign = CampaignFile {
+        version: CAMPAIGN_FILE_VERSION,
+        exported_at: Utc::now(),
+        tables,
+    };
+    let json = serde_json::to_string_pretty(&campaign).map_err(|e| e.to_string())?;
+    fs::write(file, json).map_err(|e| format!("Writing {}: {e}", file.display()))?;
+
+    println!("Exported the campaign to {}", file.display());
+    Ok(())
+}
+
+/// Refuses to mix the file into a campaign that already has anything in it,
+/// unless `replace` says to delete what's there first
+pub fn import_campaign(db: &dyn Storage, file: &Path, replace: bool) -> Result<(), String> {
+    let json = fs::read_to_string(file).map_err(|e| format!("Reading {}: {e}", file.display()))?;
+    let campaign: CampaignFile = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if campaign.version > CAMPAIGN_FILE_VERSION {
+        return Err(format!(
+            "The campaign was exported by a newer version of the server (file version {})",
+            campaign.version
+        ));
+    }
+
+    for table in campaign.tables.keys() {
+        if !CAMPAIGN_TABLES.iter().any(|(x, _)| x == table) {
+            println!("Skipping unknown table '{table}'");
+        }
+    }
+
+    let mut writes = Vec::new();
+    if replace {
+        let deletes = CAMPAIGN_TABLES
+            .iter()
+            .rev()
+            .map(|(table, column)| Write::delete(Query::table(*table).not_null(*column)));
+        writes.extend(deletes);
+    } else {
+        for (table, _) in CAMPAIGN_TABLES {
+            let rows = futures::executor::block_on(db.select(Query::table(table)))?;
+            if !rows.is_empty() {
+                return Err(format!(
+                    "The campaign already has {table}, pass --replace to delete it first"
+                ));
+            }
+        }
+    }
+
+    let mut counts = Vec::new();
+    for (table, _) in CAMPAIGN_TABLES {
+        let Some(rows) = campaign.tables.get(table).filter(|x| !x.is_empty()) else {
+            continue;
+        };
+        writes.push(Write::insert(table, Value::Array(rows.clone())));
+        counts.push((table, rows.len()));
+    }
+    writes.extend(IDENTITY_TABLES.map(|table| Write::reset_identity(table, "id")));
+
+    // All or nothing, so a bad file can't leave the old campaign half deleted
+    futures::executor::block_on(db.transaction(writes))?;
+    for (table, count) in counts {
+        println!("{table}: {count} rows");
+    }
+
+    println!(
+        "Imported the campaign exported at {}",
+        campaign.exported_at.format("%Y-%m-%d %H:%M")
+    );
+    Ok(())
+}
+
+pub fn prune_saves(db: &dyn Storage, keep: usize) -> Result<(), String> {
+    if keep == 0 {
+        return Err("Keeping 0 would delete every automatic snapshot".to_owned());
+    }
+    let pruned = DndServer::prune_snapshots(db, keep)?;
+    println!("Deleted {pruned} automatic snapshots, kept the newest {keep}");
+    Ok(())
+}
+
+pub fn list_users(db: &dyn Storage) -> Result<(), String> {
+    let gm = dotenv::var("DND_GM").ok();
+    let roster = DndServer::load_roster(db).map_err(|e| e.to_string())?;
+
+    for name in roster.iter() {
+        if gm.as_ref() == Some(name) {
+            println!("{name} (GM)");
+        } else {
+            println!("{name}");
+        }
+    }
+    if let Some(gm) = gm.filter(|x| !roster.contains(x)) {
+        println!("{gm} (GM, no character)");
+    }
+    Ok(())
+}
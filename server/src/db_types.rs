@@ -1,6 +1,13 @@
-use std::string;
+use std::{collections::HashMap, string};
 
-use common::{Ability, Item};
+use common::{
+    formula::{self, RollFormula},
+    message::PinnedMessage,
+    ruleset::Ruleset,
+    soundboard::Soundboard,
+    stats::RollStats,
+    Ability, EquipSlot, Handout, IssueReport, Item, Loot, Recharge, SnapshotInfo, XpTable,
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct DBItem {
@@ -9,11 +16,39 @@ pub struct DBItem {
     description: String,
     flavor_text: String,
     quest_item: bool,
+    armor_class: Option<i16>,
+    attack_bonus: Option<i16>,
+    #[serde(default)]
+    requires_attunement: bool,
+    #[serde(default)]
+    weight: Option<f32>,
+}
+
+impl DBItem {
+    pub fn with_count(self, count: u32) -> Item {
+        Item {
+            id: self.id,
+            count,
+            name: self.name,
+            description: self.description,
+            flavor_text: self.flavor_text,
+            quest_item: self.quest_item,
+            slot: None,
+            armor_class: self.armor_class,
+            attack_bonus: self.attack_bonus,
+            requires_attunement: self.requires_attunement,
+            attuned: false,
+            weight: self.weight,
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct DBItemResponse {
     count: u32,
+    slot: Option<EquipSlot>,
+    #[serde(default)]
+    attuned: bool,
     items: DBItem,
 }
 
@@ -21,12 +56,9 @@ pub struct DBItemResponse {
 impl Into<common::Item> for DBItemResponse {
     fn into(self) -> common::Item {
         Item {
-            id: self.items.id,
-            count: self.count,
-            name: self.items.name,
-            description: self.items.description,
-            flavor_text: self.items.flavor_text,
-            quest_item: self.items.quest_item,
+            slot: self.slot,
+            attuned: self.attuned,
+            ..self.items.with_count(self.count)
         }
     }
 }
@@ -40,9 +72,14 @@ pub struct DBAbility {
     flavor_text: Option<String>,
     resource: String,
     max_count: i64,
+    #[serde(default, deserialize_with = "formula::deserialize_lenient")]
+    to_hit: Option<RollFormula>,
+    #[serde(default, deserialize_with = "formula::deserialize_lenient")]
+    damage: Option<RollFormula>,
+    #[serde(default)]
+    recharge: Option<Recharge>,
 }
 
-
 #[derive(serde::Deserialize, Clone)]
 pub struct DBAbilityResponse {
     pub abilities: DBAbility,
@@ -61,7 +98,133 @@ impl Into<common::Ability> for DBAbilityResponse {
             resource: self.abilities.resource,
             max_count: self.abilities.max_count,
             uses: self.uses,
+            to_hit: self.abilities.to_hit,
+            damage: self.abilities.damage,
+            recharge: self.abilities.recharge,
         }
     }
 }
 
+/// Just enough of a catalog row to link it to a character
+#[derive(serde::Deserialize, Clone)]
+pub struct DBItemId {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct DBInventoryRow {
+    pub player: String,
+    pub item_id: i64,
+    pub count: u32,
+    pub slot: Option<EquipSlot>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct DBPlayerAbilityRow {
+    pub player: String,
+    pub ability_name: String,
+    pub uses: i64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct DBHandout {
+    pub id: uuid::Uuid,
+    #[serde(flatten)]
+    pub handout: Handout,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct DBPinnedMessage {
+    pub id: uuid::Uuid,
+    #[serde(flatten)]
+    pub pin: PinnedMessage,
+}
+
+/// The campaign only has one ruleset, it's always saved under [`RULESET_ID`]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct DBRuleset {
+    pub id: i64,
+    #[serde(flatten)]
+    pub ruleset: Ruleset,
+}
+
+pub const RULESET_ID: i64 = 1;
+
+/// Like the ruleset, there's only one XP table per campaign
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct DBXpTable {
+    pub id: i64,
+    pub levels: XpTable,
+}
+
+pub const XP_TABLE_ID: i64 = 1;
+
+/// Like the ruleset, there's only one soundboard per campaign
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct DBSoundboard {
+    pub id: i64,
+    #[serde(flatten)]
+    pub soundboard: Soundboard,
+}
+
+pub const SOUNDBOARD_ID: i64 = 1;
+
+/// A player's rolls from every session before this one
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct DBRollStats {
+    pub player: String,
+    pub stats: RollStats,
+    /// Their rolls this session so far, so a restart doesn't lose them
+    #[serde(default)]
+    pub session: RollStats,
+}
+
+/// Rows of every snapshotted table, keyed by table name
+#[derive(serde::Serialize, Clone)]
+pub struct DBSnapshot {
+    #[serde(flatten)]
+    pub info: SnapshotInfo,
+    /// Length of `data` as JSON, so the usage can be shown without loading it
+    pub size: u64,
+    pub data: serde_json::Value,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct DBSnapshotData {
+    pub tag: String,
+    pub data: HashMap<String, serde_json::Value>,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct DBFeedback {
+    pub username: String,
+    #[serde(flatten)]
+    pub report: IssueReport,
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct DBStashRow {
+    pub id: uuid::Uuid,
+    pub item_id: i64,
+    pub count: u32,
+    pub pending_claim: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct DBStashResponse {
+    pub id: uuid::Uuid,
+    count: u32,
+    pending_claim: Option<String>,
+    items: DBItem,
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<Loot> for DBStashResponse {
+    fn into(self) -> Loot {
+        Loot {
+            item: self.items.with_count(self.count),
+            pending_claim: self.pending_claim,
+        }
+    }
+}
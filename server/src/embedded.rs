@@ -0,0 +1,79 @@
+//! The server run inside another program, ie. the client's Host button, with
+//! the campaign kept in a folder of files instead of Supabase
+
+use std::{
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use log::{error, info};
+use message_io::node::NodeHandler;
+
+use crate::{
+    storage::{FileStorage, Storage},
+    DndServer, ServerSignal,
+};
+
+/// A server on its own thread, stopped when dropped
+pub struct EmbeddedServer {
+    addr: SocketAddr,
+    handler: NodeHandler<ServerSignal>,
+    thread: Option<JoinHandle<()>>,
+    // The character workers run here, it has to outlive the server
+    _runtime: tokio::runtime::Runtime,
+}
+
+impl EmbeddedServer {
+    /// Hosts the campaign saved in `dir` for anyone who can reach `port`,
+    /// with `gm` running the game
+    pub fn start(dir: PathBuf, port: u16, gm: &str) -> io::Result<Self> {
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let server = Self::launch(addr, Arc::new(FileStorage::new(dir)), |server| {
+            server.gm = Some(gm.to_owned())
+        })?;
+
+        info!("Hosting the campaign at {}, '{gm}' is the GM", server.addr);
+        Ok(server)
+    }
+
+    pub(crate) fn launch(
+        addr: SocketAddr,
+        db: Arc<dyn Storage>,
+        configure: impl FnOnce(&mut DndServer),
+    ) -> io::Result<Self> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        let _guard = runtime.enter();
+
+        let (mut server, addr) = DndServer::listen(addr, db)?;
+        configure(&mut server);
+
+        let handler = server.handler.clone();
+        let thread = thread::spawn(move || server.run());
+
+        Ok(Self {
+            addr,
+            handler,
+            thread: Some(thread),
+            _runtime: runtime,
+        })
+    }
+
+    /// Where it's listening, with the port the OS picked if it was given 0
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for EmbeddedServer {
+    fn drop(&mut self) {
+        self.handler.stop();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("The embedded server panicked");
+            }
+        }
+    }
+}
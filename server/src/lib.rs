@@ -0,0 +1,3916 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, VecDeque},
+    error::Error,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use log::{error, info, warn};
+use message_io::{
+    network::{Endpoint, NetEvent, Transport},
+    node::{self, NodeEvent, NodeHandler, NodeListener},
+};
+
+use common::{
+    message::{
+        compact_journal, record_journal_entry, replay_journal, AreaHit, BoardMessage, DieKind,
+        DieRoll, DndMessage, EffectMessage, HandoutMessage, JournalChange, JournalEntry,
+        JournalMessage, LogMessage, PinMessage, PinnedMessage, RollVisibility, SessionClock,
+        SessionClockMessage, SnapshotMessage, SoundMessage, StashMessage, TradeMessage,
+        JOURNAL_PAGE, MANUAL_SAVE_TAG,
+    },
+    ruleset::Ruleset,
+    soundboard::{NowPlaying, Soundboard},
+    stats::{counted_rolls, RollStats},
+    Ability, AbilityDefinition, Ambience, Annotation, BoardInfo, CampaignDate, Character, Cooldown,
+    DndPlayerPiece, EffectTarget, GridSettings, Handout, IssueReport, Item, ItemDefinition,
+    LifeState, Loot, PieceGroups, PieceTemplate, Recharge, RollTable, SnapshotInfo, SnapshotUsage,
+    SortingLayer, TimedEffect, Trade, User, XpTable, MAIN_BOARD,
+};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub mod admin;
+mod db_types;
+mod embedded;
+mod notifier;
+mod rate_limit;
+pub mod storage;
+#[cfg(test)]
+mod tests;
+mod worker;
+use db_types::*;
+pub use embedded::EmbeddedServer;
+use notifier::{loud_roll, Notifier};
+use rate_limit::{MessageCategory, RateLimiter, Throttle};
+use storage::{parse_rows, Query, Storage, StorageFuture, Write};
+use worker::{CharacterWorker, DbResult, WorkerPool};
+
+struct ClientInfo {
+    user_data: User,
+    endpoint: Endpoint,
+}
+
+/// Tables saved in a snapshot, in the order they're restored, along with a
+/// column every row has a value for
+const SNAPSHOT_TABLES: [(&str, &str); 6] = [
+    ("items", "id"),
+    ("abilities", "name"),
+    ("character", "name"),
+    ("characters", "player"),
+    ("inventory", "player"),
+    ("player_abilities", "player"),
+];
+
+/// Where the board goes in a snapshot's data, alongside the tables
+const SNAPSHOT_BOARD: &str = "board";
+
+/// The catalog isn't cleared on restore since the stash may point at newer
+/// entries, the snapshot's entries are upserted back over it instead
+const CATALOG_TABLES: [&str; 2] = ["items", "abilities"];
+
+/// Minutes between automatic snapshots. Override with `DND_SNAPSHOT_MINUTES`
+/// or `--snapshot-minutes`, 0 turns them off
+const DEFAULT_SNAPSHOT_MINUTES: u64 = 30;
+
+/// Older automatic snapshots are deleted, the GM's own are kept until they delete
+/// them. Override with `DND_KEEP_SNAPSHOTS`
+const DEFAULT_KEPT_SNAPSHOTS: usize = 10;
+
+/// The GM's own snapshots are never pruned, so stop taking more past this
+const MAX_TAGGED_SNAPSHOTS: usize = 50;
+
+/// Times the server re-reads a character that changed under it before giving up
+const MAX_CHARACTER_RETRIES: usize = 5;
+
+/// Tasks working through character sheet requests off the listener thread
+const CHARACTER_WORKERS: usize = 4;
+
+/// How often to check whether a break reminder is due
+const SESSION_TICK: Duration = Duration::from_secs(30);
+
+/// Holds not renewed for this long are released, clients renew well before
+const HOLD_TIMEOUT: Duration = Duration::from_secs(30);
+const HOLD_TICK: Duration = Duration::from_secs(5);
+
+/// Public log messages remembered so pins can be checked against them
+const RECENT_LOG_LEN: usize = 500;
+
+/// Board changes come in many times a second while pieces are dragged, so the
+/// journal is written out in batches
+const JOURNAL_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Clients ping every few seconds, anyone not heard from in this long is
+/// assumed gone. Connections behind NATs can die without ever closing
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+const NOT_OWNER: &str = "You can only control your own pieces";
+const GM_ONLY_VISION: &str = "Only the GM can change what a piece can see";
+const NOT_AUTHOR: &str = "You can only erase your own annotations";
+
+enum ServerSignal {
+    Snapshot,
+    SessionTick,
+    ExpireHolds,
+    CheckConnections,
+    SaveJournal,
+}
+
+/// How often to take automatic snapshots, from `DND_SNAPSHOT_MINUTES`. `None`
+/// when they're turned off
+fn snapshot_interval() -> Option<Duration> {
+    let minutes = match dotenv::var("DND_SNAPSHOT_MINUTES") {
+        Ok(minutes) => minutes.parse().unwrap_or_else(|e| {
+            warn!("DND_SNAPSHOT_MINUTES should be a number, got '{minutes}': {e}");
+            DEFAULT_SNAPSHOT_MINUTES
+        }),
+        Err(_) => DEFAULT_SNAPSHOT_MINUTES,
+    };
+    minutes_to_interval(minutes)
+}
+
+pub fn minutes_to_interval(minutes: u64) -> Option<Duration> {
+    (minutes > 0).then(|| Duration::from_secs(minutes.saturating_mul(60)))
+}
+
+/// How many automatic snapshots to keep, from `DND_KEEP_SNAPSHOTS`. 0 would
+/// prune every one of them, so it falls back to the default
+pub fn kept_snapshots() -> usize {
+    let Ok(keep) = dotenv::var("DND_KEEP_SNAPSHOTS") else {
+        return DEFAULT_KEPT_SNAPSHOTS;
+    };
+    match keep.parse() {
+        Ok(0) => {
+            warn!("DND_KEEP_SNAPSHOTS can't be 0, keeping {DEFAULT_KEPT_SNAPSHOTS}");
+            DEFAULT_KEPT_SNAPSHOTS
+        }
+        Ok(keep) => keep,
+        Err(e) => {
+            warn!("DND_KEEP_SNAPSHOTS should be a number, got '{keep}': {e}");
+            DEFAULT_KEPT_SNAPSHOTS
+        }
+    }
+}
+
+/// Writes the character's new count of the item, `held` being what they have
+/// now. Rows are removed at zero like the sheet does
+fn set_inventory_count(character: &str, item_id: i64, held: Option<u32>, count: u32) -> Write {
+    let query = Query::table("inventory")
+        .eq("player", character)
+        .eq("item_id", item_id);
+    match held {
+        Some(_) if count == 0 => Write::delete(query),
+        Some(_) => Write::update(query, serde_json::json!({ "count": count })),
+        None => Write::insert(
+            "inventory",
+            serde_json::json!({ "player": character, "item_id": item_id, "count": count }),
+        ),
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+struct BoardData {
+    players: HashMap<uuid::Uuid, DndPlayerPiece>,
+    annotations: HashMap<uuid::Uuid, Annotation>,
+    ambience: Ambience,
+    date: CampaignDate,
+    grid: GridSettings,
+    boards: HashMap<uuid::Uuid, BoardInfo>,
+    active_board: uuid::Uuid,
+    groups: PieceGroups,
+}
+
+impl BoardData {
+    /// A board with nothing on it, where journal replays start from
+    fn empty() -> Self {
+        Self {
+            boards: HashMap::from([(MAIN_BOARD, BoardInfo::main())]),
+            ..Default::default()
+        }
+    }
+
+    /// Applies a change that's already been allowed. Returns false if it
+    /// didn't apply and shouldn't be passed on
+    fn apply(&mut self, msg: BoardMessage) -> bool {
+        match msg {
+            BoardMessage::AddPlayerPiece(uuid, player)
+            | BoardMessage::UpdatePlayerPiece(uuid, player) => {
+                self.players.insert(uuid, player);
+            }
+            BoardMessage::UpdatePlayerLocation(uuid, new_location) => {
+                if let Some(player) = self.players.get_mut(&uuid) {
+                    player.position = new_location;
+                }
+            }
+            BoardMessage::DeletePlayerPiece(uuid) => {
+                self.players.remove(&uuid);
+                self.groups.retain_pieces(|x| *x != uuid);
+            }
+            BoardMessage::AddAnnotation(uuid, annotation) => {
+                self.annotations.insert(uuid, annotation);
+            }
+            BoardMessage::DeleteAnnotation(uuid) => {
+                self.annotations.remove(&uuid);
+            }
+            BoardMessage::ClearAnnotations(board, layer) => {
+                self.annotations
+                    .retain(|_, annotation| annotation.board != board || annotation.layer != layer);
+            }
+            BoardMessage::SetAmbience(ambience) => self.ambience = ambience,
+            BoardMessage::SetCampaignDate(date) => self.date = date,
+            BoardMessage::SetGrid(grid) => self.grid = grid,
+            BoardMessage::AdvanceTime(minutes) => {
+                self.date = self.date.advanced(minutes);
+                info!("Campaign date is now {}", self.date);
+            }
+            BoardMessage::GroupPieces(uuid, pieces) => self.groups.set(uuid, pieces),
+            BoardMessage::Ungroup(uuid) => self.groups.remove(&uuid),
+            BoardMessage::CreateBoard(uuid, name) => {
+                info!("Created board '{}'", name);
+                self.boards.insert(
+                    uuid,
+                    BoardInfo {
+                        name,
+                        ..Default::default()
+                    },
+                );
+            }
+            BoardMessage::DeleteBoard(uuid) => {
+                if uuid == MAIN_BOARD {
+                    warn!("The main board can't be deleted");
+                    return false;
+                }
+
+                self.boards.remove(&uuid);
+                self.players.retain(|_, x| x.board != uuid);
+                self.groups.retain_pieces(|x| self.players.contains_key(x));
+                self.annotations.retain(|_, x| x.board != uuid);
+                for board in self.boards.values_mut() {
+                    board.portals.retain(|_, x| x.target_board != uuid);
+                }
+                if self.active_board == uuid {
+                    self.active_board = MAIN_BOARD;
+                }
+            }
+            BoardMessage::SetActiveBoard(uuid) => {
+                if !self.boards.contains_key(&uuid) {
+                    error!("Board {uuid} could not be found on the server!");
+                    return false;
+                }
+
+                self.active_board = uuid;
+            }
+            BoardMessage::AddPortal(board, uuid, portal) => {
+                let Some(board) = self.boards.get_mut(&board) else {
+                    error!("Board {board} could not be found on the server!");
+                    return false;
+                };
+
+                board.portals.insert(uuid, portal);
+            }
+            BoardMessage::DeletePortal(board, uuid) => {
+                if let Some(board) = self.boards.get_mut(&board) {
+                    board.portals.remove(&uuid);
+                }
+            }
+            BoardMessage::AddWall(board, uuid, wall) => {
+                let Some(board) = self.boards.get_mut(&board) else {
+                    error!("Board {board} could not be found on the server!");
+                    return false;
+                };
+
+                board.walls.insert(uuid, wall);
+            }
+            BoardMessage::DeleteWall(board, uuid) => {
+                if let Some(board) = self.boards.get_mut(&board) {
+                    board.walls.remove(&uuid);
+                }
+            }
+            BoardMessage::SetDoorOpen(board, uuid, open) => {
+                let Some(wall) = self
+                    .boards
+                    .get_mut(&board)
+                    .and_then(|x| x.walls.get_mut(&uuid))
+                    .filter(|x| x.door.is_some())
+                else {
+                    error!("Door {uuid} could not be found on the server!");
+                    return false;
+                };
+
+                wall.door = Some(open);
+            }
+            BoardMessage::HoldPiece(_)
+            | BoardMessage::ReleasePiece(_)
+            | BoardMessage::SetHolder(..) => return false,
+        }
+
+        true
+    }
+
+    /// Changes that set up this board from an empty one, for squashing the journal
+    fn messages(&self) -> Vec<BoardMessage> {
+        let mut messages = Vec::new();
+        for (uuid, board) in self.boards.iter() {
+            messages.push(BoardMessage::CreateBoard(*uuid, board.name.clone()));
+            messages.extend(
+                board
+                    .portals
+                    .iter()
+                    .map(|(id, portal)| BoardMessage::AddPortal(*uuid, *id, *portal)),
+            );
+            messages.extend(
+                board
+                    .walls
+                    .iter()
+                    .map(|(id, wall)| BoardMessage::AddWall(*uuid, *id, *wall)),
+            );
+        }
+        messages.push(BoardMessage::SetActiveBoard(self.active_board));
+        messages.extend(
+            self.players
+                .iter()
+                .map(|(uuid, x)| BoardMessage::AddPlayerPiece(*uuid, x.clone())),
+        );
+        messages.extend(
+            self.groups
+                .0
+                .iter()
+                .map(|(uuid, x)| BoardMessage::GroupPieces(*uuid, x.clone())),
+        );
+        messages.extend(
+            self.annotations
+                .iter()
+                .map(|(uuid, x)| BoardMessage::AddAnnotation(*uuid, x.clone())),
+        );
+        messages.push(BoardMessage::SetGrid(self.grid));
+        messages.push(BoardMessage::SetCampaignDate(self.date));
+        messages.push(BoardMessage::SetAmbience(self.ambience));
+        messages
+    }
+
+    /// The board after the first `count` journal entries
+    fn replayed(journal: &[JournalEntry], count: usize) -> Self {
+        let mut data = Self::empty();
+        for msg in replay_journal(journal, count) {
+            data.apply(msg.clone());
+        }
+        data
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct EffectData {
+    round: u32,
+    effects: HashMap<uuid::Uuid, TimedEffect>,
+    cooldowns: HashMap<uuid::Uuid, Cooldown>,
+}
+
+#[derive(Debug, Clone)]
+struct PartyInventory {
+    loot: HashMap<uuid::Uuid, Loot>,
+    require_approval: bool,
+}
+
+/// Real time clock the GM runs during a session
+#[derive(Debug, Clone, Default)]
+struct SessionTimer {
+    /// Set while the clock is running
+    started: Option<Instant>,
+    /// Time counted before the clock was last paused
+    banked: Duration,
+    break_every: Option<u32>,
+    /// Break intervals already reminded about
+    breaks: u64,
+}
+
+impl SessionTimer {
+    fn elapsed(&self) -> Duration {
+        self.banked + self.started.map(|x| x.elapsed()).unwrap_or_default()
+    }
+
+    fn clock(&self) -> SessionClock {
+        SessionClock {
+            running: self.started.is_some(),
+            elapsed: self.elapsed().as_secs(),
+            break_every: self.break_every,
+        }
+    }
+
+    fn break_interval(&self) -> Option<u64> {
+        self.break_every
+            .filter(|x| *x > 0)
+            .map(|x| u64::from(x) * 60)
+    }
+
+    /// Minutes into the session when another break is due
+    fn due_break(&mut self) -> Option<u64> {
+        let interval = self.break_interval()?;
+        let elapsed = self.elapsed().as_secs();
+        if self.started.is_none() || elapsed / interval <= self.breaks {
+            return None;
+        }
+
+        self.breaks = elapsed / interval;
+        Some(elapsed / 60)
+    }
+}
+
+/// Soft lock on a piece while someone drags it or has its properties open
+#[derive(Debug, Clone)]
+struct PieceHold {
+    user: String,
+    renewed: Instant,
+}
+
+impl PieceHold {
+    fn expired(&self) -> bool {
+        self.renewed.elapsed() >= HOLD_TIMEOUT
+    }
+}
+
+/// A trade being negotiated, along with what each side held when they made their offer
+#[derive(Debug, Clone)]
+struct TradeSession {
+    trade: Trade,
+    /// Item id to the count held, for every offered item
+    snapshots: [HashMap<i64, u32>; 2],
+}
+
+pub struct DndServer {
+    handler: NodeHandler<ServerSignal>,
+    board_data: BoardData,
+    /// Every board change applied, oldest first
+    journal: Vec<JournalEntry>,
+    /// How many of the journal's entries are in storage, `None` once it's
+    /// been squashed and has to be written out again
+    journal_saved: Option<usize>,
+    holds: HashMap<uuid::Uuid, PieceHold>,
+    effect_data: EffectData,
+    handouts: HashMap<uuid::Uuid, Handout>,
+    pins: HashMap<uuid::Uuid, PinnedMessage>,
+    /// The latest log messages sent out, as serialized `(user, message)`, so
+    /// nobody can pin a quote someone else never said
+    recent_log: RefCell<VecDeque<Vec<u8>>>,
+    stash: PartyInventory,
+    trades: HashMap<uuid::Uuid, TradeSession>,
+    xp_table: XpTable,
+    ruleset: Ruleset,
+    roll_tables: Vec<RollTable>,
+    piece_templates: Vec<PieceTemplate>,
+    /// Public rolls by player since the session clock was last reset
+    session_rolls: BTreeMap<String, RollStats>,
+    /// Rolls from every session before this one, saved whenever one ends
+    campaign_rolls: BTreeMap<String, RollStats>,
+    soundboard: Soundboard,
+    /// Music the GM started and when, so late joiners can start part way in
+    now_playing: Option<(NowPlaying, Instant)>,
+    /// Names of every character, kept in step with the database as characters
+    /// come and go so clients never have to ask for it
+    roster: Vec<String>,
+    /// Automatic snapshots kept before the oldest are pruned
+    keep_snapshots: usize,
+    /// `None` when automatic snapshots are off
+    snapshot_interval: Option<Duration>,
+    session_timer: SessionTimer,
+    node_listener: Option<NodeListener<ServerSignal>>,
+    users: HashMap<String, ClientInfo>,
+    gm: Option<String>,
+    db: Arc<dyn Storage>,
+    workers: WorkerPool,
+    rate_limiter: RateLimiter,
+    /// When each endpoint last sent anything
+    last_heard: HashMap<Endpoint, Instant>,
+    connection_timeout: Duration,
+    notifier: Notifier,
+}
+
+impl DndServer {
+    pub fn new(addr: &str, port: u16) -> io::Result<Self> {
+        let addr = (addr, port).to_socket_addrs().unwrap().next().unwrap();
+        let (server, addr) = Self::listen(addr, storage::from_env())?;
+
+        info!("Server running at {}", addr);
+        Ok(server)
+    }
+
+    pub fn with_snapshot_interval(mut self, interval: Option<Duration>) -> Self {
+        self.snapshot_interval = interval;
+        self
+    }
+
+    /// Starts listening on `addr` with the campaign kept in `db`, handing back
+    /// the address it's actually bound to. Must be called from inside the
+    /// tokio runtime
+    fn listen(addr: SocketAddr, db: Arc<dyn Storage>) -> io::Result<(Self, SocketAddr)> {
+        let (handler, node_listener) = node::split::<ServerSignal>();
+        let (_, addr) = handler.network().listen(Transport::Ws, addr)?;
+
+        let gm = dotenv::var("DND_GM").ok();
+        match &gm {
+            Some(gm) => info!("'{}' is the GM", gm),
+            None => warn!("No GM configured, set DND_GM to enable GM features"),
+        }
+
+        let keep_snapshots = kept_snapshots();
+        info!("Keeping the last {keep_snapshots} automatic snapshots");
+
+        let handouts = Self::load_handouts(&*db).unwrap_or_else(|e| {
+            error!("Failed to load handouts: {e:?}");
+            HashMap::new()
+        });
+
+        // The board is whatever the journal left it as
+        let journal = Self::load_journal(&*db).unwrap_or_else(|e| {
+            error!("Failed to load the board journal: {e:?}");
+            Vec::new()
+        });
+        let board_data = BoardData::replayed(&journal, journal.len());
+
+        let pins = Self::load_pins(&*db).unwrap_or_else(|e| {
+            error!("Failed to load pinned messages: {e:?}");
+            HashMap::new()
+        });
+
+        let loot = Self::load_stash(&*db).unwrap_or_else(|e| {
+            error!("Failed to load the party stash: {e:?}");
+            HashMap::new()
+        });
+
+        let ruleset = Self::load_ruleset(&*db).unwrap_or_else(|e| {
+            error!("Failed to load the ruleset: {e:?}");
+            Ruleset::default()
+        });
+
+        let xp_table = Self::load_xp_table(&*db).unwrap_or_else(|e| {
+            error!("Failed to load the XP table: {e:?}");
+            XpTable::default()
+        });
+
+        let roll_tables = Self::load_roll_tables(&*db).unwrap_or_else(|e| {
+            error!("Failed to load roll tables: {e:?}");
+            Vec::new()
+        });
+
+        let piece_templates = Self::load_piece_templates(&*db).unwrap_or_else(|e| {
+            error!("Failed to load piece templates: {e:?}");
+            Vec::new()
+        });
+
+        let roll_stats = Self::load_roll_stats(&*db).unwrap_or_else(|e| {
+            error!("Failed to load roll stats: {e:?}");
+            Vec::new()
+        });
+        let campaign_rolls = roll_stats
+            .iter()
+            .map(|x| (x.player.clone(), x.stats.clone()))
+            .collect();
+        // Picks the session back up if the server restarted part way through
+        let session_rolls = roll_stats
+            .into_iter()
+            .filter(|x| x.session.rolls > 0)
+            .map(|x| (x.player, x.session))
+            .collect();
+
+        let soundboard = Self::load_soundboard(&*db).unwrap_or_else(|e| {
+            error!("Failed to load the soundboard: {e:?}");
+            Soundboard::default()
+        });
+
+        let roster = Self::load_roster(&*db).unwrap_or_else(|e| {
+            error!("Failed to load the character roster: {e:?}");
+            Vec::new()
+        });
+
+        let workers = WorkerPool::new(
+            CharacterWorker::new(db.clone(), handler.clone()),
+            CHARACTER_WORKERS,
+        );
+
+        let notifier = Notifier::from_env();
+
+        let server = Self {
+            db,
+            workers,
+            handler,
+            node_listener: Some(node_listener),
+            users: HashMap::new(),
+            gm,
+            board_data,
+            journal_saved: Some(journal.len()),
+            journal,
+            holds: HashMap::new(),
+            effect_data: EffectData::default(),
+            handouts,
+            pins,
+            recent_log: RefCell::default(),
+            stash: PartyInventory {
+                loot,
+                require_approval: true,
+            },
+            trades: HashMap::new(),
+            xp_table,
+            ruleset,
+            roll_tables,
+            piece_templates,
+            session_rolls,
+            campaign_rolls,
+            soundboard,
+            now_playing: None,
+            roster,
+            keep_snapshots,
+            snapshot_interval: snapshot_interval(),
+            session_timer: SessionTimer::default(),
+            rate_limiter: RateLimiter::default(),
+            last_heard: HashMap::new(),
+            connection_timeout: CONNECTION_TIMEOUT,
+            notifier,
+        };
+        Ok((server, addr))
+    }
+
+    pub fn run(mut self) {
+        let node_listener = self.node_listener.take().unwrap();
+        match self.snapshot_interval {
+            Some(interval) => {
+                let minutes = interval.as_secs() / 60;
+                info!("Taking automatic snapshots every {minutes} minutes");
+                self.handler
+                    .signals()
+                    .send_with_timer(ServerSignal::Snapshot, interval);
+            }
+            None => info!("Automatic snapshots are off"),
+        }
+        self.handler
+            .signals()
+            .send_with_timer(ServerSignal::SessionTick, SESSION_TICK);
+        self.handler
+            .signals()
+            .send_with_timer(ServerSignal::ExpireHolds, HOLD_TICK);
+        self.handler
+            .signals()
+            .send_with_timer(ServerSignal::SaveJournal, JOURNAL_SAVE_INTERVAL);
+        self.handler.signals().send_with_timer(
+            ServerSignal::CheckConnections,
+            self.connection_check_interval(),
+        );
+
+        node_listener.for_each(move |event| match event {
+            NodeEvent::Signal(signal) => self.handle_signal(signal),
+            NodeEvent::Network(event) => match event {
+                NetEvent::Connected(_, _) => unreachable!(),
+                NetEvent::Accepted(endpoint, _) => {
+                    self.last_heard.insert(endpoint, Instant::now());
+                }
+                NetEvent::Message(endpoint, input_data) => {
+                    self.last_heard.insert(endpoint, Instant::now());
+
+                    let message: DndMessage = match bincode::deserialize(input_data) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            warn!("Dropping malformed message from {endpoint}: {e}");
+                            return;
+                        }
+                    };
+
+                    let category = MessageCategory::of(&message);
+                    if let Throttle::Dropped { notify } =
+                        self.rate_limiter.check(endpoint, category)
+                    {
+                        if notify {
+                            self.notify_throttled(endpoint, category);
+                        }
+                        return;
+                    }
+
+                    match message {
+                        DndMessage::RegisterUser(name) => {
+                            self.register(&name, endpoint);
+                            self.broadcast_log_message(
+                                endpoint,
+                                User::server(),
+                                LogMessage::Joined(name),
+                            )
+                        }
+                        DndMessage::UnregisterUser(name) => {
+                            self.unregister(&name);
+                        }
+                        DndMessage::UserNotificationRemoved(_) => todo!(),
+                        DndMessage::Log(user, msg, _) => {
+                            self.handle_log_message(endpoint, user, msg)
+                        }
+                        DndMessage::RetrieveCharacterData(user) => {
+                            self.workers
+                                .submit(endpoint, DndMessage::RetrieveCharacterData(user.clone()));
+
+                            self.send_initial_board_data(endpoint, &user.name);
+                            self.send_initial_effect_data(endpoint);
+                            self.send_initial_handouts(endpoint, &user.name);
+                            self.send_initial_pins(endpoint);
+                            self.send_initial_stash(endpoint);
+                        }
+                        DndMessage::RetrievePartyMember(_)
+                        | DndMessage::UpdateItemCount(..)
+                        | DndMessage::UpdateAbilityCount(..)
+                        | DndMessage::UpdateCharacter(..)
+                        | DndMessage::UpdatePowerSlotCount(..)
+                        | DndMessage::UpdateItemSlot(..) => self.workers.submit(endpoint, message),
+                        DndMessage::UpdateItemAttunement(user, item_id, attuned) => {
+                            let result = self.set_item_attunement(&user, item_id, attuned);
+                            if let Err(e) = result {
+                                // Put back the attunement the client already showed
+                                self.report_error(endpoint, "Attuning item", Err(e));
+                                self.workers
+                                    .submit(endpoint, DndMessage::RetrieveCharacterData(user));
+                            }
+                        }
+                        DndMessage::AwardXp(names, xp) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.award_xp(names, xp);
+                                self.report_error(endpoint, "Awarding XP", result);
+                            } else {
+                                warn!("Only the GM can award XP");
+                            }
+                        }
+                        DndMessage::GrantInspiration(names, points) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.grant_inspiration(names, points);
+                                self.report_error(endpoint, "Granting inspiration", result);
+                            } else {
+                                warn!("Only the GM can grant inspiration");
+                            }
+                        }
+                        DndMessage::SpendInspiration(user) => {
+                            if self.username(endpoint) == Some(&user.name) {
+                                let result = self.spend_inspiration(user);
+                                self.report_error(endpoint, "Spending inspiration", result);
+                            } else {
+                                warn!("'{}' can only be spent by its owner", user.name);
+                            }
+                        }
+                        DndMessage::AdjustHp(name, amount) => {
+                            let from = self.username(endpoint).cloned();
+                            if self.is_gm_endpoint(endpoint) || from.as_ref() == Some(&name) {
+                                let user = User {
+                                    name: from.unwrap_or_default(),
+                                };
+                                let result = self.adjust_hp(user, name, amount);
+                                self.report_error(endpoint, "Changing HP", result);
+                            } else {
+                                warn!("Only the GM or '{}' can change their HP", name);
+                            }
+                        }
+                        DndMessage::ApplyAreaDamage(source, roll, hits) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let user = User {
+                                    name: self.username(endpoint).cloned().unwrap_or_default(),
+                                };
+                                let result = self.apply_area_damage(user, source, roll, hits);
+                                self.report_error(endpoint, "Applying area damage", result);
+                            } else {
+                                warn!("Only the GM can apply area damage");
+                            }
+                        }
+                        DndMessage::RollDeathSave(name) => {
+                            let from = self.username(endpoint).cloned();
+                            if self.is_gm_endpoint(endpoint) || from.as_ref() == Some(&name) {
+                                let user = User {
+                                    name: from.unwrap_or_default(),
+                                };
+                                let result = self.roll_death_save(user, name);
+                                self.report_error(endpoint, "Rolling a death save", result);
+                            } else {
+                                warn!("Only the GM or '{}' can roll their death saves", name);
+                            }
+                        }
+                        DndMessage::SetLifeState(name, life) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let user = User {
+                                    name: self.username(endpoint).cloned().unwrap_or_default(),
+                                };
+                                let result = self.set_life_state(user, name, life);
+                                self.report_error(endpoint, "Marking a character", result);
+                            } else {
+                                warn!("Only the GM can mark characters dead or stable");
+                            }
+                        }
+                        DndMessage::SetXpTable(table) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.save_xp_table(endpoint, table);
+                                self.report_error(endpoint, "Saving XP table", result);
+                            } else {
+                                warn!("Only the GM can change the XP table");
+                            }
+                        }
+                        DndMessage::SetRuleset(ruleset) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.save_ruleset(endpoint, ruleset);
+                                self.report_error(endpoint, "Saving ruleset", result);
+                            } else {
+                                warn!("Only the GM can change the ruleset");
+                            }
+                        }
+                        DndMessage::SaveRollTable(table) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.save_roll_table(endpoint, table);
+                                self.report_error(endpoint, "Saving roll table", result);
+                            } else {
+                                warn!("Only the GM can change roll tables");
+                            }
+                        }
+                        DndMessage::DeleteRollTable(name) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.delete_roll_table(endpoint, name);
+                                self.report_error(endpoint, "Deleting roll table", result);
+                            } else {
+                                warn!("Only the GM can change roll tables");
+                            }
+                        }
+                        DndMessage::SavePieceTemplate(template) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.save_piece_template(endpoint, template);
+                                self.report_error(endpoint, "Sharing piece template", result);
+                            } else {
+                                warn!("Only the GM can share piece templates");
+                            }
+                        }
+                        DndMessage::DeletePieceTemplate(name) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                let result = self.delete_piece_template(endpoint, name);
+                                self.report_error(endpoint, "Unsharing piece template", result);
+                            } else {
+                                warn!("Only the GM can share piece templates");
+                            }
+                        }
+                        DndMessage::CreateCharacter(character) => {
+                            let result = self.create_character(character);
+                            self.report_error(endpoint, "Creating character", result);
+                        }
+                        DndMessage::ImportCharacter(character, items, abilities) => {
+                            let result =
+                                self.import_character(endpoint, character, items, abilities);
+                            self.report_error(endpoint, "Importing character", result);
+                        }
+                        DndMessage::DeleteCharacter(name) => self.delete_character(endpoint, name),
+                        DndMessage::ImportItems(items) => {
+                            self.import_rows(endpoint, "items", &items)
+                        }
+                        DndMessage::ImportAbilities(abilities) => {
+                            self.import_rows(endpoint, "abilities", &abilities)
+                        }
+                        DndMessage::ReportIssue(report) => {
+                            let result = self.save_issue_report(endpoint, report);
+                            self.report_error(endpoint, "Sending issue report", result);
+                        }
+                        DndMessage::BoardMessage(msg) => self.handle_board_message(endpoint, msg),
+                        DndMessage::EffectMessage(msg) => self.handle_effect_message(endpoint, msg),
+                        DndMessage::HandoutMessage(msg) => {
+                            self.handle_handout_message(endpoint, msg)
+                        }
+                        DndMessage::StashMessage(msg) => self.handle_stash_message(endpoint, msg),
+                        DndMessage::TradeMessage(msg) => self.handle_trade_message(endpoint, msg),
+                        DndMessage::SnapshotMessage(msg) => {
+                            self.handle_snapshot_message(endpoint, msg)
+                        }
+                        DndMessage::SessionClockMessage(msg) => {
+                            self.handle_session_clock_message(endpoint, msg)
+                        }
+                        DndMessage::JournalMessage(msg) => {
+                            self.handle_journal_message(endpoint, msg)
+                        }
+                        DndMessage::PinMessage(msg) => self.handle_pin_message(endpoint, msg),
+                        DndMessage::SoundMessage(msg) => {
+                            let result = self.handle_sound_message(endpoint, msg);
+                            self.report_error(endpoint, "Saving soundboard", result);
+                        }
+                        DndMessage::Typing(..) => self.broadcast_message(endpoint, message),
+                        DndMessage::FocusView(..) | DndMessage::GmView(_) => {
+                            if self.is_gm_endpoint(endpoint) {
+                                self.broadcast_message(endpoint, message);
+                            } else {
+                                warn!("Only the GM can move everyone's view");
+                            }
+                        }
+                        DndMessage::Ping(sent) => {
+                            let output_data = bincode::serialize(&DndMessage::Pong(sent)).unwrap();
+                            self.handler.network().send(endpoint, &output_data);
+                        }
+                        _ => {
+                            warn!("Unhandled message {message:?}");
+                        }
+                    }
+                }
+                NetEvent::Disconnected(endpoint) => self.disconnect(endpoint),
+            },
+        });
+    }
+
+    fn disconnect(&mut self, endpoint: Endpoint) {
+        self.rate_limiter.remove(endpoint);
+        self.last_heard.remove(&endpoint);
+
+        let user = self
+            .users
+            .iter()
+            .find(|(_, info)| info.endpoint == endpoint);
+
+        if let Some((name, _)) = user {
+            self.broadcast_log_message(
+                endpoint,
+                User::server(),
+                LogMessage::Disconnected(name.clone()),
+            );
+            self.unregister(&name.clone());
+        }
+    }
+
+    /// Often enough that nobody lingers much past the timeout
+    fn connection_check_interval(&self) -> Duration {
+        self.connection_timeout / 4
+    }
+
+    /// Closes connections that have gone quiet for longer than the timeout.
+    /// Closing them ourselves doesn't raise a `Disconnected` event, so they're
+    /// cleaned up the same way here
+    fn drop_stale_connections(&mut self) {
+        let stale = self
+            .last_heard
+            .iter()
+            .filter(|(_, heard)| heard.elapsed() > self.connection_timeout)
+            .map(|(endpoint, _)| *endpoint)
+            .collect::<Vec<_>>();
+
+        for endpoint in stale {
+            warn!("Nothing heard from {endpoint} in a while, dropping the connection");
+            self.handler.network().remove(endpoint.resource_id());
+            self.disconnect(endpoint);
+        }
+    }
+
+    fn register(&mut self, name: &str, endpoint: Endpoint) {
+        if !self.users.contains_key(name) {
+            let list = self.users.keys().cloned().collect();
+
+            let message = DndMessage::UserList(list);
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            if let Some(gm) = &self.gm {
+                let message = DndMessage::GameMaster(gm.clone());
+                let output_data = bincode::serialize(&message).unwrap();
+                self.handler.network().send(endpoint, &output_data);
+            }
+
+            let message = DndMessage::SetXpTable(self.xp_table.clone());
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            let message = DndMessage::SetRuleset(self.ruleset.clone());
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            let message = DndMessage::RollTables(self.roll_tables.clone());
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            let message = DndMessage::PieceTemplates(self.piece_templates.clone());
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            let message =
+                DndMessage::RollStats(self.session_rolls.clone(), self.campaign_rolls.clone());
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            let clock = self.session_timer.clock();
+            let message = DndMessage::SessionClockMessage(SessionClockMessage::State(clock));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            let message =
+                DndMessage::SoundMessage(SoundMessage::SetSoundboard(self.soundboard.clone()));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            if let Some((playing, started)) = &self.now_playing {
+                let playing = NowPlaying {
+                    elapsed: playing.elapsed + started.elapsed().as_secs_f32(),
+                    ..playing.clone()
+                };
+                let message = DndMessage::SoundMessage(SoundMessage::Play(playing));
+                let output_data = bincode::serialize(&message).unwrap();
+                self.handler.network().send(endpoint, &output_data);
+            }
+
+            if self.gm.as_deref() == Some(name) {
+                self.send_snapshot_list(endpoint);
+            }
+
+            let message = DndMessage::CharacterRoster(self.roster.clone());
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+
+            // Notify other users about this new user
+            let message = DndMessage::UserNotificationAdded(name.to_string());
+            let output_data = bincode::serialize(&message).unwrap();
+            for (_name, user) in self.users.iter() {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+
+            self.users.insert(
+                name.to_string(),
+                ClientInfo {
+                    user_data: User {
+                        name: name.to_string(),
+                    },
+                    endpoint,
+                },
+            );
+
+            info!("Added user '{}'", name);
+            self.notifier.notify(format!("{} joined the session", name));
+        } else {
+            info!(
+                "User with name '{}' already exists, whart are you doing??",
+                name
+            );
+        }
+    }
+
+    fn unregister(&mut self, name: &str) {
+        if let Some(info) = self.users.remove(name) {
+            let message = DndMessage::UserNotificationRemoved(name.to_string());
+            let output_data = bincode::serialize(&message).unwrap();
+            for (_name, user) in self.users.iter() {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+
+            // Nobody is left to confirm their trades
+            let trades: Vec<_> = self
+                .trades
+                .iter()
+                .filter(|(_, session)| session.trade.side(name).is_some())
+                .map(|(uuid, _)| *uuid)
+                .collect();
+            for uuid in trades {
+                if let Some(session) = self.trades.remove(&uuid) {
+                    self.close_trade(uuid, &session.trade, format!("{} left", name));
+                }
+            }
+
+            // Whatever they were dragging or editing is free again
+            let held = self
+                .holds
+                .iter()
+                .filter(|(_, hold)| hold.user == name)
+                .map(|(uuid, _)| *uuid)
+                .collect::<Vec<_>>();
+            for uuid in held {
+                self.holds.remove(&uuid);
+                self.send_holder(uuid, None);
+            }
+
+            info!("Removed participant '{}'", name);
+        } else {
+            error!("Cannot unregister a user '{}' who doesn't exist??", name);
+        }
+    }
+
+    fn load_roster(db: &dyn Storage) -> Result<Vec<String>, Box<dyn Error>> {
+        #[derive(serde::Deserialize)]
+        struct Name {
+            name: String,
+        }
+
+        let query = Query::table("character").select("name").order("name");
+        let names: Vec<Name> = parse_rows(futures::executor::block_on(db.select(query))?)?;
+        info!("Loaded {} characters", names.len());
+        Ok(names.into_iter().map(|x| x.name).collect())
+    }
+
+    fn create_character(&mut self, character: Character) -> Result<(), String> {
+        self.check_new_character(&character.name)?;
+
+        let json = serde_json::to_value(&character).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.insert("character", json))?;
+
+        self.character_created(character);
+        Ok(())
+    }
+
+    fn check_new_character(&self, name: &str) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("Characters need a name".to_owned());
+        }
+
+        if self.roster.iter().any(|x| x == name) {
+            return Err(format!("Character '{}' already exists", name));
+        }
+        Ok(())
+    }
+
+    /// Adds a saved character to the roster
+    fn character_created(&mut self, character: Character) {
+        let name = character.name.clone();
+        info!("Created character '{}'", name);
+
+        self.roster.push(name.clone());
+        self.roster.sort();
+        self.broadcast_roster();
+
+        // The player may already be connected under this name, give them their sheet straight away
+        if let Some(user) = self.users.get(&name) {
+            let message = DndMessage::CharacterData(character);
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+
+    fn delete_character(&mut self, from: Endpoint, name: String) {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can delete characters");
+            return;
+        }
+
+        // Their items and abilities go first, otherwise a new character with
+        // the same name would pick them back up
+        let _hold = self.workers.hold([name.as_str()]);
+        let result = ["inventory", "player_abilities"]
+            .into_iter()
+            .try_for_each(|table| {
+                let query = Query::table(table).eq("player", &name);
+                self.execute_write(self.db.delete(query))
+            })
+            .and_then(|_| {
+                let query = Query::table("character").eq("name", &name);
+                self.execute_write(self.db.delete(query))
+            });
+        if result.is_ok() {
+            info!("Deleted character '{}'", name);
+            self.roster.retain(|x| *x != name);
+            self.broadcast_roster();
+        }
+        self.report_error(from, &format!("Deleting '{}'", name), result);
+    }
+
+    /// Recreates an exported character, saving all of it or none of it. Items
+    /// and abilities are matched up with the catalog by name. Only the GM can
+    /// add to the catalog, so players can only import what's already in it
+    fn import_character(
+        &mut self,
+        from: Endpoint,
+        character: Character,
+        items: Vec<Item>,
+        abilities: Vec<Ability>,
+    ) -> Result<(), String> {
+        let name = character.name.clone();
+        self.check_new_character(&name)?;
+
+        if self.is_gm_endpoint(from) {
+            self.add_to_catalog(&items, &abilities)?;
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Name {
+            name: String,
+        }
+
+        let item_ids: Vec<DBItemId> = self.select(Query::table("items").select("id,name"))?;
+        let known_abilities: Vec<Name> = self.select(Query::table("abilities").select("name"))?;
+
+        let missing_items = items
+            .iter()
+            .map(|x| x.name.as_str())
+            .filter(|x| !item_ids.iter().any(|id| id.name == *x));
+        let missing_abilities = abilities
+            .iter()
+            .map(|x| x.name.as_str())
+            .filter(|x| !known_abilities.iter().any(|known| known.name == *x));
+        let missing: Vec<_> = missing_items.chain(missing_abilities).collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Ask the GM to add these to the catalog first: {}",
+                missing.join(", ")
+            ));
+        }
+
+        let inventory: Vec<_> = items
+            .iter()
+            .filter_map(|item| {
+                let id = item_ids.iter().find(|x| x.name == item.name)?;
+                Some(DBInventoryRow {
+                    player: name.clone(),
+                    item_id: id.id,
+                    count: item.count,
+                    slot: item.slot,
+                })
+            })
+            .collect();
+        let player_abilities: Vec<_> = abilities
+            .iter()
+            .map(|ability| DBPlayerAbilityRow {
+                player: name.clone(),
+                ability_name: ability.name.clone(),
+                uses: ability.uses,
+            })
+            .collect();
+
+        let mut writes = vec![Write::insert(
+            "character",
+            serde_json::to_value(&character).map_err(|e| e.to_string())?,
+        )];
+        if !inventory.is_empty() {
+            writes.push(Write::insert(
+                "inventory",
+                serde_json::to_value(&inventory).map_err(|e| e.to_string())?,
+            ));
+        }
+        if !player_abilities.is_empty() {
+            writes.push(Write::insert(
+                "player_abilities",
+                serde_json::to_value(&player_abilities).map_err(|e| e.to_string())?,
+            ));
+        }
+        let _hold = self.workers.hold([name.as_str()]);
+        self.execute_write(self.db.transaction(writes))?;
+
+        info!(
+            "Imported '{}' with {} items and {} abilities",
+            name,
+            items.len(),
+            abilities.len()
+        );
+        self.character_created(character);
+        Ok(())
+    }
+
+    /// Upserts the definitions of the items and abilities by name. Catalog
+    /// entries don't belong to anyone, so these are kept even if the
+    /// character they came with fails to import
+    fn add_to_catalog(&self, items: &[Item], abilities: &[Ability]) -> Result<(), String> {
+        if !items.is_empty() {
+            let definitions: Vec<_> = items
+                .iter()
+                .map(|item| ItemDefinition {
+                    name: item.name.clone(),
+                    description: item.description.clone(),
+                    flavor_text: item.flavor_text.clone(),
+                    quest_item: item.quest_item,
+                    armor_class: item.armor_class,
+                    attack_bonus: item.attack_bonus,
+                    requires_attunement: item.requires_attunement,
+                    weight: item.weight,
+                })
+                .collect();
+            let json = serde_json::to_value(&definitions).map_err(|e| e.to_string())?;
+            self.execute_write(self.db.upsert("items", json, "name"))?;
+        }
+
+        if !abilities.is_empty() {
+            let definitions: Vec<_> = abilities
+                .iter()
+                .map(|ability| AbilityDefinition {
+                    name: ability.name.clone(),
+                    description: ability.description.clone(),
+                    notes: ability.notes.clone(),
+                    ability_type: ability.ability_type.clone(),
+                    flavor_text: ability.flavor_text.clone(),
+                    resource: ability.resource.clone(),
+                    max_count: ability.max_count,
+                    to_hit: ability.to_hit.clone(),
+                    damage: ability.damage.clone(),
+                    recharge: ability.recharge,
+                })
+                .collect();
+            let json = serde_json::to_value(&definitions).map_err(|e| e.to_string())?;
+            self.execute_write(self.db.upsert("abilities", json, "name"))?;
+        }
+        Ok(())
+    }
+
+    /// Bulk upserts catalog rows by name and reports back to the GM how it went
+    fn import_rows<T: serde::Serialize>(&self, from: Endpoint, table: &str, rows: &[T]) {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can import into '{}'", table);
+            return;
+        }
+
+        let result = serde_json::to_value(rows)
+            .map_err(|e| e.to_string())
+            .and_then(|json| self.execute_write(self.db.upsert(table, json, "name")))
+            .map(|_| rows.len());
+
+        match &result {
+            Ok(count) => info!("Imported {} rows into '{}'", count, table),
+            Err(e) => error!("Failed to import into '{}': {}", table, e),
+        }
+
+        let message = DndMessage::ImportResult(result);
+        let output_data = bincode::serialize(&message).unwrap();
+        self.handler.network().send(from, &output_data);
+    }
+
+    fn broadcast_roster(&self) {
+        let message = DndMessage::CharacterRoster(self.roster.clone());
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+
+    /// How many of the item the character has, if any
+    fn inventory_count(&self, character: &str, item_id: i64) -> Result<Option<u32>, String> {
+        #[derive(serde::Deserialize)]
+        struct Count {
+            count: u32,
+        }
+
+        let query = Query::table("inventory")
+            .select("count")
+            .eq("player", character)
+            .eq("item_id", item_id);
+        let existing: Vec<Count> = self.select(query)?;
+
+        Ok(existing.first().map(|x| x.count))
+    }
+
+    /// Adds `count` of the item to whatever the character already has, as a
+    /// write that can go in a transaction with where the items came from
+    fn inventory_write(&self, character: &str, item_id: i64, count: u32) -> Result<Write, String> {
+        let held = self.inventory_count(character, item_id)?;
+        let total = held.unwrap_or_default() + count;
+        Ok(set_inventory_count(character, item_id, held, total))
+    }
+
+    /// Refreshes a connected player's items after someone else changed them
+    fn send_item_list(&self, character: &str) {
+        let Some(info) = self.users.get(character) else {
+            return;
+        };
+
+        match self.get_item_list(&info.user_data) {
+            Ok(list) => {
+                let output_data = bincode::serialize(&DndMessage::ItemList(list)).unwrap();
+                self.handler.network().send(info.endpoint, &output_data);
+            }
+            Err(e) => error!("Failed to get item list for {}: {e:?}", character),
+        }
+    }
+
+    /// Only the characters whose XP was saved are announced, even if a later
+    /// one fails
+    fn award_xp(&self, names: Vec<String>, xp: u32) -> Result<(), String> {
+        let mut awarded = Vec::new();
+        let mut level_ups = Vec::new();
+
+        let result: Result<(), String> = names.iter().try_for_each(|name| {
+            let character = self.modify_character(name, |character| {
+                character.xp = character.xp.saturating_add(xp);
+                Ok(())
+            })?;
+            info!("{} now has {} XP", name, character.xp);
+
+            if character.can_level_up(&self.xp_table) {
+                level_ups.push(name.clone());
+            }
+            awarded.push(name.clone());
+
+            self.send_character_update(character);
+            Ok(())
+        });
+
+        if !awarded.is_empty() {
+            self.send_log_message_to_all(
+                User::server(),
+                LogMessage::Chat(format!("{} gained {} XP", awarded.join(", "), xp)),
+            );
+        }
+        for name in level_ups {
+            self.send_log_message_to_all(
+                User::server(),
+                LogMessage::Chat(format!("{} can level up!", name)),
+            );
+        }
+
+        result
+    }
+
+    fn grant_inspiration(&self, names: Vec<String>, points: u32) -> Result<(), String> {
+        for name in names {
+            let character = self.modify_character(&name, |character| {
+                character.inspiration = character.inspiration.saturating_add(points);
+                Ok(())
+            })?;
+            info!("{} now has {} inspiration", name, character.inspiration);
+
+            self.send_log_message_to_all(
+                User::server(),
+                LogMessage::InspirationGranted(name, points),
+            );
+            self.send_character_update(character);
+        }
+
+        Ok(())
+    }
+
+    fn spend_inspiration(&self, user: User) -> Result<(), String> {
+        let character = self.modify_character(&user.name, |character| {
+            character.inspiration = character
+                .inspiration
+                .checked_sub(1)
+                .ok_or_else(|| format!("{} has no inspiration to spend", character.name))?;
+            Ok(())
+        })?;
+        info!(
+            "{} has {} inspiration left",
+            user.name, character.inspiration
+        );
+
+        self.send_log_message_to_all(user, LogMessage::InspirationSpent(character.name.clone()));
+        self.send_character_update(character);
+        Ok(())
+    }
+
+    /// Damage and healing are applied to whatever the HP is by the time they
+    /// land, so hits from the GM and the player don't overwrite each other
+    fn adjust_hp(&self, from: User, name: String, amount: i32) -> Result<(), String> {
+        let before = Cell::new(LifeState::Conscious);
+        let character = self.modify_character(&name, |character| {
+            before.set(character.life);
+            character.adjust_hp(amount);
+            Ok(())
+        })?;
+        info!("{} is now at {} HP", name, character.curr_hp);
+
+        let life_changed = before.get() != character.life;
+        self.send_log_message_to_all(
+            from.clone(),
+            LogMessage::HpChanged(name.clone(), amount, character.curr_hp),
+        );
+        if life_changed {
+            self.send_log_message_to_all(from, LogMessage::LifeChanged(name, character.life));
+        }
+        self.send_character_update(character);
+        Ok(())
+    }
+
+    fn roll_death_save(&self, from: User, name: String) -> Result<(), String> {
+        let roll = rand::rng().random_range(1..=20);
+        let character = self.modify_character(&name, |character| {
+            if !matches!(character.life, LifeState::Dying { .. }) {
+                return Err(format!("{name} isn't making death saves"));
+            }
+            character.death_save(roll);
+            Ok(())
+        })?;
+        info!("{name} rolled {roll} on a death save");
+
+        self.send_log_message_to_all(from, LogMessage::DeathSave(name, roll, character.life));
+        self.send_character_update(character);
+        Ok(())
+    }
+
+    fn set_life_state(&self, from: User, name: String, life: LifeState) -> Result<(), String> {
+        let character = self.modify_character(&name, |character| {
+            character.set_life(life);
+            Ok(())
+        })?;
+        info!("{} is now {}", name, character.life);
+
+        self.send_log_message_to_all(from, LogMessage::LifeChanged(name, character.life));
+        self.send_character_update(character);
+        Ok(())
+    }
+
+    /// Everything an area of effect hit goes into a single log message, the
+    /// tokens linked to a character have the damage taken off their HP first
+    fn apply_area_damage(
+        &self,
+        from: User,
+        source: String,
+        roll: DieRoll,
+        mut hits: Vec<AreaHit>,
+    ) -> Result<(), String> {
+        let mut life_changes = Vec::new();
+        for hit in hits.iter_mut().filter(|x| self.roster.contains(&x.name)) {
+            let damage = hit.damage;
+            let before = Cell::new(LifeState::Conscious);
+            let character = self.modify_character(&hit.name, |character| {
+                before.set(character.life);
+                character.adjust_hp(-damage);
+                Ok(())
+            })?;
+            info!("{} is now at {} HP", hit.name, character.curr_hp);
+
+            if before.get() != character.life {
+                life_changes.push(LogMessage::LifeChanged(hit.name.clone(), character.life));
+            }
+            hit.hp = Some(character.curr_hp);
+            self.send_character_update(character);
+        }
+
+        // Players only hear that something was hit if they can't see the token
+        let timestamp = Some(Utc::now());
+        for (name, user) in self.users.iter() {
+            let hits = hits
+                .iter()
+                .cloned()
+                .map(|mut hit| {
+                    let hidden = self
+                        .board_data
+                        .players
+                        .get(&hit.piece)
+                        .is_some_and(|piece| !self.can_see_piece(name, piece));
+                    if hidden {
+                        hit.name = "Something".to_owned();
+                        hit.hp = None;
+                    }
+                    hit
+                })
+                .collect();
+            let msg = LogMessage::AreaDamage(source.clone(), roll.clone(), hits);
+            self.remember_log(&from, &msg);
+            let message = DndMessage::Log(from.clone(), msg, timestamp);
+            self.handler
+                .network()
+                .send(user.endpoint, &bincode::serialize(&message).unwrap());
+        }
+        for msg in life_changes {
+            self.send_log_message_to_all(from.clone(), msg);
+        }
+        Ok(())
+    }
+
+    /// Applies `change` to the latest copy of the character, retrying if the
+    /// player saved something in between. Only for changes that are always
+    /// safe to apply on top of someone else's, like adding XP
+    fn modify_character(
+        &self,
+        name: &str,
+        change: impl Fn(&mut Character) -> Result<(), String>,
+    ) -> Result<Character, String> {
+        let user = User {
+            name: name.to_owned(),
+        };
+        let _hold = self.workers.hold([name]);
+
+        for _ in 0..MAX_CHARACTER_RETRIES {
+            let mut character = self.get_character_stats(&user).map_err(|e| e.to_string())?;
+            let version = character.version;
+            change(&mut character)?;
+            character.version += 1;
+
+            if self.write_character_version(&character, version)? {
+                return Ok(character);
+            }
+        }
+
+        Err(format!("{name} kept changing, try again in a moment"))
+    }
+
+    /// Sends the owner their character after the server changed it
+    fn send_character_update(&self, character: Character) {
+        if let Some(info) = self.users.get(&character.name) {
+            let output_data = bincode::serialize(&DndMessage::CharacterData(character)).unwrap();
+            self.handler.network().send(info.endpoint, &output_data);
+        }
+    }
+
+    /// Runs a write against the DB, failed requests and error statuses both become an `Err`
+    fn execute_write<T>(&self, query: StorageFuture<'_, T>) -> Result<(), String> {
+        self.execute_query(query).map(|_| ())
+    }
+
+    /// Like [`Self::execute_write`] but hands back what the DB sent
+    fn execute_query<T>(&self, query: StorageFuture<'_, T>) -> Result<T, String> {
+        futures::executor::block_on(query)
+    }
+
+    fn select<T: DeserializeOwned>(&self, query: Query) -> Result<Vec<T>, String> {
+        parse_rows(self.execute_query(self.db.select(query))?)
+    }
+
+    // The listener's own uses of the character worker, these wait on the DB
+    // like everything else here does
+
+    fn get_character_stats(&self, user: &User) -> DbResult<Character> {
+        futures::executor::block_on(self.workers.characters.get_character_stats(user))
+    }
+
+    fn get_item_list(&self, user: &User) -> DbResult<Vec<Item>> {
+        futures::executor::block_on(self.workers.characters.get_item_list(user))
+    }
+
+    /// Only refuses when the ruleset enforces its attunement limit
+    fn check_attunement(&self, user: &User) -> Result<(), String> {
+        if !self.ruleset.enforce_attunement_limit {
+            return Ok(());
+        }
+
+        let items = self.get_item_list(user).map_err(|e| e.to_string())?;
+        let attuned = items.iter().filter(|x| x.attuned).count();
+        if self.ruleset.can_attune(attuned) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} is already attuned to {} items",
+                user.name, self.ruleset.attunement_limit
+            ))
+        }
+    }
+
+    /// Checks the limit and saves in one go, with the character's queue held
+    /// so two quick attunes can't both see room for one more item
+    fn set_item_attunement(&self, user: &User, item_id: i64, attuned: bool) -> Result<(), String> {
+        let _hold = self.workers.hold([user.name.as_str()]);
+        if attuned {
+            self.check_attunement(user)?;
+        }
+
+        futures::executor::block_on(self.workers.characters.update_item_attunement(
+            user.clone(),
+            item_id,
+            attuned,
+        ))
+    }
+
+    fn write_character_version(
+        &self,
+        character: &Character,
+        expected_version: u32,
+    ) -> Result<bool, String> {
+        futures::executor::block_on(
+            self.workers
+                .characters
+                .write_character_version(character, expected_version),
+        )
+    }
+
+    fn handle_log_message(&mut self, from: Endpoint, user: User, msg: LogMessage) {
+        let as_npc = matches!(msg, LogMessage::NpcChat(..) | LogMessage::Emote(Some(_), _));
+        if as_npc && !self.is_gm_endpoint(from) {
+            warn!("'{}' can't speak as an NPC, they aren't the GM", user.name);
+            return;
+        }
+
+        let rolls = counted_rolls(&msg);
+        if !rolls.is_empty() {
+            let stats = self.session_rolls.entry(user.name.clone()).or_default();
+            rolls.into_iter().for_each(|x| stats.record(x));
+            if let Err(e) = self.save_session_rolls(&user.name) {
+                error!("Failed to save {}'s roll stats: {e}", user.name);
+            }
+        }
+
+        if msg.is_private() {
+            self.send_to_gm(from, DndMessage::Log(user, msg, Some(Utc::now())));
+        } else {
+            if let Some(text) = loud_roll(&user, &msg) {
+                self.notifier.notify(text);
+            }
+            self.broadcast_log_message(from, user, msg);
+        }
+    }
+
+    fn is_gm_endpoint(&self, endpoint: Endpoint) -> bool {
+        self.gm
+            .as_ref()
+            .and_then(|name| self.users.get(name))
+            .is_some_and(|gm| gm.endpoint == endpoint)
+    }
+
+    fn username(&self, endpoint: Endpoint) -> Option<&String> {
+        self.users
+            .iter()
+            .find(|(_, info)| info.endpoint == endpoint)
+            .map(|(name, _)| name)
+    }
+
+    fn notify_throttled(&self, endpoint: Endpoint, category: MessageCategory) {
+        let name = self
+            .username(endpoint)
+            .map_or_else(|| endpoint.to_string(), |name| name.clone());
+
+        warn!("Throttling {} messages from '{}'", category, name);
+
+        let msg = LogMessage::Chat(format!(
+            "'{}' is sending too many {} messages, some are being dropped",
+            name, category
+        ));
+        self.send_to_gm(
+            endpoint,
+            DndMessage::Log(User::server(), msg, Some(Utc::now())),
+        );
+    }
+
+    fn send_error(&self, endpoint: Endpoint, request_context: &str, message: impl ToString) {
+        self.workers
+            .characters
+            .send_error(endpoint, request_context, message);
+    }
+
+    /// Logs a failed request and lets the client that made it know
+    fn report_error(&self, endpoint: Endpoint, request_context: &str, result: Result<(), String>) {
+        self.workers
+            .characters
+            .report_error(endpoint, request_context, result);
+    }
+
+    fn send_to_gm(&self, ignore_enpoint: Endpoint, message: DndMessage) {
+        let Some(gm) = self.gm.as_ref().and_then(|name| self.users.get(name)) else {
+            warn!("No GM connected to recieve {message:?}");
+            return;
+        };
+
+        if gm.endpoint != ignore_enpoint {
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(gm.endpoint, &output_data);
+        }
+    }
+
+    fn broadcast_message(&self, ignore_enpoint: Endpoint, message: DndMessage) {
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            if user.endpoint != ignore_enpoint {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+        }
+    }
+
+    fn broadcast_log_message(&self, ignore_enpoint: Endpoint, username: User, msg: LogMessage) {
+        info!("Broadcasting log message!");
+        self.remember_log(&username, &msg);
+        let message = DndMessage::Log(username, msg, Some(Utc::now()));
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            if user.endpoint != ignore_enpoint {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+        }
+    }
+
+    fn can_control_piece(&self, endpoint: Endpoint, piece: &DndPlayerPiece) -> bool {
+        self.is_gm_endpoint(endpoint)
+            || self
+                .username(endpoint)
+                .is_some_and(|name| piece.is_owned_by(name))
+    }
+
+    /// The client applied the change locally already, so send back what the piece really looks like
+    fn reject_piece_change(
+        &self,
+        endpoint: Endpoint,
+        uuid: uuid::Uuid,
+        action: &str,
+        reason: &str,
+    ) {
+        let name = self
+            .username(endpoint)
+            .map_or_else(|| endpoint.to_string(), |name| name.clone());
+        error!("Rejected {action} of piece {uuid} from '{name}': {reason}");
+
+        self.send_error(endpoint, action, reason);
+
+        let correction = match self.board_data.players.get(&uuid) {
+            Some(player) if self.can_see_piece(&name, player) => {
+                BoardMessage::UpdatePlayerPiece(uuid, player.clone())
+            }
+            _ => BoardMessage::DeletePlayerPiece(uuid),
+        };
+        let output_data = bincode::serialize(&DndMessage::BoardMessage(correction)).unwrap();
+        self.handler.network().send(endpoint, &output_data);
+    }
+
+    fn handle_board_message(&mut self, from: Endpoint, mut msg: BoardMessage) {
+        // Holds aren't part of the board, they're only kept while people are connected
+        match msg {
+            BoardMessage::HoldPiece(uuid) => {
+                self.hold_piece(from, uuid);
+                return;
+            }
+            BoardMessage::ReleasePiece(uuid) => {
+                self.release_piece(from, uuid);
+                return;
+            }
+            BoardMessage::SetHolder(..) => return,
+            _ => {}
+        }
+
+        // The GM can always step in, ie. when someone walked away mid-edit
+        let name = self.username(from).cloned().unwrap_or_default();
+        if let Some(uuid) = msg.piece_id().filter(|_| !self.is_gm_endpoint(from)) {
+            if let Some(holder) = self.holder(&uuid).filter(|x| **x != name) {
+                let reason = format!("{holder} is editing this piece");
+                self.reject_piece_change(from, uuid, "Edit piece", &reason);
+                return;
+            }
+        }
+
+        let before = msg
+            .piece_id()
+            .and_then(|uuid| self.board_data.players.get(&uuid).cloned());
+        let annotation_before = msg
+            .annotation_id()
+            .and_then(|uuid| self.board_data.annotations.get(&uuid).cloned());
+
+        if let BoardMessage::AddAnnotation(_, annotation) = &mut msg {
+            annotation.author = name.clone();
+        }
+
+        match &msg {
+            BoardMessage::AddPlayerPiece(uuid, player) => {
+                // Adding over an existing uuid replaces that piece
+                let existing = self.board_data.players.get(uuid);
+                let replaces_other =
+                    existing.is_some_and(|existing| !self.can_control_piece(from, existing));
+                if replaces_other || !self.can_control_piece(from, player) {
+                    self.reject_piece_change(from, *uuid, "Add piece", NOT_OWNER);
+                    return;
+                }
+
+                if !self.is_gm_endpoint(from) && existing.and_then(|x| x.vision) != player.vision {
+                    self.reject_piece_change(from, *uuid, "Add piece", GM_ONLY_VISION);
+                    return;
+                }
+            }
+            BoardMessage::UpdatePlayerPiece(uuid, new_player) => {
+                let Some(player) = self.board_data.players.get(uuid) else {
+                    error!("Player {uuid} could not be found on the server!");
+                    return;
+                };
+
+                // Owners can edit their pieces but only the GM can hand them out
+                let allowed = self.is_gm_endpoint(from)
+                    || (self.can_control_piece(from, player) && player.owners == new_player.owners);
+                if !allowed {
+                    self.reject_piece_change(from, *uuid, "Update piece", NOT_OWNER);
+                    return;
+                }
+
+                // Fog is only drawn by the clients, so players could otherwise see the whole map
+                if !self.is_gm_endpoint(from) && player.vision != new_player.vision {
+                    self.reject_piece_change(from, *uuid, "Update piece", GM_ONLY_VISION);
+                    return;
+                }
+            }
+            BoardMessage::UpdatePlayerLocation(uuid, new_location) => {
+                let Some(player) = self.board_data.players.get(uuid) else {
+                    error!("Player {uuid} could not be found on the server!");
+                    return;
+                };
+
+                if !self.can_control_piece(from, player) {
+                    self.reject_piece_change(from, *uuid, "Move piece", NOT_OWNER);
+                    return;
+                }
+
+                let half_size = player.size / 2.0;
+                let blocked = self
+                    .board_data
+                    .boards
+                    .get(&player.board)
+                    .is_some_and(|board| {
+                        board
+                            .blocks_movement(player.position + half_size, *new_location + half_size)
+                    });
+                if blocked && !self.is_gm_endpoint(from) {
+                    self.reject_piece_change(
+                        from,
+                        *uuid,
+                        "Move piece",
+                        "There's a wall in the way",
+                    );
+                    return;
+                }
+            }
+            BoardMessage::DeletePlayerPiece(uuid) => {
+                let allowed = self
+                    .board_data
+                    .players
+                    .get(uuid)
+                    .is_none_or(|player| self.can_control_piece(from, player));
+                if !allowed {
+                    self.reject_piece_change(from, *uuid, "Delete piece", NOT_OWNER);
+                    return;
+                }
+            }
+            BoardMessage::AddAnnotation(uuid, _) => {
+                // Drawing over an existing uuid replaces that annotation
+                let allowed = annotation_before
+                    .as_ref()
+                    .is_none_or(|x| self.can_edit_annotation(from, x));
+                if !allowed {
+                    self.reject_annotation_change(from, *uuid, "Draw annotation");
+                    return;
+                }
+            }
+            BoardMessage::DeleteAnnotation(uuid) => {
+                let allowed = annotation_before
+                    .as_ref()
+                    .is_none_or(|x| self.can_edit_annotation(from, x));
+                if !allowed {
+                    self.reject_annotation_change(from, *uuid, "Erase annotation");
+                    return;
+                }
+            }
+            BoardMessage::ClearAnnotations(board, layer) => {
+                if !self.is_gm_endpoint(from) {
+                    self.clear_own_annotations(from, *board, *layer);
+                    return;
+                }
+            }
+            BoardMessage::SetAmbience(_) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can change the board ambience");
+                    return;
+                }
+            }
+            BoardMessage::SetCampaignDate(_) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can change the campaign date");
+                    return;
+                }
+            }
+            BoardMessage::SetGrid(_) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can change the grid");
+                    return;
+                }
+            }
+            BoardMessage::AdvanceTime(_) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can advance the campaign clock");
+                    return;
+                }
+            }
+            BoardMessage::GroupPieces(..) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can group pieces");
+                    return;
+                }
+            }
+            BoardMessage::Ungroup(_) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can ungroup pieces");
+                    return;
+                }
+            }
+            BoardMessage::CreateBoard(..)
+            | BoardMessage::DeleteBoard(_)
+            | BoardMessage::SetActiveBoard(_)
+            | BoardMessage::AddPortal(..)
+            | BoardMessage::DeletePortal(..)
+            | BoardMessage::AddWall(..)
+            | BoardMessage::DeleteWall(..)
+            | BoardMessage::SetDoorOpen(..) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can manage boards");
+                    return;
+                }
+            }
+            // Handled before the holder check
+            BoardMessage::HoldPiece(_)
+            | BoardMessage::ReleasePiece(_)
+            | BoardMessage::SetHolder(..) => return,
+        }
+
+        if !self.board_data.apply(msg.clone()) {
+            return;
+        }
+        self.record_board_change(from, &msg);
+
+        // Changes from the holder keep the hold alive, deleting the piece ends it
+        if let Some(uuid) = msg.piece_id() {
+            if matches!(msg, BoardMessage::DeletePlayerPiece(_)) {
+                self.holds.remove(&uuid);
+            } else if let Some(hold) = self.holds.get_mut(&uuid).filter(|x| x.user == name) {
+                hold.renewed = Instant::now();
+            }
+        }
+
+        match (msg.piece_id(), msg.annotation_id()) {
+            (Some(uuid), _) => self.broadcast_piece_message(from, uuid, msg, before),
+            (_, Some(uuid)) => {
+                self.broadcast_annotation_message(from, uuid, msg, annotation_before)
+            }
+            _ => self.broadcast_board_message(from, msg),
+        }
+    }
+
+    /// Who's holding the piece, if their hold hasn't run out
+    fn holder(&self, uuid: &uuid::Uuid) -> Option<&String> {
+        self.holds
+            .get(uuid)
+            .filter(|x| !x.expired())
+            .map(|x| &x.user)
+    }
+
+    fn hold_piece(&mut self, from: Endpoint, uuid: uuid::Uuid) {
+        let Some(name) = self.username(from).cloned() else {
+            return;
+        };
+        let Some(piece) = self.board_data.players.get(&uuid) else {
+            return;
+        };
+        if !self.can_control_piece(from, piece) {
+            warn!("'{name}' can't hold a piece they don't control");
+            return;
+        }
+
+        // Let them know who got there first, unless it's the GM taking over
+        let holder = self.holder(&uuid).filter(|x| **x != name);
+        if let Some(holder) = holder.filter(|_| !self.is_gm_endpoint(from)) {
+            let msg = BoardMessage::SetHolder(uuid, Some(holder.clone()));
+            let output_data = bincode::serialize(&DndMessage::BoardMessage(msg)).unwrap();
+            self.handler.network().send(from, &output_data);
+            return;
+        }
+
+        let hold = PieceHold {
+            user: name.clone(),
+            renewed: Instant::now(),
+        };
+        if self.holds.insert(uuid, hold).is_none_or(|x| x.user != name) {
+            self.send_holder(uuid, Some(name));
+        }
+    }
+
+    /// The GM can break anyone's hold
+    fn release_piece(&mut self, from: Endpoint, uuid: uuid::Uuid) {
+        let name = self.username(from);
+        let allowed = self
+            .holds
+            .get(&uuid)
+            .is_some_and(|x| Some(&x.user) == name || self.is_gm_endpoint(from));
+        if allowed {
+            self.holds.remove(&uuid);
+            self.send_holder(uuid, None);
+        }
+    }
+
+    /// Tells everyone who can see the piece who's holding it
+    fn send_holder(&self, uuid: uuid::Uuid, holder: Option<String>) {
+        let Some(piece) = self.board_data.players.get(&uuid) else {
+            return;
+        };
+
+        let msg = DndMessage::BoardMessage(BoardMessage::SetHolder(uuid, holder));
+        let output_data = bincode::serialize(&msg).unwrap();
+        for (name, user) in self.users.iter() {
+            if self.can_see_piece(name, piece) {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+        }
+    }
+
+    fn can_see_annotation(&self, name: &str, annotation: &Annotation) -> bool {
+        self.gm.as_deref() == Some(name) || annotation.visible_to(name)
+    }
+
+    /// Annotations from before they had authors can only be erased by the GM
+    fn can_edit_annotation(&self, from: Endpoint, annotation: &Annotation) -> bool {
+        self.is_gm_endpoint(from)
+            || (!annotation.author.is_empty() && self.username(from) == Some(&annotation.author))
+    }
+
+    /// The sender already changed their own board, so it's put back the way
+    /// the server has it
+    fn reject_annotation_change(&self, endpoint: Endpoint, uuid: uuid::Uuid, action: &str) {
+        let name = self.username(endpoint).cloned().unwrap_or_default();
+        error!("Rejected {action} of annotation {uuid} from '{name}': {NOT_AUTHOR}");
+
+        self.send_error(endpoint, action, NOT_AUTHOR);
+
+        let correction = match self.board_data.annotations.get(&uuid) {
+            Some(annotation) if self.can_see_annotation(&name, annotation) => {
+                BoardMessage::AddAnnotation(uuid, annotation.clone())
+            }
+            _ => BoardMessage::DeleteAnnotation(uuid),
+        };
+        let output_data = bincode::serialize(&DndMessage::BoardMessage(correction)).unwrap();
+        self.handler.network().send(endpoint, &output_data);
+    }
+
+    /// Players clearing a layer only erase what they drew. Their client
+    /// already cleared all of it, so the rest is sent back
+    fn clear_own_annotations(&mut self, from: Endpoint, board: uuid::Uuid, layer: SortingLayer) {
+        let name = self.username(from).cloned().unwrap_or_default();
+        let on_layer: Vec<_> = self
+            .board_data
+            .annotations
+            .iter()
+            .filter(|(_, x)| x.board == board && x.layer == layer)
+            .map(|(uuid, x)| (*uuid, x.clone()))
+            .collect();
+
+        for (uuid, annotation) in on_layer {
+            if self.can_edit_annotation(from, &annotation) {
+                let msg = BoardMessage::DeleteAnnotation(uuid);
+                self.board_data.apply(msg.clone());
+                self.record_board_change(from, &msg);
+                self.broadcast_annotation_message(from, uuid, msg, Some(annotation));
+            } else if self.can_see_annotation(&name, &annotation) {
+                let msg = BoardMessage::AddAnnotation(uuid, annotation);
+                let output_data = bincode::serialize(&DndMessage::BoardMessage(msg)).unwrap();
+                self.handler.network().send(from, &output_data);
+            }
+        }
+    }
+
+    /// Like [`Self::broadcast_piece_message`], players never receive
+    /// annotations hidden from them
+    fn broadcast_annotation_message(
+        &self,
+        ignore_enpoint: Endpoint,
+        uuid: uuid::Uuid,
+        msg: BoardMessage,
+        before: Option<Annotation>,
+    ) {
+        let after = self.board_data.annotations.get(&uuid);
+
+        for (name, user) in self.users.iter() {
+            if user.endpoint == ignore_enpoint {
+                continue;
+            }
+
+            let saw = before
+                .as_ref()
+                .is_some_and(|x| self.can_see_annotation(name, x));
+            let sees = after.is_some_and(|x| self.can_see_annotation(name, x));
+
+            let msg = match (saw, sees) {
+                (_, true) => msg.clone(),
+                (true, false) => BoardMessage::DeleteAnnotation(uuid),
+                (false, false) => continue,
+            };
+
+            let output_data = bincode::serialize(&DndMessage::BoardMessage(msg)).unwrap();
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+
+    fn can_see_piece(&self, name: &str, piece: &DndPlayerPiece) -> bool {
+        self.gm.as_deref() == Some(name) || piece.visible_to(name)
+    }
+
+    /// Players never receive pieces hidden from them, so a change in visibility
+    /// turns into an add or delete depending on what each player could see before
+    fn broadcast_piece_message(
+        &self,
+        ignore_enpoint: Endpoint,
+        uuid: uuid::Uuid,
+        msg: BoardMessage,
+        before: Option<DndPlayerPiece>,
+    ) {
+        let after = self.board_data.players.get(&uuid);
+
+        for (name, user) in self.users.iter() {
+            if user.endpoint == ignore_enpoint {
+                continue;
+            }
+
+            let saw = before.as_ref().is_some_and(|x| self.can_see_piece(name, x));
+            let sees = after.is_some_and(|x| self.can_see_piece(name, x));
+
+            let msg = match (saw, sees, after) {
+                (true, true, _) => msg.clone(),
+                (false, true, Some(piece)) => BoardMessage::AddPlayerPiece(uuid, piece.clone()),
+                (true, false, _) => BoardMessage::DeletePlayerPiece(uuid),
+                _ => continue,
+            };
+
+            let output_data = bincode::serialize(&DndMessage::BoardMessage(msg)).unwrap();
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+
+    fn record_board_change(&mut self, from: Endpoint, msg: &BoardMessage) {
+        let user = self.username(from).cloned().unwrap_or_default();
+        record_journal_entry(&mut self.journal, user, msg.clone());
+        self.squash_journal();
+    }
+
+    /// Squashes the journal once it's grown too long, storage gets rewritten
+    /// with what's left on the next save
+    fn squash_journal(&mut self) {
+        let squashed = compact_journal(&mut self.journal, |messages| {
+            let mut data = BoardData::empty();
+            for msg in messages {
+                data.apply(msg.clone());
+            }
+            data.messages()
+        });
+        if !squashed {
+            return;
+        }
+
+        info!(
+            "Squashed the board journal to {} entries",
+            self.journal.len()
+        );
+        self.journal_saved = None;
+
+        // Everything the GM has been sent has moved
+        if let Some(gm) = self.gm_endpoint() {
+            self.send_journal(gm, 0);
+        }
+    }
+
+    /// Writes out the entries added since the last save, or the whole journal
+    /// if it's been squashed since
+    fn save_journal(&mut self) {
+        let result = match self.journal_saved {
+            Some(saved) if saved >= self.journal.len() => return,
+            Some(saved) => serde_json::to_value(&self.journal[saved..])
+                .map_err(|e| e.to_string())
+                .and_then(|rows| self.execute_write(self.db.insert("board_journal", rows))),
+            None => serde_json::to_value(&self.journal)
+                .map_err(|e| e.to_string())
+                .and_then(|rows| {
+                    let writes = vec![
+                        Write::delete(Query::table("board_journal").not_null("id")),
+                        Write::insert("board_journal", rows),
+                    ];
+                    self.execute_write(self.db.transaction(writes))
+                }),
+        };
+
+        match result {
+            Ok(()) => self.journal_saved = Some(self.journal.len()),
+            Err(e) => error!("Failed to save the board journal: {e}"),
+        }
+    }
+
+    fn handle_journal_message(&mut self, from: Endpoint, msg: JournalMessage) {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can look through the board history");
+            return;
+        }
+
+        match msg {
+            JournalMessage::Request(start) => self.send_journal(from, start),
+            JournalMessage::Branch(point) => {
+                let point = point.min(self.journal.len());
+
+                // Nothing is lost, the entries after the point stay in the
+                // journal and branching from before this one undoes it
+                self.board_data = BoardData::replayed(&self.journal, point);
+                self.holds.clear();
+
+                let user = self.username(from).cloned().unwrap_or_default();
+                self.journal.push(JournalEntry {
+                    at: Utc::now(),
+                    user,
+                    change: JournalChange::Branch(point),
+                });
+                let branch = self.journal.len() - 1;
+                self.squash_journal();
+                info!("Board rewound to journal entry {point}");
+
+                self.resend_board(point);
+                self.send_log_message_to_all(
+                    User::server(),
+                    LogMessage::Chat(format!("The GM rewound the board to change {point}")),
+                );
+                self.send_journal(from, branch);
+            }
+            JournalMessage::Entries(..) | JournalMessage::Branched(_) => {}
+        }
+    }
+
+    /// Everyone clears their board, then gets it sent again like when they joined
+    fn resend_board(&self, point: usize) {
+        self.send_message_to_all(DndMessage::JournalMessage(JournalMessage::Branched(point)));
+        for (name, user) in self.users.iter() {
+            self.send_initial_board_data(user.endpoint, name);
+        }
+    }
+
+    /// Swaps in a board from a snapshot. It's journaled as going back to an
+    /// empty board and setting this one up, so the history before it is kept
+    fn restore_board(&mut self, board: BoardData) {
+        let user = self.gm.clone().unwrap_or_default();
+        self.journal.push(JournalEntry {
+            at: Utc::now(),
+            user: user.clone(),
+            change: JournalChange::Branch(0),
+        });
+        for msg in board.messages() {
+            record_journal_entry(&mut self.journal, user.clone(), msg);
+        }
+        self.board_data = board;
+        self.holds.clear();
+        self.squash_journal();
+
+        self.resend_board(0);
+        if let Some(gm) = self.gm_endpoint() {
+            self.send_journal(gm, 0);
+        }
+    }
+
+    /// A page of the journal from `start` on
+    fn send_journal(&self, endpoint: Endpoint, start: usize) {
+        let start = start.min(self.journal.len());
+        let page = self.journal[start..]
+            .iter()
+            .take(JOURNAL_PAGE)
+            .cloned()
+            .collect();
+        let message =
+            DndMessage::JournalMessage(JournalMessage::Entries(start, page, self.journal.len()));
+        let output_data = bincode::serialize(&message).unwrap();
+        self.handler.network().send(endpoint, &output_data);
+    }
+
+    fn send_initial_board_data(&self, endpoint: Endpoint, name: &str) {
+        let mut messages = Vec::new();
+        for (uuid, board) in self.board_data.boards.iter() {
+            messages.push(BoardMessage::CreateBoard(*uuid, board.name.clone()));
+            messages.extend(
+                board
+                    .portals
+                    .iter()
+                    .map(|(id, portal)| BoardMessage::AddPortal(*uuid, *id, *portal)),
+            );
+            messages.extend(
+                board
+                    .walls
+                    .iter()
+                    .map(|(id, wall)| BoardMessage::AddWall(*uuid, *id, *wall)),
+            );
+        }
+        messages.push(BoardMessage::SetActiveBoard(self.board_data.active_board));
+
+        for msg in messages {
+            let output_data = bincode::serialize(&DndMessage::BoardMessage(msg)).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+
+        for (uuid, player) in self.board_data.players.iter() {
+            if !self.can_see_piece(name, player) {
+                continue;
+            }
+
+            let message =
+                DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(*uuid, player.clone()));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+
+        for (uuid, hold) in self.holds.iter().filter(|(_, x)| !x.expired()) {
+            let visible = self
+                .board_data
+                .players
+                .get(uuid)
+                .is_some_and(|x| self.can_see_piece(name, x));
+            if !visible {
+                continue;
+            }
+
+            let message =
+                DndMessage::BoardMessage(BoardMessage::SetHolder(*uuid, Some(hold.user.clone())));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+
+        for (uuid, pieces) in self.board_data.groups.0.iter() {
+            let message =
+                DndMessage::BoardMessage(BoardMessage::GroupPieces(*uuid, pieces.clone()));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+
+        for (uuid, annotation) in self.board_data.annotations.iter() {
+            if !self.can_see_annotation(name, annotation) {
+                continue;
+            }
+            let message =
+                DndMessage::BoardMessage(BoardMessage::AddAnnotation(*uuid, annotation.clone()));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+
+        let message = DndMessage::BoardMessage(BoardMessage::SetGrid(self.board_data.grid));
+        let output_data = bincode::serialize(&message).unwrap();
+        self.handler.network().send(endpoint, &output_data);
+
+        let message = DndMessage::BoardMessage(BoardMessage::SetCampaignDate(self.board_data.date));
+        let output_data = bincode::serialize(&message).unwrap();
+        self.handler.network().send(endpoint, &output_data);
+
+        if !self.board_data.ambience.is_clear() {
+            let message =
+                DndMessage::BoardMessage(BoardMessage::SetAmbience(self.board_data.ambience));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+    }
+
+    fn broadcast_board_message(&self, ignore_enpoint: Endpoint, msg: BoardMessage) {
+        let message = DndMessage::BoardMessage(msg);
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            if user.endpoint != ignore_enpoint {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+        }
+    }
+
+    fn handle_effect_message(&mut self, from: Endpoint, msg: EffectMessage) {
+        match msg.clone() {
+            EffectMessage::ApplyEffect(uuid, effect) => {
+                let allowed = self.can_edit_target(from, &effect.target)
+                    && self
+                        .effect_data
+                        .effects
+                        .get(&uuid)
+                        .is_none_or(|x| self.can_edit_target(from, &x.target));
+                if !allowed {
+                    warn!("Only the GM can put effects on someone else");
+                    return;
+                }
+                self.effect_data.effects.insert(uuid, effect);
+            }
+            EffectMessage::RemoveEffect(uuid) => {
+                let allowed = self
+                    .effect_data
+                    .effects
+                    .get(&uuid)
+                    .is_none_or(|x| self.can_edit_target(from, &x.target));
+                if !allowed {
+                    warn!("Only the GM can remove effects from someone else");
+                    return;
+                }
+                self.effect_data.effects.remove(&uuid);
+            }
+            EffectMessage::AdvanceRound => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can advance the round");
+                    return;
+                }
+                self.effect_data.round += 1;
+
+                let mut expired = Vec::new();
+                self.effect_data.effects.retain(|_, effect| {
+                    let done = effect.tick();
+                    if done {
+                        expired.push(effect.clone());
+                    }
+                    !done
+                });
+
+                for effect in expired {
+                    let target = match effect.target {
+                        EffectTarget::Character(name) => name,
+                        EffectTarget::Piece(_) => String::from("a token"),
+                    };
+
+                    info!("{} expired on {}", effect.name, target);
+                    self.send_log_message_to_all(
+                        User::server(),
+                        LogMessage::EffectExpired(effect.name, target),
+                    );
+                }
+
+                // Recharge rolls wait for the creature's turn
+                self.recharge_abilities(from, |cooldown| {
+                    matches!(cooldown.recharge, Recharge::Rounds(_))
+                });
+            }
+            EffectMessage::SetRound(round) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can set the round");
+                    return;
+                }
+                self.effect_data.round = round;
+            }
+            EffectMessage::SetCooldown(uuid, cooldown) => {
+                match self.checked_cooldown(from, uuid, cooldown) {
+                    Ok(cooldown) => {
+                        self.effect_data.cooldowns.insert(uuid, cooldown.clone());
+                        self.send_message_to_all(DndMessage::EffectMessage(
+                            EffectMessage::SetCooldown(uuid, cooldown),
+                        ));
+                    }
+                    Err(e) => self.report_error(from, "Starting cooldown", Err(e)),
+                }
+                return;
+            }
+            EffectMessage::RemoveCooldown(uuid) => {
+                let allowed = self
+                    .effect_data
+                    .cooldowns
+                    .get(&uuid)
+                    .is_none_or(|x| self.can_edit_target(from, &x.target));
+                if !allowed {
+                    warn!("Only the GM can reset someone else's cooldown");
+                    return;
+                }
+                self.effect_data.cooldowns.remove(&uuid);
+            }
+            EffectMessage::StartTurn(piece) => {
+                if !self.is_gm_endpoint(from) {
+                    warn!("Only the GM can start turns");
+                    return;
+                }
+                let Some(player) = self.board_data.players.get(&piece) else {
+                    error!("Player {piece} could not be found on the server!");
+                    return;
+                };
+
+                let piece_target = EffectTarget::Piece(piece);
+                let character = player
+                    .link_stats_to
+                    .clone()
+                    .or_else(|| {
+                        self.roster
+                            .contains(&player.name)
+                            .then(|| player.name.clone())
+                    })
+                    .map(EffectTarget::Character);
+                self.recharge_abilities(from, |cooldown| {
+                    matches!(cooldown.recharge, Recharge::Roll(_))
+                        && (cooldown.target == piece_target
+                            || Some(&cooldown.target) == character.as_ref())
+                });
+                return;
+            }
+        }
+
+        let message = DndMessage::EffectMessage(msg);
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            if user.endpoint != from {
+                self.handler.network().send(user.endpoint, &output_data);
+            }
+        }
+    }
+
+    /// Whether the sender can change effects on the target, the GM or whoever
+    /// the character or token belongs to
+    fn can_edit_target(&self, from: Endpoint, target: &EffectTarget) -> bool {
+        self.is_gm_endpoint(from)
+            || match target {
+                EffectTarget::Character(name) => self.username(from) == Some(name),
+                EffectTarget::Piece(piece) => self
+                    .board_data
+                    .players
+                    .get(piece)
+                    .is_some_and(|x| self.can_control_piece(from, x)),
+            }
+    }
+
+    /// Cooldowns are rebuilt from the ability on the character's sheet, so a
+    /// client can't hand itself uses back or restart a wait that's nearly done
+    fn checked_cooldown(
+        &self,
+        from: Endpoint,
+        uuid: uuid::Uuid,
+        cooldown: Cooldown,
+    ) -> Result<Cooldown, String> {
+        if !self.can_edit_target(from, &cooldown.target) {
+            return Err("You can only put your own abilities on cooldown".to_owned());
+        }
+
+        let already_recharging = self.effect_data.cooldowns.iter().any(|(id, x)| {
+            *id == uuid || (x.target == cooldown.target && x.ability == cooldown.ability)
+        });
+        if already_recharging && !self.is_gm_endpoint(from) {
+            return Err(format!("{} is already recharging", cooldown.ability));
+        }
+
+        let (recharge, uses) = match &cooldown.target {
+            EffectTarget::Character(name) => {
+                let query = Query::table("player_abilities")
+                    .select("uses,abilities(*)")
+                    .eq("player", name)
+                    .eq("ability_name", &cooldown.ability);
+                let rows: Vec<DBAbilityResponse> = self.select(query)?;
+                match rows.into_iter().next() {
+                    Some(row) => {
+                        let ability: Ability = row.into();
+                        let recharge = ability.recharge.unwrap_or(cooldown.recharge);
+                        (recharge, Some(ability.max_count))
+                    }
+                    None => (cooldown.recharge, None),
+                }
+            }
+            EffectTarget::Piece(_) => (cooldown.recharge, None),
+        };
+
+        Ok(Cooldown::new(
+            cooldown.ability,
+            recharge,
+            uses,
+            cooldown.target,
+        ))
+    }
+
+    /// Rolls for or counts down the spent abilities `should_tick` picks out.
+    /// Characters get their uses back in the DB, tokens only have the cooldown
+    fn recharge_abilities(&mut self, from: Endpoint, should_tick: impl Fn(&Cooldown) -> bool) {
+        let mut rng = rand::rng();
+        let mut rolls = Vec::new();
+        let mut recharged = Vec::new();
+        let mut updated = Vec::new();
+
+        let cooldowns = self.effect_data.cooldowns.iter_mut();
+        for (uuid, cooldown) in cooldowns.filter(|(_, x)| should_tick(x)) {
+            let ability = cooldown.ability.clone();
+            let done = cooldown.tick(|| {
+                let value = rng.random_range(1..=6);
+                rolls.push((ability, value));
+                value
+            });
+
+            if done {
+                recharged.push((*uuid, cooldown.clone()));
+            } else if matches!(cooldown.recharge, Recharge::Rounds(_)) {
+                updated.push((*uuid, cooldown.clone()));
+            }
+        }
+
+        // Recharge rolls are for the GM, everyone finds out once it's back
+        if let Some(gm) = self.gm.as_ref().and_then(|name| self.users.get(name)) {
+            for (ability, value) in rolls {
+                let roll = DieRoll {
+                    die: 6,
+                    count: 1,
+                    value: value as u32,
+                    modifier: 0,
+                    character: None,
+                    reason: Some(format!("{} recharge", ability)),
+                    visibility: RollVisibility::GmOnly,
+                    kind: DieKind::Standard,
+                    rolls: vec![value as u32],
+                    outcome: None,
+                }
+                .resolve(&self.ruleset);
+                let message =
+                    DndMessage::Log(User::server(), LogMessage::Roll(roll), Some(Utc::now()));
+                let output_data = bincode::serialize(&message).unwrap();
+                self.handler.network().send(gm.endpoint, &output_data);
+            }
+        }
+
+        for (uuid, cooldown) in updated {
+            self.send_message_to_all(DndMessage::EffectMessage(EffectMessage::SetCooldown(
+                uuid, cooldown,
+            )));
+        }
+
+        for (uuid, cooldown) in recharged {
+            self.effect_data.cooldowns.remove(&uuid);
+            self.send_message_to_all(DndMessage::EffectMessage(EffectMessage::RemoveCooldown(
+                uuid,
+            )));
+
+            let target = match cooldown.target {
+                EffectTarget::Character(name) => {
+                    if let Some(uses) = cooldown.uses {
+                        self.restore_ability_uses(from, &name, &cooldown.ability, uses);
+                    }
+                    name
+                }
+                EffectTarget::Piece(_) => String::from("a token"),
+            };
+
+            info!("{} recharged on {}", cooldown.ability, target);
+            self.send_log_message_to_all(
+                User::server(),
+                LogMessage::AbilityRecharged(cooldown.ability, target),
+            );
+        }
+    }
+
+    fn restore_ability_uses(&self, from: Endpoint, name: &str, ability: &str, uses: i64) {
+        let user = User {
+            name: name.to_owned(),
+        };
+        let endpoint = self.users.get(name).map(|x| x.endpoint);
+
+        self.workers.submit(
+            endpoint.unwrap_or(from),
+            DndMessage::UpdateAbilityCount(user.clone(), ability.to_owned(), uses),
+        );
+        // Queued behind the update, so the refreshed sheet has the uses back
+        if let Some(endpoint) = endpoint {
+            self.workers
+                .submit(endpoint, DndMessage::RetrieveCharacterData(user));
+        }
+    }
+
+    fn load_handouts(db: &dyn Storage) -> Result<HashMap<uuid::Uuid, Handout>, Box<dyn Error>> {
+        let res = futures::executor::block_on(db.select(Query::table("handouts")))?;
+
+        let handouts: Vec<DBHandout> = parse_rows(res)?;
+        info!("Loaded {} handouts", handouts.len());
+
+        Ok(handouts.into_iter().map(|x| (x.id, x.handout)).collect())
+    }
+
+    fn handle_handout_message(&mut self, from: Endpoint, msg: HandoutMessage) {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can manage handouts");
+            return;
+        }
+
+        match msg {
+            HandoutMessage::CreateHandout(uuid, handout) => {
+                self.handouts.insert(uuid, handout);
+                let result = self.save_handout(uuid);
+                self.report_error(from, "Saving handout", result);
+                self.sync_handout(from, uuid);
+            }
+            HandoutMessage::ShareHandout(uuid, visibility) => {
+                let Some(handout) = self.handouts.get_mut(&uuid) else {
+                    error!("Handout {uuid} could not be found on the server!");
+                    return;
+                };
+
+                handout.visibility = visibility;
+                let result = self.save_handout(uuid);
+                self.report_error(from, "Sharing handout", result);
+                self.sync_handout(from, uuid);
+            }
+            HandoutMessage::DeleteHandout(uuid) => {
+                self.handouts.remove(&uuid);
+
+                let query = Query::table("handouts").eq("id", uuid);
+                let result = self.execute_write(self.db.delete(query));
+                if result.is_ok() {
+                    info!("Deleted handout {uuid}");
+                }
+                self.report_error(from, "Deleting handout", result);
+
+                self.broadcast_message(
+                    from,
+                    DndMessage::HandoutMessage(HandoutMessage::DeleteHandout(uuid)),
+                );
+            }
+        }
+    }
+
+    fn save_handout(&self, uuid: uuid::Uuid) -> Result<(), String> {
+        let Some(handout) = self.handouts.get(&uuid) else {
+            return Ok(());
+        };
+
+        let row = DBHandout {
+            id: uuid,
+            handout: handout.clone(),
+        };
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("handouts", json, "id"))?;
+
+        info!("Saved handout {uuid}");
+        Ok(())
+    }
+
+    fn can_see_handout(&self, name: &str, handout: &Handout) -> bool {
+        self.gm.as_deref() == Some(name) || handout.visible_to(name)
+    }
+
+    /// Sends the handout to everyone who can see it and removes it for everyone else
+    fn sync_handout(&self, ignore_enpoint: Endpoint, uuid: uuid::Uuid) {
+        let Some(handout) = self.handouts.get(&uuid) else {
+            return;
+        };
+
+        for (name, user) in self.users.iter() {
+            if user.endpoint == ignore_enpoint {
+                continue;
+            }
+
+            let msg = if self.can_see_handout(name, handout) {
+                HandoutMessage::CreateHandout(uuid, handout.clone())
+            } else {
+                HandoutMessage::DeleteHandout(uuid)
+            };
+
+            let output_data = bincode::serialize(&DndMessage::HandoutMessage(msg)).unwrap();
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+
+    fn send_initial_handouts(&self, endpoint: Endpoint, name: &str) {
+        for (uuid, handout) in self.handouts.iter() {
+            if self.can_see_handout(name, handout) {
+                let message = DndMessage::HandoutMessage(HandoutMessage::CreateHandout(
+                    *uuid,
+                    handout.clone(),
+                ));
+                let output_data = bincode::serialize(&message).unwrap();
+                self.handler.network().send(endpoint, &output_data);
+            }
+        }
+    }
+
+    fn load_journal(db: &dyn Storage) -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+        let res =
+            futures::executor::block_on(db.select(Query::table("board_journal").order("id")))?;
+
+        let journal: Vec<JournalEntry> = parse_rows(res)?;
+        info!("Loaded {} board journal entries", journal.len());
+
+        Ok(journal)
+    }
+
+    fn load_pins(db: &dyn Storage) -> Result<HashMap<uuid::Uuid, PinnedMessage>, Box<dyn Error>> {
+        let res = futures::executor::block_on(db.select(Query::table("pinned_messages")))?;
+
+        let pins: Vec<DBPinnedMessage> = parse_rows(res)?;
+        info!("Loaded {} pinned messages", pins.len());
+
+        Ok(pins.into_iter().map(|x| (x.id, x.pin)).collect())
+    }
+
+    fn handle_pin_message(&mut self, from: Endpoint, msg: PinMessage) {
+        let Some(name) = self.username(from).cloned() else {
+            warn!("Only registered users can pin messages");
+            return;
+        };
+
+        match msg {
+            PinMessage::Pin(uuid, mut pin) => {
+                if pin.message.is_private() {
+                    let result = Err("Private rolls can't be pinned".to_owned());
+                    self.report_error(from, "Pinning message", result);
+                    return;
+                }
+
+                if pin.user.name != name && !self.was_logged(&pin.user, &pin.message) {
+                    let result = Err("Only messages from this session can be pinned".to_owned());
+                    self.report_error(from, "Pinning message", result);
+                    return;
+                }
+
+                pin.pinned_by = name;
+                let row = DBPinnedMessage {
+                    id: uuid,
+                    pin: pin.clone(),
+                };
+                let result = serde_json::to_value(&row)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| self.execute_write(self.db.insert("pinned_messages", json)));
+                if result.is_err() {
+                    self.report_error(from, "Pinning message", result);
+                    return;
+                }
+
+                self.pins.insert(uuid, pin.clone());
+                self.broadcast_message(from, DndMessage::PinMessage(PinMessage::Pin(uuid, pin)));
+            }
+            PinMessage::Unpin(uuid) => {
+                let Some(pin) = self.pins.get(&uuid) else {
+                    return;
+                };
+                if pin.pinned_by != name && !self.is_gm_endpoint(from) {
+                    warn!(
+                        "'{name}' can't unpin a message pinned by '{}'",
+                        pin.pinned_by
+                    );
+                    return;
+                }
+
+                let query = Query::table("pinned_messages").eq("id", uuid);
+                let result = self.execute_write(self.db.delete(query));
+                if result.is_err() {
+                    self.report_error(from, "Unpinning message", result);
+                    return;
+                }
+
+                self.pins.remove(&uuid);
+                self.broadcast_message(from, DndMessage::PinMessage(PinMessage::Unpin(uuid)));
+            }
+        }
+    }
+
+    fn send_initial_pins(&self, endpoint: Endpoint) {
+        for (uuid, pin) in self.pins.iter() {
+            let message = DndMessage::PinMessage(PinMessage::Pin(*uuid, pin.clone()));
+            let output_data = bincode::serialize(&message).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+    }
+
+    fn load_ruleset(db: &dyn Storage) -> Result<Ruleset, Box<dyn Error>> {
+        let query = Query::table("ruleset").eq("id", RULESET_ID);
+        let res = futures::executor::block_on(db.select(query))?;
+
+        let rows: Vec<DBRuleset> = parse_rows(res)?;
+        Ok(rows
+            .into_iter()
+            .next()
+            .map(|x| x.ruleset)
+            .unwrap_or_default())
+    }
+
+    fn save_ruleset(&mut self, from: Endpoint, ruleset: Ruleset) -> Result<(), String> {
+        self.ruleset = ruleset;
+        self.broadcast_message(from, DndMessage::SetRuleset(self.ruleset.clone()));
+
+        let row = DBRuleset {
+            id: RULESET_ID,
+            ruleset: self.ruleset.clone(),
+        };
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("ruleset", json, "id"))?;
+
+        info!("Saved the ruleset");
+        Ok(())
+    }
+
+    fn load_xp_table(db: &dyn Storage) -> Result<XpTable, Box<dyn Error>> {
+        let query = Query::table("xp_table").eq("id", XP_TABLE_ID);
+        let res = futures::executor::block_on(db.select(query))?;
+
+        let rows: Vec<DBXpTable> = parse_rows(res)?;
+        Ok(rows
+            .into_iter()
+            .next()
+            .map(|x| x.levels)
+            .unwrap_or_default())
+    }
+
+    fn save_xp_table(&mut self, from: Endpoint, table: XpTable) -> Result<(), String> {
+        self.xp_table = table;
+        self.broadcast_message(from, DndMessage::SetXpTable(self.xp_table.clone()));
+
+        let row = DBXpTable {
+            id: XP_TABLE_ID,
+            levels: self.xp_table.clone(),
+        };
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("xp_table", json, "id"))?;
+
+        info!("Saved the XP table");
+        Ok(())
+    }
+
+    fn load_soundboard(db: &dyn Storage) -> Result<Soundboard, Box<dyn Error>> {
+        let query = Query::table("soundboard").eq("id", SOUNDBOARD_ID);
+        let res = futures::executor::block_on(db.select(query))?;
+
+        let rows: Vec<DBSoundboard> = parse_rows(res)?;
+        Ok(rows
+            .into_iter()
+            .next()
+            .map(|x| x.soundboard)
+            .unwrap_or_default())
+    }
+
+    fn handle_sound_message(&mut self, from: Endpoint, msg: SoundMessage) -> Result<(), String> {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can use the soundboard");
+            return Ok(());
+        }
+
+        match &msg {
+            SoundMessage::SetSoundboard(soundboard) => {
+                self.soundboard = soundboard.clone();
+
+                let row = DBSoundboard {
+                    id: SOUNDBOARD_ID,
+                    soundboard: soundboard.clone(),
+                };
+                let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+                self.execute_write(self.db.upsert("soundboard", json, "id"))?;
+                info!("Saved the soundboard");
+            }
+            SoundMessage::Play(playing) => {
+                self.now_playing = Some((playing.clone(), Instant::now()));
+            }
+            SoundMessage::Stop(_) => self.now_playing = None,
+            SoundMessage::Effect(_) => {}
+        }
+
+        self.broadcast_message(from, DndMessage::SoundMessage(msg));
+        Ok(())
+    }
+
+    fn load_roll_tables(db: &dyn Storage) -> Result<Vec<RollTable>, Box<dyn Error>> {
+        let res = futures::executor::block_on(db.select(Query::table("roll_tables")))?;
+
+        let tables: Vec<RollTable> = parse_rows(res)?;
+        info!("Loaded {} roll tables", tables.len());
+        Ok(tables)
+    }
+
+    /// Tables are keyed by name, saving one with an existing name replaces it
+    fn save_roll_table(&mut self, from: Endpoint, table: RollTable) -> Result<(), String> {
+        let json = serde_json::to_value(&table).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("roll_tables", json, "name"))?;
+
+        match self.roll_tables.iter_mut().find(|x| x.name == table.name) {
+            Some(existing) => *existing = table.clone(),
+            None => self.roll_tables.push(table.clone()),
+        }
+        info!("Saved roll table '{}'", table.name);
+
+        self.broadcast_message(from, DndMessage::SaveRollTable(table));
+        Ok(())
+    }
+
+    fn delete_roll_table(&mut self, from: Endpoint, name: String) -> Result<(), String> {
+        let query = Query::table("roll_tables").eq("name", &name);
+        self.execute_write(self.db.delete(query))?;
+
+        self.roll_tables.retain(|x| x.name != name);
+        info!("Deleted roll table '{}'", name);
+
+        self.broadcast_message(from, DndMessage::DeleteRollTable(name));
+        Ok(())
+    }
+
+    fn load_piece_templates(db: &dyn Storage) -> Result<Vec<PieceTemplate>, Box<dyn Error>> {
+        let res = futures::executor::block_on(db.select(Query::table("piece_templates")))?;
+
+        let templates: Vec<PieceTemplate> = parse_rows(res)?;
+        info!("Loaded {} piece templates", templates.len());
+        Ok(templates)
+    }
+
+    /// Keyed by name like roll tables
+    fn save_piece_template(
+        &mut self,
+        from: Endpoint,
+        template: PieceTemplate,
+    ) -> Result<(), String> {
+        let json = serde_json::to_value(&template).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("piece_templates", json, "name"))?;
+
+        match self
+            .piece_templates
+            .iter_mut()
+            .find(|x| x.name == template.name)
+        {
+            Some(existing) => *existing = template.clone(),
+            None => self.piece_templates.push(template.clone()),
+        }
+        info!("Saved piece template '{}'", template.name);
+
+        self.broadcast_message(from, DndMessage::SavePieceTemplate(template));
+        Ok(())
+    }
+
+    fn delete_piece_template(&mut self, from: Endpoint, name: String) -> Result<(), String> {
+        let query = Query::table("piece_templates").eq("name", &name);
+        self.execute_write(self.db.delete(query))?;
+
+        self.piece_templates.retain(|x| x.name != name);
+        info!("Deleted piece template '{}'", name);
+
+        self.broadcast_message(from, DndMessage::DeletePieceTemplate(name));
+        Ok(())
+    }
+
+    fn load_roll_stats(db: &dyn Storage) -> Result<Vec<DBRollStats>, Box<dyn Error>> {
+        let res = futures::executor::block_on(db.select(Query::table("roll_stats")))?;
+
+        let rows: Vec<DBRollStats> = parse_rows(res)?;
+        info!("Loaded roll stats for {} players", rows.len());
+        Ok(rows)
+    }
+
+    /// Writes the player's session rolls so far alongside their totals
+    fn save_session_rolls(&self, player: &str) -> Result<(), String> {
+        let row = DBRollStats {
+            player: player.to_owned(),
+            stats: self.campaign_rolls.get(player).cloned().unwrap_or_default(),
+            session: self.session_rolls.get(player).cloned().unwrap_or_default(),
+        };
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("roll_stats", json, "player"))
+    }
+
+    /// Posts the recap of the session's rolls and adds them to the campaign's.
+    /// Every player's stats are written at once, and nothing is cleared
+    /// unless that worked
+    fn end_roll_stats_session(&mut self) -> Result<(), String> {
+        if self.session_rolls.is_empty() {
+            return Ok(());
+        }
+
+        let mut totals = self.campaign_rolls.clone();
+        let rows: Vec<_> = self
+            .session_rolls
+            .iter()
+            .map(|(player, stats)| {
+                let total = totals.entry(player.clone()).or_default();
+                total.merge(stats);
+                DBRollStats {
+                    player: player.clone(),
+                    stats: total.clone(),
+                    session: RollStats::default(),
+                }
+            })
+            .collect();
+        let json = serde_json::to_value(&rows).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("roll_stats", json, "player"))?;
+
+        self.campaign_rolls = totals;
+        let session = std::mem::take(&mut self.session_rolls);
+        self.send_log_message_to_all(User::server(), LogMessage::SessionSummary(session));
+        info!("Saved this session's roll stats");
+        Ok(())
+    }
+
+    fn save_issue_report(&self, from: Endpoint, report: IssueReport) -> Result<(), String> {
+        let username = self
+            .username(from)
+            .map_or_else(|| from.to_string(), |name| name.clone());
+        info!("Issue reported by '{username}': {}", report.description);
+
+        let row = DBFeedback { username, report };
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.insert("feedback", json))
+    }
+
+    fn handle_signal(&mut self, signal: ServerSignal) {
+        match signal {
+            ServerSignal::Snapshot => {
+                let result = self
+                    .take_snapshot("Automatic", true)
+                    .and_then(|_| Self::prune_snapshots(&*self.db, self.keep_snapshots))
+                    .map(|_| ());
+                if let Err(e) = result {
+                    error!("Automatic snapshot failed: {e}");
+                }
+
+                if let Some(endpoint) = self.gm_endpoint() {
+                    self.send_snapshot_list(endpoint);
+                }
+
+                if let Some(interval) = self.snapshot_interval {
+                    self.handler
+                        .signals()
+                        .send_with_timer(ServerSignal::Snapshot, interval);
+                }
+            }
+            ServerSignal::SessionTick => {
+                if let Some(minutes) = self.session_timer.due_break() {
+                    self.send_log_message_to_all(
+                        User::server(),
+                        LogMessage::BreakReminder(minutes),
+                    );
+                }
+
+                self.handler
+                    .signals()
+                    .send_with_timer(ServerSignal::SessionTick, SESSION_TICK);
+            }
+            ServerSignal::ExpireHolds => {
+                let expired = self
+                    .holds
+                    .iter()
+                    .filter(|(_, hold)| hold.expired())
+                    .map(|(uuid, _)| *uuid)
+                    .collect::<Vec<_>>();
+                for uuid in expired {
+                    self.holds.remove(&uuid);
+                    self.send_holder(uuid, None);
+                }
+
+                self.handler
+                    .signals()
+                    .send_with_timer(ServerSignal::ExpireHolds, HOLD_TICK);
+            }
+            ServerSignal::CheckConnections => {
+                self.drop_stale_connections();
+
+                self.handler.signals().send_with_timer(
+                    ServerSignal::CheckConnections,
+                    self.connection_check_interval(),
+                );
+            }
+            ServerSignal::SaveJournal => {
+                self.save_journal();
+
+                self.handler
+                    .signals()
+                    .send_with_timer(ServerSignal::SaveJournal, JOURNAL_SAVE_INTERVAL);
+            }
+        }
+    }
+
+    fn handle_session_clock_message(&mut self, from: Endpoint, msg: SessionClockMessage) {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can control the session clock");
+            return;
+        }
+
+        let timer = &mut self.session_timer;
+        match msg {
+            SessionClockMessage::Start => {
+                if timer.started.is_none() && timer.banked.is_zero() {
+                    self.notifier.notify("The session has started");
+                }
+                timer.started.get_or_insert_with(Instant::now);
+            }
+            SessionClockMessage::Pause => {
+                timer.banked = timer.elapsed();
+                timer.started = None;
+            }
+            SessionClockMessage::Reset => {
+                *timer = SessionTimer {
+                    break_every: timer.break_every,
+                    ..Default::default()
+                };
+                let result = self.end_roll_stats_session();
+                self.report_error(from, "Saving roll stats", result);
+            }
+            SessionClockMessage::SetBreakInterval(minutes) => {
+                timer.break_every = minutes;
+                // Only remind about breaks from here on
+                timer.breaks = timer
+                    .break_interval()
+                    .map(|x| timer.elapsed().as_secs() / x)
+                    .unwrap_or_default();
+            }
+            SessionClockMessage::State(_) => return,
+        }
+
+        let clock = self.session_timer.clock();
+        self.send_message_to_all(DndMessage::SessionClockMessage(SessionClockMessage::State(
+            clock,
+        )));
+    }
+
+    fn gm_endpoint(&self) -> Option<Endpoint> {
+        self.users.get(self.gm.as_ref()?).map(|info| info.endpoint)
+    }
+
+    fn handle_snapshot_message(&mut self, from: Endpoint, msg: SnapshotMessage) {
+        if !self.is_gm_endpoint(from) {
+            warn!("Only the GM can manage snapshots");
+            return;
+        }
+
+        match msg {
+            SnapshotMessage::Take(tag) => {
+                let result = self.take_snapshot(&tag, false).map(|info| {
+                    self.notifier
+                        .notify(format!("The GM saved the campaign as '{}'", tag));
+                    let message = DndMessage::SnapshotMessage(SnapshotMessage::Saved(info));
+                    let output_data = bincode::serialize(&message).unwrap();
+                    self.handler.network().send(from, &output_data);
+                });
+                self.report_error(from, "Taking snapshot", result);
+            }
+            SnapshotMessage::SaveNow => {
+                self.save_journal();
+                let result = self.save_now().map(|info| {
+                    let message = DndMessage::SnapshotMessage(SnapshotMessage::Saved(info));
+                    let output_data = bincode::serialize(&message).unwrap();
+                    self.handler.network().send(from, &output_data);
+                });
+                self.report_error(from, "Saving", result);
+            }
+            SnapshotMessage::Restore(uuid) => {
+                let result = self.restore_snapshot(uuid);
+                self.report_error(from, "Restoring snapshot", result);
+            }
+            SnapshotMessage::Delete(uuid) => {
+                let query = Query::table("snapshots").eq("id", uuid);
+                let result = self.execute_write(self.db.delete(query));
+                self.report_error(from, "Deleting snapshot", result);
+            }
+            SnapshotMessage::RequestUsage => {
+                let result = self.snapshot_usage().map(|usage| {
+                    let message = DndMessage::SnapshotMessage(SnapshotMessage::Usage(usage));
+                    let output_data = bincode::serialize(&message).unwrap();
+                    self.handler.network().send(from, &output_data);
+                });
+                self.report_error(from, "Checking snapshot storage", result);
+                return;
+            }
+            SnapshotMessage::List(_) | SnapshotMessage::Usage(_) | SnapshotMessage::Saved(_) => {}
+        }
+
+        self.send_snapshot_list(from);
+    }
+
+    fn take_snapshot(&self, tag: &str, automatic: bool) -> Result<SnapshotInfo, String> {
+        if !automatic {
+            let tagged = Self::list_snapshots(&*self.db)?
+                .iter()
+                .filter(|x| !x.automatic)
+                .count();
+            if tagged >= MAX_TAGGED_SNAPSHOTS {
+                return Err(format!(
+                    "There are already {MAX_TAGGED_SNAPSHOTS} saved snapshots, delete some first"
+                ));
+            }
+        }
+
+        let row = self.snapshot(tag, automatic)?;
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.insert("snapshots", json))?;
+
+        info!("Took snapshot '{}'", tag);
+        Ok(row.info)
+    }
+
+    /// Swaps the one manual save for a new snapshot, so saving often doesn't
+    /// pile them up
+    fn save_now(&self) -> Result<SnapshotInfo, String> {
+        let row = self.snapshot(MANUAL_SAVE_TAG, false)?;
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        let old = Query::table("snapshots")
+            .eq("tag", MANUAL_SAVE_TAG)
+            .eq("automatic", false);
+        let writes = vec![Write::delete(old), Write::insert("snapshots", json)];
+        self.execute_write(self.db.transaction(writes))?;
+
+        info!("Saved the campaign");
+        Ok(row.info)
+    }
+
+    /// The character tables and the board as they are now
+    fn snapshot(&self, tag: &str, automatic: bool) -> Result<DBSnapshot, String> {
+        let mut data = serde_json::Map::new();
+        for (table, _) in SNAPSHOT_TABLES {
+            let rows = self.execute_query(self.db.select(Query::table(table)))?;
+            data.insert(table.to_owned(), Value::Array(rows));
+        }
+        let board = serde_json::to_value(&self.board_data).map_err(|e| e.to_string())?;
+        data.insert(SNAPSHOT_BOARD.to_owned(), board);
+        let data = Value::Object(data);
+
+        Ok(DBSnapshot {
+            info: SnapshotInfo {
+                id: uuid::Uuid::new_v4(),
+                tag: tag.to_owned(),
+                automatic,
+                created_at: Utc::now(),
+            },
+            size: data.to_string().len() as u64,
+            data,
+        })
+    }
+
+    /// Newest first
+    fn list_snapshots(db: &dyn Storage) -> Result<Vec<SnapshotInfo>, String> {
+        let query = Query::table("snapshots")
+            .select("id,tag,automatic,created_at")
+            .order_desc("created_at");
+        parse_rows(futures::executor::block_on(db.select(query))?)
+    }
+
+    fn send_snapshot_list(&self, endpoint: Endpoint) {
+        match Self::list_snapshots(&*self.db) {
+            Ok(list) => {
+                let message = DndMessage::SnapshotMessage(SnapshotMessage::List(list));
+                let output_data = bincode::serialize(&message).unwrap();
+                self.handler.network().send(endpoint, &output_data);
+            }
+            Err(e) => {
+                error!("Failed to list snapshots: {e}");
+                self.send_error(endpoint, "Loading snapshots", e);
+            }
+        }
+    }
+
+    /// Deletes all but the newest `keep` automatic snapshots, handing back
+    /// how many were deleted
+    fn prune_snapshots(db: &dyn Storage, keep: usize) -> Result<usize, String> {
+        let old = Self::list_snapshots(db)?
+            .into_iter()
+            .filter(|x| x.automatic)
+            .skip(keep);
+
+        let mut pruned = 0;
+        for snapshot in old {
+            let query = Query::table("snapshots").eq("id", snapshot.id);
+            futures::executor::block_on(db.delete(query))?;
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+
+    fn snapshot_usage(&self) -> Result<SnapshotUsage, String> {
+        #[derive(serde::Deserialize)]
+        struct Row {
+            automatic: bool,
+            /// Missing on snapshots taken before sizes were saved
+            size: Option<u64>,
+        }
+
+        let rows: Vec<Row> = self.select(Query::table("snapshots").select("automatic,size"))?;
+
+        let automatic = rows.iter().filter(|x| x.automatic).count();
+        Ok(SnapshotUsage {
+            automatic,
+            tagged: rows.len() - automatic,
+            bytes: rows.iter().filter_map(|x| x.size).sum(),
+            keep_automatic: Some(self.keep_snapshots),
+        })
+    }
+
+    /// Owned rows are replaced outright. Catalog entries in the snapshot are
+    /// upserted, so deleted ones come back and edited ones revert, but newer
+    /// ones are kept. Everything is written in one transaction
+    fn restore_snapshot(&mut self, uuid: uuid::Uuid) -> Result<(), String> {
+        let snapshot: Vec<DBSnapshotData> =
+            self.select(Query::table("snapshots").select("tag,data").eq("id", uuid))?;
+        let snapshot = snapshot
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Snapshot {uuid} could not be found"))?;
+
+        // Restoring the wrong snapshot shouldn't be the end of the world either
+        self.take_snapshot(&format!("Before restoring '{}'", snapshot.tag), false)?;
+
+        let _hold = self.workers.hold_all();
+        let mut writes: Vec<_> = SNAPSHOT_TABLES
+            .iter()
+            .rev()
+            .filter(|(table, _)| !CATALOG_TABLES.contains(table))
+            .map(|(table, column)| Write::delete(Query::table(*table).not_null(*column)))
+            .collect();
+
+        for (table, column) in SNAPSHOT_TABLES {
+            let rows = snapshot
+                .data
+                .get(table)
+                .cloned()
+                .unwrap_or(Value::Array(vec![]));
+            if rows.as_array().is_none_or(|x| x.is_empty()) {
+                continue;
+            }
+
+            if CATALOG_TABLES.contains(&table) {
+                writes.push(Write::upsert(table, rows, column));
+            } else {
+                writes.push(Write::insert(table, rows));
+            }
+        }
+        // The snapshot's items keep their ids
+        writes.push(Write::reset_identity("items", "id"));
+
+        // Older snapshots only have the characters, the board is left alone
+        let board: Option<BoardData> = snapshot
+            .data
+            .get(SNAPSHOT_BOARD)
+            .map(|x| serde_json::from_value(x.clone()))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+
+        self.execute_write(self.db.transaction(writes))?;
+
+        info!("Restored snapshot '{}'", snapshot.tag);
+        self.send_log_message_to_all(
+            User::server(),
+            LogMessage::Chat(format!("The GM restored the snapshot '{}'", snapshot.tag)),
+        );
+
+        if let Some(board) = board {
+            self.restore_board(board);
+        }
+
+        // The snapshot may bring back deleted characters or drop newer ones
+        match Self::load_roster(&*self.db) {
+            Ok(roster) => {
+                self.roster = roster;
+                self.broadcast_roster();
+            }
+            Err(e) => error!("Failed to reload the character roster: {e:?}"),
+        }
+        for info in self.users.values() {
+            self.workers.submit(
+                info.endpoint,
+                DndMessage::RetrieveCharacterData(info.user_data.clone()),
+            );
+        }
+        Ok(())
+    }
+
+    fn load_stash(db: &dyn Storage) -> Result<HashMap<uuid::Uuid, Loot>, Box<dyn Error>> {
+        let query = Query::table("party_stash").select("id,count,pending_claim,items(*)");
+        let res = futures::executor::block_on(db.select(query))?;
+
+        let loot: Vec<DBStashResponse> = parse_rows(res)?;
+        info!("Loaded {} stashed items", loot.len());
+
+        Ok(loot.into_iter().map(|x| (x.id, x.into())).collect())
+    }
+
+    fn handle_stash_message(&mut self, from: Endpoint, msg: StashMessage) {
+        let is_gm = self.is_gm_endpoint(from);
+
+        match msg {
+            StashMessage::AddLoot(name, count) if is_gm => {
+                let result = self.add_loot(&name, count);
+                self.report_error(from, &format!("Stashing '{}'", name), result);
+            }
+            StashMessage::ClaimLoot(uuid, character) => {
+                if !is_gm && self.username(from) != Some(&character) {
+                    warn!("Loot can only be claimed for your own character");
+                    return;
+                }
+
+                let Some(loot) = self.stash.loot.get_mut(&uuid) else {
+                    error!("Loot {uuid} could not be found on the server!");
+                    return;
+                };
+                if loot.pending_claim.is_some() {
+                    self.send_error(from, "Claiming loot", "Someone has already claimed that");
+                    return;
+                }
+
+                if self.stash.require_approval && !is_gm {
+                    loot.pending_claim = Some(character);
+                    let result = self.save_loot(uuid);
+                    self.report_error(from, "Claiming loot", result);
+                } else {
+                    let result = self.give_loot(uuid, &character);
+                    self.report_error(from, "Claiming loot", result);
+                }
+            }
+            StashMessage::ResolveClaim(uuid, approved) if is_gm => {
+                let Some(loot) = self.stash.loot.get_mut(&uuid) else {
+                    error!("Loot {uuid} could not be found on the server!");
+                    return;
+                };
+                let Some(character) = loot.pending_claim.take() else {
+                    return;
+                };
+
+                let result = if approved {
+                    self.give_loot(uuid, &character)
+                } else {
+                    self.save_loot(uuid)
+                };
+                self.report_error(from, "Resolving claim", result);
+            }
+            StashMessage::RemoveLoot(uuid) if is_gm => {
+                let result = self.remove_loot(uuid).map(|_| ());
+                self.report_error(from, "Removing loot", result);
+            }
+            StashMessage::RequireApproval(required) if is_gm => {
+                self.stash.require_approval = required;
+                self.broadcast_message(
+                    from,
+                    DndMessage::StashMessage(StashMessage::RequireApproval(required)),
+                );
+            }
+            StashMessage::SetLoot(..) => warn!("Loot can only be changed through the server"),
+            _ => warn!("Only the GM can manage the party stash"),
+        }
+    }
+
+    fn add_loot(&mut self, name: &str, count: u32) -> Result<(), String> {
+        let items: Vec<DBItem> = self.select(Query::table("items").eq("name", name))?;
+        let item = items
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("There is no item called '{}'", name))?;
+
+        let uuid = uuid::Uuid::new_v4();
+        self.stash.loot.insert(
+            uuid,
+            Loot {
+                item: item.with_count(count),
+                pending_claim: None,
+            },
+        );
+
+        info!("Added {} x{} to the party stash", name, count);
+        self.save_loot(uuid)
+    }
+
+    /// Saves the loot and sends it to everyone
+    fn save_loot(&self, uuid: uuid::Uuid) -> Result<(), String> {
+        let Some(loot) = self.stash.loot.get(&uuid) else {
+            return Ok(());
+        };
+
+        self.send_message_to_all(DndMessage::StashMessage(StashMessage::SetLoot(
+            uuid,
+            loot.clone(),
+        )));
+
+        let row = DBStashRow {
+            id: uuid,
+            item_id: loot.item.id,
+            count: loot.item.count,
+            pending_claim: loot.pending_claim.clone(),
+        };
+        let json = serde_json::to_value(&row).map_err(|e| e.to_string())?;
+        self.execute_write(self.db.upsert("party_stash", json, "id"))
+    }
+
+    fn remove_loot(&mut self, uuid: uuid::Uuid) -> Result<Loot, String> {
+        let loot = self
+            .stash
+            .loot
+            .remove(&uuid)
+            .ok_or_else(|| format!("Loot {uuid} could not be found"))?;
+
+        self.send_message_to_all(DndMessage::StashMessage(StashMessage::RemoveLoot(uuid)));
+
+        let query = Query::table("party_stash").eq("id", uuid);
+        self.execute_write(self.db.delete(query))?;
+        Ok(loot)
+    }
+
+    /// Moves loot out of the stash and into a character's inventory. Both
+    /// happen together so a failed write can't lose or duplicate the item
+    fn give_loot(&mut self, uuid: uuid::Uuid, character: &str) -> Result<(), String> {
+        let item = self
+            .stash
+            .loot
+            .get(&uuid)
+            .map(|x| x.item.clone())
+            .ok_or_else(|| format!("Loot {uuid} could not be found"))?;
+
+        let _hold = self.workers.hold([character]);
+        let writes = vec![
+            self.inventory_write(character, item.id, item.count)?,
+            Write::delete(Query::table("party_stash").eq("id", uuid)),
+        ];
+        self.execute_write(self.db.transaction(writes))?;
+
+        self.stash.loot.remove(&uuid);
+        self.send_message_to_all(DndMessage::StashMessage(StashMessage::RemoveLoot(uuid)));
+
+        info!(
+            "'{}' took {} x{} from the party stash",
+            character, item.name, item.count
+        );
+        self.send_log_message_to_all(
+            User::server(),
+            LogMessage::Chat(format!(
+                "{} took {} x{} from the party stash",
+                character, item.name, item.count
+            )),
+        );
+
+        self.send_item_list(character);
+        Ok(())
+    }
+
+    fn send_initial_stash(&self, endpoint: Endpoint) {
+        let mut messages = vec![StashMessage::RequireApproval(self.stash.require_approval)];
+        messages.extend(
+            self.stash
+                .loot
+                .iter()
+                .map(|(uuid, loot)| StashMessage::SetLoot(*uuid, loot.clone())),
+        );
+
+        for msg in messages {
+            let output_data = bincode::serialize(&DndMessage::StashMessage(msg)).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+    }
+
+    fn handle_trade_message(&mut self, from: Endpoint, msg: TradeMessage) {
+        let Some(name) = self.username(from).cloned() else {
+            warn!("Only registered users can trade");
+            return;
+        };
+
+        match msg {
+            TradeMessage::Request(other) => {
+                if other == name || !self.users.contains_key(&other) {
+                    self.send_error(from, "Starting trade", format!("'{}' isn't online", other));
+                    return;
+                }
+
+                let uuid = uuid::Uuid::new_v4();
+                info!("'{}' started trade {uuid} with '{}'", name, other);
+                self.trades.insert(
+                    uuid,
+                    TradeSession {
+                        trade: Trade::new(name, other),
+                        snapshots: Default::default(),
+                    },
+                );
+                self.send_trade_update(uuid);
+            }
+            TradeMessage::SetOffer(uuid, items) => {
+                let Some(side) = self.trade_side(uuid, &name) else {
+                    return;
+                };
+
+                match self.inventory_snapshot(&name, &items) {
+                    Ok(snapshot) => {
+                        let session = self.trades.get_mut(&uuid).unwrap();
+                        session.trade.offers[side] = items;
+                        session.trade.confirmed = [false; 2];
+                        session.snapshots[side] = snapshot;
+                        self.send_trade_update(uuid);
+                    }
+                    Err(e) => self.send_error(from, "Offering items", e),
+                }
+            }
+            TradeMessage::Confirm(uuid) => {
+                let Some(side) = self.trade_side(uuid, &name) else {
+                    return;
+                };
+
+                let session = self.trades.get_mut(&uuid).unwrap();
+                session.trade.confirmed[side] = true;
+                if !session.trade.confirmed.iter().all(|x| *x) {
+                    self.send_trade_update(uuid);
+                    return;
+                }
+
+                let session = self.trades.remove(&uuid).unwrap();
+                let reason = match self.complete_trade(&session) {
+                    Ok(()) => "Trade complete".to_owned(),
+                    Err(e) => {
+                        error!("Trade {uuid} failed: {e}");
+                        format!("Trade failed: {e}")
+                    }
+                };
+                self.close_trade(uuid, &session.trade, reason);
+            }
+            TradeMessage::Cancel(uuid) => {
+                if self.trade_side(uuid, &name).is_none() {
+                    return;
+                }
+
+                let session = self.trades.remove(&uuid).unwrap();
+                self.close_trade(uuid, &session.trade, format!("{} cancelled", name));
+            }
+            TradeMessage::Update(..) | TradeMessage::Closed(..) => {
+                warn!("Trades can only be changed through the server")
+            }
+        }
+    }
+
+    fn trade_side(&self, uuid: uuid::Uuid, name: &str) -> Option<usize> {
+        let side = self
+            .trades
+            .get(&uuid)
+            .and_then(|session| session.trade.side(name));
+        if side.is_none() {
+            warn!("'{}' isn't part of trade {uuid}", name);
+        }
+        side
+    }
+
+    /// Checks the character actually has everything in the offer and records
+    /// how many of each item they hold
+    fn inventory_snapshot(&self, name: &str, offer: &[Item]) -> Result<HashMap<i64, u32>, String> {
+        let user = User {
+            name: name.to_owned(),
+        };
+        let items = self.get_item_list(&user).map_err(|e| e.to_string())?;
+
+        let mut snapshot = HashMap::new();
+        for offered in offer {
+            let held = items
+                .iter()
+                .find(|x| x.id == offered.id)
+                .map_or(0, |x| x.count);
+            if offered.count == 0 || offered.count > held || snapshot.contains_key(&offered.id) {
+                return Err(format!(
+                    "{} doesn't have {} {}",
+                    name, offered.count, offered.name
+                ));
+            }
+            snapshot.insert(offered.id, held);
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Swaps the offered items in one transaction, so a failed write leaves
+    /// both inventories as they were
+    fn complete_trade(&self, session: &TradeSession) -> Result<(), String> {
+        let trade = &session.trade;
+        let _hold = self.workers.hold(trade.players.iter().map(|x| x.as_str()));
+
+        for (side, name) in trade.players.iter().enumerate() {
+            let current = self.inventory_snapshot(name, &trade.offers[side])?;
+            if current != session.snapshots[side] {
+                return Err(format!("{}'s inventory changed", name));
+            }
+        }
+
+        // Both sides can offer the same item, so work out where each count ends up first
+        let mut counts: HashMap<(&str, i64), (Option<u32>, i64)> = HashMap::new();
+        for (side, offer) in trade.offers.iter().enumerate() {
+            let giver = trade.players[side].as_str();
+            let receiver = trade.players[1 - side].as_str();
+
+            for item in offer {
+                let moves = [
+                    (giver, -i64::from(item.count)),
+                    (receiver, i64::from(item.count)),
+                ];
+                for (name, change) in moves {
+                    let key = (name, item.id);
+                    if !counts.contains_key(&key) {
+                        let held = self.inventory_count(name, item.id)?;
+                        counts.insert(key, (held, held.map_or(0, i64::from)));
+                    }
+                    counts.get_mut(&key).unwrap().1 += change;
+                }
+            }
+        }
+
+        let writes = counts
+            .into_iter()
+            .map(|((name, item_id), (held, count))| {
+                let count = u32::try_from(count)
+                    .map_err(|_| format!("{name} doesn't have enough to give"))?;
+                Ok(set_inventory_count(name, item_id, held, count))
+            })
+            .collect::<Result<_, String>>()?;
+        self.execute_write(self.db.transaction(writes))?;
+
+        info!("'{}' and '{}' traded", trade.players[0], trade.players[1]);
+        trade.players.iter().for_each(|x| self.send_item_list(x));
+        Ok(())
+    }
+
+    fn send_to_traders(&self, trade: &Trade, message: DndMessage) {
+        let output_data = bincode::serialize(&message).unwrap();
+        for name in trade.players.iter() {
+            if let Some(info) = self.users.get(name) {
+                self.handler.network().send(info.endpoint, &output_data);
+            }
+        }
+    }
+
+    fn send_trade_update(&self, uuid: uuid::Uuid) {
+        if let Some(session) = self.trades.get(&uuid) {
+            let msg = TradeMessage::Update(uuid, session.trade.clone());
+            self.send_to_traders(&session.trade, DndMessage::TradeMessage(msg));
+        }
+    }
+
+    fn close_trade(&self, uuid: uuid::Uuid, trade: &Trade, reason: String) {
+        info!("Trade {uuid} closed: {reason}");
+        self.send_to_traders(
+            trade,
+            DndMessage::TradeMessage(TradeMessage::Closed(uuid, reason)),
+        );
+    }
+
+    fn send_initial_effect_data(&self, endpoint: Endpoint) {
+        let mut messages = vec![EffectMessage::SetRound(self.effect_data.round)];
+        messages.extend(
+            self.effect_data
+                .effects
+                .iter()
+                .map(|(uuid, effect)| EffectMessage::ApplyEffect(*uuid, effect.clone())),
+        );
+        messages.extend(
+            self.effect_data
+                .cooldowns
+                .iter()
+                .map(|(uuid, cooldown)| EffectMessage::SetCooldown(*uuid, cooldown.clone())),
+        );
+
+        for msg in messages {
+            let output_data = bincode::serialize(&DndMessage::EffectMessage(msg)).unwrap();
+            self.handler.network().send(endpoint, &output_data);
+        }
+    }
+
+    fn remember_log(&self, user: &User, msg: &LogMessage) {
+        let mut log = self.recent_log.borrow_mut();
+        if log.len() >= RECENT_LOG_LEN {
+            log.pop_front();
+        }
+        log.push_back(bincode::serialize(&(user, msg)).unwrap());
+    }
+
+    fn was_logged(&self, user: &User, msg: &LogMessage) -> bool {
+        let bytes = bincode::serialize(&(user, msg)).unwrap();
+        self.recent_log.borrow().contains(&bytes)
+    }
+
+    fn send_message_to_all(&self, message: DndMessage) {
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+
+    fn send_log_message_to_all(&self, username: User, msg: LogMessage) {
+        self.remember_log(&username, &msg);
+        let message = DndMessage::Log(username, msg, Some(Utc::now()));
+        let output_data = bincode::serialize(&message).unwrap();
+        for (_name, user) in self.users.iter() {
+            self.handler.network().send(user.endpoint, &output_data);
+        }
+    }
+}
+
+impl Drop for DndServer {
+    /// Journal entries still waiting for the next batch are written out on the way down
+    fn drop(&mut self) {
+        self.save_journal();
+    }
+}
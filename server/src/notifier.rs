@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use common::{
+    message::{DieRoll, LogMessage},
+    User,
+};
+use log::{info, warn};
+use tokio::sync::mpsc;
+
+/// Discord allows a handful of webhook posts every few seconds, anything
+/// that comes in while waiting is sent together in the next post
+const MIN_POST_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest message Discord accepts
+const MAX_MESSAGE_LEN: usize = 2000;
+
+/// Events waiting to be posted. Past this they're dropped rather than piling
+/// up while Discord is unreachable
+const QUEUE_LEN: usize = 256;
+
+/// Times a post is retried after Discord says to slow down
+const MAX_RETRIES: u32 = 3;
+
+/// Posts session events to a Discord webhook so the group channel keeps up
+/// without anyone having the app open. Does nothing unless
+/// `DND_DISCORD_WEBHOOK` is set
+#[derive(Clone, Default)]
+pub struct Notifier {
+    queue: Option<mpsc::Sender<String>>,
+}
+
+impl Notifier {
+    /// Must be called from inside the tokio runtime
+    pub fn from_env() -> Self {
+        let Ok(url) = dotenv::var("DND_DISCORD_WEBHOOK") else {
+            return Self::default();
+        };
+
+        info!("Posting session events to the Discord webhook");
+        let (tx, rx) = mpsc::channel(QUEUE_LEN);
+        tokio::spawn(post_events(url, rx));
+
+        Self { queue: Some(tx) }
+    }
+
+    pub fn notify(&self, text: impl Into<String>) {
+        if let Some(queue) = &self.queue {
+            match queue.try_send(text.into()) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!("Too many Discord events waiting, dropping the event")
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    warn!("The Discord notifier has stopped, dropping the event")
+                }
+            }
+        }
+    }
+}
+
+/// Natural 20s and 1s rolled in the open, the ones worth telling the channel
+/// about. Dice are rolled by the clients, so the post says it's what they reported
+pub fn loud_roll(user: &User, msg: &LogMessage) -> Option<String> {
+    let describe = |roll: &DieRoll, what: &str| {
+        let who = roll.character.as_ref().unwrap_or(&user.name);
+        let result = match (roll.is_crit(), roll.is_fumble()) {
+            (true, _) => "a critical",
+            (_, true) => "a fumble",
+            _ => return None,
+        };
+        Some(format!(
+            "{} reports rolling {} on {} ({})",
+            who,
+            result,
+            what,
+            roll.total()
+        ))
+    };
+
+    match msg {
+        LogMessage::Roll(roll) => describe(roll, roll.reason.as_deref().unwrap_or("a d20")),
+        LogMessage::Attack(attack) => describe(
+            &attack.to_hit,
+            &format!("an attack against {}", attack.target),
+        ),
+        _ => None,
+    }
+}
+
+async fn post_events(url: String, mut rx: mpsc::Receiver<String>) {
+    let client = reqwest::Client::new();
+
+    while let Some(first) = rx.recv().await {
+        let mut lines = vec![first];
+        while let Ok(line) = rx.try_recv() {
+            lines.push(line);
+        }
+
+        for content in batch(lines) {
+            post(&client, &url, &content).await;
+            tokio::time::sleep(MIN_POST_INTERVAL).await;
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, content: &str) {
+    // Names and roll reasons come from players, so "@everyone" mustn't ping anyone
+    let body = serde_json::json!({
+        "content": content,
+        "allowed_mentions": { "parse": [] },
+    })
+    .to_string();
+
+    for _ in 0..=MAX_RETRIES {
+        let result = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send()
+            .await;
+
+        match result {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let wait = retry_after(&resp).unwrap_or(MIN_POST_INTERVAL);
+                warn!("Discord asked to slow down, waiting {wait:?}");
+                tokio::time::sleep(wait).await;
+            }
+            Ok(resp) => {
+                warn!("Discord webhook refused the post: {}", resp.status());
+                return;
+            }
+            Err(e) => {
+                warn!("Could not post to the Discord webhook: {e}");
+                return;
+            }
+        }
+    }
+    warn!("Discord kept asking to slow down, dropping the post");
+}
+
+/// How long Discord wants us to wait, in seconds in the `Retry-After` header
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let secs: f64 = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Joins lines into as few messages as fit under Discord's length limit
+fn batch(lines: Vec<String>) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for line in lines {
+        let line: String = line.chars().take(MAX_MESSAGE_LEN).collect();
+        match messages.last_mut() {
+            Some(last) if last.len() + line.len() < MAX_MESSAGE_LEN => {
+                last.push('\n');
+                last.push_str(&line);
+            }
+            _ => messages.push(line),
+        }
+    }
+    messages
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use common::message::DndMessage;
+use message_io::network::Endpoint;
+
+/// How long to wait before telling the GM about the same client again
+const NOTIFY_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageCategory {
+    Board,
+    Chat,
+    Character,
+    Other,
+}
+
+impl MessageCategory {
+    pub fn of(message: &DndMessage) -> Self {
+        match message {
+            DndMessage::BoardMessage(_) => Self::Board,
+            DndMessage::Log(..) | DndMessage::Typing(..) => Self::Chat,
+            DndMessage::RetrieveCharacterData(_)
+            | DndMessage::RetrievePartyMember(_)
+            | DndMessage::UpdateItemCount(..)
+            | DndMessage::UpdateAbilityCount(..)
+            | DndMessage::UpdatePowerSlotCount(..)
+            | DndMessage::UpdateItemSlot(..)
+            | DndMessage::UpdateItemAttunement(..)
+            | DndMessage::UpdateCharacter(..)
+            | DndMessage::SpendInspiration(..)
+            | DndMessage::AdjustHp(..)
+            | DndMessage::ApplyAreaDamage(..) => Self::Character,
+            _ => Self::Other,
+        }
+    }
+
+    /// (burst size, messages refilled per second)
+    fn limits(&self) -> (f32, f32) {
+        match self {
+            // Dragging a piece sends a location update every few frames
+            Self::Board => (120.0, 60.0),
+            Self::Chat => (20.0, 3.0),
+            Self::Character => (30.0, 10.0),
+            Self::Other => (20.0, 5.0),
+        }
+    }
+}
+
+impl std::fmt::Display for MessageCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Board => write!(f, "board"),
+            Self::Chat => write!(f, "chat"),
+            Self::Character => write!(f, "character"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f32,
+    last_refill: Instant,
+}
+
+pub enum Throttle {
+    Allowed,
+    /// The message should be dropped. `notify` is set when the GM hasn't been told recently
+    Dropped {
+        notify: bool,
+    },
+}
+
+/// Token bucket per endpoint and message category
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: HashMap<(Endpoint, MessageCategory), TokenBucket>,
+    last_notified: HashMap<Endpoint, Instant>,
+}
+
+impl RateLimiter {
+    pub fn check(&mut self, endpoint: Endpoint, category: MessageCategory) -> Throttle {
+        let (burst, refill) = category.limits();
+        let now = Instant::now();
+
+        let bucket = self
+            .buckets
+            .entry((endpoint, category))
+            .or_insert(TokenBucket {
+                tokens: burst,
+                last_refill: now,
+            });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f32();
+        bucket.tokens = (bucket.tokens + elapsed * refill).min(burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Throttle::Allowed;
+        }
+
+        let notify = self
+            .last_notified
+            .get(&endpoint)
+            .is_none_or(|last| now.duration_since(*last) > NOTIFY_COOLDOWN);
+        if notify {
+            self.last_notified.insert(endpoint, now);
+        }
+
+        Throttle::Dropped { notify }
+    }
+
+    pub fn remove(&mut self, endpoint: Endpoint) {
+        self.buckets.retain(|(x, _), _| *x != endpoint);
+        self.last_notified.remove(&endpoint);
+    }
+}
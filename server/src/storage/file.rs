@@ -0,0 +1,448 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs, io,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+use serde_json::Value;
+
+use super::{Filter, Query, Storage, StorageFuture, Write};
+
+/// How rows point at another table, mirroring the foreign keys in the
+/// Supabase schema: (table, other table, column, other table's column)
+const FOREIGN_KEYS: [(&str, &str, &str, &str); 3] = [
+    ("inventory", "items", "item_id", "id"),
+    ("party_stash", "items", "item_id", "id"),
+    ("player_abilities", "abilities", "ability_name", "name"),
+];
+
+/// Tables where the DB would hand out the next number as the id
+const SERIAL_TABLES: [&str; 3] = ["items", "feedback", "board_journal"];
+
+/// Columns no two rows of a table can share, mirroring the primary keys and
+/// unique constraints in the Supabase schema. Upserts can only merge on one
+/// of the single column keys, the same as `on_conflict` there
+const UNIQUE_KEYS: [(&str, &[&str]); 17] = [
+    ("items", &["id"]),
+    ("items", &["name"]),
+    ("abilities", &["name"]),
+    ("character", &["name"]),
+    ("inventory", &["player", "item_id"]),
+    ("player_abilities", &["player", "ability_name"]),
+    ("party_stash", &["id"]),
+    ("handouts", &["id"]),
+    ("pinned_messages", &["id"]),
+    ("ruleset", &["id"]),
+    ("xp_table", &["id"]),
+    ("soundboard", &["id"]),
+    ("roll_tables", &["name"]),
+    ("piece_templates", &["name"]),
+    ("roll_stats", &["player"]),
+    ("snapshots", &["id"]),
+    ("feedback", &["id"]),
+];
+
+type Tables = HashMap<String, Vec<Value>>;
+
+/// The campaign as a JSON file per table, for running a server without a
+/// Supabase project. Tables are read the first time they're used and written
+/// back whole after every change
+pub struct FileStorage {
+    /// Nothing is read or written without one
+    dir: Option<PathBuf>,
+    tables: Mutex<Tables>,
+}
+
+impl FileStorage {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts out empty and forgets everything once dropped, for the tests
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            dir: None,
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn path(&self, table: &str) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{table}.json")))
+    }
+
+    fn load<'a>(&self, tables: &'a mut Tables, table: &str) -> Result<&'a mut Vec<Value>, String> {
+        if !tables.contains_key(table) {
+            let rows = match self.path(table).map(fs::read_to_string) {
+                Some(Ok(text)) => {
+                    serde_json::from_str(&text).map_err(|e| format!("{table}: {e}"))?
+                }
+                Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(format!("{table}: {e}"))
+                }
+                _ => Vec::new(),
+            };
+            tables.insert(table.to_owned(), rows);
+        }
+        Ok(tables.get_mut(table).unwrap())
+    }
+
+    fn save(&self, table: &str, rows: &[Value]) -> Result<(), String> {
+        self.save_tables([(table, rows)])
+    }
+
+    /// Writes next to the old files first so a crash can't leave half a table,
+    /// then swaps them all in once every table has been written
+    fn save_tables<'a>(
+        &self,
+        tables: impl IntoIterator<Item = (&'a str, &'a [Value])>,
+    ) -> Result<(), String> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let mut written = Vec::new();
+        for (table, rows) in tables {
+            let path = dir.join(format!("{table}.json"));
+            let temp = path.with_extension("json.tmp");
+            let write = || {
+                fs::create_dir_all(dir)?;
+                fs::write(&temp, serde_json::to_string_pretty(rows)?)
+            };
+            write().map_err(|e: io::Error| format!("Saving {table}: {e}"))?;
+            written.push((table, temp, path));
+        }
+
+        for (table, temp, path) in written {
+            fs::rename(temp, path).map_err(|e| format!("Saving {table}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    fn select_rows(&self, query: Query) -> Result<Vec<Value>, String> {
+        let mut tables = self.lock();
+        let columns: Vec<_> = query.columns.split(',').map(str::trim).collect();
+        let mut rows: Vec<Value> = self
+            .load(&mut tables, &query.table)?
+            .iter()
+            .filter(|row| matches(row, &query.filters))
+            .cloned()
+            .collect();
+
+        if let Some((column, desc)) = &query.order {
+            rows.sort_by(|a, b| {
+                let ordering = compare(&a[column], &b[column]);
+                if *desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+
+        let embedded = columns.iter().filter_map(|x| x.strip_suffix("(*)"));
+        for other in embedded {
+            let Some((_, _, column, other_column)) = FOREIGN_KEYS
+                .iter()
+                .find(|(table, x, _, _)| *table == query.table && *x == other)
+            else {
+                return Err(format!("{} has no link to {other}", query.table));
+            };
+
+            let other_rows = self.load(&mut tables, other)?;
+            for row in rows.iter_mut() {
+                let linked = other_rows
+                    .iter()
+                    .find(|x| x[other_column] == row[column])
+                    .cloned()
+                    .unwrap_or_default();
+                row[other] = linked;
+            }
+        }
+
+        // Only what was asked for comes back, the same as PostgREST
+        if !columns.contains(&"*") {
+            for row in rows.iter_mut().filter_map(Value::as_object_mut) {
+                row.retain(|column, _| {
+                    columns
+                        .iter()
+                        .any(|&x| x.strip_suffix("(*)").unwrap_or(x) == column.as_str())
+                });
+            }
+        }
+
+        Ok(rows)
+    }
+
+    fn insert_rows(&self, table: &str, rows: Value) -> Result<Vec<Value>, String> {
+        self.upsert_rows(table, rows, None)
+    }
+
+    fn upsert_rows(
+        &self,
+        table: &str,
+        rows: Value,
+        key: Option<&str>,
+    ) -> Result<Vec<Value>, String> {
+        let mut tables = self.lock();
+        let existing = self.load(&mut tables, table)?;
+
+        // Nothing is kept if any of the rows clash
+        let mut changed = existing.clone();
+        let saved = upsert_into(&mut changed, table, rows, key)?;
+        self.save(table, &changed)?;
+        *existing = changed;
+        Ok(saved)
+    }
+
+    fn update_rows(&self, query: Query, changes: Value) -> Result<Vec<Value>, String> {
+        let mut tables = self.lock();
+        let rows = self.load(&mut tables, &query.table)?;
+
+        let mut changed = rows.clone();
+        let updated = update_in(&mut changed, &query.table, &query.filters, changes)?;
+        if !updated.is_empty() {
+            self.save(&query.table, &changed)?;
+            *rows = changed;
+        }
+        Ok(updated)
+    }
+
+    fn delete_rows(&self, query: Query) -> Result<(), String> {
+        let mut tables = self.lock();
+        let rows = self.load(&mut tables, &query.table)?;
+
+        if delete_from(rows, &query.filters) {
+            self.save(&query.table, rows)?;
+        }
+        Ok(())
+    }
+
+    /// The writes are made to copies of the tables, which only replace them
+    /// once every write has worked
+    fn transaction_rows(&self, writes: Vec<Write>) -> Result<(), String> {
+        let mut tables = self.lock();
+
+        let mut changed = Tables::new();
+        for write in writes {
+            let table = write.table().to_owned();
+            if !changed.contains_key(&table) {
+                let rows = self.load(&mut tables, &table)?.clone();
+                changed.insert(table.clone(), rows);
+            }
+            let rows = changed.get_mut(&table).unwrap();
+
+            match write {
+                Write::Insert { rows: new, .. } => {
+                    upsert_into(rows, &table, new, None)?;
+                }
+                Write::Upsert { rows: new, key, .. } => {
+                    upsert_into(rows, &table, new, Some(&key))?;
+                }
+                Write::Update { query, changes } => {
+                    update_in(rows, &table, &query.filters, changes)?;
+                }
+                Write::Delete(query) => {
+                    delete_from(rows, &query.filters);
+                }
+                // New ids already come after the biggest one in the table
+                Write::ResetIdentity { .. } => {}
+            }
+        }
+
+        self.save_tables(changed.iter().map(|(k, v)| (k.as_str(), v.as_slice())))?;
+        tables.extend(changed);
+        Ok(())
+    }
+}
+
+/// Adds the rows, merging them into any with the same `key`. Hands back the
+/// rows as saved, or which unique key they'd clash on. `existing` may be left
+/// half changed on an error
+fn upsert_into(
+    existing: &mut Vec<Value>,
+    table: &str,
+    rows: Value,
+    key: Option<&str>,
+) -> Result<Vec<Value>, String> {
+    if let Some(key) = key {
+        let unique = UNIQUE_KEYS
+            .iter()
+            .any(|(x, columns)| *x == table && *columns == [key]);
+        if !unique {
+            return Err(format!("{table} has no unique key on {key} to upsert with"));
+        }
+    }
+
+    let rows = match rows {
+        Value::Array(rows) => rows,
+        row => vec![row],
+    };
+
+    let mut saved = Vec::new();
+    for mut row in rows {
+        let replaces = key.and_then(|key| {
+            existing
+                .iter()
+                .position(|x| !row[key].is_null() && x[key] == row[key])
+        });
+
+        let index = match replaces {
+            Some(index) => {
+                merge(&mut existing[index], row);
+                index
+            }
+            None => {
+                if SERIAL_TABLES.contains(&table) && row["id"].is_null() {
+                    row["id"] = next_id(existing).into();
+                }
+                existing.push(row);
+                existing.len() - 1
+            }
+        };
+
+        check_unique(existing, table, index)?;
+        saved.push(existing[index].clone());
+    }
+    Ok(saved)
+}
+
+/// Whether the row at `index` shares a unique key with any other row
+fn check_unique(rows: &[Value], table: &str, index: usize) -> Result<(), String> {
+    let row = &rows[index];
+    let unique = UNIQUE_KEYS.iter().filter(|(x, _)| *x == table);
+    for (_, columns) in unique {
+        let clashes = rows.iter().enumerate().any(|(i, other)| {
+            i != index
+                && columns
+                    .iter()
+                    .all(|&x| !row[x].is_null() && row[x] == other[x])
+        });
+        if clashes {
+            return Err(format!(
+                "{table} already has a row with the same {}",
+                columns.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Hands back the changed rows, or which unique key they'd clash on. `rows`
+/// may be left half changed on an error
+fn update_in(
+    rows: &mut [Value],
+    table: &str,
+    filters: &[Filter],
+    changes: Value,
+) -> Result<Vec<Value>, String> {
+    let mut changed = Vec::new();
+    for (index, row) in rows.iter_mut().enumerate() {
+        if matches(row, filters) {
+            merge(row, changes.clone());
+            changed.push(index);
+        }
+    }
+
+    let mut updated = Vec::new();
+    for index in changed {
+        check_unique(rows, table, index)?;
+        updated.push(rows[index].clone());
+    }
+    Ok(updated)
+}
+
+/// Whether anything was deleted
+fn delete_from(rows: &mut Vec<Value>, filters: &[Filter]) -> bool {
+    let count = rows.len();
+    rows.retain(|x| !matches(x, filters));
+    rows.len() != count
+}
+
+impl Storage for FileStorage {
+    fn select(&self, query: Query) -> StorageFuture<'_, Vec<Value>> {
+        Box::pin(futures::future::ready(self.select_rows(query)))
+    }
+
+    fn insert(&self, table: &str, rows: Value) -> StorageFuture<'_, ()> {
+        let result = self.insert_rows(table, rows).map(|_| ());
+        Box::pin(futures::future::ready(result))
+    }
+
+    fn upsert(&self, table: &str, rows: Value, key: &str) -> StorageFuture<'_, Vec<Value>> {
+        Box::pin(futures::future::ready(self.upsert_rows(
+            table,
+            rows,
+            Some(key),
+        )))
+    }
+
+    fn update(&self, query: Query, changes: Value) -> StorageFuture<'_, Vec<Value>> {
+        Box::pin(futures::future::ready(self.update_rows(query, changes)))
+    }
+
+    fn delete(&self, query: Query) -> StorageFuture<'_, ()> {
+        Box::pin(futures::future::ready(self.delete_rows(query)))
+    }
+
+    fn transaction(&self, writes: Vec<Write>) -> StorageFuture<'_, ()> {
+        Box::pin(futures::future::ready(self.transaction_rows(writes)))
+    }
+}
+
+/// Values are compared the way they'd be written in a PostgREST filter
+fn as_filter_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(x) => Some(x.clone()),
+        x => Some(x.to_string()),
+    }
+}
+
+fn matches(row: &Value, filters: &[Filter]) -> bool {
+    filters.iter().all(|filter| match filter {
+        Filter::Eq(column, value) => as_filter_value(&row[column]).as_ref() == Some(value),
+        Filter::NotNull(column) => !row[column].is_null(),
+    })
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        // Nulls go last, like they do in Postgres
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => Ordering::Equal,
+    }
+}
+
+/// Only the given fields change, like a PostgREST update
+fn merge(row: &mut Value, changes: Value) {
+    match (row, changes) {
+        (Value::Object(row), Value::Object(changes)) => row.extend(changes),
+        (row, changes) => *row = changes,
+    }
+}
+
+fn next_id(rows: &[Value]) -> i64 {
+    rows.iter()
+        .filter_map(|x| x["id"].as_i64())
+        .max()
+        .unwrap_or_default()
+        + 1
+}
@@ -0,0 +1,186 @@
+//! Where the campaign is kept. Everything goes through [`Storage`] as rows of
+//! JSON in named tables, so the same queries work against Supabase or a
+//! folder of files on the machine running the server
+
+use std::{path::PathBuf, sync::Arc};
+
+use futures::future::BoxFuture;
+use log::info;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+mod file;
+mod postgrest;
+
+pub use self::file::FileStorage;
+pub use self::postgrest::PostgrestStorage;
+
+pub type StorageFuture<'a, T> = BoxFuture<'a, Result<T, String>>;
+
+/// Folder the file backend uses when `DND_DATA_DIR` isn't set
+const DEFAULT_DATA_DIR: &str = "data";
+
+pub trait Storage: Send + Sync {
+    /// Rows matching the query, any tables named in its columns like
+    /// `items(*)` are nested in each row under that name
+    fn select(&self, query: Query) -> StorageFuture<'_, Vec<Value>>;
+
+    /// Adds a row, or an array of rows
+    fn insert(&self, table: &str, rows: Value) -> StorageFuture<'_, ()>;
+
+    /// Adds rows, replacing any that have the same `key`. Hands back the
+    /// rows as saved, with any ids they were given
+    fn upsert(&self, table: &str, rows: Value, key: &str) -> StorageFuture<'_, Vec<Value>>;
+
+    /// Sets the fields in `changes` on every row matching the query, handing
+    /// back the rows that were changed
+    fn update(&self, query: Query, changes: Value) -> StorageFuture<'_, Vec<Value>>;
+
+    fn delete(&self, query: Query) -> StorageFuture<'_, ()>;
+
+    /// Makes every write or, if any of them fails, none of them
+    fn transaction(&self, writes: Vec<Write>) -> StorageFuture<'_, ()>;
+}
+
+/// One change made as part of a [`Storage::transaction`]
+#[derive(Clone, Debug)]
+pub enum Write {
+    Insert {
+        table: String,
+        rows: Value,
+    },
+    Upsert {
+        table: String,
+        rows: Value,
+        key: String,
+    },
+    Update {
+        query: Query,
+        changes: Value,
+    },
+    Delete(Query),
+    /// Moves the table's id sequence past the ids it has, for after rows were
+    /// added with ids of their own
+    ResetIdentity {
+        table: String,
+        column: String,
+    },
+}
+
+impl Write {
+    pub fn insert(table: impl Into<String>, rows: Value) -> Self {
+        Self::Insert {
+            table: table.into(),
+            rows,
+        }
+    }
+
+    pub fn upsert(table: impl Into<String>, rows: Value, key: impl Into<String>) -> Self {
+        Self::Upsert {
+            table: table.into(),
+            rows,
+            key: key.into(),
+        }
+    }
+
+    pub fn update(query: Query, changes: Value) -> Self {
+        Self::Update { query, changes }
+    }
+
+    pub fn delete(query: Query) -> Self {
+        Self::Delete(query)
+    }
+
+    pub fn reset_identity(table: impl Into<String>, column: impl Into<String>) -> Self {
+        Self::ResetIdentity {
+            table: table.into(),
+            column: column.into(),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        match self {
+            Self::Insert { table, .. }
+            | Self::Upsert { table, .. }
+            | Self::ResetIdentity { table, .. } => table,
+            Self::Update { query, .. } | Self::Delete(query) => &query.table,
+        }
+    }
+}
+
+/// Picks the backend from `DND_STORAGE`. Supabase is used unless it's set to
+/// `file`, which keeps everything under `DND_DATA_DIR`
+pub fn from_env() -> Arc<dyn Storage> {
+    match dotenv::var("DND_STORAGE").as_deref() {
+        Ok("file") => {
+            let dir = dotenv::var("DND_DATA_DIR").unwrap_or_else(|_| DEFAULT_DATA_DIR.to_owned());
+            info!("Storing the campaign in '{dir}'");
+            Arc::new(FileStorage::new(PathBuf::from(dir)))
+        }
+        _ => {
+            let url = dotenv::var("NEXT_PUBLIC_SUPABASE_URL").unwrap();
+            let key = dotenv::var("NEXT_PUBLIC_SUPABASE_ANON_KEY").unwrap();
+            info!("Storing the campaign in Supabase");
+            Arc::new(PostgrestStorage::new(url, key))
+        }
+    }
+}
+
+/// Turns rows from [`Storage::select`] into the type they're stored as
+pub fn parse_rows<T: DeserializeOwned>(rows: Vec<Value>) -> Result<Vec<T>, String> {
+    serde_json::from_value(Value::Array(rows)).map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Debug)]
+pub enum Filter {
+    Eq(String, String),
+    NotNull(String),
+}
+
+/// Which rows of a table to read, update or delete. Reads every column of
+/// every row unless narrowed down
+#[derive(Clone, Debug)]
+pub struct Query {
+    table: String,
+    columns: String,
+    filters: Vec<Filter>,
+    /// Column to sort by and whether it's descending
+    order: Option<(String, bool)>,
+}
+
+impl Query {
+    pub fn table(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            columns: "*".to_owned(),
+            filters: Vec::new(),
+            order: None,
+        }
+    }
+
+    pub fn select(mut self, columns: impl Into<String>) -> Self {
+        self.columns = columns.into();
+        self
+    }
+
+    pub fn eq(mut self, column: impl Into<String>, value: impl ToString) -> Self {
+        self.filters
+            .push(Filter::Eq(column.into(), value.to_string()));
+        self
+    }
+
+    pub fn not_null(mut self, column: impl Into<String>) -> Self {
+        self.filters.push(Filter::NotNull(column.into()));
+        self
+    }
+
+    pub fn order(mut self, column: impl Into<String>) -> Self {
+        self.order = Some((column.into(), false));
+        self
+    }
+
+    pub fn order_desc(mut self, column: impl Into<String>) -> Self {
+        self.order = Some((column.into(), true));
+        self
+    }
+}
@@ -0,0 +1,127 @@
+use ::postgrest::{Builder, Postgrest};
+use serde_json::{json, Value};
+
+use super::{Filter, Query, Storage, StorageFuture, Write};
+
+/// The campaign as tables in Supabase, reached through its PostgREST API.
+/// Transactions need the functions in `server/sql/transactions.sql`
+pub struct PostgrestStorage {
+    db: Postgrest,
+}
+
+impl PostgrestStorage {
+    pub fn new(url: String, key: String) -> Self {
+        Self {
+            db: Postgrest::new(url).insert_header("apikey", key),
+        }
+    }
+
+    fn builder(&self, query: &Query) -> Builder {
+        let mut builder = self.db.from(&query.table);
+        for filter in query.filters.iter() {
+            builder = match filter {
+                Filter::Eq(column, value) => builder.eq(column, value),
+                Filter::NotNull(column) => builder.not("is", column, "null"),
+            };
+        }
+        builder
+    }
+}
+
+/// Runs a query against the DB, failed requests and error statuses both become
+/// an `Err`. Hands back the response body
+async fn execute_query(query: Builder) -> Result<String, String> {
+    let resp = query.execute().await.map_err(|e| e.to_string())?;
+    let success = resp.status().is_success();
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+
+    if success {
+        Ok(text)
+    } else {
+        Err(text)
+    }
+}
+
+async fn execute_rows(query: Builder) -> Result<Vec<Value>, String> {
+    let text = execute_query(query).await?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+impl Storage for PostgrestStorage {
+    fn select(&self, query: Query) -> StorageFuture<'_, Vec<Value>> {
+        let mut builder = self.builder(&query).select(&query.columns);
+        if let Some((column, desc)) = &query.order {
+            let direction = if *desc { "desc" } else { "asc" };
+            builder = builder.order(format!("{column}.{direction}"));
+        }
+        Box::pin(execute_rows(builder))
+    }
+
+    fn insert(&self, table: &str, rows: Value) -> StorageFuture<'_, ()> {
+        let builder = self.db.from(table).insert(rows.to_string());
+        Box::pin(async { execute_query(builder).await.map(|_| ()) })
+    }
+
+    fn upsert(&self, table: &str, rows: Value, key: &str) -> StorageFuture<'_, Vec<Value>> {
+        let builder = self
+            .db
+            .from(table)
+            .upsert(rows.to_string())
+            .on_conflict(key);
+        Box::pin(execute_rows(builder))
+    }
+
+    fn update(&self, query: Query, changes: Value) -> StorageFuture<'_, Vec<Value>> {
+        let builder = self.builder(&query).update(changes.to_string());
+        Box::pin(execute_rows(builder))
+    }
+
+    fn delete(&self, query: Query) -> StorageFuture<'_, ()> {
+        let builder = self.builder(&query).delete();
+        Box::pin(async { execute_query(builder).await.map(|_| ()) })
+    }
+
+    fn transaction(&self, writes: Vec<Write>) -> StorageFuture<'_, ()> {
+        let writes: Vec<_> = writes.iter().map(write_json).collect();
+        let builder = self
+            .db
+            .rpc("apply_writes", json!({ "writes": writes }).to_string());
+        Box::pin(async { execute_query(builder).await.map(|_| ()) })
+    }
+}
+
+/// A write the way `apply_writes` reads it. Not null filters are sent
+/// without a value
+fn write_json(write: &Write) -> Value {
+    let filters = |query: &Query| -> Vec<Value> {
+        query
+            .filters
+            .iter()
+            .map(|filter| match filter {
+                Filter::Eq(column, value) => json!([column, value]),
+                Filter::NotNull(column) => json!([column, null]),
+            })
+            .collect()
+    };
+
+    match write {
+        Write::Insert { table, rows } => json!({ "op": "insert", "table": table, "rows": rows }),
+        Write::Upsert { table, rows, key } => {
+            json!({ "op": "upsert", "table": table, "rows": rows, "key": key })
+        }
+        Write::Update { query, changes } => json!({
+            "op": "update",
+            "table": query.table,
+            "filters": filters(query),
+            "changes": changes,
+        }),
+        Write::Delete(query) => json!({
+            "op": "delete",
+            "table": query.table,
+            "filters": filters(query),
+        }),
+        Write::ResetIdentity { table, column } => {
+            json!({ "op": "reset_identity", "table": table, "key": column })
+        }
+    }
+}
@@ -0,0 +1,1199 @@
+use common::{
+    message::{
+        BoardMessage, DieKind, DieRoll, DndMessage, EffectMessage, JournalMessage, LogMessage,
+        PinMessage, PinnedMessage, RollVisibility, SessionClockMessage, SnapshotMessage,
+        SoundMessage, TradeMessage, MANUAL_SAVE_TAG,
+    },
+    ruleset::Ruleset,
+    soundboard::{NowPlaying, Playlist, SoundTrack, Soundboard},
+    Annotation, AnnotationShape, Character, CharacterChange, Cooldown, DndPlayerPiece,
+    EffectTarget, Item, LifeState, PieceVisibility, Recharge, RollTable, SortingLayer, TableEntry,
+    XpTable, MAIN_BOARD,
+};
+
+use chrono::Utc;
+use futures::executor::block_on;
+use serde_json::json;
+use std::{net::SocketAddr, time::Duration};
+
+use super::{TestClient, TestServer, GM};
+use crate::{storage::Query, EmbeddedServer};
+
+fn piece(name: &str) -> DndPlayerPiece {
+    DndPlayerPiece {
+        name: name.to_owned(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn joining_is_announced_to_everyone_already_there() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    let _bob = server.join("Bob");
+
+    let joined = alice.expect("Bob joining", |msg| match msg {
+        DndMessage::UserNotificationAdded(name) => Some(name),
+        _ => None,
+    });
+    assert_eq!(joined, "Bob");
+
+    let logged = alice.expect("the join log", |msg| match msg {
+        DndMessage::Log(_, LogMessage::Joined(name), _) => Some(name),
+        _ => None,
+    });
+    assert_eq!(logged, "Bob");
+}
+
+#[test]
+fn new_players_are_told_who_is_here() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    let alice = server.join("Alice");
+    let list = alice.expect("the user list", |msg| match msg {
+        DndMessage::UserList(list) => Some(list),
+        _ => None,
+    });
+    assert_eq!(list, vec![GM.to_owned()]);
+
+    let gm_name = alice.expect("who the GM is", |msg| match msg {
+        DndMessage::GameMaster(name) => Some(name),
+        _ => None,
+    });
+    assert_eq!(gm_name, GM);
+}
+
+#[test]
+fn board_updates_are_broadcast_to_other_players() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        piece("Goblin"),
+    )));
+
+    let added = alice.expect("the new piece", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, piece)) if id == uuid => {
+            Some(piece)
+        }
+        _ => None,
+    });
+    assert_eq!(added.name, "Goblin");
+    // The sender already has the change, it isn't echoed back
+    gm.expect_none("its own piece back", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(..))
+        )
+    });
+}
+
+#[test]
+fn hidden_pieces_are_kept_from_players() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let hidden = DndPlayerPiece {
+        visibility: PieceVisibility::GmOnly,
+        ..piece("Assassin")
+    };
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid::Uuid::new_v4(),
+        hidden,
+    )));
+
+    alice.expect_none("a GM only piece", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(..))
+        )
+    });
+}
+
+fn annotation(text: &str, visible_by: &[&str]) -> Annotation {
+    Annotation {
+        shape: AnnotationShape::Text(Default::default(), text.to_owned()),
+        color: [255; 4],
+        width: 1.0,
+        layer: SortingLayer(1),
+        visible_by: visible_by.iter().map(|x| x.to_string()).collect(),
+        board: MAIN_BOARD,
+        author: String::new(),
+    }
+}
+
+#[test]
+fn hidden_annotations_are_kept_from_players() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddAnnotation(
+        uuid::Uuid::new_v4(),
+        annotation("Trap", &[GM]),
+    )));
+    alice.expect_none("a GM only annotation", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::AddAnnotation(..))
+        )
+    });
+
+    let bob = server.join("Bob");
+    bob.expect_none("a GM only annotation", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::AddAnnotation(..))
+        )
+    });
+}
+
+#[test]
+fn players_can_only_erase_their_own_annotations() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let gms = uuid::Uuid::new_v4();
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddAnnotation(
+        gms,
+        annotation("Here be dragons", &[]),
+    )));
+    alice.expect("the GM's annotation", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddAnnotation(..)) => Some(()),
+        _ => None,
+    });
+
+    alice.send(DndMessage::BoardMessage(BoardMessage::DeleteAnnotation(
+        gms,
+    )));
+    let restored = alice.expect("the annotation put back", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddAnnotation(id, _)) => Some(id),
+        _ => None,
+    });
+    assert_eq!(restored, gms);
+    gm.expect_none("the annotation erased", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::DeleteAnnotation(_))
+        )
+    });
+
+    let alices = uuid::Uuid::new_v4();
+    alice.send(DndMessage::BoardMessage(BoardMessage::AddAnnotation(
+        alices,
+        annotation("Shortcut", &[]),
+    )));
+    let author = gm.expect("Alice's annotation", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddAnnotation(_, x)) => Some(x.author),
+        _ => None,
+    });
+    assert_eq!(author, "Alice");
+
+    // Clearing the layer only takes what Alice drew
+    alice.send(DndMessage::BoardMessage(BoardMessage::ClearAnnotations(
+        MAIN_BOARD,
+        SortingLayer(1),
+    )));
+    let erased = gm.expect("Alice's annotation erased", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::DeleteAnnotation(id)) => Some(id),
+        _ => None,
+    });
+    assert_eq!(erased, alices);
+    let restored = alice.expect("the GM's annotation put back", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddAnnotation(id, _)) => Some(id),
+        _ => None,
+    });
+    assert_eq!(restored, gms);
+}
+
+#[test]
+fn players_cant_add_pieces_they_dont_own() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    alice.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        piece("Dragon"),
+    )));
+
+    let removed = alice.expect("the piece taken back", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::DeletePlayerPiece(id)) => Some(id),
+        _ => None,
+    });
+    assert_eq!(removed, uuid);
+    gm.expect_none("the rejected piece", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(..))
+        )
+    });
+}
+
+#[test]
+fn players_cant_replace_pieces_they_dont_own() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        piece("Goblin"),
+    )));
+    alice.expect("the goblin", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, _)) if id == uuid => Some(()),
+        _ => None,
+    });
+
+    let mut stolen = piece("Goblin");
+    stolen.owners = vec!["Alice".to_owned()];
+    alice.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid, stolen,
+    )));
+
+    let corrected = alice.expect("the goblin put back", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(id, piece)) if id == uuid => {
+            Some(piece)
+        }
+        _ => None,
+    });
+    assert!(corrected.owners.is_empty());
+    gm.expect_none("the stolen goblin", |msg| {
+        matches!(
+            msg,
+            DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(..))
+        )
+    });
+}
+
+#[test]
+fn players_cant_change_their_tokens_vision() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    let mut token = piece("Alice");
+    token.owners = vec!["Alice".to_owned()];
+    token.vision = Some(12.0);
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        token.clone(),
+    )));
+    alice.expect("her token", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, _)) if id == uuid => Some(()),
+        _ => None,
+    });
+
+    token.vision = None;
+    alice.send(DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(
+        uuid, token,
+    )));
+
+    let corrected = alice.expect("her vision put back", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(id, piece)) if id == uuid => {
+            Some(piece)
+        }
+        _ => None,
+    });
+    assert_eq!(corrected.vision, Some(12.0));
+}
+
+#[test]
+fn the_gm_can_break_holds() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    let mut token = piece("Alice");
+    token.owners = vec!["Alice".to_owned()];
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid, token,
+    )));
+    alice.expect("her token", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, _)) if id == uuid => Some(()),
+        _ => None,
+    });
+
+    alice.send(DndMessage::BoardMessage(BoardMessage::HoldPiece(uuid)));
+    gm.expect("alice holding her token", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::SetHolder(id, Some(_))) if id == uuid => Some(()),
+        _ => None,
+    });
+
+    gm.send(DndMessage::BoardMessage(BoardMessage::ReleasePiece(uuid)));
+    alice.expect("the hold broken", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::SetHolder(id, None)) if id == uuid => Some(()),
+        _ => None,
+    });
+}
+
+#[test]
+fn late_joiners_get_the_board_as_it_is() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        piece("Goblin"),
+    )));
+    gm.settle();
+
+    let alice = server.join("Alice");
+    let existing = alice.expect("the existing piece", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, piece)) if id == uuid => {
+            Some(piece)
+        }
+        _ => None,
+    });
+    assert_eq!(existing.name, "Goblin");
+}
+
+#[test]
+fn roll_tables_are_loaded_again_after_a_restart() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    let table = RollTable {
+        name: "Loot".to_owned(),
+        entries: vec![TableEntry {
+            weight: 1,
+            text: "1d4 blue potions".to_owned(),
+            item: Some("Blue potion".to_owned()),
+        }],
+        add_to_stash: true,
+    };
+    gm.send(DndMessage::SaveRollTable(table.clone()));
+    gm.settle();
+    drop(gm);
+
+    let server = server.restart();
+    let alice = server.join("Alice");
+    let tables = alice.expect("the saved roll tables", |msg| match msg {
+        DndMessage::RollTables(tables) => Some(tables),
+        _ => None,
+    });
+    assert_eq!(tables, vec![table]);
+}
+
+#[test]
+fn the_xp_table_is_loaded_again_after_a_restart() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    let table = XpTable(vec![0, 100, 250]);
+    gm.send(DndMessage::SetXpTable(table.clone()));
+    gm.settle();
+    drop(gm);
+
+    let server = server.restart();
+    let alice = server.join("Alice");
+    let saved = alice.expect("the saved XP table", |msg| match msg {
+        DndMessage::SetXpTable(table) => Some(table),
+        _ => None,
+    });
+    assert_eq!(saved, table);
+}
+
+#[test]
+fn the_board_is_rebuilt_from_the_journal_after_a_restart() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        piece("Goblin"),
+    )));
+    gm.send(DndMessage::BoardMessage(BoardMessage::UpdatePlayerPiece(
+        uuid,
+        piece("Hobgoblin"),
+    )));
+    gm.settle();
+    drop(gm);
+
+    let server = server.restart();
+    let gm = server.join(GM);
+    let goblin = gm.expect("the goblin", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, piece)) if id == uuid => {
+            Some(piece)
+        }
+        _ => None,
+    });
+    assert_eq!(goblin.name, "Hobgoblin");
+
+    gm.send(DndMessage::JournalMessage(JournalMessage::Request(1)));
+    let (start, entries, total) = gm.expect("the second page", |msg| match msg {
+        DndMessage::JournalMessage(JournalMessage::Entries(start, entries, total)) => {
+            Some((start, entries, total))
+        }
+        _ => None,
+    });
+    assert_eq!((start, entries.len(), total), (1, 1, 2));
+}
+
+#[test]
+fn character_changes_are_saved() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    alice.send(DndMessage::CreateCharacter(Character {
+        name: "Alice".to_owned(),
+        ..Default::default()
+    }));
+    let created = alice.expect("the new sheet", |msg| match msg {
+        DndMessage::CharacterData(character) => Some(character),
+        _ => None,
+    });
+    assert_eq!(created.version, 0);
+
+    alice.send(DndMessage::UpdateCharacter(
+        alice.user(),
+        0,
+        CharacterChange::MaxHp(30),
+    ));
+    alice.settle();
+
+    alice.send(DndMessage::RetrieveCharacterData(alice.user()));
+    let saved = alice.expect("the saved sheet", |msg| match msg {
+        DndMessage::CharacterData(character) => Some(character),
+        _ => None,
+    });
+    assert_eq!(saved.max_hp, 30);
+    assert_eq!(saved.version, 1);
+}
+
+#[test]
+fn stale_character_changes_are_sent_back_as_conflicts() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    alice.send(DndMessage::CreateCharacter(Character {
+        name: "Alice".to_owned(),
+        version: 3,
+        ..Default::default()
+    }));
+    alice.settle();
+
+    alice.send(DndMessage::UpdateCharacter(
+        alice.user(),
+        2,
+        CharacterChange::Level(2),
+    ));
+    let (current, change) = alice.expect("the conflict", |msg| match msg {
+        DndMessage::CharacterConflict(character, change) => Some((character, change)),
+        _ => None,
+    });
+    assert_eq!(current.version, 3);
+    assert_eq!(change, CharacterChange::Level(2));
+}
+
+#[test]
+fn deleting_a_character_removes_their_items_and_abilities() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    gm.send(DndMessage::CreateCharacter(Character {
+        name: "Alice".to_owned(),
+        ..Default::default()
+    }));
+    gm.settle();
+    let rows = [
+        (
+            "inventory",
+            json!({ "player": "Alice", "item_id": 1, "count": 2 }),
+        ),
+        (
+            "player_abilities",
+            json!({ "player": "Alice", "ability_name": "Rage", "uses": 1 }),
+        ),
+    ];
+    for (table, row) in rows {
+        block_on(server.db.insert(table, row)).unwrap();
+    }
+
+    gm.send(DndMessage::DeleteCharacter("Alice".to_owned()));
+    gm.expect("the new roster", |msg| match msg {
+        DndMessage::CharacterRoster(list) => list.is_empty().then_some(()),
+        _ => None,
+    });
+
+    for table in ["inventory", "player_abilities"] {
+        let left = block_on(server.db.select(Query::table(table))).unwrap();
+        assert!(left.is_empty(), "{table} still has {left:?}");
+    }
+}
+
+#[test]
+fn players_cant_import_items_missing_from_the_catalog() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    let sword = Item {
+        id: 0,
+        count: 1,
+        name: "Vorpal Sword".to_owned(),
+        description: "Snicker-snack".to_owned(),
+        flavor_text: String::new(),
+        quest_item: false,
+        slot: None,
+        armor_class: None,
+        attack_bonus: Some(3),
+        requires_attunement: true,
+        attuned: false,
+        weight: Some(3.0),
+    };
+    alice.send(DndMessage::ImportCharacter(
+        Character {
+            name: "Alice".to_owned(),
+            ..Default::default()
+        },
+        vec![sword],
+        vec![],
+    ));
+
+    let message = alice.expect("the import failing", |msg| match msg {
+        DndMessage::Error { message, .. } => Some(message),
+        _ => None,
+    });
+    assert!(message.contains("Vorpal Sword"), "{message}");
+
+    for table in ["items", "character", "inventory"] {
+        let rows = block_on(server.db.select(Query::table(table))).unwrap();
+        assert!(rows.is_empty(), "{table} has {rows:?}");
+    }
+}
+
+#[test]
+fn attuning_twice_in_a_row_stays_within_the_limit() {
+    let server = TestServer::start();
+    let items = json!([
+        { "id": 1, "name": "Ring", "description": "", "flavor_text": "", "quest_item": false },
+        { "id": 2, "name": "Amulet", "description": "", "flavor_text": "", "quest_item": false },
+    ]);
+    let inventory = json!([
+        { "player": "Alice", "item_id": 1, "count": 1, "attuned": false },
+        { "player": "Alice", "item_id": 2, "count": 1, "attuned": false },
+    ]);
+    block_on(server.db.insert("items", items)).unwrap();
+    block_on(server.db.insert("inventory", inventory)).unwrap();
+
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    gm.send(DndMessage::SetRuleset(Ruleset {
+        attunement_limit: 1,
+        enforce_attunement_limit: true,
+        ..Default::default()
+    }));
+    gm.settle();
+
+    alice.send(DndMessage::UpdateItemAttunement(alice.user(), 1, true));
+    alice.send(DndMessage::UpdateItemAttunement(alice.user(), 2, true));
+    alice.expect("the second attune refused", |msg| match msg {
+        DndMessage::Error { .. } => Some(()),
+        _ => None,
+    });
+
+    let attuned = block_on(
+        server
+            .db
+            .select(Query::table("inventory").eq("attuned", true)),
+    )
+    .unwrap();
+    assert_eq!(attuned.len(), 1, "{attuned:?}");
+}
+
+fn item(id: i64, name: &str, count: u32) -> Item {
+    Item {
+        id,
+        count,
+        name: name.to_owned(),
+        description: String::new(),
+        flavor_text: String::new(),
+        quest_item: false,
+        slot: None,
+        armor_class: None,
+        attack_bonus: None,
+        requires_attunement: false,
+        attuned: false,
+        weight: None,
+    }
+}
+
+#[test]
+fn trades_swap_the_offered_items() {
+    let server = TestServer::start();
+    let items = json!([
+        { "id": 1, "name": "Gold", "description": "", "flavor_text": "", "quest_item": false },
+        { "id": 2, "name": "Sword", "description": "", "flavor_text": "", "quest_item": false },
+    ]);
+    let inventory = json!([
+        { "player": "Alice", "item_id": 1, "count": 10 },
+        { "player": "Alice", "item_id": 2, "count": 1 },
+        { "player": "Bob", "item_id": 1, "count": 5 },
+    ]);
+    block_on(server.db.insert("items", items)).unwrap();
+    block_on(server.db.insert("inventory", inventory)).unwrap();
+
+    let alice = server.join("Alice");
+    let bob = server.join("Bob");
+    alice.settle();
+    bob.settle();
+
+    alice.send(DndMessage::TradeMessage(TradeMessage::Request(
+        "Bob".to_owned(),
+    )));
+    let uuid = bob.expect("the trade opening", |msg| match msg {
+        DndMessage::TradeMessage(TradeMessage::Update(uuid, _)) => Some(uuid),
+        _ => None,
+    });
+
+    let offers = [
+        (&alice, vec![item(1, "Gold", 3), item(2, "Sword", 1)]),
+        (&bob, vec![item(1, "Gold", 2)]),
+    ];
+    for (client, offer) in offers {
+        client.send(DndMessage::TradeMessage(TradeMessage::SetOffer(
+            uuid, offer,
+        )));
+        client.settle();
+    }
+    alice.send(DndMessage::TradeMessage(TradeMessage::Confirm(uuid)));
+    alice.settle();
+    bob.send(DndMessage::TradeMessage(TradeMessage::Confirm(uuid)));
+
+    let reason = bob.expect("the trade closing", |msg| match msg {
+        DndMessage::TradeMessage(TradeMessage::Closed(_, reason)) => Some(reason),
+        _ => None,
+    });
+    assert_eq!(reason, "Trade complete");
+
+    let count = |player: &str, item_id: i64| {
+        let query = Query::table("inventory")
+            .eq("player", player)
+            .eq("item_id", item_id);
+        let rows = block_on(server.db.select(query)).unwrap();
+        rows.first().map(|x| x["count"].as_u64().unwrap())
+    };
+    assert_eq!(count("Alice", 1), Some(9));
+    assert_eq!(count("Alice", 2), None);
+    assert_eq!(count("Bob", 1), Some(6));
+    assert_eq!(count("Bob", 2), Some(1));
+}
+
+#[test]
+fn cooldown_uses_come_from_the_sheet() {
+    let server = TestServer::start();
+    let ability = json!({
+        "name": "Breath Weapon",
+        "description": "",
+        "ability_type": "Action",
+        "resource": "UseToken",
+        "max_count": 1,
+        "recharge": { "Roll": 5 },
+    });
+    let row = json!({ "player": "Alice", "ability_name": "Breath Weapon", "uses": 0 });
+    block_on(server.db.insert("abilities", ability)).unwrap();
+    block_on(server.db.insert("player_abilities", row)).unwrap();
+
+    let alice = server.join("Alice");
+    alice.settle();
+
+    let target = EffectTarget::Character("Alice".to_owned());
+    let cooldown = Cooldown::new(
+        "Breath Weapon".to_owned(),
+        Recharge::Rounds(1),
+        Some(99),
+        target,
+    );
+    alice.send(DndMessage::EffectMessage(EffectMessage::SetCooldown(
+        uuid::Uuid::new_v4(),
+        cooldown,
+    )));
+
+    let saved = alice.expect("the cooldown", |msg| match msg {
+        DndMessage::EffectMessage(EffectMessage::SetCooldown(_, cooldown)) => Some(cooldown),
+        _ => None,
+    });
+    assert_eq!(saved.uses, Some(1));
+    assert_eq!(saved.recharge, Recharge::Roll(5));
+}
+
+#[test]
+fn only_the_gm_can_advance_the_round() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    alice.send(DndMessage::EffectMessage(EffectMessage::AdvanceRound));
+    gm.expect_none("the round advancing", |msg| {
+        matches!(msg, DndMessage::EffectMessage(EffectMessage::AdvanceRound))
+    });
+
+    gm.send(DndMessage::EffectMessage(EffectMessage::AdvanceRound));
+    alice.expect("the round advancing", |msg| match msg {
+        DndMessage::EffectMessage(EffectMessage::AdvanceRound) => Some(()),
+        _ => None,
+    });
+}
+
+#[test]
+fn players_cant_advance_the_round_to_recharge_abilities() {
+    let server = TestServer::start();
+    let ability = json!({
+        "name": "Second Wind",
+        "description": "",
+        "ability_type": "Action",
+        "resource": "UseToken",
+        "max_count": 1,
+        "recharge": { "Rounds": 1 },
+    });
+    let row = json!({ "player": "Alice", "ability_name": "Second Wind", "uses": 0 });
+    block_on(server.db.insert("abilities", ability)).unwrap();
+    block_on(server.db.insert("player_abilities", row)).unwrap();
+
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let target = EffectTarget::Character("Alice".to_owned());
+    let cooldown = Cooldown::new("Second Wind".to_owned(), Recharge::Rounds(1), None, target);
+    alice.send(DndMessage::EffectMessage(EffectMessage::SetCooldown(
+        uuid::Uuid::new_v4(),
+        cooldown,
+    )));
+    alice.expect("the cooldown", |msg| match msg {
+        DndMessage::EffectMessage(EffectMessage::SetCooldown(..)) => Some(()),
+        _ => None,
+    });
+
+    alice.send(DndMessage::EffectMessage(EffectMessage::AdvanceRound));
+    alice.expect_none("the ability recharging", |msg| {
+        matches!(
+            msg,
+            DndMessage::EffectMessage(EffectMessage::RemoveCooldown(_))
+        )
+    });
+
+    let query = Query::table("player_abilities")
+        .eq("player", "Alice")
+        .eq("ability_name", "Second Wind");
+    let rows = block_on(server.db.select(query)).unwrap();
+    assert_eq!(rows[0]["uses"].as_i64(), Some(0));
+
+    gm.send(DndMessage::EffectMessage(EffectMessage::AdvanceRound));
+    alice.expect("the ability recharging", |msg| match msg {
+        DndMessage::EffectMessage(EffectMessage::RemoveCooldown(_)) => Some(()),
+        _ => None,
+    });
+}
+
+#[test]
+fn dropping_to_zero_hp_starts_death_saves() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    alice.send(DndMessage::CreateCharacter(Character {
+        name: "Alice".to_owned(),
+        curr_hp: 5,
+        max_hp: 10,
+        ..Default::default()
+    }));
+    alice.settle();
+
+    alice.send(DndMessage::AdjustHp("Alice".to_owned(), -8));
+    let life = alice.expect("the announcement", |msg| match msg {
+        DndMessage::Log(_, LogMessage::LifeChanged(name, life), _) if name == "Alice" => Some(life),
+        _ => None,
+    });
+    let dying = LifeState::Dying {
+        successes: 0,
+        failures: 0,
+    };
+    assert_eq!(life, dying);
+
+    alice.send(DndMessage::RollDeathSave("Alice".to_owned()));
+    let (roll, life) = alice.expect("the death save", |msg| match msg {
+        DndMessage::Log(_, LogMessage::DeathSave(_, roll, life), _) => Some((roll, life)),
+        _ => None,
+    });
+    assert!((1..=20).contains(&roll));
+    assert_ne!(life, dying);
+
+    alice.send(DndMessage::RetrieveCharacterData(alice.user()));
+    let saved = alice.expect("the saved sheet", |msg| match msg {
+        DndMessage::CharacterData(character) => Some(character),
+        _ => None,
+    });
+    assert_eq!(saved.life, life);
+}
+
+#[test]
+fn saving_from_the_gm_is_confirmed() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    gm.send(DndMessage::SnapshotMessage(SnapshotMessage::Take(
+        "Manual save".to_owned(),
+    )));
+    let saved = gm.expect("the confirmation", |msg| match msg {
+        DndMessage::SnapshotMessage(SnapshotMessage::Saved(info)) => Some(info),
+        _ => None,
+    });
+    assert_eq!(saved.tag, "Manual save");
+    assert!(!saved.automatic);
+}
+
+#[test]
+fn snapshot_usage_comes_from_the_saved_sizes() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    gm.settle();
+
+    gm.send(DndMessage::SnapshotMessage(SnapshotMessage::Take(
+        "Manual save".to_owned(),
+    )));
+    gm.settle();
+
+    let rows = block_on(server.db.select(Query::table("snapshots"))).unwrap();
+    let size = rows[0]["size"].as_u64().unwrap();
+    assert_eq!(size, rows[0]["data"].to_string().len() as u64);
+
+    gm.send(DndMessage::SnapshotMessage(SnapshotMessage::RequestUsage));
+    let usage = gm.expect("the usage", |msg| match msg {
+        DndMessage::SnapshotMessage(SnapshotMessage::Usage(usage)) => Some(usage),
+        _ => None,
+    });
+    assert_eq!(usage.tagged, 1);
+    assert_eq!(usage.bytes, size);
+}
+
+#[test]
+fn saving_now_overwrites_one_save_that_brings_the_board_back() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let uuid = uuid::Uuid::new_v4();
+    gm.send(DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(
+        uuid,
+        piece("Goblin"),
+    )));
+    let mut saves = Vec::new();
+    for _ in 0..2 {
+        gm.send(DndMessage::SnapshotMessage(SnapshotMessage::SaveNow));
+        saves.push(gm.expect("the confirmation", |msg| match msg {
+            DndMessage::SnapshotMessage(SnapshotMessage::Saved(info)) => Some(info),
+            _ => None,
+        }));
+    }
+    let rows = block_on(server.db.select(Query::table("snapshots"))).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["tag"], MANUAL_SAVE_TAG);
+
+    gm.send(DndMessage::BoardMessage(BoardMessage::DeletePlayerPiece(
+        uuid,
+    )));
+    gm.settle();
+    alice.settle();
+
+    gm.send(DndMessage::SnapshotMessage(SnapshotMessage::Restore(
+        saves[1].id,
+    )));
+    alice.expect("the saved piece", |msg| match msg {
+        DndMessage::BoardMessage(BoardMessage::AddPlayerPiece(id, _)) if id == uuid => Some(()),
+        _ => None,
+    });
+}
+
+fn d20(value: u32, visibility: RollVisibility) -> DieRoll {
+    DieRoll {
+        die: 20,
+        count: 1,
+        value,
+        modifier: 0,
+        character: None,
+        reason: None,
+        visibility,
+        kind: DieKind::Standard,
+        rolls: vec![value],
+        outcome: None,
+    }
+}
+
+#[test]
+fn public_rolls_are_summed_up_when_the_session_ends() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let user = alice.user();
+    let roll = d20(20, RollVisibility::Public);
+    alice.send(DndMessage::Log(user.clone(), LogMessage::Roll(roll), None));
+    let secret = d20(1, RollVisibility::GmOnly);
+    alice.send(DndMessage::Log(user, LogMessage::Roll(secret), None));
+    gm.settle();
+
+    gm.send(DndMessage::SessionClockMessage(SessionClockMessage::Reset));
+    let summary = alice.expect("the session summary", |msg| match msg {
+        DndMessage::Log(_, LogMessage::SessionSummary(stats), _) => Some(stats),
+        _ => None,
+    });
+    let stats = &summary["Alice"];
+    assert_eq!(stats.rolls, 1);
+    assert_eq!(stats.nat20s(), 1);
+    assert_eq!(stats.nat1s(), 0);
+
+    let bob = server.join("Bob");
+    let (session, campaign) = bob.expect("the roll stats", |msg| match msg {
+        DndMessage::RollStats(session, campaign) => Some((session, campaign)),
+        _ => None,
+    });
+    assert!(session.is_empty());
+    assert_eq!(&campaign["Alice"], stats);
+}
+
+#[test]
+fn session_rolls_survive_a_restart() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    let user = alice.user();
+    let roll = d20(20, RollVisibility::Public);
+    alice.send(DndMessage::Log(user, LogMessage::Roll(roll), None));
+    alice.settle();
+    drop(alice);
+
+    let server = server.restart();
+    let gm = server.join(GM);
+    let session = gm.expect("the roll stats", |msg| match msg {
+        DndMessage::RollStats(session, _) => Some(session),
+        _ => None,
+    });
+    assert_eq!(session["Alice"].nat20s(), 1);
+
+    gm.send(DndMessage::SessionClockMessage(SessionClockMessage::Reset));
+    gm.expect("the session summary", |msg| match msg {
+        DndMessage::Log(_, LogMessage::SessionSummary(stats), _) => Some(stats),
+        _ => None,
+    });
+
+    let server = server.restart();
+    let bob = server.join("Bob");
+    let (session, campaign) = bob.expect("the roll stats", |msg| match msg {
+        DndMessage::RollStats(session, campaign) => Some((session, campaign)),
+        _ => None,
+    });
+    assert!(session.is_empty());
+    assert_eq!(campaign["Alice"].nat20s(), 1);
+}
+
+#[test]
+fn late_joiners_hear_the_music_part_way_in() {
+    let server = TestServer::start();
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    gm.settle();
+    alice.settle();
+
+    let soundboard = Soundboard {
+        tracks: vec![SoundTrack {
+            name: "Tavern".to_owned(),
+            source: "https://example.com/tavern.wav".to_owned(),
+        }],
+        playlists: vec![Playlist {
+            name: "Town".to_owned(),
+            tracks: vec!["Tavern".to_owned()],
+        }],
+    };
+    gm.send(DndMessage::SoundMessage(SoundMessage::SetSoundboard(
+        soundboard.clone(),
+    )));
+    let playing = NowPlaying {
+        name: "Town".to_owned(),
+        tracks: vec!["Tavern".to_owned()],
+        fade: 2.0,
+        elapsed: 0.0,
+    };
+    gm.send(DndMessage::SoundMessage(SoundMessage::Play(playing)));
+    alice.expect("the music starting", |msg| match msg {
+        DndMessage::SoundMessage(SoundMessage::Play(x)) => Some(x),
+        _ => None,
+    });
+
+    // Players can't take over the music
+    alice.send(DndMessage::SoundMessage(SoundMessage::Stop(0.0)));
+    gm.expect_none("the music stopping", |msg| {
+        matches!(msg, DndMessage::SoundMessage(SoundMessage::Stop(_)))
+    });
+
+    std::thread::sleep(Duration::from_millis(200));
+    let bob = server.join("Bob");
+    let saved = bob.expect("the soundboard", |msg| match msg {
+        DndMessage::SoundMessage(SoundMessage::SetSoundboard(x)) => Some(x),
+        _ => None,
+    });
+    assert_eq!(saved, soundboard);
+    let playing = bob.expect("the music already playing", |msg| match msg {
+        DndMessage::SoundMessage(SoundMessage::Play(x)) => Some(x),
+        _ => None,
+    });
+    assert_eq!(playing.name, "Town");
+    assert!(playing.elapsed >= 0.2);
+}
+
+#[test]
+fn pings_are_answered() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    alice.settle();
+
+    alice.send(DndMessage::Ping(42));
+    let answered = alice.expect("the pong", |msg| match msg {
+        DndMessage::Pong(sent) => Some(sent),
+        _ => None,
+    });
+    assert_eq!(answered, 42);
+}
+
+#[test]
+fn silent_connections_are_dropped() {
+    let server = TestServer::with_connection_timeout(Duration::from_secs(1));
+    let gm = server.join(GM);
+    let alice = server.join("Alice");
+    alice.settle();
+
+    // Alice stops pinging, like a connection that died behind a NAT
+    let left = gm.expect_while_pinging("Alice leaving", |msg| match msg {
+        DndMessage::Log(_, LogMessage::Disconnected(name), _) => Some(name),
+        _ => None,
+    });
+    assert_eq!(left, "Alice");
+    gm.expect_while_pinging("Alice leaving the user list", |msg| match msg {
+        DndMessage::UserNotificationRemoved(name) => (name == "Alice").then_some(()),
+        _ => None,
+    });
+}
+
+#[test]
+fn only_messages_that_were_said_can_be_pinned() {
+    let server = TestServer::start();
+    let alice = server.join("Alice");
+    let bob = server.join("Bob");
+    alice.settle();
+    bob.settle();
+
+    let said = LogMessage::Chat("Hello".to_owned());
+    alice.send(DndMessage::Log(alice.user(), said.clone(), None));
+    bob.settle();
+
+    let pin = |message| PinnedMessage {
+        user: alice.user(),
+        message,
+        time: Utc::now(),
+        pinned_by: "Bob".to_owned(),
+    };
+    let made_up = LogMessage::Chat("I owe Bob 100 gold".to_owned());
+    bob.send(DndMessage::PinMessage(PinMessage::Pin(
+        uuid::Uuid::new_v4(),
+        pin(made_up),
+    )));
+    bob.expect("the made up pin refused", |msg| match msg {
+        DndMessage::Error { .. } => Some(()),
+        _ => None,
+    });
+
+    bob.send(DndMessage::PinMessage(PinMessage::Pin(
+        uuid::Uuid::new_v4(),
+        pin(said),
+    )));
+    alice.expect("the real pin", |msg| match msg {
+        DndMessage::PinMessage(PinMessage::Pin(_, pin)) => Some(pin.pinned_by),
+        _ => None,
+    });
+}
+
+#[test]
+fn hosting_makes_the_host_gm_and_keeps_the_campaign_in_its_folder() {
+    let dir = std::env::temp_dir().join(format!("dnd-hosted-{}", uuid::Uuid::new_v4()));
+    let local = |server: &EmbeddedServer| SocketAddr::from(([127, 0, 0, 1], server.addr().port()));
+
+    let server = EmbeddedServer::start(dir.clone(), 0, "Alice").unwrap();
+    let alice = TestClient::connect(local(&server), "Alice");
+    let gm = alice.expect("the GM", |msg| match msg {
+        DndMessage::GameMaster(name) => Some(name),
+        _ => None,
+    });
+    assert_eq!(gm, "Alice");
+    alice.settle();
+
+    alice.send(DndMessage::SetRuleset(Ruleset {
+        attunement_limit: 5,
+        ..Default::default()
+    }));
+    alice.settle();
+    drop(alice);
+    drop(server);
+
+    let server = EmbeddedServer::start(dir.clone(), 0, "Alice").unwrap();
+    let bob = TestClient::connect(local(&server), "Bob");
+    let ruleset = bob.expect("the saved ruleset", |msg| match msg {
+        DndMessage::SetRuleset(ruleset) => Some(ruleset),
+        _ => None,
+    });
+    assert_eq!(ruleset.attunement_limit, 5);
+
+    drop(bob);
+    drop(server);
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,214 @@
+//! Runs the real server on a spare port and talks to it over the network the
+//! same way the client does, so message routing can be checked without
+//! starting a game
+
+use std::{
+    cell::RefCell,
+    io,
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use common::{message::DndMessage, User};
+use tungstenite::{Message, WebSocket};
+
+use crate::{
+    storage::{FileStorage, Storage},
+    DndServer, EmbeddedServer,
+};
+
+mod messaging;
+mod storage;
+
+/// How long to wait for a message before failing the test
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a message has to not show up before it's assumed it never will
+const QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+pub const GM: &str = "GM";
+
+/// A server on its own thread, stopped when dropped
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub db: Arc<dyn Storage>,
+    _server: EmbeddedServer,
+}
+
+impl TestServer {
+    /// An empty campaign with [`GM`] as the GM
+    pub fn start() -> Self {
+        Self::with_storage(Arc::new(FileStorage::in_memory()))
+    }
+
+    /// Picks up wherever the campaign in `db` left off, like a restart
+    pub fn with_storage(db: Arc<dyn Storage>) -> Self {
+        Self::launch(db, |_| {})
+    }
+
+    /// An empty campaign that gives up on quiet connections after `timeout`
+    pub fn with_connection_timeout(timeout: Duration) -> Self {
+        Self::launch(Arc::new(FileStorage::in_memory()), |server| {
+            server.connection_timeout = timeout
+        })
+    }
+
+    fn launch(db: Arc<dyn Storage>, configure: impl FnOnce(&mut DndServer)) -> Self {
+        // Shows the server's logs for failed tests, RUST_LOG picks how much
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let server = EmbeddedServer::launch(addr, db.clone(), |server| {
+            server.gm = Some(GM.to_owned());
+            configure(server);
+        })
+        .unwrap();
+
+        Self {
+            addr: server.addr(),
+            db,
+            _server: server,
+        }
+    }
+
+    /// Stops the server and starts a new one on the same campaign
+    pub fn restart(self) -> Self {
+        let db = self.db.clone();
+        drop(self);
+        Self::with_storage(db)
+    }
+
+    /// Someone connecting and logging in as `name`
+    pub fn join(&self, name: &str) -> TestClient {
+        TestClient::connect(self.addr, name)
+    }
+}
+
+/// A player without the UI. Connects and registers the way the client's
+/// `DndListener` does, everything the server sends is read back with
+/// [`Self::expect`]
+///
+/// This talks WebSocket directly instead of through message-io, whose
+/// adapter can leave messages that arrived together unread until something
+/// else comes in. The server is what's under test, not the transport
+pub struct TestClient {
+    pub name: String,
+    socket: RefCell<WebSocket<TcpStream>>,
+}
+
+impl TestClient {
+    pub fn connect(addr: SocketAddr, name: &str) -> Self {
+        let stream = TcpStream::connect(addr).unwrap();
+        let (socket, _) = tungstenite::client(format!("ws://{addr}"), stream).unwrap();
+
+        let client = Self {
+            name: name.to_owned(),
+            socket: RefCell::new(socket),
+        };
+        client.send(DndMessage::RegisterUser(name.to_owned()));
+        client.send(DndMessage::RetrieveCharacterData(client.user()));
+        client
+    }
+
+    pub fn user(&self) -> User {
+        User {
+            name: self.name.clone(),
+        }
+    }
+
+    pub fn send(&self, message: DndMessage) {
+        let data = bincode::serialize(&message).unwrap();
+        self.socket
+            .borrow_mut()
+            .send(Message::binary(data))
+            .unwrap();
+    }
+
+    /// The next message from the server, if one comes before `deadline`
+    fn recv(&self, deadline: Instant) -> Option<DndMessage> {
+        let mut socket = self.socket.borrow_mut();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+            socket.get_ref().set_read_timeout(Some(remaining)).unwrap();
+
+            match socket.read() {
+                Ok(Message::Binary(data)) => {
+                    let message = bincode::deserialize(&data);
+                    return Some(message.expect("Server sent a malformed message"));
+                }
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => panic!("'{}' lost the connection: {e}", self.name),
+            }
+        }
+    }
+
+    /// Skips ahead to the first message `check` accepts, failing the test if
+    /// it doesn't arrive in time
+    pub fn expect<T>(&self, what: &str, mut check: impl FnMut(DndMessage) -> Option<T>) -> T {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut skipped = Vec::new();
+        while let Some(message) = self.recv(deadline) {
+            let summary = format!("{message:?}");
+            match check(message) {
+                Some(found) => return found,
+                None => skipped.push(summary),
+            }
+        }
+        panic!(
+            "'{}' never received {}, only got:\n{}",
+            self.name,
+            what,
+            skipped.join("\n")
+        )
+    }
+
+    /// Fails the test if a message `check` accepts arrives soon
+    pub fn expect_none(&self, what: &str, check: impl Fn(&DndMessage) -> bool) {
+        let deadline = Instant::now() + QUIET_PERIOD;
+        while let Some(message) = self.recv(deadline) {
+            assert!(!check(&message), "'{}' received {}", self.name, what);
+        }
+    }
+
+    /// Pings until a message `check` accepts arrives, the way a connected
+    /// client keeps itself from timing out
+    pub fn expect_while_pinging<T>(
+        &self,
+        what: &str,
+        mut check: impl FnMut(DndMessage) -> Option<T>,
+    ) -> T {
+        let deadline = Instant::now() + TIMEOUT;
+        while Instant::now() < deadline {
+            self.send(DndMessage::Ping(0));
+            let until = Instant::now() + QUIET_PERIOD;
+            while let Some(message) = self.recv(until) {
+                if let Some(found) = check(message) {
+                    return found;
+                }
+            }
+        }
+        panic!("'{}' never received {}", self.name, what)
+    }
+
+    /// Waits for the server to finish sending everything from joining, so
+    /// later expectations only see what happens afterwards
+    pub fn settle(&self) {
+        while self.recv(Instant::now() + QUIET_PERIOD).is_some() {}
+    }
+}
+
+impl Drop for TestClient {
+    fn drop(&mut self) {
+        // The server may already be gone
+        let _ = self.socket.get_mut().close(None);
+    }
+}
@@ -0,0 +1,176 @@
+use futures::executor::block_on;
+use serde_json::{json, Value};
+
+use crate::{
+    admin,
+    storage::{FileStorage, Query, Storage, Write},
+};
+
+fn item(id: i64, name: &str) -> Value {
+    json!({ "id": id, "name": name, "description": "" })
+}
+
+#[test]
+fn only_the_selected_columns_come_back() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+    block_on(db.insert(
+        "inventory",
+        json!({ "player": "Alice", "item_id": 1, "count": 2, "slot": 0 }),
+    ))
+    .unwrap();
+
+    let rows = block_on(db.select(Query::table("items").select("id,name"))).unwrap();
+    assert_eq!(rows, [json!({ "id": 1, "name": "Rope" })]);
+
+    let query = Query::table("inventory").select("count,items(*)");
+    let rows = block_on(db.select(query)).unwrap();
+    assert_eq!(rows, [json!({ "count": 2, "items": item(1, "Rope") })]);
+
+    let rows = block_on(db.select(Query::table("inventory"))).unwrap();
+    assert_eq!(rows[0]["slot"], 0);
+}
+
+#[test]
+fn inserting_a_taken_key_is_refused() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+
+    assert!(block_on(db.insert("items", item(1, "Torch"))).is_err());
+    assert!(block_on(db.insert("items", item(2, "Rope"))).is_err());
+
+    let inventory = json!({ "player": "Alice", "item_id": 1, "count": 1 });
+    block_on(db.insert("inventory", inventory.clone())).unwrap();
+    assert!(block_on(db.insert("inventory", inventory)).is_err());
+
+    let rows = block_on(db.select(Query::table("items"))).unwrap();
+    assert_eq!(rows, [item(1, "Rope")]);
+}
+
+#[test]
+fn a_clash_part_way_through_keeps_none_of_the_rows() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+
+    let rows = json!([item(2, "Torch"), item(3, "Rope")]);
+    assert!(block_on(db.insert("items", rows)).is_err());
+
+    let rows = block_on(db.select(Query::table("items"))).unwrap();
+    assert_eq!(rows, [item(1, "Rope")]);
+}
+
+#[test]
+fn upserts_merge_on_the_conflict_key() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+
+    let saved = block_on(db.upsert(
+        "items",
+        json!({ "name": "Rope", "description": "50ft" }),
+        "name",
+    ))
+    .unwrap();
+    assert_eq!(
+        saved,
+        [json!({ "id": 1, "name": "Rope", "description": "50ft" })]
+    );
+
+    let saved = block_on(db.upsert("items", json!({ "name": "Torch" }), "name")).unwrap();
+    assert_eq!(saved[0]["id"], 2);
+
+    // Merging on the name can't give the row an id another row has
+    let taken = json!({ "id": 1, "name": "Torch" });
+    assert!(block_on(db.upsert("items", taken, "name")).is_err());
+}
+
+#[test]
+fn upserts_need_a_unique_key() {
+    let db = FileStorage::in_memory();
+    let row = json!({ "player": "Alice", "item_id": 1, "count": 1 });
+    assert!(block_on(db.upsert("inventory", row, "player")).is_err());
+}
+
+#[test]
+fn updates_cant_give_two_rows_the_same_key() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", json!([item(1, "Rope"), item(2, "Torch")]))).unwrap();
+
+    let query = Query::table("items").eq("id", 2);
+    assert!(block_on(db.update(query, json!({ "name": "Rope" }))).is_err());
+
+    let rows = block_on(db.select(Query::table("items").eq("id", 2))).unwrap();
+    assert_eq!(rows[0]["name"], "Torch");
+}
+
+#[test]
+fn a_failed_transaction_changes_nothing() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+
+    let writes = vec![
+        Write::delete(Query::table("items").eq("id", 1)),
+        Write::insert("items", item(2, "Torch")),
+        Write::insert("items", item(3, "Torch")),
+    ];
+    assert!(block_on(db.transaction(writes)).is_err());
+
+    let rows = block_on(db.select(Query::table("items"))).unwrap();
+    assert_eq!(rows, [item(1, "Rope")]);
+}
+
+#[test]
+fn tables_are_read_back_from_the_folder() {
+    let dir = std::env::temp_dir().join(format!("dnd-storage-{}", uuid::Uuid::new_v4()));
+
+    let db = FileStorage::new(dir.clone());
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+    block_on(db.insert("feedback", json!({ "username": "Alice", "text": "Hi" }))).unwrap();
+    drop(db);
+
+    let db = FileStorage::new(dir.clone());
+    let rows = block_on(db.select(Query::table("items"))).unwrap();
+    assert_eq!(rows, [item(1, "Rope")]);
+    let rows = block_on(db.select(Query::table("feedback").select("id"))).unwrap();
+    assert_eq!(rows, [json!({ "id": 1 })]);
+
+    drop(db);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn a_failed_import_keeps_the_old_campaign() {
+    let db = FileStorage::in_memory();
+    block_on(db.insert("items", item(1, "Rope"))).unwrap();
+
+    let file = std::env::temp_dir().join(format!("dnd-campaign-{}.json", uuid::Uuid::new_v4()));
+    let campaign = json!({
+        "version": 1,
+        "exported_at": "2024-01-01T00:00:00Z",
+        "tables": { "items": [item(2, "Torch"), item(3, "Torch")] },
+    });
+    std::fs::write(&file, campaign.to_string()).unwrap();
+
+    assert!(admin::import_campaign(&db, &file, true).is_err());
+    let rows = block_on(db.select(Query::table("items"))).unwrap();
+    assert_eq!(rows, [item(1, "Rope")]);
+
+    let campaign = json!({
+        "version": 1,
+        "exported_at": "2024-01-01T00:00:00Z",
+        "tables": { "items": [item(5, "Torch")] },
+    });
+    std::fs::write(&file, campaign.to_string()).unwrap();
+
+    admin::import_campaign(&db, &file, true).unwrap();
+    block_on(db.insert("items", json!({ "name": "Lantern", "description": "" }))).unwrap();
+    let rows = block_on(db.select(Query::table("items").select("id,name").order("id"))).unwrap();
+    assert_eq!(
+        rows,
+        [
+            json!({ "id": 5, "name": "Torch" }),
+            json!({ "id": 6, "name": "Lantern" })
+        ]
+    );
+
+    let _ = std::fs::remove_file(file);
+}
@@ -0,0 +1,442 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    error::Error,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use log::{error, info, warn};
+use message_io::{network::Endpoint, node::NodeHandler};
+use tokio::sync::{mpsc, oneshot};
+
+use common::{message::DndMessage, Ability, Character, CharacterChange, EquipSlot, Item, User};
+use serde_json::json;
+
+use crate::{
+    db_types::*,
+    storage::{parse_rows, Query, Storage},
+    ServerSignal,
+};
+
+pub type DbResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Handles character sheet requests. These only touch the DB, so they can run
+/// alongside the listener instead of holding up board updates
+#[derive(Clone)]
+pub struct CharacterWorker {
+    db: Arc<dyn Storage>,
+    handler: NodeHandler<ServerSignal>,
+}
+
+impl CharacterWorker {
+    pub fn new(db: Arc<dyn Storage>, handler: NodeHandler<ServerSignal>) -> Self {
+        Self { db, handler }
+    }
+
+    async fn handle(&self, endpoint: Endpoint, message: DndMessage) {
+        match message {
+            DndMessage::RetrieveCharacterData(user) => {
+                self.send_character_data(endpoint, &user).await
+            }
+            DndMessage::RetrievePartyMember(name) => self.send_party_member(endpoint, name).await,
+            DndMessage::UpdateItemCount(user, item_id, new_count) => {
+                let result = self.update_item_count(user, item_id, new_count).await;
+                self.report_error(endpoint, "Saving item count", result);
+            }
+            DndMessage::UpdateAbilityCount(user, ability_name, count) => {
+                let result = self.update_ability_count(user, ability_name, count).await;
+                self.report_error(endpoint, "Saving ability uses", result);
+            }
+            DndMessage::UpdateCharacter(user, version, change) => {
+                let context = format!("Saving {}", change.field_name().to_lowercase());
+                let result = self.update_character(endpoint, user, version, change).await;
+                self.report_error(endpoint, &context, result);
+            }
+            DndMessage::UpdatePowerSlotCount(user, count) => {
+                let result = self.update_powerslot_count(user, count.into()).await;
+                self.report_error(endpoint, "Saving power slots", result);
+            }
+            DndMessage::UpdateItemSlot(user, item_id, slot) => {
+                let result = self.update_item_slot(user, item_id, slot).await;
+                self.report_error(endpoint, "Saving equipment", result);
+            }
+            _ => {
+                warn!("Character worker can't handle {message:?}");
+            }
+        }
+    }
+
+    fn send(&self, endpoint: Endpoint, message: &DndMessage) {
+        let output_data = bincode::serialize(message).unwrap();
+        self.handler.network().send(endpoint, &output_data);
+    }
+
+    pub fn send_error(&self, endpoint: Endpoint, request_context: &str, message: impl ToString) {
+        self.send(
+            endpoint,
+            &DndMessage::Error {
+                request_context: request_context.to_owned(),
+                message: message.to_string(),
+            },
+        );
+    }
+
+    /// Logs a failed request and lets the client that made it know
+    pub fn report_error(
+        &self,
+        endpoint: Endpoint,
+        request_context: &str,
+        result: Result<(), String>,
+    ) {
+        if let Err(e) = result {
+            error!("{} failed: {}", request_context, e);
+            self.send_error(endpoint, request_context, e);
+        }
+    }
+
+    async fn send_party_member(&self, endpoint: Endpoint, name: String) {
+        let user = User { name };
+
+        let data = async {
+            let character = self.get_character_stats(&user).await?;
+            let items = self.get_item_list(&user).await?;
+            let abilities = self.get_ability_list(&user).await?;
+            DbResult::Ok(DndMessage::PartyMemberData(character, items, abilities))
+        };
+
+        match data.await {
+            Ok(msg) => self.send(endpoint, &msg),
+            Err(e) => {
+                error!("Failed to get party member {}: {e:?}", user.name);
+                self.send_error(endpoint, &format!("Loading {}'s sheet", user.name), e);
+            }
+        }
+    }
+
+    /// Items, abilities and stats for the user's own sheet
+    async fn send_character_data(&self, endpoint: Endpoint, user: &User) {
+        match self.get_item_list(user).await {
+            Ok(list) => self.send(endpoint, &DndMessage::ItemList(list)),
+            Err(e) => {
+                error!("Failed to get item list for {}: {e:?}", user.name);
+                self.send_error(endpoint, "Loading items", e);
+            }
+        }
+
+        match self.get_ability_list(user).await {
+            Ok(list) => self.send(endpoint, &DndMessage::AbilityList(list)),
+            Err(e) => {
+                error!("Failed to get ability list for {}: {e:?}", user.name);
+                self.send_error(endpoint, "Loading abilities", e);
+            }
+        }
+
+        match self.get_character_stats(user).await {
+            Ok(stats) => self.send(endpoint, &DndMessage::CharacterData(stats)),
+            Err(e) => {
+                error!("Failed to get character stats for {}: {e:?}", user.name);
+                self.send_error(endpoint, "Loading character", e);
+            }
+        }
+    }
+
+    async fn get_ability_list(&self, user: &User) -> DbResult<Vec<Ability>> {
+        info!("Retrieving ability list for {}", user.name);
+        let query = Query::table("player_abilities")
+            .select("abilities(*),uses")
+            .eq("player", &user.name);
+        let abilities: Vec<DBAbilityResponse> = parse_rows(self.db.select(query).await?)?;
+
+        Ok(abilities.into_iter().map(|x| x.into()).collect())
+    }
+
+    pub async fn get_item_list(&self, user: &User) -> DbResult<Vec<Item>> {
+        info!("Retrieving item list for {}", user.name);
+        let query = Query::table("inventory")
+            .select("count,slot,attuned,items(*)")
+            .eq("player", &user.name);
+        let items: Vec<DBItemResponse> = parse_rows(self.db.select(query).await?)?;
+
+        Ok(items.into_iter().map(|x| x.into()).collect())
+    }
+
+    pub async fn get_character_stats(&self, user: &User) -> DbResult<Character> {
+        let query = Query::table("character").eq("name", &user.name);
+        let characters: Vec<Character> = parse_rows(self.db.select(query).await?)?;
+
+        info!("Retrieved '{}' character data", user.name);
+
+        let character = characters
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("There is no character called '{}'", user.name))?;
+        Ok(character)
+    }
+
+    async fn update_item_count(
+        &self,
+        user: User,
+        item_id: i64,
+        new_count: u32,
+    ) -> Result<(), String> {
+        let query = Query::table("inventory")
+            .eq("player", &user.name)
+            .eq("item_id", item_id);
+
+        if new_count > 0 {
+            self.db.update(query, json!({ "count": new_count })).await?;
+            info!("{}'s item count updated to {}", user.name, new_count);
+        } else {
+            self.db.delete(query).await?;
+            info!("{}'s item count reached 0, deleting from DB", user.name);
+        }
+
+        Ok(())
+    }
+
+    async fn update_item_slot(
+        &self,
+        user: User,
+        item_id: i64,
+        slot: Option<EquipSlot>,
+    ) -> Result<(), String> {
+        let query = Query::table("inventory")
+            .eq("player", &user.name)
+            .eq("item_id", item_id);
+        self.db.update(query, json!({ "slot": slot })).await?;
+
+        info!("{}'s item {} moved to slot {:?}", user.name, item_id, slot);
+        Ok(())
+    }
+
+    pub async fn update_item_attunement(
+        &self,
+        user: User,
+        item_id: i64,
+        attuned: bool,
+    ) -> Result<(), String> {
+        let query = Query::table("inventory")
+            .eq("player", &user.name)
+            .eq("item_id", item_id);
+        self.db.update(query, json!({ "attuned": attuned })).await?;
+
+        info!("{}'s item {} attuned: {}", user.name, item_id, attuned);
+        Ok(())
+    }
+
+    async fn update_ability_count(
+        &self,
+        user: User,
+        ability_name: String,
+        new_count: i64,
+    ) -> Result<(), String> {
+        let query = Query::table("player_abilities")
+            .eq("player", &user.name)
+            .eq("ability_name", ability_name);
+        self.db.update(query, json!({ "uses": new_count })).await?;
+
+        info!("{}'s ability uses updated to {}", user.name, new_count);
+        Ok(())
+    }
+
+    async fn update_powerslot_count(&self, user: User, new_count: i64) -> Result<(), String> {
+        let query = Query::table("characters").eq("player", &user.name);
+        self.db
+            .update(query, json!({ "power_slots": new_count }))
+            .await?;
+
+        info!("{}'s ability uses updated to {}", user.name, new_count);
+        Ok(())
+    }
+
+    /// Applies `change` only if nobody else has changed the character since
+    /// `version`. Otherwise the client is sent the character as it is now so
+    /// it can decide what to keep
+    async fn update_character(
+        &self,
+        endpoint: Endpoint,
+        user: User,
+        version: u32,
+        change: CharacterChange,
+    ) -> Result<(), String> {
+        let mut character = self
+            .get_character_stats(&user)
+            .await
+            .map_err(|e| e.to_string())?;
+        if character.version != version {
+            info!(
+                "{}'s {} change was made against version {version}, it's now {}",
+                user.name,
+                change.field_name(),
+                character.version
+            );
+            self.send(endpoint, &DndMessage::CharacterConflict(character, change));
+            return Ok(());
+        }
+
+        change.apply(&mut character);
+        character.version += 1;
+
+        if !self.write_character_version(&character, version).await? {
+            // Lost the race between reading and writing, fetch the winner
+            let current = self
+                .get_character_stats(&user)
+                .await
+                .map_err(|e| e.to_string())?;
+            self.send(endpoint, &DndMessage::CharacterConflict(current, change));
+            return Ok(());
+        }
+
+        info!(
+            "{}'s {} updated to {}",
+            user.name,
+            change.field_name(),
+            change
+        );
+        Ok(())
+    }
+
+    /// Saves `character` if the stored copy is still at `expected_version`,
+    /// returning whether anything was written
+    pub async fn write_character_version(
+        &self,
+        character: &Character,
+        expected_version: u32,
+    ) -> Result<bool, String> {
+        let json = serde_json::to_value(character).map_err(|e| e.to_string())?;
+        let query = Query::table("character")
+            .eq("name", &character.name)
+            .eq("version", expected_version);
+
+        let rows = self.db.update(query, json).await?;
+        Ok(!rows.is_empty())
+    }
+}
+
+/// Which character a request reads or writes, requests without one aren't for
+/// the workers
+fn character_of(message: &DndMessage) -> Option<&str> {
+    match message {
+        DndMessage::RetrieveCharacterData(user)
+        | DndMessage::UpdateItemCount(user, ..)
+        | DndMessage::UpdateAbilityCount(user, ..)
+        | DndMessage::UpdateCharacter(user, ..)
+        | DndMessage::UpdatePowerSlotCount(user, ..)
+        | DndMessage::UpdateItemSlot(user, ..)
+        | DndMessage::UpdateItemAttunement(user, ..) => Some(&user.name),
+        DndMessage::RetrievePartyMember(name) => Some(name),
+        _ => None,
+    }
+}
+
+enum Task {
+    Message(Endpoint, DndMessage),
+    /// Lets the listener know everything before it is done, then waits for it
+    /// to finish its own writes
+    Hold {
+        reached: oneshot::Sender<()>,
+        release: oneshot::Receiver<()>,
+    },
+}
+
+/// Keeps some character queues paused while the listener writes to those
+/// characters itself. They carry on once this is dropped
+pub struct QueueHold {
+    _release: Vec<oneshot::Sender<()>>,
+}
+
+/// A fixed set of tasks working through character requests. Requests for one
+/// character always go to the same worker, so they're saved in the order
+/// they were made
+pub struct WorkerPool {
+    pub characters: CharacterWorker,
+    queues: Vec<mpsc::UnboundedSender<Task>>,
+}
+
+impl WorkerPool {
+    /// Must be called from inside the tokio runtime
+    pub fn new(characters: CharacterWorker, size: usize) -> Self {
+        let queues = (0..size)
+            .map(|_| {
+                let (tx, mut rx) = mpsc::unbounded_channel::<Task>();
+                let worker = characters.clone();
+                tokio::spawn(async move {
+                    while let Some(task) = rx.recv().await {
+                        match task {
+                            Task::Message(endpoint, message) => {
+                                worker.handle(endpoint, message).await
+                            }
+                            Task::Hold { reached, release } => {
+                                let _ = reached.send(());
+                                let _ = release.await;
+                            }
+                        }
+                    }
+                });
+                tx
+            })
+            .collect();
+
+        Self { characters, queues }
+    }
+
+    fn queue_for(&self, character: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        character.hash(&mut hasher);
+        hasher.finish() as usize % self.queues.len()
+    }
+
+    pub fn submit(&self, endpoint: Endpoint, message: DndMessage) {
+        let Some(character) = character_of(&message) else {
+            warn!("Character workers can't handle {message:?}");
+            return;
+        };
+
+        let idx = self.queue_for(character);
+        if self.queues[idx]
+            .send(Task::Message(endpoint, message))
+            .is_err()
+        {
+            error!("Character worker {idx} has stopped, dropping message from {endpoint}");
+        }
+    }
+
+    /// Waits until everything already queued for the characters has been
+    /// saved, then pauses their queues until the hold is dropped. The
+    /// listener takes one before writing to a character so nothing a player
+    /// sent earlier lands on top of it
+    pub fn hold<'a>(&self, characters: impl IntoIterator<Item = &'a str>) -> QueueHold {
+        let mut queues: Vec<_> = characters.into_iter().map(|x| self.queue_for(x)).collect();
+        queues.sort_unstable();
+        queues.dedup();
+        self.hold_queues(queues)
+    }
+
+    /// For writes that touch every character, like restoring a snapshot
+    pub fn hold_all(&self) -> QueueHold {
+        self.hold_queues((0..self.queues.len()).collect())
+    }
+
+    fn hold_queues(&self, queues: Vec<usize>) -> QueueHold {
+        let mut reached = Vec::new();
+        let mut release = Vec::new();
+        for idx in queues {
+            let (reached_tx, reached_rx) = oneshot::channel();
+            let (release_tx, release_rx) = oneshot::channel();
+            let task = Task::Hold {
+                reached: reached_tx,
+                release: release_rx,
+            };
+            if self.queues[idx].send(task).is_ok() {
+                reached.push(reached_rx);
+                release.push(release_tx);
+            }
+        }
+
+        for rx in reached {
+            // A worker that stopped has nothing left to save anyway
+            let _ = futures::executor::block_on(rx);
+        }
+        QueueHold { _release: release }
+    }
+}